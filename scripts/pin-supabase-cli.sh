@@ -0,0 +1,27 @@
+#!/bin/bash
+# Print the PINNED_SHA256 table of src-tauri/src/supabase_cli.rs for a
+# Supabase CLI release. Check the hashes against the release page before
+# pasting them in; the app trusts nothing else.
+#
+# Usage: scripts/pin-supabase-cli.sh 2.20.5
+
+set -euo pipefail
+
+VERSION="${1:?usage: $0 <version>}"
+URL="https://github.com/supabase/cli/releases/download/v${VERSION}/supabase_${VERSION}_checksums.txt"
+
+CHECKSUMS="$(curl -fsSL "$URL")"
+
+echo "const PINNED_SHA256: &[(&str, &str)] = &["
+for os in darwin linux windows; do
+    for arch in amd64 arm64; do
+        asset="supabase_${os}_${arch}.tar.gz"
+        hash="$(awk -v f="$asset" '$2 == f || $2 == "*" f { print $1 }' <<< "$CHECKSUMS")"
+        if [[ -z "$hash" ]]; then
+            echo "No checksum for $asset in $URL" >&2
+            exit 1
+        fi
+        printf '    (\n        "%s",\n        "%s",\n    ),\n' "$asset" "$hash"
+    done
+done
+echo "];"
@@ -1,3 +1,13 @@
 fn main() {
-  tauri_build::build()
+    // protoc comes with the build so no system install is needed
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+    }
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/flowstate.proto"], &["proto"])
+        .expect("failed to compile proto/flowstate.proto");
+    tauri_build::build()
 }
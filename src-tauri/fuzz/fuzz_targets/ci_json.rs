@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = app_lib::parsers::parse_github_runs(data);
+    let _ = app_lib::parsers::parse_github_jobs(data);
+    let _ = app_lib::parsers::parse_gitlab_pipelines(data);
+    let _ = app_lib::parsers::parse_gitlab_jobs(data);
+    let _ = app_lib::parsers::parse_ci_log_excerpt(data, 60);
+});
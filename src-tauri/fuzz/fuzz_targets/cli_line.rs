@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(line) = app_lib::parsers::parse_cli_line(data) {
+        assert!(!line.chars().any(char::is_control));
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, body)) = app_lib::parsers::parse_engine_response(data) {
+        let _ = app_lib::parsers::parse_engine_version(body);
+        let _ = app_lib::parsers::parse_container_list(body);
+    }
+});
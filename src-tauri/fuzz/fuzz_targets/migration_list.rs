@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(rows) = app_lib::parsers::parse_migration_list(data) {
+        assert!(rows.iter().all(|r| r.local || r.applied));
+    }
+});
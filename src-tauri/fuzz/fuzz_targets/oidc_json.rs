@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = app_lib::parsers::parse_oidc_discovery(data);
+    let _ = app_lib::parsers::parse_oidc_token_response(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(statements) = app_lib::parsers::split_sql_statements(data) {
+        assert!(statements.iter().all(|s| !s.trim().is_empty()));
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = app_lib::parsers::parse_todoist_export(data);
+    let _ = app_lib::parsers::parse_trello_export(data);
+    let _ = app_lib::parsers::parse_csv(data);
+});
@@ -0,0 +1,418 @@
+//! Unusual weeks in personal focus metrics.
+//!
+//! Daily focus time, idle time and interruptions come from `sessions.db`
+//! (recorded focus sessions, idle periods from `idle.rs` and logged
+//! interruptions, or their daily rollups past the retention window) and are
+//! summed per local week, Monday to Sunday. A week is compared with up to
+//! `BASELINE_WEEKS` weeks before it that saw any activity: a metric is
+//! flagged when it is both `MIN_Z` standard deviations and `MIN_CHANGE` away
+//! from the baseline mean ("40% more interruptions
+//! than your baseline"). Holidays and vacation days (`holidays.rs`) are
+//! marked, and weeks are compared per workday they had, so a week with a
+//! public holiday isn't flagged for less focus time. A week without
+//! workdays is neither flagged nor part of a baseline. The weekly report
+//! asks for this on demand;
+//! everything is computed on this machine and nothing is stored or sent.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone};
+use rusqlite::params;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+const BASELINE_WEEKS: u64 = 8;
+/// Fewer active weeks than this make no baseline
+const MIN_BASELINE_WEEKS: usize = 4;
+const MIN_Z: f64 = 2.0;
+const MIN_CHANGE: f64 = 0.3;
+const WORKDAYS: usize = 5;
+/// Idle periods count up to this long; longer ones are time away (nights,
+/// lunch with the app left open), not idling while working
+pub(crate) const MAX_IDLE_MS: i64 = 2 * 60 * 60 * 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    FocusTime,
+    IdleTime,
+    Interruptions,
+}
+
+impl Metric {
+    const ALL: [Metric; 3] = [Metric::FocusTime, Metric::IdleTime, Metric::Interruptions];
+
+    fn of(self, totals: &DayMetrics) -> f64 {
+        match self {
+            Metric::FocusTime => totals.focus_ms as f64,
+            Metric::IdleTime => totals.idle_ms as f64,
+            Metric::Interruptions => totals.interruptions as f64,
+        }
+    }
+
+    /// "more"/"less" phrase for messages
+    fn describe(self, up: bool) -> &'static str {
+        match (self, up) {
+            (Metric::FocusTime, true) => "more focus time",
+            (Metric::FocusTime, false) => "less focus time",
+            (Metric::IdleTime, true) => "more idle time",
+            (Metric::IdleTime, false) => "less idle time",
+            (Metric::Interruptions, true) => "more interruptions",
+            (Metric::Interruptions, false) => "fewer interruptions",
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayMetrics {
+    /// Local day, "YYYY-MM-DD"
+    pub date: String,
+    pub focus_ms: i64,
+    pub idle_ms: i64,
+    pub interruptions: i64,
+    /// Holiday or vacation day (days of a week only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day_off: Option<crate::holidays::DayOff>,
+}
+
+impl DayMetrics {
+    fn add(&mut self, other: &DayMetrics) {
+        self.focus_ms += other.focus_ms;
+        self.idle_ms += other.idle_ms;
+        self.interruptions += other.interruptions;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.focus_ms == 0 && self.idle_ms == 0 && self.interruptions == 0
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Anomaly {
+    pub metric: Metric,
+    /// This week's total (ms for times, a count for interruptions), scaled
+    /// to a week without days off
+    pub value: f64,
+    /// Mean of the baseline weeks, scaled the same way
+    pub baseline: f64,
+    /// Relative to the baseline, e.g. 0.4 for 40% more
+    pub change: f64,
+    /// None when the baseline weeks were all the same
+    pub z_score: Option<f64>,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyAnomalies {
+    /// Monday and Sunday of the week, "YYYY-MM-DD"
+    pub week_start: String,
+    pub week_end: String,
+    pub days: Vec<DayMetrics>,
+    pub totals: DayMetrics,
+    /// Monday to Friday not taken off
+    pub workdays: usize,
+    /// Active weeks the baseline was built from
+    pub baseline_weeks: usize,
+    /// Empty when there are fewer than `MIN_BASELINE_WEEKS` baseline weeks
+    pub anomalies: Vec<Anomaly>,
+}
+
+/// Local midnight at the start of `day` in ms since the epoch
+fn day_start_ms(day: NaiveDate) -> i64 {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| midnight.and_utc().timestamp_millis())
+}
+
+fn local_day(ms: i64) -> Option<NaiveDate> {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| t.date_naive())
+}
+
+/// Per local day metrics for days in [from, to)
+fn daily(
+    app: &AppHandle,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, DayMetrics>, String> {
+    let (from_ms, to_ms) = (day_start_ms(from), day_start_ms(to));
+    let rows = crate::sessions::with_db(app, |conn| {
+        let mut rows: Vec<(i64, DayMetrics)> = Vec::new();
+        let mut stmt = conn.prepare(
+            "SELECT started_at, duration_ms FROM focus_sessions \
+             WHERE started_at >= ?1 AND started_at < ?2",
+        )?;
+        for row in stmt.query_map(params![from_ms, to_ms], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })? {
+            let (at, focus_ms) = row?;
+            rows.push((
+                at,
+                DayMetrics {
+                    focus_ms,
+                    ..DayMetrics::default()
+                },
+            ));
+        }
+        let mut stmt = conn.prepare(
+            "SELECT started_at, min(ended_at - started_at, ?3) FROM idle_periods \
+             WHERE started_at >= ?1 AND started_at < ?2",
+        )?;
+        for row in stmt.query_map(params![from_ms, to_ms, MAX_IDLE_MS], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })? {
+            let (at, idle_ms) = row?;
+            rows.push((
+                at,
+                DayMetrics {
+                    idle_ms,
+                    ..DayMetrics::default()
+                },
+            ));
+        }
+        let mut stmt = conn.prepare("SELECT at FROM interruptions WHERE at >= ?1 AND at < ?2")?;
+        for at in stmt.query_map(params![from_ms, to_ms], |row| row.get::<_, i64>(0))? {
+            rows.push((
+                at?,
+                DayMetrics {
+                    interruptions: 1,
+                    ..DayMetrics::default()
+                },
+            ));
+        }
+        // Days past the retention window only have their rollups
+        let pruned_before = crate::rollups::pruned_before(conn)?;
+        let mut stmt = conn.prepare(
+            "SELECT day_start, focus_ms, idle_ms, interruptions FROM activity_daily \
+             WHERE day_start >= ?1 AND day_start < min(?2, ?3)",
+        )?;
+        for row in stmt.query_map(params![from_ms, to_ms, pruned_before], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                DayMetrics {
+                    focus_ms: row.get(1)?,
+                    idle_ms: row.get(2)?,
+                    interruptions: row.get(3)?,
+                    ..DayMetrics::default()
+                },
+            ))
+        })? {
+            rows.push(row?);
+        }
+        Ok(rows)
+    })?;
+
+    let mut days: BTreeMap<NaiveDate, DayMetrics> = BTreeMap::new();
+    for (at, metrics) in rows {
+        if let Some(day) = local_day(at) {
+            days.entry(day).or_default().add(&metrics);
+        }
+    }
+    Ok(days)
+}
+
+fn week_totals(days: &BTreeMap<NaiveDate, DayMetrics>, monday: NaiveDate) -> DayMetrics {
+    let mut totals = DayMetrics {
+        date: monday.to_string(),
+        ..DayMetrics::default()
+    };
+    for (_, day) in days.range(monday..monday + Days::new(7)) {
+        totals.add(day);
+    }
+    totals
+}
+
+/// Weekdays of the week starting `monday` that aren't days off
+fn workdays(days_off: &BTreeMap<NaiveDate, crate::holidays::DayOff>, monday: NaiveDate) -> usize {
+    monday
+        .iter_days()
+        .take(WORKDAYS)
+        .filter(|day| !days_off.contains_key(day))
+        .count()
+}
+
+/// `metric` of a week scaled to a full working week
+fn per_week(metric: Metric, totals: &DayMetrics, workdays: usize) -> f64 {
+    metric.of(totals) * WORKDAYS as f64 / workdays as f64
+}
+
+/// Flag `value` against the baseline values of the same metric
+fn compare(metric: Metric, value: f64, baseline: &[f64]) -> Option<Anomaly> {
+    let n = baseline.len() as f64;
+    let mean = baseline.iter().sum::<f64>() / n;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = baseline.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let sd = variance.sqrt();
+    let z_score = (sd > 0.0).then(|| (value - mean) / sd);
+    let change = (value - mean) / mean;
+    if change.abs() < MIN_CHANGE || z_score.is_some_and(|z| z.abs() < MIN_Z) {
+        return None;
+    }
+    Some(Anomaly {
+        metric,
+        value,
+        baseline: mean,
+        change,
+        z_score,
+        message: format!(
+            "{:.0}% {} than your baseline",
+            change.abs() * 100.0,
+            metric.describe(change > 0.0)
+        ),
+    })
+}
+
+/// Daily metrics of a week and what stands out against the weeks before it.
+/// `week_start` is any day of the week ("YYYY-MM-DD"); the default is last
+/// week, the most recent complete one.
+#[tauri::command]
+pub fn get_weekly_anomalies(
+    app: AppHandle,
+    week_start: Option<String>,
+) -> Result<WeeklyAnomalies, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_weekly_anomalies")?;
+    let day = match week_start {
+        Some(day) => NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("'{}' is not a date (YYYY-MM-DD)", day))?,
+        None => crate::clock::now_local().date_naive() - Days::new(7),
+    };
+    let monday = day - Days::new(u64::from(day.weekday().num_days_from_monday()));
+    let next_monday = monday + Days::new(7);
+    let first_baseline = monday - Days::new(7 * BASELINE_WEEKS);
+    let days = daily(&app, first_baseline, next_monday)?;
+    let days_off = crate::holidays::days_off(&app, first_baseline, next_monday - Days::new(1));
+
+    let week: Vec<DayMetrics> = monday
+        .iter_days()
+        .take(7)
+        .map(|day| DayMetrics {
+            date: day.to_string(),
+            day_off: days_off.get(&day).cloned(),
+            ..days.get(&day).cloned().unwrap_or_default()
+        })
+        .collect();
+    let totals = week_totals(&days, monday);
+    let week_workdays = workdays(&days_off, monday);
+    let baseline: Vec<(DayMetrics, usize)> = (1..=BASELINE_WEEKS)
+        .map(|back| {
+            let start = monday - Days::new(7 * back);
+            (week_totals(&days, start), workdays(&days_off, start))
+        })
+        .filter(|(totals, workdays)| !totals.is_empty() && *workdays > 0)
+        .collect();
+
+    let anomalies = if baseline.len() < MIN_BASELINE_WEEKS || week_workdays == 0 {
+        Vec::new()
+    } else {
+        Metric::ALL
+            .into_iter()
+            .filter_map(|metric| {
+                let values: Vec<f64> = baseline
+                    .iter()
+                    .map(|(week, workdays)| per_week(metric, week, *workdays))
+                    .collect();
+                compare(metric, per_week(metric, &totals, week_workdays), &values)
+            })
+            .collect()
+    };
+    Ok(WeeklyAnomalies {
+        week_start: monday.to_string(),
+        week_end: (next_monday - Days::new(1)).to_string(),
+        days: week,
+        totals,
+        workdays: week_workdays,
+        baseline_weeks: baseline.len(),
+        anomalies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_values_far_from_a_steady_baseline() {
+        let baseline = [10.0, 12.0, 11.0, 9.0, 13.0];
+        let up = compare(Metric::Interruptions, 16.0, &baseline).unwrap();
+        assert_eq!(up.baseline, 11.0);
+        assert!(up.z_score.unwrap() > MIN_Z);
+        assert_eq!(up.message, "45% more interruptions than your baseline");
+        let down = compare(Metric::FocusTime, 4.0, &baseline).unwrap();
+        assert_eq!(down.message, "64% less focus time than your baseline");
+        assert!(compare(Metric::FocusTime, 12.0, &baseline).is_none());
+
+        // A noisy baseline needs more than a large relative change
+        assert!(compare(Metric::IdleTime, 20.0, &[2.0, 20.0, 2.0, 20.0]).is_none());
+        // A flat one has no z-score, so the change alone decides
+        let flat = compare(Metric::IdleTime, 13.0, &[10.0; 4]).unwrap();
+        assert_eq!(flat.z_score, None);
+        assert!(compare(Metric::IdleTime, 12.0, &[10.0; 4]).is_none());
+        assert!(compare(Metric::IdleTime, 5.0, &[0.0; 4]).is_none());
+    }
+
+    #[test]
+    fn week_totals_cover_monday_to_sunday() {
+        let monday = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let day = |focus_ms| DayMetrics {
+            focus_ms,
+            interruptions: 1,
+            ..DayMetrics::default()
+        };
+        let days: BTreeMap<NaiveDate, DayMetrics> = [
+            (monday - Days::new(1), day(1)),
+            (monday, day(10)),
+            (monday + Days::new(6), day(100)),
+            (monday + Days::new(7), day(1000)),
+        ]
+        .into_iter()
+        .collect();
+        let totals = week_totals(&days, monday);
+        assert_eq!(totals.date, "2026-03-02");
+        assert_eq!(totals.focus_ms, 110);
+        assert_eq!(totals.interruptions, 2);
+        assert!(week_totals(&days, monday + Days::new(14)).is_empty());
+    }
+
+    #[test]
+    fn weeks_with_days_off_compare_per_workday() {
+        use crate::holidays::{DayOff, DayOffKind};
+
+        let monday = NaiveDate::from_ymd_opt(2026, 4, 6).unwrap();
+        let off = |day: NaiveDate| {
+            (
+                day,
+                DayOff {
+                    date: day.to_string(),
+                    kind: DayOffKind::Holiday,
+                    name: "Easter Monday".to_string(),
+                },
+            )
+        };
+        // A Saturday off doesn't cost a workday
+        let days_off: BTreeMap<NaiveDate, DayOff> = [off(monday), off(monday + Days::new(5))]
+            .into_iter()
+            .collect();
+        assert_eq!(workdays(&days_off, monday), 4);
+        assert_eq!(workdays(&days_off, monday + Days::new(7)), 5);
+
+        let totals = DayMetrics {
+            focus_ms: 400,
+            ..DayMetrics::default()
+        };
+        assert_eq!(per_week(Metric::FocusTime, &totals, 4), 500.0);
+        // Four days of the usual focus time is not an anomaly
+        assert!(compare(Metric::FocusTime, 500.0, &[500.0, 480.0, 520.0, 500.0]).is_none());
+    }
+}
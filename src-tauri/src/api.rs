@@ -0,0 +1,213 @@
+//! Versioned command envelope.
+//!
+//! The frontend can keep calling the plain Tauri commands, or route through
+//! `invoke_api("v1/docker.status", args)`. Routes are resolved per API version,
+//! so when a command changes shape the old version keeps its own handler and
+//! frontends built against it keep working during staged updates.
+//!
+//! v1 returns the original string payloads ("running:<version>", JSON-in-a-string)
+//! and plain string errors, and `docker.start` returns once the launcher ran;
+//! v2 returns the typed structs from `status.rs` as JSON, `FlowStateError`
+//! objects on failure, and `docker.start` waits for the daemon.
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::error::FlowStateError;
+use crate::status::{LegacyMemoryUsage, ServiceState, ServiceStatus};
+
+/// Current command API version spoken by this backend
+pub const API_VERSION: u32 = 2;
+
+/// Oldest API version still accepted by `invoke_api`
+pub const MIN_API_VERSION: u32 = 1;
+
+/// Routes available in API v1 and v2
+const ROUTES: &[&str] = &[
+    "debug.memory",
+    "docker.installed",
+    "docker.start",
+    "docker.status",
+    "services.cleanup",
+    "supabase.config",
+    "supabase.installed",
+    "supabase.migrations",
+    "supabase.start",
+    "supabase.status",
+    "supabase.stop",
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiVersionInfo {
+    pub api_version: u32,
+    pub min_supported_version: u32,
+    pub app_version: String,
+    pub routes: Vec<String>,
+}
+
+/// Error of `invoke_api`: the message alone for v1 routes, as v1 always
+/// reported it, and the typed `FlowStateError` from v2 on
+#[derive(Debug)]
+pub enum ApiError {
+    Legacy(String),
+    Typed(FlowStateError),
+}
+
+impl Serialize for ApiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ApiError::Legacy(message) => serializer.serialize_str(message),
+            ApiError::Typed(e) => e.serialize(serializer),
+        }
+    }
+}
+
+/// Split "v1/docker.status" into (1, "docker.status")
+fn parse_route(route: &str) -> Result<(u32, &str), String> {
+    let (version, name) = route.split_once('/').ok_or_else(|| {
+        format!(
+            "Invalid API route '{}': expected 'v<version>/<name>'",
+            route
+        )
+    })?;
+
+    let version = version
+        .strip_prefix('v')
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| format!("Invalid API version in route '{}'", route))?;
+
+    if !(MIN_API_VERSION..=API_VERSION).contains(&version) {
+        return Err(format!(
+            "API version {} is not supported (supported: {}-{})",
+            version, MIN_API_VERSION, API_VERSION
+        ));
+    }
+
+    Ok((version, name))
+}
+
+/// Report the command API version so the frontend can pick compatible routes
+#[tauri::command]
+pub fn get_api_version(app: tauri::AppHandle) -> ApiVersionInfo {
+    ApiVersionInfo {
+        api_version: API_VERSION,
+        min_supported_version: MIN_API_VERSION,
+        app_version: app.package_info().version.to_string(),
+        routes: (MIN_API_VERSION..=API_VERSION)
+            .flat_map(|v| ROUTES.iter().map(move |r| format!("v{}/{}", v, r)))
+            .collect(),
+    }
+}
+
+/// Dispatch a versioned route to the matching command handler
+#[tauri::command]
+pub async fn invoke_api(
+    app: tauri::AppHandle,
+    route: String,
+    args: Option<Value>,
+) -> Result<Value, ApiError> {
+    let legacy = route.starts_with("v1/");
+    let result = match parse_route(&route) {
+        Ok((1, name)) => invoke_v1(app, name, &args.unwrap_or(Value::Null)).await,
+        Ok((2, name)) => invoke_v2(app, name, &args.unwrap_or(Value::Null)).await,
+        Ok((version, _)) => Err(format!("API version {} is not supported", version).into()),
+        Err(e) => Err(e.into()),
+    };
+    result.map_err(|e| {
+        if legacy {
+            ApiError::Legacy(e.to_string())
+        } else {
+            ApiError::Typed(e)
+        }
+    })
+}
+
+/// v1 string form of a service status: "running:<detail>" or "not_running"
+fn legacy_status(status: ServiceStatus, empty_detail: &str) -> Result<String, String> {
+    if status.state == ServiceState::NotRunning {
+        return Ok(status.state.to_string());
+    }
+
+    let detail = match (status.version, status.config) {
+        (Some(version), _) => version,
+        (None, Some(config)) => serde_json::to_string(&config).map_err(|e| e.to_string())?,
+        (None, None) => empty_detail.to_string(),
+    };
+    Ok(format!("running:{}", detail))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, FlowStateError> {
+    serde_json::to_value(value).map_err(|e| e.to_string().into())
+}
+
+fn stop_supabase_arg(args: &Value) -> bool {
+    args.get("stopSupabaseFlag")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// v1 reported a missing tool as "not_installed" rather than an error
+fn legacy_installed(result: Result<String, FlowStateError>) -> Result<String, FlowStateError> {
+    match result {
+        Err(FlowStateError::DockerNotInstalled | FlowStateError::SupabaseCliMissing(_)) => {
+            Ok("not_installed".to_string())
+        }
+        other => other,
+    }
+}
+
+async fn invoke_v1(
+    app: tauri::AppHandle,
+    name: &str,
+    args: &Value,
+) -> Result<Value, FlowStateError> {
+    let result = match name {
+        "debug.memory" => {
+            let usage = LegacyMemoryUsage::from(crate::get_memory_usage());
+            serde_json::to_string(&usage).map_err(|e| e.to_string())?
+        }
+        "docker.installed" => legacy_installed(crate::check_docker_installed(app).await)?,
+        "docker.start" => {
+            // v1 reports "started" as soon as the launcher ran, without
+            // waiting for the daemon
+            crate::launch_docker_if_down(&app, None).await?;
+            "started".to_string()
+        }
+        "docker.status" => legacy_status(crate::check_docker_status(app).await?, "")?,
+        "services.cleanup" => crate::cleanup_services(app, stop_supabase_arg(args)).await?,
+        "supabase.config" => serde_json::to_string(&crate::get_supabase_config(app).await?)
+            .map_err(|e| e.to_string())?,
+        "supabase.installed" => legacy_installed(crate::check_supabase_installed(app).await)?,
+        "supabase.migrations" => crate::run_supabase_migrations(app).await?,
+        "supabase.start" => crate::start_supabase(app).await?,
+        "supabase.status" => legacy_status(crate::check_supabase_status(app).await?, "{}")?,
+        "supabase.stop" => crate::stop_supabase(app).await?,
+        _ => return Err(format!("Unknown API route 'v1/{}'", name).into()),
+    };
+
+    Ok(Value::String(result))
+}
+
+async fn invoke_v2(
+    app: tauri::AppHandle,
+    name: &str,
+    args: &Value,
+) -> Result<Value, FlowStateError> {
+    match name {
+        "debug.memory" => to_value(crate::get_memory_usage()),
+        "docker.installed" => to_value(crate::check_docker_installed(app).await?),
+        "docker.start" => to_value(crate::start_docker_desktop(app, None, None).await?),
+        "docker.status" => to_value(crate::check_docker_status(app).await?),
+        "services.cleanup" => {
+            to_value(crate::cleanup_services(app, stop_supabase_arg(args)).await?)
+        }
+        "supabase.config" => to_value(crate::get_supabase_config(app).await?),
+        "supabase.installed" => to_value(crate::check_supabase_installed(app).await?),
+        "supabase.migrations" => to_value(crate::run_supabase_migrations(app).await?),
+        "supabase.start" => to_value(crate::start_supabase(app).await?),
+        "supabase.status" => to_value(crate::check_supabase_status(app).await?),
+        "supabase.stop" => to_value(crate::stop_supabase(app).await?),
+        _ => Err(format!("Unknown API route 'v2/{}'", name).into()),
+    }
+}
@@ -0,0 +1,596 @@
+//! App lock for shared machines.
+//!
+//! With a PIN set, the app starts locked and locks again after the configured
+//! inactivity (the frontend reports input with `record_activity`). Lock changes
+//! are published as `app://lock` and included in the state snapshot, but the
+//! lock is enforced here: while locked, only the lock screen, service status
+//! and control, and window plumbing (`OPEN_COMMANDS`) get through `gated`;
+//! every other command gets an `AppLockError` before it runs, until
+//! `unlock_app` gets the right PIN or `unlock_app_with_os_auth` gets past the
+//! OS prompt (Touch ID, Windows Hello or polkit, see `os_auth.rs`). Data
+//! commands also call `ensure_unlocked`, which covers calls from inside the
+//! backend. The PIN is stored as an argon2 hash in `app-lock.json`, and
+//! repeated wrong PINs back off exponentially; the count and the hold are
+//! kept next to the hash, so restarting the app doesn't reset them.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::os_auth::Purpose;
+
+pub(crate) const LOCK_STORE: &str = "app-lock.json";
+const LOCK_KEY: &str = "settings";
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const MIN_PIN_LEN: usize = 4;
+const MAX_PIN_LEN: usize = 64;
+/// Wrong PINs allowed before backing off
+const FREE_ATTEMPTS: u32 = 5;
+const BACKOFF_BASE_MS: u64 = 30 * 1000;
+const BACKOFF_MAX_MS: u64 = 30 * 60 * 1000;
+
+/// Commands that run while the app is locked; none of them return or change
+/// user data
+const OPEN_COMMANDS: &[&str] = &[
+    // The lock screen
+    "get_app_lock_state",
+    "unlock_app",
+    "unlock_app_with_os_auth",
+    "lock_app",
+    "disable_app_lock",
+    "record_activity",
+    // Service status and control (`invoke_api` routes only these)
+    "check_docker_status",
+    "check_docker_installed",
+    "start_docker_desktop",
+    "check_supabase_status",
+    "check_supabase_installed",
+    "start_supabase",
+    "stop_supabase",
+    "cleanup_services",
+    "get_memory_usage",
+    "get_api_version",
+    "invoke_api",
+    "get_stale_instance",
+    "check_supabase_services",
+    "get_supabase_cli_version",
+    "get_supervisor_state",
+    "ensure_stack_ready",
+    "get_init_status",
+    "get_otel_status",
+    "detect_container_runtimes",
+    "subscribe_service_status",
+    // Window plumbing
+    "get_read_only_mode",
+    "get_privacy_mode",
+    "get_org_policy",
+    "get_app_visibility",
+    "get_idle_state",
+    "subscribe_focus_ticks",
+    "set_focus_tick_visibility",
+    "unsubscribe_focus_ticks",
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LockSettings {
+    /// argon2 PHC string; None means the lock is off
+    pin_hash: Option<String>,
+    /// 0 locks only on launch and on `lock_app`
+    idle_minutes: u32,
+    /// Wrong PINs in a row
+    failed_attempts: u32,
+    /// No PIN is checked before this time (ms since the epoch)
+    retry_after_ms: u64,
+}
+
+impl Default for LockSettings {
+    fn default() -> Self {
+        LockSettings {
+            pin_hash: None,
+            idle_minutes: 10,
+            failed_attempts: 0,
+            retry_after_ms: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct LockInner {
+    settings: LockSettings,
+    locked: bool,
+    last_activity_ms: u64,
+}
+
+impl LockInner {
+    /// State at launch: locked when a PIN is set
+    fn load(settings: LockSettings, now: u64) -> Self {
+        LockInner {
+            locked: settings.pin_hash.is_some(),
+            last_activity_ms: now,
+            settings,
+        }
+    }
+
+    /// Clear the backoff; false when there was none (nothing to save)
+    fn record_success(&mut self) -> bool {
+        let had_failures = self.settings.failed_attempts > 0;
+        self.settings.failed_attempts = 0;
+        self.settings.retry_after_ms = 0;
+        had_failures
+    }
+
+    fn record_failure(&mut self, now: u64) {
+        self.settings.failed_attempts += 1;
+        if let Some(backoff) = backoff_ms(self.settings.failed_attempts) {
+            self.settings.retry_after_ms = now + backoff;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AppLock {
+    inner: Mutex<LockInner>,
+    /// Held while a PIN is checked, so attempts can't race past the backoff
+    /// while the hash is computed outside `inner`
+    verifying: Mutex<()>,
+}
+
+impl AppLock {
+    pub fn is_locked(&self) -> bool {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).locked
+    }
+
+    fn state(&self) -> AppLockState {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        AppLockState {
+            enabled: inner.settings.pin_hash.is_some(),
+            locked: inner.locked,
+            idle_minutes: inner.settings.idle_minutes,
+            retry_after_ms: (inner.settings.retry_after_ms > crate::events::now_ms())
+                .then_some(inner.settings.retry_after_ms),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLockState {
+    pub enabled: bool,
+    pub locked: bool,
+    pub idle_minutes: u32,
+    /// Set while wrong PINs have unlocking on hold
+    pub retry_after_ms: Option<u64>,
+}
+
+/// Error returned by data commands while the app is locked.
+/// Serialized as `locked:<command> ...` so the frontend can match the prefix.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLockError {
+    pub command: Cow<'static, str>,
+}
+
+impl fmt::Display for AppLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "locked:{} is unavailable while the app is locked",
+            self.command
+        )
+    }
+}
+
+impl std::error::Error for AppLockError {}
+
+impl From<AppLockError> for String {
+    fn from(e: AppLockError) -> String {
+        e.to_string()
+    }
+}
+
+/// Reject a data command while the app is locked
+pub fn ensure_unlocked(app: &AppHandle, command: &'static str) -> Result<(), AppLockError> {
+    if app.state::<AppLock>().is_locked() {
+        log::info!("Rejected {} (app locked)", command);
+        return Err(AppLockError {
+            command: command.into(),
+        });
+    }
+    Ok(())
+}
+
+/// Wrap the command handler so that, while the app is locked, commands not in
+/// `OPEN_COMMANDS` are rejected before they are dispatched
+pub fn gated<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        if OPEN_COMMANDS.contains(&command)
+            || !invoke.message.webview_ref().state::<AppLock>().is_locked()
+        {
+            return handler(invoke);
+        }
+        log::info!("Rejected {} (app locked)", command);
+        let error = FlowStateError::from(AppLockError {
+            command: command.to_string().into(),
+        });
+        invoke.resolver.reject(error);
+        true
+    }
+}
+
+fn validate_pin(pin: &str) -> Result<(), String> {
+    let len = pin.chars().count();
+    if !(MIN_PIN_LEN..=MAX_PIN_LEN).contains(&len) {
+        return Err(format!(
+            "PIN must be {} to {} characters",
+            MIN_PIN_LEN, MAX_PIN_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Wait after `failed_attempts` wrong PINs in a row; None while attempts are free
+fn backoff_ms(failed_attempts: u32) -> Option<u64> {
+    let doublings = failed_attempts.checked_sub(FREE_ATTEMPTS)?.min(16);
+    Some((BACKOFF_BASE_MS << doublings).min(BACKOFF_MAX_MS))
+}
+
+fn hash_pin(pin: &str) -> Result<String, String> {
+    validate_pin(pin)?;
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pin.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash PIN: {}", e))
+}
+
+fn pin_matches(hash: &str, pin: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(pin.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn save_settings(app: &AppHandle, settings: &LockSettings) -> Result<(), String> {
+    let store = app
+        .store(LOCK_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", LOCK_STORE, e))?;
+    store.set(
+        LOCK_KEY,
+        serde_json::to_value(settings).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", LOCK_STORE, e))
+}
+
+fn set_locked(app: &AppHandle, locked: bool) {
+    let lock = app.state::<AppLock>();
+    {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.locked == locked {
+            return;
+        }
+        inner.locked = locked;
+        inner.last_activity_ms = crate::events::now_ms();
+    }
+    log::info!("App {}", if locked { "locked" } else { "unlocked" });
+    crate::events::publish(app, "app://lock", &lock.state());
+}
+
+/// Check a PIN against the stored hash, counting failures towards the backoff.
+/// The hash is computed without holding the lock state, so lock checks by
+/// other commands don't wait for it.
+fn verify(app: &AppHandle, pin: &str) -> Result<(), String> {
+    let lock = app.state::<AppLock>();
+    let _verifying = lock.verifying.lock().unwrap_or_else(|e| e.into_inner());
+    let hash = {
+        let inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let now = crate::events::now_ms();
+        if inner.settings.retry_after_ms > now {
+            return Err(format!(
+                "Too many wrong PINs; try again in {} s",
+                (inner.settings.retry_after_ms - now).div_ceil(1000)
+            ));
+        }
+        inner.settings.pin_hash.clone()
+    };
+    let Some(hash) = hash else {
+        return Ok(());
+    };
+    let matches = pin_matches(&hash, pin);
+
+    let settings = {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if matches {
+            inner.record_success().then(|| inner.settings.clone())
+        } else {
+            inner.record_failure(crate::events::now_ms());
+            log::warn!(
+                "Wrong app lock PIN ({} in a row)",
+                inner.settings.failed_attempts
+            );
+            Some(inner.settings.clone())
+        }
+    };
+    if let Some(settings) = settings {
+        save_backoff(app, &settings);
+    }
+    if matches {
+        Ok(())
+    } else {
+        Err("Wrong PIN".to_string())
+    }
+}
+
+/// Keep the wrong-PIN count across restarts; a failed save only loses that
+fn save_backoff(app: &AppHandle, settings: &LockSettings) {
+    if let Err(e) = save_settings(app, settings) {
+        log::warn!("Wrong-PIN backoff not saved: {}", e);
+    }
+}
+
+/// Load settings (locking right away when a PIN is set) and start the idle timer
+pub fn init(app: &AppHandle) {
+    let settings = match app.store(LOCK_STORE) {
+        Ok(store) => store
+            .get(LOCK_KEY)
+            .and_then(
+                |value| match serde_json::from_value::<LockSettings>(value) {
+                    Ok(settings) => Some(settings),
+                    Err(e) => {
+                        log::warn!("Ignoring invalid app lock settings: {}", e);
+                        None
+                    }
+                },
+            )
+            .unwrap_or_default(),
+        Err(e) => {
+            log::warn!("Failed to open {}: {}", LOCK_STORE, e);
+            LockSettings::default()
+        }
+    };
+
+    *app.state::<AppLock>()
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = LockInner::load(settings, crate::events::now_ms());
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let idle = {
+                let lock = app.state::<AppLock>();
+                let inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+                let timeout_ms = u64::from(inner.settings.idle_minutes) * 60 * 1000;
+                inner.settings.pin_hash.is_some()
+                    && !inner.locked
+                    && timeout_ms > 0
+                    && crate::events::now_ms().saturating_sub(inner.last_activity_ms) >= timeout_ms
+            };
+            if idle {
+                set_locked(&app, true);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_app_lock_state(lock: tauri::State<'_, AppLock>) -> AppLockState {
+    lock.state()
+}
+
+/// Set or change the PIN (changing needs the current one)
+#[tauri::command]
+pub fn set_app_lock_pin(
+    app: AppHandle,
+    pin: String,
+    current_pin: Option<String>,
+) -> Result<AppLockState, FlowStateError> {
+    ensure_unlocked(&app, "set_app_lock_pin")?;
+    crate::read_only::ensure_writable(&app, "set_app_lock_pin")?;
+    verify(&app, current_pin.as_deref().unwrap_or_default())?;
+
+    let lock = app.state::<AppLock>();
+    let pin_hash = hash_pin(&pin)?;
+    let settings = {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.settings.pin_hash = Some(pin_hash);
+        inner.settings.clone()
+    };
+    save_settings(&app, &settings)?;
+    log::info!("App lock PIN set");
+
+    let state = lock.state();
+    crate::events::publish(&app, "app://lock", &state);
+    Ok(state)
+}
+
+/// Turn the lock off (needs the current PIN)
+#[tauri::command]
+pub fn disable_app_lock(app: AppHandle, pin: String) -> Result<AppLockState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "disable_app_lock")?;
+    verify(&app, &pin)?;
+
+    let lock = app.state::<AppLock>();
+    let settings = {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.settings.pin_hash = None;
+        inner.locked = false;
+        inner.settings.clone()
+    };
+    save_settings(&app, &settings)?;
+    log::info!("App lock disabled");
+
+    let state = lock.state();
+    crate::events::publish(&app, "app://lock", &state);
+    Ok(state)
+}
+
+/// Inactivity before locking, in minutes (0 = never lock on idle)
+#[tauri::command]
+pub fn set_app_lock_timeout(
+    app: AppHandle,
+    idle_minutes: u32,
+) -> Result<AppLockState, FlowStateError> {
+    ensure_unlocked(&app, "set_app_lock_timeout")?;
+    crate::read_only::ensure_writable(&app, "set_app_lock_timeout")?;
+
+    let lock = app.state::<AppLock>();
+    let settings = {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.settings.idle_minutes = idle_minutes;
+        inner.settings.clone()
+    };
+    save_settings(&app, &settings)?;
+    Ok(lock.state())
+}
+
+/// Lock now (no-op without a PIN)
+#[tauri::command]
+pub fn lock_app(app: AppHandle) -> AppLockState {
+    let enabled = app.state::<AppLock>().state().enabled;
+    if enabled {
+        set_locked(&app, true);
+    }
+    app.state::<AppLock>().state()
+}
+
+#[tauri::command]
+pub fn unlock_app(app: AppHandle, pin: String) -> Result<AppLockState, FlowStateError> {
+    verify(&app, &pin)?;
+    set_locked(&app, false);
+    Ok(app.state::<AppLock>().state())
+}
+
+/// Unlock after the OS authentication prompt instead of the PIN. A
+/// successful prompt also clears the wrong-PIN backoff.
+#[tauri::command]
+pub async fn unlock_app_with_os_auth(app: AppHandle) -> Result<AppLockState, FlowStateError> {
+    let lock = app.state::<AppLock>();
+    if !lock.is_locked() {
+        return Ok(lock.state());
+    }
+    crate::os_auth::verify(&app, Purpose::Unlock, "unlock FlowState").await?;
+    let cleared = {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.record_success().then(|| inner.settings.clone())
+    };
+    if let Some(settings) = cleared {
+        save_backoff(&app, &settings);
+    }
+    set_locked(&app, false);
+    Ok(lock.state())
+}
+
+/// Reset the inactivity timer (called by the frontend on user input, throttled)
+#[tauri::command]
+pub fn record_activity(app: AppHandle, lock: tauri::State<'_, AppLock>) {
+    {
+        let mut inner = lock.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if !inner.locked {
+            inner.last_activity_ms = crate::events::now_ms();
+        }
+    }
+    crate::background::record_interaction(&app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_length() {
+        assert!(validate_pin("123").is_err());
+        assert!(validate_pin("1234").is_ok());
+        assert!(validate_pin(&"9".repeat(MAX_PIN_LEN)).is_ok());
+        assert!(validate_pin(&"9".repeat(MAX_PIN_LEN + 1)).is_err());
+        // Characters, not bytes
+        assert!(validate_pin("ääää").is_ok());
+        assert!(validate_pin("äää").is_err());
+        assert!(validate_pin("").is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        for attempts in 0..FREE_ATTEMPTS {
+            assert_eq!(backoff_ms(attempts), None);
+        }
+        assert_eq!(backoff_ms(FREE_ATTEMPTS), Some(30_000));
+        assert_eq!(backoff_ms(FREE_ATTEMPTS + 1), Some(60_000));
+        assert_eq!(backoff_ms(FREE_ATTEMPTS + 2), Some(120_000));
+        assert_eq!(backoff_ms(FREE_ATTEMPTS + 5), Some(960_000));
+        assert_eq!(backoff_ms(FREE_ATTEMPTS + 6), Some(BACKOFF_MAX_MS));
+        assert_eq!(backoff_ms(u32::MAX), Some(BACKOFF_MAX_MS));
+    }
+
+    #[test]
+    fn failures_hold_unlocking_until_a_right_pin() {
+        let mut inner = LockInner::default();
+        for _ in 0..FREE_ATTEMPTS - 1 {
+            inner.record_failure(1_000);
+        }
+        assert_eq!(inner.settings.retry_after_ms, 0);
+        inner.record_failure(1_000);
+        assert_eq!(inner.settings.retry_after_ms, 31_000);
+        inner.record_failure(40_000);
+        assert_eq!(inner.settings.retry_after_ms, 100_000);
+
+        assert!(inner.record_success());
+        assert_eq!(inner.settings.failed_attempts, 0);
+        assert_eq!(inner.settings.retry_after_ms, 0);
+        assert!(!inner.record_success());
+        // Free attempts again after a reset
+        inner.record_failure(200_000);
+        assert_eq!(inner.settings.retry_after_ms, 0);
+    }
+
+    #[test]
+    fn backoff_survives_a_reload() {
+        let mut inner = LockInner::load(
+            LockSettings {
+                pin_hash: Some("$argon2id$hash".to_string()),
+                ..LockSettings::default()
+            },
+            0,
+        );
+        for _ in 0..FREE_ATTEMPTS + 1 {
+            inner.record_failure(1_000);
+        }
+        assert_eq!(inner.settings.retry_after_ms, 61_000);
+
+        // Saved to app-lock.json and read back at the next launch
+        let saved = serde_json::to_value(&inner.settings).unwrap();
+        let mut reloaded = LockInner::load(serde_json::from_value(saved).unwrap(), 5_000);
+        assert!(reloaded.locked);
+        assert_eq!(reloaded.settings.failed_attempts, FREE_ATTEMPTS + 1);
+        assert_eq!(reloaded.settings.retry_after_ms, 61_000);
+        // The next wrong PIN keeps doubling instead of starting over
+        reloaded.record_failure(70_000);
+        assert_eq!(reloaded.settings.retry_after_ms, 190_000);
+
+        // Settings saved before the backoff was kept load without it
+        let old: LockSettings =
+            serde_json::from_value(serde_json::json!({ "pinHash": "x", "idleMinutes": 5 }))
+                .unwrap();
+        assert_eq!(old.failed_attempts, 0);
+        assert_eq!(old.retry_after_ms, 0);
+    }
+}
@@ -0,0 +1,280 @@
+//! Text of attachments for task search.
+//!
+//! Plain text, Markdown, CSV, JSON and PDF attachments have their text
+//! extracted in the background, at most `WORKERS` files at a time, and kept
+//! in `attachments.db` (encrypted with it) so the search index (`search.rs`)
+//! can be rebuilt without reading the files again. Files over
+//! `MAX_INDEXED_FILE` are skipped and text is cut at `MAX_TEXT_CHARS`. Each
+//! attachment can be left out of search (`set_attachment_indexing`), and
+//! `rebuild_search_index` extracts everything again.
+
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::AppHandle;
+use tokio::sync::Semaphore;
+
+use crate::attachments::Attachment;
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+
+const WORKERS: usize = 2;
+const MAX_INDEXED_FILE: u64 = 25 * 1024 * 1024;
+const MAX_TEXT_CHARS: usize = 200_000;
+/// Longest the startup pass waits for the user to pause
+const MAX_START_DEFER: Duration = Duration::from_secs(90);
+
+static EXTRACTORS: Semaphore = Semaphore::const_new(WORKERS);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentIndexState {
+    pub attachment_id: String,
+    /// Left out of search by the user
+    pub excluded: bool,
+    /// Characters of text in the index (None until extracted, 0 for files
+    /// without extractable text)
+    pub indexed_chars: Option<usize>,
+}
+
+/// Text of a file by MIME type; None for types without extractable text
+fn extract(mime: &str, bytes: &[u8]) -> Result<Option<String>, String> {
+    let text = match mime {
+        "text/plain" | "text/markdown" | "text/csv" | "application/json" => {
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+        "application/pdf" => {
+            // lopdf can panic on malformed files
+            let extracted = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                let doc = lopdf::Document::load_mem(bytes)?;
+                let pages: Vec<u32> = doc.get_pages().keys().copied().collect();
+                doc.extract_text(&pages)
+            }))
+            .map_err(|_| "PDF parser crashed".to_string())?;
+            extracted.map_err(|e| format!("Unreadable PDF: {}", e))?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text,
+    }))
+}
+
+fn excluded(app: &AppHandle, id: &str) -> Result<bool, String> {
+    crate::attachments::with_db(app, |conn| {
+        conn.query_row(
+            "SELECT disabled FROM attachment_text WHERE attachment_id = ?1",
+            params![id],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()
+    })
+    .map(|excluded| excluded.unwrap_or(false))
+}
+
+/// Extract and store the text of one attachment, then re-index its task
+fn index(app: &AppHandle, attachment: &Attachment) -> Result<(), String> {
+    if excluded(app, &attachment.id)? {
+        return Ok(());
+    }
+    let text = if attachment.size > MAX_INDEXED_FILE {
+        None
+    } else {
+        let mut bytes = Vec::with_capacity(attachment.size as usize);
+        crate::attachments::read_blob(app, &attachment.hash, &mut bytes)?;
+        extract(&attachment.mime, &bytes).unwrap_or_else(|e| {
+            log::info!("No text from {}: {}", attachment.file_name, e);
+            None
+        })
+    };
+
+    let stored = crate::attachments::with_db(app, |conn| {
+        // The attachment may have been removed while its text was extracted
+        conn.execute(
+            "INSERT INTO attachment_text (attachment_id, disabled, content, extracted_at) \
+             SELECT id, 0, ?2, ?3 FROM attachments WHERE id = ?1 \
+             ON CONFLICT (attachment_id) DO UPDATE \
+             SET content = excluded.content, extracted_at = excluded.extracted_at \
+             WHERE NOT attachment_text.disabled",
+            params![attachment.id, text, now_ms() as i64],
+        )
+    })?;
+    if stored > 0 && text.is_some() {
+        crate::search::reindex(app, &attachment.task_id);
+    }
+    Ok(())
+}
+
+/// Extract an attachment's text in the background
+pub fn queue(app: &AppHandle, attachment: Attachment) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Ok(_permit) = EXTRACTORS.acquire().await else {
+            return;
+        };
+        let name = attachment.file_name.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || index(&app, &attachment)).await;
+        if let Err(e) = result.map_err(|e| e.to_string()).and_then(|r| r) {
+            log::warn!("Failed to index {}: {}", name, e);
+        }
+    });
+}
+
+/// Queue the attachments whose text hasn't been extracted; returns how many
+fn queue_pending(app: &AppHandle) -> Result<usize, String> {
+    let pending = crate::attachments::with_db(app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM attachments WHERE id NOT IN ( \
+                 SELECT attachment_id FROM attachment_text \
+                 WHERE disabled OR extracted_at IS NOT NULL) \
+             ORDER BY added_at",
+            crate::attachments::COLUMNS
+        ))?;
+        let rows = stmt.query_map([], crate::attachments::from_row)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    let count = pending.len();
+    for attachment in pending {
+        queue(app, attachment);
+    }
+    Ok(count)
+}
+
+/// Extracted text per task, of one task or all of them
+pub(crate) fn task_texts(
+    app: &AppHandle,
+    task_id: Option<&str>,
+) -> Result<HashMap<String, String>, String> {
+    crate::attachments::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT a.task_id, t.content FROM attachment_text t \
+             JOIN attachments a ON a.id = t.attachment_id \
+             WHERE NOT t.disabled AND t.content IS NOT NULL \
+               AND (?1 IS NULL OR a.task_id = ?1) \
+             ORDER BY a.added_at",
+        )?;
+        let rows = stmt.query_map(params![task_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut texts: HashMap<String, String> = HashMap::new();
+        for row in rows {
+            let (task_id, content) = row?;
+            let text = texts.entry(task_id).or_default();
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&content);
+        }
+        Ok(texts)
+    })
+}
+
+/// Drop the text of a removed attachment
+pub(crate) fn forget(app: &AppHandle, attachment: &Attachment) {
+    let result = crate::attachments::with_db(app, |conn| {
+        conn.execute(
+            "DELETE FROM attachment_text WHERE attachment_id = ?1",
+            params![attachment.id],
+        )
+    });
+    match result {
+        Ok(_) => crate::search::reindex(app, &attachment.task_id),
+        Err(e) => log::warn!("{}", e),
+    }
+}
+
+/// Extract the attachments left from the last run once the user pauses (a
+/// startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        crate::background::yield_to_user(&app, MAX_START_DEFER).await;
+        match queue_pending(&app) {
+            Ok(0) => {}
+            Ok(count) => log::info!("Indexing the text of {} attachments", count),
+            Err(e) => log::warn!("Attachment indexing not started: {}", e),
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_attachment_indexing(
+    app: AppHandle,
+    task_id: String,
+) -> Result<Vec<AttachmentIndexState>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_attachment_indexing")?;
+    Ok(crate::attachments::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT a.id, coalesce(t.disabled, 0), t.extracted_at, length(t.content) \
+             FROM attachments a LEFT JOIN attachment_text t ON t.attachment_id = a.id \
+             WHERE a.task_id = ?1 ORDER BY a.added_at",
+        )?;
+        let rows = stmt.query_map(params![task_id], |row| {
+            let extracted_at: Option<i64> = row.get(2)?;
+            let chars: Option<i64> = row.get(3)?;
+            Ok(AttachmentIndexState {
+                attachment_id: row.get(0)?,
+                excluded: row.get(1)?,
+                indexed_chars: extracted_at.map(|_| chars.unwrap_or(0) as usize),
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+/// Include an attachment's text in search or leave it out
+#[tauri::command]
+pub fn set_attachment_indexing(
+    app: AppHandle,
+    id: String,
+    enabled: bool,
+) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "set_attachment_indexing")?;
+    crate::read_only::ensure_writable(&app, "set_attachment_indexing")?;
+    let attachment = crate::attachments::get(&app, &id)?;
+    crate::attachments::with_db(&app, |conn| {
+        conn.execute(
+            "INSERT INTO attachment_text (attachment_id, disabled) VALUES (?1, ?2) \
+             ON CONFLICT (attachment_id) DO UPDATE \
+             SET disabled = excluded.disabled, content = NULL, extracted_at = NULL",
+            params![id, !enabled],
+        )
+    })?;
+    crate::search::reindex(&app, &attachment.task_id);
+    if enabled {
+        queue(&app, attachment);
+    }
+    Ok(())
+}
+
+/// Extract the text of every attachment again and rebuild the search index
+#[tauri::command]
+pub async fn rebuild_search_index(app: AppHandle) -> Result<usize, FlowStateError> {
+    crate::trace::scope("rebuild_search_index", async move {
+        crate::app_lock::ensure_unlocked(&app, "rebuild_search_index")?;
+        crate::read_only::ensure_writable(&app, "rebuild_search_index")?;
+        crate::attachments::with_db(&app, |conn| {
+            conn.execute(
+                "UPDATE attachment_text SET content = NULL, extracted_at = NULL",
+                [],
+            )
+        })?;
+        let handle = app.clone();
+        let tasks = tauri::async_runtime::spawn_blocking(move || crate::search::rebuild(&handle))
+            .await
+            .map_err(|e| format!("Search rebuild failed: {}", e))??;
+        let attachments = queue_pending(&app)?;
+        log::info!(
+            "Rebuilt the search index ({} tasks, {} attachments queued)",
+            tasks,
+            attachments
+        );
+        Ok(tasks)
+    })
+    .await
+}
@@ -0,0 +1,539 @@
+//! Files attached to tasks.
+//!
+//! Attached files are copied into `attachments/` in the app data directory,
+//! stored by SHA-256 (`attachments/ab/abcdef…`), so attaching the same file
+//! twice keeps one copy. Metadata (task, original name, hash, size, MIME
+//! type) lives in `attachments.db`. A file is deleted with its last
+//! attachment; files nothing refers to (left by a crash mid-copy or
+//! mid-remove) are collected on startup. With encryption at rest on, stored
+//! files are sealed (`encryption::seal_to`) and converted when it is switched.
+//! Opening decrypts a copy into a private directory in the app's cache
+//! directory, which is emptied on startup and exit. Text of documents is
+//! extracted for search by `attachment_index.rs`. Changes publish
+//! `attachment://changed` with the task id.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::sqlite::LocalDb;
+
+const DB_FILE: &str = "attachments.db";
+const STORE_DIR: &str = "attachments";
+/// Decrypted copies handed to viewers, below the app cache directory
+const OPENED_DIR: &str = "opened-attachments";
+const MAX_ATTACHMENT: u64 = 512 * 1024 * 1024;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS attachments (
+    id TEXT PRIMARY KEY,
+    task_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    mime TEXT NOT NULL,
+    added_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_attachments_task ON attachments(task_id);
+CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(hash);
+CREATE TABLE IF NOT EXISTS attachment_text (
+    attachment_id TEXT PRIMARY KEY,
+    disabled INTEGER NOT NULL DEFAULT 0,
+    content TEXT,
+    extracted_at INTEGER
+);";
+
+pub(crate) const COLUMNS: &str = "id, task_id, file_name, hash, size, mime, added_at";
+
+/// MIME types by lowercase extension; anything else is application/octet-stream
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("heic", "image/heic"),
+    ("pdf", "application/pdf"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("json", "application/json"),
+    ("html", "text/html"),
+    ("zip", "application/zip"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("mov", "video/quicktime"),
+];
+
+pub struct Attachments {
+    pub(crate) db: LocalDb,
+}
+
+impl Default for Attachments {
+    fn default() -> Self {
+        Attachments {
+            db: LocalDb::new(DB_FILE, SCHEMA),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub task_id: String,
+    pub file_name: String,
+    /// SHA-256, hex
+    pub hash: String,
+    pub size: u64,
+    pub mime: String,
+    pub added_at_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentsChanged {
+    pub task_id: String,
+}
+
+pub(crate) fn with_db<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    app.state::<Attachments>().db.with(app, f)
+}
+
+pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        file_name: row.get(2)?,
+        hash: row.get(3)?,
+        size: row.get::<_, i64>(4)? as u64,
+        mime: row.get(5)?,
+        added_at_ms: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+fn store_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(STORE_DIR))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+fn blob_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(&hash[..2]).join(hash)
+}
+
+fn opened_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join(OPENED_DIR))
+        .map_err(|e| format!("No app cache directory: {}", e))
+}
+
+/// Delete the decrypted copies made for viewers
+pub fn clean_opened(app: &AppHandle) {
+    let Ok(dir) = opened_dir(app) else {
+        return;
+    };
+    match std::fs::remove_dir_all(&dir) {
+        Ok(()) => log::debug!("Removed opened attachment copies"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Failed to remove {}: {}", dir.display(), e),
+    }
+}
+
+/// Directory only this user can read (the copies are decrypted)
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))
+}
+
+/// Write `target` through a `.partial` file from `fill`, so a half-written
+/// file never has the final name
+fn write_atomic(
+    target: &Path,
+    fill: impl FnOnce(&mut BufWriter<File>) -> Result<(), String>,
+) -> Result<(), String> {
+    let partial = target.with_extension("partial");
+    let result = File::create(&partial)
+        .map_err(|e| format!("Failed to create {}: {}", partial.display(), e))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            fill(&mut writer)?;
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))
+        })
+        .and_then(|()| {
+            std::fs::rename(&partial, target)
+                .map_err(|e| format!("Failed to replace {}: {}", target.display(), e))
+        });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+fn open_file(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    MIME_TYPES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map_or("application/octet-stream", |(_, mime)| mime)
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Copy `source` into the store (unless that content is already there)
+fn store_file(dir: &Path, source: &Path) -> Result<(String, u64), String> {
+    let size = std::fs::metadata(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?
+        .len();
+    if size > MAX_ATTACHMENT {
+        return Err(format!(
+            "{} is larger than {} MiB",
+            source.display(),
+            MAX_ATTACHMENT / 1024 / 1024
+        ));
+    }
+
+    let hash = hash_file(source)?;
+    let target = blob_path(dir, &hash);
+    if !target.exists() {
+        let parent = target.parent().unwrap_or(dir);
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        let mut reader = open_file(source)?;
+        write_atomic(&target, |writer| {
+            crate::encryption::seal_to(&mut reader, writer)
+                .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))
+        })?;
+    }
+    Ok((hash, size))
+}
+
+/// Decrypt the stored file of `hash` into `writer`
+pub(crate) fn read_blob(
+    app: &AppHandle,
+    hash: &str,
+    writer: &mut impl Write,
+) -> Result<(), String> {
+    let path = blob_path(&store_dir(app)?, hash);
+    crate::encryption::unseal_to(&mut open_file(&path)?, writer)
+}
+
+/// Rewrite every stored file for the current encryption state; returns how many
+pub(crate) fn rewrite_blobs(app: &AppHandle) -> Result<usize, String> {
+    let dir = store_dir(app)?;
+    let Ok(shards) = std::fs::read_dir(&dir) else {
+        return Ok(0);
+    };
+    let mut rewritten = 0;
+    for shard in shards.flatten() {
+        let Ok(files) = std::fs::read_dir(shard.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().is_some_and(|e| e == "partial") {
+                continue;
+            }
+            let mut reader = open_file(&path)?;
+            // Round-trip through a decrypted temporary copy
+            let plain = path.with_extension("plain");
+            write_atomic(&plain, |writer| {
+                crate::encryption::unseal_to(&mut reader, writer)
+            })?;
+            let mut plain_reader = open_file(&plain)?;
+            let sealed = write_atomic(&path, |writer| {
+                crate::encryption::seal_to(&mut plain_reader, writer)
+            });
+            let _ = std::fs::remove_file(&plain);
+            sealed?;
+            rewritten += 1;
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Delete the stored file of `hash` if no attachment refers to it any more
+fn release(app: &AppHandle, hash: &str) -> Result<(), String> {
+    let used = with_db(app, |conn| {
+        conn.query_row(
+            "SELECT 1 FROM attachments WHERE hash = ?1 LIMIT 1",
+            params![hash],
+            |_| Ok(()),
+        )
+        .optional()
+    })?;
+    if used.is_none() {
+        let path = blob_path(&store_dir(app)?, hash);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Failed to delete {}: {}", path.display(), e));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Delete stored files that no attachment refers to; returns how many
+pub(crate) fn collect_garbage(app: &AppHandle) -> Result<usize, String> {
+    let dir = store_dir(app)?;
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let hashes: std::collections::HashSet<String> = with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT DISTINCT hash FROM attachments")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    })?;
+
+    let mut removed = 0;
+    let shards =
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?;
+    for shard in shards.flatten() {
+        let Ok(files) = std::fs::read_dir(shard.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let name = file.file_name().to_string_lossy().into_owned();
+            // Leftovers of an interrupted write or rewrite go as well
+            if hashes.contains(&name) {
+                continue;
+            }
+            match std::fs::remove_file(file.path()) {
+                Ok(()) => removed += 1,
+                Err(e) => log::warn!("Failed to delete orphaned attachment {}: {}", name, e),
+            }
+        }
+        // Only succeeds once the shard is empty
+        let _ = std::fs::remove_dir(shard.path());
+    }
+    Ok(removed)
+}
+
+/// Collect orphaned files and copies left by the last run in the background at
+/// startup (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        clean_opened(&app);
+        match collect_garbage(&app) {
+            Ok(0) => {}
+            Ok(removed) => log::info!("Removed {} orphaned attachment files", removed),
+            Err(e) => log::warn!("Attachment cleanup failed: {}", e),
+        }
+    })
+}
+
+pub(crate) fn get(app: &AppHandle, id: &str) -> Result<Attachment, String> {
+    with_db(app, |conn| {
+        conn.query_row(
+            &format!("SELECT {} FROM attachments WHERE id = ?1", COLUMNS),
+            params![id],
+            from_row,
+        )
+        .optional()
+    })?
+    .ok_or_else(|| format!("Unknown attachment: {}", id))
+}
+
+/// Copy a file into the attachment store and attach it to a task
+#[tauri::command]
+pub async fn add_attachment(
+    app: AppHandle,
+    task_id: String,
+    path: String,
+) -> Result<Attachment, FlowStateError> {
+    crate::trace::scope("add_attachment", async move {
+        crate::app_lock::ensure_unlocked(&app, "add_attachment")?;
+        crate::read_only::ensure_writable(&app, "add_attachment")?;
+
+        let source = PathBuf::from(&path);
+        if !source.is_file() {
+            return Err(format!("Not a file: {}", path).into());
+        }
+        let dir = store_dir(&app)?;
+        let copy_from = source.clone();
+        let (hash, size) =
+            tauri::async_runtime::spawn_blocking(move || store_file(&dir, &copy_from))
+                .await
+                .map_err(|e| format!("Attachment copy failed: {}", e))??;
+
+        let bytes: [u8; 16] = rand::random();
+        let attachment = Attachment {
+            id: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            task_id,
+            file_name: source
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| hash.clone()),
+            mime: mime_type(&source).to_string(),
+            hash,
+            size,
+            added_at_ms: now_ms(),
+        };
+        with_db(&app, |conn| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO attachments ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    COLUMNS
+                ),
+                params![
+                    attachment.id,
+                    attachment.task_id,
+                    attachment.file_name,
+                    attachment.hash,
+                    attachment.size as i64,
+                    attachment.mime,
+                    attachment.added_at_ms as i64,
+                ],
+            )
+        })?;
+
+        log::info!(
+            "Attached {} ({} bytes) to task {}",
+            attachment.file_name,
+            attachment.size,
+            attachment.task_id
+        );
+        crate::events::publish(
+            &app,
+            "attachment://changed",
+            &AttachmentsChanged {
+                task_id: attachment.task_id.clone(),
+            },
+        );
+        crate::attachment_index::queue(&app, attachment.clone());
+        Ok(attachment)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn list_attachments(
+    app: AppHandle,
+    task_id: String,
+) -> Result<Vec<Attachment>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "list_attachments")?;
+    Ok(with_db(&app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM attachments WHERE task_id = ?1 ORDER BY added_at",
+            COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![task_id], from_row)?;
+        rows.collect()
+    })?)
+}
+
+/// Open an attachment with the system's default app
+#[tauri::command]
+pub fn open_attachment(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "open_attachment")?;
+    let attachment = get(&app, &id)?;
+    let stored = blob_path(&store_dir(&app)?, &attachment.hash);
+    if !stored.is_file() {
+        return Err(format!("The file of {} is missing", attachment.file_name).into());
+    }
+
+    // Viewers pick the app by extension, so open a decrypted copy under the
+    // original name rather than the hash-named file
+    let dir = opened_dir(&app)?.join(&attachment.id);
+    create_private_dir(&dir)?;
+    let name = Path::new(&attachment.file_name)
+        .file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_else(|| attachment.hash.clone().into());
+    let copy = dir.join(name);
+    write_atomic(&copy, |writer| read_blob(&app, &attachment.hash, writer))
+        .map_err(|e| format!("Failed to copy {}: {}", attachment.file_name, e))?;
+
+    app.opener()
+        .open_path(copy.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {}", attachment.file_name, e))?;
+    Ok(())
+}
+
+/// Detach a file; the stored copy goes when nothing else refers to it
+#[tauri::command]
+pub fn remove_attachment(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "remove_attachment")?;
+    crate::read_only::ensure_writable(&app, "remove_attachment")?;
+    let attachment = get(&app, &id)?;
+    with_db(&app, |conn| {
+        conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])
+    })?;
+    release(&app, &attachment.hash)?;
+    crate::attachment_index::forget(&app, &attachment);
+    crate::events::publish(
+        &app,
+        "attachment://changed",
+        &AttachmentsChanged {
+            task_id: attachment.task_id,
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,798 @@
+//! Audit log of sensitive actions.
+//!
+//! Actions worth answering "who saw this, and when" for, such as revealing
+//! a secret from the keychain, are appended to `audit.db` in the app data
+//! directory (encrypted with the other databases). Callers record before
+//! they act and give up if the entry can't be written, so nothing is
+//! revealed without a trace. Entries are never edited or deleted by the app.
+//!
+//! The log is tamper-evident. Each entry is hash-chained to the one before
+//! it (SHA-256 over the previous hash and the entry) in the same transaction
+//! that appends it, and finished time entries are chained too
+//! (`record_time_entry`), so editing, removing or slipping in an entry, or
+//! changing a time entry after it finished, breaks the chain or no longer
+//! matches it. With signing on (`set_audit_signing`), every completed UTC day
+//! is signed with an Ed25519 key kept in the OS keychain: the signature
+//! covers the hash of the day's last entry and so everything before it.
+//! `verify_audit_log` checks all of this, and `export_audit_log` writes the
+//! chain, signatures and public keys for someone else to check.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use base64::Engine;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::sqlite::LocalDb;
+use crate::time_tracking::TimeEntry;
+
+const DB_FILE: &str = "audit.db";
+const DEFAULT_LIMIT: u32 = 200;
+const MAX_LIMIT: u32 = 5000;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    detail TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log(at);
+-- Hash chain over audit_log, one row per entry
+CREATE TABLE IF NOT EXISTS audit_chain (
+    entry_id INTEGER PRIMARY KEY,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+-- Signatures of completed UTC days, over the hash of the day's last entry
+CREATE TABLE IF NOT EXISTS audit_signatures (
+    day TEXT PRIMARY KEY,
+    last_entry_id INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    signed_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS audit_meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);";
+/// Previous hash of the first entry
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const KEYRING_SERVICE: &str = "flowstate-audit-signing";
+const KEYRING_ACCOUNT: &str = "ed25519";
+/// audit_meta key holding the public key while signing is on
+const SIGNING_KEY: &str = "signing_key";
+const TIME_ENTRY_ACTION: &str = "time_entry.recorded";
+const EXPORT_VERSION: u32 = 1;
+
+pub struct AuditLog {
+    pub(crate) db: LocalDb,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog {
+            db: LocalDb::new(DB_FILE, SCHEMA),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at_ms: i64,
+    /// What happened, e.g. `secret.reveal`
+    pub action: String,
+    /// What it happened to, e.g. `<workspace>/<secret name>`
+    pub subject: String,
+    pub detail: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditSigning {
+    pub enabled: bool,
+    /// Hex Ed25519 public key, to publish for checking exports
+    pub public_key: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditVerification {
+    pub entries: usize,
+    /// First entry that doesn't match the chain (edited, removed or added
+    /// outside the app); None when the chain is intact
+    pub broken_at: Option<i64>,
+    pub signed_days: usize,
+    /// Days whose signature doesn't match the chain or doesn't verify
+    pub bad_signatures: Vec<String>,
+    /// Time entries that changed after they were chained
+    pub altered_time_entries: Vec<i64>,
+    /// Finished time entries never chained (from before chaining existed)
+    pub unrecorded_time_entries: usize,
+    pub valid: bool,
+}
+
+/// A time entry as it was chained
+#[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedEntry {
+    task_id: String,
+    started_at_ms: i64,
+    ended_at_ms: i64,
+}
+
+/// Hex SHA-256 of an entry and the hash before it
+fn entry_hash(
+    prev: &str,
+    id: i64,
+    at: i64,
+    action: &str,
+    subject: &str,
+    detail: Option<&str>,
+) -> String {
+    let encoded = serde_json::json!([prev, id, at, action, subject, detail]).to_string();
+    Sha256::digest(encoded.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// What a day's signature covers
+fn signed_message(day: &str, last_entry_id: i64, hash: &str) -> String {
+    format!("flowstate-audit:{}:{}:{}", day, last_entry_id, hash)
+}
+
+/// Chain the entries appended since the last chained one
+fn extend_chain(conn: &Connection) -> rusqlite::Result<()> {
+    let (last_id, mut prev): (i64, String) = conn
+        .query_row(
+            "SELECT entry_id, hash FROM audit_chain ORDER BY entry_id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .unwrap_or((0, GENESIS.to_string()));
+    let mut stmt = conn.prepare(
+        "SELECT id, at, action, subject, detail FROM audit_log WHERE id > ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![last_id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, at, action, subject, detail) = row?;
+        let hash = entry_hash(&prev, id, at, &action, &subject, detail.as_deref());
+        conn.execute(
+            "INSERT INTO audit_chain (entry_id, prev_hash, hash) VALUES (?1, ?2, ?3)",
+            params![id, prev, hash],
+        )?;
+        prev = hash;
+    }
+    Ok(())
+}
+
+/// Number of entries and the first one that breaks the chain
+fn verify_chain(conn: &Connection) -> rusqlite::Result<(usize, Option<i64>)> {
+    let mut stmt = conn.prepare(
+        "SELECT l.id, l.at, l.action, l.subject, l.detail, c.prev_hash, c.hash \
+         FROM audit_log l LEFT JOIN audit_chain c ON c.entry_id = l.id ORDER BY l.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+        ))
+    })?;
+    let mut prev = GENESIS.to_string();
+    let mut entries = 0;
+    let mut broken = None;
+    for row in rows {
+        let (id, at, action, subject, detail, prev_hash, hash) = row?;
+        entries += 1;
+        if broken.is_some() {
+            continue;
+        }
+        let expected = entry_hash(&prev, id, at, &action, &subject, detail.as_deref());
+        match (prev_hash, hash) {
+            (Some(prev_hash), Some(hash)) if prev_hash == prev && hash == expected => prev = hash,
+            _ => broken = Some(id),
+        }
+    }
+    // Chained entries that are gone
+    let removed: Option<i64> = conn.query_row(
+        "SELECT min(entry_id) FROM audit_chain WHERE entry_id NOT IN (SELECT id FROM audit_log)",
+        [],
+        |row| row.get(0),
+    )?;
+    let broken = match (broken, removed) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    Ok((entries, broken))
+}
+
+/// Completed days (before `today`, UTC) without a signature: the day, its
+/// last entry and that entry's hash
+fn unsigned_days(conn: &Connection, today: &str) -> rusqlite::Result<Vec<(String, i64, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.day, d.last_id, c.hash FROM ( \
+             SELECT date(at / 1000, 'unixepoch') AS day, max(id) AS last_id \
+             FROM audit_log GROUP BY day \
+         ) d JOIN audit_chain c ON c.entry_id = d.last_id \
+         WHERE d.day < ?1 AND d.day NOT IN (SELECT day FROM audit_signatures) \
+         ORDER BY d.day",
+    )?;
+    let rows = stmt.query_map(params![today], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?;
+    rows.collect()
+}
+
+fn sign_days(conn: &Connection, pair: &Ed25519KeyPair, today: &str) -> rusqlite::Result<usize> {
+    let public_key = hex(pair.public_key().as_ref());
+    let days = unsigned_days(conn, today)?;
+    for (day, last_id, hash) in &days {
+        let signature = pair.sign(signed_message(day, *last_id, hash).as_bytes());
+        conn.execute(
+            "INSERT INTO audit_signatures (day, last_entry_id, hash, signature, public_key, signed_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                day,
+                last_id,
+                hash,
+                hex(signature.as_ref()),
+                public_key,
+                now_ms() as i64
+            ],
+        )?;
+    }
+    Ok(days.len())
+}
+
+/// Signed days and the days whose signature is wrong
+fn verify_signatures(conn: &Connection) -> rusqlite::Result<(usize, Vec<String>)> {
+    let mut stmt = conn.prepare(
+        "SELECT s.day, s.last_entry_id, s.hash, s.signature, s.public_key, c.hash \
+         FROM audit_signatures s LEFT JOIN audit_chain c ON c.entry_id = s.last_entry_id \
+         ORDER BY s.day",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, Option<String>>(5)?,
+        ))
+    })?;
+    let mut signed = 0;
+    let mut bad = Vec::new();
+    for row in rows {
+        let (day, last_id, hash, signature, public_key, chained) = row?;
+        signed += 1;
+        let verified = match (unhex(&signature), unhex(&public_key)) {
+            (Some(signature), Some(public_key)) => UnparsedPublicKey::new(&ED25519, public_key)
+                .verify(signed_message(&day, last_id, &hash).as_bytes(), &signature)
+                .is_ok(),
+            _ => false,
+        };
+        if !verified || chained.as_deref() != Some(hash.as_str()) {
+            bad.push(day);
+        }
+    }
+    Ok((signed, bad))
+}
+
+/// Latest chained version of each time entry, ignoring those chained before
+/// the last data deletion (`takeout.rs`), which started ids over
+fn recorded_entries(conn: &Connection) -> rusqlite::Result<HashMap<i64, RecordedEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT subject, detail FROM audit_log \
+         WHERE action = ?1 AND id > coalesce((SELECT max(id) FROM audit_log WHERE action = ?2), 0) \
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(
+        params![TIME_ENTRY_ACTION, crate::takeout::DELETE_ACTION],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+    )?;
+    let mut recorded = HashMap::new();
+    for row in rows {
+        let (subject, detail) = row?;
+        let id = subject
+            .strip_prefix("time_entry/")
+            .and_then(|id| id.parse().ok());
+        let entry = detail.and_then(|d| serde_json::from_str(&d).ok());
+        if let (Some(id), Some(entry)) = (id, entry) {
+            recorded.insert(id, entry);
+        }
+    }
+    Ok(recorded)
+}
+
+/// Finished entries that differ from their chained version (or vanished),
+/// and how many were never chained
+fn compare_time_entries(
+    recorded: &HashMap<i64, RecordedEntry>,
+    entries: &[TimeEntry],
+) -> (Vec<i64>, usize) {
+    let mut altered = Vec::new();
+    let mut unrecorded = 0;
+    for entry in entries {
+        let Some(ended_at_ms) = entry.ended_at_ms else {
+            continue;
+        };
+        match recorded.get(&entry.id) {
+            Some(chained)
+                if *chained
+                    == (RecordedEntry {
+                        task_id: entry.task_id.clone(),
+                        started_at_ms: entry.started_at_ms,
+                        ended_at_ms,
+                    }) => {}
+            Some(_) => altered.push(entry.id),
+            None => unrecorded += 1,
+        }
+    }
+    let present: std::collections::HashSet<i64> = entries.iter().map(|e| e.id).collect();
+    altered.extend(recorded.keys().filter(|id| !present.contains(id)));
+    altered.sort_unstable();
+    (altered, unrecorded)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn today() -> String {
+    crate::clock::now_local()
+        .with_timezone(&chrono::Utc)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// The signing key from the keychain
+fn signing_key() -> Result<Ed25519KeyPair, String> {
+    let encoded = keyring_entry()?
+        .get_password()
+        .map_err(|e| format!("Failed to read the audit signing key: {}", e))?;
+    let pkcs8 = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Invalid audit signing key: {}", e))?;
+    Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| format!("Invalid audit signing key: {}", e))
+}
+
+/// Sign the completed days not signed yet, when signing is on
+fn sign_completed_days(app: &AppHandle) -> Result<usize, String> {
+    let db = &app.state::<AuditLog>().inner().db;
+    let pending = db.with(app, |conn| {
+        let enabled = conn
+            .query_row(
+                "SELECT 1 FROM audit_meta WHERE key = ?1",
+                params![SIGNING_KEY],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !enabled {
+            return Ok(false);
+        }
+        Ok(!unsigned_days(conn, &today())?.is_empty())
+    })?;
+    if !pending {
+        return Ok(0);
+    }
+    let pair = signing_key()?;
+    db.with(app, |conn| sign_days(conn, &pair, &today()))
+}
+
+/// Append an entry; callers abort the action when this fails
+pub(crate) fn record(
+    app: &AppHandle,
+    action: &str,
+    subject: &str,
+    detail: Option<&str>,
+) -> Result<(), String> {
+    app.state::<AuditLog>().db.with(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO audit_log (at, action, subject, detail) VALUES (?1, ?2, ?3, ?4)",
+            params![now_ms() as i64, action, subject, detail],
+        )?;
+        extend_chain(&tx)?;
+        tx.commit()
+    })?;
+    log::info!("audit: {} {}", action, subject);
+    if let Err(e) = sign_completed_days(app) {
+        log::warn!("Failed to sign the audit log: {}", e);
+    }
+    Ok(())
+}
+
+/// Chain a finished time entry, so later changes to it show up in
+/// `verify_audit_log`
+pub(crate) fn record_time_entry(app: &AppHandle, entry: &TimeEntry) {
+    let Some(ended_at_ms) = entry.ended_at_ms else {
+        return;
+    };
+    let detail = serde_json::to_string(&RecordedEntry {
+        task_id: entry.task_id.clone(),
+        started_at_ms: entry.started_at_ms,
+        ended_at_ms,
+    })
+    .unwrap_or_default();
+    let subject = format!("time_entry/{}", entry.id);
+    if let Err(e) = record(app, TIME_ENTRY_ACTION, &subject, Some(&detail)) {
+        log::warn!("Failed to chain time entry {}: {}", entry.id, e);
+    }
+}
+
+/// Most recent entries first
+#[tauri::command]
+pub fn get_audit_log(
+    app: AppHandle,
+    limit: Option<u32>,
+) -> Result<Vec<AuditEntry>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_audit_log")?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    Ok(app.state::<AuditLog>().db.with(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, at, action, subject, detail FROM audit_log \
+             ORDER BY at DESC, id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                at_ms: row.get(1)?,
+                action: row.get(2)?,
+                subject: row.get(3)?,
+                detail: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+/// Whether daily signing is on, and its public key
+#[tauri::command]
+pub fn get_audit_signing(app: AppHandle) -> Result<AuditSigning, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_audit_signing")?;
+    let public_key: Option<String> = app.state::<AuditLog>().db.with(&app, |conn| {
+        conn.query_row(
+            "SELECT value FROM audit_meta WHERE key = ?1",
+            params![SIGNING_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+    })?;
+    Ok(AuditSigning {
+        enabled: public_key.is_some(),
+        public_key,
+    })
+}
+
+/// Turn daily signing on (with a new key in the keychain) or off (deleting
+/// the key; existing signatures keep their public key and still verify)
+#[tauri::command]
+pub fn set_audit_signing(app: AppHandle, enabled: bool) -> Result<AuditSigning, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_audit_signing")?;
+    crate::app_lock::ensure_unlocked(&app, "set_audit_signing")?;
+    let db = &app.state::<AuditLog>().inner().db;
+    if !enabled {
+        record(&app, "audit.signing_disabled", "audit", None)?;
+        match keyring_entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(format!("Failed to delete the audit signing key: {}", e).into()),
+        }
+        db.with(&app, |conn| {
+            conn.execute(
+                "DELETE FROM audit_meta WHERE key = ?1",
+                params![SIGNING_KEY],
+            )
+        })?;
+        return get_audit_signing(app);
+    }
+
+    let public_key = match signing_key() {
+        Ok(pair) => hex(pair.public_key().as_ref()),
+        Err(_) => {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+                .map_err(|_| "Failed to generate an audit signing key".to_string())?;
+            let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                .map_err(|e| format!("Invalid audit signing key: {}", e))?;
+            keyring_entry()?
+                .set_password(&base64::engine::general_purpose::STANDARD.encode(pkcs8.as_ref()))
+                .map_err(|e| format!("Failed to store the audit signing key: {}", e))?;
+            hex(pair.public_key().as_ref())
+        }
+    };
+    db.with(&app, |conn| {
+        conn.execute(
+            "INSERT INTO audit_meta (key, value) VALUES (?1, ?2) \
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![SIGNING_KEY, public_key],
+        )
+    })?;
+    record(&app, "audit.signing_enabled", "audit", Some(&public_key))?;
+    get_audit_signing(app)
+}
+
+/// Check the hash chain, the daily signatures and the time entries against it
+#[tauri::command]
+pub fn verify_audit_log(app: AppHandle) -> Result<AuditVerification, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "verify_audit_log")?;
+    let (entries, broken_at, signed_days, bad_signatures, recorded) =
+        app.state::<AuditLog>().db.with(&app, |conn| {
+            // Entries from before chaining existed are chained on first use
+            let chained: i64 =
+                conn.query_row("SELECT count(*) FROM audit_chain", [], |row| row.get(0))?;
+            if chained == 0 {
+                extend_chain(conn)?;
+            }
+            let (entries, broken_at) = verify_chain(conn)?;
+            let (signed_days, bad_signatures) = verify_signatures(conn)?;
+            Ok((
+                entries,
+                broken_at,
+                signed_days,
+                bad_signatures,
+                recorded_entries(conn)?,
+            ))
+        })?;
+    let time_entries = crate::time_tracking::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM time_entries ORDER BY id",
+            crate::time_tracking::COLUMNS
+        ))?;
+        let rows = stmt.query_map([], TimeEntry::from_row)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    let (altered_time_entries, unrecorded_time_entries) =
+        compare_time_entries(&recorded, &time_entries);
+    let valid = broken_at.is_none() && bad_signatures.is_empty() && altered_time_entries.is_empty();
+    Ok(AuditVerification {
+        entries,
+        broken_at,
+        signed_days,
+        bad_signatures,
+        altered_time_entries,
+        unrecorded_time_entries,
+        valid,
+    })
+}
+
+/// Write the log with its chain, signatures and public keys to
+/// `flowstate-audit-<time>.json` in `dir` (picked in a dialog when not given)
+#[tauri::command]
+pub async fn export_audit_log(
+    app: AppHandle,
+    dir: Option<String>,
+) -> Result<PathBuf, FlowStateError> {
+    crate::trace::scope("export_audit_log", async move {
+        crate::app_lock::ensure_unlocked(&app, "export_audit_log")?;
+        let dir = crate::export::choose_dir(&app, dir).await?;
+        let path = dir.join(format!(
+            "flowstate-audit-{}.json",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        record(&app, "audit.export", &path.display().to_string(), None)?;
+        let (entries, signatures) = app.state::<AuditLog>().db.with(&app, |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT l.id, l.at, l.action, l.subject, l.detail, c.prev_hash, c.hash \
+                 FROM audit_log l LEFT JOIN audit_chain c ON c.entry_id = l.id ORDER BY l.id",
+            )?;
+            let entries = stmt
+                .query_map([], |row| {
+                    Ok(serde_json::json!({
+                        "id": row.get::<_, i64>(0)?,
+                        "atMs": row.get::<_, i64>(1)?,
+                        "action": row.get::<_, String>(2)?,
+                        "subject": row.get::<_, String>(3)?,
+                        "detail": row.get::<_, Option<String>>(4)?,
+                        "prevHash": row.get::<_, Option<String>>(5)?,
+                        "hash": row.get::<_, Option<String>>(6)?,
+                    }))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let mut stmt = conn.prepare(
+                "SELECT day, last_entry_id, hash, signature, public_key, signed_at \
+                 FROM audit_signatures ORDER BY day",
+            )?;
+            let signatures = stmt
+                .query_map([], |row| {
+                    Ok(serde_json::json!({
+                        "day": row.get::<_, String>(0)?,
+                        "lastEntryId": row.get::<_, i64>(1)?,
+                        "hash": row.get::<_, String>(2)?,
+                        "signature": row.get::<_, String>(3)?,
+                        "publicKey": row.get::<_, String>(4)?,
+                        "signedAtMs": row.get::<_, i64>(5)?,
+                    }))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok((entries, signatures))
+        })?;
+        let export = serde_json::json!({
+            "version": EXPORT_VERSION,
+            "exportedAt": chrono::Utc::now().to_rfc3339(),
+            "hashing": "hash = hex(sha256(JSON [prevHash, id, atMs, action, subject, detail])), \
+                        prevHash of the first entry = 64 zeros",
+            "signing": "Ed25519 signature over \"flowstate-audit:<day>:<lastEntryId>:<hash>\"",
+            "entries": entries,
+            "signatures": signatures,
+        });
+        let text = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        for (at, action) in [
+            (1_700_000_000_000i64, "secret.reveal"),
+            (1_700_000_100_000, "secret.export"),
+            (1_700_090_000_000, "secret.reveal"),
+        ] {
+            conn.execute(
+                "INSERT INTO audit_log (at, action, subject) VALUES (?1, ?2, 'ws/key')",
+                params![at, action],
+            )
+            .unwrap();
+            extend_chain(&conn).unwrap();
+        }
+        conn
+    }
+
+    fn entry(id: i64, task_id: &str, started_at_ms: i64, ended_at_ms: Option<i64>) -> TimeEntry {
+        TimeEntry {
+            id,
+            task_id: task_id.to_string(),
+            started_at_ms,
+            ended_at_ms,
+            duration_ms: 0,
+            stop_reason: None,
+        }
+    }
+
+    #[test]
+    fn intact_chain_verifies() {
+        let conn = fixture();
+        assert_eq!(verify_chain(&conn).unwrap(), (3, None));
+        let first: String = conn
+            .query_row(
+                "SELECT prev_hash FROM audit_chain WHERE entry_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(first, GENESIS);
+    }
+
+    #[test]
+    fn edits_removals_and_insertions_break_the_chain() {
+        let conn = fixture();
+        conn.execute("UPDATE audit_log SET subject = 'ws/other' WHERE id = 2", [])
+            .unwrap();
+        assert_eq!(verify_chain(&conn).unwrap(), (3, Some(2)));
+
+        let conn = fixture();
+        conn.execute("DELETE FROM audit_log WHERE id = 2", [])
+            .unwrap();
+        assert_eq!(verify_chain(&conn).unwrap(), (2, Some(2)));
+
+        let conn = fixture();
+        conn.execute("DELETE FROM audit_log WHERE id = 3", [])
+            .unwrap();
+        assert_eq!(verify_chain(&conn).unwrap(), (2, Some(3)));
+
+        let conn = fixture();
+        conn.execute(
+            "INSERT INTO audit_log (at, action, subject) VALUES (1, 'secret.reveal', 'x')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(verify_chain(&conn).unwrap(), (4, Some(4)));
+    }
+
+    #[test]
+    fn completed_days_are_signed_once() {
+        let conn = fixture();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        // 2023-11-14 holds entries 1 and 2, 2023-11-15 entry 3
+        assert_eq!(sign_days(&conn, &pair, "2023-11-15").unwrap(), 1);
+        assert_eq!(sign_days(&conn, &pair, "2023-11-15").unwrap(), 0);
+        assert_eq!(sign_days(&conn, &pair, "2023-11-16").unwrap(), 1);
+        assert_eq!(verify_signatures(&conn).unwrap(), (2, vec![]));
+
+        conn.execute(
+            "UPDATE audit_signatures SET last_entry_id = 1 WHERE day = '2023-11-14'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            verify_signatures(&conn).unwrap(),
+            (2, vec!["2023-11-14".to_string()])
+        );
+    }
+
+    #[test]
+    fn time_entries_match_their_chained_version() {
+        let recorded: HashMap<i64, RecordedEntry> = [
+            (1, ("a", 100, 200)),
+            (2, ("a", 300, 400)),
+            (5, ("b", 500, 600)),
+        ]
+        .into_iter()
+        .map(|(id, (task_id, started_at_ms, ended_at_ms))| {
+            (
+                id,
+                RecordedEntry {
+                    task_id: task_id.to_string(),
+                    started_at_ms,
+                    ended_at_ms,
+                },
+            )
+        })
+        .collect();
+        let entries = [
+            entry(1, "a", 100, Some(200)),
+            // Stretched after the fact
+            entry(2, "a", 300, Some(450)),
+            entry(3, "c", 700, Some(800)),
+            // Still running
+            entry(4, "c", 900, None),
+        ];
+        // 5 was deleted
+        assert_eq!(compare_time_entries(&recorded, &entries), (vec![2, 5], 1));
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(unhex(&hex(&[0, 15, 255])), Some(vec![0, 15, 255]));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+}
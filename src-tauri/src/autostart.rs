@@ -0,0 +1,150 @@
+//! Launch at login, optionally straight to the tray.
+//!
+//! The autostart plugin registers the app with the OS (a LaunchAgent on
+//! macOS, the Run key on Windows, an XDG autostart entry on Linux) with the
+//! `--autostart` flag; whether such a launch stays in the tray is kept in
+//! `autostart.json`. The main window starts hidden (see tauri.conf.json) and
+//! `init` shows it, except on a login launch to the tray: then it stays
+//! hidden, and the startup graph (the Docker and Supabase checks) waits until
+//! the window is first opened from the tray or by a second launch.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+/// Argument of the launch registered with the OS
+pub const LOGIN_FLAG: &str = "--autostart";
+pub(crate) const AUTOSTART_STORE: &str = "autostart.json";
+const MINIMIZED_KEY: &str = "minimized";
+
+pub struct Autostart {
+    launched_at_login: bool,
+    /// The startup graph is waiting for the main window to be shown
+    startup_deferred: AtomicBool,
+}
+
+impl Autostart {
+    /// Initial state from the process arguments
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        Autostart {
+            launched_at_login: args.any(|a| a == LOGIN_FLAG),
+            startup_deferred: AtomicBool::new(false),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutostartSettings {
+    pub enabled: bool,
+    /// Start in the tray when launched at login
+    pub minimized: bool,
+    /// This instance was started at login
+    pub launched_at_login: bool,
+}
+
+fn minimized(app: &AppHandle) -> bool {
+    app.store(AUTOSTART_STORE)
+        .ok()
+        .and_then(|store| store.get(MINIMIZED_KEY))
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
+
+fn settings(app: &AppHandle) -> Result<AutostartSettings, String> {
+    let enabled = app
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read autostart state: {}", e))?;
+    Ok(AutostartSettings {
+        enabled,
+        minimized: minimized(app),
+        launched_at_login: app.state::<Autostart>().launched_at_login,
+    })
+}
+
+fn start_backend(app: &AppHandle) {
+    // Initialize backend subsystems in the background so a slow or hung
+    // probe never blocks the window from becoming interactive
+    tauri::async_runtime::spawn(crate::init::run_startup_graph(app.clone()));
+}
+
+/// Show the main window and start the backend, unless this is a login launch
+/// to the tray (which needs the tray icon to exist)
+pub fn init(app: &AppHandle) {
+    let state = app.state::<Autostart>();
+    let to_tray =
+        state.launched_at_login && minimized(app) && app.tray_by_id(crate::tray::TRAY_ID).is_some();
+    if to_tray {
+        log::info!("Started at login; staying in the tray");
+        state.startup_deferred.store(true, Ordering::SeqCst);
+        return;
+    }
+
+    start_backend(app);
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window.show() {
+            log::error!("Failed to show main window: {}", e);
+        }
+    }
+}
+
+/// The main window was shown: start what a login launch to the tray held back
+pub fn on_main_window_shown(app: &AppHandle) {
+    if app
+        .state::<Autostart>()
+        .startup_deferred
+        .swap(false, Ordering::SeqCst)
+    {
+        start_backend(app);
+    }
+}
+
+#[tauri::command]
+pub fn get_autostart(app: AppHandle) -> Result<AutostartSettings, FlowStateError> {
+    Ok(settings(&app)?)
+}
+
+/// Register or unregister the app as a login item, and whether it then
+/// starts in the tray
+#[tauri::command]
+pub fn set_autostart(
+    app: AppHandle,
+    enabled: bool,
+    minimized: bool,
+) -> Result<AutostartSettings, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_autostart")?;
+    let autolaunch = app.autolaunch();
+    let result = if enabled {
+        autolaunch.enable()
+    } else if autolaunch.is_enabled().unwrap_or(true) {
+        autolaunch.disable()
+    } else {
+        Ok(())
+    };
+    result.map_err(|e| format!("Failed to update autostart: {}", e))?;
+
+    let store = app
+        .store(AUTOSTART_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", AUTOSTART_STORE, e))?;
+    store.set(MINIMIZED_KEY, minimized);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", AUTOSTART_STORE, e))?;
+
+    log::info!(
+        "Autostart {}{}",
+        if enabled { "enabled" } else { "disabled" },
+        if enabled && minimized {
+            " (to tray)"
+        } else {
+            ""
+        }
+    );
+    Ok(settings(&app)?)
+}
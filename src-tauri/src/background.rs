@@ -0,0 +1,140 @@
+//! Window visibility and user activity, for scheduling background work.
+//!
+//! Window events keep track of which windows are focused and visible, and the
+//! frontend's throttled input reports (`record_activity`) mark interaction.
+//! Subsystems with deferrable work (the periodic task sync, building the
+//! search index at startup) call `yield_to_user` first, which waits while the
+//! user is interacting, up to a limit so the work still happens on a busy day.
+//! When the last window is hidden the heatmap cache is refreshed, so it is
+//! warm when the window comes back. Focus, visibility and input changes
+//! publish `app://visibility`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Window, WindowEvent};
+
+use crate::events::now_ms;
+
+/// Input this recent counts as interacting
+const INTERACTION_GRACE_MS: u64 = 10_000;
+/// How often a yielding task checks again
+const POLL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Default)]
+struct WindowState {
+    focused: bool,
+    visible: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppVisibility {
+    /// Some window has focus
+    pub focused: bool,
+    /// Some window is shown and not minimized
+    pub visible: bool,
+    /// A focused window had input within the last few seconds
+    pub interacting: bool,
+}
+
+#[derive(Default)]
+pub struct BackgroundWork {
+    windows: Mutex<HashMap<String, WindowState>>,
+    last_interaction_ms: Mutex<u64>,
+    published: Mutex<Option<AppVisibility>>,
+}
+
+impl BackgroundWork {
+    fn visibility(&self) -> AppVisibility {
+        let windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let focused = windows.values().any(|w| w.focused);
+        let last = *self
+            .last_interaction_ms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        AppVisibility {
+            focused,
+            visible: windows.values().any(|w| w.visible),
+            interacting: focused && now_ms().saturating_sub(last) < INTERACTION_GRACE_MS,
+        }
+    }
+}
+
+/// Publish the visibility if it changed
+fn publish(app: &AppHandle) {
+    let work = app.state::<BackgroundWork>();
+    let current = work.visibility();
+    let previous = {
+        let mut published = work.published.lock().unwrap_or_else(|e| e.into_inner());
+        if *published == Some(current) {
+            return;
+        }
+        published.replace(current)
+    };
+    crate::events::publish(app, "app://visibility", &current);
+    if previous.is_some_and(|p| p.visible) && !current.visible {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { crate::heatmap::prewarm(&app).await });
+    }
+}
+
+pub fn visibility(app: &AppHandle) -> AppVisibility {
+    app.state::<BackgroundWork>().visibility()
+}
+
+/// The user is working in the app right now
+pub fn user_active(app: &AppHandle) -> bool {
+    visibility(app).interacting
+}
+
+/// Note user input (from `record_activity`)
+pub fn record_interaction(app: &AppHandle) {
+    *app.state::<BackgroundWork>()
+        .last_interaction_ms
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = now_ms();
+    publish(app);
+}
+
+/// Wait while the user is interacting, for at most `max_wait`
+pub async fn yield_to_user(app: &AppHandle, max_wait: Duration) {
+    let started = Instant::now();
+    while user_active(app) && started.elapsed() < max_wait {
+        tokio::time::sleep(POLL).await;
+    }
+}
+
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    let work = window.state::<BackgroundWork>();
+    {
+        let mut windows = work.windows.lock().unwrap_or_else(|e| e.into_inner());
+        match event {
+            WindowEvent::Destroyed => {
+                windows.remove(window.label());
+            }
+            WindowEvent::Focused(_) | WindowEvent::Resized(_) => {
+                let state = windows.entry(window.label().to_string()).or_default();
+                state.focused = window.is_focused().unwrap_or(false);
+                state.visible =
+                    window.is_visible().unwrap_or(true) && !window.is_minimized().unwrap_or(false);
+            }
+            _ => return,
+        }
+    }
+    if let WindowEvent::Focused(true) = event {
+        // Switching to the app is interaction
+        *work
+            .last_interaction_ms
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = now_ms();
+    }
+    publish(window.app_handle());
+}
+
+#[tauri::command]
+pub fn get_app_visibility(app: AppHandle) -> AppVisibility {
+    visibility(&app)
+}
@@ -0,0 +1,662 @@
+//! Backup and restore of the local Supabase database.
+//!
+//! `pg_dump`/`pg_restore` run inside the Supabase db container (no local
+//! Postgres tools needed) in custom format. The dump is streamed from the
+//! container's stdout to the file and the file back into `pg_restore`'s stdin,
+//! publishing `backup://progress` events every few megabytes; the file side
+//! runs on a blocking thread. With encryption at rest on, dumps are sealed
+//! like attachment blobs (`encryption::seal_to`), so restoring one needs this
+//! device's key; plain dumps still restore. Without a path, the user picks
+//! one in a save/open dialog.
+//!
+//! Nightly backups are opt-in (`set_backup_schedule`): at the chosen local
+//! hour the database is dumped to `backups/` in the app data directory, one
+//! file per night, and all but the newest `keep` are deleted. The hour is
+//! read on the app clock, so on the night a DST change skips it the backup
+//! runs when the clock jumps, and when it comes twice the backup runs once.
+//! A night missed while the app was closed or asleep (or a failed backup) is
+//! made up at the scheduler's next look, within `SCHEDULE_POLL`.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+/// Bytes between progress events
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+/// pg_dump output buffered between the process and the file writer
+const DUMP_CHUNKS: usize = 16;
+pub(crate) const SCHEDULE_STORE: &str = "backup-schedule.json";
+const SCHEDULE_KEY: &str = "schedule";
+const BACKUP_DIR: &str = "backups";
+const FILE_PREFIX: &str = "flowstate-local-";
+const FILE_SUFFIX: &str = ".dump";
+const MAX_KEEP: u32 = 365;
+/// Longest wait before the schedule is read again
+const SCHEDULE_POLL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupSchedule {
+    pub enabled: bool,
+    /// Local hour of the nightly backup
+    pub hour: u32,
+    /// Nightly backups kept; older ones are deleted
+    pub keep: u32,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        BackupSchedule {
+            enabled: false,
+            hour: 2,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupProgress {
+    /// backup | restore
+    pub operation: &'static str,
+    pub bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub done: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupResult {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+fn publish(
+    app: &AppHandle,
+    operation: &'static str,
+    bytes: u64,
+    total_bytes: Option<u64>,
+    done: bool,
+) {
+    crate::events::publish(
+        app,
+        "backup://progress",
+        &BackupProgress {
+            operation,
+            bytes,
+            total_bytes,
+            done,
+        },
+    );
+}
+
+/// Use the given path or ask the user for one
+async fn choose_path(app: &AppHandle, path: Option<String>, save: bool) -> Result<PathBuf, String> {
+    if let Some(path) = path {
+        return Ok(PathBuf::from(path));
+    }
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let dialog = app
+        .dialog()
+        .file()
+        .add_filter("Postgres dump", &["dump"])
+        .set_file_name("flowstate-local.dump");
+    if save {
+        dialog.save_file(move |file| {
+            let _ = tx.send(file);
+        });
+    } else {
+        dialog.pick_file(move |file| {
+            let _ = tx.send(file);
+        });
+    }
+
+    rx.await
+        .map_err(|_| "File dialog closed unexpectedly".to_string())?
+        .ok_or_else(|| "No file selected".to_string())?
+        .into_path()
+        .map_err(|e| format!("Unsupported file location: {}", e))
+}
+
+/// The dump as it arrives, read on the blocking side
+struct ChunkReader {
+    chunks: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reads through to `inner`, calling `report` every `PROGRESS_STEP` bytes
+struct Progress<R, F> {
+    inner: R,
+    report: F,
+    bytes: u64,
+    next_report: u64,
+}
+
+impl<R: Read, F: FnMut(u64)> Progress<R, F> {
+    fn new(inner: R, report: F) -> Self {
+        Progress {
+            inner,
+            report,
+            bytes: 0,
+            next_report: PROGRESS_STEP,
+        }
+    }
+}
+
+impl<R: Read, F: FnMut(u64)> Read for Progress<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        if self.bytes >= self.next_report {
+            (self.report)(self.bytes);
+            self.next_report += PROGRESS_STEP;
+        }
+        Ok(n)
+    }
+}
+
+/// pg_restore's stdin
+struct ChildInput(CommandChild);
+
+impl Write for ChildInput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf).map_err(|e| {
+            std::io::Error::other(format!("pg_restore stopped reading input: {}", e))
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Dump the local database to `path`, sealed when encryption is on; the
+/// bytes pg_dump wrote
+async fn dump(app: &AppHandle, path: &Path) -> Result<u64, String> {
+    let container = crate::db_container_name();
+
+    let file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?
+        .into_std()
+        .await;
+
+    let (mut rx, child) = match crate::trace::command(app, "docker")
+        .args([
+            "exec",
+            container.as_str(),
+            "pg_dump",
+            "-U",
+            "postgres",
+            "-Fc",
+            "postgres",
+        ])
+        .set_raw_out(true)
+        .spawn()
+    {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(format!("Failed to run docker: {}", e));
+        }
+    };
+
+    // Sealed and written on a blocking thread as the output arrives
+    let (chunks, received) = tokio::sync::mpsc::channel(DUMP_CHUNKS);
+    let writer = {
+        let app = app.clone();
+        let path = path.to_path_buf();
+        tauri::async_runtime::spawn_blocking(move || -> Result<u64, String> {
+            let mut file = file;
+            let chunks = ChunkReader {
+                chunks: received,
+                chunk: Vec::new(),
+                pos: 0,
+            };
+            let mut reader =
+                Progress::new(chunks, |bytes| publish(&app, "backup", bytes, None, false));
+            crate::encryption::seal_to(&mut reader, &mut file)
+                .and_then(|_| file.sync_all().map_err(|e| e.to_string()))
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Ok(reader.bytes)
+        })
+    };
+
+    let mut stderr = String::new();
+    let mut success = false;
+    while let Some(event) = rx.recv().await {
+        let chunk = match event {
+            CommandEvent::Stdout(chunk) => chunk,
+            CommandEvent::Stderr(chunk) => {
+                stderr.push_str(&String::from_utf8_lossy(&chunk));
+                continue;
+            }
+            CommandEvent::Terminated(payload) => {
+                success = payload.code == Some(0);
+                break;
+            }
+            _ => continue,
+        };
+        // The writer only stops taking chunks when it failed
+        if chunks.send(chunk).await.is_err() {
+            let _ = child.kill();
+            break;
+        }
+    }
+    drop(chunks);
+
+    let written = writer
+        .await
+        .map_err(|e| format!("Backup task failed: {}", e))
+        .and_then(|written| written)
+        .and_then(|bytes| {
+            if success {
+                Ok(bytes)
+            } else {
+                Err(format!("pg_dump failed: {}", stderr.trim()))
+            }
+        });
+    let bytes = match written {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(e);
+        }
+    };
+
+    publish(app, "backup", bytes, Some(bytes), true);
+    log::info!(
+        "Backed up local database to {} ({} bytes)",
+        path.display(),
+        bytes
+    );
+    Ok(bytes)
+}
+
+/// Dump the local database to a file (custom format)
+#[tauri::command]
+pub async fn backup_database(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<BackupResult, FlowStateError> {
+    crate::trace::scope("backup_database", async move {
+        crate::app_lock::ensure_unlocked(&app, "backup_database")?;
+        let path = choose_path(&app, path, true).await?;
+        let bytes = dump(&app, &path).await?;
+        Ok(BackupResult { path, bytes })
+    })
+    .await
+}
+
+/// Restore the local database from a custom-format dump (replaces existing objects)
+#[tauri::command]
+pub async fn restore_database(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<BackupResult, FlowStateError> {
+    crate::trace::scope("restore_database", async move {
+        crate::app_lock::ensure_unlocked(&app, "restore_database")?;
+        crate::read_only::ensure_writable(&app, "restore_database")?;
+
+        let path = choose_path(&app, path, false).await?;
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let total = file.metadata().await.map(|m| m.len()).ok();
+        let file = file.into_std().await;
+        let container = crate::db_container_name();
+
+        let (mut rx, child) = crate::trace::command(&app, "docker")
+            .args([
+                "exec",
+                "-i",
+                container.as_str(),
+                "pg_restore",
+                "-U",
+                "postgres",
+                "-d",
+                "postgres",
+                "--clean",
+                "--if-exists",
+            ])
+            .spawn()
+            .map_err(|e| format!("Failed to run docker: {}", e))?;
+
+        // Feed the dump (unsealed) from a blocking task while draining the
+        // output here; pg_restore stops reading once its output isn't read
+        let writer = {
+            let app = app.clone();
+            let path = path.clone();
+            tauri::async_runtime::spawn_blocking(move || -> Result<u64, String> {
+                let mut reader =
+                    Progress::new(file, |bytes| publish(&app, "restore", bytes, total, false));
+                let mut input = ChildInput(child);
+                crate::encryption::unseal_to(&mut reader, &mut input)
+                    .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+                // Dropping the child closes stdin so pg_restore sees EOF
+                drop(input);
+                Ok(reader.bytes)
+            })
+        };
+
+        let mut stderr = String::new();
+        let mut success = false;
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stderr(line) => stderr.push_str(&String::from_utf8_lossy(&line)),
+                CommandEvent::Terminated(payload) => {
+                    success = payload.code == Some(0);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let written = writer
+            .await
+            .map_err(|e| format!("Restore task failed: {}", e))?;
+
+        if !success {
+            return Err(format!("pg_restore failed: {}", stderr.trim()).into());
+        }
+        let bytes = written?;
+
+        publish(&app, "restore", bytes, total, true);
+        crate::heatmap::invalidate(&app);
+        log::info!("Restored local database from {}", path.display());
+
+        Ok(BackupResult { path, bytes })
+    })
+    .await
+}
+
+fn schedule(app: &AppHandle) -> BackupSchedule {
+    app.store(SCHEDULE_STORE)
+        .ok()
+        .and_then(|store| store.get(SCHEDULE_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn file_name(date: NaiveDate) -> String {
+    format!("{}{}{}", FILE_PREFIX, date, FILE_SUFFIX)
+}
+
+/// Nightly backups among `names` beyond the newest `keep`
+fn expired(names: &[String], keep: u32) -> Vec<String> {
+    let mut backups: Vec<&String> = names
+        .iter()
+        .filter(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        .collect();
+    // Dates sort as text
+    backups.sort_unstable_by(|a, b| b.cmp(a));
+    backups.into_iter().skip(keep as usize).cloned().collect()
+}
+
+/// Time until the scheduler next looks on the clock of `tz`: the backup
+/// hour, or sooner so a changed hour is picked up
+fn next_wake<Tz: TimeZone>(tz: &Tz, hour: u32) -> Duration {
+    crate::clock::until_next(&crate::clock::now_in(tz), hour).min(SCHEDULE_POLL)
+}
+
+/// Local date of the nightly backup due at `now`, unless `names` has it:
+/// the date the clock last showed `hour`, so a night missed while the app
+/// was closed or asleep is caught up, and a repeated hour finds its backup
+fn due<Tz: TimeZone>(names: &[String], now: &DateTime<Tz>, hour: u32) -> Option<NaiveDate> {
+    let date = crate::clock::last_at(now, hour)?.date_naive();
+    (!names.contains(&file_name(date))).then_some(date)
+}
+
+async fn file_names(dir: &Path) -> Result<Vec<String>, String> {
+    let read_failed = |e: std::io::Error| format!("Failed to read {}: {}", dir.display(), e);
+    let mut entries = tokio::fs::read_dir(dir).await.map_err(read_failed)?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await.map_err(read_failed)? {
+        if let Ok(name) = entry.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+async fn run_nightly(app: &AppHandle, schedule: &BackupSchedule) -> Result<(), String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?
+        .join(BACKUP_DIR);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let Some(date) = due(
+        &file_names(&dir).await?,
+        &crate::clock::now_local(),
+        schedule.hour,
+    ) else {
+        return Ok(());
+    };
+    dump(app, &dir.join(file_name(date))).await?;
+
+    for name in expired(&file_names(&dir).await?, schedule.keep) {
+        if let Err(e) = tokio::fs::remove_file(dir.join(&name)).await {
+            log::warn!("Failed to delete old backup {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+/// Back up every night while scheduled (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Waits before the first look too, so the stack can come up
+            crate::clock::sleep(next_wake(&Local, schedule(&app).hour)).await;
+            let schedule = schedule(&app);
+            if !schedule.enabled {
+                continue;
+            }
+            if let Err(e) = run_nightly(&app, &schedule).await {
+                log::warn!("Nightly backup failed: {}", e);
+            }
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_backup_schedule(app: AppHandle) -> BackupSchedule {
+    schedule(&app)
+}
+
+/// Turn nightly backups on or off, or change their hour and how many are kept
+#[tauri::command]
+pub fn set_backup_schedule(
+    app: AppHandle,
+    schedule: BackupSchedule,
+) -> Result<BackupSchedule, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_backup_schedule")?;
+    crate::app_lock::ensure_unlocked(&app, "set_backup_schedule")?;
+    if schedule.hour > 23 {
+        return Err("hour must be 0 to 23".into());
+    }
+    if schedule.keep == 0 || schedule.keep > MAX_KEEP {
+        return Err(format!("Keep 1 to {} backups", MAX_KEEP).into());
+    }
+    let store = app
+        .store(SCHEDULE_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", SCHEDULE_STORE, e))?;
+    store.set(
+        SCHEDULE_KEY,
+        serde_json::to_value(&schedule).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", SCHEDULE_STORE, e))?;
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backups the scheduler takes at `hour` in `hours` from `start` on the
+    /// New York clock, with `names` already on disk: each file and the local
+    /// time it was written
+    fn backups(start: &str, hour: u32, names: &[&str], hours: u64) -> Vec<(String, String)> {
+        let tz = chrono_tz::America::New_York;
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        crate::clock::set(
+            chrono::DateTime::parse_from_rfc3339(start)
+                .unwrap()
+                .timestamp_millis(),
+        );
+        let end = crate::clock::now_ms() + hours * 60 * 60 * 1000;
+        let mut runs = Vec::new();
+        while crate::clock::now_ms() < end {
+            crate::clock::advance(next_wake(&tz, hour).as_millis() as u64);
+            let now = crate::clock::now_in(&tz);
+            if let Some(date) = due(&names, &now, hour) {
+                names.push(file_name(date));
+                // Real time passes between steps; compare to the minute
+                runs.push((file_name(date), now.format("%m-%d %H:%M %Z").to_string()));
+            }
+        }
+        crate::clock::reset();
+        runs
+    }
+
+    fn runs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(name, at)| (name.to_string(), at.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn backs_up_once_when_the_hour_repeats() {
+        // 01:00 comes twice on 2026-11-01; the second finds its backup
+        assert_eq!(
+            backups(
+                "2026-10-31T12:00:00-04:00",
+                1,
+                &["flowstate-local-2026-10-31.dump"],
+                40
+            ),
+            runs(&[
+                ("flowstate-local-2026-11-01.dump", "11-01 01:00 EDT"),
+                ("flowstate-local-2026-11-02.dump", "11-02 01:00 EST"),
+            ])
+        );
+    }
+
+    #[test]
+    fn catches_up_after_a_missed_night() {
+        // Closed over the nights of 03-07 and 03-08: the latest is caught up
+        // at the first look, then the schedule goes on
+        assert_eq!(
+            backups(
+                "2026-03-08T09:00:00-04:00",
+                2,
+                &["flowstate-local-2026-03-06.dump"],
+                20
+            ),
+            runs(&[
+                ("flowstate-local-2026-03-08.dump", "03-08 09:10 EDT"),
+                ("flowstate-local-2026-03-09.dump", "03-09 02:00 EDT"),
+            ])
+        );
+        // Opened before the hour: the night before is caught up, and the
+        // night's own backup runs when the clock jumps past 02:00
+        assert_eq!(
+            backups(
+                "2026-03-08T01:00:00-05:00",
+                2,
+                &["flowstate-local-2026-03-06.dump"],
+                4
+            ),
+            runs(&[
+                ("flowstate-local-2026-03-07.dump", "03-08 01:10 EST"),
+                ("flowstate-local-2026-03-08.dump", "03-08 03:00 EDT"),
+            ])
+        );
+    }
+
+    #[test]
+    fn hands_the_dump_over_as_it_arrives() {
+        let (chunks, received) = tokio::sync::mpsc::channel(DUMP_CHUNKS);
+        let sender = std::thread::spawn(move || {
+            for chunk in [b"PGDMP".to_vec(), Vec::new(), vec![7; 5 * 1024 * 1024]] {
+                chunks.blocking_send(chunk).unwrap();
+            }
+        });
+        let mut reports = Vec::new();
+        let mut reader = Progress::new(
+            ChunkReader {
+                chunks: received,
+                chunk: Vec::new(),
+                pos: 0,
+            },
+            |bytes| reports.push(bytes),
+        );
+        let mut out = Vec::new();
+        std::io::copy(&mut reader, &mut out).unwrap();
+        sender.join().unwrap();
+        assert_eq!(reader.bytes, 5 + 5 * 1024 * 1024);
+        assert_eq!(&out[..5], b"PGDMP");
+        assert_eq!(out.len() as u64, reader.bytes);
+        drop(reader);
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0] >= PROGRESS_STEP);
+    }
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let names: Vec<String> = [
+            "flowstate-local-2026-03-09.dump",
+            "flowstate-local-2026-03-07.dump",
+            "notes.txt",
+            "flowstate-local-2026-03-08.dump",
+            "flowstate-local-2026-03-06.dump",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        assert_eq!(
+            expired(&names, 2),
+            [
+                "flowstate-local-2026-03-07.dump",
+                "flowstate-local-2026-03-06.dump"
+            ]
+        );
+        assert!(expired(&names, 7).is_empty());
+    }
+}
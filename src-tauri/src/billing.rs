@@ -0,0 +1,429 @@
+//! Billable time and per-project hourly rates.
+//!
+//! When a timer starts its entry is stamped with the task's project and
+//! counted as billable until `set_entry_billable` says otherwise (not for
+//! entries locked after invoicing, see `entry_locks.rs`). Project
+//! rates are an append-only history in the time tracking database: a new
+//! rate takes effect from now (or a later date) and an entry is priced at
+//! the rate in effect when it started, so a rate change never reprices time
+//! already tracked. `get_earnings` prices each entry's exact duration at
+//! that rate, in integer cents; the timesheet generator uses the same rate
+//! but rounds each entry by its rounding rule first, so a sheet can bill more
+//! or less than the earnings of its range.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::time_tracking::{TimeEntry, TimeRange};
+
+/// Allowance for clock skew between the frontend and backend when a rate
+/// is meant to start now
+const RATE_SLACK_MS: i64 = 60_000;
+const HOUR_MS: f64 = 3_600_000.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectRate {
+    pub project_id: String,
+    /// Per hour
+    pub rate_cents: i64,
+    pub currency: String,
+    pub effective_from_ms: i64,
+}
+
+/// A finished entry with what it bills
+#[derive(Clone)]
+pub struct PricedEntry {
+    pub entry: TimeEntry,
+    /// Stamped when the timer started; None for entries from before billing
+    pub project_id: Option<String>,
+    pub billable: bool,
+    /// Rate in effect when the entry started
+    pub rate: Option<ProjectRate>,
+}
+
+impl PricedEntry {
+    /// Amount for a duration at this entry's rate
+    pub fn amount_cents(&self, duration_ms: i64) -> Option<i64> {
+        self.rate
+            .as_ref()
+            .map(|rate| (duration_ms as f64 * rate.rate_cents as f64 / HOUR_MS).round() as i64)
+    }
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectEarnings {
+    pub project_id: Option<String>,
+    /// None when no rate applies
+    pub currency: Option<String>,
+    pub billable_ms: i64,
+    pub non_billable_ms: i64,
+    /// Billable time without a rate in effect
+    pub unpriced_ms: i64,
+    pub amount_cents: i64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Earnings {
+    pub billable_ms: i64,
+    pub non_billable_ms: i64,
+    /// Per project and currency
+    pub projects: Vec<ProjectEarnings>,
+}
+
+/// Stamp a new entry with its project; entries start out billable
+pub(crate) fn stamp_entry(
+    conn: &Connection,
+    entry_id: i64,
+    project_id: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO time_entry_billing (entry_id, project_id, billable) VALUES (?1, ?2, 1)",
+        params![entry_id, project_id],
+    )?;
+    Ok(())
+}
+
+/// Finished entries that started in a range with their project, billable
+/// flag and rate, oldest first
+pub(crate) fn priced_entries(
+    app: &AppHandle,
+    range: &TimeRange,
+) -> Result<Vec<PricedEntry>, String> {
+    crate::time_tracking::with_db(app, |conn| query_priced(conn, range))
+}
+
+fn query_priced(conn: &Connection, range: &TimeRange) -> rusqlite::Result<Vec<PricedEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.task_id, e.started_at, e.ended_at, e.stop_reason, \
+                b.project_id, coalesce(b.billable, 1), r.rate_cents, r.currency, r.effective_from \
+         FROM time_entries e \
+         LEFT JOIN time_entry_billing b ON b.entry_id = e.id \
+         LEFT JOIN project_rates r ON r.id = ( \
+             SELECT id FROM project_rates \
+             WHERE project_id = b.project_id AND effective_from <= e.started_at \
+             ORDER BY effective_from DESC, id DESC LIMIT 1) \
+         WHERE e.ended_at IS NOT NULL \
+           AND (?1 IS NULL OR e.started_at >= ?1) \
+           AND (?2 IS NULL OR e.started_at < ?2) \
+           AND (?3 IS NULL OR e.task_id = ?3) \
+         ORDER BY e.started_at",
+    )?;
+    let rows = stmt.query_map(params![range.from_ms, range.to_ms, range.task_id], |row| {
+        let project_id: Option<String> = row.get(5)?;
+        let rate_cents: Option<i64> = row.get(7)?;
+        let rate = match (&project_id, rate_cents) {
+            (Some(project_id), Some(rate_cents)) => Some(ProjectRate {
+                project_id: project_id.clone(),
+                rate_cents,
+                currency: row.get(8)?,
+                effective_from_ms: row.get(9)?,
+            }),
+            _ => None,
+        };
+        Ok(PricedEntry {
+            entry: TimeEntry::from_row(row)?,
+            project_id,
+            billable: row.get(6)?,
+            rate,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Mark a finished or running entry billable or not
+#[tauri::command]
+pub fn set_entry_billable(
+    app: AppHandle,
+    entry_id: i64,
+    billable: bool,
+) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_entry_billable")?;
+    crate::entry_locks::ensure_editable(&app, entry_id)?;
+    let updated = crate::time_tracking::with_db(&app, |conn| {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM time_entries WHERE id = ?1",
+                params![entry_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if exists {
+            conn.execute(
+                "INSERT INTO time_entry_billing (entry_id, project_id, billable) VALUES (?1, NULL, ?2) \
+                 ON CONFLICT (entry_id) DO UPDATE SET billable = excluded.billable",
+                params![entry_id, billable],
+            )?;
+        }
+        Ok(exists)
+    })?;
+    if !updated {
+        return Err(format!("No time entry {}", entry_id).into());
+    }
+    Ok(())
+}
+
+/// Validate a rate added at `now`
+fn new_rate(
+    project_id: &str,
+    rate_cents: i64,
+    currency: &str,
+    effective_from_ms: Option<i64>,
+    now: i64,
+) -> Result<ProjectRate, String> {
+    let project_id = project_id.trim().to_string();
+    let currency = currency.trim().to_uppercase();
+    if project_id.is_empty() {
+        return Err("project_id must not be empty".into());
+    }
+    if rate_cents < 0 {
+        return Err("Rate must not be negative".into());
+    }
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("'{}' is not a currency code", currency));
+    }
+    let effective_from_ms = effective_from_ms.unwrap_or(now);
+    if effective_from_ms < now - RATE_SLACK_MS {
+        return Err("A rate can't take effect in the past".into());
+    }
+    Ok(ProjectRate {
+        project_id,
+        rate_cents,
+        currency,
+        effective_from_ms: effective_from_ms.max(now),
+    })
+}
+
+/// Add a rate for a project from `effective_from_ms` (default now); earlier
+/// dates are rejected so tracked time keeps the rate it was tracked at
+#[tauri::command]
+pub fn set_project_rate(
+    app: AppHandle,
+    project_id: String,
+    rate_cents: i64,
+    currency: String,
+    effective_from_ms: Option<i64>,
+) -> Result<ProjectRate, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_project_rate")?;
+    let rate = new_rate(
+        &project_id,
+        rate_cents,
+        &currency,
+        effective_from_ms,
+        now_ms() as i64,
+    )?;
+    crate::time_tracking::with_db(&app, |conn| {
+        conn.execute(
+            "INSERT INTO project_rates (project_id, rate_cents, currency, effective_from) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                rate.project_id,
+                rate.rate_cents,
+                rate.currency,
+                rate.effective_from_ms
+            ],
+        )
+    })?;
+    log::info!(
+        "Rate of project {} is {} {} from {}",
+        rate.project_id,
+        rate.rate_cents,
+        rate.currency,
+        rate.effective_from_ms
+    );
+    Ok(rate)
+}
+
+/// Rate history, optionally of one project, oldest first
+#[tauri::command]
+pub fn get_project_rates(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<Vec<ProjectRate>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_project_rates")?;
+    Ok(crate::time_tracking::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT project_id, rate_cents, currency, effective_from FROM project_rates \
+             WHERE (?1 IS NULL OR project_id = ?1) ORDER BY effective_from, id",
+        )?;
+        let rows = stmt.query_map(params![project_id], |row| {
+            Ok(ProjectRate {
+                project_id: row.get(0)?,
+                rate_cents: row.get(1)?,
+                currency: row.get(2)?,
+                effective_from_ms: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+/// Billable and non-billable time and earnings of the entries that started
+/// in a range
+#[tauri::command]
+pub fn get_earnings(app: AppHandle, range: Option<TimeRange>) -> Result<Earnings, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_earnings")?;
+    let entries = priced_entries(&app, &range.unwrap_or_default())?;
+    let mut projects: BTreeMap<(Option<String>, Option<String>), ProjectEarnings> = BTreeMap::new();
+    for priced in &entries {
+        let currency = priced.rate.as_ref().map(|r| r.currency.clone());
+        let project = projects
+            .entry((priced.project_id.clone(), currency.clone()))
+            .or_insert_with(|| ProjectEarnings {
+                project_id: priced.project_id.clone(),
+                currency,
+                ..ProjectEarnings::default()
+            });
+        let duration_ms = priced.entry.duration_ms;
+        if !priced.billable {
+            project.non_billable_ms += duration_ms;
+            continue;
+        }
+        project.billable_ms += duration_ms;
+        match priced.amount_cents(duration_ms) {
+            Some(amount) => project.amount_cents += amount,
+            None => project.unpriced_ms += duration_ms,
+        }
+    }
+    let projects: Vec<ProjectEarnings> = projects.into_values().collect();
+    Ok(Earnings {
+        billable_ms: projects.iter().map(|p| p.billable_ms).sum(),
+        non_billable_ms: projects.iter().map(|p| p.non_billable_ms).sum(),
+        projects,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600_000;
+
+    /// Project p1 at 100.00 from hour 0 and 120.00 from hour 10; one entry
+    /// before any rate, one under each rate, one unstamped, one not billable
+    fn db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(crate::time_tracking::SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO project_rates (project_id, rate_cents, currency, effective_from) VALUES
+                ('p1', 10000, 'EUR', 0),
+                ('p1', 12000, 'EUR', ?1)",
+            params![10 * HOUR],
+        )
+        .unwrap();
+        for (id, started_at, project, billable) in [
+            (1, -HOUR, Some("p1"), true),
+            (2, HOUR, Some("p1"), true),
+            (3, 10 * HOUR, Some("p1"), true),
+            (4, 11 * HOUR, None, true),
+            (5, 12 * HOUR, Some("p1"), false),
+        ] {
+            conn.execute(
+                "INSERT INTO time_entries (id, task_id, started_at, ended_at, last_seen_at) \
+                 VALUES (?1, 'task', ?2, ?3, ?3)",
+                params![id, started_at, started_at + HOUR / 2],
+            )
+            .unwrap();
+            if id != 4 {
+                stamp_entry(&conn, id, project).unwrap();
+            }
+            if !billable {
+                conn.execute(
+                    "UPDATE time_entry_billing SET billable = 0 WHERE entry_id = ?1",
+                    params![id],
+                )
+                .unwrap();
+            }
+        }
+        conn
+    }
+
+    fn rate_of(entries: &[PricedEntry], id: i64) -> Option<i64> {
+        entries
+            .iter()
+            .find(|p| p.entry.id == id)
+            .and_then(|p| p.rate.as_ref())
+            .map(|rate| rate.rate_cents)
+    }
+
+    #[test]
+    fn entries_use_the_rate_in_effect_when_they_started() {
+        let entries = query_priced(&db(), &TimeRange::default()).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(rate_of(&entries, 1), None);
+        assert_eq!(rate_of(&entries, 2), Some(10_000));
+        // A rate applies from the millisecond it takes effect
+        assert_eq!(rate_of(&entries, 3), Some(12_000));
+        assert_eq!(rate_of(&entries, 4), None);
+        assert_eq!(rate_of(&entries, 5), Some(12_000));
+        assert!(!entries[4].billable);
+        assert_eq!(entries[1].amount_cents(HOUR / 2), Some(5000));
+        assert_eq!(entries[2].amount_cents(HOUR / 2), Some(6000));
+    }
+
+    #[test]
+    fn later_rate_doesnt_reprice_tracked_time() {
+        let conn = db();
+        conn.execute(
+            "INSERT INTO project_rates (project_id, rate_cents, currency, effective_from) \
+             VALUES ('p1', 15000, 'EUR', ?1)",
+            params![20 * HOUR],
+        )
+        .unwrap();
+        let entries = query_priced(&conn, &TimeRange::default()).unwrap();
+        assert_eq!(rate_of(&entries, 2), Some(10_000));
+        assert_eq!(rate_of(&entries, 3), Some(12_000));
+
+        let range = TimeRange {
+            from_ms: Some(HOUR),
+            to_ms: Some(10 * HOUR),
+            task_id: None,
+        };
+        let entries = query_priced(&conn, &range).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entry.id, 2);
+    }
+
+    #[test]
+    fn rates_cant_start_in_the_past() {
+        let now = 100 * HOUR;
+        assert_eq!(
+            new_rate("p1", 10_000, "eur", Some(now - HOUR), now)
+                .err()
+                .as_deref(),
+            Some("A rate can't take effect in the past")
+        );
+        // Within the clock skew allowance the rate starts now
+        let rate = new_rate("p1", 10_000, "eur", Some(now - RATE_SLACK_MS), now).unwrap();
+        assert_eq!(rate.effective_from_ms, now);
+        assert_eq!(rate.currency, "EUR");
+        let rate = new_rate(" p1 ", 10_000, "EUR", None, now).unwrap();
+        assert_eq!(
+            (rate.project_id.as_str(), rate.effective_from_ms),
+            ("p1", now)
+        );
+        assert_eq!(
+            new_rate("p1", 10_000, "EUR", Some(now + HOUR), now)
+                .unwrap()
+                .effective_from_ms,
+            now + HOUR
+        );
+    }
+
+    #[test]
+    fn rates_are_validated() {
+        assert!(new_rate("", 10_000, "EUR", None, 0).is_err());
+        assert!(new_rate("p1", -1, "EUR", None, 0).is_err());
+        assert!(new_rate("p1", 10_000, "EURO", None, 0).is_err());
+        assert!(new_rate("p1", 10_000, "E1R", None, 0).is_err());
+        assert!(new_rate("p1", 0, "EUR", None, 0).is_ok());
+    }
+}
@@ -0,0 +1,475 @@
+//! Experiment branches of the task list.
+//!
+//! A branch is a copy of the signed-in user's tasks in its own Postgres
+//! schema (`branch_<user>_<name>`, holding a `tasks` table shaped like
+//! `public.tasks`), so a big reorganization can be tried out and then merged
+//! or thrown away. Hosted Supabase branching (`supabase db branch`) has no
+//! local equivalent, so branches are plain schemas on the local database.
+//!
+//! While a branch is active, the sync engine (`offline.rs`) pushes and pulls
+//! its tasks instead of `public.tasks`; custom field definitions and every
+//! other table stay shared. Switching empties the task cache, which then
+//! fills from the other side on the next sync, so edits that haven't synced
+//! yet (or are parked as conflicts) have to be settled first: queued edits
+//! belong to the branch they were made on.
+//!
+//! Merging makes the user's `public.tasks` match the branch (changed rows
+//! are written, rows missing from the branch are deleted) in one
+//! transaction, then drops the branch. Discarding drops it, along with any
+//! unsynced edits made on it.
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+use crate::offline::{tasks_table, with_db};
+
+const MAIN: &str = "public";
+/// Longest slug of a branch name in its schema name
+const MAX_SLUG: usize = 40;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Branch {
+    pub name: String,
+    pub tasks: i64,
+    pub active: bool,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeStats {
+    /// Tasks of the branch written to main
+    pub written: u64,
+    /// Tasks deleted from main because the branch no longer has them
+    pub deleted: u64,
+}
+
+/// Schema the sync engine works against: the active branch, or `public`
+pub(crate) fn active_schema(conn: &rusqlite::Connection) -> rusqlite::Result<String> {
+    Ok(conn
+        .query_row("SELECT value FROM meta WHERE key = 'branch'", [], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .unwrap_or_else(|| MAIN.to_string()))
+}
+
+/// Start of the schema names of `user`'s branches
+fn schema_prefix(user: &str) -> String {
+    let owner: String = user
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(8)
+        .collect();
+    format!("branch_{}_", owner.to_lowercase())
+}
+
+/// Schema of `user`'s branch called `name`
+fn schema_name(user: &str, name: &str) -> Result<String, String> {
+    let mut slug = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug = slug.trim_end_matches('_');
+    if slug.is_empty() {
+        return Err("Branch names need a letter or digit".to_string());
+    }
+    Ok(format!(
+        "{}{}",
+        schema_prefix(user),
+        &slug[..slug.len().min(MAX_SLUG)]
+    ))
+}
+
+/// Point the cache at `schema`, dropping what it holds from the previous
+/// side; fails while edits of `user` are still queued or parked
+fn switch_cache(
+    conn: &mut rusqlite::Connection,
+    user: &str,
+    schema: &str,
+) -> rusqlite::Result<Result<(), String>> {
+    let tx = conn.transaction()?;
+    let unsettled: i64 = tx.query_row(
+        "SELECT (SELECT count(*) FROM outbox WHERE user_id = ?1) \
+              + (SELECT count(*) FROM conflicts)",
+        params![user],
+        |row| row.get(0),
+    )?;
+    if unsettled > 0 {
+        return Ok(Err(format!(
+            "{} edits haven't synced yet; sync (and resolve conflicts) before switching branches",
+            unsettled
+        )));
+    }
+    tx.execute_batch(
+        "DELETE FROM tasks; DELETE FROM outbox_base; \
+         DELETE FROM meta WHERE key IN ('last_pulled', 'branch');",
+    )?;
+    if schema != MAIN {
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES ('branch', ?1)",
+            params![schema],
+        )?;
+    }
+    tx.commit()?;
+    Ok(Ok(()))
+}
+
+/// Drop `user`'s queued and parked edits (made on a branch being discarded)
+fn drop_edits(conn: &mut rusqlite::Connection, user: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM outbox WHERE user_id = ?1", params![user])?;
+    tx.execute_batch("DELETE FROM conflicts; DELETE FROM outbox_base;")?;
+    tx.commit()
+}
+
+fn signed_in(app: &AppHandle) -> Result<String, String> {
+    crate::offline::current_user(app)
+        .ok_or_else(|| "Not signed in; branches belong to the sync user".to_string())
+}
+
+/// Whether `schema` exists remotely
+async fn exists(client: &tokio_postgres::Client, schema: &str) -> Result<bool, String> {
+    let row = client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = $1)",
+            &[&schema],
+        )
+        .await
+        .map_err(|e| format!("Failed to look up the branch: {}", e))?;
+    Ok(row.get(0))
+}
+
+/// Sync, then switch the cache to `schema` with syncing paused
+async fn switch_to(app: &AppHandle, user: &str, schema: &str) -> Result<(), String> {
+    crate::offline::sync(app).await?;
+    {
+        let _paused = crate::offline::pause_sync(app).await;
+        with_db(app, |conn| switch_cache(conn, user, schema))??;
+    }
+    crate::search::rebuild(app)?;
+    crate::heatmap::invalidate(app);
+    crate::offline::sync_soon(app);
+    Ok(())
+}
+
+/// The signed-in user's branches
+#[tauri::command]
+pub async fn list_branches(app: AppHandle) -> Result<Vec<Branch>, FlowStateError> {
+    crate::trace::scope("list_branches", async move {
+        crate::app_lock::ensure_unlocked(&app, "list_branches")?;
+        let user = signed_in(&app)?;
+        let active = with_db(&app, |conn| active_schema(conn))?;
+        let prefix = schema_prefix(&user);
+        let client = crate::db::connect(&app).await?;
+        let schemas = client
+            .query(
+                "SELECT nspname::text, coalesce(obj_description(oid, 'pg_namespace'), nspname::text) \
+                 FROM pg_namespace WHERE starts_with(nspname, $1) ORDER BY 2",
+                &[&prefix],
+            )
+            .await
+            .map_err(|e| format!("Failed to list branches: {}", e))?;
+        let mut branches = Vec::new();
+        for row in schemas {
+            let schema: String = row.get(0);
+            let tasks: i64 = client
+                .query_one(
+                    &format!(
+                        "SELECT count(*) FROM {} WHERE user_id::text = $1",
+                        tasks_table(&schema)
+                    ),
+                    &[&user],
+                )
+                .await
+                .map_err(|e| format!("Failed to count the tasks of {}: {}", schema, e))?
+                .get(0);
+            branches.push(Branch {
+                name: row.get(1),
+                tasks,
+                active: schema == active,
+            });
+        }
+        Ok(branches)
+    })
+    .await
+}
+
+/// Copy the signed-in user's tasks on main into a new branch (not switched to)
+#[tauri::command]
+pub async fn create_branch(app: AppHandle, name: String) -> Result<Branch, FlowStateError> {
+    crate::trace::scope("create_branch", async move {
+        crate::read_only::ensure_writable(&app, "create_branch")?;
+        crate::app_lock::ensure_unlocked(&app, "create_branch")?;
+        let user = signed_in(&app)?;
+        let schema = schema_name(&user, &name)?;
+        let mut client = crate::db::connect(&app).await?;
+        if exists(&client, &schema).await? {
+            return Err(format!("A branch called \"{}\" already exists", name.trim()).into());
+        }
+
+        let table = tasks_table(&schema);
+        let tx = client
+            .transaction()
+            .await
+            .map_err(|e| format!("Failed to create the branch: {}", e))?;
+        tx.batch_execute(&format!(
+            "CREATE SCHEMA \"{}\"; \
+             CREATE TABLE {} (LIKE public.tasks INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES); \
+             COMMENT ON SCHEMA \"{}\" IS '{}'",
+            schema,
+            table,
+            schema,
+            name.trim().replace('\'', "''")
+        ))
+        .await
+        .map_err(|e| format!("Failed to create the branch: {}", e))?;
+        let tasks = tx
+            .execute(
+                &format!(
+                    "INSERT INTO {} SELECT * FROM public.tasks WHERE user_id::text = $1",
+                    table
+                ),
+                &[&user],
+            )
+            .await
+            .map_err(|e| format!("Failed to copy tasks into the branch: {}", e))?;
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to create the branch: {}", e))?;
+        log::info!("Created branch {} with {} tasks", schema, tasks);
+
+        Ok(Branch {
+            name: name.trim().to_string(),
+            tasks: tasks as i64,
+            active: false,
+        })
+    })
+    .await
+}
+
+/// Sync against the branch called `name`, or main when None. Edits that
+/// haven't synced have to be settled first.
+#[tauri::command]
+pub async fn switch_branch(app: AppHandle, name: Option<String>) -> Result<(), FlowStateError> {
+    crate::trace::scope("switch_branch", async move {
+        crate::read_only::ensure_writable(&app, "switch_branch")?;
+        crate::app_lock::ensure_unlocked(&app, "switch_branch")?;
+        let user = signed_in(&app)?;
+        let schema = match &name {
+            Some(name) => {
+                let schema = schema_name(&user, name)?;
+                let client = crate::db::connect(&app).await?;
+                if !exists(&client, &schema).await? {
+                    return Err(format!("No branch called \"{}\"", name.trim()).into());
+                }
+                schema
+            }
+            None => MAIN.to_string(),
+        };
+        if with_db(&app, |conn| active_schema(conn))? == schema {
+            return Ok(());
+        }
+        switch_to(&app, &user, &schema).await?;
+        log::info!("Switched tasks to {}", schema);
+        Ok(())
+    })
+    .await
+}
+
+/// Make the signed-in user's tasks on main match the branch called `name`,
+/// then drop the branch (switching back to main if it was active)
+#[tauri::command]
+pub async fn merge_branch(app: AppHandle, name: String) -> Result<MergeStats, FlowStateError> {
+    crate::trace::scope("merge_branch", async move {
+        crate::read_only::ensure_writable(&app, "merge_branch")?;
+        crate::app_lock::ensure_unlocked(&app, "merge_branch")?;
+        let user = signed_in(&app)?;
+        let schema = schema_name(&user, &name)?;
+        let mut client = crate::db::connect(&app).await?;
+        if !exists(&client, &schema).await? {
+            return Err(format!("No branch called \"{}\"", name.trim()).into());
+        }
+        // Pushes whatever was queued on either side first
+        switch_to(&app, &user, MAIN).await?;
+
+        let main_columns = crate::offline::task_columns(&client, MAIN).await?;
+        let mut columns: Vec<String> = crate::offline::task_columns(&client, &schema)
+            .await?
+            .into_iter()
+            .filter(|c| main_columns.contains(c))
+            .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+            .collect();
+        columns.sort();
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|c| c.as_str() != "\"id\"")
+            .map(|c| format!("{} = EXCLUDED.{}", c, c))
+            .collect();
+        let list = columns.join(", ");
+        let table = tasks_table(&schema);
+
+        let paused = crate::offline::pause_sync(&app).await;
+        let tx = client
+            .transaction()
+            .await
+            .map_err(|e| format!("Failed to start the merge: {}", e))?;
+        let written = tx
+            .execute(
+                &format!(
+                    "INSERT INTO public.tasks AS t ({}) SELECT {} FROM {} WHERE user_id::text = $1 \
+                     ON CONFLICT (id) DO UPDATE SET {} WHERE t.user_id = EXCLUDED.user_id",
+                    list,
+                    list,
+                    table,
+                    updates.join(", ")
+                ),
+                &[&user],
+            )
+            .await
+            .map_err(|e| format!("Failed to merge the branch: {}", e))?;
+        let deleted = tx
+            .execute(
+                &format!(
+                    "DELETE FROM public.tasks WHERE user_id::text = $1 \
+                     AND id NOT IN (SELECT id FROM {})",
+                    table
+                ),
+                &[&user],
+            )
+            .await
+            .map_err(|e| format!("Failed to merge the branch: {}", e))?;
+        tx.batch_execute(&format!("DROP SCHEMA \"{}\" CASCADE", schema))
+            .await
+            .map_err(|e| format!("Failed to drop the merged branch: {}", e))?;
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to merge the branch: {}", e))?;
+        drop(paused);
+
+        log::info!(
+            "Merged branch {}: {} tasks written, {} deleted",
+            schema,
+            written,
+            deleted
+        );
+        crate::offline::sync_soon(&app);
+        Ok(MergeStats { written, deleted })
+    })
+    .await
+}
+
+/// Drop the branch called `name` and any unsynced edits made on it
+/// (switching back to main if it was active)
+#[tauri::command]
+pub async fn discard_branch(app: AppHandle, name: String) -> Result<(), FlowStateError> {
+    crate::trace::scope("discard_branch", async move {
+        crate::read_only::ensure_writable(&app, "discard_branch")?;
+        crate::app_lock::ensure_unlocked(&app, "discard_branch")?;
+        let user = signed_in(&app)?;
+        let schema = schema_name(&user, &name)?;
+        let client = crate::db::connect(&app).await?;
+
+        if with_db(&app, |conn| active_schema(conn))? == schema {
+            {
+                let _paused = crate::offline::pause_sync(&app).await;
+                with_db(&app, |conn| drop_edits(conn, &user))?;
+            }
+            switch_to(&app, &user, MAIN).await?;
+        }
+        client
+            .batch_execute(&format!("DROP SCHEMA IF EXISTS \"{}\" CASCADE", schema))
+            .await
+            .map_err(|e| format!("Failed to drop the branch: {}", e))?;
+        log::info!("Discarded branch {}", schema);
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: &str = "3f2a9c1e-0000-4000-8000-000000000001";
+
+    fn cache() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tasks (id TEXT PRIMARY KEY, data TEXT NOT NULL, updated_at TEXT);
+             CREATE TABLE outbox (seq INTEGER PRIMARY KEY, task_id TEXT, user_id TEXT);
+             CREATE TABLE outbox_base (task_id TEXT PRIMARY KEY, data TEXT NOT NULL);
+             CREATE TABLE conflicts (id INTEGER PRIMARY KEY, task_id TEXT);
+             CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO tasks VALUES ('a', '{}', '2026-01-01');
+             INSERT INTO meta VALUES ('last_pulled', '2026-01-01');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn branch_names_become_schema_names() {
+        assert_eq!(
+            schema_name(USER, "Q3 reorg!").unwrap(),
+            "branch_3f2a9c1e_q3_reorg"
+        );
+        assert_eq!(
+            schema_name(USER, "  --Big  Move--  ").unwrap(),
+            "branch_3f2a9c1e_big_move"
+        );
+        assert!(schema_name(USER, " ?! ").is_err());
+        assert_eq!(
+            schema_name(USER, &"x".repeat(100)).unwrap().len(),
+            "branch_3f2a9c1e_".len() + MAX_SLUG
+        );
+    }
+
+    #[test]
+    fn switching_empties_the_cache_and_follows_the_branch() {
+        let mut conn = cache();
+        assert_eq!(active_schema(&conn).unwrap(), "public");
+
+        switch_cache(&mut conn, USER, "branch_3f2a9c1e_x")
+            .unwrap()
+            .unwrap();
+        assert_eq!(active_schema(&conn).unwrap(), "branch_3f2a9c1e_x");
+        let left: i64 = conn
+            .query_row(
+                "SELECT (SELECT count(*) FROM tasks) + (SELECT count(*) FROM meta WHERE key = 'last_pulled')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(left, 0);
+
+        switch_cache(&mut conn, USER, MAIN).unwrap().unwrap();
+        assert_eq!(active_schema(&conn).unwrap(), "public");
+    }
+
+    #[test]
+    fn unsynced_edits_block_switching_until_dropped() {
+        let mut conn = cache();
+        conn.execute(
+            "INSERT INTO outbox (task_id, user_id) VALUES ('a', ?1)",
+            params![USER],
+        )
+        .unwrap();
+        assert!(switch_cache(&mut conn, USER, "branch_3f2a9c1e_x")
+            .unwrap()
+            .is_err());
+        assert_eq!(active_schema(&conn).unwrap(), "public");
+
+        drop_edits(&mut conn, USER).unwrap();
+        switch_cache(&mut conn, USER, "branch_3f2a9c1e_x")
+            .unwrap()
+            .unwrap();
+        assert_eq!(active_schema(&conn).unwrap(), "branch_3f2a9c1e_x");
+    }
+}
@@ -0,0 +1,408 @@
+//! Bulk edits of cached tasks.
+//!
+//! `bulk_apply` selects tasks from the offline cache with a filter evaluated
+//! in SQL (tag, statuses, project, days overdue) and applies one operation to
+//! all of them, e.g. "move the tasks tagged clientx that are more than a week
+//! overdue to the backlog". A dry run only reports what would change.
+//! Otherwise the edits go through the cache and outbox like any local edit,
+//! in one transaction with a journal entry holding the previous values of the
+//! changed fields, so `undo_bulk` puts the whole batch back in one step.
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+
+pub(crate) const STATUSES: [&str; 5] = ["planned", "in_progress", "done", "backlog", "on_hold"];
+pub(crate) const PRIORITIES: [&str; 3] = ["low", "medium", "high"];
+/// Batches kept for undo
+const JOURNAL_LENGTH: i64 = 50;
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BulkFilter {
+    /// Tasks with this tag (case-insensitive)
+    pub tag: Option<String>,
+    /// Any of these statuses
+    pub status: Option<Vec<String>>,
+    pub project_id: Option<String>,
+    /// Due more than this many days ago and not done
+    pub overdue_days: Option<u32>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum BulkOperation {
+    SetStatus { status: String },
+    SetProject { project_id: Option<String> },
+    SetPriority { priority: Option<String> },
+    AddTag { tag: String },
+    RemoveTag { tag: String },
+    Delete,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkChange {
+    pub id: String,
+    pub title: String,
+    /// Previous values of the changed fields
+    pub before: Map<String, Value>,
+    /// New values of the changed fields; None when the task is deleted
+    pub after: Option<Map<String, Value>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResult {
+    /// Journal entry to pass to `undo_bulk`; None for a dry run or when
+    /// nothing changed
+    pub batch_id: Option<i64>,
+    /// Tasks the filter selected, including those already as requested
+    pub matched: usize,
+    pub changes: Vec<BulkChange>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkBatch {
+    pub id: i64,
+    pub filter: BulkFilter,
+    pub operation: BulkOperation,
+    pub tasks: i64,
+    pub applied_at_ms: i64,
+    pub undone_at_ms: Option<i64>,
+}
+
+impl BulkFilter {
+    fn normalized(mut self) -> Result<Self, String> {
+        self.tag = self
+            .tag
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty());
+        self.project_id = self.project_id.filter(|p| !p.is_empty());
+        if let Some(status) = self
+            .status
+            .iter()
+            .flatten()
+            .find(|s| !STATUSES.contains(&s.as_str()))
+        {
+            return Err(format!("Unknown status '{}'", status));
+        }
+        if self.tag.is_none()
+            && self.status.is_none()
+            && self.project_id.is_none()
+            && self.overdue_days.is_none()
+        {
+            return Err("A bulk filter needs at least one condition".to_string());
+        }
+        Ok(self)
+    }
+}
+
+impl BulkOperation {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            BulkOperation::SetStatus { status } if !STATUSES.contains(&status.as_str()) => {
+                Err(format!("Unknown status '{}'", status))
+            }
+            BulkOperation::SetPriority {
+                priority: Some(priority),
+            } if !PRIORITIES.contains(&priority.as_str()) => {
+                Err(format!("Unknown priority '{}'", priority))
+            }
+            BulkOperation::AddTag { tag } | BulkOperation::RemoveTag { tag }
+                if tag.trim().is_empty() =>
+            {
+                Err("Tag must not be empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Previous and new values of the fields this changes in `row`; None
+    /// when the row is already as requested
+    fn change(&self, row: &Map<String, Value>) -> Option<BulkChange> {
+        let id = row.get("id").and_then(Value::as_str)?.to_string();
+        let title = row
+            .get("title")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let tags: Vec<&str> = row
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let has_tag = |tag: &str| tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()));
+
+        let (key, value) = match self {
+            BulkOperation::Delete => {
+                return Some(BulkChange {
+                    id,
+                    title,
+                    before: row.clone(),
+                    after: None,
+                });
+            }
+            BulkOperation::SetStatus { status } => ("status", Value::from(status.as_str())),
+            BulkOperation::SetProject { project_id } => {
+                ("project_id", Value::from(project_id.clone()))
+            }
+            BulkOperation::SetPriority { priority } => ("priority", Value::from(priority.clone())),
+            BulkOperation::AddTag { tag } if !has_tag(tag) => {
+                let mut next: Vec<&str> = tags.clone();
+                next.push(tag.trim());
+                ("tags", Value::from(next))
+            }
+            BulkOperation::RemoveTag { tag } if has_tag(tag) => {
+                let next: Vec<&str> = tags
+                    .iter()
+                    .copied()
+                    .filter(|t| !t.eq_ignore_ascii_case(tag.trim()))
+                    .collect();
+                ("tags", Value::from(next))
+            }
+            BulkOperation::AddTag { .. } | BulkOperation::RemoveTag { .. } => return None,
+        };
+        let previous = row.get(key).cloned().unwrap_or(Value::Null);
+        if previous == value {
+            return None;
+        }
+        Some(BulkChange {
+            id,
+            title,
+            before: Map::from_iter([(key.to_string(), previous)]),
+            after: Some(Map::from_iter([(key.to_string(), value)])),
+        })
+    }
+}
+
+/// Cached rows the filter selects
+fn select(conn: &Connection, filter: &BulkFilter) -> rusqlite::Result<Vec<Map<String, Value>>> {
+    let statuses = filter
+        .status
+        .as_ref()
+        .map(|s| Value::from(s.clone()).to_string());
+    let mut stmt = conn.prepare(
+        "SELECT data FROM tasks \
+         WHERE coalesce(json_extract(data, '$.is_deleted'), 0) = 0 \
+           AND (?1 IS NULL OR EXISTS ( \
+               SELECT 1 FROM json_each(data, '$.tags') WHERE lower(value) = ?1)) \
+           AND (?2 IS NULL OR json_extract(data, '$.status') IN (SELECT value FROM json_each(?2))) \
+           AND (?3 IS NULL OR json_extract(data, '$.project_id') = ?3) \
+           AND (?4 IS NULL OR ( \
+               julianday(json_extract(data, '$.due_date')) < julianday('now') - ?4 \
+               AND coalesce(json_extract(data, '$.status'), '') <> 'done')) \
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(
+        params![filter.tag, statuses, filter.project_id, filter.overdue_days],
+        |row| row.get::<_, String>(0),
+    )?;
+    let mut selected = Vec::new();
+    for data in rows {
+        if let Ok(row) = serde_json::from_str::<Map<String, Value>>(&data?) {
+            selected.push(row);
+        }
+    }
+    Ok(selected)
+}
+
+/// Run `f` in a savepoint; the cache's own writes nest inside it
+pub(crate) fn in_batch<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    conn.execute_batch("SAVEPOINT bulk")?;
+    match f(conn) {
+        Ok(value) => {
+            conn.execute_batch("RELEASE bulk")?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(rollback) = conn.execute_batch("ROLLBACK TO bulk; RELEASE bulk") {
+                log::error!("Failed to roll back a bulk edit: {}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Write the changes and their journal entry; returns the batch id and the
+/// stored rows
+fn apply(
+    conn: &mut Connection,
+    filter: &BulkFilter,
+    operation: &BulkOperation,
+    changes: &[BulkChange],
+) -> rusqlite::Result<(i64, Vec<String>)> {
+    in_batch(conn, |conn| {
+        conn.execute(
+            "INSERT INTO bulk_batches (filter, operation, applied_at) VALUES (?1, ?2, ?3)",
+            params![
+                serde_json::to_string(filter).unwrap_or_default(),
+                serde_json::to_string(operation).unwrap_or_default(),
+                now_ms() as i64
+            ],
+        )?;
+        let batch_id = conn.last_insert_rowid();
+        let mut stored = Vec::new();
+        for change in changes {
+            conn.execute(
+                "INSERT INTO bulk_batch_tasks (batch_id, task_id, before, deleted) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    batch_id,
+                    change.id,
+                    Value::Object(change.before.clone()).to_string(),
+                    change.after.is_none()
+                ],
+            )?;
+            match &change.after {
+                Some(after) => {
+                    stored.push(crate::offline::store_task(conn, &change.id, after.clone())?)
+                }
+                None => crate::offline::remove_task(conn, &change.id)?,
+            }
+        }
+        conn.execute(
+            "DELETE FROM bulk_batch_tasks WHERE batch_id <= ?1",
+            params![batch_id - JOURNAL_LENGTH],
+        )?;
+        conn.execute(
+            "DELETE FROM bulk_batches WHERE id <= ?1",
+            params![batch_id - JOURNAL_LENGTH],
+        )?;
+        Ok((batch_id, stored))
+    })
+}
+
+/// Apply an operation to every cached task the filter selects, or with
+/// `dry_run` only report what would change
+#[tauri::command]
+pub fn bulk_apply(
+    app: AppHandle,
+    filter: BulkFilter,
+    operation: BulkOperation,
+    dry_run: bool,
+) -> Result<BulkResult, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "bulk_apply")?;
+    if !dry_run {
+        crate::read_only::ensure_writable(&app, "bulk_apply")?;
+    }
+    let filter = filter.normalized()?;
+    operation.validate()?;
+
+    let (matched, changes, applied) = crate::offline::with_db(&app, |conn| {
+        let rows = select(conn, &filter)?;
+        let changes: Vec<BulkChange> = rows
+            .iter()
+            .filter_map(|row| operation.change(row))
+            .collect();
+        let applied = if dry_run || changes.is_empty() {
+            None
+        } else {
+            Some(apply(conn, &filter, &operation, &changes)?)
+        };
+        Ok((rows.len(), changes, applied))
+    })?;
+
+    let batch_id = applied.map(|(batch_id, stored)| {
+        crate::search::upsert(&app, &stored);
+        for change in changes.iter().filter(|c| c.after.is_none()) {
+            crate::search::remove(&app, &change.id);
+        }
+        crate::offline::sync_soon(&app);
+        log::info!("Bulk edit {} changed {} tasks", batch_id, changes.len());
+        batch_id
+    });
+    Ok(BulkResult {
+        batch_id,
+        matched,
+        changes,
+    })
+}
+
+/// Put back the previous values of every task a bulk edit changed; tasks
+/// deleted since are left deleted. Returns how many tasks were restored.
+#[tauri::command]
+pub fn undo_bulk(app: AppHandle, batch_id: i64) -> Result<usize, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "undo_bulk")?;
+    crate::read_only::ensure_writable(&app, "undo_bulk")?;
+    let stored = crate::offline::with_db(&app, |conn| {
+        in_batch(conn, |conn| {
+            let marked = conn.execute(
+                "UPDATE bulk_batches SET undone_at = ?2 WHERE id = ?1 AND undone_at IS NULL",
+                params![batch_id, now_ms() as i64],
+            )?;
+            if marked == 0 {
+                return Ok(None);
+            }
+            let rows: Vec<(String, String)> = {
+                let mut stmt = conn.prepare(
+                    "SELECT b.task_id, b.before FROM bulk_batch_tasks b \
+                     WHERE b.batch_id = ?1 \
+                       AND (b.deleted OR EXISTS (SELECT 1 FROM tasks t WHERE t.id = b.task_id))",
+                )?;
+                let rows =
+                    stmt.query_map(params![batch_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<rusqlite::Result<_>>()?
+            };
+            let mut stored = Vec::with_capacity(rows.len());
+            for (task_id, before) in rows {
+                let before: Map<String, Value> = serde_json::from_str(&before).unwrap_or_default();
+                stored.push(crate::offline::store_task(conn, &task_id, before)?);
+            }
+            Ok(Some(stored))
+        })
+    })?
+    .ok_or_else(|| format!("Bulk edit {} is unknown or already undone", batch_id))?;
+
+    crate::search::upsert(&app, &stored);
+    crate::offline::sync_soon(&app);
+    log::info!("Undid bulk edit {} ({} tasks)", batch_id, stored.len());
+    Ok(stored.len())
+}
+
+/// Recent bulk edits, newest first
+#[tauri::command]
+pub fn get_bulk_batches(app: AppHandle) -> Result<Vec<BulkBatch>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_bulk_batches")?;
+    let rows = crate::offline::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, filter, operation, applied_at, undone_at, \
+                    (SELECT count(*) FROM bulk_batch_tasks WHERE batch_id = bulk_batches.id) \
+             FROM bulk_batches ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(rows
+        .into_iter()
+        .filter_map(
+            |(id, filter, operation, applied_at_ms, undone_at_ms, tasks)| {
+                Some(BulkBatch {
+                    id,
+                    filter: serde_json::from_str(&filter).ok()?,
+                    operation: serde_json::from_str(&operation).ok()?,
+                    tasks,
+                    applied_at_ms,
+                    undone_at_ms,
+                })
+            },
+        )
+        .collect())
+}
@@ -0,0 +1,745 @@
+//! CalDAV view of the task cache, served by the local API (`local_api.rs`).
+//!
+//! One calendar collection, `/caldav/tasks/`, holds every cached task as a
+//! VTODO named `<task id>.ics`. Reads come from the cache; writes go through
+//! `offline::save_task` / `offline::delete_task` like any local edit, so they
+//! are queued with their base row and reconciled by `conflicts.rs` on the
+//! next push when the task also changed elsewhere.
+//!
+//! Supported: OPTIONS, PROPFIND (principal, home and collection),
+//! REPORT calendar-query (every task; clients filter) and calendar-multiget,
+//! and GET/PUT/DELETE on resources with `If-Match` / `If-None-Match`.
+//! ETags are digests of the cached rows. There is no sync-collection
+//! REPORT; clients fall back to comparing ETags.
+//!
+//! Field mapping:
+//! - title ↔ SUMMARY, description ↔ DESCRIPTION, tags ↔ CATEGORIES
+//! - status: done ↔ COMPLETED, in_progress ↔ IN-PROCESS, CANCELLED → on_hold;
+//!   planned, backlog and on_hold go out as NEEDS-ACTION, which only moves a
+//!   done or in-progress task back to planned
+//! - priority: high ↔ 1, medium ↔ 5, low ↔ 9 (1–4, 5 and 6–9 coming in)
+//! - progress ↔ PERCENT-COMPLETE, due_date ↔ DUE, completed_at ↔ COMPLETED
+//!
+//! A PUT replaces the mapped fields, so a property the client left out is
+//! cleared. Times with a TZID, and floating times, are read in the system
+//! time zone; dates are midnight local time.
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+
+use crate::local_api::{Request, Response};
+use crate::parsers::{IcsTime, IcsTodo};
+
+const HOME: &str = "/caldav/";
+const COLLECTION: &str = "/caldav/tasks/";
+const ICS_TYPE: &str = "text/calendar; charset=utf-8";
+const XML_TYPE: &str = "application/xml; charset=utf-8";
+const ALLOW: &str = "OPTIONS, GET, PUT, DELETE, PROPFIND, REPORT";
+const UNTITLED: &str = "Untitled";
+
+/// Whether `path` belongs to the CalDAV tree
+pub(crate) fn handles(path: &str) -> bool {
+    matches!(path, "/" | "/.well-known/caldav" | "/caldav") || path.starts_with(HOME)
+}
+
+pub(crate) fn handle(app: &AppHandle, request: &Request) -> Response {
+    route(app, request).unwrap_or_else(|e| {
+        log::warn!("CalDAV {} {} failed: {}", request.method, request.path, e);
+        Response::text(500, &e)
+    })
+}
+
+fn route(app: &AppHandle, request: &Request) -> Result<Response, String> {
+    let path = request.path.as_str();
+    if path == "/.well-known/caldav" {
+        return Ok(Response::new(301).header("Location", HOME));
+    }
+    if request.method == "OPTIONS" {
+        return Ok(Response::new(200)
+            .header("Allow", ALLOW)
+            .header("DAV", "1, calendar-access"));
+    }
+    match (path, request.method.as_str()) {
+        ("/" | "/caldav" | HOME, "PROPFIND") => home(app, request),
+        ("/caldav/tasks" | COLLECTION, "PROPFIND") => collection(app, request),
+        ("/caldav/tasks" | COLLECTION, "REPORT") => report(app, request),
+        ("/" | "/caldav" | HOME | "/caldav/tasks" | COLLECTION, _) => Ok(not_allowed()),
+        _ => match path.strip_prefix(COLLECTION).and_then(task_id) {
+            Some(id) => match request.method.as_str() {
+                "GET" => get(app, &id),
+                "PUT" => put(app, request, &id),
+                "DELETE" => delete(app, request, &id),
+                "PROPFIND" => Ok(match cached(app, &id)? {
+                    Some(task) => multistatus(&[item(&task, false)]),
+                    None => Response::text(404, "No such task"),
+                }),
+                _ => Ok(not_allowed()),
+            },
+            None => Ok(Response::text(404, "Not found")),
+        },
+    }
+}
+
+fn not_allowed() -> Response {
+    Response::text(405, "Method not allowed here").header("Allow", ALLOW)
+}
+
+/// Task id of a resource name: a UUID followed by `.ics`
+fn task_id(name: &str) -> Option<String> {
+    let id = name.strip_suffix(".ics")?.to_ascii_lowercase();
+    let uuid = id.len() == 36
+        && id.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        });
+    uuid.then_some(id)
+}
+
+/// A cached task with the ETag of its row
+struct Task {
+    id: String,
+    etag: String,
+    data: Map<String, Value>,
+}
+
+impl Task {
+    fn new(id: String, raw: &str) -> Option<Self> {
+        let data: Map<String, Value> = serde_json::from_str(raw).ok()?;
+        if data.get("is_deleted").and_then(Value::as_bool) == Some(true) {
+            return None;
+        }
+        let digest = Sha256::digest(raw.as_bytes());
+        let etag = format!(
+            "\"{}\"",
+            digest[..16]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+        Some(Task { id, etag, data })
+    }
+
+    fn href(&self) -> String {
+        format!("{}{}.ics", COLLECTION, self.id)
+    }
+}
+
+fn cached(app: &AppHandle, id: &str) -> Result<Option<Task>, String> {
+    use rusqlite::OptionalExtension;
+
+    let raw: Option<String> = crate::offline::with_db(app, |conn| {
+        conn.query_row("SELECT data FROM tasks WHERE id = ?1", [id], |row| {
+            row.get(0)
+        })
+        .optional()
+    })?;
+    Ok(raw.and_then(|raw| Task::new(id.to_string(), &raw)))
+}
+
+fn all_tasks(app: &AppHandle) -> Result<Vec<Task>, String> {
+    let rows = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT id, data FROM tasks ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, raw)| Task::new(id, &raw))
+        .collect())
+}
+
+/// Collection tag: changes whenever any task does
+fn ctag(tasks: &[Task]) -> String {
+    let mut hasher = Sha256::new();
+    for task in tasks {
+        hasher.update(task.etag.as_bytes());
+    }
+    let digest = hasher.finalize();
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        xml_escape(href),
+        props
+    )
+}
+
+fn multistatus(responses: &[String]) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+         xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+    Response::new(207).body(XML_TYPE, body)
+}
+
+/// Depth 0 unless the client asks for more
+fn deep(request: &Request) -> bool {
+    request.header("Depth").is_some_and(|depth| depth != "0")
+}
+
+/// Principal and calendar home are the same resource
+fn home(app: &AppHandle, request: &Request) -> Result<Response, String> {
+    let mut responses = vec![response(
+        &request.path,
+        &format!(
+            "<d:resourcetype><d:collection/><d:principal/></d:resourcetype>\
+             <d:displayname>FlowState</d:displayname>\
+             <d:current-user-principal><d:href>{home}</d:href></d:current-user-principal>\
+             <d:principal-URL><d:href>{home}</d:href></d:principal-URL>\
+             <c:calendar-home-set><d:href>{home}</d:href></c:calendar-home-set>",
+            home = HOME
+        ),
+    )];
+    if deep(request) {
+        responses.push(collection_response(app, &all_tasks(app)?));
+    }
+    Ok(multistatus(&responses))
+}
+
+fn collection_response(app: &AppHandle, tasks: &[Task]) -> String {
+    let write = if crate::read_only::ensure_writable(app, "caldav").is_ok() {
+        "<d:privilege><d:write/></d:privilege>"
+    } else {
+        ""
+    };
+    let ctag = ctag(tasks);
+    response(
+        COLLECTION,
+        &format!(
+            "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+             <d:displayname>FlowState tasks</d:displayname>\
+             <c:supported-calendar-component-set><c:comp name=\"VTODO\"/>\
+             </c:supported-calendar-component-set>\
+             <d:supported-report-set>\
+             <d:supported-report><d:report><c:calendar-query/></d:report></d:supported-report>\
+             <d:supported-report><d:report><c:calendar-multiget/></d:report></d:supported-report>\
+             </d:supported-report-set>\
+             <d:current-user-privilege-set><d:privilege><d:read/></d:privilege>{}\
+             </d:current-user-privilege-set>\
+             <cs:getctag>{}</cs:getctag><d:getetag>\"{}\"</d:getetag>",
+            write, ctag, ctag
+        ),
+    )
+}
+
+fn item(task: &Task, with_data: bool) -> String {
+    let data = if with_data {
+        format!(
+            "<c:calendar-data>{}</c:calendar-data>",
+            xml_escape(&to_ics(&task.id, &task.data))
+        )
+    } else {
+        String::new()
+    };
+    response(
+        &task.href(),
+        &format!(
+            "<d:resourcetype/><d:getetag>{}</d:getetag>\
+             <d:getcontenttype>{}; component=VTODO</d:getcontenttype>{}",
+            xml_escape(&task.etag),
+            ICS_TYPE,
+            data
+        ),
+    )
+}
+
+fn collection(app: &AppHandle, request: &Request) -> Result<Response, String> {
+    let tasks = all_tasks(app)?;
+    let mut responses = vec![collection_response(app, &tasks)];
+    if deep(request) {
+        responses.extend(tasks.iter().map(|task| item(task, false)));
+    }
+    Ok(multistatus(&responses))
+}
+
+/// Hrefs of a calendar-multiget; None for a calendar-query
+fn multiget_hrefs(body: &[u8]) -> Result<Option<Vec<String>>, String> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_reader(body);
+    reader.config_mut().trim_text(true);
+    let mut root: Option<Vec<u8>> = None;
+    let mut in_href = false;
+    let mut hrefs = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Invalid REPORT body: {}", e))?
+        {
+            Event::Start(element) | Event::Empty(element) if root.is_none() => {
+                root = Some(element.local_name().as_ref().to_vec());
+            }
+            Event::Start(element) => in_href = element.local_name().as_ref() == b"href",
+            Event::End(_) => in_href = false,
+            Event::Text(text) if in_href => {
+                let href = text
+                    .unescape()
+                    .map_err(|e| format!("Invalid REPORT body: {}", e))?;
+                hrefs.push(href.into_owned());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    match root.as_deref() {
+        Some(b"calendar-multiget") => Ok(Some(hrefs)),
+        Some(b"calendar-query") => Ok(None),
+        _ => Err("Only calendar-query and calendar-multiget reports are supported".to_string()),
+    }
+}
+
+/// Path of an href, which may be a full URL
+fn href_path(href: &str) -> String {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => href,
+    };
+    crate::local_api::decode_percent(path)
+}
+
+fn report(app: &AppHandle, request: &Request) -> Result<Response, String> {
+    let hrefs = match multiget_hrefs(&request.body) {
+        Ok(hrefs) => hrefs,
+        Err(e) => return Ok(Response::text(501, &e)),
+    };
+    let responses = match hrefs {
+        None => all_tasks(app)?
+            .iter()
+            .map(|task| item(task, true))
+            .collect(),
+        Some(hrefs) => {
+            let mut responses = Vec::new();
+            for href in hrefs {
+                let path = href_path(&href);
+                let task = match path.strip_prefix(COLLECTION).and_then(task_id) {
+                    Some(id) => cached(app, &id)?,
+                    None => None,
+                };
+                responses.push(match task {
+                    Some(task) => item(&task, true),
+                    None => format!(
+                        "<d:response><d:href>{}</d:href>\
+                         <d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+                        xml_escape(&href)
+                    ),
+                });
+            }
+            responses
+        }
+    };
+    Ok(multistatus(&responses))
+}
+
+fn get(app: &AppHandle, id: &str) -> Result<Response, String> {
+    Ok(match cached(app, id)? {
+        Some(task) => Response::new(200)
+            .header("ETag", task.etag.clone())
+            .body(ICS_TYPE, to_ics(&task.id, &task.data)),
+        None => Response::text(404, "No such task"),
+    })
+}
+
+/// 412 unless `If-Match` / `If-None-Match` agree with the current ETag
+fn precondition(request: &Request, current: Option<&Task>) -> Option<Response> {
+    let etag = current.map(|task| task.etag.as_str());
+    let matches = |header: &str| {
+        header
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || Some(tag.trim_start_matches("W/")) == etag)
+    };
+    let failed = match (request.header("If-Match"), request.header("If-None-Match")) {
+        (Some(header), _) if etag.is_none() || !matches(header) => true,
+        (_, Some(header)) if etag.is_some() && matches(header) => true,
+        _ => false,
+    };
+    failed.then(|| Response::text(412, "The task changed; fetch it again"))
+}
+
+fn refuse_writes(app: &AppHandle) -> Option<Response> {
+    crate::read_only::ensure_writable(app, "caldav")
+        .err()
+        .map(|_| Response::text(403, "FlowState is in read-only mode"))
+}
+
+fn put(app: &AppHandle, request: &Request, id: &str) -> Result<Response, String> {
+    if let Some(refused) = refuse_writes(app) {
+        return Ok(refused);
+    }
+    let current = cached(app, id)?;
+    if let Some(failed) = precondition(request, current.as_ref()) {
+        return Ok(failed);
+    }
+    let todo = match crate::parsers::parse_ics_todos(&request.body) {
+        Ok(mut todos) if todos.len() == 1 => todos.remove(0),
+        Ok(_) => return Ok(Response::text(415, "Send exactly one VTODO")),
+        Err(e) => return Ok(Response::text(400, &e.to_string())),
+    };
+    let mut task = from_todo(&todo, current.as_ref().map(|task| &task.data));
+    task.insert("id".to_string(), Value::String(id.to_string()));
+    crate::offline::save_task(app, task)?;
+
+    let stored = cached(app, id)?.ok_or_else(|| "Task was not saved".to_string())?;
+    Ok(Response::new(if current.is_some() { 204 } else { 201 }).header("ETag", stored.etag))
+}
+
+fn delete(app: &AppHandle, request: &Request, id: &str) -> Result<Response, String> {
+    if let Some(refused) = refuse_writes(app) {
+        return Ok(refused);
+    }
+    let current = cached(app, id)?;
+    if current.is_none() {
+        return Ok(Response::text(404, "No such task"));
+    }
+    if let Some(failed) = precondition(request, current.as_ref()) {
+        return Ok(failed);
+    }
+    crate::offline::delete_task(app, id)?;
+    Ok(Response::new(204))
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z"))
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|time| time.and_utc())
+        })
+}
+
+fn ics_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn from_ics_time(time: &IcsTime) -> Option<DateTime<Utc>> {
+    let local = |naive: NaiveDateTime| {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    };
+    match time {
+        IcsTime::Utc(naive) => Some(naive.and_utc()),
+        IcsTime::Local(naive, _) => local(*naive),
+        IcsTime::Date(date) => local(date.and_hms_opt(0, 0, 0)?),
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Content line folded at 75 octets
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn text_field<'a>(task: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    task.get(key)
+        .and_then(Value::as_str)
+        .filter(|text| !text.is_empty())
+}
+
+fn to_ics(id: &str, task: &Map<String, Value>) -> String {
+    let mut lines = vec![format!("UID:{}", id)];
+    let updated = text_field(task, "updated_at").and_then(parse_time);
+    lines.push(format!(
+        "DTSTAMP:{}",
+        ics_utc(updated.unwrap_or_else(Utc::now))
+    ));
+    if let Some(updated) = updated {
+        lines.push(format!("LAST-MODIFIED:{}", ics_utc(updated)));
+    }
+    lines.push(format!(
+        "SUMMARY:{}",
+        escape_text(text_field(task, "title").unwrap_or(UNTITLED))
+    ));
+    if let Some(description) = text_field(task, "description") {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    let status = match text_field(task, "status") {
+        Some("done") => "COMPLETED",
+        Some("in_progress") => "IN-PROCESS",
+        _ => "NEEDS-ACTION",
+    };
+    lines.push(format!("STATUS:{}", status));
+    let priority = match text_field(task, "priority") {
+        Some("high") => Some(1),
+        Some("medium") => Some(5),
+        Some("low") => Some(9),
+        _ => None,
+    };
+    if let Some(priority) = priority {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if let Some(progress) = task.get("progress").and_then(Value::as_u64) {
+        lines.push(format!("PERCENT-COMPLETE:{}", progress.min(100)));
+    }
+    if let Some(due) = text_field(task, "due_date").and_then(parse_time) {
+        lines.push(format!("DUE:{}", ics_utc(due)));
+    }
+    if let Some(completed) = text_field(task, "completed_at").and_then(parse_time) {
+        lines.push(format!("COMPLETED:{}", ics_utc(completed)));
+    }
+    let tags: Vec<String> = task
+        .get("tags")
+        .and_then(Value::as_array)
+        .map(|tags| {
+            tags.iter()
+                .filter_map(Value::as_str)
+                .map(escape_text)
+                .collect()
+        })
+        .unwrap_or_default();
+    if !tags.is_empty() {
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//FlowState//Tasks//EN",
+        "BEGIN:VTODO",
+    ] {
+        push_line(&mut out, line);
+    }
+    for line in &lines {
+        push_line(&mut out, line);
+    }
+    push_line(&mut out, "END:VTODO");
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Task fields of a VTODO; `current` is the cached row it replaces
+fn from_todo(todo: &IcsTodo, current: Option<&Map<String, Value>>) -> Map<String, Value> {
+    let current_status = current.and_then(|task| text_field(task, "status"));
+    let status = match todo.status.as_deref() {
+        Some("COMPLETED") => "done",
+        Some("IN-PROCESS") => "in_progress",
+        Some("CANCELLED") => "on_hold",
+        _ => match current_status {
+            None | Some("done") | Some("in_progress") => "planned",
+            Some(other) => other,
+        },
+    };
+    let priority = match todo.priority {
+        Some(1..=4) => Value::from("high"),
+        Some(5) => Value::from("medium"),
+        Some(6..=9) => Value::from("low"),
+        _ => Value::Null,
+    };
+    let time = |time: &Option<IcsTime>| {
+        time.as_ref()
+            .and_then(from_ics_time)
+            .map_or(Value::Null, |time| Value::String(time.to_rfc3339()))
+    };
+    let done = status == "done";
+    let completed_at = match time(&todo.completed) {
+        Value::Null if done => current
+            .and_then(|task| task.get("completed_at"))
+            .filter(|value| !value.is_null())
+            .cloned()
+            .unwrap_or_else(|| Value::String(Utc::now().to_rfc3339())),
+        _ if !done => Value::Null,
+        completed => completed,
+    };
+    let progress = todo
+        .percent_complete
+        .map(|percent| percent.min(100))
+        .unwrap_or(if done { 100 } else { 0 });
+    let title = todo
+        .summary
+        .clone()
+        .filter(|summary| !summary.trim().is_empty())
+        .or_else(|| {
+            current
+                .and_then(|task| text_field(task, "title"))
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| UNTITLED.to_string());
+
+    let mut task = Map::new();
+    task.insert("title".to_string(), Value::String(title));
+    task.insert(
+        "description".to_string(),
+        todo.description.clone().map_or(Value::Null, Value::String),
+    );
+    task.insert("status".to_string(), Value::from(status));
+    task.insert("priority".to_string(), priority);
+    task.insert("progress".to_string(), Value::from(progress));
+    task.insert("due_date".to_string(), time(&todo.due));
+    task.insert("completed_at".to_string(), completed_at);
+    task.insert(
+        "tags".to_string(),
+        Value::Array(todo.categories.iter().cloned().map(Value::String).collect()),
+    );
+    task
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "PUT".to_string(),
+            path: format!("{}x.ics", COLLECTION),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn names_resources_by_task_id() {
+        assert_eq!(
+            task_id("6F1C2B3A-0D4E-4F5A-8B6C-7D8E9F0A1B2C.ics").as_deref(),
+            Some("6f1c2b3a-0d4e-4f5a-8b6c-7d8e9f0a1b2c")
+        );
+        assert_eq!(task_id("6f1c2b3a-0d4e-4f5a-8b6c-7d8e9f0a1b2c"), None);
+        assert_eq!(task_id("not-a-uuid.ics"), None);
+        assert_eq!(task_id("../x.ics"), None);
+    }
+
+    #[test]
+    fn round_trips_a_task_through_a_vtodo() {
+        let task = object(json!({
+            "title": "Plan, review; ship",
+            "description": "Line one\nline two",
+            "status": "in_progress",
+            "priority": "high",
+            "progress": 40,
+            "due_date": "2026-03-29T09:30:00+00:00",
+            "tags": ["work", "q4, planning"],
+            "updated_at": "2026-03-01T08:00:00.123+00:00",
+        }));
+        let ics = to_ics("6f1c2b3a-0d4e-4f5a-8b6c-7d8e9f0a1b2c", &task);
+        assert!(ics.contains("SUMMARY:Plan\\, review\\; ship\r\n"));
+        assert!(ics.contains("DUE:20260329T093000Z\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+
+        let todo = crate::parsers::parse_ics_todos(ics.as_bytes())
+            .unwrap()
+            .remove(0);
+        let back = from_todo(&todo, Some(&task));
+        for key in [
+            "title",
+            "description",
+            "status",
+            "priority",
+            "progress",
+            "tags",
+        ] {
+            assert_eq!(back[key], task[key], "{}", key);
+        }
+        assert_eq!(
+            parse_time(back["due_date"].as_str().unwrap()),
+            parse_time("2026-03-29T09:30:00Z")
+        );
+        assert_eq!(back["completed_at"], Value::Null);
+    }
+
+    #[test]
+    fn maps_statuses_both_ways() {
+        let todo = |status: &str| IcsTodo {
+            status: Some(status.to_string()),
+            ..IcsTodo::default()
+        };
+        let on_hold = object(json!({"status": "on_hold", "title": "Kept"}));
+        let done = object(json!({"status": "done", "completed_at": "2026-01-02T00:00:00Z"}));
+
+        // NEEDS-ACTION keeps statuses CalDAV can't express
+        let back = from_todo(&todo("NEEDS-ACTION"), Some(&on_hold));
+        assert_eq!(back["status"], "on_hold");
+        assert_eq!(back["title"], "Kept");
+        assert_eq!(
+            from_todo(&todo("NEEDS-ACTION"), Some(&done))["status"],
+            "planned"
+        );
+        assert_eq!(from_todo(&todo("NEEDS-ACTION"), None)["status"], "planned");
+        assert_eq!(from_todo(&todo("CANCELLED"), None)["status"], "on_hold");
+
+        // Completing keeps an existing completion time, or stamps one
+        let completed = from_todo(&todo("COMPLETED"), Some(&done));
+        assert_eq!(completed["completed_at"], "2026-01-02T00:00:00Z");
+        assert_eq!(completed["progress"], 100);
+        assert!(from_todo(&todo("COMPLETED"), None)["completed_at"].is_string());
+        assert!(to_ics("x", &on_hold).contains("STATUS:NEEDS-ACTION"));
+    }
+
+    #[test]
+    fn checks_preconditions_against_the_etag() {
+        let task = Task::new("x".to_string(), "{\"title\":\"a\"}").unwrap();
+        let etag = task.etag.clone();
+        let status = |headers: &[(&str, &str)], current: Option<&Task>| {
+            precondition(&request(headers), current).map(|r| r.status)
+        };
+        assert_eq!(status(&[], Some(&task)), None);
+        assert_eq!(status(&[("If-Match", &etag)], Some(&task)), None);
+        assert_eq!(status(&[("If-Match", "\"stale\"")], Some(&task)), Some(412));
+        assert_eq!(status(&[("If-Match", "*")], None), Some(412));
+        assert_eq!(status(&[("If-None-Match", "*")], Some(&task)), Some(412));
+        assert_eq!(status(&[("If-None-Match", "*")], None), None);
+    }
+
+    #[test]
+    fn reads_report_bodies() {
+        let multiget = br#"<?xml version="1.0"?>
+            <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+              <d:prop><d:getetag/><c:calendar-data/></d:prop>
+              <d:href>/caldav/tasks/a.ics</d:href>
+              <d:href>http://127.0.0.1:47315/caldav/tasks/b%20c.ics</d:href>
+            </c:calendar-multiget>"#;
+        let hrefs = multiget_hrefs(multiget).unwrap().unwrap();
+        assert_eq!(
+            hrefs.iter().map(|h| href_path(h)).collect::<Vec<_>>(),
+            ["/caldav/tasks/a.ics", "/caldav/tasks/b c.ics"]
+        );
+        let query = br#"<c:calendar-query xmlns:c="urn:ietf:params:xml:ns:caldav"/>"#;
+        assert_eq!(multiget_hrefs(query).unwrap(), None);
+        assert!(multiget_hrefs(b"<d:sync-collection xmlns:d=\"DAV:\"/>").is_err());
+    }
+}
@@ -0,0 +1,619 @@
+//! Subscribed external calendars (ICS URLs) for availability.
+//!
+//! Feeds are fetched by the backend every `REFRESH_INTERVAL` with
+//! `If-None-Match`/`If-Modified-Since`, so unchanged calendars cost a 304.
+//! The last body of each feed is kept in `calendars/<id>.ics` in the app data
+//! directory and parsed on startup, so busy times are known offline.
+//! `get_busy_blocks` expands events (including DAILY/WEEKLY/MONTHLY/YEARLY
+//! RRULEs with INTERVAL, COUNT, UNTIL, BYDAY and EXDATE) into time blocks for
+//! the scheduler and focus-block suggestions; transparent and cancelled
+//! events don't block time. Times with a TZID are read as local time.
+//! Subscriptions persist in `calendars.json`; each refresh publishes
+//! `calendar://updated`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, Months, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::parsers::{self, IcsEvent, IcsTime};
+
+pub(crate) const CALENDAR_STORE: &str = "calendars.json";
+const CALENDAR_KEY: &str = "subscriptions";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Recurrence periods looked at per event before giving up
+const MAX_PERIODS: u32 = 20_000;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarSubscription {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_fetched_at_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub event_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusyBlock {
+    pub calendar_id: String,
+    pub uid: Option<String>,
+    pub title: Option<String>,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub all_day: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarUpdated {
+    pub id: String,
+    /// False when the server answered 304 or the fetch failed
+    pub changed: bool,
+    pub event_count: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CalendarFeeds {
+    subscriptions: Mutex<Vec<CalendarSubscription>>,
+    events: Mutex<HashMap<String, Vec<IcsEvent>>>,
+}
+
+enum Fetched {
+    Unchanged,
+    Updated {
+        body: Vec<u8>,
+        events: Vec<IcsEvent>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn cache_path(app: &AppHandle, id: &str) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("calendars").join(format!("{}.ics", id)))
+}
+
+/// webcal:// is https:// for fetching purposes
+fn normalize_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    let url = match url.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    };
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Unsupported URL scheme: {}", parsed.scheme()));
+    }
+    Ok(url)
+}
+
+async fn fetch(sub: &CalendarSubscription) -> Result<Fetched, String> {
+    let mut request = client()?.get(&sub.url);
+    if let Some(etag) = &sub.etag {
+        request = request.header("If-None-Match", etag);
+    }
+    if let Some(modified) = &sub.last_modified {
+        request = request.header("If-Modified-Since", modified);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch calendar: {}", e))?;
+    if response.status().as_u16() == 304 {
+        return Ok(Fetched::Unchanged);
+    }
+    if !response.status().is_success() {
+        return Err(format!(
+            "Calendar fetch failed: HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header("etag");
+    let last_modified = header("last-modified");
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read calendar: {}", e))?
+        .to_vec();
+    let events =
+        parsers::parse_ics_events(&body).map_err(|e| format!("Invalid calendar: {}", e))?;
+
+    Ok(Fetched::Updated {
+        body,
+        events,
+        etag,
+        last_modified,
+    })
+}
+
+fn save_subscriptions(app: &AppHandle) -> Result<(), String> {
+    let subs = app
+        .state::<CalendarFeeds>()
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let store = app
+        .store(CALENDAR_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", CALENDAR_STORE, e))?;
+    store.set(
+        CALENDAR_KEY,
+        serde_json::to_value(&subs).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", CALENDAR_STORE, e))
+}
+
+/// Fetch one subscription and apply the result to the state
+async fn refresh_one(app: &AppHandle, mut sub: CalendarSubscription) -> CalendarUpdated {
+    let result = fetch(&sub).await;
+    let feeds = app.state::<CalendarFeeds>();
+    let mut changed = false;
+
+    match result {
+        Ok(Fetched::Unchanged) => sub.last_error = None,
+        Ok(Fetched::Updated {
+            body,
+            events,
+            etag,
+            last_modified,
+        }) => {
+            if let Some(path) = cache_path(app, &sub.id) {
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(&path, &body));
+                if let Err(e) = written {
+                    log::warn!("Failed to cache calendar {}: {}", sub.id, e);
+                }
+            }
+            sub.etag = etag;
+            sub.last_modified = last_modified;
+            sub.event_count = events.len();
+            sub.last_error = None;
+            feeds
+                .events
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(sub.id.clone(), events);
+            changed = true;
+        }
+        Err(e) => {
+            log::warn!("Calendar {} not refreshed: {}", sub.name, e);
+            sub.last_error = Some(e);
+        }
+    }
+    sub.last_fetched_at_ms = Some(crate::events::now_ms());
+
+    let update = CalendarUpdated {
+        id: sub.id.clone(),
+        changed,
+        event_count: sub.event_count,
+        error: sub.last_error.clone(),
+    };
+    // It may have been removed while fetching
+    if let Some(slot) = feeds
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter_mut()
+        .find(|s| s.id == sub.id)
+    {
+        *slot = sub;
+    }
+    update
+}
+
+async fn refresh_all(app: &AppHandle) -> Vec<CalendarUpdated> {
+    let subs = app
+        .state::<CalendarFeeds>()
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let mut updates = Vec::new();
+    for sub in subs {
+        let update = refresh_one(app, sub).await;
+        crate::events::publish(app, "calendar://updated", &update);
+        updates.push(update);
+    }
+    if !updates.is_empty() {
+        if let Err(e) = save_subscriptions(app) {
+            log::warn!("{}", e);
+        }
+    }
+    updates
+}
+
+/// Load subscriptions and cached feeds
+pub fn init(app: &AppHandle) {
+    let subs: Vec<CalendarSubscription> = app
+        .store(CALENDAR_STORE)
+        .ok()
+        .and_then(|store| store.get(CALENDAR_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default();
+
+    let feeds = app.state::<CalendarFeeds>();
+    {
+        let mut events = feeds.events.lock().unwrap_or_else(|e| e.into_inner());
+        for sub in &subs {
+            let cached = cache_path(app, &sub.id).and_then(|path| std::fs::read(path).ok());
+            if let Some(parsed) = cached.and_then(|body| parsers::parse_ics_events(&body).ok()) {
+                events.insert(sub.id.clone(), parsed);
+            }
+        }
+    }
+    *feeds
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = subs;
+}
+
+/// Refresh the feeds periodically (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh_all(&app).await;
+            crate::clock::sleep(REFRESH_INTERVAL).await;
+        }
+    })
+}
+
+/// Wall-clock start of an ICS time in local time, and whether it is all-day
+fn local_naive(time: &IcsTime) -> (NaiveDateTime, bool) {
+    match time {
+        IcsTime::Date(date) => (date.and_hms_opt(0, 0, 0).unwrap_or_default(), true),
+        IcsTime::Utc(utc) => (
+            Utc.from_utc_datetime(utc)
+                .with_timezone(&Local)
+                .naive_local(),
+            false,
+        ),
+        IcsTime::Local(time, _) => (*time, false),
+    }
+}
+
+fn local_ms(time: NaiveDateTime) -> Option<i64> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+}
+
+struct Rule {
+    freq: String,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDateTime>,
+    by_day: Vec<Weekday>,
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+    match code.to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_rule(rrule: &str) -> Option<Rule> {
+    let mut rule = Rule {
+        freq: String::new(),
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+    };
+    for part in rrule.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => rule.freq = value.to_ascii_uppercase(),
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|i| *i > 0)?,
+            "COUNT" => rule.count = value.parse().ok(),
+            "UNTIL" => {
+                let time = if value.len() == 8 {
+                    IcsTime::Date(chrono::NaiveDate::parse_from_str(value, "%Y%m%d").ok()?)
+                } else {
+                    let utc = value.strip_suffix('Z').unwrap_or(value);
+                    IcsTime::Utc(NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?)
+                };
+                let (until, all_day) = local_naive(&time);
+                // A date-only UNTIL includes that whole day
+                rule.until = Some(if all_day {
+                    until + chrono::Duration::days(1)
+                } else {
+                    until
+                });
+            }
+            "BYDAY" => {
+                rule.by_day = value
+                    .split(',')
+                    // Ordinals like "1MO" only make sense for MONTHLY; the weekday is kept
+                    .filter_map(|d| {
+                        weekday(d.trim_start_matches(|c: char| {
+                            c == '-' || c == '+' || c.is_ascii_digit()
+                        }))
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+    (!rule.freq.is_empty()).then_some(rule)
+}
+
+/// Occurrence starts of `event` (local wall time) overlapping [from, to)
+fn occurrences(
+    event: &IcsEvent,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Vec<(NaiveDateTime, NaiveDateTime, bool)> {
+    let Some(start) = &event.start else {
+        return Vec::new();
+    };
+    let (start, all_day) = local_naive(start);
+    let duration = match (&event.end, event.duration_secs) {
+        (Some(end), _) => local_naive(end).0 - start,
+        (None, Some(secs)) => chrono::Duration::seconds(secs),
+        (None, None) if all_day => chrono::Duration::days(1),
+        (None, None) => chrono::Duration::zero(),
+    };
+    let excluded: Vec<NaiveDateTime> = event.exdates.iter().map(|t| local_naive(t).0).collect();
+    let overlaps =
+        |s: NaiveDateTime| s < to && (s + duration > from || (duration.is_zero() && s >= from));
+
+    let Some(rule) = event.rrule.as_deref().and_then(parse_rule) else {
+        return if overlaps(start) {
+            vec![(start, start + duration, all_day)]
+        } else {
+            Vec::new()
+        };
+    };
+
+    let mut out = Vec::new();
+    let mut emitted = 0u32;
+    let week_start = start - chrono::Duration::days(start.weekday().num_days_from_monday() as i64);
+
+    'periods: for k in 0..MAX_PERIODS {
+        let step = k * rule.interval;
+        let candidates: Vec<NaiveDateTime> = match rule.freq.as_str() {
+            "DAILY" => vec![start + chrono::Duration::days(step as i64)],
+            "WEEKLY" if !rule.by_day.is_empty() => {
+                let week = week_start + chrono::Duration::weeks(step as i64);
+                let mut days: Vec<NaiveDateTime> = rule
+                    .by_day
+                    .iter()
+                    .map(|d| week + chrono::Duration::days(d.num_days_from_monday() as i64))
+                    .filter(|d| *d >= start)
+                    .collect();
+                days.sort();
+                days
+            }
+            "WEEKLY" => vec![start + chrono::Duration::weeks(step as i64)],
+            "MONTHLY" | "YEARLY" => {
+                let months = if rule.freq == "MONTHLY" {
+                    step
+                } else {
+                    step * 12
+                };
+                // Months without that day (e.g. the 31st) are skipped
+                start
+                    .checked_add_months(Months::new(months))
+                    .filter(|d| d.day() == start.day())
+                    .into_iter()
+                    .collect()
+            }
+            _ => vec![start],
+        };
+
+        for candidate in candidates {
+            if candidate >= to || rule.until.is_some_and(|u| candidate > u) {
+                break 'periods;
+            }
+            if rule.count.is_some_and(|c| emitted >= c) {
+                break 'periods;
+            }
+            emitted += 1;
+            if !excluded.contains(&candidate) && overlaps(candidate) {
+                out.push((candidate, candidate + duration, all_day));
+            }
+        }
+        if !matches!(
+            rule.freq.as_str(),
+            "DAILY" | "WEEKLY" | "MONTHLY" | "YEARLY"
+        ) {
+            break;
+        }
+    }
+    out
+}
+
+#[tauri::command]
+pub fn list_calendar_subscriptions(
+    feeds: tauri::State<'_, CalendarFeeds>,
+) -> Vec<CalendarSubscription> {
+    feeds
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Subscribe to an ICS (or webcal://) URL; it is fetched once before being added
+#[tauri::command]
+pub async fn add_calendar_subscription(
+    app: AppHandle,
+    url: String,
+    name: Option<String>,
+) -> Result<CalendarSubscription, FlowStateError> {
+    crate::trace::scope("add_calendar_subscription", async move {
+        crate::read_only::ensure_writable(&app, "add_calendar_subscription")?;
+        let url = normalize_url(&url)?;
+        let bytes: [u8; 8] = rand::random();
+        let mut sub = CalendarSubscription {
+            id: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            name: name
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| url.clone()),
+            url,
+            etag: None,
+            last_modified: None,
+            last_fetched_at_ms: None,
+            last_error: None,
+            event_count: 0,
+        };
+
+        app.state::<CalendarFeeds>()
+            .subscriptions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sub.clone());
+        let update = refresh_one(&app, sub.clone()).await;
+        if let Some(error) = update.error {
+            remove(&app, &sub.id);
+            return Err(error.into());
+        }
+
+        save_subscriptions(&app)?;
+        crate::events::publish(&app, "calendar://updated", &update);
+        sub.event_count = update.event_count;
+        log::info!(
+            "Subscribed to calendar {} ({} events)",
+            sub.name,
+            sub.event_count
+        );
+        Ok(list_calendar_subscriptions(app.state())
+            .into_iter()
+            .find(|s| s.id == sub.id)
+            .unwrap_or(sub))
+    })
+    .await
+}
+
+fn remove(app: &AppHandle, id: &str) -> bool {
+    let feeds = app.state::<CalendarFeeds>();
+    let mut subs = feeds
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let before = subs.len();
+    subs.retain(|s| s.id != id);
+    feeds
+        .events
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(id);
+    if let Some(path) = cache_path(app, id) {
+        let _ = std::fs::remove_file(path);
+    }
+    subs.len() != before
+}
+
+#[tauri::command]
+pub fn remove_calendar_subscription(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "remove_calendar_subscription")?;
+    if !remove(&app, &id) {
+        return Err(format!("Unknown calendar: {}", id).into());
+    }
+    save_subscriptions(&app)?;
+    Ok(())
+}
+
+/// Re-fetch every subscription now (conditional requests)
+#[tauri::command]
+pub async fn refresh_calendars(app: AppHandle) -> Result<Vec<CalendarUpdated>, FlowStateError> {
+    crate::trace::scope(
+        "refresh_calendars",
+        async move { Ok(refresh_all(&app).await) },
+    )
+    .await
+}
+
+/// Busy time from all subscribed calendars between two instants (ms since epoch)
+#[tauri::command]
+pub fn get_busy_blocks(
+    app: AppHandle,
+    from_ms: i64,
+    to_ms: i64,
+) -> Result<Vec<BusyBlock>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_busy_blocks")?;
+    if to_ms <= from_ms {
+        return Err("to_ms must be after from_ms".into());
+    }
+    let naive = |ms: i64| {
+        Local
+            .timestamp_millis_opt(ms)
+            .single()
+            .map(|t| t.naive_local())
+            .ok_or_else(|| format!("Invalid timestamp: {}", ms))
+    };
+    let (from, to) = (naive(from_ms)?, naive(to_ms)?);
+
+    let feeds = app.state::<CalendarFeeds>();
+    let events = feeds.events.lock().unwrap_or_else(|e| e.into_inner());
+    let mut blocks: Vec<BusyBlock> = events
+        .iter()
+        .flat_map(|(calendar_id, events)| {
+            events
+                .iter()
+                .filter(|e| !e.transparent && !e.cancelled)
+                .flat_map(move |event| {
+                    occurrences(event, from, to).into_iter().filter_map(
+                        move |(start, end, all_day)| {
+                            Some(BusyBlock {
+                                calendar_id: calendar_id.clone(),
+                                uid: event.uid.clone(),
+                                title: event.summary.clone(),
+                                start_ms: local_ms(start)?,
+                                end_ms: local_ms(end)?,
+                                all_day,
+                            })
+                        },
+                    )
+                })
+        })
+        .collect();
+    blocks.sort_by_key(|b| (b.start_ms, b.end_ms));
+    Ok(blocks)
+}
@@ -0,0 +1,618 @@
+//! Red CI builds on watched repositories become "fix build" tasks.
+//!
+//! Each watch names a GitHub Actions repository or GitLab project and a
+//! branch. Every `POLL_INTERVAL` the latest finished run on that branch is
+//! looked up; when it failed, a task is created (or the open one updated and
+//! reopened) with the tail of the failed job's log in its description. When a
+//! later run passes, the task is marked done. Tasks go through the offline
+//! cache like any local edit. API tokens are kept in the OS keychain, watches
+//! in `ci.json`, and each change of build state publishes `ci://build`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::parsers::{self, CiJob, CiRun, CiState};
+
+pub(crate) const CI_STORE: &str = "ci.json";
+const CI_KEY: &str = "watches";
+const KEYRING_SERVICE: &str = "flowstate-ci";
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Log lines attached to the task
+const EXCERPT_LINES: usize = 60;
+const DEFAULT_BRANCH: &str = "main";
+const TASK_TAGS: [&str; 2] = ["ci", "fix-build"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CiProvider {
+    Github,
+    Gitlab,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildState {
+    Passing,
+    Failing,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CiWatch {
+    pub id: String,
+    pub provider: CiProvider,
+    /// `owner/name` on GitHub, the project path on GitLab
+    pub repo: String,
+    pub branch: String,
+    /// API root for GitHub Enterprise or a self-hosted GitLab
+    pub api_url: Option<String>,
+    /// The open "fix build" task while the build is red
+    pub task_id: Option<String>,
+    /// Failed run the task describes
+    pub failed_run_id: Option<u64>,
+    pub state: Option<BuildState>,
+    pub last_checked_at_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CiBuildEvent {
+    pub watch_id: String,
+    pub repo: String,
+    pub branch: String,
+    pub state: BuildState,
+    pub task_id: Option<String>,
+    pub run_url: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CiWatches {
+    watches: Mutex<Vec<CiWatch>>,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .user_agent("FlowState")
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn keyring_entry(id: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, id).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// Percent-encode a GitLab project path for use as its id
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+struct Api<'a> {
+    watch: &'a CiWatch,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Api<'_> {
+    fn base(&self) -> String {
+        let root = match (&self.watch.api_url, self.watch.provider) {
+            (Some(url), _) => url.trim_end_matches('/').to_string(),
+            (None, CiProvider::Github) => "https://api.github.com".to_string(),
+            (None, CiProvider::Gitlab) => "https://gitlab.com/api/v4".to_string(),
+        };
+        match self.watch.provider {
+            CiProvider::Github => format!("{}/repos/{}", root, self.watch.repo),
+            CiProvider::Gitlab => format!("{}/projects/{}", root, encode_path(&self.watch.repo)),
+        }
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<reqwest::Response, String> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.base(), path))
+            .query(query);
+        request = match (self.watch.provider, &self.token) {
+            (CiProvider::Github, token) => {
+                let request = request
+                    .header("Accept", "application/vnd.github+json")
+                    .header("X-GitHub-Api-Version", "2022-11-28");
+                match token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            (CiProvider::Gitlab, Some(token)) => request.header("PRIVATE-TOKEN", token),
+            (CiProvider::Gitlab, None) => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", self.watch.repo, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "CI request for {} failed: HTTP {}",
+                self.watch.repo,
+                response.status().as_u16()
+            ));
+        }
+        Ok(response)
+    }
+
+    async fn get_json(&self, path: &str, query: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+        let body = self
+            .get(path, query)
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read CI response: {}", e))?;
+        Ok(body.to_vec())
+    }
+
+    /// Newest run on the branch that has finished as passed or failed
+    async fn latest_finished(&self) -> Result<Option<CiRun>, String> {
+        let branch = self.watch.branch.as_str();
+        let runs = match self.watch.provider {
+            CiProvider::Github => {
+                let body = self
+                    .get_json(
+                        "/actions/runs",
+                        &[
+                            ("branch", branch),
+                            ("per_page", "20"),
+                            ("exclude_pull_requests", "true"),
+                        ],
+                    )
+                    .await?;
+                parsers::parse_github_runs(&body)
+            }
+            CiProvider::Gitlab => {
+                let body = self
+                    .get_json(
+                        "/pipelines",
+                        &[
+                            ("ref", branch),
+                            ("per_page", "20"),
+                            ("order_by", "id"),
+                            ("sort", "desc"),
+                        ],
+                    )
+                    .await?;
+                parsers::parse_gitlab_pipelines(&body)
+            }
+        }
+        .map_err(|e| format!("Invalid CI response: {}", e))?;
+
+        Ok(runs
+            .into_iter()
+            .find(|run| matches!(run.state, CiState::Succeeded | CiState::Failed)))
+    }
+
+    async fn failed_jobs(&self, run: &CiRun) -> Result<Vec<CiJob>, String> {
+        let jobs = match self.watch.provider {
+            CiProvider::Github => {
+                let body = self
+                    .get_json(
+                        &format!("/actions/runs/{}/jobs", run.id),
+                        &[("per_page", "100")],
+                    )
+                    .await?;
+                parsers::parse_github_jobs(&body)
+            }
+            CiProvider::Gitlab => {
+                let body = self
+                    .get_json(
+                        &format!("/pipelines/{}/jobs", run.id),
+                        &[("scope[]", "failed"), ("per_page", "100")],
+                    )
+                    .await?;
+                parsers::parse_gitlab_jobs(&body)
+            }
+        }
+        .map_err(|e| format!("Invalid CI response: {}", e))?;
+        Ok(jobs.into_iter().filter(|job| job.failed).collect())
+    }
+
+    /// The last `MAX_CI_LOG` bytes of a job log
+    async fn log_tail(&self, job: &CiJob) -> Result<Vec<u8>, String> {
+        let path = match self.watch.provider {
+            CiProvider::Github => format!("/actions/jobs/{}/logs", job.id),
+            CiProvider::Gitlab => format!("/jobs/{}/trace", job.id),
+        };
+        let mut response = self.get(&path, &[]).await?;
+        let mut tail = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to read job log: {}", e))?
+        {
+            tail.extend_from_slice(&chunk);
+            if tail.len() > parsers::MAX_CI_LOG {
+                tail.drain(..tail.len() - parsers::MAX_CI_LOG);
+            }
+        }
+        Ok(tail)
+    }
+
+    /// Task description for a failed run: links, failed jobs and a log excerpt
+    async fn describe_failure(&self, run: &CiRun) -> String {
+        let mut text = format!("CI failed on {} ({}).", self.watch.repo, self.watch.branch);
+        if let Some(url) = &run.web_url {
+            text.push_str(&format!("\n\nRun: {}", url));
+        }
+        if let Some(commit) = &run.commit {
+            text.push_str(&format!("\nCommit: {}", commit));
+        }
+
+        let jobs = match self.failed_jobs(run).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                log::warn!("No failed jobs for {}: {}", self.watch.repo, e);
+                Vec::new()
+            }
+        };
+        if !jobs.is_empty() {
+            let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+            text.push_str(&format!("\nFailed jobs: {}", names.join(", ")));
+        }
+        if let Some(job) = jobs.first() {
+            let excerpt = match self.log_tail(job).await {
+                Ok(tail) => parsers::parse_ci_log_excerpt(&tail, EXCERPT_LINES)
+                    .map_err(|e| format!("Invalid job log: {}", e)),
+                Err(e) => Err(e),
+            };
+            match excerpt {
+                Ok(excerpt) if !excerpt.is_empty() => text.push_str(&format!(
+                    "\n\nLog excerpt ({}):\n```\n{}\n```",
+                    job.name, excerpt
+                )),
+                Ok(_) => {}
+                Err(e) => log::warn!("No log excerpt for {}: {}", self.watch.repo, e),
+            }
+        }
+        text
+    }
+}
+
+/// Create the fix-build task, or update and reopen the one already open
+fn open_task(app: &AppHandle, watch: &CiWatch, description: String) -> Result<String, String> {
+    crate::read_only::ensure_writable(app, "ci_builds")?;
+    let existing = match &watch.task_id {
+        Some(id) => crate::offline::cached_task(app, id)?,
+        None => None,
+    };
+
+    let mut tags: Vec<Value> = existing
+        .as_ref()
+        .and_then(|row| row.get("tags"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    for tag in TASK_TAGS {
+        if !tags.iter().any(|t| t.as_str() == Some(tag)) {
+            tags.push(Value::from(tag));
+        }
+    }
+
+    let mut task = crate::offline::owned_task(app);
+    if let Some(id) = existing.as_ref().and_then(|row| row.get("id")) {
+        task.insert("id".to_string(), id.clone());
+    }
+    task.insert(
+        "title".to_string(),
+        Value::from(format!("Fix build: {} ({})", watch.repo, watch.branch)),
+    );
+    task.insert("description".to_string(), Value::from(description));
+    task.insert("status".to_string(), Value::from("planned"));
+    task.insert("priority".to_string(), Value::from("high"));
+    task.insert("tags".to_string(), Value::Array(tags));
+    task.insert("completed_at".to_string(), Value::Null);
+
+    let saved = crate::offline::save_task(app, task)?;
+    saved
+        .get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Saved task has no id".to_string())
+}
+
+/// Mark the fix-build task done (unless it was deleted meanwhile)
+fn close_task(app: &AppHandle, task_id: &str) -> Result<(), String> {
+    crate::read_only::ensure_writable(app, "ci_builds")?;
+    if crate::offline::cached_task(app, task_id)?.is_none() {
+        return Ok(());
+    }
+    let mut task = Map::new();
+    task.insert("id".to_string(), Value::from(task_id));
+    task.insert("status".to_string(), Value::from("done"));
+    task.insert(
+        "completed_at".to_string(),
+        Value::from(chrono::Utc::now().to_rfc3339()),
+    );
+    crate::offline::save_task(app, task).map(|_| ())
+}
+
+/// Look at the latest finished run and open or close the task accordingly
+async fn check_one(app: &AppHandle, mut watch: CiWatch) -> CiWatch {
+    let http = match client() {
+        Ok(http) => http,
+        Err(e) => {
+            watch.last_error = Some(e);
+            return watch;
+        }
+    };
+    let api = Api {
+        token: keyring_entry(&watch.id)
+            .ok()
+            .and_then(|entry| entry.get_password().ok()),
+        http,
+        watch: &watch,
+    };
+
+    let result = match api.latest_finished().await {
+        Ok(Some(run)) if run.state == CiState::Failed => {
+            if watch.failed_run_id == Some(run.id) {
+                Ok(None)
+            } else {
+                let description = api.describe_failure(&run).await;
+                open_task(app, &watch, description)
+                    .map(|task_id| Some((BuildState::Failing, Some(task_id), run)))
+            }
+        }
+        Ok(Some(run)) => match &watch.task_id {
+            Some(task_id) => {
+                close_task(app, task_id).map(|_| Some((BuildState::Passing, None, run)))
+            }
+            None if watch.state != Some(BuildState::Passing) => {
+                Ok(Some((BuildState::Passing, None, run)))
+            }
+            None => Ok(None),
+        },
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    watch.last_checked_at_ms = Some(crate::events::now_ms());
+    match result {
+        Ok(change) => {
+            watch.last_error = None;
+            if let Some((state, task_id, run)) = change {
+                let closed = watch.task_id.take();
+                watch.failed_run_id = match state {
+                    BuildState::Failing => Some(run.id),
+                    BuildState::Passing => None,
+                };
+                watch.task_id = task_id.clone();
+                watch.state = Some(state);
+                log::info!("CI for {} ({}) is {:?}", watch.repo, watch.branch, state);
+                crate::events::publish(
+                    app,
+                    "ci://build",
+                    &CiBuildEvent {
+                        watch_id: watch.id.clone(),
+                        repo: watch.repo.clone(),
+                        branch: watch.branch.clone(),
+                        state,
+                        task_id: task_id.or(closed),
+                        run_url: run.web_url,
+                    },
+                );
+            } else if watch.failed_run_id.is_some() {
+                watch.state = Some(BuildState::Failing);
+            }
+        }
+        Err(e) => {
+            log::warn!("CI check for {} failed: {}", watch.repo, e);
+            watch.last_error = Some(e);
+        }
+    }
+    watch
+}
+
+fn save_watches(app: &AppHandle) -> Result<(), String> {
+    let watches = app
+        .state::<CiWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let store = app
+        .store(CI_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", CI_STORE, e))?;
+    store.set(
+        CI_KEY,
+        serde_json::to_value(&watches).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", CI_STORE, e))
+}
+
+/// Check a watch and store the result (unless it was removed meanwhile)
+async fn refresh_one(app: &AppHandle, watch: CiWatch) -> CiWatch {
+    let watch = check_one(app, watch).await;
+    if let Some(slot) = app
+        .state::<CiWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter_mut()
+        .find(|w| w.id == watch.id)
+    {
+        *slot = watch.clone();
+    }
+    watch
+}
+
+async fn refresh_all(app: &AppHandle) -> Vec<CiWatch> {
+    let watches = app
+        .state::<CiWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let mut checked = Vec::new();
+    for watch in watches {
+        checked.push(refresh_one(app, watch).await);
+    }
+    if !checked.is_empty() {
+        if let Err(e) = save_watches(app) {
+            log::warn!("{}", e);
+        }
+    }
+    checked
+}
+
+/// Load watches
+pub fn init(app: &AppHandle) {
+    let watches: Vec<CiWatch> = app
+        .store(CI_STORE)
+        .ok()
+        .and_then(|store| store.get(CI_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default();
+    *app.state::<CiWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = watches;
+}
+
+/// Poll the watches periodically (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh_all(&app).await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+fn remove(app: &AppHandle, id: &str) -> bool {
+    let state = app.state::<CiWatches>();
+    let mut watches = state.watches.lock().unwrap_or_else(|e| e.into_inner());
+    let before = watches.len();
+    watches.retain(|w| w.id != id);
+    if let Ok(entry) = keyring_entry(id) {
+        let _ = entry.delete_credential();
+    }
+    watches.len() != before
+}
+
+#[tauri::command]
+pub fn list_ci_watches(watches: tauri::State<'_, CiWatches>) -> Vec<CiWatch> {
+    watches
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Watch a repository's branch; it is checked once before being added
+#[tauri::command]
+pub async fn add_ci_watch(
+    app: AppHandle,
+    provider: CiProvider,
+    repo: String,
+    branch: Option<String>,
+    api_url: Option<String>,
+    token: Option<String>,
+) -> Result<CiWatch, FlowStateError> {
+    crate::trace::scope("add_ci_watch", async move {
+        crate::app_lock::ensure_unlocked(&app, "add_ci_watch")?;
+        crate::read_only::ensure_writable(&app, "add_ci_watch")?;
+
+        let repo = repo.trim().trim_matches('/').to_string();
+        if repo.split('/').filter(|part| !part.is_empty()).count() < 2 {
+            return Err(format!("Expected owner/name, got {:?}", repo).into());
+        }
+        let api_url = match api_url.filter(|url| !url.trim().is_empty()) {
+            Some(url) => {
+                let parsed =
+                    reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+                if parsed.scheme() != "https" {
+                    return Err("CI API URLs must use https".into());
+                }
+                Some(url.trim().to_string())
+            }
+            None => None,
+        };
+
+        let bytes: [u8; 8] = rand::random();
+        let watch = CiWatch {
+            id: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            provider,
+            repo,
+            branch: branch
+                .map(|b| b.trim().to_string())
+                .filter(|b| !b.is_empty())
+                .unwrap_or_else(|| DEFAULT_BRANCH.to_string()),
+            api_url,
+            task_id: None,
+            failed_run_id: None,
+            state: None,
+            last_checked_at_ms: None,
+            last_error: None,
+        };
+        if let Some(token) = token.filter(|t| !t.is_empty()) {
+            keyring_entry(&watch.id)?
+                .set_password(&token)
+                .map_err(|e| format!("Failed to store CI token in keychain: {}", e))?;
+        }
+
+        app.state::<CiWatches>()
+            .watches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(watch.clone());
+        let checked = refresh_one(&app, watch).await;
+        if let Some(error) = checked.last_error {
+            remove(&app, &checked.id);
+            return Err(error.into());
+        }
+
+        save_watches(&app)?;
+        log::info!("Watching CI for {} ({})", checked.repo, checked.branch);
+        Ok(checked)
+    })
+    .await
+}
+
+/// Stop watching; an open fix-build task is left as it is
+#[tauri::command]
+pub fn remove_ci_watch(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "remove_ci_watch")?;
+    if !remove(&app, &id) {
+        return Err(format!("Unknown CI watch: {}", id).into());
+    }
+    save_watches(&app)?;
+    Ok(())
+}
+
+/// Check every watched repository now
+#[tauri::command]
+pub async fn check_ci_builds(app: AppHandle) -> Result<Vec<CiWatch>, FlowStateError> {
+    crate::trace::scope("check_ci_builds", async move {
+        // Checks open and close tasks; refuse up front rather than per watch
+        crate::read_only::ensure_writable(&app, "check_ci_builds")?;
+        Ok(refresh_all(&app).await)
+    })
+    .await
+}
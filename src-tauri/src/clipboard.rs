@@ -0,0 +1,145 @@
+//! Copying secrets (share link tokens, API keys) to the clipboard.
+//!
+//! `copy_secret` takes the name of a keychain secret (`secrets.rs`), never
+//! its value, so the plaintext doesn't pass through the webview: it is read
+//! only after the OS authentication prompt and each copy is audited, like
+//! `get_secret`. The platform is asked to keep the text out of clipboard
+//! history and monitoring tools where it can (Windows: the
+//! ExcludeClipboardContentFromMonitorProcessing / history / cloud formats;
+//! macOS: `org.nspasteboard.ConcealedType`, which clipboard managers skip;
+//! Linux: the KDE password-manager hint that Klipper and friends honor) and
+//! it is cleared again after a timeout, unless the user has copied something
+//! else in the meantime.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+const DEFAULT_CLEAR_AFTER: Duration = Duration::from_secs(30);
+const MAX_CLEAR_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Bumped on every copy so only the latest copy's timer clears the clipboard
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// X11 only serves clipboard contents while a Clipboard instance is alive
+#[cfg(all(unix, not(target_os = "macos")))]
+static HOLDER: std::sync::Mutex<Option<arboard::Clipboard>> = std::sync::Mutex::new(None);
+
+fn digest(text: &str) -> Vec<u8> {
+    Sha256::digest(text.as_bytes()).to_vec()
+}
+
+/// The general pasteboard with the text and the concealed marker
+#[cfg(target_os = "macos")]
+fn set_text(text: &str) -> Result<(), String> {
+    use objc2_app_kit::{NSPasteboard, NSPasteboardTypeString};
+    use objc2_foundation::NSString;
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    pasteboard.clearContents();
+    // SAFETY: an AppKit constant, valid for the life of the process
+    let string_type = unsafe { NSPasteboardTypeString };
+    let concealed = NSString::from_str("org.nspasteboard.ConcealedType");
+    if pasteboard.setString_forType(&NSString::from_str(text), string_type)
+        && pasteboard.setString_forType(&NSString::from_str(""), &concealed)
+    {
+        Ok(())
+    } else {
+        Err("Failed to copy to clipboard".to_string())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_text(text: &str) -> Result<(), String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Clipboard unavailable: {}", e))?;
+    {
+        let set = clipboard.set();
+
+        #[cfg(target_os = "windows")]
+        let set = {
+            use arboard::SetExtWindows;
+            set.exclude_from_monitoring()
+                .exclude_from_history()
+                .exclude_from_cloud()
+        };
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let set = {
+            use arboard::SetExtLinux;
+            set.exclude_from_history()
+        };
+
+        set.text(text)
+            .map_err(|e| format!("Failed to copy to clipboard: {}", e))?;
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        *HOLDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(clipboard);
+    }
+    Ok(())
+}
+
+/// Clear the clipboard if it still holds the copied secret
+fn clear_if_unchanged(expected: &[u8]) {
+    let Ok(mut clipboard) = arboard::Clipboard::new() else {
+        return;
+    };
+    let unchanged = clipboard
+        .get_text()
+        .map(|current| digest(&current) == expected)
+        .unwrap_or(false);
+    if unchanged {
+        match clipboard.clear() {
+            Ok(()) => log::info!("Cleared copied secret from clipboard"),
+            Err(e) => log::warn!("Failed to clear clipboard: {}", e),
+        }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        *HOLDER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Copy the secret `name` of `workspace` (default: the current one) once the
+/// user passes the OS authentication prompt, and clear it after
+/// `clear_after_secs` (default 30)
+#[tauri::command]
+pub async fn copy_secret(
+    app: AppHandle,
+    workspace: Option<String>,
+    name: String,
+    clear_after_secs: Option<u64>,
+) -> Result<(), FlowStateError> {
+    crate::trace::scope("copy_secret", async move {
+        crate::app_lock::ensure_unlocked(&app, "copy_secret")?;
+        let clear_after = clear_after_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CLEAR_AFTER)
+            .min(MAX_CLEAR_AFTER);
+        let text = crate::secrets::reveal(&app, workspace, &name, crate::secrets::COPY_ACTION)
+            .await?
+            .ok_or_else(|| format!("No secret named {}", name))?;
+        let expected = digest(&text);
+        let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+        tauri::async_runtime::spawn_blocking(move || set_text(&text))
+            .await
+            .map_err(|e| format!("Clipboard task failed: {}", e))??;
+
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(clear_after).await;
+            if GENERATION.load(Ordering::SeqCst) == generation {
+                let _ = tauri::async_runtime::spawn_blocking(move || clear_if_unchanged(&expected))
+                    .await;
+            }
+        });
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,315 @@
+//! The app's clock, which debug builds can fast-forward.
+//!
+//! Schedulers read the time here (`now_ms`, `now_local`; `events::now_ms` is
+//! this clock) and wait with `sleep`, so shifting the clock moves all of them:
+//! the focus timer, the time tracking heartbeat, notification quiet hours and
+//! digests, calendar refreshes, nightly backups and retention, daily audit
+//! signing and the "today" of the heatmap, anomalies and forecasts. Local
+//! times come from the shifted instant with the system's time zone rules, so
+//! moving across a DST change behaves as it will for real. `dev_advance_clock`
+//! is a hidden command for trying schedules out by hand and only works in
+//! debug builds; tests call `advance` and `set` directly. In tests the offset
+//! belongs to the test's thread, so a test moving the clock doesn't skew the
+//! tests running beside it. Sleepers wake as soon as the clock passes their
+//! deadline. `until_next` finds the next time of day for nightly jobs and
+//! `last_at` the one before, for catching up: on the night a DST change skips
+//! that time it is the moment the clock jumps, and when the time comes twice
+//! only the first counts.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Days, Local, LocalResult, NaiveDate, TimeZone};
+use serde::Serialize;
+use tauri::AppHandle;
+use tokio::sync::Notify;
+
+use crate::error::FlowStateError;
+
+/// Longest single jump, a year
+const MAX_ADVANCE_MS: u64 = 366 * 24 * 60 * 60 * 1000;
+
+#[cfg(not(test))]
+static OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+#[cfg(test)]
+thread_local! {
+    static OFFSET_MS: AtomicI64 = const { AtomicI64::new(0) };
+}
+static ADVANCED: Notify = Notify::const_new();
+
+#[cfg(not(test))]
+fn with_offset<T>(f: impl FnOnce(&AtomicI64) -> T) -> T {
+    f(&OFFSET_MS)
+}
+
+#[cfg(test)]
+fn with_offset<T>(f: impl FnOnce(&AtomicI64) -> T) -> T {
+    OFFSET_MS.with(f)
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockState {
+    pub now_ms: u64,
+    /// How far the clock is ahead of the system clock
+    pub offset_ms: i64,
+}
+
+/// Milliseconds since the epoch
+pub(crate) fn now_ms() -> u64 {
+    (real_ms() + with_offset(|offset| offset.load(Ordering::Relaxed))).max(0) as u64
+}
+
+fn real_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn now_local() -> DateTime<Local> {
+    now_in(&Local)
+}
+
+/// Now in the time zone `tz`
+pub(crate) fn now_in<Tz: TimeZone>(tz: &Tz) -> DateTime<Tz> {
+    tz.timestamp_millis_opt(now_ms() as i64)
+        .single()
+        .unwrap_or_else(|| chrono::Utc::now().with_timezone(tz))
+}
+
+/// When the local clock shows `hour`:00 on `day`
+fn on_day<Tz: TimeZone>(tz: &Tz, day: NaiveDate, hour: u32) -> Option<DateTime<Tz>> {
+    let at = day.and_hms_opt(hour, 0, 0)?;
+    match tz.from_local_datetime(&at) {
+        LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => Some(at),
+        // Skipped by a DST change: the first minute after the jump
+        LocalResult::None => (1..=24 * 60).find_map(|minutes| {
+            tz.from_local_datetime(&(at + chrono::Duration::minutes(minutes)))
+                .earliest()
+        }),
+    }
+}
+
+/// The next time after `now` the local clock shows `hour`:00
+pub(crate) fn next_at<Tz: TimeZone>(now: &DateTime<Tz>, hour: u32) -> Option<DateTime<Tz>> {
+    let today = now.date_naive();
+    [Some(today), today.checked_add_days(Days::new(1))]
+        .into_iter()
+        .flatten()
+        .filter_map(|day| on_day(&now.timezone(), day, hour))
+        .find(|at| at > now)
+}
+
+/// The last time up to `now` the local clock showed `hour`:00
+pub(crate) fn last_at<Tz: TimeZone>(now: &DateTime<Tz>, hour: u32) -> Option<DateTime<Tz>> {
+    let today = now.date_naive();
+    [Some(today), today.checked_sub_days(Days::new(1))]
+        .into_iter()
+        .flatten()
+        .filter_map(|day| on_day(&now.timezone(), day, hour))
+        .find(|at| at <= now)
+}
+
+/// Time from `now` until the local clock next shows `hour`:00
+pub(crate) fn until_next<Tz: TimeZone>(now: &DateTime<Tz>, hour: u32) -> Duration {
+    let ms = next_at(now, hour)
+        .map(|at| at.timestamp_millis() - now.timestamp_millis())
+        .unwrap_or(24 * 60 * 60 * 1000);
+    Duration::from_millis(ms.max(0) as u64)
+}
+
+/// Wait `duration` on this clock; returns early when it is moved past the end
+pub(crate) async fn sleep(duration: Duration) {
+    let deadline = now_ms().saturating_add(duration.as_millis() as u64);
+    loop {
+        let remaining = deadline.saturating_sub(now_ms());
+        if remaining == 0 {
+            return;
+        }
+        let _ = tokio::time::timeout(Duration::from_millis(remaining), ADVANCED.notified()).await;
+    }
+}
+
+/// Move the clock forward and wake the sleepers
+pub(crate) fn advance(ms: u64) {
+    with_offset(|offset| offset.fetch_add(ms as i64, Ordering::Relaxed));
+    ADVANCED.notify_waiters();
+}
+
+/// Back to the system clock
+pub(crate) fn reset() {
+    with_offset(|offset| offset.store(0, Ordering::Relaxed));
+    ADVANCED.notify_waiters();
+}
+
+/// Put the clock at `at_ms`
+#[cfg(test)]
+pub(crate) fn set(at_ms: i64) {
+    with_offset(|offset| offset.store(at_ms - real_ms(), Ordering::Relaxed));
+    ADVANCED.notify_waiters();
+}
+
+fn state() -> ClockState {
+    ClockState {
+        now_ms: now_ms(),
+        offset_ms: with_offset(|offset| offset.load(Ordering::Relaxed)),
+    }
+}
+
+fn ensure_dev(command: &str) -> Result<(), String> {
+    if cfg!(debug_assertions) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is only available in development builds",
+            command
+        ))
+    }
+}
+
+#[tauri::command]
+pub fn get_clock() -> ClockState {
+    state()
+}
+
+/// Fast-forward the clock by `ms` (development builds only)
+#[tauri::command]
+pub fn dev_advance_clock(app: AppHandle, ms: u64) -> Result<ClockState, FlowStateError> {
+    ensure_dev("dev_advance_clock")?;
+    if ms == 0 || ms > MAX_ADVANCE_MS {
+        return Err(format!("Advance by 1 to {} ms", MAX_ADVANCE_MS).into());
+    }
+    advance(ms);
+    let state = state();
+    log::warn!(
+        "Clock advanced by {} ms (now {} ms ahead)",
+        ms,
+        state.offset_ms
+    );
+    crate::events::publish(&app, "clock://changed", &state);
+    Ok(state)
+}
+
+/// Back to the system clock (development builds only)
+#[tauri::command]
+pub fn dev_reset_clock(app: AppHandle) -> Result<ClockState, FlowStateError> {
+    ensure_dev("dev_reset_clock")?;
+    reset();
+    let state = state();
+    crate::events::publish(&app, "clock://changed", &state);
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleepers_wake_when_the_clock_moves_past_them() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let day = 24 * 60 * 60 * 1000;
+            let before = now_ms();
+            let sleeper = tokio::spawn(sleep(Duration::from_millis(2 * day)));
+            tokio::task::yield_now().await;
+
+            advance(day);
+            assert!(now_ms() >= before + day);
+            tokio::task::yield_now().await;
+            assert!(!sleeper.is_finished(), "woke a day early");
+
+            advance(day);
+            tokio::time::timeout(Duration::from_secs(5), sleeper)
+                .await
+                .expect("still asleep after two days")
+                .unwrap();
+
+            let local = now_local();
+            assert_eq!(local.timestamp_millis() / 1000, now_ms() as i64 / 1000);
+            reset();
+            assert!(now_ms() < before + day);
+        });
+    }
+
+    fn at(text: &str) -> i64 {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    /// Run a nightly job at `hour` for `nights` nights from `start`, moving
+    /// the clock instead of waiting; the local times it ran at
+    fn nightly_runs(start: &str, hour: u32, nights: usize) -> Vec<String> {
+        let tz = chrono_tz::America::New_York;
+        set(at(start));
+        let runs = (0..nights)
+            .map(|_| {
+                advance(until_next(&now_in(&tz), hour).as_millis() as u64);
+                // Real time passes between steps; compare to the minute
+                now_in(&tz).format("%Y-%m-%dT%H:%M%:z").to_string()
+            })
+            .collect();
+        reset();
+        runs
+    }
+
+    #[test]
+    fn nightly_times_follow_the_local_clock_across_dst() {
+        // 2026-03-08: 02:00 EST jumps to 03:00 EDT in New York
+        assert_eq!(
+            nightly_runs("2026-03-06T12:00:00-05:00", 3, 4),
+            [
+                "2026-03-07T03:00-05:00",
+                "2026-03-08T03:00-04:00",
+                "2026-03-09T03:00-04:00",
+                "2026-03-10T03:00-04:00",
+            ]
+        );
+        // 02:00 doesn't exist that night; the job runs when the clock jumps
+        assert_eq!(
+            nightly_runs("2026-03-07T12:00:00-05:00", 2, 2),
+            ["2026-03-08T03:00-04:00", "2026-03-09T02:00-04:00"]
+        );
+        // 2026-11-01: 02:00 EDT falls back to 01:00 EST; 01:00 comes twice
+        // but the job runs once
+        assert_eq!(
+            nightly_runs("2026-10-31T12:00:00-04:00", 1, 2),
+            ["2026-11-01T01:00-04:00", "2026-11-02T01:00-05:00"]
+        );
+    }
+
+    #[test]
+    fn finds_the_last_time_the_clock_showed_an_hour() {
+        let tz = chrono_tz::America::New_York;
+        let last = |now: &str, hour| {
+            let now = tz.timestamp_millis_opt(at(now)).unwrap();
+            last_at(&now, hour).map(|at| at.to_rfc3339())
+        };
+        assert_eq!(
+            last("2026-03-05T01:00:00-05:00", 2).as_deref(),
+            Some("2026-03-04T02:00:00-05:00")
+        );
+        assert_eq!(
+            last("2026-03-08T09:00:00-04:00", 2).as_deref(),
+            Some("2026-03-08T03:00:00-04:00")
+        );
+        // The second 01:30 of the fall-back night still belongs to the first 01:00
+        assert_eq!(
+            last("2026-11-01T01:30:00-05:00", 1).as_deref(),
+            Some("2026-11-01T01:00:00-04:00")
+        );
+    }
+
+    #[test]
+    fn the_offset_belongs_to_the_test_thread() {
+        set(at("2030-01-01T00:00:00Z"));
+        let other = std::thread::spawn(now_ms).join().unwrap();
+        assert!(other < at("2030-01-01T00:00:00Z") as u64);
+        assert_eq!(now_ms() / 1000, at("2030-01-01T00:00:00Z") as u64 / 1000);
+        reset();
+    }
+}
@@ -0,0 +1,621 @@
+//! Conflict resolution for the offline task cache.
+//!
+//! Before an edited task is pushed, its server row is compared with the
+//! version the edit was based on. If the server changed in between, the
+//! workspace's strategy decides: `field_merge` merges the two rows field by
+//! field (a field changed on both sides goes to the row changed later; the
+//! server only keeps a time per row, so that is the local edit's queue time
+//! against the server row's `updated_at`, not per-field times),
+//! `prefer_local` overwrites the server, `prefer_remote` drops the local edit,
+//! and `manual` parks both versions until `resolve_conflict`. Parked tasks are
+//! not pushed or overwritten by pulls. A workspace is the Supabase project the
+//! cache syncs with; strategies persist in `conflicts.json`. Deleting a task
+//! always wins over remote edits.
+
+use std::collections::HashMap;
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::offline::with_db;
+
+pub(crate) const STRATEGY_STORE: &str = "conflicts.json";
+const STRATEGY_KEY: &str = "strategies";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Fields changed on one side win; a field changed on both sides goes to
+    /// the side whose row changed later
+    #[default]
+    #[serde(alias = "field_last_write_wins")]
+    FieldMerge,
+    PreferLocal,
+    PreferRemote,
+    Manual,
+}
+
+/// Server row of a task as seen during a push
+pub struct RemoteRow {
+    pub data: String,
+    pub updated_at: Option<String>,
+}
+
+/// A queued local edit about to be pushed
+pub struct LocalEdit<'a> {
+    pub task_id: &'a str,
+    pub data: &'a str,
+    pub base_updated_at: Option<&'a str>,
+    pub queued_at_ms: i64,
+}
+
+pub enum Outcome {
+    /// Push this row
+    Push(String),
+    /// Keep the server row; the local edit is dropped
+    KeepRemote,
+    /// Parked for `resolve_conflict`
+    Parked,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub id: i64,
+    pub task_id: String,
+    pub local: Value,
+    pub remote: Value,
+    /// Version both sides started from, when known
+    pub base: Option<Value>,
+    /// Fields that differ between local and remote
+    pub fields: Vec<String>,
+    pub detected_at_ms: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "choice", rename_all = "snake_case")]
+pub enum ConflictChoice {
+    Local,
+    Remote,
+    /// A row merged in the UI
+    Merged {
+        task: Map<String, Value>,
+    },
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictEvent {
+    pub id: i64,
+    pub task_id: String,
+    /// detected | resolved
+    pub action: &'static str,
+}
+
+/// Workspace id of the task cache
+pub fn current_workspace() -> String {
+    crate::multi_user::project_id()
+}
+
+fn strategies(app: &AppHandle) -> HashMap<String, ConflictStrategy> {
+    app.store(STRATEGY_STORE)
+        .ok()
+        .and_then(|store| store.get(STRATEGY_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+pub fn strategy(app: &AppHandle, workspace: &str) -> ConflictStrategy {
+    strategies(app).get(workspace).copied().unwrap_or_default()
+}
+
+fn parse_row(data: &str) -> Map<String, Value> {
+    serde_json::from_str(data).unwrap_or_default()
+}
+
+/// Timestamp text from Postgres or RFC 3339 as ms since epoch
+fn parse_time_ms(text: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(text)
+        .or_else(|_| chrono::DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z"))
+        .ok()
+        .map(|t| t.timestamp_millis())
+}
+
+/// Whether a local edit queued at `queued_at_ms` is later than the server
+/// row's `updated_at`; the local edit wins when that can't be read
+fn local_is_later(queued_at_ms: i64, remote_updated_at: Option<&str>) -> bool {
+    remote_updated_at
+        .and_then(parse_time_ms)
+        .map_or(true, |remote_ms| queued_at_ms >= remote_ms)
+}
+
+/// Merge field by field; a field changed on both sides goes to local when
+/// `local_wins_ties`
+fn merge_fields(
+    base: Option<&Map<String, Value>>,
+    local: &Map<String, Value>,
+    remote: &Map<String, Value>,
+    local_wins_ties: bool,
+) -> Map<String, Value> {
+    let mut merged = remote.clone();
+    for (key, value) in local {
+        let local_changed = base.map_or(true, |b| b.get(key) != Some(value));
+        let remote_changed = base.map_or(true, |b| b.get(key) != remote.get(key));
+        if local_changed && (!remote_changed || local_wins_ties) {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+fn differing_fields(local: &Map<String, Value>, remote: &Map<String, Value>) -> Vec<String> {
+    let mut fields: Vec<String> = local
+        .keys()
+        .chain(remote.keys())
+        .filter(|k| k.as_str() != "updated_at" && local.get(*k) != remote.get(*k))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+/// Whether the server row changed since the version the edit was based on
+fn changed_remotely(edit: &LocalEdit, remote: &RemoteRow) -> bool {
+    match (edit.base_updated_at, remote.updated_at.as_deref()) {
+        (Some(base), Some(current)) => base != current,
+        // Created offline but the id exists remotely
+        (None, Some(_)) => true,
+        // No timestamps to compare
+        (_, None) => false,
+    }
+}
+
+/// What `strategy` makes of an edit whose server row changed; `Parked` for
+/// the caller to park
+fn resolve(
+    strategy: ConflictStrategy,
+    edit: &LocalEdit,
+    remote: &RemoteRow,
+    base: Option<&str>,
+) -> Outcome {
+    match strategy {
+        ConflictStrategy::PreferLocal => Outcome::Push(edit.data.to_string()),
+        ConflictStrategy::PreferRemote => Outcome::KeepRemote,
+        ConflictStrategy::FieldMerge => {
+            let merged = merge_fields(
+                base.map(parse_row).as_ref(),
+                &parse_row(edit.data),
+                &parse_row(&remote.data),
+                local_is_later(edit.queued_at_ms, remote.updated_at.as_deref()),
+            );
+            Outcome::Push(Value::Object(merged).to_string())
+        }
+        ConflictStrategy::Manual => Outcome::Parked,
+    }
+}
+
+fn park(
+    app: &AppHandle,
+    edit: &LocalEdit,
+    remote: &RemoteRow,
+    base: Option<&str>,
+) -> Result<(), String> {
+    let id = with_db(app, |conn| {
+        conn.execute(
+            "INSERT INTO conflicts (task_id, local, remote, remote_updated_at, base, detected_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                edit.task_id,
+                edit.data,
+                remote.data,
+                remote.updated_at,
+                base,
+                now_ms() as i64
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })?;
+    crate::events::publish(
+        app,
+        "sync://conflict",
+        &ConflictEvent {
+            id,
+            task_id: edit.task_id.to_string(),
+            action: "detected",
+        },
+    );
+    Ok(())
+}
+
+/// Decide what to push for `edit` given the current server row
+pub fn reconcile(
+    app: &AppHandle,
+    edit: &LocalEdit,
+    remote: Option<&RemoteRow>,
+) -> Result<Outcome, String> {
+    let Some(remote) = remote.filter(|remote| changed_remotely(edit, remote)) else {
+        return Ok(Outcome::Push(edit.data.to_string()));
+    };
+
+    let workspace = current_workspace();
+    let strategy = strategy(app, &workspace);
+    log::info!(
+        "Task {} changed on both sides; resolving with {:?}",
+        edit.task_id,
+        strategy
+    );
+
+    let base: Option<String> = with_db(app, |conn| {
+        conn.query_row(
+            "SELECT data FROM outbox_base WHERE task_id = ?1",
+            params![edit.task_id],
+            |row| row.get(0),
+        )
+        .optional()
+    })?;
+
+    let outcome = resolve(strategy, edit, remote, base.as_deref());
+    if let Outcome::Parked = outcome {
+        park(app, edit, remote, base.as_deref())?;
+    }
+    Ok(outcome)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyInfo {
+    pub workspace: String,
+    pub strategy: ConflictStrategy,
+}
+
+#[tauri::command]
+pub fn get_conflict_strategy(app: AppHandle, workspace: Option<String>) -> StrategyInfo {
+    let workspace = workspace.unwrap_or_else(current_workspace);
+    StrategyInfo {
+        strategy: strategy(&app, &workspace),
+        workspace,
+    }
+}
+
+/// Choose how conflicts are resolved in a workspace (default: the current one)
+#[tauri::command]
+pub fn set_conflict_strategy(
+    app: AppHandle,
+    strategy: ConflictStrategy,
+    workspace: Option<String>,
+) -> Result<StrategyInfo, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_conflict_strategy")?;
+    let workspace = workspace.unwrap_or_else(current_workspace);
+    let mut all = strategies(&app);
+    all.insert(workspace.clone(), strategy);
+
+    let store = app
+        .store(STRATEGY_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", STRATEGY_STORE, e))?;
+    store.set(
+        STRATEGY_KEY,
+        serde_json::to_value(&all).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", STRATEGY_STORE, e))?;
+    Ok(StrategyInfo {
+        workspace,
+        strategy,
+    })
+}
+
+/// Unresolved conflicts with both versions of each task
+#[tauri::command]
+pub fn list_conflicts(app: AppHandle) -> Result<Vec<Conflict>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "list_conflicts")?;
+    let rows = with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, local, remote, base, detected_at FROM conflicts ORDER BY id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, task_id, local, remote, base, detected_at_ms)| {
+            let local = parse_row(&local);
+            let remote = parse_row(&remote);
+            Conflict {
+                id,
+                task_id,
+                fields: differing_fields(&local, &remote),
+                local: Value::Object(local),
+                remote: Value::Object(remote),
+                base: base.map(|b| Value::Object(parse_row(&b))),
+                detected_at_ms,
+            }
+        })
+        .collect())
+}
+
+/// Settle a parked conflict with the local, remote or a merged version
+#[tauri::command]
+pub fn resolve_conflict(
+    app: AppHandle,
+    id: i64,
+    choice: ConflictChoice,
+) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "resolve_conflict")?;
+    crate::read_only::ensure_writable(&app, "resolve_conflict")?;
+
+    let task_id = with_db(&app, |conn| {
+        let tx = conn.transaction()?;
+        let Some((task_id, local, remote, remote_updated_at)) = tx
+            .query_row(
+                "SELECT task_id, local, remote, remote_updated_at FROM conflicts WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                },
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let push = match &choice {
+            ConflictChoice::Local => Some(local),
+            ConflictChoice::Remote => None,
+            ConflictChoice::Merged { task } => {
+                let mut row = parse_row(&local);
+                row.extend(task.clone());
+                row.insert("id".to_string(), Value::String(task_id.clone()));
+                Some(Value::Object(row).to_string())
+            }
+        };
+
+        tx.execute("DELETE FROM conflicts WHERE id = ?1", params![id])?;
+        tx.execute(
+            "DELETE FROM outbox_base WHERE task_id = ?1",
+            params![task_id],
+        )?;
+        match push {
+            // Based on the server version just reviewed, so it won't conflict again
+            Some(data) => {
+                tx.execute(
+                    "UPDATE tasks SET data = ?2 WHERE id = ?1",
+                    params![task_id, data],
+                )?;
+                tx.execute(
+                    "INSERT INTO outbox (task_id, op, data, base_updated_at, queued_at, user_id) \
+                     VALUES (?1, 'upsert', ?2, ?3, ?4, json_extract(?2, '$.user_id'))",
+                    params![task_id, data, remote_updated_at, now_ms() as i64],
+                )?;
+            }
+            None => {
+                tx.execute(
+                    "UPDATE tasks SET data = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![task_id, remote, remote_updated_at],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(Some(task_id))
+    })?;
+
+    let Some(task_id) = task_id else {
+        return Err(format!("Unknown conflict: {}", id).into());
+    };
+    crate::search::reindex(&app, &task_id);
+    crate::events::publish(
+        &app,
+        "sync://conflict",
+        &ConflictEvent {
+            id,
+            task_id,
+            action: "resolved",
+        },
+    );
+    crate::offline::sync_soon(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    fn edit<'a>(
+        data: &'a str,
+        base_updated_at: Option<&'a str>,
+        queued_at_ms: i64,
+    ) -> LocalEdit<'a> {
+        LocalEdit {
+            task_id: "t1",
+            data,
+            base_updated_at,
+            queued_at_ms,
+        }
+    }
+
+    fn remote(data: Value, updated_at: Option<&str>) -> RemoteRow {
+        RemoteRow {
+            data: data.to_string(),
+            updated_at: updated_at.map(str::to_string),
+        }
+    }
+
+    fn pushed(outcome: Outcome) -> Value {
+        match outcome {
+            Outcome::Push(data) => serde_json::from_str(&data).unwrap(),
+            Outcome::KeepRemote => panic!("kept the remote row"),
+            Outcome::Parked => panic!("parked"),
+        }
+    }
+
+    #[test]
+    fn reads_postgres_and_rfc3339_times() {
+        assert_eq!(parse_time_ms("2026-03-01T10:00:00Z"), Some(1772359200000));
+        assert_eq!(
+            parse_time_ms("2026-03-01T12:00:00.250+02:00"),
+            Some(1772359200250)
+        );
+        assert_eq!(
+            parse_time_ms("2026-03-01 10:00:00.5+00"),
+            Some(1772359200500)
+        );
+        assert_eq!(parse_time_ms("yesterday"), None);
+        assert_eq!(parse_time_ms(""), None);
+    }
+
+    #[test]
+    fn merges_fields_changed_on_one_side() {
+        let base = row(json!({"title": "a", "status": "planned", "priority": "low"}));
+        let local = row(json!({"title": "b", "status": "planned", "priority": "low"}));
+        let remote = row(json!({"title": "a", "status": "done", "priority": "low"}));
+        let expected = json!({"title": "b", "status": "done", "priority": "low"});
+        // Ties don't matter when each field changed on one side only
+        for local_wins_ties in [true, false] {
+            assert_eq!(
+                Value::Object(merge_fields(Some(&base), &local, &remote, local_wins_ties)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn fields_changed_on_both_sides_go_to_the_later_row() {
+        let base = row(json!({"title": "a", "status": "planned"}));
+        let local = row(json!({"title": "local", "status": "planned"}));
+        let remote = row(json!({"title": "remote", "status": "planned"}));
+        assert_eq!(
+            merge_fields(Some(&base), &local, &remote, true)["title"],
+            "local"
+        );
+        assert_eq!(
+            merge_fields(Some(&base), &local, &remote, false)["title"],
+            "remote"
+        );
+
+        // Without a base every field counts as changed on both sides
+        let merged = merge_fields(None, &local, &remote, false);
+        assert_eq!(merged["title"], "remote");
+        // A field only the server has stays
+        let remote = row(json!({"title": "remote", "status": "planned", "tags": ["x"]}));
+        assert_eq!(
+            merge_fields(Some(&base), &local, &remote, true)["tags"],
+            json!(["x"])
+        );
+    }
+
+    #[test]
+    fn ties_and_unreadable_times_favour_the_local_edit() {
+        let at = parse_time_ms("2026-03-01T10:00:00Z").unwrap();
+        assert!(local_is_later(at, Some("2026-03-01T10:00:00Z")));
+        assert!(local_is_later(at + 1, Some("2026-03-01T10:00:00Z")));
+        assert!(!local_is_later(at - 1, Some("2026-03-01T10:00:00Z")));
+        assert!(local_is_later(0, Some("not a time")));
+        assert!(local_is_later(0, None));
+    }
+
+    #[test]
+    fn reconcile_pushes_when_the_server_did_not_change() {
+        let data = json!({"title": "b"}).to_string();
+        let same = remote(json!({"title": "a"}), Some("2026-03-01T10:00:00Z"));
+        assert!(!changed_remotely(
+            &edit(&data, Some("2026-03-01T10:00:00Z"), 0),
+            &same
+        ));
+        // No server time to compare
+        let untimed = remote(json!({}), None);
+        assert!(!changed_remotely(
+            &edit(&data, Some("2026-03-01T10:00:00Z"), 0),
+            &untimed
+        ));
+        // Changed since the base, or created offline under an id the server has
+        assert!(changed_remotely(
+            &edit(&data, Some("2026-02-01T10:00:00Z"), 0),
+            &same
+        ));
+        assert!(changed_remotely(&edit(&data, None, 0), &same));
+    }
+
+    #[test]
+    fn reconcile_follows_the_strategy() {
+        let base = json!({"title": "a", "status": "planned"}).to_string();
+        let local = json!({"title": "local", "status": "planned"}).to_string();
+        let server = remote(
+            json!({"title": "remote", "status": "done"}),
+            Some("2026-03-01T10:00:00Z"),
+        );
+        let server_ms = parse_time_ms("2026-03-01T10:00:00Z").unwrap();
+        let earlier = edit(&local, Some("2026-02-01T10:00:00Z"), server_ms - 60_000);
+        let later = edit(&local, Some("2026-02-01T10:00:00Z"), server_ms + 60_000);
+
+        let merge = |edit: &LocalEdit| {
+            pushed(resolve(
+                ConflictStrategy::FieldMerge,
+                edit,
+                &server,
+                Some(&base),
+            ))
+        };
+        assert_eq!(
+            merge(&earlier),
+            json!({"title": "remote", "status": "done"})
+        );
+        assert_eq!(merge(&later), json!({"title": "local", "status": "done"}));
+        // Same time: local wins
+        let tie = edit(&local, Some("2026-02-01T10:00:00Z"), server_ms);
+        assert_eq!(merge(&tie)["title"], "local");
+
+        assert_eq!(
+            pushed(resolve(
+                ConflictStrategy::PreferLocal,
+                &earlier,
+                &server,
+                Some(&base)
+            )),
+            json!({"title": "local", "status": "planned"})
+        );
+        assert!(matches!(
+            resolve(ConflictStrategy::PreferRemote, &later, &server, Some(&base)),
+            Outcome::KeepRemote
+        ));
+        assert!(matches!(
+            resolve(ConflictStrategy::Manual, &later, &server, Some(&base)),
+            Outcome::Parked
+        ));
+    }
+
+    #[test]
+    fn old_strategy_name_still_reads() {
+        let old: ConflictStrategy = serde_json::from_value(json!("field_last_write_wins")).unwrap();
+        assert_eq!(old, ConflictStrategy::FieldMerge);
+        assert_eq!(serde_json::to_value(old).unwrap(), json!("field_merge"));
+    }
+}
@@ -0,0 +1,290 @@
+//! Container runtime detection (Docker Desktop, Podman, Colima, OrbStack,
+//! Rancher Desktop, plain Docker Engine).
+//!
+//! The active runtime is the first known Engine API socket that answers
+//! `/version`; each runtime puts its socket in a different place. Installed
+//! runtimes are found by their CLI or app bundle, and each one knows how to
+//! start itself on the current platform.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerRuntime {
+    DockerDesktop,
+    DockerEngine,
+    Podman,
+    Colima,
+    OrbStack,
+    RancherDesktop,
+}
+
+impl ContainerRuntime {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContainerRuntime::DockerDesktop => "Docker Desktop",
+            ContainerRuntime::DockerEngine => "Docker Engine",
+            ContainerRuntime::Podman => "Podman",
+            ContainerRuntime::Colima => "Colima",
+            ContainerRuntime::OrbStack => "OrbStack",
+            ContainerRuntime::RancherDesktop => "Rancher Desktop",
+        }
+    }
+
+    /// CLI and arguments that print a version when the runtime is installed
+    fn version_command(&self) -> Option<(&'static str, &'static [&'static str])> {
+        match self {
+            ContainerRuntime::DockerDesktop | ContainerRuntime::DockerEngine => None,
+            ContainerRuntime::Podman => Some(("podman", &["--version"])),
+            ContainerRuntime::Colima => Some(("colima", &["version"])),
+            ContainerRuntime::OrbStack => Some(("orb", &["version"])),
+            ContainerRuntime::RancherDesktop => Some(("rdctl", &["version"])),
+        }
+    }
+
+    /// App bundle / install locations for runtimes without a dedicated CLI
+    fn app_paths(&self) -> Vec<PathBuf> {
+        match self {
+            ContainerRuntime::DockerDesktop => {
+                let mut paths = vec![
+                    PathBuf::from("/Applications/Docker.app"),
+                    PathBuf::from("/opt/docker-desktop"),
+                ];
+                paths.extend(crate::process::docker_desktop_exe());
+                paths
+            }
+            ContainerRuntime::OrbStack => vec![PathBuf::from("/Applications/OrbStack.app")],
+            ContainerRuntime::RancherDesktop => vec![
+                PathBuf::from("/Applications/Rancher Desktop.app"),
+                PathBuf::from("/opt/rancher-desktop"),
+            ],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Runtimes looked for by `installed`, Docker Desktop first
+const RUNTIMES: &[ContainerRuntime] = &[
+    ContainerRuntime::DockerDesktop,
+    ContainerRuntime::OrbStack,
+    ContainerRuntime::Colima,
+    ContainerRuntime::RancherDesktop,
+    ContainerRuntime::Podman,
+];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveRuntime {
+    pub runtime: ContainerRuntime,
+    pub socket: PathBuf,
+    pub version: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeReport {
+    pub active: Option<ActiveRuntime>,
+    pub installed: Vec<ContainerRuntime>,
+}
+
+#[cfg(not(target_os = "windows"))]
+fn home() -> Option<PathBuf> {
+    std::env::var("HOME").ok().map(PathBuf::from)
+}
+
+/// Engine API sockets to try, each attributed to the runtime that creates it
+fn socket_candidates() -> Vec<(ContainerRuntime, PathBuf)> {
+    // A selected docker context wins; tcp/ssh endpoints are left to the CLI
+    if let Some(endpoint) = crate::docker_context::selected_endpoint() {
+        return match endpoint.strip_prefix("unix://").map(PathBuf::from) {
+            Some(path) => vec![(runtime_for_socket(&path), path)],
+            None => Vec::new(),
+        };
+    }
+
+    let mut candidates = Vec::new();
+
+    if let Some(path) = std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|h| h.strip_prefix("unix://").map(PathBuf::from))
+    {
+        candidates.push((runtime_for_socket(&path), path));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        candidates.push((
+            ContainerRuntime::DockerDesktop,
+            PathBuf::from(r"\\.\pipe\docker_engine"),
+        ));
+        candidates.push((
+            ContainerRuntime::Podman,
+            PathBuf::from(r"\\.\pipe\podman-machine-default"),
+        ));
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Some(home) = home() {
+            candidates.push((
+                ContainerRuntime::OrbStack,
+                home.join(".orbstack/run/docker.sock"),
+            ));
+            candidates.push((
+                ContainerRuntime::Colima,
+                home.join(".colima/default/docker.sock"),
+            ));
+            candidates.push((
+                ContainerRuntime::Colima,
+                home.join(".config/colima/default/docker.sock"),
+            ));
+            candidates.push((
+                ContainerRuntime::RancherDesktop,
+                home.join(".rd/docker.sock"),
+            ));
+            candidates.push((
+                ContainerRuntime::DockerDesktop,
+                home.join(".docker/run/docker.sock"),
+            ));
+            candidates.push((
+                ContainerRuntime::DockerDesktop,
+                home.join(".docker/desktop/docker.sock"),
+            ));
+        }
+        if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+            candidates.push((
+                ContainerRuntime::Podman,
+                PathBuf::from(runtime_dir).join("podman/podman.sock"),
+            ));
+        }
+        candidates.push((
+            ContainerRuntime::Podman,
+            PathBuf::from("/run/podman/podman.sock"),
+        ));
+
+        // Usually a symlink to whichever runtime was started last
+        let default = PathBuf::from("/var/run/docker.sock");
+        candidates.push((runtime_for_socket(&default), default));
+    }
+
+    candidates
+}
+
+/// Attribute a socket path to a runtime by where it (or its symlink target) lives
+fn runtime_for_socket(path: &Path) -> ContainerRuntime {
+    let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let resolved = resolved.to_string_lossy();
+
+    if resolved.contains(".orbstack") {
+        ContainerRuntime::OrbStack
+    } else if resolved.contains("colima") {
+        ContainerRuntime::Colima
+    } else if resolved.contains(".rd/") {
+        ContainerRuntime::RancherDesktop
+    } else if resolved.contains("podman") {
+        ContainerRuntime::Podman
+    } else if resolved.contains(".docker/") || cfg!(any(target_os = "macos", target_os = "windows"))
+    {
+        ContainerRuntime::DockerDesktop
+    } else {
+        ContainerRuntime::DockerEngine
+    }
+}
+
+/// First runtime whose Engine API socket answers
+pub async fn active() -> Option<ActiveRuntime> {
+    for (runtime, socket) in socket_candidates() {
+        if cfg!(unix) && !socket.exists() {
+            continue;
+        }
+        match crate::docker::server_version(socket.clone()).await {
+            Ok(version) => {
+                return Some(ActiveRuntime {
+                    runtime,
+                    socket,
+                    version,
+                })
+            }
+            Err(e) => log::info!("{} not answering: {}", runtime.label(), e),
+        }
+    }
+    None
+}
+
+/// Runtimes installed on this machine, in detection order
+pub async fn installed(app: &AppHandle) -> Vec<ContainerRuntime> {
+    let mut found = Vec::new();
+
+    for runtime in RUNTIMES {
+        let has_app = runtime.app_paths().iter().any(|p| p.exists());
+        let has_cli = match runtime.version_command() {
+            Some((program, args)) => crate::trace::command(app, program)
+                .args(args)
+                .output()
+                .await
+                .is_ok_and(|o| o.status.success()),
+            None => false,
+        };
+        if has_app || has_cli {
+            found.push(*runtime);
+        }
+    }
+
+    found
+}
+
+/// Runtime to start when none was chosen: Docker Desktop unless only others are installed
+pub async fn default_runtime(app: &AppHandle) -> ContainerRuntime {
+    let installed = installed(app).await;
+    if installed.is_empty() || installed.contains(&ContainerRuntime::DockerDesktop) {
+        ContainerRuntime::DockerDesktop
+    } else {
+        installed[0]
+    }
+}
+
+/// Start a runtime other than Docker Desktop (which keeps its own fallbacks in lib.rs)
+pub async fn start(app: &AppHandle, runtime: ContainerRuntime) -> Result<String, String> {
+    let (program, args): (&str, &[&str]) = match runtime {
+        ContainerRuntime::Podman if cfg!(target_os = "linux") => {
+            ("systemctl", &["--user", "start", "podman.socket"])
+        }
+        ContainerRuntime::Podman => ("podman", &["machine", "start"]),
+        ContainerRuntime::Colima => ("colima", &["start"]),
+        ContainerRuntime::OrbStack => ("orb", &["start"]),
+        ContainerRuntime::RancherDesktop => ("rdctl", &["start"]),
+        ContainerRuntime::DockerEngine => ("systemctl", &["start", "docker"]),
+        ContainerRuntime::DockerDesktop => {
+            return Err("Docker Desktop is started by start_docker_desktop".to_string())
+        }
+    };
+
+    let output = crate::trace::command(app, program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to start {}: {}", runtime.label(), e))?;
+
+    if output.status.success() {
+        Ok("started".to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(format!("Failed to start {}: {}", runtime.label(), stderr))
+    }
+}
+
+/// Which runtimes are installed and which one is serving the Engine API
+#[tauri::command]
+pub async fn detect_container_runtimes(app: AppHandle) -> Result<RuntimeReport, FlowStateError> {
+    crate::trace::scope("detect_container_runtimes", async move {
+        Ok(RuntimeReport {
+            active: active().await,
+            installed: installed(&app).await,
+        })
+    })
+    .await
+}
@@ -0,0 +1,605 @@
+//! User-defined task fields (text, number, select, date).
+//!
+//! Definitions live in `task_cache.db` per user and sync to
+//! `public.custom_fields` with the tasks (last writer wins, deletions kept as
+//! tombstones). Values are a `custom_fields` object on the task row, keyed by
+//! field key, so they are cached, queued and pushed like any other column
+//! (`public.tasks.custom_fields`). Every value is checked against its
+//! definition before it is stored: numbers must be finite, selects one of the
+//! options, dates `YYYY-MM-DD`. Each field gets an expression index on the
+//! cache, which the smart list conditions on it (`smart_lists.rs`) use.
+
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+const MAX_KEY: usize = 40;
+const MAX_NAME: usize = 100;
+const MAX_OPTIONS: usize = 100;
+const MAX_TEXT: usize = 10_000;
+/// Most fields one user can define
+const MAX_FIELDS: usize = 50;
+/// Owner of the definitions in use: the signed-in user, or '' while signed out
+const OWNER: &str = "coalesce((SELECT value FROM meta WHERE key = 'user_id'), '')";
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Text,
+    Number,
+    Select,
+    Date,
+}
+
+impl FieldKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::Number => "number",
+            FieldKind::Select => "select",
+            FieldKind::Date => "date",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "text" => Some(FieldKind::Text),
+            "number" => Some(FieldKind::Number),
+            "select" => Some(FieldKind::Select),
+            "date" => Some(FieldKind::Date),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    /// Stable key of the values in `task.custom_fields`
+    pub key: String,
+    pub name: String,
+    pub kind: FieldKind,
+    /// Allowed values of a select field
+    pub options: Vec<String>,
+}
+
+impl CustomField {
+    /// The value as stored, or why it doesn't fit the field; null clears it
+    pub(crate) fn normalize(&self, value: &Value) -> Result<Value, String> {
+        let invalid = |expected: &str| {
+            Err(format!(
+                "Field '{}' expects {}, got {}",
+                self.name, expected, value
+            ))
+        };
+        match (self.kind, value) {
+            (_, Value::Null) => Ok(Value::Null),
+            (FieldKind::Text, Value::String(text)) if text.chars().count() <= MAX_TEXT => {
+                Ok(value.clone())
+            }
+            (FieldKind::Text, _) => invalid(&format!("text of at most {} characters", MAX_TEXT)),
+            (FieldKind::Number, Value::Number(n)) if n.as_f64().is_some_and(f64::is_finite) => {
+                Ok(value.clone())
+            }
+            (FieldKind::Number, _) => invalid("a number"),
+            (FieldKind::Select, Value::String(option)) if self.options.contains(option) => {
+                Ok(value.clone())
+            }
+            (FieldKind::Select, _) => invalid(&format!("one of {}", self.options.join(", "))),
+            (FieldKind::Date, Value::String(date)) => {
+                match chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+                    Ok(date) => Ok(Value::from(date.format("%Y-%m-%d").to_string())),
+                    Err(_) => invalid("a date (YYYY-MM-DD)"),
+                }
+            }
+            (FieldKind::Date, _) => invalid("a date (YYYY-MM-DD)"),
+        }
+    }
+}
+
+/// Cache expression that the field's index covers; `key` must be valid
+pub(crate) fn value_sql(key: &str) -> String {
+    format!("json_extract(data, '$.custom_fields.{}')", key)
+}
+
+fn valid_key(key: &str) -> bool {
+    key.len() <= MAX_KEY
+        && key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Key for a new field named `name`: lowercase words joined by underscores
+fn key_for(name: &str) -> String {
+    let mut key = String::new();
+    for word in name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if !key.is_empty() {
+            key.push('_');
+        }
+        key.push_str(word);
+    }
+    let key = key.trim_start_matches(|c: char| c.is_ascii_digit() || c == '_');
+    let key: String = key.chars().take(MAX_KEY - 4).collect();
+    if key.is_empty() {
+        "field".to_string()
+    } else {
+        key
+    }
+}
+
+fn index_sql(key: &str) -> String {
+    format!(
+        "CREATE INDEX IF NOT EXISTS idx_task_field_{} ON tasks({})",
+        key,
+        value_sql(key)
+    )
+}
+
+/// Create or drop the field's index to match its definition
+fn sync_index(conn: &Connection, key: &str, deleted: bool) -> rusqlite::Result<()> {
+    if !valid_key(key) {
+        return Ok(());
+    }
+    if deleted {
+        conn.execute_batch(&format!("DROP INDEX IF EXISTS idx_task_field_{}", key))
+    } else {
+        conn.execute_batch(&index_sql(key))
+    }
+}
+
+/// Live fields of the signed-in user, by key
+pub(crate) fn definitions(conn: &Connection) -> rusqlite::Result<HashMap<String, CustomField>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT key, name, kind, options FROM custom_fields WHERE NOT deleted AND user_id = {}",
+        OWNER
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })?;
+    let mut fields = HashMap::new();
+    for row in rows {
+        let (key, name, kind, options) = row?;
+        let Some(kind) = FieldKind::parse(&kind) else {
+            continue;
+        };
+        let options = options
+            .and_then(|o| serde_json::from_str(&o).ok())
+            .unwrap_or_default();
+        fields.insert(
+            key.clone(),
+            CustomField {
+                key,
+                name,
+                kind,
+                options,
+            },
+        );
+    }
+    Ok(fields)
+}
+
+/// `values` checked against `fields` and merged over `current`; null clears
+fn merged_values(
+    fields: &HashMap<String, CustomField>,
+    current: Option<&Value>,
+    values: &Value,
+) -> Result<Value, String> {
+    let Value::Object(values) = values else {
+        return Err("custom_fields must be an object keyed by field".to_string());
+    };
+    let mut merged: Map<String, Value> = current
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    for (key, value) in values {
+        let field = fields
+            .get(key)
+            .ok_or_else(|| format!("Unknown custom field '{}'", key))?;
+        match field.normalize(value)? {
+            Value::Null => {
+                merged.remove(key);
+            }
+            value => {
+                merged.insert(key.clone(), value);
+            }
+        }
+    }
+    Ok(Value::Object(merged))
+}
+
+/// Tasks being saved, by id
+type Tasks = Vec<(String, Map<String, Value>)>;
+
+/// Validate the custom field values of tasks about to be saved and merge
+/// them into the cached values, so a save only has to send what changed
+pub(crate) fn prepare(app: &AppHandle, tasks: Tasks) -> Result<Tasks, String> {
+    if tasks
+        .iter()
+        .all(|(_, task)| !task.contains_key("custom_fields"))
+    {
+        return Ok(tasks);
+    }
+    let (fields, current) = crate::offline::with_db(app, |conn| {
+        let mut current = HashMap::new();
+        for (id, _) in tasks
+            .iter()
+            .filter(|(_, t)| t.contains_key("custom_fields"))
+        {
+            let values: Option<String> = conn
+                .query_row(
+                    "SELECT json_extract(data, '$.custom_fields') FROM tasks WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+            if let Some(values) = values.and_then(|v| serde_json::from_str::<Value>(&v).ok()) {
+                current.insert(id.clone(), values);
+            }
+        }
+        Ok((definitions(conn)?, current))
+    })?;
+    tasks
+        .into_iter()
+        .map(|(id, mut task)| {
+            if let Some(values) = task.get("custom_fields") {
+                let merged = merged_values(&fields, current.get(&id), values)?;
+                task.insert("custom_fields".to_string(), merged);
+            }
+            Ok((id, task))
+        })
+        .collect()
+}
+
+type FieldRow = (String, String, String, Option<String>, bool, String);
+
+/// Give the definitions made while signed out to `user` (`set_sync_user`)
+pub(crate) fn adopt(conn: &Connection, user: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE OR IGNORE custom_fields SET user_id = ?1 WHERE user_id = ''",
+        params![user],
+    )
+}
+
+/// Push the user's changed definitions, then take the remote ones (the
+/// newest edit of a field wins). Skipped until the custom_fields migration
+/// is applied.
+pub(crate) async fn sync(
+    app: &AppHandle,
+    client: &tokio_postgres::Client,
+    user: &str,
+) -> Result<(), String> {
+    let migrated: bool = client
+        .query_one(
+            "SELECT to_regclass('public.custom_fields') IS NOT NULL",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("Failed to look up custom fields: {}", e))?
+        .get(0);
+    if !migrated {
+        return Ok(());
+    }
+
+    let changed: Vec<FieldRow> = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT key, name, kind, options, deleted, updated_at FROM custom_fields \
+             WHERE dirty AND user_id = ?1",
+        )?;
+        let rows = stmt.query_map(params![user], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?;
+        rows.collect()
+    })?;
+    for (key, name, kind, options, deleted, updated_at) in &changed {
+        client
+            .execute(
+                "INSERT INTO public.custom_fields \
+                     (user_id, key, name, kind, options, deleted, updated_at) \
+                 VALUES ($1::text::uuid, $2, $3, $4, $5::text::jsonb, $6, $7::text::timestamptz) \
+                 ON CONFLICT (user_id, key) DO UPDATE SET name = EXCLUDED.name, \
+                     kind = EXCLUDED.kind, options = EXCLUDED.options, \
+                     deleted = EXCLUDED.deleted, updated_at = EXCLUDED.updated_at \
+                 WHERE public.custom_fields.updated_at < EXCLUDED.updated_at",
+                &[&user, key, name, kind, options, deleted, updated_at],
+            )
+            .await
+            .map_err(|e| format!("Failed to push custom field '{}': {}", key, e))?;
+        crate::offline::with_db(app, |conn| {
+            // Unless edited again meanwhile
+            conn.execute(
+                "UPDATE custom_fields SET dirty = 0 \
+                 WHERE key = ?1 AND user_id = ?2 AND updated_at = ?3",
+                params![key, user, updated_at],
+            )
+        })?;
+    }
+
+    let remote: Vec<FieldRow> = client
+        .query(
+            "SELECT key, name, kind, options::text, deleted, updated_at::text \
+             FROM public.custom_fields WHERE user_id::text = $1",
+            &[&user],
+        )
+        .await
+        .map_err(|e| format!("Failed to pull custom fields: {}", e))?
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2), r.get(3), r.get(4), r.get(5)))
+        .collect();
+    crate::offline::with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        for (key, name, kind, options, deleted, updated_at) in &remote {
+            tx.execute(
+                "INSERT INTO custom_fields \
+                     (user_id, key, name, kind, options, deleted, updated_at, dirty) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0) \
+                 ON CONFLICT (user_id, key) DO UPDATE SET name = excluded.name, \
+                     kind = excluded.kind, options = excluded.options, \
+                     deleted = excluded.deleted, updated_at = excluded.updated_at \
+                 WHERE NOT custom_fields.dirty",
+                params![user, key, name, kind, options, deleted, updated_at],
+            )?;
+            sync_index(&tx, key, *deleted)?;
+        }
+        tx.commit()
+    })?;
+    Ok(())
+}
+
+/// Fields defined for the signed-in user's tasks, by name
+#[tauri::command]
+pub fn get_custom_fields(app: AppHandle) -> Result<Vec<CustomField>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_custom_fields")?;
+    let mut fields: Vec<CustomField> = crate::offline::with_db(&app, |conn| definitions(conn))?
+        .into_values()
+        .collect();
+    fields.sort_by_key(|f| f.name.to_lowercase());
+    Ok(fields)
+}
+
+/// Define a field, or rename one / change its options when `key` is given;
+/// a field's kind can't change once defined
+#[tauri::command]
+pub fn save_custom_field(
+    app: AppHandle,
+    key: Option<String>,
+    name: String,
+    kind: FieldKind,
+    options: Option<Vec<String>>,
+) -> Result<CustomField, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "save_custom_field")?;
+    crate::read_only::ensure_writable(&app, "save_custom_field")?;
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME {
+        return Err(format!("Field names are 1 to {} characters", MAX_NAME).into());
+    }
+    let mut options: Vec<String> = options
+        .unwrap_or_default()
+        .iter()
+        .map(|o| o.trim().to_string())
+        .filter(|o| !o.is_empty())
+        .collect();
+    options.dedup();
+    match kind {
+        FieldKind::Select if options.is_empty() || options.len() > MAX_OPTIONS => {
+            return Err(format!("Select fields need 1 to {} options", MAX_OPTIONS).into());
+        }
+        FieldKind::Select => {}
+        _ if !options.is_empty() => {
+            return Err("Only select fields have options".to_string().into());
+        }
+        _ => {}
+    }
+    if let Some(key) = &key {
+        if !valid_key(key) {
+            return Err(format!("Invalid field key '{}'", key).into());
+        }
+    }
+
+    let field = crate::offline::with_db(&app, |conn| {
+        let tx = conn.transaction()?;
+        let fields = definitions(&tx)?;
+        let user: String = tx.query_row(&format!("SELECT {}", OWNER), [], |row| row.get(0))?;
+        let key = match &key {
+            Some(key) => match fields.get(key) {
+                Some(existing) if existing.kind != kind => {
+                    return Ok(Err(format!(
+                        "Field '{}' is a {} field; its kind can't change",
+                        existing.name,
+                        existing.kind.as_str()
+                    )));
+                }
+                Some(_) => key.clone(),
+                None => return Ok(Err(format!("Unknown custom field '{}'", key))),
+            },
+            None if fields.len() >= MAX_FIELDS => {
+                return Ok(Err(format!("At most {} custom fields", MAX_FIELDS)));
+            }
+            None => {
+                // Keys of deleted fields aren't reused, so old values stay orphaned
+                let base = key_for(&name);
+                let taken = |key: &str| {
+                    tx.query_row(
+                        "SELECT 1 FROM custom_fields WHERE key = ?1 AND user_id = ?2",
+                        params![key, user],
+                        |_| Ok(()),
+                    )
+                    .optional()
+                    .map(|found| found.is_some())
+                };
+                let mut key = base.clone();
+                let mut n = 1;
+                while taken(&key)? {
+                    n += 1;
+                    key = format!("{}_{}", base, n);
+                }
+                key
+            }
+        };
+        let options_json =
+            (kind == FieldKind::Select).then(|| Value::from(options.clone()).to_string());
+        tx.execute(
+            "INSERT INTO custom_fields (user_id, key, name, kind, options, deleted, updated_at, dirty) \
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, 1) \
+             ON CONFLICT (user_id, key) DO UPDATE SET name = excluded.name, \
+                 options = excluded.options, updated_at = excluded.updated_at, dirty = 1",
+            params![
+                user,
+                key,
+                name,
+                kind.as_str(),
+                options_json,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        sync_index(&tx, &key, false)?;
+        tx.commit()?;
+        Ok(Ok(CustomField {
+            key,
+            name: name.clone(),
+            kind,
+            options: options.clone(),
+        }))
+    })??;
+    crate::offline::sync_soon(&app);
+    Ok(field)
+}
+
+/// Delete a field definition; values already on tasks are kept but no longer
+/// shown, filtered or accepted
+#[tauri::command]
+pub fn delete_custom_field(app: AppHandle, key: String) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "delete_custom_field")?;
+    crate::read_only::ensure_writable(&app, "delete_custom_field")?;
+    let deleted = crate::offline::with_db(&app, |conn| {
+        let deleted = conn.execute(
+            &format!(
+                "UPDATE custom_fields SET deleted = 1, dirty = 1, updated_at = ?2 \
+                 WHERE key = ?1 AND NOT deleted AND user_id = {}",
+                OWNER
+            ),
+            params![key, chrono::Utc::now().to_rfc3339()],
+        )?;
+        sync_index(conn, &key, true)?;
+        Ok(deleted)
+    })?;
+    if deleted == 0 {
+        return Err(format!("Unknown custom field '{}'", key).into());
+    }
+    crate::offline::sync_soon(&app);
+    Ok(())
+}
+
+/// Set (or with null, clear) one field of a task; returns the cached row
+#[tauri::command]
+pub fn set_task_field(
+    app: AppHandle,
+    task_id: String,
+    key: String,
+    value: Value,
+) -> Result<Value, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "set_task_field")?;
+    crate::read_only::ensure_writable(&app, "set_task_field")?;
+    if crate::offline::cached_task(&app, &task_id)?.is_none() {
+        return Err(format!("Task {} is not cached", task_id).into());
+    }
+    let task = Map::from_iter([
+        ("id".to_string(), Value::String(task_id)),
+        (
+            "custom_fields".to_string(),
+            Value::Object(Map::from_iter([(key, value)])),
+        ),
+    ]);
+    Ok(crate::offline::save_task(&app, task)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> HashMap<String, CustomField> {
+        [
+            ("effort", FieldKind::Number, vec![]),
+            ("stage", FieldKind::Select, vec!["lead", "won"]),
+            ("launch", FieldKind::Date, vec![]),
+            ("notes", FieldKind::Text, vec![]),
+        ]
+        .into_iter()
+        .map(|(key, kind, options)| {
+            (
+                key.to_string(),
+                CustomField {
+                    key: key.to_string(),
+                    name: key.to_string(),
+                    kind,
+                    options: options.into_iter().map(str::to_string).collect(),
+                },
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn values_are_checked_against_their_field() {
+        let fields = fields();
+        let check = |key: &str, value: Value| fields[key].normalize(&value);
+        assert_eq!(check("effort", Value::from(2.5)), Ok(Value::from(2.5)));
+        assert!(check("effort", Value::from("3")).is_err());
+        assert_eq!(check("stage", Value::from("won")), Ok(Value::from("won")));
+        assert!(check("stage", Value::from("lost")).is_err());
+        assert_eq!(
+            check("launch", Value::from(" 2026-03-01 ")),
+            Ok(Value::from("2026-03-01"))
+        );
+        assert!(check("launch", Value::from("2026-02-30")).is_err());
+        assert!(check("notes", Value::from(1)).is_err());
+        assert_eq!(check("notes", Value::Null), Ok(Value::Null));
+    }
+
+    #[test]
+    fn saved_values_merge_over_the_cached_ones() {
+        let fields = fields();
+        let current = serde_json::json!({"effort": 3, "stage": "lead"});
+        assert_eq!(
+            merged_values(
+                &fields,
+                Some(&current),
+                &serde_json::json!({"stage": "won", "effort": null, "launch": "2026-01-05"})
+            ),
+            Ok(serde_json::json!({"stage": "won", "launch": "2026-01-05"}))
+        );
+        assert!(merged_values(&fields, None, &serde_json::json!({"owner": "me"})).is_err());
+        assert!(merged_values(&fields, None, &serde_json::json!(["effort"])).is_err());
+    }
+
+    #[test]
+    fn keys_come_from_names() {
+        assert_eq!(key_for("Deal Stage"), "deal_stage");
+        assert_eq!(key_for("2nd opinion?"), "nd_opinion");
+        assert_eq!(key_for("¿?"), "field");
+        assert!(valid_key(&key_for(&"long name ".repeat(20))));
+        assert!(!valid_key("stage'); DROP TABLE tasks; --"));
+    }
+}
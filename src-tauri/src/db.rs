@@ -0,0 +1,184 @@
+//! Direct Postgres access to the local Supabase database.
+//!
+//! Schema verification connects to the database port (from the discovered
+//! endpoints) instead of going through the REST gateway, so a Kong or auth
+//! failure is reported as a connection problem rather than a missing schema.
+//! `information_schema` is compared against the tables and columns the app
+//! relies on, and the result lists exactly what is missing.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tokio_postgres::NoTls;
+
+use crate::error::FlowStateError;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tables (and the columns of each) the frontend depends on, from supabase/migrations
+const REQUIRED_SCHEMA: &[(&str, &[&str])] = &[
+    (
+        "tasks",
+        &[
+            "id",
+            "user_id",
+            "project_id",
+            "title",
+            "status",
+            "priority",
+            "due_date",
+            "position",
+            "is_deleted",
+            "updated_at",
+        ],
+    ),
+    (
+        "projects",
+        &[
+            "id",
+            "user_id",
+            "name",
+            "parent_id",
+            "is_deleted",
+            "updated_at",
+        ],
+    ),
+    (
+        "groups",
+        &[
+            "id",
+            "user_id",
+            "name",
+            "position_json",
+            "is_deleted",
+            "updated_at",
+        ],
+    ),
+    ("user_settings", &["id", "user_id"]),
+    (
+        "timer_sessions",
+        &[
+            "id",
+            "user_id",
+            "task_id",
+            "start_time",
+            "duration",
+            "is_active",
+        ],
+    ),
+];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingColumn {
+    pub table: String,
+    pub column: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaReport {
+    pub ok: bool,
+    pub missing_tables: Vec<String>,
+    /// Missing columns of tables that do exist
+    pub missing_columns: Vec<MissingColumn>,
+}
+
+impl SchemaReport {
+    /// "tasks, projects.parent_id"
+    pub fn summary(&self) -> String {
+        self.missing_tables
+            .iter()
+            .cloned()
+            .chain(
+                self.missing_columns
+                    .iter()
+                    .map(|c| format!("{}.{}", c.table, c.column)),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub async fn connect(app: &AppHandle) -> Result<tokio_postgres::Client, String> {
+    let endpoints = crate::endpoints::get(app).await;
+    let host = crate::docker_context::api_host();
+
+    let mut config = tokio_postgres::Config::new();
+    config
+        .host(&host)
+        .port(endpoints.db_port)
+        .user("postgres")
+        .password("postgres")
+        .dbname("postgres")
+        .connect_timeout(CONNECT_TIMEOUT);
+
+    let (client, connection) = config.connect(NoTls).await.map_err(|e| {
+        format!(
+            "Failed to connect to the local database at {}:{}: {}",
+            host, endpoints.db_port, e
+        )
+    })?;
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = connection.await {
+            log::warn!("Local database connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// Compare the public schema with REQUIRED_SCHEMA
+pub async fn check_schema(app: &AppHandle) -> Result<SchemaReport, String> {
+    let client = connect(app).await?;
+    let tables: Vec<&str> = REQUIRED_SCHEMA.iter().map(|(table, _)| *table).collect();
+
+    let rows = client
+        .query(
+            "SELECT table_name::text, column_name::text FROM information_schema.columns \
+             WHERE table_schema = 'public' AND table_name = ANY($1)",
+            &[&tables],
+        )
+        .await
+        .map_err(|e| format!("Failed to query information_schema: {}", e))?;
+
+    let mut present: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for row in rows {
+        present.entry(row.get(0)).or_default().insert(row.get(1));
+    }
+
+    let mut missing_tables = Vec::new();
+    let mut missing_columns = Vec::new();
+    for (table, columns) in REQUIRED_SCHEMA {
+        let Some(existing) = present.get(*table) else {
+            missing_tables.push(table.to_string());
+            continue;
+        };
+        missing_columns.extend(
+            columns
+                .iter()
+                .filter(|column| !existing.contains(**column))
+                .map(|column| MissingColumn {
+                    table: table.to_string(),
+                    column: column.to_string(),
+                }),
+        );
+    }
+
+    Ok(SchemaReport {
+        ok: missing_tables.is_empty() && missing_columns.is_empty(),
+        missing_tables,
+        missing_columns,
+    })
+}
+
+/// Check the local database for the tables and columns the app needs
+#[tauri::command]
+pub async fn verify_schema(app: AppHandle) -> Result<SchemaReport, FlowStateError> {
+    crate::trace::scope(
+        "verify_schema",
+        async move { Ok(check_schema(&app).await?) },
+    )
+    .await
+}
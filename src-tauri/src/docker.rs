@@ -0,0 +1,128 @@
+//! Direct Docker Engine API client.
+//!
+//! GUI launches often don't have the docker CLI on PATH even though the daemon
+//! is running, so status checks talk to the Engine API over its local socket
+//! (Unix socket, or the named pipe on Windows) and only fall back to the CLI
+//! when the socket is unreachable. Which socket to use is decided by
+//! `container_runtime`. Requests are plain HTTP/1.0 so the response is never
+//! chunked and ends when the daemon closes the connection.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::FlowStateError;
+use crate::parsers;
+
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupabaseContainer {
+    pub name: String,
+    /// Service part of the name (db, kong, auth, rest, realtime, storage, ...)
+    pub service: String,
+    pub image: String,
+    pub state: String,
+    pub status: String,
+    /// healthy | unhealthy | starting, when the container has a healthcheck
+    pub health: Option<String>,
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> std::io::Result<impl Read + Write> {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn connect(path: &Path) -> std::io::Result<impl Read + Write> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+}
+
+/// GET an Engine API path; returns the raw response (status line, headers, body)
+fn get_blocking(socket: &Path, api_path: &str) -> Result<Vec<u8>, String> {
+    let mut stream = connect(socket)
+        .map_err(|e| format!("Docker socket {} unavailable: {}", socket.display(), e))?;
+
+    let request = format!("GET {} HTTP/1.0\r\nHost: docker\r\n\r\n", api_path);
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("Docker API request failed: {}", e))?;
+
+    let mut response = Vec::new();
+    stream
+        .take(parsers::MAX_ENGINE_RESPONSE as u64 + 1)
+        .read_to_end(&mut response)
+        .map_err(|e| format!("Docker API read failed: {}", e))?;
+    Ok(response)
+}
+
+/// GET an Engine API path and return the body of a 200 response
+async fn get(socket: PathBuf, api_path: &'static str) -> Result<Vec<u8>, String> {
+    let response = tauri::async_runtime::spawn_blocking(move || get_blocking(&socket, api_path))
+        .await
+        .map_err(|e| format!("Docker API task failed: {}", e))??;
+
+    let (status, body) = parsers::parse_engine_response(&response)
+        .map_err(|e| format!("Unexpected Docker API response: {}", e))?;
+    if status != 200 {
+        return Err(format!("Docker API {} returned {}", api_path, status));
+    }
+    Ok(body.to_vec())
+}
+
+/// Daemon version straight from the Engine API at `socket`
+pub async fn server_version(socket: PathBuf) -> Result<String, String> {
+    let body = get(socket, "/version").await?;
+    parsers::parse_engine_version(&body)
+        .map(|v| v.version)
+        .map_err(|e| format!("Unexpected Docker version response: {}", e))
+}
+
+/// "Up 5 minutes (healthy)" -> "healthy"
+fn health_from_status(status: &str) -> Option<String> {
+    let inner = status.rsplit_once('(')?.1.strip_suffix(')')?;
+    let health = inner.strip_prefix("health: ").unwrap_or(inner);
+    matches!(health, "healthy" | "unhealthy" | "starting").then(|| health.to_string())
+}
+
+/// List the containers of the local Supabase stack with their state
+#[tauri::command]
+pub async fn inspect_supabase_containers() -> Result<Vec<SupabaseContainer>, FlowStateError> {
+    let runtime = crate::container_runtime::active()
+        .await
+        .ok_or_else(|| "No container runtime is answering on a known socket".to_string())?;
+    let body = get(runtime.socket, "/containers/json?all=1").await?;
+    let containers = parsers::parse_container_list(&body)
+        .map_err(|e| format!("Unexpected Docker container list: {}", e))?;
+
+    // The CLI names containers supabase_<service>_<project_id>
+    let suffix = format!("_{}", crate::multi_user::project_id());
+
+    Ok(containers
+        .into_iter()
+        .filter_map(|c| {
+            let name = c.names.first()?.trim_start_matches('/').to_string();
+            let service = name
+                .strip_prefix("supabase_")?
+                .strip_suffix(&suffix)?
+                .to_string();
+            Some(SupabaseContainer {
+                health: health_from_status(&c.status),
+                name,
+                service,
+                image: c.image,
+                state: c.state,
+                status: c.status,
+            })
+        })
+        .collect())
+}
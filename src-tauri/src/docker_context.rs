@@ -0,0 +1,191 @@
+//! Docker context selection for daemons that don't run locally.
+//!
+//! When a context is selected, docker subprocesses get `DOCKER_CONTEXT`, the
+//! Supabase CLI gets the context's endpoint as `DOCKER_HOST`, the Engine API
+//! probe only uses the context's socket (or defers to the CLI for tcp/ssh
+//! endpoints), and the Supabase health checks target the context's host.
+//! The selection persists in `docker-context.json`.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::Command;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+pub(crate) const CONTEXT_STORE: &str = "docker-context.json";
+const CONTEXT_KEY: &str = "selected";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DockerContext {
+    pub name: String,
+    pub description: String,
+    /// unix:///var/run/docker.sock, tcp://host:2375, ssh://user@host, ...
+    pub endpoint: String,
+    /// Current context of the docker CLI itself
+    pub cli_current: bool,
+    /// Selected for FlowState's checks
+    pub selected: bool,
+}
+
+#[derive(Clone)]
+struct Selection {
+    name: String,
+    endpoint: String,
+}
+
+static SELECTED: Mutex<Option<Selection>> = Mutex::new(None);
+
+fn selection() -> Option<Selection> {
+    SELECTED.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn set_selection(selection: Option<Selection>) {
+    *SELECTED.lock().unwrap_or_else(|e| e.into_inner()) = selection;
+}
+
+/// Endpoint of the selected context, if any
+pub fn selected_endpoint() -> Option<String> {
+    selection().map(|s| s.endpoint)
+}
+
+/// Host the selected daemon (and so the Supabase stack) runs on
+pub fn api_host() -> String {
+    let Some(endpoint) = selected_endpoint() else {
+        return crate::LOCAL_API_HOST.to_string();
+    };
+
+    let remote = endpoint
+        .strip_prefix("tcp://")
+        .or_else(|| endpoint.strip_prefix("ssh://"))
+        .map(|rest| {
+            let authority = rest.split('/').next().unwrap_or_default();
+            let host_port = authority.rsplit('@').next().unwrap_or_default();
+            host_port.split(':').next().unwrap_or_default().to_string()
+        });
+
+    match remote {
+        Some(host) if !host.is_empty() => host,
+        _ => crate::LOCAL_API_HOST.to_string(),
+    }
+}
+
+/// Point docker / supabase subprocesses at the selected context
+pub fn apply_env(program: &str, cmd: Command) -> Command {
+    match (selection(), program) {
+        (Some(s), "docker") => cmd.env("DOCKER_CONTEXT", s.name),
+        (Some(s), "supabase") => cmd.env("DOCKER_HOST", s.endpoint),
+        _ => cmd,
+    }
+}
+
+async fn list(app: &AppHandle) -> Result<Vec<DockerContext>, String> {
+    let output = crate::trace::command(app, "docker")
+        .args(["context", "ls", "--format", "{{json .}}"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("Failed to list docker contexts: {}", stderr));
+    }
+
+    let selected = selection().map(|s| s.name);
+    let entries = crate::parsers::parse_docker_contexts(&output.stdout)
+        .map_err(|e| format!("Unexpected 'docker context ls' output: {}", e))?;
+
+    Ok(entries
+        .into_iter()
+        .map(|c| DockerContext {
+            selected: selected.as_deref() == Some(c.name.as_str()),
+            name: c.name,
+            description: c.description,
+            endpoint: c.docker_endpoint,
+            cli_current: c.current,
+        })
+        .collect())
+}
+
+/// Restore the persisted selection
+pub fn init(app: &AppHandle) {
+    let store = match app.store(CONTEXT_STORE) {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Failed to open {}: {}", CONTEXT_STORE, e);
+            return;
+        }
+    };
+
+    let saved = store.get(CONTEXT_KEY).and_then(|v| {
+        Some(Selection {
+            name: v.get("name")?.as_str()?.to_string(),
+            endpoint: v.get("endpoint")?.as_str()?.to_string(),
+        })
+    });
+    if let Some(s) = &saved {
+        log::info!("Using docker context '{}' ({})", s.name, s.endpoint);
+    }
+    set_selection(saved);
+}
+
+/// Docker contexts known to the CLI, with FlowState's selection marked
+#[tauri::command]
+pub async fn list_docker_contexts(app: AppHandle) -> Result<Vec<DockerContext>, FlowStateError> {
+    crate::trace::scope("list_docker_contexts", async move { Ok(list(&app).await?) }).await
+}
+
+/// Select the context used by Docker and Supabase checks (None = local daemon)
+#[tauri::command]
+pub async fn select_docker_context(
+    app: AppHandle,
+    name: Option<String>,
+) -> Result<Option<DockerContext>, FlowStateError> {
+    crate::trace::scope("select_docker_context", async move {
+        crate::read_only::ensure_writable(&app, "select_docker_context")?;
+        let store = app
+            .store(CONTEXT_STORE)
+            .map_err(|e| format!("Failed to open {}: {}", CONTEXT_STORE, e))?;
+
+        let Some(name) = name else {
+            store.delete(CONTEXT_KEY);
+            store
+                .save()
+                .map_err(|e| format!("Failed to save {}: {}", CONTEXT_STORE, e))?;
+            set_selection(None);
+            log::info!("Docker context selection cleared");
+            return Ok(None);
+        };
+
+        let mut context = list(&app)
+            .await?
+            .into_iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| format!("Unknown docker context: {}", name))?;
+
+        store.set(
+            CONTEXT_KEY,
+            serde_json::json!({ "name": context.name, "endpoint": context.endpoint }),
+        );
+        store
+            .save()
+            .map_err(|e| format!("Failed to save {}: {}", CONTEXT_STORE, e))?;
+
+        set_selection(Some(Selection {
+            name: context.name.clone(),
+            endpoint: context.endpoint.clone(),
+        }));
+        log::info!(
+            "Selected docker context '{}' ({})",
+            context.name,
+            context.endpoint
+        );
+
+        context.selected = true;
+        Ok(Some(context))
+    })
+    .await
+}
@@ -0,0 +1,575 @@
+//! Near-duplicate tasks and merging them.
+//!
+//! Imports from other tools often create the same task twice with slightly
+//! different titles. `find_duplicate_tasks` compares the titles of the cached
+//! tasks (`offline.rs`) by trigram similarity, the way pg_trgm's
+//! `similarity()` does: each lowercased word is padded with two spaces in
+//! front and one behind, and the score is the trigrams both titles share over
+//! all distinct trigrams of the two. Pairs at or above the threshold are
+//! reported, most similar first.
+//!
+//! `merge_tasks` folds tasks into the one kept: their tags, embedded
+//! subtasks and pomodoro counts go to it, child tasks and dependencies are
+//! pointed at it, and so is everything recorded against them (focus history
+//! in `pomodoro_history`, time entries, attachments and recorded focus
+//! sessions). The merged tasks are then deleted through the cache and
+//! outbox. Each database changes in its own transaction and the cache goes
+//! last, so a merge that fails part way leaves the merged tasks in place with
+//! their records on the kept task, and running it again finishes the job.
+//! Time entries locked by an invoice (`entry_locks.rs`) refuse the merge.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+const DEFAULT_THRESHOLD: f64 = 0.6;
+/// Pairs reported at most
+const MAX_PAIRS: usize = 500;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatePair {
+    /// The older task of the two, the natural one to keep
+    pub task_id: String,
+    pub title: String,
+    pub duplicate_id: String,
+    pub duplicate_title: String,
+    pub similarity: f64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    /// Cached row of the kept task after the merge
+    pub task: Value,
+    pub merged: Vec<String>,
+    /// Other tasks whose parent or dependencies now point at the kept task
+    pub relinked_tasks: usize,
+    pub focus_history: u64,
+    pub time_entries: usize,
+    pub attachments: usize,
+    pub focus_sessions: usize,
+}
+
+/// Distinct trigrams of `title`, as pg_trgm's `show_trgm`
+fn trigrams(title: &str) -> HashSet<String> {
+    let lower = title.to_lowercase();
+    let mut trigrams = HashSet::new();
+    for word in lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let padded: Vec<char> = format!("  {} ", word).chars().collect();
+        for window in padded.windows(3) {
+            trigrams.insert(window.iter().collect());
+        }
+    }
+    trigrams
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    let all = a.len() + b.len() - shared;
+    if all == 0 {
+        0.0
+    } else {
+        shared as f64 / all as f64
+    }
+}
+
+/// Pairs of `(id, title)` tasks at least `threshold` similar, most similar
+/// first; tasks come oldest first, so the first of a pair is the older one
+fn duplicate_pairs(tasks: &[(String, String)], threshold: f64) -> Vec<DuplicatePair> {
+    let grams: Vec<HashSet<String>> = tasks.iter().map(|(_, title)| trigrams(title)).collect();
+    let mut pairs = Vec::new();
+    for i in 0..tasks.len() {
+        for j in i + 1..tasks.len() {
+            let score = similarity(&grams[i], &grams[j]);
+            if score >= threshold && score > 0.0 {
+                pairs.push(DuplicatePair {
+                    task_id: tasks[i].0.clone(),
+                    title: tasks[i].1.clone(),
+                    duplicate_id: tasks[j].0.clone(),
+                    duplicate_title: tasks[j].1.clone(),
+                    similarity: score,
+                });
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs.truncate(MAX_PAIRS);
+    pairs
+}
+
+/// Fields of the kept row that change when `merged` are folded into it
+fn merged_fields(keep: &Map<String, Value>, merged: &[Map<String, Value>]) -> Map<String, Value> {
+    let mut fields = Map::new();
+    let rows = || std::iter::once(keep).chain(merged.iter());
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in rows()
+        .filter_map(|row| row.get("tags").and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    if !tags.is_empty() && Some(&Value::from(tags.clone())) != keep.get("tags") {
+        fields.insert("tags".to_string(), Value::from(tags));
+    }
+
+    let subtasks: Vec<Value> = rows()
+        .filter_map(|row| row.get("subtasks").and_then(Value::as_array))
+        .flatten()
+        .cloned()
+        .collect();
+    if !subtasks.is_empty() && Some(&Value::from(subtasks.clone())) != keep.get("subtasks") {
+        fields.insert("subtasks".to_string(), Value::from(subtasks));
+    }
+
+    // Values of the kept task win
+    let mut custom_fields = Map::new();
+    for values in rows().filter_map(|row| row.get("custom_fields").and_then(Value::as_object)) {
+        for (key, value) in values {
+            if !value.is_null() && !custom_fields.contains_key(key) {
+                custom_fields.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    if !custom_fields.is_empty()
+        && Some(&Value::Object(custom_fields.clone())) != keep.get("custom_fields")
+    {
+        fields.insert("custom_fields".to_string(), Value::Object(custom_fields));
+    }
+
+    for counter in ["completed_pomodoros", "total_pomodoros"] {
+        let extra: i64 = merged
+            .iter()
+            .filter_map(|row| row.get(counter).and_then(Value::as_i64))
+            .sum();
+        if extra > 0 {
+            let own = keep.get(counter).and_then(Value::as_i64).unwrap_or(0);
+            fields.insert(counter.to_string(), Value::from(own + extra));
+        }
+    }
+
+    let blank = |row: &Map<String, Value>| {
+        row.get("description")
+            .and_then(Value::as_str)
+            .map_or(true, |d| d.trim().is_empty())
+    };
+    if blank(keep) {
+        if let Some(description) = merged.iter().find(|row| !blank(row)) {
+            fields.insert(
+                "description".to_string(),
+                description["description"].clone(),
+            );
+        }
+    }
+    fields
+}
+
+/// Fields of a task that change when it points at merged tasks: its parent
+/// and its dependencies move to `keep_id` (the kept task itself loses such a
+/// parent)
+fn relinked_fields(
+    row: &Map<String, Value>,
+    merged: &HashSet<&str>,
+    keep_id: &str,
+) -> Map<String, Value> {
+    let mut fields = Map::new();
+    let own_id = row.get("id").and_then(Value::as_str);
+    if row
+        .get("parent_task_id")
+        .and_then(Value::as_str)
+        .is_some_and(|parent| merged.contains(parent))
+    {
+        let parent = if own_id == Some(keep_id) {
+            Value::Null
+        } else {
+            Value::from(keep_id)
+        };
+        fields.insert("parent_task_id".to_string(), parent);
+    }
+    if let Some(depends_on) = row.get("depends_on").and_then(Value::as_array) {
+        if depends_on
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|id| merged.contains(id))
+        {
+            let mut next: Vec<&str> = Vec::new();
+            for id in depends_on.iter().filter_map(Value::as_str) {
+                let id = if merged.contains(id) { keep_id } else { id };
+                if Some(id) != own_id && !next.contains(&id) {
+                    next.push(id);
+                }
+            }
+            fields.insert("depends_on".to_string(), Value::from(next));
+        }
+    }
+    fields
+}
+
+/// Whether a cached task has been pulled from the database
+fn is_synced(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT updated_at IS NOT NULL FROM tasks WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )
+}
+
+/// Cached tasks that aren't deleted, by id, oldest first
+fn cached_rows(conn: &Connection) -> rusqlite::Result<Vec<Map<String, Value>>> {
+    let mut stmt = conn.prepare(
+        "SELECT data FROM tasks \
+         WHERE coalesce(json_extract(data, '$.is_deleted'), 0) = 0 \
+         ORDER BY json_extract(data, '$.created_at'), id",
+    )?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut cached = Vec::new();
+    for data in rows {
+        if let Ok(row) = serde_json::from_str::<Map<String, Value>>(&data?) {
+            cached.push(row);
+        }
+    }
+    Ok(cached)
+}
+
+fn id_of(row: &Map<String, Value>) -> &str {
+    row.get("id").and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Point the focus history of the merged tasks at the kept one, before the
+/// remote deletes would set it to NULL
+async fn move_focus_history(
+    app: &AppHandle,
+    user: &str,
+    keep_id: &str,
+    merged: &[String],
+) -> Result<u64, String> {
+    let client = crate::db::connect(app)
+        .await
+        .map_err(|e| format!("Focus history can't be moved while offline: {}", e))?;
+    client
+        .execute(
+            "UPDATE public.pomodoro_history SET task_id = $1::text::uuid \
+             WHERE user_id::text = $2 AND task_id::text = ANY($3)",
+            &[&keep_id, &user, &merged],
+        )
+        .await
+        .map_err(|e| format!("Failed to move focus history: {}", e))
+}
+
+/// First locked time entry of the merged tasks and its invoice
+fn locked_entry(
+    conn: &Connection,
+    merged: &str,
+) -> rusqlite::Result<Option<(i64, Option<String>)>> {
+    conn.query_row(
+        "SELECT l.entry_id, l.invoice_id FROM time_entry_locks l \
+         JOIN time_entries e ON e.id = l.entry_id \
+         WHERE e.task_id IN (SELECT value FROM json_each(?1)) LIMIT 1",
+        params![merged],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Point `table.task_id` of the merged tasks at the kept task
+fn move_rows(
+    conn: &Connection,
+    table: &str,
+    keep_id: &str,
+    merged: &str,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        &format!(
+            "UPDATE {} SET task_id = ?1 WHERE task_id IN (SELECT value FROM json_each(?2))",
+            table
+        ),
+        params![keep_id, merged],
+    )
+}
+
+/// Finished time entries of the merged tasks, as they'll be once moved
+fn moving_entries(
+    conn: &Connection,
+    keep_id: &str,
+    merged: &str,
+) -> rusqlite::Result<Vec<crate::time_tracking::TimeEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM time_entries \
+         WHERE task_id IN (SELECT value FROM json_each(?1)) AND ended_at IS NOT NULL ORDER BY id",
+        crate::time_tracking::COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![merged], crate::time_tracking::TimeEntry::from_row)?;
+    rows.map(|entry| {
+        entry.map(|entry| crate::time_tracking::TimeEntry {
+            task_id: keep_id.to_string(),
+            ..entry
+        })
+    })
+    .collect()
+}
+
+/// Pairs of cached tasks whose titles are at least `threshold` similar (0 to
+/// 1, default 0.6), most similar first
+#[tauri::command]
+pub fn find_duplicate_tasks(
+    app: AppHandle,
+    threshold: Option<f64>,
+) -> Result<Vec<DuplicatePair>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "find_duplicate_tasks")?;
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("Threshold must be between 0 and 1, got {}", threshold).into());
+    }
+    let tasks: Vec<(String, String)> = crate::offline::with_db(&app, |conn| cached_rows(conn))?
+        .iter()
+        .filter_map(|row| {
+            let title = row.get("title").and_then(Value::as_str)?;
+            Some((id_of(row).to_string(), title.to_string()))
+        })
+        .collect();
+    Ok(duplicate_pairs(&tasks, threshold))
+}
+
+/// Merge `merge_ids` into `keep_id` and delete them
+#[tauri::command]
+pub async fn merge_tasks(
+    app: AppHandle,
+    keep_id: String,
+    merge_ids: Vec<String>,
+) -> Result<MergeResult, FlowStateError> {
+    crate::trace::scope("merge_tasks", async move {
+        crate::read_only::ensure_writable(&app, "merge_tasks")?;
+        crate::app_lock::ensure_unlocked(&app, "merge_tasks")?;
+        let mut merged: Vec<String> = Vec::new();
+        for id in merge_ids {
+            if id == keep_id {
+                return Err("A task can't be merged into itself".into());
+            }
+            if !merged.contains(&id) {
+                merged.push(id);
+            }
+        }
+        if merged.is_empty() {
+            return Err("No tasks to merge".into());
+        }
+
+        let rows: HashMap<String, Map<String, Value>> =
+            crate::offline::with_db(&app, |conn| cached_rows(conn))?
+                .into_iter()
+                .map(|row| (id_of(&row).to_string(), row))
+                .collect();
+        for id in std::iter::once(&keep_id).chain(merged.iter()) {
+            if !rows.contains_key(id) {
+                return Err(format!("Task {} is not in the task cache", id).into());
+            }
+        }
+
+        // History can only point at a task the database has
+        let (keep_synced, merging_synced) = crate::offline::with_db(&app, |conn| {
+            let mut merging_synced = false;
+            for id in &merged {
+                merging_synced |= is_synced(conn, id)?;
+            }
+            Ok((is_synced(conn, &keep_id)?, merging_synced))
+        })?;
+        let focus_history = match crate::offline::current_user(&app) {
+            Some(_) if merging_synced && !keep_synced => {
+                return Err(format!(
+                    "Task {} hasn't synced yet; sync before merging tasks into it",
+                    keep_id
+                )
+                .into());
+            }
+            Some(user) if merging_synced => {
+                move_focus_history(&app, &user, &keep_id, &merged).await?
+            }
+            _ => 0,
+        };
+
+        let merged_json = Value::from(merged.clone()).to_string();
+        let locked = crate::time_tracking::with_db(&app, |conn| locked_entry(conn, &merged_json))?;
+        if let Some((entry_id, invoice_id)) = locked {
+            crate::entry_locks::refuse_locked(entry_id, Some(invoice_id))?;
+        }
+        let (time_entries, moved) = crate::time_tracking::with_db(&app, |conn| {
+            let tx = conn.transaction()?;
+            let moved = moving_entries(&tx, &keep_id, &merged_json)?;
+            let count = move_rows(&tx, "time_entries", &keep_id, &merged_json)?;
+            tx.commit()?;
+            Ok((count, moved))
+        })?;
+        // Re-chain them under their new task
+        for entry in &moved {
+            crate::audit::record_time_entry(&app, entry);
+        }
+        let attachments = crate::attachments::with_db(&app, |conn| {
+            move_rows(conn, "attachments", &keep_id, &merged_json)
+        })?;
+        let focus_sessions = crate::sessions::with_db(&app, |conn| {
+            move_rows(conn, "focus_sessions", &keep_id, &merged_json)
+        })?;
+
+        let merging: HashSet<&str> = merged.iter().map(String::as_str).collect();
+        let mut keep_fields = merged_fields(
+            &rows[&keep_id],
+            &merged.iter().map(|id| rows[id].clone()).collect::<Vec<_>>(),
+        );
+        keep_fields.extend(relinked_fields(&rows[&keep_id], &merging, &keep_id));
+        let relinked: Vec<(String, Map<String, Value>)> = rows
+            .iter()
+            .filter(|(id, _)| **id != keep_id && !merging.contains(id.as_str()))
+            .map(|(id, row)| (id.clone(), relinked_fields(row, &merging, &keep_id)))
+            .filter(|(_, fields)| !fields.is_empty())
+            .collect();
+        let stored = crate::offline::with_db(&app, |conn| {
+            crate::bulk::in_batch(conn, |conn| {
+                let mut stored = vec![crate::offline::store_task(conn, &keep_id, keep_fields)?];
+                for (id, fields) in relinked.iter().cloned() {
+                    stored.push(crate::offline::store_task(conn, &id, fields)?);
+                }
+                for id in &merged {
+                    crate::offline::remove_task(conn, id)?;
+                }
+                Ok(stored)
+            })
+        })?;
+
+        crate::search::upsert(&app, &stored);
+        for id in &merged {
+            crate::search::remove(&app, id);
+        }
+        if attachments > 0 {
+            crate::events::publish(
+                &app,
+                "attachment://changed",
+                &crate::attachments::AttachmentsChanged {
+                    task_id: keep_id.clone(),
+                },
+            );
+        }
+        crate::offline::sync_soon(&app);
+        log::info!("Merged {} tasks into {}", merged.len(), keep_id);
+
+        Ok(MergeResult {
+            task: serde_json::from_str(&stored[0]).map_err(|e| e.to_string())?,
+            merged,
+            relinked_tasks: relinked.len(),
+            focus_history,
+            time_entries,
+            attachments,
+            focus_sessions,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    fn tasks(titles: &[&str]) -> Vec<(String, String)> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(i, title)| (format!("t{}", i), title.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn trigrams_match_pg_trgm() {
+        let mut cat: Vec<String> = trigrams("Cat").into_iter().collect();
+        cat.sort();
+        assert_eq!(cat, ["  c", " ca", "at ", "cat"]);
+        assert!(trigrams(" -- ").is_empty());
+        assert_eq!(
+            similarity(&trigrams("Fix login"), &trigrams("fix LOGIN!")),
+            1.0
+        );
+        assert_eq!(similarity(&trigrams(""), &trigrams("")), 0.0);
+    }
+
+    #[test]
+    fn similar_titles_pair_up_oldest_first() {
+        let pairs = duplicate_pairs(
+            &tasks(&[
+                "Write quarterly report",
+                "Buy milk",
+                "Write the quarterly report",
+                "write quarterly report",
+            ]),
+            0.6,
+        );
+        let ids: Vec<(&str, &str)> = pairs
+            .iter()
+            .map(|p| (p.task_id.as_str(), p.duplicate_id.as_str()))
+            .collect();
+        assert_eq!(ids, [("t0", "t3"), ("t0", "t2"), ("t2", "t3")]);
+        assert_eq!(pairs[0].similarity, 1.0);
+        assert!(pairs[1].similarity < 1.0 && pairs[1].similarity >= 0.6);
+        assert!(duplicate_pairs(&tasks(&["Buy bread", "Call Sam"]), 0.0).is_empty());
+    }
+
+    #[test]
+    fn merging_folds_tags_subtasks_and_counts() {
+        let keep = row(serde_json::json!({
+            "id": "a", "tags": ["Work"], "subtasks": [{"id": "s1"}],
+            "completed_pomodoros": 2, "description": ""
+        }));
+        let merged = [
+            row(serde_json::json!({
+                "id": "b", "tags": ["work", "urgent"], "subtasks": [{"id": "s2"}],
+                "completed_pomodoros": 3, "total_pomodoros": 1, "description": "Notes"
+            })),
+            row(serde_json::json!({ "id": "c", "description": "Other" })),
+        ];
+        let fields = merged_fields(&keep, &merged);
+        assert_eq!(fields["tags"], serde_json::json!(["Work", "urgent"]));
+        assert_eq!(
+            fields["subtasks"],
+            serde_json::json!([{"id": "s1"}, {"id": "s2"}])
+        );
+        assert_eq!(fields["completed_pomodoros"], 5);
+        assert_eq!(fields["total_pomodoros"], 1);
+        assert_eq!(fields["description"], "Notes");
+
+        let plain = row(serde_json::json!({ "id": "a", "tags": ["x"], "description": "Mine" }));
+        assert!(merged_fields(&plain, &[row(serde_json::json!({ "id": "b" }))]).is_empty());
+    }
+
+    #[test]
+    fn children_and_dependencies_move_to_the_kept_task() {
+        let merged: HashSet<&str> = ["b", "c"].into();
+        let child = row(serde_json::json!({
+            "id": "d", "parent_task_id": "b", "depends_on": ["c", "a", "e", "b"]
+        }));
+        let fields = relinked_fields(&child, &merged, "a");
+        assert_eq!(fields["parent_task_id"], "a");
+        assert_eq!(fields["depends_on"], serde_json::json!(["a", "e"]));
+
+        // The kept task doesn't come to depend on or descend from itself
+        let keep =
+            row(serde_json::json!({ "id": "a", "parent_task_id": "c", "depends_on": ["b"] }));
+        let fields = relinked_fields(&keep, &merged, "a");
+        assert_eq!(fields["parent_task_id"], Value::Null);
+        assert_eq!(fields["depends_on"], serde_json::json!([]));
+
+        let unrelated = row(serde_json::json!({ "id": "e", "parent_task_id": "a" }));
+        assert!(relinked_fields(&unrelated, &merged, "a").is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! Deploy helper for the Supabase Edge Functions the app depends on.
+//!
+//! Functions are deployed through the Supabase CLI. After each successful deploy
+//! the app version is pinned in `edge-functions.json`, so the status check can
+//! tell whether the remote functions match the running client.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+/// Edge functions shipped in supabase/functions that the client relies on
+pub const EDGE_FUNCTIONS: &[&str] = &[
+    "ai-chat-proxy",
+    "google-calendar-proxy",
+    "url-scraper-proxy",
+    "whisper-transcribe",
+];
+
+pub(crate) const PIN_STORE: &str = "edge-functions.json";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionDeployResult {
+    pub name: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeFunctionStatus {
+    pub name: String,
+    pub deployed: bool,
+    pub remote_version: Option<u64>,
+    pub pinned_app_version: Option<String>,
+    pub in_sync: bool,
+}
+
+fn project_ref_args(project_ref: &Option<String>) -> Result<Vec<String>, String> {
+    match project_ref {
+        Some(r) if !r.trim().is_empty() => Ok(vec![
+            "--project-ref".to_string(),
+            crate::process::operand(r, "project ref")?.to_string(),
+        ]),
+        _ if crate::is_remote_project_linked() => Ok(Vec::new()),
+        _ => Err(
+            "No remote project linked. Pass a project ref or run 'supabase link' first."
+                .to_string(),
+        ),
+    }
+}
+
+/// Deploy bundled edge functions (all of them unless a subset is given)
+#[tauri::command]
+pub async fn deploy_edge_functions(
+    app: AppHandle,
+    project_ref: Option<String>,
+    functions: Option<Vec<String>>,
+) -> Result<Vec<FunctionDeployResult>, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "deploy_edge_functions")?;
+    let ref_args = project_ref_args(&project_ref)?;
+    let names: Vec<String> = match functions {
+        Some(list) => {
+            if let Some(unknown) = list.iter().find(|f| !EDGE_FUNCTIONS.contains(&f.as_str())) {
+                return Err(format!("Unknown edge function: {}", unknown).into());
+            }
+            list
+        }
+        None => EDGE_FUNCTIONS.iter().map(|f| f.to_string()).collect(),
+    };
+
+    let store = app
+        .store(PIN_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", PIN_STORE, e))?;
+    let app_version = app.package_info().version.to_string();
+    let mut results = Vec::new();
+
+    for name in names {
+        let mut args = vec!["functions".to_string(), "deploy".to_string(), name.clone()];
+        args.extend(ref_args.iter().cloned());
+
+        let output = crate::trace::command(&app, "supabase")
+            .args(&args)
+            .output()
+            .await;
+
+        let result = match output {
+            Ok(o) if o.status.success() => {
+                store.set(
+                    name.clone(),
+                    json!({ "appVersion": app_version, "deployedAt": crate::events::now_ms() }),
+                );
+                log::info!("Deployed edge function {}", name);
+                FunctionDeployResult {
+                    name,
+                    success: true,
+                    error: None,
+                }
+            }
+            Ok(o) => {
+                let stderr = String::from_utf8_lossy(&o.stderr).trim().to_string();
+                log::warn!("Failed to deploy edge function {}: {}", name, stderr);
+                FunctionDeployResult {
+                    name,
+                    success: false,
+                    error: Some(stderr),
+                }
+            }
+            Err(e) => FunctionDeployResult {
+                name,
+                success: false,
+                error: Some(format!("Failed to run supabase: {}", e)),
+            },
+        };
+        results.push(result);
+    }
+
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", PIN_STORE, e))?;
+
+    Ok(results)
+}
+
+/// Compare remote edge functions with the versions pinned for this client
+#[tauri::command]
+pub async fn get_edge_functions_status(
+    app: AppHandle,
+    project_ref: Option<String>,
+) -> Result<Vec<EdgeFunctionStatus>, FlowStateError> {
+    let mut args = vec![
+        "functions".to_string(),
+        "list".to_string(),
+        "-o".to_string(),
+        "json".to_string(),
+    ];
+    args.extend(project_ref_args(&project_ref)?);
+
+    let output = crate::trace::command(&app, "supabase")
+        .args(&args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run supabase: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("Failed to list edge functions: {}", stderr).into());
+    }
+
+    let remote = crate::parsers::parse_functions_list(&output.stdout)
+        .map_err(|e| format!("Unexpected 'supabase functions list' output: {}", e))?;
+
+    let store = app
+        .store(PIN_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", PIN_STORE, e))?;
+    let app_version = app.package_info().version.to_string();
+
+    Ok(EDGE_FUNCTIONS
+        .iter()
+        .map(|name| {
+            let entry = remote.iter().find(|f| f.slug == *name);
+            let pinned_app_version = store.get(*name).and_then(|v| {
+                v.get("appVersion")
+                    .and_then(Value::as_str)
+                    .map(String::from)
+            });
+            let deployed = entry.is_some();
+
+            EdgeFunctionStatus {
+                name: name.to_string(),
+                deployed,
+                remote_version: entry.and_then(|f| f.version),
+                in_sync: deployed && pinned_app_version.as_deref() == Some(app_version.as_str()),
+                pinned_app_version,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_given_project_ref_becomes_a_flag_value() {
+        assert_eq!(
+            project_ref_args(&Some(" abcd1234 ".to_string())),
+            Ok(vec!["--project-ref".to_string(), "abcd1234".to_string()])
+        );
+        assert!(project_ref_args(&Some("--debug".to_string())).is_err());
+    }
+}
@@ -0,0 +1,512 @@
+//! Encryption at rest for the local stores and SQLite databases.
+//!
+//! When enabled, a random 256-bit key lives in the OS keychain. Store files
+//! (`*.json` in the app data directory, written by tauri-plugin-store) are
+//! sealed with AES-256-GCM through the plugin's serialize hooks, and the
+//! SQLite databases (`sqlite.rs`) are SQLCipher databases keyed with the same
+//! key. Attachment blobs are sealed as a stream of AES-256-GCM chunks (a
+//! counter nonce per chunk, the last one flagged so truncation is caught).
+//! Reading accepts both forms, so a store written before encryption was
+//! enabled still loads and is sealed on its next save.
+//!
+//! `enable_encryption` stores the key first and then converts everything;
+//! `disable_encryption` converts everything back and deletes the key last.
+//! If either is interrupted, the keychain decides: databases are converted
+//! on their next open and stores on their next save.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::RwLock;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+const KEYRING_SERVICE: &str = "flowstate-encryption";
+const KEYRING_ACCOUNT: &str = "local-data";
+/// Prefix of sealed store files, followed by the nonce and ciphertext
+const MAGIC: &[u8] = b"FLOWSTATE-ENC1\n";
+const NONCE_LEN: usize = 12;
+/// Prefix of sealed blobs, followed by a random nonce prefix and the chunks
+const BLOB_MAGIC: &[u8] = b"FLOWSTATE-BLOB1\n";
+const BLOB_NONCE_PREFIX: usize = 8;
+/// Plaintext bytes per sealed chunk
+const BLOB_CHUNK: usize = 1024 * 1024;
+const TAG_LEN: usize = 16;
+
+type Key = [u8; 32];
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The key in use; None when encryption is off
+static KEY: RwLock<Option<Key>> = RwLock::new(None);
+/// The previous key while `disable_encryption` converts databases back
+static RETIRED: RwLock<Option<Key>> = RwLock::new(None);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionState {
+    pub enabled: bool,
+}
+
+fn hex(key: &Key) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<Key> {
+    let text = text.trim();
+    if text.len() != 64 {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(text.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(key)
+}
+
+fn current() -> Option<Key> {
+    *KEY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn set_current(key: Option<Key>) {
+    *KEY.write().unwrap_or_else(|e| e.into_inner()) = key;
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// Read the key from the keychain; must run before any store or database is
+/// opened
+pub fn load_key() {
+    let stored = keyring_entry().and_then(|entry| match entry.get_password() {
+        Ok(text) => Ok(Some(text)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read encryption key: {}", e)),
+    });
+    match stored {
+        Ok(Some(text)) => match parse_hex(&text) {
+            Some(key) => {
+                set_current(Some(key));
+                log::info!("Local data encryption is on");
+            }
+            None => log::error!("Encryption key in the keychain is malformed"),
+        },
+        Ok(None) => {}
+        Err(e) => log::error!("{}", e),
+    }
+}
+
+/// SQLCipher key of the databases (hex), if encryption is on
+pub(crate) fn sqlite_key() -> Option<String> {
+    current().as_ref().map(hex)
+}
+
+/// Key the databases had before `disable_encryption` started, while it runs
+pub(crate) fn retired_sqlite_key() -> Option<String> {
+    RETIRED
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(hex)
+}
+
+fn seal(key: &Key, plain: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let sealed = cipher
+        .encrypt(Nonce::from_slice(&nonce), plain)
+        .map_err(|_| "Encryption failed")?;
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+fn unseal(key: &Key, body: &[u8]) -> Result<Vec<u8>, BoxError> {
+    if body.len() < NONCE_LEN {
+        return Err("Encrypted store is truncated".into());
+    }
+    let (nonce, sealed) = body.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| "Encrypted store could not be decrypted (wrong key or corrupted)".into())
+}
+
+/// Fill `buf` from `reader`; short only at the end of the input
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn blob_nonce(prefix: &[u8; BLOB_NONCE_PREFIX], counter: u32) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..BLOB_NONCE_PREFIX].copy_from_slice(prefix);
+    nonce[BLOB_NONCE_PREFIX..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// Run `chunk` over `reader` in pieces of `size` bytes, telling it which
+/// piece is the last (an empty input is one empty last piece)
+fn for_each_chunk(
+    reader: &mut impl Read,
+    size: usize,
+    mut chunk: impl FnMut(&[u8], u32, bool) -> Result<(), String>,
+) -> Result<(), String> {
+    let read_failed = |e: std::io::Error| format!("Failed to read: {}", e);
+    let mut current = vec![0u8; size];
+    let mut next = vec![0u8; size];
+    let mut len = read_full(reader, &mut current).map_err(read_failed)?;
+    let mut counter = 0u32;
+    loop {
+        let next_len = if len == size {
+            read_full(reader, &mut next).map_err(read_failed)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        chunk(&current[..len], counter, last)?;
+        if last {
+            return Ok(());
+        }
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+        counter = counter.checked_add(1).ok_or("Input is too large to seal")?;
+    }
+}
+
+/// Copy `reader` to `writer`, sealed when encryption is on (attachment blobs)
+pub(crate) fn seal_to(reader: &mut impl Read, writer: &mut impl Write) -> Result<(), String> {
+    let write_failed = |e: std::io::Error| format!("Failed to write: {}", e);
+    let Some(key) = current() else {
+        std::io::copy(reader, writer).map_err(write_failed)?;
+        return Ok(());
+    };
+    let cipher = Aes256Gcm::new((&key).into());
+    let prefix: [u8; BLOB_NONCE_PREFIX] = rand::random();
+    writer.write_all(BLOB_MAGIC).map_err(write_failed)?;
+    writer.write_all(&prefix).map_err(write_failed)?;
+    for_each_chunk(reader, BLOB_CHUNK, |plain, counter, last| {
+        let payload = Payload {
+            msg: plain,
+            aad: &[u8::from(last)],
+        };
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&blob_nonce(&prefix, counter)), payload)
+            .map_err(|_| "Encryption failed".to_string())?;
+        writer.write_all(&sealed).map_err(write_failed)
+    })
+}
+
+/// Copy a blob written by `seal_to` (sealed or plain) to `writer` in the clear
+pub(crate) fn unseal_to(reader: &mut impl Read, writer: &mut impl Write) -> Result<(), String> {
+    let write_failed = |e: std::io::Error| format!("Failed to write: {}", e);
+    let mut magic = [0u8; BLOB_MAGIC.len()];
+    let len = read_full(reader, &mut magic).map_err(|e| format!("Failed to read: {}", e))?;
+    if &magic[..len] != BLOB_MAGIC {
+        writer.write_all(&magic[..len]).map_err(write_failed)?;
+        std::io::copy(reader, writer).map_err(write_failed)?;
+        return Ok(());
+    }
+
+    let mut prefix = [0u8; BLOB_NONCE_PREFIX];
+    if read_full(reader, &mut prefix).map_err(|e| format!("Failed to read: {}", e))? != prefix.len()
+    {
+        return Err("Encrypted file is truncated".to_string());
+    }
+    let keys = [
+        current(),
+        *RETIRED.read().unwrap_or_else(|e| e.into_inner()),
+    ];
+    let ciphers: Vec<Aes256Gcm> = keys
+        .iter()
+        .flatten()
+        .map(|key| Aes256Gcm::new(key.into()))
+        .collect();
+    if ciphers.is_empty() {
+        return Err("File is encrypted but no key is in the keychain".to_string());
+    }
+    // Picked by the first chunk, then kept
+    let mut chosen: Option<&Aes256Gcm> = None;
+    for_each_chunk(reader, BLOB_CHUNK + TAG_LEN, |sealed, counter, last| {
+        let nonce = blob_nonce(&prefix, counter);
+        let decrypt = |cipher: &Aes256Gcm| {
+            let payload = Payload {
+                msg: sealed,
+                aad: &[u8::from(last)],
+            };
+            cipher.decrypt(Nonce::from_slice(&nonce), payload).ok()
+        };
+        let plain = match chosen {
+            Some(cipher) => decrypt(cipher),
+            None => ciphers.iter().find_map(|cipher| {
+                let plain = decrypt(cipher)?;
+                chosen = Some(cipher);
+                Some(plain)
+            }),
+        }
+        .ok_or("Encrypted file could not be decrypted (wrong key, truncated or corrupted)")?;
+        writer.write_all(&plain).map_err(write_failed)
+    })
+}
+
+/// tauri-plugin-store serializer: JSON, sealed when encryption is on
+pub fn serialize_store(cache: &HashMap<String, Value>) -> Result<Vec<u8>, BoxError> {
+    let json = serde_json::to_vec_pretty(cache)?;
+    match current() {
+        Some(key) => seal(&key, &json),
+        None => Ok(json),
+    }
+}
+
+/// tauri-plugin-store deserializer: sealed or plain JSON
+pub fn deserialize_store(bytes: &[u8]) -> Result<HashMap<String, Value>, BoxError> {
+    let Some(body) = bytes.strip_prefix(MAGIC) else {
+        return Ok(serde_json::from_slice(bytes)?);
+    };
+    let keys = [
+        current(),
+        *RETIRED.read().unwrap_or_else(|e| e.into_inner()),
+    ];
+    let mut last_error: BoxError = "Store is encrypted but no key is in the keychain".into();
+    for key in keys.iter().flatten() {
+        match unseal(key, body) {
+            Ok(plain) => return Ok(serde_json::from_slice(&plain)?),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Store files written through tauri-plugin-store. Only these are sealed:
+/// other JSON in the same directory (`otel.json` shares it on Windows and
+/// macOS) is read as plain files.
+pub(crate) const STORES: &[&str] = &[
+    crate::app_lock::LOCK_STORE,
+    crate::autostart::AUTOSTART_STORE,
+    crate::backup::SCHEDULE_STORE,
+    crate::calendars::CALENDAR_STORE,
+    crate::ci_builds::CI_STORE,
+    crate::conflicts::STRATEGY_STORE,
+    crate::docker_context::CONTEXT_STORE,
+    crate::edge_functions::PIN_STORE,
+    crate::feeds::FEED_STORE,
+    crate::focus::SESSION_STORE,
+    crate::git::GIT_STORE,
+    crate::holidays::HOLIDAY_STORE,
+    crate::local_api::LOCAL_API_STORE,
+    crate::multi_user::NAMESPACE_STORE,
+    crate::notifications::PREFS_STORE,
+    crate::preseed::PRESEED_STORE,
+    crate::project_dir::PROJECT_STORE,
+    crate::provision::REMOTE_STORE,
+    crate::secrets::INDEX_STORE,
+    crate::shortcut::SHORTCUT_STORE,
+    crate::sso::SSO_STORE,
+    crate::supervisor::POLICY_STORE,
+    crate::timesheet::TIMESHEET_STORE,
+    crate::webhooks::WEBHOOK_STORE,
+    crate::window_state::WINDOW_STORE,
+    // Startup settings (init.rs)
+    "settings.json",
+    // Frontend preferences (usePersistentRef.ts)
+    "ui-preferences.json",
+];
+
+/// Re-save every store that exists with the current key
+fn rewrite_stores(app: &AppHandle) -> Result<usize, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?;
+    let mut rewritten = 0;
+    for name in STORES {
+        if !dir.join(name).is_file() {
+            continue;
+        }
+        let store = app
+            .store(*name)
+            .map_err(|e| format!("Failed to open {}: {}", name, e))?;
+        store
+            .save()
+            .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+/// Close and reopen every local database, converting it to the current key,
+/// and rewrite the attachment blobs
+fn rewrite_databases(app: &AppHandle) -> Result<(), String> {
+    app.state::<crate::offline::TaskCache>().db.reopen(app)?;
+    app.state::<crate::time_tracking::TimeTracker>()
+        .db
+        .reopen(app)?;
+    app.state::<crate::sessions::Sessions>().db.reopen(app)?;
+    app.state::<crate::attachments::Attachments>()
+        .db
+        .reopen(app)?;
+    app.state::<crate::audit::AuditLog>().db.reopen(app)?;
+    let blobs = crate::attachments::rewrite_blobs(app)?;
+    log::info!("Rewrote {} attachment files", blobs);
+    Ok(())
+}
+
+fn publish(app: &AppHandle) -> EncryptionState {
+    let state = EncryptionState {
+        enabled: current().is_some(),
+    };
+    crate::events::publish(app, "encryption://changed", &state);
+    state
+}
+
+#[tauri::command]
+pub fn get_encryption_state() -> EncryptionState {
+    EncryptionState {
+        enabled: current().is_some(),
+    }
+}
+
+/// Create a key in the keychain and encrypt the stores and databases with it
+#[tauri::command]
+pub async fn enable_encryption(app: AppHandle) -> Result<EncryptionState, FlowStateError> {
+    crate::trace::scope("enable_encryption", async move {
+        crate::read_only::ensure_writable(&app, "enable_encryption")?;
+        crate::app_lock::ensure_unlocked(&app, "enable_encryption")?;
+        if current().is_some() {
+            return Err("Encryption is already enabled".into());
+        }
+
+        let key: Key = rand::random();
+        keyring_entry()?
+            .set_password(&hex(&key))
+            .map_err(|e| format!("Failed to store encryption key in keychain: {}", e))?;
+        set_current(Some(key));
+
+        let handle = app.clone();
+        let stores = tauri::async_runtime::spawn_blocking(move || {
+            rewrite_databases(&handle)?;
+            rewrite_stores(&handle)
+        })
+        .await
+        .map_err(|e| format!("Encryption task failed: {}", e))??;
+
+        log::info!("Encrypted local data ({} stores)", stores);
+        Ok(publish(&app))
+    })
+    .await
+}
+
+/// Decrypt the stores and databases, then delete the key from the keychain
+#[tauri::command]
+pub async fn disable_encryption(app: AppHandle) -> Result<EncryptionState, FlowStateError> {
+    crate::trace::scope("disable_encryption", async move {
+        crate::read_only::ensure_writable(&app, "disable_encryption")?;
+        crate::app_lock::ensure_unlocked(&app, "disable_encryption")?;
+        let Some(key) = current() else {
+            return Err("Encryption is not enabled".into());
+        };
+
+        *RETIRED.write().unwrap_or_else(|e| e.into_inner()) = Some(key);
+        set_current(None);
+        let handle = app.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            rewrite_databases(&handle)?;
+            rewrite_stores(&handle)
+        })
+        .await
+        .map_err(|e| format!("Decryption task failed: {}", e))
+        .and_then(|result| result);
+
+        let stores = match result {
+            Ok(stores) => stores,
+            Err(e) => {
+                // Stay encrypted; what was converted is converted back on use
+                set_current(Some(key));
+                *RETIRED.write().unwrap_or_else(|e| e.into_inner()) = None;
+                return Err(e.into());
+            }
+        };
+        *RETIRED.write().unwrap_or_else(|e| e.into_inner()) = None;
+        keyring_entry()?
+            .delete_credential()
+            .map_err(|e| format!("Failed to delete encryption key from keychain: {}", e))?;
+
+        log::info!("Decrypted local data ({} stores)", stores);
+        Ok(publish(&app))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_round_trip_and_reject_the_wrong_key() {
+        let key: Key = [7; 32];
+        let sealed = seal(&key, b"{\"a\":1}").unwrap();
+        let body = sealed.strip_prefix(MAGIC).unwrap();
+        assert_eq!(unseal(&key, body).unwrap(), b"{\"a\":1}");
+        assert!(unseal(&[8; 32], body).is_err());
+        assert!(unseal(&key, &body[..NONCE_LEN - 1]).is_err());
+        assert_eq!(parse_hex(&hex(&key)), Some(key));
+    }
+
+    // The only test that sets the global key
+    #[test]
+    fn blobs_round_trip_across_chunk_boundaries() {
+        set_current(Some([1; 32]));
+        let sizes = [
+            0,
+            1,
+            BLOB_CHUNK - 1,
+            BLOB_CHUNK,
+            BLOB_CHUNK + 1,
+            2 * BLOB_CHUNK,
+        ];
+        for size in sizes {
+            let plain: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let mut sealed = Vec::new();
+            seal_to(&mut plain.as_slice(), &mut sealed).unwrap();
+            assert!(sealed.starts_with(BLOB_MAGIC));
+            let mut opened = Vec::new();
+            unseal_to(&mut sealed.as_slice(), &mut opened).unwrap();
+            assert_eq!(opened, plain, "{} bytes", size);
+        }
+
+        // Dropping a whole trailing chunk leaves a first chunk not sealed as last
+        let plain = vec![3u8; 2 * BLOB_CHUNK];
+        let mut sealed = Vec::new();
+        seal_to(&mut plain.as_slice(), &mut sealed).unwrap();
+        let cut = sealed.len() - (BLOB_CHUNK + TAG_LEN);
+        assert!(unseal_to(&mut &sealed[..cut], &mut Vec::new()).is_err());
+
+        set_current(Some([2; 32]));
+        assert!(unseal_to(&mut sealed.as_slice(), &mut Vec::new()).is_err());
+
+        // Plain files pass through either way
+        set_current(None);
+        let mut copied = Vec::new();
+        seal_to(&mut &b"plain"[..], &mut copied).unwrap();
+        let mut opened = Vec::new();
+        unseal_to(&mut copied.as_slice(), &mut opened).unwrap();
+        assert_eq!(opened, b"plain");
+    }
+}
@@ -0,0 +1,361 @@
+//! Locking invoiced time entries.
+//!
+//! Every generated timesheet is recorded under the next invoice number
+//! ("INV-00042") with the entries on it. `lock_entries` freezes the finished
+//! entries of an invoice or of a range, and commands that change an entry
+//! (`set_entry_billable`) refuse locked ones, so the database can't drift
+//! from what was billed. Changing a locked entry takes an explicit
+//! `unlock_entries` with a reason, which goes into the audit log
+//! (`audit.rs`) along with every lock.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::time_tracking::TimeRange;
+
+const LOCK_ACTION: &str = "time.lock";
+const UNLOCK_ACTION: &str = "time.unlock";
+const MAX_REASON_LEN: usize = 500;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryLock {
+    pub entry_id: i64,
+    /// None for entries locked by range
+    pub invoice_id: Option<String>,
+    pub locked_at_ms: i64,
+}
+
+/// Number after the highest recorded one
+fn next_invoice_id(conn: &Connection) -> rusqlite::Result<String> {
+    let last: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(CAST(substr(id, 5) AS INTEGER)), 0) FROM invoices \
+         WHERE id LIKE 'INV-%'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(format!("INV-{:05}", last + 1))
+}
+
+/// Insert an invoice and its entries; an id that is already taken fails
+fn insert_invoice(
+    conn: &Connection,
+    invoice_id: &str,
+    client_id: &str,
+    range: &TimeRange,
+    entry_ids: &[i64],
+    now: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO invoices (id, client_id, from_ms, to_ms, created_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![invoice_id, client_id, range.from_ms, range.to_ms, now],
+    )?;
+    for entry_id in entry_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO invoice_entries (invoice_id, entry_id) VALUES (?1, ?2)",
+            params![invoice_id, entry_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Record the entries on a generated invoice under the next invoice number,
+/// which is returned. Invoices are never replaced, so regenerating a sheet
+/// gets a new number.
+pub(crate) fn record_invoice(
+    app: &AppHandle,
+    client_id: &str,
+    range: &TimeRange,
+    entry_ids: &[i64],
+) -> Result<String, String> {
+    let now = now_ms() as i64;
+    crate::time_tracking::with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        let invoice_id = next_invoice_id(&tx)?;
+        insert_invoice(&tx, &invoice_id, client_id, range, entry_ids, now)?;
+        tx.commit()?;
+        Ok(invoice_id)
+    })
+}
+
+/// Forget an invoice whose files couldn't be written
+pub(crate) fn discard_invoice(app: &AppHandle, invoice_id: &str) -> Result<(), String> {
+    crate::time_tracking::with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM invoice_entries WHERE invoice_id = ?1",
+            params![invoice_id],
+        )?;
+        tx.execute("DELETE FROM invoices WHERE id = ?1", params![invoice_id])?;
+        tx.commit()
+    })
+}
+
+/// Lock of an entry: None when unlocked, Some(invoice id) when locked
+fn lock_of(conn: &Connection, entry_id: i64) -> rusqlite::Result<Option<Option<String>>> {
+    conn.query_row(
+        "SELECT invoice_id FROM time_entry_locks WHERE entry_id = ?1",
+        params![entry_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Error for changing an entry with the given lock
+pub(crate) fn refuse_locked(entry_id: i64, lock: Option<Option<String>>) -> Result<(), String> {
+    match lock {
+        None => Ok(()),
+        Some(Some(invoice_id)) => Err(format!(
+            "Time entry {} is locked by invoice {}; unlock it with a reason first",
+            entry_id, invoice_id
+        )),
+        Some(None) => Err(format!(
+            "Time entry {} is locked; unlock it with a reason first",
+            entry_id
+        )),
+    }
+}
+
+/// Refuse changes to a locked entry
+pub(crate) fn ensure_editable(app: &AppHandle, entry_id: i64) -> Result<(), String> {
+    let lock = crate::time_tracking::with_db(app, |conn| lock_of(conn, entry_id))?;
+    refuse_locked(entry_id, lock)
+}
+
+/// Lock the finished entries of an invoice; None when there is no such invoice
+fn lock_invoice(conn: &Connection, invoice_id: &str, now: i64) -> rusqlite::Result<Option<usize>> {
+    let known = conn
+        .query_row(
+            "SELECT 1 FROM invoices WHERE id = ?1",
+            params![invoice_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !known {
+        return Ok(None);
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO time_entry_locks (entry_id, invoice_id, locked_at) \
+         SELECT i.entry_id, i.invoice_id, ?2 FROM invoice_entries i \
+         JOIN time_entries e ON e.id = i.entry_id \
+         WHERE i.invoice_id = ?1 AND e.ended_at IS NOT NULL",
+        params![invoice_id, now],
+    )
+    .map(Some)
+}
+
+/// Lock the finished entries that started in a range
+fn lock_range(conn: &Connection, range: &TimeRange, now: i64) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR IGNORE INTO time_entry_locks (entry_id, invoice_id, locked_at) \
+         SELECT id, NULL, ?4 FROM time_entries \
+         WHERE ended_at IS NOT NULL \
+           AND (?1 IS NULL OR started_at >= ?1) \
+           AND (?2 IS NULL OR started_at < ?2) \
+           AND (?3 IS NULL OR task_id = ?3)",
+        params![range.from_ms, range.to_ms, range.task_id, now],
+    )
+}
+
+/// Freeze the finished entries of an invoice, or those that started in a
+/// range; returns how many were newly locked
+#[tauri::command]
+pub fn lock_entries(
+    app: AppHandle,
+    range: Option<TimeRange>,
+    invoice_id: Option<String>,
+) -> Result<usize, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "lock_entries")?;
+    crate::app_lock::ensure_unlocked(&app, "lock_entries")?;
+    let now = now_ms() as i64;
+    let (locked, subject) = match (range, invoice_id) {
+        (None, Some(invoice_id)) => {
+            let locked =
+                crate::time_tracking::with_db(&app, |conn| lock_invoice(conn, &invoice_id, now))?
+                    .ok_or_else(|| format!("No invoice {}", invoice_id))?;
+            (locked, format!("invoice {}", invoice_id))
+        }
+        (Some(range), None) => {
+            let locked = crate::time_tracking::with_db(&app, |conn| lock_range(conn, &range, now))?;
+            let bound = |ms: Option<i64>| ms.map(|ms| ms.to_string()).unwrap_or_default();
+            (
+                locked,
+                format!("range {}..{}", bound(range.from_ms), bound(range.to_ms)),
+            )
+        }
+        _ => return Err("Give either a range or an invoice id".into()),
+    };
+    crate::audit::record(
+        &app,
+        LOCK_ACTION,
+        &subject,
+        Some(&format!("{} entries", locked)),
+    )?;
+    log::info!("Locked {} time entries of {}", locked, subject);
+    Ok(locked)
+}
+
+/// Unfreeze entries so they can be changed; the reason is audited per entry
+#[tauri::command]
+pub fn unlock_entries(
+    app: AppHandle,
+    entry_ids: Vec<i64>,
+    reason: String,
+) -> Result<usize, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "unlock_entries")?;
+    crate::app_lock::ensure_unlocked(&app, "unlock_entries")?;
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("Give a reason for unlocking".into());
+    }
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err(format!("Reason is longer than {} characters", MAX_REASON_LEN).into());
+    }
+
+    let mut unlocked = 0;
+    for entry_id in entry_ids {
+        let lock = crate::time_tracking::with_db(&app, |conn| lock_of(conn, entry_id))?;
+        let Some(invoice_id) = lock else {
+            continue;
+        };
+        // Audited before the lock goes, so no unlock goes unrecorded
+        let detail = match &invoice_id {
+            Some(invoice_id) => format!("{} (invoice {})", reason, invoice_id),
+            None => reason.to_string(),
+        };
+        crate::audit::record(
+            &app,
+            UNLOCK_ACTION,
+            &format!("time entry {}", entry_id),
+            Some(&detail),
+        )?;
+        unlocked += crate::time_tracking::with_db(&app, |conn| {
+            conn.execute(
+                "DELETE FROM time_entry_locks WHERE entry_id = ?1",
+                params![entry_id],
+            )
+        })?;
+    }
+    Ok(unlocked)
+}
+
+/// Locks of the entries that started in a range, oldest first
+#[tauri::command]
+pub fn get_entry_locks(
+    app: AppHandle,
+    range: Option<TimeRange>,
+) -> Result<Vec<EntryLock>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_entry_locks")?;
+    let range = range.unwrap_or_default();
+    Ok(crate::time_tracking::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT l.entry_id, l.invoice_id, l.locked_at FROM time_entry_locks l \
+             JOIN time_entries e ON e.id = l.entry_id \
+             WHERE (?1 IS NULL OR e.started_at >= ?1) \
+               AND (?2 IS NULL OR e.started_at < ?2) \
+               AND (?3 IS NULL OR e.task_id = ?3) \
+             ORDER BY e.started_at",
+        )?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms, range.task_id], |row| {
+            Ok(EntryLock {
+                entry_id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                locked_at_ms: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two finished entries of task-a, one running entry of task-b
+    fn db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(crate::time_tracking::SCHEMA).unwrap();
+        conn.execute_batch(
+            "INSERT INTO time_entries (id, task_id, started_at, ended_at, last_seen_at) VALUES
+                (1, 'task-a', 1000, 2000, 2000),
+                (2, 'task-a', 3000, 4000, 4000),
+                (3, 'task-b', 5000, NULL, 5000);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn invoice_numbers_are_sequential() {
+        let conn = db();
+        let range = TimeRange::default();
+        assert_eq!(next_invoice_id(&conn).unwrap(), "INV-00001");
+        insert_invoice(&conn, "INV-00001", "acme", &range, &[1], 10).unwrap();
+        // Ids from before numbering don't count
+        insert_invoice(
+            &conn,
+            "timesheet-acme-start-2026-05-04",
+            "acme",
+            &range,
+            &[],
+            10,
+        )
+        .unwrap();
+        insert_invoice(&conn, "INV-00041", "acme", &range, &[], 10).unwrap();
+        assert_eq!(next_invoice_id(&conn).unwrap(), "INV-00042");
+    }
+
+    #[test]
+    fn invoice_ids_are_never_replaced() {
+        let conn = db();
+        let range = TimeRange::default();
+        insert_invoice(&conn, "INV-00001", "acme", &range, &[1, 2], 10).unwrap();
+        assert!(insert_invoice(&conn, "INV-00001", "other", &range, &[3], 20).is_err());
+
+        let client: String = conn
+            .query_row(
+                "SELECT client_id FROM invoices WHERE id = 'INV-00001'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(client, "acme");
+        let entries: Vec<i64> = conn
+            .prepare("SELECT entry_id FROM invoice_entries ORDER BY entry_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(entries, [1, 2]);
+    }
+
+    #[test]
+    fn locked_entries_refuse_edits() {
+        let conn = db();
+        let range = TimeRange::default();
+        insert_invoice(&conn, "INV-00001", "acme", &range, &[1, 3], 10).unwrap();
+        // The running entry stays editable
+        assert_eq!(lock_invoice(&conn, "INV-00001", 20).unwrap(), Some(1));
+        assert_eq!(lock_invoice(&conn, "INV-00002", 20).unwrap(), None);
+
+        let err = refuse_locked(1, lock_of(&conn, 1).unwrap()).unwrap_err();
+        assert!(err.contains("locked by invoice INV-00001"), "{}", err);
+        assert!(refuse_locked(2, lock_of(&conn, 2).unwrap()).is_ok());
+        assert!(refuse_locked(3, lock_of(&conn, 3).unwrap()).is_ok());
+
+        assert_eq!(lock_range(&conn, &range, 30).unwrap(), 1);
+        let err = refuse_locked(2, lock_of(&conn, 2).unwrap()).unwrap_err();
+        assert!(err.contains("Time entry 2 is locked;"), "{}", err);
+        // Already locked by the invoice, not relocked by the range
+        assert_eq!(
+            lock_of(&conn, 1).unwrap(),
+            Some(Some("INV-00001".to_string()))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Error type returned by every command.
+//!
+//! Commands used to fail with a bare string, so the frontend had to match on
+//! message text to tell a port conflict from a missing CLI. `FlowStateError`
+//! serializes as `{ "code": "port_conflict", "message": "..." }`; the codes
+//! are stable and safe to branch on, the messages are for display only.
+//! Errors without a dedicated variant convert from `String` into `Other`.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use crate::app_lock::AppLockError;
+use crate::read_only::ReadOnlyError;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum FlowStateError {
+    #[error("Docker is not installed")]
+    DockerNotInstalled,
+    #[error("Failed to start Docker: {0}")]
+    DockerStartFailed(String),
+    /// The daemon didn't answer (in time)
+    #[error("{0}")]
+    DockerDaemonDown(String),
+    #[error("Supabase CLI not found: {0}")]
+    SupabaseCliMissing(String),
+    #[error("Failed to start Supabase: {0}")]
+    SupabaseStartFailed(String),
+    #[error("Failed to stop Supabase: {0}")]
+    SupabaseStopFailed(String),
+    /// Ports of the local stack are taken by another process or OS user
+    #[error("Failed to start Supabase: {0}")]
+    PortConflict(String),
+    #[error("Database schema not ready (missing: {0}). Set the FlowState project directory so migrations can be applied, or run 'supabase migration up --local' there.")]
+    SchemaMissing(String),
+    #[error(transparent)]
+    ReadOnly(#[from] ReadOnlyError),
+    #[error(transparent)]
+    Locked(#[from] AppLockError),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl FlowStateError {
+    /// Stable identifier the frontend can branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            FlowStateError::DockerNotInstalled => "docker_not_installed",
+            FlowStateError::DockerStartFailed(_) => "docker_start_failed",
+            FlowStateError::DockerDaemonDown(_) => "docker_daemon_down",
+            FlowStateError::SupabaseCliMissing(_) => "supabase_cli_missing",
+            FlowStateError::SupabaseStartFailed(_) => "supabase_start_failed",
+            FlowStateError::SupabaseStopFailed(_) => "supabase_stop_failed",
+            FlowStateError::PortConflict(_) => "port_conflict",
+            FlowStateError::SchemaMissing(_) => "schema_missing",
+            FlowStateError::ReadOnly(_) => "read_only",
+            FlowStateError::Locked(_) => "locked",
+            FlowStateError::Other(_) => "other",
+        }
+    }
+}
+
+impl Serialize for FlowStateError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FlowStateError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<String> for FlowStateError {
+    fn from(message: String) -> Self {
+        FlowStateError::Other(message)
+    }
+}
+
+impl From<&str> for FlowStateError {
+    fn from(message: &str) -> Self {
+        FlowStateError::Other(message.to_string())
+    }
+}
+
+/// For helpers that still report errors as plain strings
+impl From<FlowStateError> for String {
+    fn from(e: FlowStateError) -> String {
+        e.to_string()
+    }
+}
@@ -0,0 +1,191 @@
+//! Internal event bus bridged to the webview.
+//!
+//! Subsystems publish serializable payloads on a topic (e.g. `service://lifecycle`).
+//! Every event gets a monotonically increasing sequence number, is emitted to the
+//! webview under the topic name, and is kept in a short per-topic replay buffer so
+//! a window that just (re)loaded can catch up with `subscribe_with_replay`.
+//! Events published to every window also go to in-process listeners
+//! (`EventBus::listen`), such as editor plugins subscribed over `ide.rs`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Number of events kept per topic for late subscribers
+const REPLAY_CAPACITY: usize = 64;
+/// Events a slow in-process listener may fall behind before it skips some
+const LISTENER_CAPACITY: usize = 256;
+/// Messages a `watch` stream buffers for a slow client; past that it falls behind
+const STREAM_CAPACITY: usize = 64;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusEvent {
+    pub seq: u64,
+    pub topic: String,
+    pub timestamp_ms: u64,
+    /// Request that triggered the event, when published from a traced command
+    pub request_id: Option<u64>,
+    pub payload: Value,
+}
+
+#[derive(Default)]
+struct BusState {
+    next_seq: u64,
+    topics: HashMap<String, VecDeque<BusEvent>>,
+}
+
+pub struct EventBus {
+    state: Mutex<BusState>,
+    listeners: broadcast::Sender<BusEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus {
+            state: Mutex::default(),
+            listeners: broadcast::channel(LISTENER_CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    /// Record an event in the replay buffer and return it with its sequence number
+    fn record(&self, topic: &str, payload: Value) -> BusEvent {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.next_seq += 1;
+
+        let event = BusEvent {
+            seq: state.next_seq,
+            topic: topic.to_string(),
+            timestamp_ms: now_ms(),
+            request_id: crate::trace::current_request_id(),
+            payload,
+        };
+
+        let buffer = state.topics.entry(topic.to_string()).or_default();
+        if buffer.len() == REPLAY_CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back(event.clone());
+
+        event
+    }
+
+    /// Events published to every window from now on
+    pub(crate) fn listen(&self) -> broadcast::Receiver<BusEvent> {
+        self.listeners.subscribe()
+    }
+
+    /// Buffered events for a topic with a sequence number greater than `since`
+    pub fn since(&self, topic: &str, since: Option<u64>) -> Vec<BusEvent> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let since = since.unwrap_or(0);
+
+        state
+            .topics
+            .get(topic)
+            .map(|buffer| buffer.iter().filter(|e| e.seq > since).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Stream `first`, then what `convert` makes of bus events, until the
+/// client goes away; `lagged` stands in for the events a slow client missed.
+/// Nothing is sent while the app is locked. Streams of the gRPC service and
+/// GraphQL subscriptions are made this way.
+pub(crate) fn watch<T: Send + 'static>(
+    app: AppHandle,
+    mut events: broadcast::Receiver<BusEvent>,
+    first: Option<T>,
+    convert: fn(&BusEvent) -> Option<T>,
+    lagged: fn(&AppHandle) -> Option<T>,
+) -> ReceiverStream<T> {
+    let (out, stream) = mpsc::channel(STREAM_CAPACITY);
+    tauri::async_runtime::spawn(async move {
+        if let Some(first) = first {
+            if out.send(first).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            let message = match events.recv().await {
+                Ok(event) => convert(&event),
+                Err(broadcast::error::RecvError::Lagged(_)) => lagged(&app),
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let Some(message) = message else {
+                continue;
+            };
+            if app.state::<crate::app_lock::AppLock>().is_locked() {
+                continue;
+            }
+            if out.send(message).await.is_err() {
+                return;
+            }
+        }
+    });
+    ReceiverStream::new(stream)
+}
+
+pub(crate) fn now_ms() -> u64 {
+    crate::clock::now_ms()
+}
+
+/// Publish an event on the bus and forward it to the webview
+pub fn publish<T: Serialize>(app: &AppHandle, topic: &str, payload: &T) {
+    let payload = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            log::error!("Failed to serialize event for {}: {}", topic, e);
+            return;
+        }
+    };
+
+    let bus = app.state::<EventBus>();
+    let event = bus.record(topic, payload);
+
+    if let Err(e) = app.emit(topic, &event) {
+        log::warn!("Failed to emit {}: {}", topic, e);
+    }
+    // Fails only when nobody listens
+    let _ = bus.listeners.send(event);
+}
+
+/// Publish an event on the bus but forward it only to the given windows
+pub fn publish_to<T: Serialize>(app: &AppHandle, labels: &[String], topic: &str, payload: &T) {
+    if labels.is_empty() {
+        return;
+    }
+    let payload = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            log::error!("Failed to serialize event for {}: {}", topic, e);
+            return;
+        }
+    };
+
+    let event = app.state::<EventBus>().record(topic, payload);
+
+    for label in labels {
+        if let Err(e) = app.emit_to(label.as_str(), topic, &event) {
+            log::warn!("Failed to emit {} to {}: {}", topic, label, e);
+        }
+    }
+}
+
+/// Return buffered events for a topic so a freshly loaded window can catch up
+/// before listening for live events (dedupe live events by `seq`)
+#[tauri::command]
+pub fn subscribe_with_replay(
+    bus: tauri::State<'_, EventBus>,
+    topic: String,
+    since: Option<u64>,
+) -> Vec<BusEvent> {
+    bus.since(&topic, since)
+}
@@ -0,0 +1,680 @@
+//! Parquet export of the local database for analysis in pandas/Polars.
+//!
+//! Each table is written to its own file in the chosen folder, with typed
+//! columns (UTC millisecond timestamps, booleans, lists) instead of CSV text.
+//! Every file carries `flowstate.schema_version` and `flowstate.table` in its
+//! key/value metadata. The schema below is version 1; columns are only ever
+//! added within a version, and renaming or retyping one bumps it.
+//!
+//! Sessions and tasks are the sync user's rows of the database; activity
+//! comes from this machine's `sessions.db`.
+//!
+//! `flowstate-sessions.parquet` (focus and break sessions, `pomodoro_history`):
+//! id, task_id, duration_seconds (int32), is_break, started_at, completed_at
+//!
+//! `flowstate-tasks.parquet`: id, project_id, parent_task_id, title, status,
+//! priority, progress, estimated_pomodoros, completed_pomodoros (int32),
+//! estimated_duration_minutes (int32), tags (list<utf8>), due_date,
+//! created_at, updated_at, completed_at, is_deleted, custom_fields (JSON
+//! object of the task's custom field values by key, `custom_fields.rs`)
+//!
+//! `flowstate-activity.parquet` (recorded focus phases and idle periods):
+//! kind (`focus` or `idle`), started_at, ended_at, task_id, completed
+//! (boolean, null for idle), tags (list<utf8>), interruptions (int32, null
+//! for idle)
+//!
+//! Ids are UUID strings; timestamps are `timestamp[ms, UTC]`.
+//!
+//! Rows are streamed: `BATCH_ROWS` at a time from a Postgres portal (or the
+//! SQLite statement) into the file, in row groups of at most
+//! `ROW_GROUP_ROWS`, so memory stays bounded however large the tables are.
+//! Exports (this one and the data takeout, `takeout.rs`) run one at a time,
+//! publish `export://progress` with the rows and bytes written so far, and
+//! stop at the next batch after `cancel_export`, removing what they wrote.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Int32Array, ListBuilder, StringArray, StringBuilder,
+    TimestampMillisecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Row;
+
+use crate::error::FlowStateError;
+
+/// Version of the column layout documented above
+pub const SCHEMA_VERSION: u32 = 1;
+/// Rows fetched and converted at a time
+pub(crate) const BATCH_ROWS: usize = 5000;
+/// Rows buffered before a Parquet row group is flushed to disk
+const ROW_GROUP_ROWS: usize = 50_000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedFile {
+    pub table: &'static str,
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParquetExport {
+    pub schema_version: u32,
+    pub files: Vec<ExportedFile>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    /// parquet | takeout
+    pub export: &'static str,
+    /// Table or part being written
+    pub part: String,
+    /// Rows and bytes written so far, over all parts
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+/// Cancellation flag of the running export, if any
+#[derive(Default)]
+pub struct ExportJobs {
+    running: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+/// The running export; dropping it lets the next one start
+pub(crate) struct ExportJob {
+    app: AppHandle,
+    export: &'static str,
+    cancelled: Arc<AtomicBool>,
+    rows: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl ExportJob {
+    pub(crate) fn start(app: &AppHandle, export: &'static str) -> Result<ExportJob, String> {
+        let jobs = app.state::<ExportJobs>();
+        let mut running = jobs.running.lock().unwrap_or_else(|e| e.into_inner());
+        if running.is_some() {
+            return Err("Another export is still running".to_string());
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        *running = Some(cancelled.clone());
+        Ok(ExportJob {
+            app: app.clone(),
+            export,
+            cancelled,
+            rows: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+        })
+    }
+
+    /// Err once `cancel_export` was called
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err("Export cancelled".to_string());
+        }
+        Ok(())
+    }
+
+    /// Count written rows and bytes and publish the totals
+    pub(crate) fn advance(&self, part: &str, rows: u64, bytes: u64) {
+        crate::events::publish(
+            &self.app,
+            "export://progress",
+            &ExportProgress {
+                export: self.export,
+                part: part.to_string(),
+                rows: self.rows.fetch_add(rows, Ordering::SeqCst) + rows,
+                bytes: self.bytes.fetch_add(bytes, Ordering::SeqCst) + bytes,
+            },
+        );
+    }
+}
+
+impl Drop for ExportJob {
+    fn drop(&mut self) {
+        *self
+            .app
+            .state::<ExportJobs>()
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Writer that counts the bytes passed through it
+pub(crate) struct Counted<W> {
+    inner: W,
+    pub(crate) bytes: u64,
+}
+
+impl<W: Write> Counted<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Counted { inner, bytes: 0 }
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Stop the running export; false if none was running
+#[tauri::command]
+pub fn cancel_export(jobs: tauri::State<'_, ExportJobs>) -> bool {
+    match &*jobs.running.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Rows of `sql` from a portal, `BATCH_ROWS` at a time, handed to `each`
+/// until they run out or it fails; stops between batches once the job is
+/// cancelled
+pub(crate) async fn stream_rows(
+    client: &mut tokio_postgres::Client,
+    job: &ExportJob,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+    mut each: impl FnMut(&[Row]) -> Result<(), String>,
+) -> Result<(), String> {
+    // Portals only live inside a transaction; this one only reads
+    let tx = client
+        .build_transaction()
+        .read_only(true)
+        .start()
+        .await
+        .map_err(|e| e.to_string())?;
+    let portal = tx.bind(sql, params).await.map_err(|e| e.to_string())?;
+    loop {
+        job.check()?;
+        let rows = tx
+            .query_portal(&portal, BATCH_ROWS as i32)
+            .await
+            .map_err(|e| e.to_string())?;
+        if rows.is_empty() {
+            break;
+        }
+        each(&rows)?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Use the given folder or ask the user for one
+pub(crate) async fn choose_dir(app: &AppHandle, dir: Option<String>) -> Result<PathBuf, String> {
+    if let Some(dir) = dir {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog().file().pick_folder(move |folder| {
+        let _ = tx.send(folder);
+    });
+
+    rx.await
+        .map_err(|_| "Folder dialog closed unexpectedly".to_string())?
+        .ok_or_else(|| "No folder selected".to_string())?
+        .into_path()
+        .map_err(|e| format!("Unsupported folder location: {}", e))
+}
+
+fn timestamp(name: &str, nullable: bool) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        nullable,
+    )
+}
+
+fn timestamps(values: Vec<Option<i64>>) -> ArrayRef {
+    Arc::new(TimestampMillisecondArray::from(values).with_timezone("UTC"))
+}
+
+/// SQL for a timestamptz column as epoch milliseconds
+fn epoch_ms(column: &str) -> String {
+    format!("(extract(epoch FROM {}) * 1000)::bigint", column)
+}
+
+/// A table of the sync user's rows: its query, columns and batch builder
+struct RemoteTable {
+    name: &'static str,
+    sql: fn() -> String,
+    schema: fn() -> Schema,
+    batch: fn(Arc<Schema>, &[Row]) -> Result<RecordBatch, String>,
+}
+
+const REMOTE_TABLES: [RemoteTable; 2] = [
+    RemoteTable {
+        name: "sessions",
+        sql: sessions_sql,
+        schema: sessions_schema,
+        batch: sessions_batch,
+    },
+    RemoteTable {
+        name: "tasks",
+        sql: tasks_sql,
+        schema: tasks_schema,
+        batch: tasks_batch,
+    },
+];
+
+fn sessions_sql() -> String {
+    format!(
+        "SELECT id::text, task_id::text, duration, coalesce(is_break, false), {}, {} \
+         FROM public.pomodoro_history WHERE user_id::text = $1 ORDER BY started_at",
+        epoch_ms("started_at"),
+        epoch_ms("completed_at")
+    )
+}
+
+fn sessions_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("task_id", DataType::Utf8, true),
+        Field::new("duration_seconds", DataType::Int32, false),
+        Field::new("is_break", DataType::Boolean, false),
+        timestamp("started_at", false),
+        timestamp("completed_at", false),
+    ])
+}
+
+fn sessions_batch(schema: Arc<Schema>, rows: &[Row]) -> Result<RecordBatch, String> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| r.get::<_, String>(0)),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.get::<_, Option<String>>(1)),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.get::<_, i32>(2)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.get::<_, bool>(3))),
+        )),
+        timestamps(rows.iter().map(|r| r.get(4)).collect()),
+        timestamps(rows.iter().map(|r| r.get(5)).collect()),
+    ];
+    RecordBatch::try_new(schema, columns).map_err(|e| e.to_string())
+}
+
+fn tasks_sql() -> String {
+    format!(
+        "SELECT id::text, project_id::text, parent_task_id::text, title, status, priority, \
+                progress, estimated_pomodoros, completed_pomodoros, estimated_duration, \
+                array_remove(tags, NULL), {}, {}, {}, {}, coalesce(is_deleted, false), \
+                (to_jsonb(t) -> 'custom_fields')::text \
+         FROM public.tasks t WHERE user_id::text = $1 ORDER BY created_at",
+        epoch_ms("due_date"),
+        epoch_ms("created_at"),
+        epoch_ms("updated_at"),
+        epoch_ms("completed_at")
+    )
+}
+
+fn tasks_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("project_id", DataType::Utf8, true),
+        Field::new("parent_task_id", DataType::Utf8, true),
+        Field::new("title", DataType::Utf8, true),
+        Field::new("status", DataType::Utf8, true),
+        Field::new("priority", DataType::Utf8, true),
+        Field::new("progress", DataType::Int32, true),
+        Field::new("estimated_pomodoros", DataType::Int32, true),
+        Field::new("completed_pomodoros", DataType::Int32, true),
+        Field::new("estimated_duration_minutes", DataType::Int32, true),
+        Field::new(
+            "tags",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            true,
+        ),
+        timestamp("due_date", true),
+        timestamp("created_at", true),
+        timestamp("updated_at", true),
+        timestamp("completed_at", true),
+        Field::new("is_deleted", DataType::Boolean, false),
+        Field::new("custom_fields", DataType::Utf8, true),
+    ])
+}
+
+fn tasks_batch(schema: Arc<Schema>, rows: &[Row]) -> Result<RecordBatch, String> {
+    let text = |i: usize| -> ArrayRef {
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.get::<_, Option<String>>(i)),
+        ))
+    };
+    let int = |i: usize| -> ArrayRef {
+        Arc::new(Int32Array::from_iter(
+            rows.iter().map(|r| r.get::<_, Option<i32>>(i)),
+        ))
+    };
+
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    for row in rows {
+        match row.get::<_, Option<Vec<String>>>(10) {
+            Some(values) => {
+                values.iter().for_each(|t| tags.values().append_value(t));
+                tags.append(true);
+            }
+            None => tags.append(false),
+        }
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        text(0),
+        text(1),
+        text(2),
+        text(3),
+        text(4),
+        text(5),
+        int(6),
+        int(7),
+        int(8),
+        int(9),
+        Arc::new(tags.finish()),
+        timestamps(rows.iter().map(|r| r.get(11)).collect()),
+        timestamps(rows.iter().map(|r| r.get(12)).collect()),
+        timestamps(rows.iter().map(|r| r.get(13)).collect()),
+        timestamps(rows.iter().map(|r| r.get(14)).collect()),
+        Arc::new(BooleanArray::from_iter(
+            rows.iter().map(|r| Some(r.get::<_, bool>(15))),
+        )),
+        text(16),
+    ];
+    RecordBatch::try_new(schema, columns).map_err(|e| e.to_string())
+}
+
+/// Stream the sync user's rows of `table` into `<dir>/flowstate-<table>.parquet`
+async fn export_remote(
+    client: &mut tokio_postgres::Client,
+    job: &ExportJob,
+    dir: &Path,
+    table: &RemoteTable,
+    user: &str,
+) -> Result<ExportedFile, String> {
+    let mut file = ParquetFile::create(dir, table.name, (table.schema)())?;
+    let streamed = stream_rows(client, job, &(table.sql)(), &[&user], |rows| {
+        let batch = (table.batch)(file.schema.clone(), rows)?;
+        file.write(job, &batch)
+    })
+    .await
+    .map_err(|e| format!("Failed to export {}: {}", table.name, e));
+    match streamed {
+        Ok(()) => file.finish(job),
+        Err(e) => {
+            file.discard();
+            Err(e)
+        }
+    }
+}
+
+/// A focus phase or idle period from `sessions.db`
+struct ActivitySample {
+    kind: String,
+    started_at: i64,
+    ended_at: i64,
+    task_id: Option<String>,
+    completed: Option<bool>,
+    tags: Option<String>,
+    interruptions: Option<i32>,
+}
+
+fn activity_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("kind", DataType::Utf8, false),
+        timestamp("started_at", false),
+        timestamp("ended_at", false),
+        Field::new("task_id", DataType::Utf8, true),
+        Field::new("completed", DataType::Boolean, true),
+        Field::new(
+            "tags",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            true,
+        ),
+        Field::new("interruptions", DataType::Int32, true),
+    ])
+}
+
+fn activity_batch(schema: Arc<Schema>, samples: &[ActivitySample]) -> Result<RecordBatch, String> {
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    for sample in samples {
+        match &sample.tags {
+            Some(values) => {
+                let mut values: Vec<&str> = values.split('\u{1f}').collect();
+                values.sort_unstable();
+                values.iter().for_each(|t| tags.values().append_value(t));
+                tags.append(true);
+            }
+            None if sample.kind == "focus" => tags.append(true),
+            None => tags.append(false),
+        }
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            samples.iter().map(|s| s.kind.as_str()),
+        )),
+        timestamps(samples.iter().map(|s| Some(s.started_at)).collect()),
+        timestamps(samples.iter().map(|s| Some(s.ended_at)).collect()),
+        Arc::new(StringArray::from_iter(
+            samples.iter().map(|s| s.task_id.as_deref()),
+        )),
+        Arc::new(BooleanArray::from_iter(samples.iter().map(|s| s.completed))),
+        Arc::new(tags.finish()),
+        Arc::new(Int32Array::from_iter(
+            samples.iter().map(|s| s.interruptions),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns).map_err(|e| e.to_string())
+}
+
+/// Stream `sessions.db` into `<dir>/flowstate-activity.parquet`
+fn export_activity(app: &AppHandle, job: &ExportJob, dir: &Path) -> Result<ExportedFile, String> {
+    let mut file = ParquetFile::create(dir, "activity", activity_schema())?;
+    let streamed = crate::sessions::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT 'focus', s.started_at, s.ended_at, s.task_id, s.completed, \
+                    (SELECT group_concat(tag, char(31)) FROM session_tags WHERE session_id = s.id), \
+                    (SELECT count(*) FROM interruptions WHERE session_id = s.id) \
+             FROM focus_sessions s \
+             UNION ALL \
+             SELECT 'idle', started_at, ended_at, NULL, NULL, NULL, NULL FROM idle_periods \
+             ORDER BY 2",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut samples = Vec::with_capacity(BATCH_ROWS);
+        let mut flush = |samples: &mut Vec<ActivitySample>| -> Result<(), String> {
+            job.check()?;
+            let batch = activity_batch(file.schema.clone(), samples)?;
+            samples.clear();
+            file.write(job, &batch)
+        };
+        while let Some(row) = rows.next()? {
+            samples.push(ActivitySample {
+                kind: row.get(0)?,
+                started_at: row.get(1)?,
+                ended_at: row.get(2)?,
+                task_id: row.get(3)?,
+                completed: row.get(4)?,
+                tags: row.get(5)?,
+                interruptions: row.get(6)?,
+            });
+            if samples.len() == BATCH_ROWS {
+                if let Err(e) = flush(&mut samples) {
+                    return Ok(Err(e));
+                }
+            }
+        }
+        if samples.is_empty() {
+            return Ok(Ok(()));
+        }
+        Ok(flush(&mut samples))
+    })
+    .and_then(|written| written)
+    .map_err(|e| format!("Failed to export activity: {}", e));
+    match streamed {
+        Ok(()) => file.finish(job),
+        Err(e) => {
+            file.discard();
+            Err(e)
+        }
+    }
+}
+
+/// A Parquet file being written, with the schema version in its metadata
+struct ParquetFile {
+    table: &'static str,
+    path: PathBuf,
+    schema: Arc<Schema>,
+    writer: ArrowWriter<std::fs::File>,
+    rows: usize,
+    /// Bytes already counted as progress
+    reported: u64,
+}
+
+impl ParquetFile {
+    fn create(dir: &Path, table: &'static str, schema: Schema) -> Result<ParquetFile, String> {
+        let path = dir.join(format!("flowstate-{}.parquet", table));
+        let metadata = [
+            ("flowstate.schema_version", SCHEMA_VERSION.to_string()),
+            ("flowstate.table", table.to_string()),
+        ];
+
+        // Also on the Arrow schema, where pyarrow/Polars surface it directly
+        let schema = Arc::new(
+            schema.with_metadata(
+                metadata
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+        );
+
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .set_key_value_metadata(Some(
+                metadata
+                    .iter()
+                    .map(|(k, v)| KeyValue::new(k.to_string(), v.clone()))
+                    .collect(),
+            ))
+            .build();
+
+        let file = std::fs::File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(ParquetFile {
+            table,
+            path,
+            schema,
+            writer,
+            rows: 0,
+            reported: 0,
+        })
+    }
+
+    fn write(&mut self, job: &ExportJob, batch: &RecordBatch) -> Result<(), String> {
+        self.writer
+            .write(batch)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.rows += batch.num_rows();
+        let bytes = self.writer.bytes_written() as u64;
+        job.advance(self.table, batch.num_rows() as u64, bytes - self.reported);
+        self.reported = bytes;
+        Ok(())
+    }
+
+    fn finish(self, job: &ExportJob) -> Result<ExportedFile, String> {
+        let bytes = self.writer.bytes_written() as u64;
+        self.writer
+            .close()
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        let size = std::fs::metadata(&self.path).map_or(bytes, |m| m.len());
+        job.advance(self.table, 0, size.saturating_sub(self.reported));
+        Ok(ExportedFile {
+            table: self.table,
+            path: self.path,
+            rows: self.rows,
+        })
+    }
+
+    fn discard(self) {
+        drop(self.writer);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Export sessions, tasks and activity as Parquet files into a folder (picked in a
+/// dialog when `dir` is not given)
+#[tauri::command]
+pub async fn export_parquet(
+    app: AppHandle,
+    dir: Option<String>,
+) -> Result<ParquetExport, FlowStateError> {
+    crate::trace::scope("export_parquet", async move {
+        crate::app_lock::ensure_unlocked(&app, "export_parquet")?;
+        let user = crate::offline::current_user(&app)
+            .ok_or_else(|| "Not signed in; the export needs a sync user".to_string())?;
+        let dir = choose_dir(&app, dir).await?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let job = ExportJob::start(&app, "parquet")?;
+
+        let mut client = crate::db::connect(&app).await?;
+        let mut files = Vec::new();
+        let written = async {
+            for table in &REMOTE_TABLES {
+                files.push(export_remote(&mut client, &job, &dir, table, &user).await?);
+            }
+            files.push(export_activity(&app, &job, &dir)?);
+            Ok::<_, String>(())
+        }
+        .await;
+        if let Err(e) = written {
+            // No half an export: the files written before the failure go too
+            for file in &files {
+                let _ = std::fs::remove_file(&file.path);
+            }
+            return Err(e.into());
+        }
+        log::info!(
+            "Exported {} Parquet files to {}",
+            files.len(),
+            dir.display()
+        );
+
+        Ok(ParquetExport {
+            schema_version: SCHEMA_VERSION,
+            files,
+        })
+    })
+    .await
+}
@@ -0,0 +1,639 @@
+//! RSS/Atom feeds that turn new entries into read/review tasks.
+//!
+//! Each watched feed is polled every `POLL_INTERVAL` with conditional requests.
+//! Entries are identified by guid/id (or link) and remembered per feed, so an
+//! entry only ever makes one task; what is in a feed when it is added counts
+//! as already seen. Entries are remembered only once their tasks are saved,
+//! so a poll that can't save (read-only mode) leaves them for the next one.
+//! New entries must match one of the feed's keywords (any entry matches when
+//! there are none) and none of its excluded words. When a poll finds more
+//! than `DIGEST_THRESHOLD` matches they become a single digest task listing
+//! them, so a busy feed can't flood the task list. Watches live in
+//! `feeds.json`; each poll that creates tasks publishes `feed://tasks`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::parsers::{self, FeedEntry};
+
+pub(crate) const FEED_STORE: &str = "feeds.json";
+const FEED_KEY: &str = "watches";
+const SEEN_KEY: &str = "seen";
+const POLL_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// More matches than this in one poll become a single digest task
+const DIGEST_THRESHOLD: usize = 3;
+/// Entry ids remembered per feed (feeds only show their latest entries)
+const MAX_SEEN: usize = 1000;
+/// Characters of an entry summary copied into its task
+const SUMMARY_CHARS: usize = 500;
+const TASK_TAG: &str = "reading";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedWatch {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    /// Case-insensitive; an entry matches when its title or summary has any
+    pub keywords: Vec<String>,
+    /// Entries containing any of these are skipped
+    pub exclude: Vec<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_checked_at_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub tasks_created: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedTasksCreated {
+    pub feed_id: String,
+    pub task_ids: Vec<String>,
+    pub entries: usize,
+    pub digest: bool,
+}
+
+#[derive(Default)]
+pub struct FeedWatches {
+    watches: Mutex<Vec<FeedWatch>>,
+    /// Entry ids already handled, per feed, oldest first
+    seen: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+enum Fetched {
+    Unchanged,
+    Updated {
+        entries: Vec<FeedEntry>,
+        title: Option<String>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+async fn fetch(watch: &FeedWatch) -> Result<Fetched, String> {
+    let mut request = client()?.get(&watch.url);
+    if let Some(etag) = &watch.etag {
+        request = request.header("If-None-Match", etag);
+    }
+    if let Some(modified) = &watch.last_modified {
+        request = request.header("If-Modified-Since", modified);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch feed: {}", e))?;
+    if response.status().as_u16() == 304 {
+        return Ok(Fetched::Unchanged);
+    }
+    if !response.status().is_success() {
+        return Err(format!(
+            "Feed fetch failed: HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header("etag");
+    let last_modified = header("last-modified");
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read feed: {}", e))?;
+    let feed = parsers::parse_feed(&body).map_err(|e| format!("Invalid feed: {}", e))?;
+
+    Ok(Fetched::Updated {
+        entries: feed.entries,
+        title: feed.title,
+        etag,
+        last_modified,
+    })
+}
+
+/// Text of an HTML summary, whitespace collapsed and cut to `SUMMARY_CHARS`
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(SUMMARY_CHARS) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+fn matches_filters(watch: &FeedWatch, entry: &FeedEntry) -> bool {
+    let haystack = format!(
+        "{} {}",
+        entry.title.as_deref().unwrap_or_default(),
+        entry.summary.as_deref().map(plain_text).unwrap_or_default()
+    )
+    .to_lowercase();
+    let has = |word: &String| haystack.contains(&word.to_lowercase());
+    (watch.keywords.is_empty() || watch.keywords.iter().any(has)) && !watch.exclude.iter().any(has)
+}
+
+fn entry_title(entry: &FeedEntry) -> String {
+    entry
+        .title
+        .clone()
+        .or_else(|| entry.link.clone())
+        .unwrap_or_else(|| "Untitled entry".to_string())
+}
+
+fn review_task(app: &AppHandle, title: String, description: String) -> Map<String, Value> {
+    let mut task = crate::offline::owned_task(app);
+    task.insert("title".to_string(), Value::from(title));
+    task.insert("description".to_string(), Value::from(description));
+    task.insert("status".to_string(), Value::from("planned"));
+    task.insert("is_in_inbox".to_string(), Value::from(true));
+    task.insert("tags".to_string(), Value::from(vec![TASK_TAG]));
+    task
+}
+
+/// One task per entry, or a single digest when there are many
+fn tasks_for(
+    app: &AppHandle,
+    watch: &FeedWatch,
+    entries: &[&FeedEntry],
+) -> Vec<Map<String, Value>> {
+    if entries.len() > DIGEST_THRESHOLD {
+        let list: Vec<String> = entries
+            .iter()
+            .map(|entry| match &entry.link {
+                Some(link) => format!("- {} ({})", entry_title(entry), link),
+                None => format!("- {}", entry_title(entry)),
+            })
+            .collect();
+        return vec![review_task(
+            app,
+            format!("Review {} new posts from {}", entries.len(), watch.name),
+            list.join("\n"),
+        )];
+    }
+
+    entries
+        .iter()
+        .map(|entry| {
+            let mut description = String::new();
+            if let Some(link) = &entry.link {
+                description.push_str(link);
+            }
+            if let Some(summary) = entry.summary.as_deref().map(plain_text) {
+                if !summary.is_empty() {
+                    description.push_str("\n\n");
+                    description.push_str(&summary);
+                }
+            }
+            description.push_str(&format!("\n\nFrom {}", watch.name));
+            review_task(
+                app,
+                format!("Read: {}", entry_title(entry)),
+                description.trim_start().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn entry_id(entry: &FeedEntry) -> Option<&String> {
+    entry.id.as_ref().or(entry.title.as_ref())
+}
+
+/// Entries whose ids aren't in `seen`, each id once
+fn unseen<'a>(seen: &VecDeque<String>, entries: &'a [FeedEntry]) -> Vec<&'a FeedEntry> {
+    let mut unseen: Vec<&FeedEntry> = Vec::new();
+    for entry in entries {
+        let Some(id) = entry_id(entry) else {
+            continue;
+        };
+        if seen.contains(id) || unseen.iter().any(|e| entry_id(e) == Some(id)) {
+            continue;
+        }
+        unseen.push(entry);
+    }
+    unseen
+}
+
+fn mark_seen(seen: &mut VecDeque<String>, entries: &[&FeedEntry]) {
+    seen.extend(entries.iter().filter_map(|entry| entry_id(entry).cloned()));
+    while seen.len() > MAX_SEEN {
+        seen.pop_front();
+    }
+}
+
+/// Apply a fetched feed to `watch` and the ids seen in it. New matching
+/// entries go to `save`; only once it succeeds are the new entries marked
+/// seen and the validators kept, so after a failed save (say in read-only
+/// mode) the next poll fetches them again. Returns what `save` returned and
+/// the number of matching entries.
+fn apply_fetch<T>(
+    watch: &mut FeedWatch,
+    seen: &mut VecDeque<String>,
+    fetched: Fetched,
+    create_tasks: bool,
+    save: impl FnOnce(&FeedWatch, &[&FeedEntry]) -> Result<T, String>,
+) -> Result<Option<(T, usize)>, String> {
+    let Fetched::Updated {
+        entries,
+        title,
+        etag,
+        last_modified,
+    } = fetched
+    else {
+        return Ok(None);
+    };
+    if watch.name.is_empty() {
+        watch.name = title.unwrap_or_else(|| watch.url.clone());
+    }
+    let new = unseen(seen, &entries);
+    let matching: Vec<&FeedEntry> = new
+        .iter()
+        .copied()
+        .filter(|entry| matches_filters(watch, entry))
+        .collect();
+    let saved = if create_tasks && !matching.is_empty() {
+        Some((save(watch, &matching)?, matching.len()))
+    } else {
+        None
+    };
+    mark_seen(seen, &new);
+    watch.etag = etag;
+    watch.last_modified = last_modified;
+    Ok(saved)
+}
+
+fn save(app: &AppHandle) -> Result<(), String> {
+    let watches = app.state::<FeedWatches>();
+    let list = watches
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let seen = watches
+        .seen
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let store = app
+        .store(FEED_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", FEED_STORE, e))?;
+    store.set(
+        FEED_KEY,
+        serde_json::to_value(&list).map_err(|e| e.to_string())?,
+    );
+    store.set(
+        SEEN_KEY,
+        serde_json::to_value(&seen).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", FEED_STORE, e))
+}
+
+/// Fetch one feed and create tasks for new matching entries. On the first
+/// fetch (`create_tasks` false) entries are only marked seen.
+async fn poll_one(app: &AppHandle, mut watch: FeedWatch, create_tasks: bool) -> FeedWatch {
+    let result = fetch(&watch).await.and_then(|fetched| {
+        let watches = app.state::<FeedWatches>();
+        let mut seen = watches.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let ids = seen.entry(watch.id.clone()).or_default();
+        apply_fetch(&mut watch, ids, fetched, create_tasks, |watch, matching| {
+            crate::read_only::ensure_writable(app, "feeds")?;
+            crate::offline::save_tasks(app, tasks_for(app, watch, matching))
+        })
+    });
+
+    watch.last_checked_at_ms = Some(crate::events::now_ms());
+    match result {
+        Ok(created) => {
+            watch.last_error = None;
+            if let Some((saved, entries)) = created {
+                let task_ids: Vec<String> = saved
+                    .iter()
+                    .filter_map(|task| task.get("id").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect();
+                watch.tasks_created += task_ids.len() as u64;
+                log::info!(
+                    "Feed {}: {} new entries, {} tasks",
+                    watch.name,
+                    entries,
+                    task_ids.len()
+                );
+                crate::events::publish(
+                    app,
+                    "feed://tasks",
+                    &FeedTasksCreated {
+                        feed_id: watch.id.clone(),
+                        digest: entries > DIGEST_THRESHOLD,
+                        task_ids,
+                        entries,
+                    },
+                );
+            }
+        }
+        Err(e) => {
+            log::warn!("Feed {} not checked: {}", watch.url, e);
+            watch.last_error = Some(e);
+        }
+    }
+
+    // It may have been removed while fetching
+    if let Some(slot) = app
+        .state::<FeedWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter_mut()
+        .find(|w| w.id == watch.id)
+    {
+        *slot = watch.clone();
+    }
+    watch
+}
+
+async fn poll_all(app: &AppHandle) -> Vec<FeedWatch> {
+    let watches = app
+        .state::<FeedWatches>()
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let mut polled = Vec::new();
+    for watch in watches {
+        polled.push(poll_one(app, watch, true).await);
+    }
+    if !polled.is_empty() {
+        if let Err(e) = save(app) {
+            log::warn!("{}", e);
+        }
+    }
+    polled
+}
+
+/// Load watches and the entries already seen
+pub fn init(app: &AppHandle) {
+    let store = app.store(FEED_STORE).ok();
+    let load = |key: &str| store.as_ref().and_then(|store| store.get(key));
+    let watches: Vec<FeedWatch> = load(FEED_KEY)
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default();
+    let seen: HashMap<String, VecDeque<String>> = load(SEEN_KEY)
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default();
+
+    let state = app.state::<FeedWatches>();
+    *state.watches.lock().unwrap_or_else(|e| e.into_inner()) = watches;
+    *state.seen.lock().unwrap_or_else(|e| e.into_inner()) = seen;
+}
+
+/// Poll the watches periodically (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            poll_all(&app).await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+fn remove(app: &AppHandle, id: &str) -> bool {
+    let state = app.state::<FeedWatches>();
+    let mut watches = state.watches.lock().unwrap_or_else(|e| e.into_inner());
+    let before = watches.len();
+    watches.retain(|w| w.id != id);
+    state
+        .seen
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(id);
+    watches.len() != before
+}
+
+fn words(list: Option<Vec<String>>) -> Vec<String> {
+    list.unwrap_or_default()
+        .into_iter()
+        .map(|w| w.trim().to_string())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+#[tauri::command]
+pub fn list_feed_watches(watches: tauri::State<'_, FeedWatches>) -> Vec<FeedWatch> {
+    watches
+        .watches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Watch an RSS/Atom feed; it is fetched once (without creating tasks) before
+/// being added
+#[tauri::command]
+pub async fn add_feed_watch(
+    app: AppHandle,
+    url: String,
+    name: Option<String>,
+    keywords: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+) -> Result<FeedWatch, FlowStateError> {
+    crate::trace::scope("add_feed_watch", async move {
+        crate::read_only::ensure_writable(&app, "add_feed_watch")?;
+        let url = url.trim().to_string();
+        let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Unsupported URL scheme: {}", parsed.scheme()).into());
+        }
+
+        let bytes: [u8; 8] = rand::random();
+        let watch = FeedWatch {
+            id: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            name: name.map(|n| n.trim().to_string()).unwrap_or_default(),
+            url,
+            keywords: words(keywords),
+            exclude: words(exclude),
+            etag: None,
+            last_modified: None,
+            last_checked_at_ms: None,
+            last_error: None,
+            tasks_created: 0,
+        };
+
+        app.state::<FeedWatches>()
+            .watches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(watch.clone());
+        let watch = poll_one(&app, watch, false).await;
+        if let Some(error) = watch.last_error {
+            remove(&app, &watch.id);
+            return Err(error.into());
+        }
+
+        save(&app)?;
+        log::info!("Watching feed {}", watch.name);
+        Ok(watch)
+    })
+    .await
+}
+
+/// Change a feed's keyword filters (applies to entries not seen yet)
+#[tauri::command]
+pub fn set_feed_filters(
+    app: AppHandle,
+    id: String,
+    keywords: Vec<String>,
+    exclude: Vec<String>,
+) -> Result<FeedWatch, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_feed_filters")?;
+    let watch = {
+        let state = app.state::<FeedWatches>();
+        let mut watches = state.watches.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(watch) = watches.iter_mut().find(|w| w.id == id) else {
+            return Err(format!("Unknown feed: {}", id).into());
+        };
+        watch.keywords = words(Some(keywords));
+        watch.exclude = words(Some(exclude));
+        watch.clone()
+    };
+    save(&app)?;
+    Ok(watch)
+}
+
+#[tauri::command]
+pub fn remove_feed_watch(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "remove_feed_watch")?;
+    if !remove(&app, &id) {
+        return Err(format!("Unknown feed: {}", id).into());
+    }
+    save(&app)?;
+    Ok(())
+}
+
+/// Poll every watched feed now
+#[tauri::command]
+pub async fn refresh_feeds(app: AppHandle) -> Result<Vec<FeedWatch>, FlowStateError> {
+    crate::trace::scope("refresh_feeds", async move { Ok(poll_all(&app).await) }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watch() -> FeedWatch {
+        FeedWatch {
+            id: "feed".to_string(),
+            name: "Blog".to_string(),
+            url: "https://example.com/feed.xml".to_string(),
+            keywords: Vec::new(),
+            exclude: Vec::new(),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            last_checked_at_ms: None,
+            last_error: None,
+            tasks_created: 0,
+        }
+    }
+
+    fn entry(id: &str) -> FeedEntry {
+        FeedEntry {
+            id: Some(id.to_string()),
+            title: Some(format!("Post {}", id)),
+            ..FeedEntry::default()
+        }
+    }
+
+    fn fetched(ids: &[&str]) -> Fetched {
+        Fetched::Updated {
+            entries: ids.iter().map(|id| entry(id)).collect(),
+            title: None,
+            etag: Some("\"v2\"".to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn a_failed_save_leaves_entries_unseen() {
+        let mut watch = watch();
+        let mut seen = VecDeque::from(["a".to_string()]);
+
+        // Read-only: the save fails, nothing is consumed
+        let result = apply_fetch(
+            &mut watch,
+            &mut seen,
+            fetched(&["a", "b", "c"]),
+            true,
+            |_, _| Err::<(), _>("read-only".to_string()),
+        );
+        assert_eq!(result.unwrap_err(), "read-only");
+        assert_eq!(seen, ["a"]);
+        assert_eq!(watch.etag.as_deref(), Some("\"v1\""));
+
+        // The next poll sees the same entries and keeps the new validator
+        let mut saved = Vec::new();
+        let result = apply_fetch(
+            &mut watch,
+            &mut seen,
+            fetched(&["a", "b", "c"]),
+            true,
+            |_, entries| {
+                saved = entries.iter().filter_map(|e| e.id.clone()).collect();
+                Ok(())
+            },
+        );
+        assert_eq!(result.unwrap().map(|(_, entries)| entries), Some(2));
+        assert_eq!(saved, ["b", "c"]);
+        assert_eq!(seen, ["a", "b", "c"]);
+        assert_eq!(watch.etag.as_deref(), Some("\"v2\""));
+    }
+
+    #[test]
+    fn the_first_fetch_only_marks_entries_seen() {
+        let mut watch = watch();
+        let mut seen = VecDeque::new();
+        let result = apply_fetch(
+            &mut watch,
+            &mut seen,
+            fetched(&["a", "a", "b"]),
+            false,
+            |_, _| panic!("no tasks on the first fetch"),
+        );
+        assert!(matches!(result, Ok(None::<((), usize)>)));
+        assert_eq!(seen, ["a", "b"]);
+    }
+}
@@ -0,0 +1,692 @@
+//! Focus (Pomodoro) timer engine.
+//!
+//! The timer lives in managed state and ticks in a background task, so it
+//! survives webview reloads and closed windows. While a phase is running,
+//! windows that called `subscribe_focus_ticks` get `focus://tick` at the rate
+//! they asked for: by default every second while visible and every 30 seconds
+//! while hidden or minimized, so a background renderer can sleep. State
+//! changes (start, pause, ...) go to every window. When a phase ends it publishes
+//! `focus://phase` (with the finished phase, for recording history) and sends
+//! a break notification through the notification engine. Focus phases roll
+//! straight into a break; after a break the next focus phase waits paused
+//! until `resume_focus_session`. Every change is saved to `focus.json` with
+//! the phase's wall-clock end, so a restart after a crash picks the session
+//! up where it was (a phase that ended meanwhile completes on the first tick).
+//! Focus phases are recorded with the session's tags when they end or are
+//! cut short (`sessions.rs`). A session started without a task can suggest
+//! one from the working context (`task_context.rs`). In read-only mode the
+//! timer runs as usual but nothing is saved or recorded.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, WebviewWindow, Window, WindowEvent};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::notifications::{Category, Priority};
+
+pub(crate) const SESSION_STORE: &str = "focus.json";
+const SESSION_KEY: &str = "session";
+const TICK: Duration = Duration::from_secs(1);
+/// Longest tick interval a window can ask for
+const MAX_TICK_MS: u64 = 5 * 60_000;
+/// Tolerance for the tick loop waking up a little early or late
+const TICK_SLACK_MS: u64 = 250;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusPhase {
+    Focus,
+    ShortBreak,
+    LongBreak,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusStatus {
+    Idle,
+    Running,
+    Paused,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FocusDurations {
+    pub focus_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// Every n-th break is a long one
+    pub long_break_every: u32,
+}
+
+impl Default for FocusDurations {
+    fn default() -> Self {
+        FocusDurations {
+            focus_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+        }
+    }
+}
+
+impl FocusDurations {
+    fn phase_ms(&self, phase: FocusPhase) -> u64 {
+        let minutes = match phase {
+            FocusPhase::Focus => self.focus_minutes,
+            FocusPhase::ShortBreak => self.short_break_minutes,
+            FocusPhase::LongBreak => self.long_break_minutes,
+        };
+        u64::from(minutes.max(1)) * 60_000
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSessionState {
+    pub status: FocusStatus,
+    pub phase: FocusPhase,
+    pub task_id: Option<String>,
+    /// Normalized (`sessions::normalize_tags`)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub durations: FocusDurations,
+    /// Focus phases finished in this session
+    pub completed_focus: u32,
+    pub phase_duration_ms: u64,
+    pub remaining_ms: u64,
+    /// Wall-clock start of the current phase (ms since epoch)
+    pub phase_started_at_ms: Option<u64>,
+    /// Wall-clock end of the current phase while running
+    pub ends_at_ms: Option<u64>,
+}
+
+impl Default for FocusSessionState {
+    fn default() -> Self {
+        FocusSessionState {
+            status: FocusStatus::Idle,
+            phase: FocusPhase::Focus,
+            task_id: None,
+            tags: Vec::new(),
+            durations: FocusDurations::default(),
+            completed_focus: 0,
+            phase_duration_ms: 0,
+            remaining_ms: 0,
+            phase_started_at_ms: None,
+            ends_at_ms: None,
+        }
+    }
+}
+
+impl FocusSessionState {
+    /// Refresh `remaining_ms` from the wall clock
+    fn update_remaining(&mut self, now: u64) {
+        if let (FocusStatus::Running, Some(ends)) = (self.status, self.ends_at_ms) {
+            self.remaining_ms = ends.saturating_sub(now);
+        }
+    }
+
+    fn begin(&mut self, phase: FocusPhase, running: bool, now: u64) {
+        self.phase = phase;
+        self.phase_duration_ms = self.durations.phase_ms(phase);
+        self.remaining_ms = self.phase_duration_ms;
+        self.phase_started_at_ms = running.then_some(now);
+        self.status = if running {
+            FocusStatus::Running
+        } else {
+            FocusStatus::Paused
+        };
+        self.ends_at_ms = running.then_some(now + self.remaining_ms);
+    }
+
+    /// Move on once the running phase is over: a focus phase leads to a
+    /// running break, a break to a paused focus phase. The phase that ended.
+    fn roll_over(&mut self, now: u64) -> Option<FocusPhase> {
+        self.update_remaining(now);
+        if self.status != FocusStatus::Running || self.remaining_ms > 0 {
+            return None;
+        }
+        let finished = self.phase;
+        match finished {
+            FocusPhase::Focus => {
+                self.completed_focus += 1;
+                let every = self.durations.long_break_every.max(1);
+                let next = if self.completed_focus % every == 0 {
+                    FocusPhase::LongBreak
+                } else {
+                    FocusPhase::ShortBreak
+                };
+                self.begin(next, true, now);
+            }
+            FocusPhase::ShortBreak | FocusPhase::LongBreak => {
+                self.begin(FocusPhase::Focus, false, now);
+            }
+        }
+        Some(finished)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusPhaseEnded {
+    pub phase: FocusPhase,
+    pub task_id: Option<String>,
+    pub tags: Vec<String>,
+    pub started_at_ms: Option<u64>,
+    pub ended_at_ms: u64,
+    pub duration_ms: u64,
+    /// Recorded focus session (`sessions.rs`); None for breaks
+    pub session_id: Option<i64>,
+    pub next: FocusSessionState,
+}
+
+/// How often a window wants `focus://tick`
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TickRates {
+    pub visible_ms: u64,
+    pub hidden_ms: u64,
+}
+
+impl Default for TickRates {
+    fn default() -> Self {
+        TickRates {
+            visible_ms: 1_000,
+            hidden_ms: 30_000,
+        }
+    }
+}
+
+struct TickSubscriber {
+    rates: TickRates,
+    /// As reported by the page (`document.visibilityState`)
+    page_visible: bool,
+    /// Shown and not minimized
+    window_visible: bool,
+    last_sent_ms: u64,
+}
+
+impl TickSubscriber {
+    fn interval_ms(&self) -> u64 {
+        if self.page_visible && self.window_visible {
+            self.rates.visible_ms
+        } else {
+            self.rates.hidden_ms
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FocusEngine {
+    state: Mutex<FocusSessionState>,
+    /// Tick subscriptions by window label
+    subscribers: Mutex<HashMap<String, TickSubscriber>>,
+}
+
+impl FocusEngine {
+    pub(crate) fn snapshot(&self) -> FocusSessionState {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.update_remaining(now_ms());
+        state.clone()
+    }
+
+    /// Windows whose tick interval has passed; they count as ticked at `now`
+    fn due_subscribers(&self, now: u64) -> Vec<String> {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers
+            .iter_mut()
+            .filter(|(_, s)| now + TICK_SLACK_MS >= s.last_sent_ms + s.interval_ms())
+            .map(|(label, s)| {
+                s.last_sent_ms = now;
+                label.clone()
+            })
+            .collect()
+    }
+
+    /// Every window just got the state (through a broadcast)
+    fn mark_ticked(&self, now: u64) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        for subscriber in subscribers.values_mut() {
+            subscriber.last_sent_ms = now;
+        }
+    }
+
+    /// Apply `change` to a window's subscription; true if it went from hidden
+    /// to visible
+    fn update_visibility(&self, label: &str, change: impl FnOnce(&mut TickSubscriber)) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(subscriber) = subscribers.get_mut(label) else {
+            return false;
+        };
+        let was_visible = subscriber.page_visible && subscriber.window_visible;
+        change(subscriber);
+        let visible = subscriber.page_visible && subscriber.window_visible;
+        if visible && !was_visible {
+            subscriber.last_sent_ms = now_ms();
+        }
+        visible && !was_visible
+    }
+}
+
+fn read_only(app: &AppHandle) -> bool {
+    app.state::<crate::read_only::ReadOnlyMode>().is_enabled()
+}
+
+fn save(app: &AppHandle, state: &FocusSessionState) {
+    if read_only(app) {
+        return;
+    }
+    let result = app
+        .store(SESSION_STORE)
+        .map_err(|e| e.to_string())
+        .and_then(|store| {
+            store.set(
+                SESSION_KEY,
+                serde_json::to_value(state).map_err(|e| e.to_string())?,
+            );
+            store.save().map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save focus session: {}", e);
+    }
+}
+
+/// Apply `change` to the session, then save and publish the result
+fn update(
+    app: &AppHandle,
+    change: impl FnOnce(&mut FocusSessionState, u64) -> Result<(), String>,
+) -> Result<FocusSessionState, FlowStateError> {
+    let engine = app.state::<FocusEngine>();
+    let state = {
+        let mut state = engine.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = now_ms();
+        state.update_remaining(now);
+        change(&mut state, now)?;
+        state.clone()
+    };
+    save(app, &state);
+    crate::events::publish(app, "focus://tick", &state);
+    engine.mark_ticked(now_ms());
+    crate::tray::refresh(app, &state);
+    Ok(state)
+}
+
+/// Record the focus time of a phase cut short by stopping or restarting
+fn record_unfinished(app: &AppHandle, previous: &FocusSessionState, now: u64) {
+    let (FocusPhase::Focus, Some(started_at_ms)) = (previous.phase, previous.phase_started_at_ms)
+    else {
+        return;
+    };
+    let focused_ms = previous
+        .phase_duration_ms
+        .saturating_sub(previous.remaining_ms);
+    if focused_ms == 0 || read_only(app) {
+        return;
+    }
+    if let Err(e) = crate::sessions::record(
+        app,
+        previous.task_id.as_deref(),
+        started_at_ms,
+        now,
+        focused_ms,
+        false,
+        &previous.tags,
+    ) {
+        log::warn!("Failed to record focus session: {}", e);
+    }
+}
+
+/// Finish the current phase if its time is up
+fn advance(app: &AppHandle) {
+    let mut ended = {
+        let engine = app.state::<FocusEngine>();
+        let mut state = engine.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = now_ms();
+        let started_at_ms = state.phase_started_at_ms;
+        let duration_ms = state.phase_duration_ms;
+        let Some(finished) = state.roll_over(now) else {
+            return;
+        };
+
+        FocusPhaseEnded {
+            phase: finished,
+            task_id: state.task_id.clone(),
+            tags: state.tags.clone(),
+            started_at_ms,
+            ended_at_ms: now,
+            duration_ms,
+            session_id: None,
+            next: state.clone(),
+        }
+    };
+
+    if let (FocusPhase::Focus, Some(started_at_ms), false) =
+        (ended.phase, ended.started_at_ms, read_only(app))
+    {
+        match crate::sessions::record(
+            app,
+            ended.task_id.as_deref(),
+            started_at_ms,
+            ended.ended_at_ms,
+            ended.duration_ms,
+            true,
+            &ended.tags,
+        ) {
+            Ok(id) => ended.session_id = Some(id),
+            Err(e) => log::warn!("Failed to record focus session: {}", e),
+        }
+    }
+
+    save(app, &ended.next);
+    crate::events::publish(app, "focus://phase", &ended);
+    crate::tray::refresh(app, &ended.next);
+    let (title, body) = match ended.phase {
+        FocusPhase::Focus => (
+            "Focus session complete",
+            format!(
+                "Take a {} minute break",
+                ended.next.phase_duration_ms / 60_000
+            ),
+        ),
+        _ => (
+            "Break is over",
+            "Ready for the next focus session".to_string(),
+        ),
+    };
+    crate::notifications::notify(app, Category::Breaks, Priority::Normal, title, &body);
+}
+
+/// Pause a running focus phase because the user went idle (breaks keep running)
+pub fn pause_for_idle(app: &AppHandle) -> bool {
+    let running_focus = {
+        let state = app.state::<FocusEngine>().snapshot();
+        state.status == FocusStatus::Running && state.phase == FocusPhase::Focus
+    };
+    running_focus && pause_focus_session(app.clone()).is_ok()
+}
+
+/// Restore a saved session and start the tick loop
+pub fn init(app: &AppHandle) {
+    match app.store(SESSION_STORE) {
+        Ok(store) => {
+            if let Some(value) = store.get(SESSION_KEY) {
+                match serde_json::from_value::<FocusSessionState>(value) {
+                    Ok(saved) => {
+                        if saved.status != FocusStatus::Idle {
+                            log::info!("Restored {:?} focus session", saved.status);
+                        }
+                        *app.state::<FocusEngine>()
+                            .state
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner()) = saved;
+                    }
+                    Err(e) => log::warn!("Ignoring invalid focus session: {}", e),
+                }
+            }
+        }
+        Err(e) => log::warn!("Failed to open {}: {}", SESSION_STORE, e),
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            let state = app.state::<FocusEngine>().snapshot();
+            // Also picks up the tray moving to a monitor of another density
+            crate::tray::refresh(&app, &state);
+            if state.status != FocusStatus::Running {
+                continue;
+            }
+            if state.remaining_ms == 0 {
+                advance(&app);
+            } else {
+                let due = app.state::<FocusEngine>().due_subscribers(now_ms());
+                crate::events::publish_to(&app, &due, "focus://tick", &state);
+            }
+        }
+    });
+}
+
+/// Start a new focus session (replacing any current one)
+#[tauri::command]
+pub fn start_focus_session(
+    app: AppHandle,
+    task_id: Option<String>,
+    durations: Option<FocusDurations>,
+    tags: Option<Vec<String>>,
+) -> Result<FocusSessionState, FlowStateError> {
+    let tags = crate::sessions::normalize_tags(tags.unwrap_or_default())?;
+    let suggest = task_id.is_none();
+    let mut previous = None;
+    let state = update(&app, |state, now| {
+        previous = Some(state.clone());
+        *state = FocusSessionState {
+            task_id,
+            tags,
+            durations: durations.unwrap_or_default(),
+            ..FocusSessionState::default()
+        };
+        state.begin(FocusPhase::Focus, true, now);
+        Ok(())
+    })?;
+    if let Some(previous) = previous {
+        record_unfinished(&app, &previous, now_ms());
+    }
+    if suggest {
+        crate::task_context::suggest_for_session(&app);
+    }
+    Ok(state)
+}
+
+/// Link the current session to a task, e.g. a confirmed suggestion
+/// (`task_context.rs`); the focus phase is recorded against it
+#[tauri::command]
+pub fn link_focus_session_task(
+    app: AppHandle,
+    task_id: String,
+) -> Result<FocusSessionState, FlowStateError> {
+    if task_id.trim().is_empty() {
+        return Err("task_id must not be empty".into());
+    }
+    update(&app, |state, _| {
+        if state.status == FocusStatus::Idle {
+            return Err("No focus session".to_string());
+        }
+        state.task_id = Some(task_id);
+        Ok(())
+    })
+}
+
+/// Replace the tags of the current session; the focus phase is recorded
+/// with them when it ends
+#[tauri::command]
+pub fn set_focus_session_tags(
+    app: AppHandle,
+    tags: Vec<String>,
+) -> Result<FocusSessionState, FlowStateError> {
+    let tags = crate::sessions::normalize_tags(tags)?;
+    update(&app, |state, _| {
+        if state.status == FocusStatus::Idle {
+            return Err("No focus session".to_string());
+        }
+        state.tags = tags;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn pause_focus_session(app: AppHandle) -> Result<FocusSessionState, FlowStateError> {
+    update(&app, |state, _| {
+        if state.status != FocusStatus::Running {
+            return Err("No running focus session".to_string());
+        }
+        state.status = FocusStatus::Paused;
+        state.ends_at_ms = None;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn resume_focus_session(app: AppHandle) -> Result<FocusSessionState, FlowStateError> {
+    update(&app, |state, now| {
+        if state.status != FocusStatus::Paused {
+            return Err("No paused focus session".to_string());
+        }
+        state.status = FocusStatus::Running;
+        state.ends_at_ms = Some(now + state.remaining_ms);
+        // A phase that was waiting to begin starts now
+        if state.remaining_ms == state.phase_duration_ms {
+            state.phase_started_at_ms = Some(now);
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn stop_focus_session(app: AppHandle) -> Result<FocusSessionState, FlowStateError> {
+    let mut previous = None;
+    let state = update(&app, |state, _| {
+        previous = Some(state.clone());
+        *state = FocusSessionState {
+            durations: state.durations.clone(),
+            ..FocusSessionState::default()
+        };
+        Ok(())
+    })?;
+    if let Some(previous) = previous {
+        record_unfinished(&app, &previous, now_ms());
+    }
+    Ok(state)
+}
+
+#[tauri::command]
+pub fn get_focus_session_state(engine: tauri::State<'_, FocusEngine>) -> FocusSessionState {
+    engine.snapshot()
+}
+
+fn window_visible(window: &Window) -> bool {
+    window.is_visible().unwrap_or(true) && !window.is_minimized().unwrap_or(false)
+}
+
+/// Send a window the current state right away, if a phase is running
+fn tick_now(app: &AppHandle, label: &str) {
+    let state = app.state::<FocusEngine>().snapshot();
+    if state.status == FocusStatus::Running {
+        crate::events::publish_to(app, &[label.to_string()], "focus://tick", &state);
+    }
+}
+
+/// Follow window visibility for the tick rate; drop closed windows
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    let engine = window.state::<FocusEngine>();
+    match event {
+        WindowEvent::Destroyed => {
+            engine
+                .subscribers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(window.label());
+        }
+        WindowEvent::Focused(_) | WindowEvent::Resized(_) => {
+            let visible = window_visible(window);
+            if engine.update_visibility(window.label(), |s| s.window_visible = visible) {
+                tick_now(window.app_handle(), window.label());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Receive `focus://tick` in this window at the given rates (replacing any
+/// earlier subscription); returns the current state
+#[tauri::command]
+pub fn subscribe_focus_ticks(window: WebviewWindow, rates: Option<TickRates>) -> FocusSessionState {
+    let rates = rates.unwrap_or_default();
+    let min_ms = TICK.as_millis() as u64;
+    let subscriber = TickSubscriber {
+        rates: TickRates {
+            visible_ms: rates.visible_ms.clamp(min_ms, MAX_TICK_MS),
+            hidden_ms: rates.hidden_ms.clamp(min_ms, MAX_TICK_MS),
+        },
+        page_visible: true,
+        window_visible: window.is_visible().unwrap_or(true)
+            && !window.is_minimized().unwrap_or(false),
+        last_sent_ms: now_ms(),
+    };
+    let engine = window.state::<FocusEngine>();
+    engine
+        .subscribers
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(window.label().to_string(), subscriber);
+    engine.snapshot()
+}
+
+/// Report the page's visibility (`visibilitychange`), which the window
+/// events alone can't tell
+#[tauri::command]
+pub fn set_focus_tick_visibility(window: WebviewWindow, visible: bool) {
+    let engine = window.state::<FocusEngine>();
+    if engine.update_visibility(window.label(), |s| s.page_visible = visible) {
+        tick_now(window.app_handle(), window.label());
+    }
+}
+
+#[tauri::command]
+pub fn unsubscribe_focus_ticks(window: WebviewWindow) {
+    window
+        .state::<FocusEngine>()
+        .subscribers
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(window.label());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_last_their_length_across_dst() {
+        let tz = chrono_tz::America::New_York;
+        let minute = 60 * 1000;
+        // 01:50 EST, ten minutes before the clocks jump to 03:00 EDT
+        crate::clock::set(
+            chrono::DateTime::parse_from_rfc3339("2026-03-08T01:50:00-05:00")
+                .unwrap()
+                .timestamp_millis(),
+        );
+        let mut state = FocusSessionState::default();
+        state.begin(FocusPhase::Focus, true, now_ms());
+
+        crate::clock::advance(20 * minute);
+        assert_eq!(
+            crate::clock::now_in(&tz).format("%H:%M").to_string(),
+            "03:10"
+        );
+        assert_eq!(state.roll_over(now_ms()), None);
+        assert_eq!(state.remaining_ms, 5 * minute);
+
+        crate::clock::advance(5 * minute);
+        assert_eq!(state.roll_over(now_ms()), Some(FocusPhase::Focus));
+        assert_eq!(
+            (state.phase, state.status),
+            (FocusPhase::ShortBreak, FocusStatus::Running)
+        );
+        assert_eq!(state.completed_focus, 1);
+        assert_eq!(state.ends_at_ms, Some(now_ms() + 5 * minute));
+
+        crate::clock::advance(5 * minute);
+        assert_eq!(state.roll_over(now_ms()), Some(FocusPhase::ShortBreak));
+        assert_eq!(
+            (state.phase, state.status),
+            (FocusPhase::Focus, FocusStatus::Paused)
+        );
+        crate::clock::reset();
+    }
+}
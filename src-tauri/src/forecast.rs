@@ -0,0 +1,249 @@
+//! Completion forecasts for projects.
+//!
+//! Throughput is the number of tasks of the project (and its subprojects)
+//! completed in each of the last `HISTORY_WEEKS` weeks, read from the local
+//! database. Only the sync user's projects and tasks count. A Monte-Carlo run replays randomly drawn historical weeks until
+//! the remaining tasks are done; the spread of those runs gives the completion
+//! date at 50/85/95 % confidence. Weeks without completions are part of the
+//! history, so an irregular pace widens the range instead of being ignored.
+
+use chrono::Duration;
+use rand::Rng;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+/// Weeks of completion history sampled from
+const HISTORY_WEEKS: i64 = 12;
+const TRIALS: usize = 10_000;
+/// A trial that hasn't finished after this many weeks counts as never
+const MAX_WEEKS: u32 = 520;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastPoint {
+    /// 0.5, 0.85 or 0.95
+    pub confidence: f32,
+    pub weeks: u32,
+    /// "YYYY-MM-DD"
+    pub date: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectForecast {
+    pub project_id: String,
+    pub remaining_tasks: i64,
+    /// Completed tasks per week, oldest first
+    pub weekly_throughput: Vec<i64>,
+    /// Empty when there is nothing left, or no completions to extrapolate from
+    pub forecast: Vec<ForecastPoint>,
+    /// Why `forecast` is empty
+    pub note: Option<String>,
+}
+
+/// Remaining tasks and per-week completions (oldest first) of a project tree
+async fn history(app: &AppHandle, project_id: &str) -> Result<(i64, Vec<i64>), String> {
+    let user = crate::offline::current_user(app)
+        .ok_or_else(|| "Not signed in; the forecast needs a sync user".to_string())?;
+    let client = crate::db::connect(app).await?;
+
+    let tree = "WITH RECURSIVE tree AS ( \
+                    SELECT id::text AS id FROM public.projects WHERE id::text = $1 AND user_id::text = $2 \
+                    UNION \
+                    SELECT p.id::text FROM public.projects p JOIN tree ON p.parent_id::text = tree.id \
+                    WHERE p.user_id::text = $2 AND NOT coalesce(p.is_deleted, false) \
+                ) ";
+
+    let exists = client
+        .query_opt(
+            "SELECT 1 FROM public.projects WHERE id::text = $1 AND user_id::text = $2",
+            &[&project_id, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to look up project: {}", e))?;
+    if exists.is_none() {
+        return Err(format!("Unknown project: {}", project_id));
+    }
+
+    let remaining: i64 = client
+        .query_one(
+            &format!(
+                "{}SELECT count(*) FROM public.tasks t JOIN tree ON t.project_id::text = tree.id \
+                 WHERE t.user_id::text = $2 \
+                   AND coalesce(t.status, '') <> 'done' AND NOT coalesce(t.is_deleted, false)",
+                tree
+            ),
+            &[&project_id, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to count remaining tasks: {}", e))?
+        .get(0);
+
+    // Rolling 7-day windows back from now, not calendar weeks: 0 is the last
+    // seven days. Windows without completions count as 0.
+    let rows = client
+        .query(
+            &format!(
+                "{}SELECT floor(extract(epoch FROM now() - t.completed_at) / 604800)::int, count(*) \
+                 FROM public.tasks t JOIN tree ON t.project_id::text = tree.id \
+                 WHERE t.user_id::text = $2 \
+                   AND t.completed_at IS NOT NULL AND t.completed_at > now() - make_interval(weeks => $3) \
+                   AND NOT coalesce(t.is_deleted, false) \
+                 GROUP BY 1",
+                tree
+            ),
+            &[&project_id, &user, &(HISTORY_WEEKS as i32)],
+        )
+        .await
+        .map_err(|e| format!("Failed to read completion history: {}", e))?;
+
+    let weeks = HISTORY_WEEKS as usize;
+    let mut weekly = vec![0i64; weeks];
+    for row in rows {
+        let weeks_ago: i32 = row.get(0);
+        if let Some(slot) = usize::try_from(weeks_ago).ok().filter(|w| *w < weeks) {
+            weekly[weeks - 1 - slot] = row.get(1);
+        }
+    }
+    Ok((remaining, weekly))
+}
+
+/// Weeks each trial needed to finish `remaining` tasks, sorted
+fn simulate(remaining: i64, weekly: &[i64], rng: &mut impl Rng) -> Vec<u32> {
+    let mut outcomes: Vec<u32> = (0..TRIALS)
+        .map(|_| {
+            let mut left = remaining;
+            let mut weeks = 0;
+            while left > 0 && weeks < MAX_WEEKS {
+                left -= weekly[rng.gen_range(0..weekly.len())];
+                weeks += 1;
+            }
+            weeks
+        })
+        .collect();
+    outcomes.sort_unstable();
+    outcomes
+}
+
+/// Weeks within which `confidence` of the sorted outcomes finished
+fn percentile(outcomes: &[u32], confidence: f32) -> u32 {
+    let index = ((outcomes.len() as f32 * confidence).ceil() as usize).clamp(1, outcomes.len()) - 1;
+    outcomes[index]
+}
+
+/// Forecast when the remaining tasks of a project (including subprojects) will be
+/// done, from the last 12 weeks of completions
+#[tauri::command]
+pub async fn forecast_project(
+    app: AppHandle,
+    id: String,
+) -> Result<ProjectForecast, FlowStateError> {
+    crate::trace::scope("forecast_project", async move {
+        crate::app_lock::ensure_unlocked(&app, "forecast_project")?;
+        let (remaining, weekly) = history(&app, &id).await?;
+
+        let mut forecast = Vec::new();
+        let note = if remaining == 0 {
+            Some("No remaining tasks".to_string())
+        } else if weekly.iter().all(|w| *w == 0) {
+            Some(format!(
+                "No tasks completed in the last {} weeks",
+                HISTORY_WEEKS
+            ))
+        } else {
+            let outcomes = simulate(remaining, &weekly, &mut rand::thread_rng());
+            let today = crate::clock::now_local().date_naive();
+            for confidence in [0.5f32, 0.85, 0.95] {
+                let weeks = percentile(&outcomes, confidence);
+                if weeks >= MAX_WEEKS {
+                    break;
+                }
+                forecast.push(ForecastPoint {
+                    confidence,
+                    weeks,
+                    date: (today + Duration::weeks(weeks as i64)).to_string(),
+                });
+            }
+            (forecast.len() < 3).then(|| {
+                format!(
+                    "Some outcomes take more than {} weeks at the current pace",
+                    MAX_WEEKS
+                )
+            })
+        };
+
+        Ok(ProjectForecast {
+            project_id: id,
+            remaining_tasks: remaining,
+            weekly_throughput: weekly,
+            forecast,
+            note,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn seeded() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn steady_pace_finishes_in_fixed_weeks() {
+        let outcomes = simulate(12, &[5; 12], &mut seeded());
+        assert_eq!(outcomes.len(), TRIALS);
+        assert!(outcomes.iter().all(|&weeks| weeks == 3));
+    }
+
+    #[test]
+    fn same_seed_same_forecast() {
+        let weekly = [0, 3, 1, 0, 7, 2, 0, 0, 4, 1, 2, 5];
+        let first = simulate(40, &weekly, &mut seeded());
+        assert_eq!(first, simulate(40, &weekly, &mut seeded()));
+        assert!(first.windows(2).all(|w| w[0] <= w[1]));
+        // Never faster than every week at the best pace
+        assert!(first[0] >= 6);
+        let (p50, p85, p95) = (
+            percentile(&first, 0.5),
+            percentile(&first, 0.85),
+            percentile(&first, 0.95),
+        );
+        assert!(p50 <= p85 && p85 <= p95, "{} {} {}", p50, p85, p95);
+    }
+
+    #[test]
+    fn empty_weeks_widen_the_range() {
+        // Half the weeks see 10 completions, half none: 10 tasks take one
+        // week with chance 1/2, two weeks with 1/4, ...
+        let outcomes = simulate(10, &[0, 10], &mut seeded());
+        assert_eq!(outcomes[0], 1);
+        assert_eq!(percentile(&outcomes, 0.4), 1);
+        assert_eq!(percentile(&outcomes, 0.6), 2);
+        assert!((3..=5).contains(&percentile(&outcomes, 0.95)));
+        let share_one = outcomes.iter().filter(|&&w| w == 1).count() as f64 / TRIALS as f64;
+        assert!((0.47..0.53).contains(&share_one), "{}", share_one);
+    }
+
+    #[test]
+    fn slow_pace_is_capped() {
+        let outcomes = simulate(10_000, &[1], &mut seeded());
+        assert!(outcomes.iter().all(|&weeks| weeks == MAX_WEEKS));
+    }
+
+    #[test]
+    fn percentiles_of_sorted_outcomes() {
+        let outcomes: Vec<u32> = (1..=100).collect();
+        assert_eq!(percentile(&outcomes, 0.5), 50);
+        assert_eq!(percentile(&outcomes, 0.85), 85);
+        assert_eq!(percentile(&outcomes, 0.95), 95);
+        assert_eq!(percentile(&[4], 0.95), 4);
+        assert_eq!(percentile(&[4, 9], 0.0), 4);
+    }
+}
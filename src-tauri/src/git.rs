@@ -0,0 +1,205 @@
+//! Git branch awareness for time tracking.
+//!
+//! The user lists the repositories they work in (`git.json`). The desktop
+//! offers no portable way to read the working directory of the focused
+//! editor or terminal, so the active repository is the listed one whose
+//! `.git` metadata (index, HEAD, reflog) changed last: saving a staged file,
+//! committing or switching branches all touch it. While an editor plugin
+//! reports its workspace (`ide.rs`), the repository of that workspace is the
+//! active one instead, listed or not. When a task timer starts
+//! its entry is tagged with that repository's current branch, and
+//! `get_time_by_branch` sums tracked time per branch for invoicing and
+//! standups.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use git2::Repository;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::time_tracking::TimeRange;
+
+pub(crate) const GIT_STORE: &str = "git.json";
+const REPOS_KEY: &str = "repos";
+/// Files under the git dir that change with day-to-day work
+const ACTIVITY_FILES: [&str; 3] = ["index", "HEAD", "logs/HEAD"];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveBranch {
+    pub repo: String,
+    /// Short name, or `detached@<sha>` on a detached HEAD
+    pub branch: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchTotal {
+    pub repo: String,
+    pub branch: String,
+    pub entries: i64,
+    /// Clipped to the range
+    pub duration_ms: i64,
+}
+
+fn repos(app: &AppHandle) -> Vec<PathBuf> {
+    app.store(GIT_STORE)
+        .ok()
+        .and_then(|store| store.get(REPOS_KEY))
+        .and_then(|value| serde_json::from_value::<Vec<PathBuf>>(value).ok())
+        .unwrap_or_default()
+}
+
+/// Newest modification time of the repository's activity files
+fn last_activity(repo: &Repository) -> Option<SystemTime> {
+    ACTIVITY_FILES
+        .iter()
+        .filter_map(|file| std::fs::metadata(repo.path().join(file)).ok())
+        .filter_map(|meta| meta.modified().ok())
+        .max()
+}
+
+fn branch_name(repo: &Repository) -> Result<String, git2::Error> {
+    let head = repo.head()?;
+    if head.is_branch() {
+        if let Some(name) = head.shorthand() {
+            return Ok(name.to_string());
+        }
+    }
+    let commit = head.peel_to_commit()?;
+    let sha = commit.id().to_string();
+    Ok(format!("detached@{}", &sha[..7]))
+}
+
+fn open(path: &Path) -> Result<Repository, String> {
+    Repository::discover(path)
+        .map_err(|e| format!("{} is not a git repository: {}", path.display(), e))
+}
+
+/// Branch of the workspace an editor reports, or of the most recently
+/// active listed repository
+pub(crate) fn active_branch(app: &AppHandle) -> Option<ActiveBranch> {
+    let reported = crate::ide::recent_workspace(app).and_then(|workspace| {
+        let repo = Repository::discover(&workspace).ok()?;
+        let root = repo.workdir()?.to_string_lossy().into_owned();
+        Some(ActiveBranch {
+            repo: root,
+            branch: branch_name(&repo).ok()?,
+        })
+    });
+    if reported.is_some() {
+        return reported;
+    }
+    let (path, repo) = repos(app)
+        .into_iter()
+        .filter_map(|path| {
+            let repo = Repository::open(&path)
+                .map_err(|e| log::debug!("Skipping {}: {}", path.display(), e))
+                .ok()?;
+            Some((last_activity(&repo)?, path, repo))
+        })
+        .max_by_key(|(modified, ..)| *modified)
+        .map(|(_, path, repo)| (path, repo))?;
+    match branch_name(&repo) {
+        Ok(branch) => Some(ActiveBranch {
+            repo: path.to_string_lossy().into_owned(),
+            branch,
+        }),
+        Err(e) => {
+            // e.g. a fresh repository without commits
+            log::debug!("No branch for {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Tag a time entry with a branch
+pub(crate) fn tag_entry(
+    conn: &Connection,
+    entry_id: i64,
+    active: &ActiveBranch,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO time_entry_branches (entry_id, repo, branch) VALUES (?1, ?2, ?3)",
+        params![entry_id, active.repo, active.branch],
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_git_repos(app: AppHandle) -> Vec<PathBuf> {
+    repos(&app)
+}
+
+/// Replace the list of repositories to watch; each must open with git
+#[tauri::command]
+pub fn set_git_repos(app: AppHandle, repos: Vec<String>) -> Result<Vec<PathBuf>, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_git_repos")?;
+    crate::app_lock::ensure_unlocked(&app, "set_git_repos")?;
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for repo in repos {
+        let repo = open(Path::new(repo.trim()))?;
+        // Normalize a path inside the worktree to its root
+        let root = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+        if !paths.contains(&root) {
+            paths.push(root);
+        }
+    }
+
+    let store = app
+        .store(GIT_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", GIT_STORE, e))?;
+    store.set(
+        REPOS_KEY,
+        serde_json::to_value(&paths).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", GIT_STORE, e))?;
+    Ok(paths)
+}
+
+#[tauri::command]
+pub fn get_active_branch(app: AppHandle) -> Option<ActiveBranch> {
+    active_branch(&app)
+}
+
+/// Tracked time per repository and branch in a range, most first; entries
+/// started outside any listed repository are left out
+#[tauri::command]
+pub fn get_time_by_branch(
+    app: AppHandle,
+    range: Option<TimeRange>,
+) -> Result<Vec<BranchTotal>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_time_by_branch")?;
+    let range = range.unwrap_or_default();
+    Ok(crate::time_tracking::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT b.repo, b.branch, count(*), \
+                    sum(max(min(coalesce(e.ended_at, ?4), coalesce(?2, ?4)) \
+                            - max(e.started_at, coalesce(?1, e.started_at)), 0)) AS total \
+             FROM time_entries e JOIN time_entry_branches b ON b.entry_id = e.id \
+             WHERE (?1 IS NULL OR coalesce(e.ended_at, ?4) > ?1) \
+               AND (?2 IS NULL OR e.started_at < ?2) \
+               AND (?3 IS NULL OR e.task_id = ?3) \
+             GROUP BY b.repo, b.branch ORDER BY total DESC",
+        )?;
+        let rows = stmt.query_map(
+            params![range.from_ms, range.to_ms, range.task_id, now_ms() as i64],
+            |row| {
+                Ok(BranchTotal {
+                    repo: row.get(0)?,
+                    branch: row.get(1)?,
+                    entries: row.get(2)?,
+                    duration_ms: row.get(3)?,
+                })
+            },
+        )?;
+        rows.collect()
+    })?)
+}
@@ -0,0 +1,478 @@
+//! GraphQL endpoint on the local API (`POST /graphql`, `local_api.rs`).
+//!
+//! The schema covers the task cache. `tasks` and `task` read the cached rows
+//! with local edits applied; `saveTask` and `deleteTask` write the way the
+//! app's own edits do (queued for sync, refused in read-only mode); the
+//! `tasksChanged` subscription streams the IDs of changed tasks from the
+//! event bus, like the gRPC `WatchTasks`. Requests carry the local API token
+//! like every other request; the server checks it before routing here.
+//!
+//! Queries and mutations answer with JSON. Subscriptions use GraphQL over
+//! server-sent events: a request with `Accept: text/event-stream` gets a
+//! `next` event per result and `complete` at the end, on a connection that
+//! stays open until the client closes it or the local API is turned off.
+//! Errors carry the app's error code (as in command errors) in
+//! `extensions.code`.
+
+use std::sync::OnceLock;
+
+use async_graphql::types::Json;
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema, SimpleObject,
+    Subscription, ID,
+};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::error::FlowStateError;
+use crate::events::BusEvent;
+use crate::local_api::{Request, Response};
+use crate::search::TasksChanged;
+
+const STATUSES: &[&str] = &["planned", "in_progress", "done", "backlog", "on_hold"];
+pub(crate) const PRIORITIES: &[&str] = &["high", "medium", "low"];
+/// Deepest selection a query may nest
+const MAX_DEPTH: usize = 8;
+
+type FlowStateSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+fn schema() -> &'static FlowStateSchema {
+    static SCHEMA: OnceLock<FlowStateSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+            .limit_depth(MAX_DEPTH)
+            .finish()
+    })
+}
+
+fn error(error: FlowStateError) -> async_graphql::Error {
+    let code = error.code();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, e| e.set("code", code))
+}
+
+fn app<'a>(ctx: &Context<'a>) -> &'a AppHandle {
+    ctx.data_unchecked::<AppHandle>()
+}
+
+/// A cached task row
+struct Task(Map<String, Value>);
+
+impl Task {
+    fn text(&self, key: &str) -> Option<String> {
+        self.0.get(key).and_then(Value::as_str).map(str::to_string)
+    }
+}
+
+#[Object]
+impl Task {
+    async fn id(&self) -> ID {
+        ID(self.text("id").unwrap_or_default())
+    }
+
+    async fn title(&self) -> Option<String> {
+        self.text("title")
+    }
+
+    async fn description(&self) -> Option<String> {
+        self.text("description")
+    }
+
+    /// planned, in_progress, done, backlog or on_hold
+    async fn status(&self) -> Option<String> {
+        self.text("status")
+    }
+
+    /// high, medium or low
+    async fn priority(&self) -> Option<String> {
+        self.text("priority")
+    }
+
+    /// Percent done
+    async fn progress(&self) -> Option<i64> {
+        self.0.get("progress").and_then(Value::as_i64)
+    }
+
+    async fn due_date(&self) -> Option<String> {
+        self.text("due_date")
+    }
+
+    async fn completed_at(&self) -> Option<String> {
+        self.text("completed_at")
+    }
+
+    async fn tags(&self) -> Vec<String> {
+        self.0
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    async fn updated_at(&self) -> Option<String> {
+        self.text("updated_at")
+    }
+
+    /// The whole row, for fields not listed here
+    async fn data(&self) -> Json<Map<String, Value>> {
+        Json(self.0.clone())
+    }
+}
+
+fn task(value: Value) -> Option<Task> {
+    match value {
+        Value::Object(row) => Some(Task(row)),
+        _ => None,
+    }
+}
+
+/// Fields to set on a task. Left-out fields keep their value; `null` clears
+/// the ones that can be empty.
+#[derive(InputObject, Default)]
+struct TaskInput {
+    /// Leave out for a new task
+    id: Option<ID>,
+    title: Option<String>,
+    description: MaybeUndefined<String>,
+    status: Option<String>,
+    priority: MaybeUndefined<String>,
+    progress: Option<i32>,
+    /// RFC 3339 time or YYYY-MM-DD date
+    due_date: MaybeUndefined<String>,
+    tags: Option<Vec<String>>,
+}
+
+fn set_maybe(fields: &mut Map<String, Value>, key: &str, value: MaybeUndefined<String>) {
+    match value {
+        MaybeUndefined::Undefined => {}
+        MaybeUndefined::Null => {
+            fields.insert(key.to_string(), Value::Null);
+        }
+        MaybeUndefined::Value(value) => {
+            fields.insert(key.to_string(), Value::String(value));
+        }
+    }
+}
+
+/// A due date is an RFC 3339 time or a YYYY-MM-DD date
+pub(crate) fn check_due_date(due: &str) -> Result<(), String> {
+    let valid = chrono::DateTime::parse_from_rfc3339(due).is_ok()
+        || chrono::NaiveDate::parse_from_str(due, "%Y-%m-%d").is_ok();
+    if !valid {
+        return Err("Due date must be an RFC 3339 time or a YYYY-MM-DD date".to_string());
+    }
+    Ok(())
+}
+
+/// Row fields for `input`, checked; `current` is the cached row, if any
+fn task_fields(
+    input: TaskInput,
+    current: Option<&Map<String, Value>>,
+    now: &str,
+) -> Result<Map<String, Value>, String> {
+    let mut fields = Map::new();
+    if let Some(id) = input.id {
+        fields.insert("id".to_string(), Value::String(id.0));
+    }
+    match input.title {
+        Some(title) if title.trim().is_empty() => return Err("Title can't be empty".to_string()),
+        Some(title) => {
+            fields.insert("title".to_string(), Value::String(title));
+        }
+        None if current.is_none() => return Err("New tasks need a title".to_string()),
+        None => {}
+    }
+    if let Some(status) = input.status {
+        if !STATUSES.contains(&status.as_str()) {
+            return Err(format!("Status must be one of {}", STATUSES.join(", ")));
+        }
+        let completed = current.is_some_and(|row| {
+            row.get("completed_at")
+                .is_some_and(|value| !value.is_null())
+        });
+        if status == "done" && !completed {
+            fields.insert("completed_at".to_string(), Value::from(now));
+        } else if status != "done" {
+            fields.insert("completed_at".to_string(), Value::Null);
+        }
+        fields.insert("status".to_string(), Value::String(status));
+    } else if current.is_none() {
+        fields.insert("status".to_string(), Value::from("planned"));
+    }
+    if let MaybeUndefined::Value(priority) = &input.priority {
+        if !PRIORITIES.contains(&priority.as_str()) {
+            return Err(format!("Priority must be one of {}", PRIORITIES.join(", ")));
+        }
+    }
+    if let Some(progress) = input.progress {
+        if !(0..=100).contains(&progress) {
+            return Err("Progress must be 0 to 100".to_string());
+        }
+        fields.insert("progress".to_string(), Value::from(progress));
+    }
+    if let MaybeUndefined::Value(due) = &input.due_date {
+        check_due_date(due)?;
+    }
+    set_maybe(&mut fields, "description", input.description);
+    set_maybe(&mut fields, "priority", input.priority);
+    set_maybe(&mut fields, "due_date", input.due_date);
+    if let Some(tags) = input.tags {
+        fields.insert("tags".to_string(), Value::from(tags));
+    }
+    Ok(fields)
+}
+
+/// Tasks saved, pulled or deleted
+#[derive(SimpleObject)]
+struct TaskChange {
+    /// Event bus sequence number
+    seq: u64,
+    updated: Vec<ID>,
+    removed: Vec<ID>,
+    /// Changes were missed; fetch the tasks again
+    reset: bool,
+}
+
+fn task_change(event: &BusEvent) -> Option<TaskChange> {
+    if event.topic != "tasks://changed" {
+        return None;
+    }
+    let change = serde_json::from_value::<TasksChanged>(event.payload.clone()).ok()?;
+    Some(TaskChange {
+        seq: event.seq,
+        updated: change.updated.into_iter().map(ID).collect(),
+        removed: change.removed.into_iter().map(ID).collect(),
+        reset: change.reset,
+    })
+}
+
+struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Cached tasks, optionally only those with `status`
+    async fn tasks(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+    ) -> async_graphql::Result<Vec<Task>> {
+        let rows = crate::offline::cached_tasks(app(ctx)).map_err(|e| error(e.into()))?;
+        Ok(rows
+            .into_iter()
+            .filter_map(task)
+            .filter(|task| status.is_none() || task.text("status") == status)
+            .collect())
+    }
+
+    async fn task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<Task>> {
+        let row = crate::offline::cached_task(app(ctx), &id).map_err(|e| error(e.into()))?;
+        Ok(row.map(Task))
+    }
+}
+
+struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Create a task, or change the given fields of one
+    async fn save_task(&self, ctx: &Context<'_>, input: TaskInput) -> async_graphql::Result<Task> {
+        let app = app(ctx);
+        crate::read_only::ensure_writable(app, "graphql").map_err(|e| error(e.into()))?;
+        let current = match &input.id {
+            Some(id) => crate::offline::cached_task(app, id).map_err(|e| error(e.into()))?,
+            None => None,
+        };
+        let now = chrono::Utc::now().to_rfc3339();
+        let fields =
+            task_fields(input, current.as_ref(), &now).map_err(async_graphql::Error::new)?;
+        let mut row = match current {
+            Some(_) => Map::new(),
+            None => crate::offline::owned_task(app),
+        };
+        row.extend(fields);
+        let saved = crate::offline::save_task(app, row).map_err(|e| error(e.into()))?;
+        task(saved).ok_or_else(|| "Task was not saved".into())
+    }
+
+    /// Delete a task; false when there is no such task
+    async fn delete_task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
+        let app = app(ctx);
+        crate::read_only::ensure_writable(app, "graphql").map_err(|e| error(e.into()))?;
+        if crate::offline::cached_task(app, &id)
+            .map_err(|e| error(e.into()))?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        crate::offline::delete_task(app, &id).map_err(|e| error(e.into()))?;
+        Ok(true)
+    }
+}
+
+struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// IDs of tasks saved, pulled or deleted from now on
+    async fn tasks_changed(&self, ctx: &Context<'_>) -> impl Stream<Item = TaskChange> {
+        let app = app(ctx).clone();
+        let events = app.state::<crate::events::EventBus>().listen();
+        crate::events::watch(app, events, None, task_change, |_| {
+            Some(TaskChange {
+                seq: 0,
+                updated: Vec::new(),
+                removed: Vec::new(),
+                reset: true,
+            })
+        })
+    }
+}
+
+/// One server-sent event
+fn sse_event(event: &str, data: &str) -> Vec<u8> {
+    format!("event: {}\ndata: {}\n\n", event, data).into_bytes()
+}
+
+pub(crate) async fn handle(app: &AppHandle, request: &Request) -> Response {
+    if request.method != "POST" {
+        return Response::text(405, "Only POST").header("Allow", "POST");
+    }
+    let query: async_graphql::Request = match serde_json::from_slice(&request.body) {
+        Ok(query) => query,
+        Err(e) => return Response::text(400, &format!("Invalid GraphQL request: {}", e)),
+    };
+    let query = query.data(app.clone());
+
+    let events = request
+        .header("Accept")
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    if events {
+        let results = schema().execute_stream(query).map(|result| {
+            let data = serde_json::to_string(&result).unwrap_or_default();
+            sse_event("next", &data)
+        });
+        let complete = tokio_stream::once(sse_event("complete", ""));
+        return Response::new(200)
+            .header("Cache-Control", "no-cache")
+            .stream("text/event-stream", results.chain(complete));
+    }
+
+    let result = schema().execute(query).await;
+    match serde_json::to_vec(&result) {
+        Ok(body) => Response::new(200).body("application/json", body),
+        Err(e) => Response::text(500, &e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    const NOW: &str = "2026-03-01T10:00:00+00:00";
+
+    #[test]
+    fn new_tasks_need_a_title_and_start_planned() {
+        assert_eq!(
+            task_fields(TaskInput::default(), None, NOW).unwrap_err(),
+            "New tasks need a title"
+        );
+        let input = TaskInput {
+            title: Some("Write docs".to_string()),
+            ..TaskInput::default()
+        };
+        assert_eq!(
+            Value::Object(task_fields(input, None, NOW).unwrap()),
+            json!({ "title": "Write docs", "status": "planned" })
+        );
+    }
+
+    #[test]
+    fn sets_only_the_given_fields() {
+        let current = row(json!({ "id": "t1", "title": "Old", "status": "planned" }));
+        let input = TaskInput {
+            id: Some(ID("t1".to_string())),
+            status: Some("done".to_string()),
+            priority: MaybeUndefined::Null,
+            due_date: MaybeUndefined::Value("2026-03-05".to_string()),
+            ..TaskInput::default()
+        };
+        assert_eq!(
+            Value::Object(task_fields(input, Some(&current), NOW).unwrap()),
+            json!({
+                "id": "t1",
+                "status": "done",
+                "completed_at": NOW,
+                "priority": null,
+                "due_date": "2026-03-05",
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_values() {
+        let current = row(json!({ "id": "t1", "title": "Old" }));
+        let cases = [
+            TaskInput {
+                status: Some("someday".to_string()),
+                ..TaskInput::default()
+            },
+            TaskInput {
+                priority: MaybeUndefined::Value("urgent".to_string()),
+                ..TaskInput::default()
+            },
+            TaskInput {
+                progress: Some(101),
+                ..TaskInput::default()
+            },
+            TaskInput {
+                due_date: MaybeUndefined::Value("next week".to_string()),
+                ..TaskInput::default()
+            },
+            TaskInput {
+                title: Some(" ".to_string()),
+                ..TaskInput::default()
+            },
+        ];
+        for input in cases {
+            assert!(task_fields(input, Some(&current), NOW).is_err());
+        }
+    }
+
+    #[test]
+    fn streams_task_changes_from_the_bus() {
+        let event = BusEvent {
+            seq: 7,
+            topic: "tasks://changed".to_string(),
+            timestamp_ms: 0,
+            request_id: None,
+            payload: json!({ "updated": ["a"], "removed": ["b"], "reset": false }),
+        };
+        let change = task_change(&event).unwrap();
+        assert_eq!(change.seq, 7);
+        assert_eq!(change.updated, [ID("a".to_string())]);
+        assert_eq!(change.removed, [ID("b".to_string())]);
+
+        let other = BusEvent {
+            topic: "timer://started".to_string(),
+            ..event
+        };
+        assert!(task_change(&other).is_none());
+    }
+
+    #[test]
+    fn the_schema_builds() {
+        let sdl = schema().sdl();
+        assert!(sdl.contains("saveTask(input: TaskInput!): Task!"));
+        assert!(sdl.contains("tasksChanged: TaskChange!"));
+    }
+}
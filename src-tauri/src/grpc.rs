@@ -0,0 +1,361 @@
+//! gRPC service for integrations that watch the app closely (Stream Deck
+//! keys, editor status bars), from `proto/flowstate.proto`.
+//!
+//! It runs while the local API (`local_api.rs`) is enabled, on
+//! `127.0.0.1:<grpc port>`, and every call needs the local API token as
+//! `authorization: Bearer <token>` metadata. Besides reading and starting
+//! timers it streams changes: `WatchTimer` and `WatchFocus` send the current
+//! state and then each change, `WatchTasks` the IDs of changed tasks. The
+//! streams are fed from the event bus (`EventBus::listen`), so a client that
+//! used to poll every second now costs nothing until something happens.
+//! A stream that falls behind resends the current state (timer, focus) or a
+//! change with `reset` (tasks). While the app is locked calls fail with
+//! `FAILED_PRECONDITION` and streams send nothing.
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status};
+
+use crate::error::FlowStateError;
+use crate::events::BusEvent;
+use crate::focus::{FocusPhase, FocusSessionState, FocusStatus};
+use crate::search::TasksChanged;
+use crate::time_tracking::{StopReason, TimeEntry};
+
+pub mod proto {
+    tonic::include_proto!("flowstate.v1");
+}
+
+use proto::flow_state_server::{FlowState, FlowStateServer};
+
+fn time_entry(entry: TimeEntry) -> proto::TimeEntry {
+    let stop_reason = match entry.stop_reason {
+        None => proto::StopReason::Unspecified,
+        Some(StopReason::Manual) => proto::StopReason::Manual,
+        Some(StopReason::Switched) => proto::StopReason::Switched,
+        Some(StopReason::Idle) => proto::StopReason::Idle,
+        Some(StopReason::Exit) => proto::StopReason::Exit,
+        Some(StopReason::Recovered) => proto::StopReason::Recovered,
+    };
+    proto::TimeEntry {
+        id: entry.id,
+        task_id: entry.task_id,
+        started_at_ms: entry.started_at_ms,
+        ended_at_ms: entry.ended_at_ms,
+        duration_ms: entry.duration_ms,
+        stop_reason: stop_reason.into(),
+    }
+}
+
+fn focus_state(seq: u64, state: FocusSessionState) -> proto::FocusState {
+    let status = match state.status {
+        FocusStatus::Idle => proto::FocusStatus::Idle,
+        FocusStatus::Running => proto::FocusStatus::Running,
+        FocusStatus::Paused => proto::FocusStatus::Paused,
+    };
+    let phase = match state.phase {
+        FocusPhase::Focus => proto::FocusPhase::Focus,
+        FocusPhase::ShortBreak => proto::FocusPhase::ShortBreak,
+        FocusPhase::LongBreak => proto::FocusPhase::LongBreak,
+    };
+    proto::FocusState {
+        seq,
+        status: status.into(),
+        phase: phase.into(),
+        task_id: state.task_id,
+        tags: state.tags,
+        completed_focus: state.completed_focus,
+        phase_duration_ms: state.phase_duration_ms,
+        remaining_ms: state.remaining_ms,
+        phase_started_at_ms: state.phase_started_at_ms,
+        ends_at_ms: state.ends_at_ms,
+    }
+}
+
+fn status(error: FlowStateError) -> Status {
+    let code = match &error {
+        FlowStateError::ReadOnly(_) | FlowStateError::Locked(_) => tonic::Code::FailedPrecondition,
+        _ => tonic::Code::Unknown,
+    };
+    let mut status = Status::new(code, error.to_string());
+    if let Ok(value) = error.code().parse() {
+        status.metadata_mut().insert("flowstate-error-code", value);
+    }
+    status
+}
+
+fn current_timer(app: &AppHandle) -> Result<proto::TimerState, Status> {
+    let timer = crate::time_tracking::active_timer(app).map_err(Status::unknown)?;
+    Ok(proto::TimerState {
+        seq: 0,
+        timer: timer.map(time_entry),
+        stopped: None,
+    })
+}
+
+fn current_focus(app: &AppHandle) -> proto::FocusState {
+    focus_state(0, app.state::<crate::focus::FocusEngine>().snapshot())
+}
+
+fn timer_event(event: &BusEvent) -> Option<proto::TimerState> {
+    let entry = serde_json::from_value::<TimeEntry>(event.payload.clone()).ok()?;
+    let (timer, stopped) = match event.topic.as_str() {
+        "timer://started" => (Some(entry), None),
+        "timer://stopped" => (None, Some(entry)),
+        _ => return None,
+    };
+    Some(proto::TimerState {
+        seq: event.seq,
+        timer: timer.map(time_entry),
+        stopped: stopped.map(time_entry),
+    })
+}
+
+fn focus_event(event: &BusEvent) -> Option<proto::FocusState> {
+    let state = match event.topic.as_str() {
+        "focus://tick" => event.payload.clone(),
+        // The session as it goes on after the phase that ended
+        "focus://phase" => event.payload.get("next")?.clone(),
+        _ => return None,
+    };
+    let state = serde_json::from_value::<FocusSessionState>(state).ok()?;
+    Some(focus_state(event.seq, state))
+}
+
+fn tasks_event(event: &BusEvent) -> Option<proto::TasksChanged> {
+    if event.topic != "tasks://changed" {
+        return None;
+    }
+    let change = serde_json::from_value::<TasksChanged>(event.payload.clone()).ok()?;
+    Some(proto::TasksChanged {
+        seq: event.seq,
+        updated: change.updated,
+        removed: change.removed,
+        reset: change.reset,
+    })
+}
+
+struct Service {
+    app: AppHandle,
+}
+
+#[tonic::async_trait]
+impl FlowState for Service {
+    async fn get_timer(
+        &self,
+        _request: Request<proto::GetTimerRequest>,
+    ) -> Result<Response<proto::TimerState>, Status> {
+        current_timer(&self.app).map(Response::new)
+    }
+
+    async fn start_timer(
+        &self,
+        request: Request<proto::StartTimerRequest>,
+    ) -> Result<Response<proto::TimeEntry>, Status> {
+        let task_id = request.into_inner().task_id;
+        crate::time_tracking::start_task_timer(self.app.clone(), task_id)
+            .map(|entry| Response::new(time_entry(entry)))
+            .map_err(status)
+    }
+
+    async fn stop_timer(
+        &self,
+        _request: Request<proto::StopTimerRequest>,
+    ) -> Result<Response<proto::TimerState>, Status> {
+        let stopped = crate::time_tracking::stop_task_timer(self.app.clone()).map_err(status)?;
+        Ok(Response::new(proto::TimerState {
+            seq: 0,
+            timer: None,
+            stopped: stopped.map(time_entry),
+        }))
+    }
+
+    async fn get_focus(
+        &self,
+        _request: Request<proto::GetFocusRequest>,
+    ) -> Result<Response<proto::FocusState>, Status> {
+        Ok(Response::new(current_focus(&self.app)))
+    }
+
+    type WatchTimerStream = ReceiverStream<Result<proto::TimerState, Status>>;
+
+    async fn watch_timer(
+        &self,
+        _request: Request<proto::WatchRequest>,
+    ) -> Result<Response<Self::WatchTimerStream>, Status> {
+        // Listening before reading the state, so no change falls in between
+        let events = self.app.state::<crate::events::EventBus>().listen();
+        let first = current_timer(&self.app)?;
+        Ok(Response::new(crate::events::watch(
+            self.app.clone(),
+            events,
+            Some(Ok(first)),
+            |event| timer_event(event).map(Ok),
+            |app| current_timer(app).ok().map(Ok),
+        )))
+    }
+
+    type WatchFocusStream = ReceiverStream<Result<proto::FocusState, Status>>;
+
+    async fn watch_focus(
+        &self,
+        _request: Request<proto::WatchRequest>,
+    ) -> Result<Response<Self::WatchFocusStream>, Status> {
+        let events = self.app.state::<crate::events::EventBus>().listen();
+        Ok(Response::new(crate::events::watch(
+            self.app.clone(),
+            events,
+            Some(Ok(current_focus(&self.app))),
+            |event| focus_event(event).map(Ok),
+            |app| Some(Ok(current_focus(app))),
+        )))
+    }
+
+    type WatchTasksStream = ReceiverStream<Result<proto::TasksChanged, Status>>;
+
+    async fn watch_tasks(
+        &self,
+        _request: Request<proto::WatchRequest>,
+    ) -> Result<Response<Self::WatchTasksStream>, Status> {
+        let events = self.app.state::<crate::events::EventBus>().listen();
+        Ok(Response::new(crate::events::watch(
+            self.app.clone(),
+            events,
+            None,
+            |event| tasks_event(event).map(Ok),
+            |_| {
+                Some(Ok(proto::TasksChanged {
+                    reset: true,
+                    ..Default::default()
+                }))
+            },
+        )))
+    }
+}
+
+/// Token check for every call
+fn authorize(app: &AppHandle, request: Request<()>) -> Result<Request<()>, Status> {
+    let Some(token) = crate::local_api::token(app) else {
+        return Err(Status::unavailable("The local API has no token yet"));
+    };
+    let given = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, given)| given.trim());
+    if !given.is_some_and(|given| crate::local_api::token_matches(given, &token)) {
+        return Err(Status::unauthenticated("Missing or wrong token"));
+    }
+    if app.state::<crate::app_lock::AppLock>().is_locked() {
+        return Err(Status::failed_precondition("FlowState is locked"));
+    }
+    Ok(request)
+}
+
+async fn serve(app: &AppHandle) -> Result<(), String> {
+    let port = crate::local_api::grpc_port(app);
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let url = format!("http://127.0.0.1:{}", port);
+    crate::local_api::set_grpc_url(app, Some(url.clone()));
+    log::info!("gRPC service listening on {}", url);
+
+    let checked = app.clone();
+    let service = FlowStateServer::with_interceptor(Service { app: app.clone() }, move |request| {
+        authorize(&checked, request)
+    });
+    let watched = app.clone();
+    let stopped = async move {
+        while crate::local_api::enabled(&watched) && crate::local_api::grpc_port(&watched) == port {
+            tokio::time::sleep(crate::local_api::ACCEPT_POLL).await;
+        }
+    };
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), stopped)
+        .await
+        .map_err(|e| format!("gRPC service failed: {}", e))
+}
+
+/// Serve gRPC while the local API is enabled (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if crate::local_api::enabled(&app) {
+                let result = serve(&app).await;
+                crate::local_api::set_grpc_url(&app, None);
+                if let Err(e) = result {
+                    log::warn!("gRPC service unavailable: {}", e);
+                    tokio::time::sleep(crate::local_api::RETRY_DELAY).await;
+                }
+            }
+            tokio::time::sleep(crate::local_api::ACCEPT_POLL).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn event(topic: &str, payload: Value) -> BusEvent {
+        BusEvent {
+            seq: 3,
+            topic: topic.to_string(),
+            timestamp_ms: 0,
+            request_id: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn timer_events_carry_the_entry() {
+        let entry = json!({
+            "id": 9, "taskId": "t1", "startedAtMs": 1000, "endedAtMs": 61000,
+            "durationMs": 60000, "stopReason": "switched"
+        });
+        let stopped = timer_event(&event("timer://stopped", entry.clone())).unwrap();
+        assert_eq!(stopped.seq, 3);
+        assert!(stopped.timer.is_none());
+        let stopped = stopped.stopped.unwrap();
+        assert_eq!(stopped.task_id, "t1");
+        assert_eq!(stopped.ended_at_ms, Some(61000));
+        assert_eq!(stopped.stop_reason(), proto::StopReason::Switched);
+
+        let started = timer_event(&event("timer://started", entry)).unwrap();
+        assert_eq!(started.timer.unwrap().id, 9);
+        assert!(timer_event(&event("focus://tick", json!({}))).is_none());
+    }
+
+    #[test]
+    fn focus_and_task_events_convert() {
+        let state = serde_json::to_value(FocusSessionState {
+            status: FocusStatus::Running,
+            phase: FocusPhase::ShortBreak,
+            remaining_ms: 5000,
+            ..FocusSessionState::default()
+        })
+        .unwrap();
+        let next = focus_event(&event("focus://phase", json!({ "next": state }))).unwrap();
+        assert_eq!(next.status(), proto::FocusStatus::Running);
+        assert_eq!(next.phase(), proto::FocusPhase::ShortBreak);
+        assert_eq!(next.remaining_ms, 5000);
+
+        let change = tasks_event(&event(
+            "tasks://changed",
+            json!({ "updated": ["a"], "removed": ["b"], "reset": false }),
+        ))
+        .unwrap();
+        assert_eq!(
+            (change.updated, change.removed),
+            (vec!["a".to_string()], vec!["b".to_string()])
+        );
+        assert!(tasks_event(&event("timer://started", json!({}))).is_none());
+    }
+}
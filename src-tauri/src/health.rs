@@ -0,0 +1,265 @@
+//! In-process HTTP health checks for the local Supabase stack.
+//!
+//! All HTTP probing goes through `http_get`, which uses the http plugin's
+//! reqwest client with per-request timeouts and retries on connection errors,
+//! instead of shelling out to curl (not available on fresh Windows installs).
+//! On top of it, each subservice behind Kong is probed on its own, since a
+//! healthy REST gateway says nothing about realtime or storage.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_http::reqwest;
+
+use crate::endpoints::Endpoints;
+use crate::error::FlowStateError;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of a single HTTP check
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum HttpCheck {
+    /// The server answered (any status code)
+    #[serde(rename_all = "camelCase")]
+    Responded { status: u16, latency_ms: u64 },
+    /// No answer after all attempts (connection refused, timeout, DNS, ...)
+    #[serde(rename_all = "camelCase")]
+    Unreachable { error: String, attempts: u32 },
+}
+
+impl HttpCheck {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            HttpCheck::Responded { status, .. } => Some(*status),
+            HttpCheck::Unreachable { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CheckOptions {
+    pub timeout: Duration,
+    /// Extra attempts after the first one, only on transport errors
+    pub retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        CheckOptions {
+            timeout: PROBE_TIMEOUT,
+            retries: 1,
+            retry_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+pub(crate) fn client() -> Result<&'static reqwest::Client, String> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = reqwest::Client::builder()
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// GET a URL (optionally with a Supabase apikey header), retrying transport errors
+pub async fn http_get(url: &str, apikey: Option<&str>, options: CheckOptions) -> HttpCheck {
+    let client = match client() {
+        Ok(c) => c,
+        Err(error) => return HttpCheck::Unreachable { error, attempts: 0 },
+    };
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let started = Instant::now();
+
+        let mut request = client.get(url).timeout(options.timeout);
+        if let Some(key) = apikey {
+            request = request.header("apikey", key);
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                return HttpCheck::Responded {
+                    status: response.status().as_u16(),
+                    latency_ms: started.elapsed().as_millis() as u64,
+                }
+            }
+            Err(e) if attempts > options.retries => {
+                return HttpCheck::Unreachable {
+                    error: e.to_string(),
+                    attempts,
+                }
+            }
+            Err(e) => {
+                log::info!("GET {} failed (attempt {}): {}", url, attempts, e);
+                tokio::time::sleep(options.retry_delay).await;
+            }
+        }
+    }
+}
+
+/// Is the REST gateway answering?
+pub async fn check_rest_api(endpoints: &Endpoints) -> HttpCheck {
+    let url = format!("{}/rest/v1/", endpoints.api_base_url());
+    http_get(&url, None, CheckOptions::default()).await
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub up: bool,
+    pub http_status: Option<u16>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupabaseServicesHealth {
+    pub rest: ProbeResult,
+    pub auth: ProbeResult,
+    pub realtime: ProbeResult,
+    pub storage: ProbeResult,
+    pub all_up: bool,
+}
+
+/// GET a URL with the anon key and treat the expected status codes as healthy
+async fn probe_http(url: String, anon_key: String, healthy: &'static [u16]) -> ProbeResult {
+    match http_get(&url, Some(&anon_key), CheckOptions::default()).await {
+        HttpCheck::Responded { status, latency_ms } => ProbeResult {
+            up: healthy.contains(&status),
+            http_status: Some(status),
+            latency_ms,
+            error: None,
+        },
+        HttpCheck::Unreachable { error, .. } => ProbeResult {
+            up: false,
+            http_status: None,
+            latency_ms: 0,
+            error: Some(error),
+        },
+    }
+}
+
+/// Perform a raw websocket upgrade handshake against the realtime endpoint.
+/// Only the status line is inspected: 101 means realtime accepted the socket.
+fn probe_realtime_handshake(host: &str, port: u16, anon_key: &str) -> ProbeResult {
+    let started = Instant::now();
+
+    let handshake = || -> Result<u16, String> {
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", host))?;
+        let mut stream =
+            TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(PROBE_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let request = format!(
+            "GET /realtime/v1/websocket?apikey={}&vsn=1.0.0 HTTP/1.1\r\n\
+             Host: {}:{}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: ZmxvdXN0YXRlLXByb2JlIQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            anon_key, host, port
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| e.to_string())?;
+
+        let mut buf = [0u8; 256];
+        let n = stream.read(&mut buf).map_err(|e| e.to_string())?;
+        let response = String::from_utf8_lossy(&buf[..n]);
+
+        // "HTTP/1.1 101 Switching Protocols"
+        response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| "Malformed handshake response".to_string())
+    };
+
+    match handshake() {
+        Ok(status) => ProbeResult {
+            up: status == 101,
+            http_status: Some(status),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error: None,
+        },
+        Err(e) => ProbeResult {
+            up: false,
+            http_status: None,
+            latency_ms: started.elapsed().as_millis() as u64,
+            error: Some(e),
+        },
+    }
+}
+
+/// Probe REST, auth, realtime and storage separately
+#[tauri::command]
+pub async fn check_supabase_services(
+    app: AppHandle,
+) -> Result<SupabaseServicesHealth, FlowStateError> {
+    let endpoints = crate::endpoints::get(&app).await;
+    let base = endpoints.api_base_url();
+    let host = crate::docker_context::api_host();
+    let key = endpoints.anon_key;
+
+    let rest = tauri::async_runtime::spawn(probe_http(
+        format!("{}/rest/v1/", base),
+        key.clone(),
+        &[200],
+    ));
+    let auth = tauri::async_runtime::spawn(probe_http(
+        format!("{}/auth/v1/health", base),
+        key.clone(),
+        &[200],
+    ));
+    let storage = tauri::async_runtime::spawn(probe_http(
+        format!("{}/storage/v1/status", base),
+        key.clone(),
+        &[200],
+    ));
+    let realtime = tauri::async_runtime::spawn_blocking(move || {
+        probe_realtime_handshake(&host, endpoints.api_port, &key)
+    });
+
+    let join_err = |e: tauri::Error| format!("Health probe task failed: {}", e);
+    let rest = rest.await.map_err(join_err)?;
+    let auth = auth.await.map_err(join_err)?;
+    let storage = storage.await.map_err(join_err)?;
+    let realtime = realtime.await.map_err(join_err)?;
+
+    let all_up = rest.up && auth.up && realtime.up && storage.up;
+    if !all_up {
+        log::warn!(
+            "Supabase subservices degraded: rest={} auth={} realtime={} storage={}",
+            rest.up,
+            auth.up,
+            realtime.up,
+            storage.up
+        );
+    }
+
+    Ok(SupabaseServicesHealth {
+        rest,
+        auth,
+        realtime,
+        storage,
+        all_up,
+    })
+}
@@ -0,0 +1,315 @@
+//! Productivity heatmap from the local database.
+//!
+//! Focus time (completed, non-break `pomodoro_history` sessions) and task
+//! completions are aggregated in SQL per local day and hour, so the frontend
+//! gets at most 168 cells instead of raw sessions. The per-day rows are cached;
+//! later calls only re-query from the last synced day onwards, and restoring a
+//! backup or seeding drops the cache. Only the sync user's rows are counted,
+//! and the cache starts over when that user changes. Local time uses the
+//! current UTC offset, so hours around a DST change may shift by one.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapRange {
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl HeatmapRange {
+    fn days(self) -> i64 {
+        match self {
+            HeatmapRange::Week => 7,
+            HeatmapRange::Month => 30,
+            HeatmapRange::Quarter => 90,
+            HeatmapRange::Year => 365,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatmapBucket {
+    /// 7 x 24 cells
+    HourOfWeek,
+    /// 7 cells
+    DayOfWeek,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Sums {
+    focus_seconds: i64,
+    sessions: i64,
+    tasks_completed: i64,
+}
+
+impl Sums {
+    fn add(&mut self, other: &Sums) {
+        self.focus_seconds += other.focus_seconds;
+        self.sessions += other.sessions;
+        self.tasks_completed += other.tasks_completed;
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapCell {
+    /// 0 = Monday
+    pub weekday: u8,
+    /// None for day-of-week buckets
+    pub hour: Option<u8>,
+    pub focus_seconds: i64,
+    pub sessions: i64,
+    pub tasks_completed: i64,
+    /// focus_seconds relative to the busiest cell, 0.0-1.0
+    pub intensity: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    pub range: HeatmapRange,
+    pub bucket: HeatmapBucket,
+    /// First and last local day covered, "YYYY-MM-DD"
+    pub from: String,
+    pub to: String,
+    pub total_focus_seconds: i64,
+    pub cells: Vec<HeatmapCell>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    /// Local day -> per-hour sums
+    days: BTreeMap<NaiveDate, [Sums; 24]>,
+    /// Oldest day fetched so far
+    loaded_from: Option<NaiveDate>,
+    /// Day of the last query; re-fetched next time since it may have grown
+    synced_day: Option<NaiveDate>,
+    /// User the cached rows belong to
+    user: Option<String>,
+}
+
+#[derive(Default)]
+pub struct HeatmapCache {
+    inner: Mutex<CacheInner>,
+}
+
+/// Drop cached aggregates (after the database content was replaced)
+pub fn invalidate(app: &AppHandle) {
+    *app.state::<HeatmapCache>()
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = CacheInner::default();
+}
+
+fn slot<'a>(
+    days: &'a mut BTreeMap<NaiveDate, [Sums; 24]>,
+    day: &str,
+    hour: i32,
+) -> Option<&'a mut Sums> {
+    let day = NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?;
+    let hour = usize::try_from(hour).ok().filter(|h| *h < 24)?;
+    Some(&mut days.entry(day).or_insert([Sums::default(); 24])[hour])
+}
+
+/// Per-day, per-hour sums for local days >= `from`
+async fn fetch(
+    app: &AppHandle,
+    from: NaiveDate,
+    offset_secs: f64,
+    user: &str,
+) -> Result<BTreeMap<NaiveDate, [Sums; 24]>, String> {
+    let client = crate::db::connect(app).await?;
+    let from_text = from.to_string();
+
+    let focus = client
+        .query(
+            "SELECT (started_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::date::text, \
+                    extract(hour FROM started_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::int, \
+                    sum(duration)::bigint, count(*)::bigint \
+             FROM public.pomodoro_history \
+             WHERE user_id::text = $3 AND NOT coalesce(is_break, false) \
+               AND (started_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::date >= $1::text::date \
+             GROUP BY 1, 2",
+            &[&from_text, &offset_secs, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to aggregate focus sessions: {}", e))?;
+
+    let completions = client
+        .query(
+            "SELECT (completed_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::date::text, \
+                    extract(hour FROM completed_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::int, \
+                    count(*)::bigint \
+             FROM public.tasks \
+             WHERE user_id::text = $3 \
+               AND completed_at IS NOT NULL AND NOT coalesce(is_deleted, false) \
+               AND (completed_at AT TIME ZONE 'UTC' + make_interval(secs => $2))::date >= $1::text::date \
+             GROUP BY 1, 2",
+            &[&from_text, &offset_secs, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to aggregate task completions: {}", e))?;
+
+    let mut days = BTreeMap::new();
+    for row in focus {
+        if let Some(sums) = slot(&mut days, row.get(0), row.get(1)) {
+            sums.focus_seconds += row.get::<_, i64>(2);
+            sums.sessions += row.get::<_, i64>(3);
+        }
+    }
+    for row in completions {
+        if let Some(sums) = slot(&mut days, row.get(0), row.get(1)) {
+            sums.tasks_completed += row.get::<_, i64>(2);
+        }
+    }
+    Ok(days)
+}
+
+/// Bring the cache up to date for days >= `from`
+async fn sync(app: &AppHandle, from: NaiveDate, today: NaiveDate) -> Result<(), String> {
+    let user = crate::offline::current_user(app)
+        .ok_or_else(|| "Not signed in; the heatmap needs a sync user".to_string())?;
+    let fetch_from = {
+        let cache = app.state::<HeatmapCache>();
+        let mut inner = cache.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.user.as_deref() != Some(user.as_str()) {
+            *inner = CacheInner {
+                user: Some(user.clone()),
+                ..CacheInner::default()
+            };
+        }
+        match (inner.loaded_from, inner.synced_day) {
+            (Some(loaded), Some(synced)) if loaded <= from => synced,
+            _ => from,
+        }
+    };
+
+    let offset_secs = crate::clock::now_local().offset().local_minus_utc() as f64;
+    let fresh = fetch(app, fetch_from, offset_secs, &user).await?;
+
+    let cache = app.state::<HeatmapCache>();
+    let mut inner = cache.inner.lock().unwrap_or_else(|e| e.into_inner());
+    if inner.user.as_deref() != Some(user.as_str()) {
+        return Err("Sync user changed while loading the heatmap".to_string());
+    }
+    // Days from fetch_from on were re-queried; replace them wholesale
+    inner.days.retain(|day, _| *day < fetch_from);
+    inner.days.extend(fresh);
+    inner.loaded_from = Some(inner.loaded_from.map_or(fetch_from, |l| l.min(fetch_from)));
+    inner.synced_day = Some(today);
+    Ok(())
+}
+
+/// Bring an already loaded cache up to date in the background
+pub async fn prewarm(app: &AppHandle) {
+    let loaded_from = app
+        .state::<HeatmapCache>()
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .loaded_from;
+    let Some(from) = loaded_from else {
+        return;
+    };
+    if let Err(e) = sync(app, from, crate::clock::now_local().date_naive()).await {
+        log::debug!("Heatmap refresh skipped: {}", e);
+    }
+}
+
+fn build(
+    app: &AppHandle,
+    range: HeatmapRange,
+    bucket: HeatmapBucket,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Heatmap {
+    let mut grid = [[Sums::default(); 24]; 7];
+    {
+        let cache = app.state::<HeatmapCache>();
+        let inner = cache.inner.lock().unwrap_or_else(|e| e.into_inner());
+        for (day, hours) in inner.days.range(from..=to) {
+            let weekday = day.weekday().num_days_from_monday() as usize;
+            for (hour, sums) in hours.iter().enumerate() {
+                grid[weekday][hour].add(sums);
+            }
+        }
+    }
+
+    let mut cells: Vec<HeatmapCell> = Vec::new();
+    for (weekday, hours) in grid.iter().enumerate() {
+        match bucket {
+            HeatmapBucket::HourOfWeek => {
+                cells.extend(hours.iter().enumerate().map(|(hour, sums)| HeatmapCell {
+                    weekday: weekday as u8,
+                    hour: Some(hour as u8),
+                    focus_seconds: sums.focus_seconds,
+                    sessions: sums.sessions,
+                    tasks_completed: sums.tasks_completed,
+                    intensity: 0.0,
+                }));
+            }
+            HeatmapBucket::DayOfWeek => {
+                let mut day = Sums::default();
+                hours.iter().for_each(|sums| day.add(sums));
+                cells.push(HeatmapCell {
+                    weekday: weekday as u8,
+                    hour: None,
+                    focus_seconds: day.focus_seconds,
+                    sessions: day.sessions,
+                    tasks_completed: day.tasks_completed,
+                    intensity: 0.0,
+                });
+            }
+        }
+    }
+
+    let max = cells.iter().map(|c| c.focus_seconds).max().unwrap_or(0);
+    if max > 0 {
+        for cell in &mut cells {
+            cell.intensity = cell.focus_seconds as f32 / max as f32;
+        }
+    }
+
+    Heatmap {
+        range,
+        bucket,
+        from: from.to_string(),
+        to: to.to_string(),
+        total_focus_seconds: cells.iter().map(|c| c.focus_seconds).sum(),
+        cells,
+    }
+}
+
+/// Focus intensity per hour-of-week (or day-of-week) over the last week, month,
+/// quarter or year, from the local database. `refresh` re-queries everything.
+#[tauri::command]
+pub async fn get_productivity_heatmap(
+    app: AppHandle,
+    range: HeatmapRange,
+    bucket: HeatmapBucket,
+    refresh: Option<bool>,
+) -> Result<Heatmap, FlowStateError> {
+    crate::trace::scope("get_productivity_heatmap", async move {
+        crate::app_lock::ensure_unlocked(&app, "get_productivity_heatmap")?;
+        if refresh.unwrap_or(false) {
+            invalidate(&app);
+        }
+
+        let today = crate::clock::now_local().date_naive();
+        let from = today - Duration::days(range.days() - 1);
+        sync(&app, from, today).await?;
+        Ok(build(&app, range, bucket, from, today))
+    })
+    .await
+}
@@ -0,0 +1,753 @@
+//! Public holidays and vacations: the days the user is out of the office.
+//!
+//! Holidays come from a small built-in dataset of national holidays per
+//! country (`COUNTRIES`): fixed dates, dates relative to Easter and "nth
+//! weekday of the month" rules, with the country's weekend substitution.
+//! Regional holidays are not included. Vacations are date ranges the user
+//! enters. Both are kept in `holidays.json`.
+//!
+//! Days off don't break streaks (`count_missed_days`), are skipped by
+//! recurring tasks when `skip_recurring` is set (`get_recurrence_skips`), and
+//! are marked in the weekly report (`anomalies.rs`). "Today" is the app's
+//! clock (`clock.rs`).
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+pub(crate) const HOLIDAY_STORE: &str = "holidays.json";
+const SETTINGS_KEY: &str = "settings";
+const MAX_VACATIONS: usize = 200;
+const MAX_VACATION_DAYS: u64 = 366;
+const MAX_LABEL_CHARS: usize = 100;
+/// Longest range a single query may cover
+const MAX_RANGE_DAYS: u64 = 3 * 366;
+
+#[derive(Clone, Copy)]
+enum Rule {
+    /// Month and day
+    Fixed(u32, u32),
+    /// Days after Easter Sunday (negative for before)
+    Easter(i64),
+    /// nth weekday of the month; -1 is the last one
+    Nth(u32, Weekday, i8),
+    /// Last `Weekday` on or before month/day
+    OnOrBefore(u32, u32, Weekday),
+}
+
+/// Where a holiday that falls on a weekend is observed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shift {
+    /// Not moved
+    None,
+    /// The next weekday that isn't already a holiday
+    NextWeekday,
+    /// Saturday to Friday, Sunday to Monday
+    NearestWeekday,
+}
+
+struct Holiday {
+    name: &'static str,
+    rule: Rule,
+    /// Whether a weekend date moves to a weekday (by the country's `shift`)
+    substitute: bool,
+}
+
+struct Country {
+    code: &'static str,
+    name: &'static str,
+    shift: Shift,
+    holidays: &'static [Holiday],
+}
+
+const fn holiday(name: &'static str, rule: Rule) -> Holiday {
+    Holiday {
+        name,
+        rule,
+        substitute: true,
+    }
+}
+
+const fn unmoved(name: &'static str, rule: Rule) -> Holiday {
+    Holiday {
+        name,
+        rule,
+        substitute: false,
+    }
+}
+
+use Rule::{Easter, Fixed, Nth, OnOrBefore};
+use Weekday::{Mon, Thu};
+
+const COUNTRIES: &[Country] = &[
+    Country {
+        code: "AU",
+        name: "Australia",
+        shift: Shift::NextWeekday,
+        holidays: &[
+            holiday("New Year's Day", Fixed(1, 1)),
+            holiday("Australia Day", Fixed(1, 26)),
+            unmoved("Good Friday", Easter(-2)),
+            unmoved("Easter Monday", Easter(1)),
+            unmoved("Anzac Day", Fixed(4, 25)),
+            holiday("Christmas Day", Fixed(12, 25)),
+            holiday("Boxing Day", Fixed(12, 26)),
+        ],
+    },
+    Country {
+        code: "CA",
+        name: "Canada",
+        shift: Shift::NextWeekday,
+        holidays: &[
+            holiday("New Year's Day", Fixed(1, 1)),
+            unmoved("Good Friday", Easter(-2)),
+            unmoved("Victoria Day", OnOrBefore(5, 24, Mon)),
+            holiday("Canada Day", Fixed(7, 1)),
+            unmoved("Labour Day", Nth(9, Mon, 1)),
+            holiday("National Day for Truth and Reconciliation", Fixed(9, 30)),
+            unmoved("Thanksgiving", Nth(10, Mon, 2)),
+            holiday("Remembrance Day", Fixed(11, 11)),
+            holiday("Christmas Day", Fixed(12, 25)),
+            holiday("Boxing Day", Fixed(12, 26)),
+        ],
+    },
+    Country {
+        code: "DE",
+        name: "Germany",
+        shift: Shift::None,
+        holidays: &[
+            holiday("Neujahr", Fixed(1, 1)),
+            holiday("Karfreitag", Easter(-2)),
+            holiday("Ostermontag", Easter(1)),
+            holiday("Tag der Arbeit", Fixed(5, 1)),
+            holiday("Christi Himmelfahrt", Easter(39)),
+            holiday("Pfingstmontag", Easter(50)),
+            holiday("Tag der Deutschen Einheit", Fixed(10, 3)),
+            holiday("1. Weihnachtstag", Fixed(12, 25)),
+            holiday("2. Weihnachtstag", Fixed(12, 26)),
+        ],
+    },
+    Country {
+        code: "ES",
+        name: "Spain",
+        shift: Shift::None,
+        holidays: &[
+            holiday("Año Nuevo", Fixed(1, 1)),
+            holiday("Epifanía del Señor", Fixed(1, 6)),
+            holiday("Viernes Santo", Easter(-2)),
+            holiday("Fiesta del Trabajo", Fixed(5, 1)),
+            holiday("Asunción de la Virgen", Fixed(8, 15)),
+            holiday("Fiesta Nacional de España", Fixed(10, 12)),
+            holiday("Todos los Santos", Fixed(11, 1)),
+            holiday("Día de la Constitución", Fixed(12, 6)),
+            holiday("Inmaculada Concepción", Fixed(12, 8)),
+            holiday("Navidad", Fixed(12, 25)),
+        ],
+    },
+    Country {
+        code: "FR",
+        name: "France",
+        shift: Shift::None,
+        holidays: &[
+            holiday("Jour de l'an", Fixed(1, 1)),
+            holiday("Lundi de Pâques", Easter(1)),
+            holiday("Fête du Travail", Fixed(5, 1)),
+            holiday("Victoire 1945", Fixed(5, 8)),
+            holiday("Ascension", Easter(39)),
+            holiday("Lundi de Pentecôte", Easter(50)),
+            holiday("Fête nationale", Fixed(7, 14)),
+            holiday("Assomption", Fixed(8, 15)),
+            holiday("Toussaint", Fixed(11, 1)),
+            holiday("Armistice 1918", Fixed(11, 11)),
+            holiday("Noël", Fixed(12, 25)),
+        ],
+    },
+    Country {
+        code: "GB",
+        name: "United Kingdom (England and Wales)",
+        shift: Shift::NextWeekday,
+        holidays: &[
+            holiday("New Year's Day", Fixed(1, 1)),
+            unmoved("Good Friday", Easter(-2)),
+            unmoved("Easter Monday", Easter(1)),
+            unmoved("Early May bank holiday", Nth(5, Mon, 1)),
+            unmoved("Spring bank holiday", Nth(5, Mon, -1)),
+            unmoved("Summer bank holiday", Nth(8, Mon, -1)),
+            holiday("Christmas Day", Fixed(12, 25)),
+            holiday("Boxing Day", Fixed(12, 26)),
+        ],
+    },
+    Country {
+        code: "IT",
+        name: "Italy",
+        shift: Shift::None,
+        holidays: &[
+            holiday("Capodanno", Fixed(1, 1)),
+            holiday("Epifania", Fixed(1, 6)),
+            holiday("Lunedì dell'Angelo", Easter(1)),
+            holiday("Festa della Liberazione", Fixed(4, 25)),
+            holiday("Festa del Lavoro", Fixed(5, 1)),
+            holiday("Festa della Repubblica", Fixed(6, 2)),
+            holiday("Ferragosto", Fixed(8, 15)),
+            holiday("Ognissanti", Fixed(11, 1)),
+            holiday("Immacolata Concezione", Fixed(12, 8)),
+            holiday("Natale", Fixed(12, 25)),
+            holiday("Santo Stefano", Fixed(12, 26)),
+        ],
+    },
+    Country {
+        code: "US",
+        name: "United States (federal)",
+        shift: Shift::NearestWeekday,
+        holidays: &[
+            holiday("New Year's Day", Fixed(1, 1)),
+            unmoved("Martin Luther King Jr. Day", Nth(1, Mon, 3)),
+            unmoved("Washington's Birthday", Nth(2, Mon, 3)),
+            unmoved("Memorial Day", Nth(5, Mon, -1)),
+            holiday("Juneteenth", Fixed(6, 19)),
+            holiday("Independence Day", Fixed(7, 4)),
+            unmoved("Labor Day", Nth(9, Mon, 1)),
+            unmoved("Columbus Day", Nth(10, Mon, 2)),
+            holiday("Veterans Day", Fixed(11, 11)),
+            unmoved("Thanksgiving Day", Nth(11, Thu, 4)),
+            holiday("Christmas Day", Fixed(12, 25)),
+        ],
+    },
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Vacation {
+    /// First and last day off, "YYYY-MM-DD"
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HolidaySettings {
+    /// ISO 3166 code of the country whose public holidays apply
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub vacations: Vec<Vacation>,
+    /// Recurring tasks skip days off
+    #[serde(default)]
+    pub skip_recurring: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DayOffKind {
+    Holiday,
+    Vacation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayOff {
+    /// "YYYY-MM-DD"
+    pub date: String,
+    pub kind: DayOffKind,
+    pub name: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayCountry {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date (YYYY-MM-DD)", text))
+}
+
+/// Easter Sunday (Gregorian, anonymous algorithm)
+fn easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+fn resolve(rule: Rule, year: i32) -> Option<NaiveDate> {
+    match rule {
+        Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+        Easter(offset) => {
+            let easter = easter(year)?;
+            if offset < 0 {
+                easter.checked_sub_days(Days::new(offset.unsigned_abs()))
+            } else {
+                easter.checked_add_days(Days::new(offset as u64))
+            }
+        }
+        Nth(month, weekday, n) if n > 0 => {
+            NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8)
+        }
+        Nth(month, weekday, _) => {
+            let (next_year, next_month) = if month == 12 {
+                (year + 1, 1)
+            } else {
+                (year, month + 1)
+            };
+            let last = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt()?;
+            let back =
+                (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+            last.checked_sub_days(Days::new(u64::from(back)))
+        }
+        OnOrBefore(month, day, weekday) => {
+            let date = NaiveDate::from_ymd_opt(year, month, day)?;
+            let back =
+                (7 + date.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+            date.checked_sub_days(Days::new(u64::from(back)))
+        }
+    }
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Observed public holidays of `country` in `year`, by date. Substitute days
+/// for the turn of the year can fall into the year before.
+fn country_holidays(country: &Country, year: i32) -> BTreeMap<NaiveDate, &'static str> {
+    let mut days = BTreeMap::new();
+    let mut moved = Vec::new();
+    for holiday in country.holidays {
+        let Some(date) = resolve(holiday.rule, year) else {
+            continue;
+        };
+        // The day itself stays a holiday
+        days.entry(date).or_insert(holiday.name);
+        if holiday.substitute && country.shift != Shift::None && is_weekend(date) {
+            moved.push((date, holiday.name));
+        }
+    }
+    for (date, name) in moved {
+        let observed = match (country.shift, date.weekday()) {
+            (Shift::NearestWeekday, Weekday::Sat) => date.pred_opt(),
+            (Shift::NearestWeekday, _) => date.succ_opt(),
+            _ => {
+                let mut next = date.succ_opt();
+                while let Some(day) = next.filter(|d| is_weekend(*d) || days.contains_key(d)) {
+                    next = day.succ_opt();
+                }
+                next
+            }
+        };
+        if let Some(observed) = observed {
+            days.entry(observed).or_insert(name);
+        }
+    }
+    days
+}
+
+fn country(code: &str) -> Option<&'static Country> {
+    COUNTRIES
+        .iter()
+        .find(|country| country.code.eq_ignore_ascii_case(code))
+}
+
+/// Days off in [from, to] under `settings`; a vacation wins over a holiday
+fn compute(
+    settings: &HolidaySettings,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> BTreeMap<NaiveDate, DayOff> {
+    let mut days = BTreeMap::new();
+    if let Some(country) = settings.country.as_deref().and_then(country) {
+        for year in from.year() - 1..=to.year() + 1 {
+            for (date, name) in country_holidays(country, year).range(from..=to) {
+                days.insert(
+                    *date,
+                    DayOff {
+                        date: date.to_string(),
+                        kind: DayOffKind::Holiday,
+                        name: name.to_string(),
+                    },
+                );
+            }
+        }
+    }
+    for vacation in &settings.vacations {
+        let (Ok(start), Ok(end)) = (parse_date(&vacation.start), parse_date(&vacation.end)) else {
+            continue;
+        };
+        for date in start
+            .max(from)
+            .iter_days()
+            .take_while(|date| *date <= end.min(to))
+        {
+            days.insert(
+                date,
+                DayOff {
+                    date: date.to_string(),
+                    kind: DayOffKind::Vacation,
+                    name: vacation
+                        .label
+                        .clone()
+                        .unwrap_or_else(|| "Vacation".to_string()),
+                },
+            );
+        }
+    }
+    days
+}
+
+pub(crate) fn settings(app: &AppHandle) -> HolidaySettings {
+    app.store(HOLIDAY_STORE)
+        .ok()
+        .and_then(|store| store.get(SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Days off in [from, to], by date
+pub(crate) fn days_off(
+    app: &AppHandle,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> BTreeMap<NaiveDate, DayOff> {
+    compute(&settings(app), from, to)
+}
+
+/// Days strictly between `last` and `today` that weren't days off
+fn missed_days(settings: &HolidaySettings, last: NaiveDate, today: NaiveDate) -> u32 {
+    let Some(first) = last.succ_opt() else {
+        return 0;
+    };
+    let Some(end) = today.pred_opt().filter(|end| *end >= first) else {
+        return 0;
+    };
+    let off = compute(settings, first, end);
+    first
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .filter(|date| !off.contains_key(date))
+        .count() as u32
+}
+
+fn validate(settings: &HolidaySettings) -> Result<(), String> {
+    if let Some(code) = &settings.country {
+        if country(code).is_none() {
+            return Err(format!("No holiday data for '{}'", code));
+        }
+    }
+    if settings.vacations.len() > MAX_VACATIONS {
+        return Err(format!("At most {} vacations", MAX_VACATIONS));
+    }
+    for vacation in &settings.vacations {
+        let (start, end) = (parse_date(&vacation.start)?, parse_date(&vacation.end)?);
+        if end < start {
+            return Err(format!(
+                "Vacation ends ({}) before it starts ({})",
+                vacation.end, vacation.start
+            ));
+        }
+        if (end - start).num_days() as u64 >= MAX_VACATION_DAYS {
+            return Err(format!("A vacation is at most {} days", MAX_VACATION_DAYS));
+        }
+        if vacation
+            .label
+            .as_ref()
+            .is_some_and(|label| label.chars().count() > MAX_LABEL_CHARS)
+        {
+            return Err(format!("Labels are at most {} characters", MAX_LABEL_CHARS));
+        }
+    }
+    Ok(())
+}
+
+fn range(from: &str, to: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let (from, to) = (parse_date(from)?, parse_date(to)?);
+    if to < from {
+        return Err(format!("{} is before {}", to, from));
+    }
+    if (to - from).num_days() as u64 > MAX_RANGE_DAYS {
+        return Err(format!("Ask for at most {} days at a time", MAX_RANGE_DAYS));
+    }
+    Ok((from, to))
+}
+
+#[tauri::command]
+pub fn list_holiday_countries() -> Vec<HolidayCountry> {
+    COUNTRIES
+        .iter()
+        .map(|country| HolidayCountry {
+            code: country.code,
+            name: country.name,
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_holiday_settings(app: AppHandle) -> Result<HolidaySettings, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_holiday_settings")?;
+    Ok(settings(&app))
+}
+
+#[tauri::command]
+pub fn set_holiday_settings(
+    app: AppHandle,
+    settings: HolidaySettings,
+) -> Result<HolidaySettings, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_holiday_settings")?;
+    crate::app_lock::ensure_unlocked(&app, "set_holiday_settings")?;
+    validate(&settings)?;
+    let store = app
+        .store(HOLIDAY_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", HOLIDAY_STORE, e))?;
+    store.set(
+        SETTINGS_KEY,
+        serde_json::to_value(&settings).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", HOLIDAY_STORE, e))?;
+    Ok(settings)
+}
+
+/// Holidays and vacation days from `from` to `to` ("YYYY-MM-DD", inclusive)
+#[tauri::command]
+pub fn get_days_off(
+    app: AppHandle,
+    from: String,
+    to: String,
+) -> Result<Vec<DayOff>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_days_off")?;
+    let (from, to) = range(&from, &to)?;
+    Ok(days_off(&app, from, to).into_values().collect())
+}
+
+/// Days recurring tasks skip between `from` and `to`: the days off when
+/// `skip_recurring` is set, otherwise none
+#[tauri::command]
+pub fn get_recurrence_skips(
+    app: AppHandle,
+    from: String,
+    to: String,
+) -> Result<Vec<String>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_recurrence_skips")?;
+    let (from, to) = range(&from, &to)?;
+    let settings = settings(&app);
+    if !settings.skip_recurring {
+        return Ok(Vec::new());
+    }
+    Ok(compute(&settings, from, to)
+        .into_keys()
+        .map(|date| date.to_string())
+        .collect())
+}
+
+/// Days missed since `last_active` ("YYYY-MM-DD"), not counting today,
+/// holidays or vacation days. A streak continues at 0.
+#[tauri::command]
+pub fn count_missed_days(app: AppHandle, last_active: String) -> Result<u32, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "count_missed_days")?;
+    let last = parse_date(&last_active)?;
+    Ok(missed_days_until_now(&settings(&app), last, &chrono::Local))
+}
+
+/// `missed_days` up to today on the clock of `tz`
+fn missed_days_until_now<Tz: chrono::TimeZone>(
+    settings: &HolidaySettings,
+    last: NaiveDate,
+    tz: &Tz,
+) -> u32 {
+    let today = crate::clock::now_in(tz).date_naive();
+    if (today - last).num_days() as u64 > MAX_RANGE_DAYS {
+        // Long gone; no need to look at every day
+        return u32::MAX;
+    }
+    missed_days(settings, last, today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        parse_date(text).unwrap()
+    }
+
+    fn holidays(code: &str, year: i32) -> Vec<String> {
+        country_holidays(country(code).unwrap(), year)
+            .into_keys()
+            .map(|date| date.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn finds_easter() {
+        for (year, sunday) in [
+            (2024, "2024-03-31"),
+            (2025, "2025-04-20"),
+            (2026, "2026-04-05"),
+            (2038, "2038-04-25"),
+        ] {
+            assert_eq!(easter(year), Some(date(sunday)), "{}", year);
+        }
+    }
+
+    #[test]
+    fn resolves_weekday_rules() {
+        assert_eq!(resolve(Nth(11, Thu, 4), 2026), Some(date("2026-11-26")));
+        assert_eq!(resolve(Nth(5, Mon, -1), 2026), Some(date("2026-05-25")));
+        assert_eq!(resolve(Nth(12, Mon, -1), 2026), Some(date("2026-12-28")));
+        assert_eq!(
+            resolve(OnOrBefore(5, 24, Mon), 2026),
+            Some(date("2026-05-18"))
+        );
+        // May 24th is itself a Monday
+        assert_eq!(
+            resolve(OnOrBefore(5, 24, Mon), 2027),
+            Some(date("2027-05-24"))
+        );
+    }
+
+    #[test]
+    fn moves_weekend_holidays_by_country() {
+        // Christmas 2027 is a Saturday, Boxing Day a Sunday
+        let gb = holidays("GB", 2027);
+        assert!(gb.contains(&"2027-12-27".to_string()));
+        assert!(gb.contains(&"2027-12-28".to_string()));
+        // Independence Day 2026 is a Saturday, observed on Friday
+        assert!(holidays("US", 2026).contains(&"2026-07-03".to_string()));
+        // New Year's Day 2028 is a Saturday, observed in 2027
+        assert!(holidays("US", 2028).contains(&"2027-12-31".to_string()));
+        // Anzac Day and German holidays don't move
+        assert!(!holidays("AU", 2026).contains(&"2026-04-27".to_string()));
+        assert_eq!(holidays("DE", 2026).len(), 9);
+    }
+
+    #[test]
+    fn vacations_and_holidays_are_days_off() {
+        let settings = HolidaySettings {
+            country: Some("de".to_string()),
+            vacations: vec![Vacation {
+                start: "2026-04-01".to_string(),
+                end: "2026-04-03".to_string(),
+                label: Some("Easter trip".to_string()),
+            }],
+            skip_recurring: false,
+        };
+        let off = compute(&settings, date("2026-03-30"), date("2026-04-06"));
+        let days: Vec<_> = off
+            .values()
+            .map(|day| (day.date.as_str(), day.kind, day.name.as_str()))
+            .collect();
+        assert_eq!(
+            days,
+            [
+                ("2026-04-01", DayOffKind::Vacation, "Easter trip"),
+                ("2026-04-02", DayOffKind::Vacation, "Easter trip"),
+                // Karfreitag, inside the vacation
+                ("2026-04-03", DayOffKind::Vacation, "Easter trip"),
+                ("2026-04-06", DayOffKind::Holiday, "Ostermontag"),
+            ]
+        );
+
+        // Active Tuesday and the Tuesday after: only the weekend was missed
+        assert_eq!(
+            missed_days(&settings, date("2026-03-31"), date("2026-04-07")),
+            2
+        );
+        // Without the vacation, Wednesday and Thursday count too
+        let plain = HolidaySettings {
+            vacations: Vec::new(),
+            ..settings
+        };
+        assert_eq!(
+            missed_days(&plain, date("2026-03-31"), date("2026-04-07")),
+            4
+        );
+        assert_eq!(
+            missed_days(&plain, date("2026-04-06"), date("2026-04-07")),
+            0
+        );
+    }
+
+    #[test]
+    fn rejects_bad_settings() {
+        let vacation = |start: &str, end: &str| HolidaySettings {
+            vacations: vec![Vacation {
+                start: start.to_string(),
+                end: end.to_string(),
+                label: None,
+            }],
+            ..HolidaySettings::default()
+        };
+        assert!(validate(&vacation("2026-01-01", "2026-01-10")).is_ok());
+        assert!(validate(&vacation("2026-01-10", "2026-01-01")).is_err());
+        assert!(validate(&vacation("2026-01-01", "2027-06-01")).is_err());
+        assert!(validate(&vacation("soon", "2026-01-01")).is_err());
+        let unknown = HolidaySettings {
+            country: Some("XX".to_string()),
+            ..HolidaySettings::default()
+        };
+        assert!(validate(&unknown).is_err());
+    }
+
+    #[test]
+    fn streaks_and_skips_count_local_days_across_dst() {
+        let tz = chrono_tz::America::New_York;
+        let settings = HolidaySettings {
+            country: Some("US".to_string()),
+            vacations: vec![Vacation {
+                start: "2026-03-09".to_string(),
+                end: "2026-03-10".to_string(),
+                label: None,
+            }],
+            skip_recurring: true,
+        };
+        let day = 24 * 60 * 60 * 1000;
+        // Sunday 23:30 EDT, the night the clocks moved forward; already
+        // Monday in UTC
+        crate::clock::set(
+            chrono::DateTime::parse_from_rfc3339("2026-03-08T23:30:00-04:00")
+                .unwrap()
+                .timestamp_millis(),
+        );
+        let last = date("2026-03-07");
+        assert_eq!(missed_days_until_now(&settings, last, &tz), 0);
+        crate::clock::advance(day);
+        // Sunday is missed; the vacation isn't
+        assert_eq!(missed_days_until_now(&settings, last, &tz), 1);
+        crate::clock::advance(2 * day);
+        assert_eq!(missed_days_until_now(&settings, last, &tz), 1);
+        crate::clock::reset();
+
+        // Each day off is skipped once, in spring and when the clocks go back
+        let skips = |from: &str, to: &str| -> Vec<String> {
+            compute(&settings, date(from), date(to))
+                .into_keys()
+                .map(|date| date.to_string())
+                .collect()
+        };
+        assert_eq!(
+            skips("2026-03-01", "2026-03-14"),
+            ["2026-03-09", "2026-03-10"]
+        );
+        assert_eq!(skips("2026-10-30", "2026-11-12"), ["2026-11-11"]);
+    }
+}
@@ -0,0 +1,1035 @@
+//! Local socket for editor plugins (VS Code, JetBrains, ...).
+//!
+//! Plugins speak JSON-RPC 2.0 framed like the Language Server Protocol
+//! (`Content-Length` headers) over `ide.sock` in the app data directory on
+//! macOS and Linux (owner-only), or the `\\.\pipe\flowstate-ide-<user>`
+//! named pipe on Windows (local clients only). The protocol is documented in
+//! `docs/ide-protocol.md`. Plugins report the active workspace and file with
+//! `heartbeat` and can list, suggest and time tasks; everything but
+//! `initialize` is refused while the app is locked. With `subscribe` a plugin
+//! gets timer, focus and task changes pushed as notifications instead of
+//! polling: they come from the event bus (`EventBus::listen`) and go out on
+//! the same connection, through one writer task per client.
+//!
+//! Heartbeats of one editor and workspace less than `HEARTBEAT_GAP_MS` apart
+//! extend the same coding span in `sessions.db`, like WakaTime. A
+//! workspace's project comes from the user's workspace → project mapping
+//! (`set_ide_projects`), else from the project most time was tracked on in
+//! that repository. While an editor reports a workspace, `git.rs` tags new
+//! time entries with its branch rather than guessing the active repository.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::error::FlowStateError;
+use crate::events::{now_ms, BusEvent};
+
+/// 2 added `subscribe` / `unsubscribe`
+pub const PROTOCOL_VERSION: u32 = 2;
+const IDE_STORE: &str = "ide.json";
+const ENABLED_KEY: &str = "enabled";
+const PROJECTS_KEY: &str = "projects";
+/// Longest pause between heartbeats that still counts as coding
+const HEARTBEAT_GAP_MS: i64 = 2 * 60 * 1000;
+/// How often the listener checks that it is still enabled
+const ACCEPT_POLL: Duration = Duration::from_secs(1);
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_HEADER_LINE: u64 = 1024;
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+const MAX_TASKS: i64 = 50;
+/// Messages waiting for a slow client before reading from it pauses
+const OUTGOING_CAPACITY: usize = 64;
+const TOPICS: [&str; 3] = ["focus", "tasks", "timer"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const APP_ERROR: i64 = -32000;
+const LOCKED: i64 = -32001;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceProject {
+    /// Folder the editor has open (or any folder above it)
+    pub workspace: String,
+    pub project_id: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorActivity {
+    pub editor: Option<String>,
+    pub workspace: String,
+    pub file: Option<String>,
+    pub language: Option<String>,
+    pub project_id: Option<String>,
+    pub at_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdeState {
+    pub enabled: bool,
+    /// Socket path or pipe name while listening
+    pub endpoint: Option<String>,
+    pub clients: usize,
+    /// Last heartbeat from any editor
+    pub activity: Option<EditorActivity>,
+    pub projects: Vec<WorkspaceProject>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodingTotal {
+    pub project_id: Option<String>,
+    pub workspace: String,
+    pub spans: i64,
+    /// Clipped to the range
+    pub duration_ms: i64,
+}
+
+#[derive(Default)]
+pub struct IdeServer {
+    clients: AtomicUsize,
+    endpoint: Mutex<Option<String>>,
+    activity: Mutex<Option<EditorActivity>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Heartbeat {
+    workspace: String,
+    file: Option<String>,
+    language: Option<String>,
+    editor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    jsonrpc: String,
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<FlowStateError> for RpcError {
+    fn from(e: FlowStateError) -> Self {
+        RpcError {
+            code: APP_ERROR,
+            message: e.to_string(),
+            data: serde_json::to_value(&e).ok(),
+        }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError::new(APP_ERROR, message)
+    }
+}
+
+type Topics = Arc<Mutex<BTreeSet<&'static str>>>;
+
+/// What an editor told us about itself in `initialize`, and what it
+/// subscribed to
+#[derive(Default)]
+struct Client {
+    name: Option<String>,
+    topics: Topics,
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.store(IDE_STORE)
+        .ok()
+        .and_then(|store| store.get(ENABLED_KEY))
+        .and_then(|value| value.as_bool())
+        .unwrap_or(true)
+}
+
+fn projects(app: &AppHandle) -> Vec<WorkspaceProject> {
+    app.store(IDE_STORE)
+        .ok()
+        .and_then(|store| store.get(PROJECTS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Project of the deepest mapped folder containing `workspace`
+fn mapped_project(projects: &[WorkspaceProject], workspace: &str) -> Option<String> {
+    projects
+        .iter()
+        .filter(|p| Path::new(workspace).starts_with(&p.workspace))
+        .max_by_key(|p| Path::new(&p.workspace).components().count())
+        .map(|p| p.project_id.clone())
+}
+
+/// Project most time was tracked on in the repository containing `workspace`
+fn tracked_project(app: &AppHandle, workspace: &str) -> Result<Option<String>, String> {
+    let totals = crate::time_tracking::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT r.repo, b.project_id, sum(coalesce(e.ended_at, e.last_seen_at) - e.started_at) \
+             FROM time_entry_branches r \
+             JOIN time_entry_billing b ON b.entry_id = r.entry_id \
+             JOIN time_entries e ON e.id = r.entry_id \
+             WHERE b.project_id IS NOT NULL \
+             GROUP BY r.repo, b.project_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(totals
+        .into_iter()
+        .filter(|(repo, ..)| Path::new(workspace).starts_with(repo))
+        .max_by_key(|(.., duration)| *duration)
+        .map(|(_, project_id, _)| project_id))
+}
+
+fn resolve_project(app: &AppHandle, workspace: &str) -> Result<Option<String>, String> {
+    match mapped_project(&projects(app), workspace) {
+        Some(project_id) => Ok(Some(project_id)),
+        None => tracked_project(app, workspace),
+    }
+}
+
+/// Extend the editor's open coding span in `workspace` or start a new one;
+/// returns the span id
+fn record_heartbeat(
+    conn: &Connection,
+    heartbeat: &Heartbeat,
+    project_id: Option<&str>,
+    now: i64,
+) -> rusqlite::Result<i64> {
+    let open: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM coding_spans \
+             WHERE workspace = ?1 AND editor IS ?2 AND ended_at >= ?3 \
+             ORDER BY ended_at DESC LIMIT 1",
+            params![
+                heartbeat.workspace,
+                heartbeat.editor,
+                now - HEARTBEAT_GAP_MS
+            ],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match open {
+        Some(id) => {
+            conn.execute(
+                "UPDATE coding_spans SET ended_at = max(ended_at, ?2), \
+                        language = coalesce(?3, language), project_id = coalesce(?4, project_id) \
+                 WHERE id = ?1",
+                params![id, now, heartbeat.language, project_id],
+            )?;
+            Ok(id)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO coding_spans (workspace, project_id, language, editor, started_at, ended_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                params![
+                    heartbeat.workspace,
+                    project_id,
+                    heartbeat.language,
+                    heartbeat.editor,
+                    now
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+fn heartbeat(app: &AppHandle, client: &Client, params: Value) -> Result<Value, RpcError> {
+    let mut heartbeat: Heartbeat =
+        serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+    if !Path::new(&heartbeat.workspace).is_absolute() {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            "workspace must be an absolute path",
+        ));
+    }
+    heartbeat.editor = heartbeat.editor.or_else(|| client.name.clone());
+    let project_id = resolve_project(app, &heartbeat.workspace)?;
+    let now = now_ms();
+    if !app.state::<crate::read_only::ReadOnlyMode>().is_enabled() {
+        crate::sessions::with_db(app, |conn| {
+            record_heartbeat(conn, &heartbeat, project_id.as_deref(), now as i64)
+        })?;
+    }
+
+    let activity = EditorActivity {
+        editor: heartbeat.editor,
+        workspace: heartbeat.workspace,
+        file: heartbeat.file,
+        language: heartbeat.language,
+        project_id: project_id.clone(),
+        at_ms: now,
+    };
+    let last = app
+        .state::<IdeServer>()
+        .activity
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(activity.clone());
+    let changed = !matches!(last, Some(last)
+        if last.workspace == activity.workspace && last.file == activity.file);
+    if changed {
+        crate::events::publish(app, "ide://activity", &activity);
+    }
+    Ok(json!({ "projectId": project_id }))
+}
+
+/// Open cached tasks, optionally of one project and matching a query
+fn open_tasks(
+    app: &AppHandle,
+    project_id: Option<&str>,
+    query: Option<&str>,
+) -> Result<Value, String> {
+    let pattern = query
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", q.to_lowercase()));
+    let tasks = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, json_extract(data, '$.title'), json_extract(data, '$.status'), \
+                    json_extract(data, '$.project_id') \
+             FROM tasks \
+             WHERE coalesce(json_extract(data, '$.is_deleted'), 0) = 0 \
+               AND coalesce(json_extract(data, '$.status'), '') <> 'done' \
+               AND (?1 IS NULL OR json_extract(data, '$.project_id') = ?1) \
+               AND (?2 IS NULL OR lower(json_extract(data, '$.title')) LIKE ?2) \
+             ORDER BY json_extract(data, '$.updated_at') DESC LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(params![project_id, pattern, MAX_TASKS], |row| {
+            Ok(json!({
+                "id": row.get::<_, String>(0)?,
+                "title": row.get::<_, Option<String>>(1)?,
+                "status": row.get::<_, Option<String>>(2)?,
+                "projectId": row.get::<_, Option<String>>(3)?,
+            }))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(Value::from(tasks))
+}
+
+fn param<'a>(params: &'a Value, name: &str) -> Option<&'a str> {
+    params.get(name).and_then(Value::as_str)
+}
+
+fn handle(
+    app: &AppHandle,
+    client: &mut Client,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    if method == "initialize" {
+        client.name = params
+            .pointer("/clientInfo/name")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        return Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "serverInfo": {
+                "name": "FlowState",
+                "version": app.package_info().version.to_string(),
+            },
+        }));
+    }
+    if app.state::<crate::app_lock::AppLock>().is_locked() {
+        return Err(RpcError::new(LOCKED, "FlowState is locked"));
+    }
+    match method {
+        "heartbeat" => heartbeat(app, client, params),
+        "subscribe" => subscribe(app, client, &params),
+        "unsubscribe" => {
+            let topics = topics_param(&params)?;
+            let mut current = client.topics.lock().unwrap_or_else(|e| e.into_inner());
+            current.retain(|topic| !topics.contains(topic));
+            Ok(json!({ "topics": *current }))
+        }
+        "timer/get" => Ok(json!(crate::time_tracking::get_active_task_timer(
+            app.clone()
+        )?)),
+        "timer/start" => {
+            let task_id = param(&params, "taskId")
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "taskId is required"))?;
+            Ok(json!(crate::time_tracking::start_task_timer(
+                app.clone(),
+                task_id.to_string()
+            )?))
+        }
+        "timer/stop" => Ok(json!(crate::time_tracking::stop_task_timer(app.clone())?)),
+        "project/resolve" => {
+            let workspace = param(&params, "workspace")
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "workspace is required"))?;
+            Ok(json!({ "projectId": resolve_project(app, workspace)? }))
+        }
+        "tasks/list" => {
+            let project_id = match (param(&params, "projectId"), param(&params, "workspace")) {
+                (Some(project_id), _) => Some(project_id.to_string()),
+                (None, Some(workspace)) => resolve_project(app, workspace)?,
+                (None, None) => None,
+            };
+            Ok(open_tasks(
+                app,
+                project_id.as_deref(),
+                param(&params, "query"),
+            )?)
+        }
+        "tasks/suggest" => {
+            let context = param(&params, "file").map(|file| {
+                Path::new(file)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| file.to_string())
+            });
+            Ok(json!(crate::task_context::suggest_task_for_context(
+                app.clone(),
+                context
+            )?))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    }
+}
+
+/// Topics named in `params.topics`
+fn topics_param(params: &Value) -> Result<Vec<&'static str>, RpcError> {
+    let names = params
+        .get("topics")
+        .and_then(Value::as_array)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "topics must be a list"))?;
+    names
+        .iter()
+        .map(|name| {
+            name.as_str()
+                .and_then(|name| TOPICS.into_iter().find(|topic| *topic == name))
+                .ok_or_else(|| {
+                    RpcError::new(
+                        INVALID_PARAMS,
+                        format!("Unknown topic {}; use {}", name, TOPICS.join(", ")),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Subscribe to `topics`; the result carries the current state of each, so
+/// nothing falls between it and the first notification
+fn subscribe(app: &AppHandle, client: &Client, params: &Value) -> Result<Value, RpcError> {
+    let topics = topics_param(params)?;
+    let mut current = client.topics.lock().unwrap_or_else(|e| e.into_inner());
+    current.extend(topics.iter().copied());
+    let mut result = json!({ "topics": *current });
+    if topics.contains(&"timer") {
+        result["timer"] = json!(crate::time_tracking::active_timer(app)?);
+    }
+    if topics.contains(&"focus") {
+        result["focus"] = json!(app.state::<crate::focus::FocusEngine>().snapshot());
+    }
+    Ok(result)
+}
+
+/// Bus topic to subscription topic, notification method and params key
+fn route_event(topic: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    match topic {
+        "timer://started" => Some(("timer", "timer/changed", "timer")),
+        "timer://stopped" => Some(("timer", "timer/changed", "stopped")),
+        "focus://tick" => Some(("focus", "focus/changed", "state")),
+        "focus://phase" => Some(("focus", "focus/phaseEnded", "ended")),
+        "tasks://changed" => Some(("tasks", "tasks/changed", "changes")),
+        _ => None,
+    }
+}
+
+/// Notification for a bus event, if `topics` cover it
+fn notification(topics: &BTreeSet<&'static str>, event: &BusEvent) -> Option<Value> {
+    let (topic, method, key) = route_event(&event.topic)?;
+    if !topics.contains(topic) {
+        return None;
+    }
+    let mut params = json!({ "seq": event.seq });
+    params[key] = event.payload.clone();
+    if event.topic == "timer://stopped" {
+        // The state after the change, as for a start
+        params["timer"] = Value::Null;
+    }
+    Some(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+/// Send the bus events a client subscribed to until it goes away
+async fn forward(
+    app: AppHandle,
+    topics: Topics,
+    mut events: broadcast::Receiver<BusEvent>,
+    out: mpsc::Sender<Vec<u8>>,
+) {
+    loop {
+        let message = match events.recv().await {
+            Ok(event) => {
+                let topics = topics.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                match notification(&topics, &event) {
+                    Some(message) if !app.state::<crate::app_lock::AppLock>().is_locked() => {
+                        message
+                    }
+                    _ => continue,
+                }
+            }
+            // The client fell behind; it should fetch the state again
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                if topics.lock().unwrap_or_else(|e| e.into_inner()).is_empty() {
+                    continue;
+                }
+                json!({ "jsonrpc": "2.0", "method": "events/lagged", "params": { "skipped": skipped } })
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if out.send(frame(&message)).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+/// Response to one message; None for notifications
+fn dispatch(app: &AppHandle, client: &mut Client, body: &[u8]) -> Option<Value> {
+    let value: Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+    let request = match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+            ))
+        }
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, e.to_string()),
+            ))
+        }
+    };
+    let result = handle(app, client, &request.method, request.params);
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    })
+}
+
+/// Body of the next message; None at the end of the stream
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
+    let invalid =
+        |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+    let mut length: Option<usize> = None;
+    let mut headers = 0;
+    loop {
+        let mut line = String::new();
+        if (&mut *reader)
+            .take(MAX_HEADER_LINE)
+            .read_line(&mut line)
+            .await?
+            == 0
+        {
+            return if headers == 0 {
+                Ok(None)
+            } else {
+                Err(invalid("Stream ended in the headers"))
+            };
+        }
+        if !line.ends_with('\n') {
+            return Err(invalid("Header line too long"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length
+        .filter(|length| *length <= MAX_MESSAGE_BYTES)
+        .ok_or_else(|| invalid("Missing or too large Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(body))
+}
+
+fn frame(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut framed = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    framed.extend_from_slice(body.as_bytes());
+    framed
+}
+
+async fn serve_client<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    app: AppHandle,
+    stream: S,
+) {
+    let server = app.state::<IdeServer>();
+    server.clients.fetch_add(1, Ordering::Relaxed);
+    let (read, mut write) = tokio::io::split(stream);
+    let mut reader = tokio::io::BufReader::new(read);
+    // Responses and notifications share the connection
+    let (out, mut outgoing) = mpsc::channel::<Vec<u8>>(OUTGOING_CAPACITY);
+    let writer = tauri::async_runtime::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if let Err(e) = write.write_all(&message).await {
+                log::debug!("Editor connection closed: {}", e);
+                break;
+            }
+        }
+    });
+    let mut client = Client::default();
+    let forwarder = tauri::async_runtime::spawn(forward(
+        app.clone(),
+        client.topics.clone(),
+        app.state::<crate::events::EventBus>().listen(),
+        out.clone(),
+    ));
+    loop {
+        let body = match read_message(&mut reader).await {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(e) => {
+                log::debug!("Editor connection closed: {}", e);
+                break;
+            }
+        };
+        if !enabled(&app) {
+            break;
+        }
+        let Some(response) = dispatch(&app, &mut client, &body) else {
+            continue;
+        };
+        if out.send(frame(&response)).await.is_err() {
+            break;
+        }
+    }
+    forwarder.abort();
+    drop(out);
+    let _ = writer.await;
+    server.clients.fetch_sub(1, Ordering::Relaxed);
+}
+
+fn set_endpoint(app: &AppHandle, endpoint: Option<String>) {
+    *app.state::<IdeServer>()
+        .endpoint
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = endpoint;
+}
+
+#[cfg(unix)]
+async fn listen(app: &AppHandle) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve the app data directory: {}", e))?
+        .join("ide.sock");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    // Left over from a crash; the instance lock keeps a second app away
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    set_endpoint(app, Some(path.to_string_lossy().into_owned()));
+    log::info!("Editor plugins can connect to {}", path.display());
+
+    while enabled(app) {
+        match tokio::time::timeout(ACCEPT_POLL, listener.accept()).await {
+            Ok(Ok((stream, _))) => {
+                tauri::async_runtime::spawn(serve_client(app.clone(), stream));
+            }
+            Ok(Err(e)) => log::warn!("Editor connection failed: {}", e),
+            Err(_) => {}
+        }
+    }
+    drop(listener);
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn listen(app: &AppHandle) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = format!(
+        r"\\.\pipe\flowstate-ide-{}",
+        crate::multi_user::current_user()
+    );
+    let create = |first: bool| {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .reject_remote_clients(true)
+            .create(&name)
+            .map_err(|e| format!("Failed to listen on {}: {}", name, e))
+    };
+    let mut server = create(true)?;
+    set_endpoint(app, Some(name.clone()));
+    log::info!("Editor plugins can connect to {}", name);
+
+    while enabled(app) {
+        match tokio::time::timeout(ACCEPT_POLL, server.connect()).await {
+            Ok(Ok(())) => {
+                let connected = std::mem::replace(&mut server, create(false)?);
+                tauri::async_runtime::spawn(serve_client(app.clone(), connected));
+            }
+            Ok(Err(e)) => {
+                log::warn!("Editor connection failed: {}", e);
+                server = create(false)?;
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Serve editor plugins while enabled (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if enabled(&app) {
+                let result = listen(&app).await;
+                set_endpoint(&app, None);
+                if let Err(e) = result {
+                    log::warn!("Editor socket unavailable: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+            tokio::time::sleep(ACCEPT_POLL).await;
+        }
+    })
+}
+
+/// Workspace an editor reported within the last heartbeat gap
+pub(crate) fn recent_workspace(app: &AppHandle) -> Option<String> {
+    let activity = app.state::<IdeServer>();
+    let activity = activity.activity.lock().unwrap_or_else(|e| e.into_inner());
+    activity
+        .as_ref()
+        .filter(|a| now_ms().saturating_sub(a.at_ms) <= HEARTBEAT_GAP_MS as u64)
+        .map(|a| a.workspace.clone())
+}
+
+fn state(app: &AppHandle) -> IdeState {
+    let server = app.state::<IdeServer>();
+    let endpoint = server
+        .endpoint
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let activity = server
+        .activity
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    IdeState {
+        enabled: enabled(app),
+        endpoint,
+        clients: server.clients.load(Ordering::Relaxed),
+        activity,
+        projects: projects(app),
+    }
+}
+
+fn save(app: &AppHandle, key: &str, value: Value) -> Result<(), String> {
+    let store = app
+        .store(IDE_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", IDE_STORE, e))?;
+    store.set(key, value);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", IDE_STORE, e))
+}
+
+#[tauri::command]
+pub fn get_ide_integration(app: AppHandle) -> IdeState {
+    state(&app)
+}
+
+/// Open or close the editor socket
+#[tauri::command]
+pub fn set_ide_integration(app: AppHandle, enabled: bool) -> Result<IdeState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_ide_integration")?;
+    save(&app, ENABLED_KEY, Value::Bool(enabled))?;
+    Ok(state(&app))
+}
+
+/// Replace the workspace → project mapping used to attribute coding time
+#[tauri::command]
+pub fn set_ide_projects(
+    app: AppHandle,
+    projects: Vec<WorkspaceProject>,
+) -> Result<IdeState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_ide_projects")?;
+    crate::app_lock::ensure_unlocked(&app, "set_ide_projects")?;
+    let mut normalized: Vec<WorkspaceProject> = Vec::new();
+    for project in projects {
+        let project = WorkspaceProject {
+            workspace: project.workspace.trim().to_string(),
+            project_id: project.project_id.trim().to_string(),
+        };
+        if !Path::new(&project.workspace).is_absolute() || project.project_id.is_empty() {
+            return Err(format!(
+                "'{}' needs an absolute folder and a project",
+                project.workspace
+            )
+            .into());
+        }
+        normalized.retain(|p| p.workspace != project.workspace);
+        normalized.push(project);
+    }
+    save(
+        &app,
+        PROJECTS_KEY,
+        serde_json::to_value(&normalized).map_err(|e| e.to_string())?,
+    )?;
+    Ok(state(&app))
+}
+
+/// Coding time reported by editors per project and workspace in
+/// [from_ms, to_ms), most first
+#[tauri::command]
+pub fn get_coding_time(
+    app: AppHandle,
+    from_ms: Option<i64>,
+    to_ms: Option<i64>,
+) -> Result<Vec<CodingTotal>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_coding_time")?;
+    Ok(crate::sessions::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT project_id, workspace, count(*), \
+                    sum(min(ended_at, coalesce(?2, ended_at)) - max(started_at, coalesce(?1, started_at))) \
+             FROM coding_spans \
+             WHERE (?1 IS NULL OR ended_at > ?1) AND (?2 IS NULL OR started_at < ?2) \
+             GROUP BY project_id, workspace ORDER BY 4 DESC",
+        )?;
+        let rows = stmt.query_map(params![from_ms, to_ms], |row| {
+            Ok(CodingTotal {
+                project_id: row.get(0)?,
+                workspace: row.get(1)?,
+                spans: row.get(2)?,
+                duration_ms: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(workspace: &str, editor: &str) -> Heartbeat {
+        Heartbeat {
+            workspace: workspace.to_string(),
+            file: None,
+            language: Some("rust".to_string()),
+            editor: Some(editor.to_string()),
+        }
+    }
+
+    #[test]
+    fn messages_round_trip_through_lsp_framing() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let first = json!({"jsonrpc": "2.0", "id": 1, "method": "timer/get"});
+            let second =
+                json!({"jsonrpc": "2.0", "method": "heartbeat", "params": {"workspace": "/ü"}});
+            let mut stream = frame(&first);
+            stream.extend(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n");
+            stream.extend(frame(&second));
+            let mut reader = tokio::io::BufReader::new(&stream[..]);
+
+            let body = read_message(&mut reader).await.unwrap().unwrap();
+            assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), first);
+            let body = read_message(&mut reader).await.unwrap().unwrap();
+            assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), second);
+            assert!(read_message(&mut reader).await.unwrap().is_none());
+
+            let mut reader = tokio::io::BufReader::new(&b"Content-Length: 99999999\r\n\r\n"[..]);
+            assert!(read_message(&mut reader).await.is_err());
+        });
+    }
+
+    #[test]
+    fn subscribed_events_become_notifications() {
+        let event = |topic: &str, payload: Value| BusEvent {
+            seq: 7,
+            topic: topic.to_string(),
+            timestamp_ms: 0,
+            request_id: None,
+            payload,
+        };
+        let topics: BTreeSet<&'static str> = ["timer", "tasks"].into_iter().collect();
+
+        let started = notification(&topics, &event("timer://started", json!({"id": 1})));
+        assert_eq!(
+            started,
+            Some(json!({"jsonrpc": "2.0", "method": "timer/changed",
+                        "params": {"seq": 7, "timer": {"id": 1}}}))
+        );
+        let stopped = notification(&topics, &event("timer://stopped", json!({"id": 1})));
+        assert_eq!(
+            stopped.unwrap()["params"],
+            json!({"seq": 7, "timer": null, "stopped": {"id": 1}})
+        );
+        let tasks = notification(
+            &topics,
+            &event("tasks://changed", json!({"removed": ["a"]})),
+        );
+        assert_eq!(tasks.unwrap()["params"]["changes"]["removed"], json!(["a"]));
+        // Not subscribed, or not a feed at all
+        assert!(notification(&topics, &event("focus://tick", json!({}))).is_none());
+        assert!(notification(&topics, &event("app://lock", json!({}))).is_none());
+    }
+
+    #[test]
+    fn topics_must_be_known() {
+        assert_eq!(
+            topics_param(&json!({"topics": ["timer", "focus"]})).ok(),
+            Some(vec!["timer", "focus"])
+        );
+        let code = |params: Value| topics_param(&params).err().map(|e| e.code);
+        assert_eq!(code(json!({"topics": ["timers"]})), Some(INVALID_PARAMS));
+        assert_eq!(code(json!({"topics": "timer"})), Some(INVALID_PARAMS));
+        assert_eq!(code(json!({})), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn deepest_mapped_folder_wins() {
+        let projects = vec![
+            WorkspaceProject {
+                workspace: "/work".to_string(),
+                project_id: "client".to_string(),
+            },
+            WorkspaceProject {
+                workspace: "/work/app".to_string(),
+                project_id: "app".to_string(),
+            },
+        ];
+        assert_eq!(
+            mapped_project(&projects, "/work/app/src").as_deref(),
+            Some("app")
+        );
+        assert_eq!(
+            mapped_project(&projects, "/work/site").as_deref(),
+            Some("client")
+        );
+        assert_eq!(
+            mapped_project(&projects, "/work/application").as_deref(),
+            Some("client")
+        );
+        assert_eq!(mapped_project(&projects, "/home"), None);
+    }
+
+    #[test]
+    fn heartbeats_close_together_extend_one_span() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(crate::sessions::SCHEMA).unwrap();
+        let start = 1_700_000_000_000;
+        let first =
+            record_heartbeat(&conn, &heartbeat("/work/app", "vscode"), Some("p"), start).unwrap();
+        let same = record_heartbeat(
+            &conn,
+            &heartbeat("/work/app", "vscode"),
+            None,
+            start + 60_000,
+        )
+        .unwrap();
+        let other_editor = record_heartbeat(
+            &conn,
+            &heartbeat("/work/app", "idea"),
+            Some("p"),
+            start + 60_000,
+        )
+        .unwrap();
+        let after_break = record_heartbeat(
+            &conn,
+            &heartbeat("/work/app", "vscode"),
+            Some("p"),
+            start + 60_000 + HEARTBEAT_GAP_MS + 1,
+        )
+        .unwrap();
+        assert_eq!(first, same);
+        assert_ne!(first, other_editor);
+        assert_ne!(first, after_break);
+        let (project, duration): (String, i64) = conn
+            .query_row(
+                "SELECT project_id, ended_at - started_at FROM coding_spans WHERE id = ?1",
+                params![first],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((project.as_str(), duration), ("p", 60_000));
+    }
+}
@@ -0,0 +1,243 @@
+//! User idle detection.
+//!
+//! Seconds since the last keyboard/mouse input come from the platform:
+//! `GetLastInputInfo` on Windows, `HIDIdleTime` from `ioreg` on macOS,
+//! Mutter's IdleMonitor over D-Bus on Wayland (GNOME) and `xprintidle` on
+//! X11. The monitor polls every few seconds and publishes `idle://started`
+//! once the threshold is crossed and `idle://ended` on the next input. When
+//! idle starts, a running focus phase is paused and the task timer stopped at
+//! the last input, and once it ends the idle period is recorded in
+//! `sessions.db`. Where no source is available the monitor reports
+//! `supported: false` and never fires, and it polls less and less often (the
+//! sources are helper programs that may simply not be installed).
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Longest wait between readings while no idle source works
+const MAX_UNSUPPORTED_INTERVAL: Duration = Duration::from_secs(10 * 60);
+pub const DEFAULT_THRESHOLD_SECS: u64 = 5 * 60;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleState {
+    /// False when no idle source works on this platform/session
+    pub supported: bool,
+    pub idle: bool,
+    pub idle_seconds: Option<u64>,
+    pub threshold_secs: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleEvent {
+    /// Seconds without input when the event fired
+    pub idle_seconds: u64,
+    /// Wall-clock time of the last input (ms since epoch)
+    pub last_input_at_ms: u64,
+}
+
+pub struct IdleMonitor {
+    state: Mutex<IdleState>,
+}
+
+impl Default for IdleMonitor {
+    fn default() -> Self {
+        IdleMonitor {
+            state: Mutex::new(IdleState {
+                supported: true,
+                idle: false,
+                idle_seconds: None,
+                threshold_secs: DEFAULT_THRESHOLD_SECS,
+            }),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+async fn idle_seconds(_app: &AppHandle) -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    // SAFETY: `info` is a properly sized LASTINPUTINFO that outlives the call
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are tick counts in ms that wrap after ~49 days
+    // SAFETY: no arguments, no preconditions
+    let now = unsafe { GetTickCount() };
+    Some(u64::from(now.wrapping_sub(info.dwTime)) / 1000)
+}
+
+#[cfg(target_os = "macos")]
+async fn idle_seconds(app: &AppHandle) -> Option<u64> {
+    let output = crate::trace::command(app, "ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .await
+        .ok()?;
+    // "HIDIdleTime" = 123456789 (nanoseconds)
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(nanos / 1_000_000_000)
+}
+
+#[cfg(target_os = "linux")]
+async fn idle_seconds(app: &AppHandle) -> Option<u64> {
+    let millis = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        // (uint64 12345,)
+        let output = crate::trace::command(app, "gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Mutter.IdleMonitor",
+                "--object-path",
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                "--method",
+                "org.gnome.Mutter.IdleMonitor.GetIdletime",
+            ])
+            .output()
+            .await
+            .ok()
+            .filter(|o| o.status.success())?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim_matches(|c: char| !c.is_ascii_digit())
+            .rsplit(' ')
+            .next()?
+            .parse::<u64>()
+            .ok()?
+    } else {
+        let output = crate::trace::command(app, "xprintidle")
+            .output()
+            .await
+            .ok()
+            .filter(|o| o.status.success())?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<u64>()
+            .ok()?
+    };
+    Some(millis / 1000)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+async fn idle_seconds(_app: &AppHandle) -> Option<u64> {
+    None
+}
+
+/// Fold a new reading into the monitor state; returns the event to publish
+fn observe(app: &AppHandle, seconds: Option<u64>) -> Option<(&'static str, IdleEvent)> {
+    let monitor = app.state::<IdleMonitor>();
+    let mut state = monitor.state.lock().unwrap_or_else(|e| e.into_inner());
+    state.supported = seconds.is_some();
+    state.idle_seconds = seconds;
+    let seconds = seconds?;
+
+    let idle = seconds >= state.threshold_secs;
+    let was_idle = std::mem::replace(&mut state.idle, idle);
+    let topic = match (was_idle, idle) {
+        (false, true) => "idle://started",
+        (true, false) => "idle://ended",
+        _ => return None,
+    };
+    Some((
+        topic,
+        IdleEvent {
+            idle_seconds: seconds,
+            last_input_at_ms: crate::events::now_ms().saturating_sub(seconds * 1000),
+        },
+    ))
+}
+
+/// Start polling the idle time (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut warned = false;
+        let mut interval = POLL_INTERVAL;
+        // Last input before the current idle period
+        let mut idle_since: Option<u64> = None;
+        loop {
+            tokio::time::sleep(interval).await;
+            let seconds = idle_seconds(&app).await;
+            if seconds.is_none() && !warned {
+                log::warn!("Idle detection is not available in this session");
+                warned = true;
+            }
+            // Back off while unsupported instead of running the helper every few seconds
+            interval = match seconds {
+                Some(_) => POLL_INTERVAL,
+                None => (interval * 2).min(MAX_UNSUPPORTED_INTERVAL),
+            };
+
+            let Some((topic, event)) = observe(&app, seconds) else {
+                continue;
+            };
+            log::info!("{} after {}s without input", topic, event.idle_seconds);
+            if topic == "idle://ended" {
+                if let Some(since) = idle_since.take() {
+                    if let Err(e) = crate::sessions::record_idle(
+                        &app,
+                        since as i64,
+                        event.last_input_at_ms as i64,
+                    ) {
+                        log::warn!("Failed to record the idle period: {}", e);
+                    }
+                }
+            }
+            if topic == "idle://started" {
+                idle_since = Some(event.last_input_at_ms);
+                if crate::focus::pause_for_idle(&app) {
+                    log::info!("Paused focus session while idle");
+                }
+                let last_input = event.last_input_at_ms as i64;
+                if let Err(e) = crate::time_tracking::stop_at(
+                    &app,
+                    last_input,
+                    crate::time_tracking::StopReason::Idle,
+                ) {
+                    log::warn!("Failed to stop the task timer: {}", e);
+                }
+            }
+            crate::events::publish(&app, topic, &event);
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_idle_state(monitor: tauri::State<'_, IdleMonitor>) -> IdleState {
+    monitor
+        .state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Seconds without input before the user counts as idle
+#[tauri::command]
+pub fn set_idle_threshold(
+    app: AppHandle,
+    monitor: tauri::State<'_, IdleMonitor>,
+    threshold_secs: u64,
+) -> Result<IdleState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_idle_threshold")?;
+    if threshold_secs < 30 {
+        return Err("Idle threshold must be at least 30 seconds".into());
+    }
+    let mut state = monitor.state.lock().unwrap_or_else(|e| e.into_inner());
+    state.threshold_secs = threshold_secs;
+    Ok(state.clone())
+}
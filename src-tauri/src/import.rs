@@ -0,0 +1,436 @@
+//! Importing tasks from Todoist, Trello and CSV files.
+//!
+//! `import_tasks` reads an export file, maps it onto FlowState task fields and
+//! saves the tasks through the offline cache, so they sync like local edits.
+//! Tasks whose title and due date match a cached task (or an earlier row of
+//! the same file) are reported as duplicates and skipped. A dry run reports
+//! the same without saving anything. Imports save in batches of `BATCH` and
+//! publish `import://progress` after each.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+use crate::parsers::{self, ImportedTask};
+
+const BATCH: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    Todoist,
+    Trello,
+    Csv,
+}
+
+/// CSV header names per task field; unset fields are guessed from the headers
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CsvMapping {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub due_date: Option<String>,
+    pub completed: Option<String>,
+    pub priority: Option<String>,
+    /// Comma- or semicolon-separated
+    pub tags: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImportOptions {
+    pub dry_run: bool,
+    pub csv: Option<CsvMapping>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub title: String,
+    pub due_date: Option<String>,
+    pub completed: bool,
+    pub priority: Option<&'static str>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub source: ImportSource,
+    pub dry_run: bool,
+    /// Tasks read from the file
+    pub total: usize,
+    /// Tasks created (or that would be, on a dry run)
+    pub tasks: Vec<ImportPreview>,
+    pub duplicates: Vec<ImportPreview>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub source: ImportSource,
+    pub done: usize,
+    pub total: usize,
+}
+
+const HEADER_GUESSES: [(&str, &[&str]); 6] = [
+    ("title", &["title", "name", "content", "task", "summary"]),
+    ("description", &["description", "notes", "desc", "details"]),
+    (
+        "dueDate",
+        &["due", "due date", "due_date", "duedate", "date", "deadline"],
+    ),
+    ("completed", &["completed", "done", "status", "checked"]),
+    ("priority", &["priority"]),
+    ("tags", &["tags", "labels", "label", "tag"]),
+];
+
+/// Column index of each field, from the mapping or the guesses above
+fn csv_columns(header: &[String], mapping: &CsvMapping) -> Result<[Option<usize>; 6], String> {
+    let find = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+    };
+    let mapped = [
+        &mapping.title,
+        &mapping.description,
+        &mapping.due_date,
+        &mapping.completed,
+        &mapping.priority,
+        &mapping.tags,
+    ];
+
+    let mut columns = [None; 6];
+    for (i, ((field, guesses), mapped)) in HEADER_GUESSES.iter().zip(mapped).enumerate() {
+        columns[i] = match mapped {
+            Some(name) => {
+                Some(find(name).ok_or_else(|| format!("No column {:?} for {}", name, field))?)
+            }
+            None => guesses.iter().find_map(|g| find(g)),
+        };
+    }
+    if columns[0].is_none() {
+        return Err("No title column found; map one explicitly".to_string());
+    }
+    Ok(columns)
+}
+
+fn csv_tasks(rows: Vec<Vec<String>>, mapping: &CsvMapping) -> Result<Vec<ImportedTask>, String> {
+    let mut rows = rows.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let [title, description, due, completed, priority, tags] = csv_columns(&header, mapping)?;
+    let cell = |row: &[String], column: Option<usize>| {
+        column
+            .and_then(|c| row.get(c))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    Ok(rows
+        .filter_map(|row| {
+            Some(ImportedTask {
+                title: cell(&row, title)?,
+                description: cell(&row, description),
+                due: cell(&row, due),
+                completed: cell(&row, completed).is_some_and(|v| {
+                    matches!(
+                        v.to_lowercase().as_str(),
+                        "true" | "yes" | "y" | "1" | "x" | "done" | "completed"
+                    )
+                }),
+                priority: cell(&row, priority).and_then(|p| match p.to_lowercase().as_str() {
+                    "high" | "urgent" | "p1" | "1" => Some("high"),
+                    "medium" | "normal" | "p2" | "2" => Some("medium"),
+                    "low" | "p3" | "3" => Some("low"),
+                    _ => None,
+                }),
+                tags: cell(&row, tags)
+                    .map(|t| {
+                        t.split([',', ';'])
+                            .map(str::trim)
+                            .filter(|t| !t.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// A due date the tasks table accepts: a date, or a date-time
+fn normalize_due(due: &str) -> Option<String> {
+    let due = due.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(due, "%Y-%m-%d") {
+        return Some(date.format("%Y-%m-%d").to_string());
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(due) {
+        return Some(time.to_rfc3339());
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(due, format).ok())
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+/// Title and due day, compared loosely
+fn duplicate_key(title: &str, due: Option<&str>) -> (String, Option<String>) {
+    let title = title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (title, due.and_then(|d| d.get(..10)).map(str::to_string))
+}
+
+/// Keys of the tasks already in the cache
+fn existing_keys(app: &AppHandle) -> Result<HashSet<(String, Option<String>)>, String> {
+    let rows = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT data FROM tasks")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(rows
+        .iter()
+        .filter_map(|data| serde_json::from_str::<Map<String, Value>>(data).ok())
+        .filter(|row| row.get("is_deleted").and_then(Value::as_bool) != Some(true))
+        .filter_map(|row| {
+            let title = row.get("title")?.as_str()?;
+            let due = row.get("due_date").and_then(Value::as_str);
+            Some(duplicate_key(title, due))
+        })
+        .collect())
+}
+
+/// `owner` is the row every imported task starts from (`offline::owned_task`)
+fn task_row(
+    owner: &Map<String, Value>,
+    preview: &ImportPreview,
+    description: Option<String>,
+) -> Map<String, Value> {
+    let mut task = owner.clone();
+    task.insert("title".to_string(), Value::from(preview.title.clone()));
+    if let Some(description) = description {
+        task.insert("description".to_string(), Value::from(description));
+    }
+    if let Some(due) = &preview.due_date {
+        task.insert("due_date".to_string(), Value::from(due.clone()));
+    }
+    if let Some(priority) = preview.priority {
+        task.insert("priority".to_string(), Value::from(priority));
+    }
+    if !preview.tags.is_empty() {
+        task.insert("tags".to_string(), Value::from(preview.tags.clone()));
+    }
+    let status = if preview.completed { "done" } else { "planned" };
+    task.insert("status".to_string(), Value::from(status));
+    if preview.completed {
+        task.insert(
+            "completed_at".to_string(),
+            Value::from(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+    task
+}
+
+/// Import tasks from a Todoist or Trello JSON export or a CSV file
+#[tauri::command]
+pub async fn import_tasks(
+    app: AppHandle,
+    source: ImportSource,
+    path: String,
+    options: Option<ImportOptions>,
+) -> Result<ImportReport, FlowStateError> {
+    crate::trace::scope("import_tasks", async move {
+        let options = options.unwrap_or_default();
+        crate::app_lock::ensure_unlocked(&app, "import_tasks")?;
+        if !options.dry_run {
+            crate::read_only::ensure_writable(&app, "import_tasks")?;
+        }
+
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let imported = match source {
+            ImportSource::Todoist => parsers::parse_todoist_export(&bytes)
+                .map_err(|e| format!("Invalid Todoist export: {}", e))?,
+            ImportSource::Trello => parsers::parse_trello_export(&bytes)
+                .map_err(|e| format!("Invalid Trello export: {}", e))?,
+            ImportSource::Csv => {
+                let rows =
+                    parsers::parse_csv(&bytes).map_err(|e| format!("Invalid CSV file: {}", e))?;
+                csv_tasks(rows, options.csv.as_ref().unwrap_or(&CsvMapping::default()))?
+            }
+        };
+        drop(bytes);
+
+        let mut seen = existing_keys(&app)?;
+        let mut report = ImportReport {
+            source,
+            dry_run: options.dry_run,
+            total: imported.len(),
+            tasks: Vec::new(),
+            duplicates: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let mut pending = Vec::new();
+        let owner = crate::offline::owned_task(&app);
+
+        for task in imported {
+            let due_date = task.due.as_deref().and_then(|due| {
+                let normalized = normalize_due(due);
+                if normalized.is_none() {
+                    report
+                        .warnings
+                        .push(format!("Ignored due date {:?} of {:?}", due, task.title));
+                }
+                normalized
+            });
+            let preview = ImportPreview {
+                title: task.title,
+                due_date,
+                completed: task.completed,
+                priority: task.priority,
+                tags: task.tags,
+            };
+            if !seen.insert(duplicate_key(&preview.title, preview.due_date.as_deref())) {
+                report.duplicates.push(preview);
+                continue;
+            }
+            pending.push(task_row(&owner, &preview, task.description));
+            report.tasks.push(preview);
+        }
+
+        if !options.dry_run {
+            let total = pending.len();
+            let mut done = 0;
+            while !pending.is_empty() {
+                let batch: Vec<_> = pending.drain(..BATCH.min(pending.len())).collect();
+                done += batch.len();
+                crate::offline::save_tasks(&app, batch)?;
+                crate::events::publish(
+                    &app,
+                    "import://progress",
+                    &ImportProgress {
+                        source,
+                        done,
+                        total,
+                    },
+                );
+            }
+        }
+
+        log::info!(
+            "{} {} tasks from {:?} ({} duplicates)",
+            if options.dry_run {
+                "Would import"
+            } else {
+                "Imported"
+            },
+            report.tasks.len(),
+            source,
+            report.duplicates.len()
+        );
+        Ok(report)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Notion database exported as CSV
+    const NOTION_CSV: &str = "Task name,Status,Due,Priority,Labels,Notes\n\
+                              Pay rent,Done,2026-11-01,High,\"home; money\",\n\
+                              ,Not started,,,,\n\
+                              Plan the trip,Not started,2026-11-20 18:30,P3,,Book early\n";
+
+    fn rows(csv: &str) -> Vec<Vec<String>> {
+        parsers::parse_csv(csv.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn maps_csv_columns() {
+        let mapping = CsvMapping {
+            title: Some("task name".to_string()),
+            completed: Some("Status".to_string()),
+            ..Default::default()
+        };
+        let tasks = csv_tasks(rows(NOTION_CSV), &mapping).unwrap();
+        assert_eq!(
+            tasks,
+            vec![
+                ImportedTask {
+                    title: "Pay rent".to_string(),
+                    due: Some("2026-11-01".to_string()),
+                    completed: true,
+                    priority: Some("high"),
+                    tags: vec!["home".to_string(), "money".to_string()],
+                    ..Default::default()
+                },
+                ImportedTask {
+                    title: "Plan the trip".to_string(),
+                    description: Some("Book early".to_string()),
+                    due: Some("2026-11-20 18:30".to_string()),
+                    priority: Some("low"),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_needs_a_title_column() {
+        assert_eq!(
+            csv_tasks(rows(NOTION_CSV), &CsvMapping::default()),
+            Err("No title column found; map one explicitly".to_string())
+        );
+        let mapping = CsvMapping {
+            title: Some("Title".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            csv_tasks(rows(NOTION_CSV), &mapping),
+            Err("No column \"Title\" for title".to_string())
+        );
+        assert_eq!(csv_tasks(Vec::new(), &mapping), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn normalizes_due_dates() {
+        assert_eq!(normalize_due(" 2026-11-01 ").as_deref(), Some("2026-11-01"));
+        assert_eq!(
+            normalize_due("2026-11-20 18:30").as_deref(),
+            Some("2026-11-20T18:30:00")
+        );
+        assert_eq!(
+            normalize_due("2026-10-20T09:00:00.000Z").as_deref(),
+            Some("2026-10-20T09:00:00+00:00")
+        );
+        for due in ["tomorrow", "2026-13-01", "01/11/2026", ""] {
+            assert_eq!(normalize_due(due), None, "{:?}", due);
+        }
+    }
+
+    #[test]
+    fn duplicates_match_loosely_on_title_and_day() {
+        assert_eq!(
+            duplicate_key("  Pay   Rent ", Some("2026-11-01T09:00:00")),
+            duplicate_key("pay rent", Some("2026-11-01"))
+        );
+        assert_ne!(
+            duplicate_key("Pay rent", Some("2026-11-01")),
+            duplicate_key("Pay rent", None)
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Startup dependency graph.
+//!
+//! After the window is up, the service probes and the background work of the
+//! subsystems (task sync, the search index, calendar, CI and feed polling,
+//! attachment cleanup, idle detection, retention, the editor socket) start
+//! as nodes of an explicit dependency graph. Every node whose dependencies
+//! are satisfied runs in parallel with a per-node timeout; a node that fails
+//! or times out only skips its dependents. Progress is exposed through `get_init_status` and the
+//! `init://status` topic. State that commands read from the first call on
+//! (settings stores, the app lock, timers) is still loaded in `setup()`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+type NodeFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+enum Run {
+    /// Check that a service is up
+    Probe(fn(AppHandle) -> NodeFuture),
+    /// Start a subsystem's background task
+    Task(fn(&AppHandle) -> JoinHandle<()>),
+}
+
+struct InitNode {
+    name: &'static str,
+    deps: &'static [&'static str],
+    timeout: Duration,
+    run: Run,
+}
+
+/// Time for a subsystem to start its background task
+const TASK_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NODES: &[InitNode] = &[
+    InitNode {
+        name: "config",
+        deps: &[],
+        timeout: Duration::from_secs(5),
+        run: Run::Probe(init_config),
+    },
+    InitNode {
+        name: "docker",
+        deps: &["config"],
+        timeout: Duration::from_secs(10),
+        run: Run::Probe(init_docker),
+    },
+    InitNode {
+        name: "supabase",
+        deps: &["docker"],
+        timeout: Duration::from_secs(15),
+        run: Run::Probe(init_supabase),
+    },
+    InitNode {
+        name: "supabase-services",
+        deps: &["supabase"],
+        timeout: Duration::from_secs(10),
+        run: Run::Probe(init_supabase_services),
+    },
+    InitNode {
+        name: "task-sync",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::offline::start),
+    },
+    InitNode {
+        name: "search-index",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::search::start),
+    },
+    InitNode {
+        name: "calendars",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::calendars::start),
+    },
+    InitNode {
+        name: "ci-builds",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::ci_builds::start),
+    },
+    InitNode {
+        name: "feeds",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::feeds::start),
+    },
+    InitNode {
+        name: "attachment-cleanup",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::attachments::start),
+    },
+    InitNode {
+        name: "attachment-text",
+        deps: &["search-index", "attachment-cleanup"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::attachment_index::start),
+    },
+    InitNode {
+        name: "idle",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::idle::start),
+    },
+    InitNode {
+        name: "ide-socket",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::ide::start),
+    },
+    InitNode {
+        name: "local-api",
+        deps: &["task-sync"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::local_api::start),
+    },
+    InitNode {
+        name: "grpc",
+        deps: &["task-sync"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::grpc::start),
+    },
+    InitNode {
+        name: "state-snapshot",
+        deps: &["task-sync"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::snapshot::start),
+    },
+    InitNode {
+        name: "backups",
+        deps: &["supabase"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::backup::start),
+    },
+    InitNode {
+        name: "retention",
+        deps: &["task-sync"],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::retention::start),
+    },
+    InitNode {
+        name: "otel-export",
+        deps: &[],
+        timeout: TASK_START_TIMEOUT,
+        run: Run::Task(crate::otel::start),
+    },
+];
+
+/// Start a subsystem's task, stopping the one a previous run started
+fn start_task(
+    app: AppHandle,
+    name: &'static str,
+    start: fn(&AppHandle) -> JoinHandle<()>,
+) -> NodeFuture {
+    Box::pin(async move {
+        let status = app.state::<InitStatus>();
+        let mut tasks = status.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(previous) = tasks.remove(name) {
+            previous.abort();
+        }
+        tasks.insert(name, start(&app));
+        Ok(())
+    })
+}
+
+fn init_config(app: AppHandle) -> NodeFuture {
+    Box::pin(async move {
+        app.store("settings.json")
+            .map(|_| ())
+            .map_err(|e| format!("Failed to load settings store: {}", e))
+    })
+}
+
+fn init_docker(app: AppHandle) -> NodeFuture {
+    Box::pin(async move {
+        let status = crate::check_docker_status(app).await?;
+        if status.is_running() {
+            Ok(())
+        } else {
+            Err(format!("Docker is {}", status.state))
+        }
+    })
+}
+
+fn init_supabase(app: AppHandle) -> NodeFuture {
+    Box::pin(async move {
+        let status = crate::check_supabase_status(app).await?;
+        if status.is_running() {
+            Ok(())
+        } else {
+            Err(format!("Supabase is {}", status.state))
+        }
+    })
+}
+
+fn init_supabase_services(app: AppHandle) -> NodeFuture {
+    Box::pin(async move {
+        let health = crate::health::check_supabase_services(app).await?;
+        if health.all_up {
+            Ok(())
+        } else {
+            Err("Some Supabase subservices are down".to_string())
+        }
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    TimedOut,
+    Skipped,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatus {
+    pub name: String,
+    pub deps: Vec<String>,
+    pub state: NodeState,
+    pub error: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct InitStatus {
+    nodes: Mutex<HashMap<&'static str, NodeStatus>>,
+    /// Background tasks of the subsystem nodes, stopped when a node is restarted
+    tasks: Mutex<HashMap<&'static str, JoinHandle<()>>>,
+}
+
+impl InitStatus {
+    fn set(
+        &self,
+        app: &AppHandle,
+        name: &'static str,
+        state: NodeState,
+        error: Option<String>,
+        duration_ms: Option<u64>,
+    ) {
+        let status = {
+            let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(node) = nodes.get_mut(name) else {
+                return;
+            };
+            node.state = state;
+            node.error = error;
+            node.duration_ms = duration_ms;
+            node.clone()
+        };
+        crate::events::publish(app, "init://status", &status);
+    }
+
+    fn state_of(&self, name: &str) -> NodeState {
+        let nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        nodes
+            .get(name)
+            .map(|n| n.state)
+            .unwrap_or(NodeState::Pending)
+    }
+}
+
+/// Run a single node with its timeout and record the outcome
+async fn run_node(app: AppHandle, node: &'static InitNode) -> Result<(), String> {
+    let status = app.state::<InitStatus>();
+    status.set(&app, node.name, NodeState::Running, None, None);
+
+    let started = std::time::Instant::now();
+    let work = match node.run {
+        Run::Probe(probe) => probe(app.clone()),
+        Run::Task(start) => start_task(app.clone(), node.name, start),
+    };
+    let result = tokio::time::timeout(node.timeout, work).await;
+    let elapsed = Some(started.elapsed().as_millis() as u64);
+
+    match result {
+        Ok(Ok(())) => {
+            status.set(&app, node.name, NodeState::Done, None, elapsed);
+            Ok(())
+        }
+        Ok(Err(e)) => {
+            log::warn!("Init node '{}' failed: {}", node.name, e);
+            status.set(&app, node.name, NodeState::Failed, Some(e.clone()), elapsed);
+            Err(e)
+        }
+        Err(_) => {
+            let e = format!("timed out after {}s", node.timeout.as_secs());
+            log::warn!("Init node '{}' {}", node.name, e);
+            status.set(
+                &app,
+                node.name,
+                NodeState::TimedOut,
+                Some(e.clone()),
+                elapsed,
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Execute the startup graph with maximal parallelism
+pub async fn run_startup_graph(app: AppHandle) {
+    let names: Vec<&'static str> = NODES.iter().map(|n| n.name).collect();
+    run_graph(&app, &names).await;
+    log::info!("Startup graph finished");
+}
+
+/// A node and every node that transitively depends on it, in graph order
+fn with_dependents(name: &str) -> Vec<&'static str> {
+    let mut selected: Vec<&'static str> = Vec::new();
+    for node in NODES {
+        if node.name == name || node.deps.iter().any(|d| selected.contains(d)) {
+            selected.push(node.name);
+        }
+    }
+    selected
+}
+
+/// Run the given subset of nodes; dependencies outside the subset must already be done
+async fn run_graph(app: &AppHandle, names: &[&'static str]) {
+    let status = app.state::<InitStatus>();
+    {
+        let mut nodes = status.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        for node in NODES.iter().filter(|n| names.contains(&n.name)) {
+            nodes.insert(
+                node.name,
+                NodeStatus {
+                    name: node.name.to_string(),
+                    deps: node.deps.iter().map(|d| d.to_string()).collect(),
+                    state: NodeState::Pending,
+                    error: None,
+                    duration_ms: None,
+                },
+            );
+        }
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<&'static str>();
+    let mut running = 0usize;
+
+    loop {
+        for node in NODES.iter().filter(|n| names.contains(&n.name)) {
+            if status.state_of(node.name) != NodeState::Pending {
+                continue;
+            }
+
+            let dep_states: Vec<NodeState> = node.deps.iter().map(|d| status.state_of(d)).collect();
+            let blocked = dep_states.iter().any(|s| {
+                matches!(
+                    s,
+                    NodeState::Failed | NodeState::TimedOut | NodeState::Skipped
+                )
+            });
+            if blocked {
+                let reason = Some("dependency not ready".to_string());
+                status.set(app, node.name, NodeState::Skipped, reason, None);
+                continue;
+            }
+            if dep_states.iter().all(|s| *s == NodeState::Done) {
+                running += 1;
+                // Mark running before spawning so the next scan doesn't start it twice
+                status.set(app, node.name, NodeState::Running, None, None);
+                let app = app.clone();
+                let tx = tx.clone();
+                tauri::async_runtime::spawn(async move {
+                    let _ = run_node(app, node).await;
+                    let _ = tx.send(node.name);
+                });
+            }
+        }
+
+        if running == 0 {
+            break;
+        }
+
+        // Wait for any node to finish, then rescan for newly unblocked nodes
+        if rx.recv().await.is_none() {
+            break;
+        }
+        running -= 1;
+    }
+}
+
+/// Report the state of every startup node
+#[tauri::command]
+pub fn get_init_status(status: tauri::State<'_, InitStatus>) -> Vec<NodeStatus> {
+    let nodes = status.nodes.lock().unwrap_or_else(|e| e.into_inner());
+    NODES
+        .iter()
+        .filter_map(|node| nodes.get(node.name).cloned())
+        .collect()
+}
+
+/// Re-run a subsystem (a probe, or a background task, which is stopped and
+/// started again) and everything that depends on it without restarting the
+/// app. Only available in debug builds, for iterating on backend subsystems.
+#[tauri::command]
+pub async fn restart_subsystem(
+    app: AppHandle,
+    name: String,
+) -> Result<Vec<NodeStatus>, FlowStateError> {
+    if !cfg!(debug_assertions) {
+        return Err("restart_subsystem is only available in debug builds".into());
+    }
+
+    let names = with_dependents(&name);
+    if names.is_empty() {
+        let known: Vec<&str> = NODES.iter().map(|n| n.name).collect();
+        return Err(format!("Unknown subsystem '{}' (known: {})", name, known.join(", ")).into());
+    }
+
+    let status = app.state::<InitStatus>();
+    if names
+        .iter()
+        .any(|n| status.state_of(n) == NodeState::Running)
+    {
+        return Err(format!("Subsystem '{}' is still initializing", name).into());
+    }
+
+    log::info!("Restarting subsystems: {}", names.join(", "));
+    run_graph(&app, &names).await;
+
+    let nodes = status.nodes.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(names.iter().filter_map(|n| nodes.get(n).cloned()).collect())
+}
@@ -0,0 +1,475 @@
+//! Services left behind by an instance that didn't exit cleanly.
+//!
+//! Each instance keeps `instance.lock` in the app data directory with its PID
+//! and the services it started (the Supabase stack, the container runtime);
+//! the file is removed on a clean exit. If it is still there at startup and
+//! its process is gone (or the PID now belongs to a newer process), the last
+//! instance was killed before `cleanup_services` could run: what it started
+//! is reported as `instance://stale` and by `get_stale_instance`, and
+//! `cleanup_stale_services` stops the orphaned Supabase containers. The
+//! container runtime is only reported; other apps may be using it.
+//!
+//! The stale lock is moved to `instance.stale.lock` (merged with one an
+//! earlier crash left there) and only removed once cleanup succeeds, so a
+//! second crash before then doesn't lose it. Using a stack found already
+//! running takes it over from the stale lock, and it is never stopped as
+//! an orphan while this instance relies on it.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+
+const LOCK_FILE: &str = "instance.lock";
+/// Lock of killed instances whose services haven't been cleaned up
+const STALE_FILE: &str = "instance.stale.lock";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartedService {
+    /// The local Supabase stack (`supabase start`)
+    Supabase,
+    /// Docker Desktop or another container runtime
+    ContainerRuntime,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceLock {
+    pub pid: u32,
+    pub started_at_ms: u64,
+    /// Project id of the Supabase stack
+    pub project_id: String,
+    pub services: Vec<StartedService>,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleCleanup {
+    pub stopped: Vec<StartedService>,
+    /// Left running (the container runtime), or failed to stop
+    pub kept: Vec<StartedService>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct InstanceLocks {
+    /// This instance's lock, as last written
+    own: Mutex<Option<InstanceLock>>,
+    /// Lock of a killed instance that still lists running services
+    stale: Mutex<Option<InstanceLock>>,
+    /// Services this instance found running and relies on without having
+    /// started them
+    in_use: Mutex<Vec<StartedService>>,
+}
+
+fn data_path(app: &AppHandle, file: &str) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(file))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+fn lock_path(app: &AppHandle) -> Result<PathBuf, String> {
+    data_path(app, LOCK_FILE)
+}
+
+fn read(path: &Path) -> Option<InstanceLock> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Write `lock` through a temporary file, so a kill mid-write leaves the
+/// previous lock rather than a truncated one
+fn write(app: &AppHandle, lock: &InstanceLock) -> Result<(), String> {
+    write_to(&lock_path(app)?, lock)
+}
+
+fn write_to(path: &Path, lock: &InstanceLock) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string(lock)
+        .map_err(|e| format!("Failed to serialize instance lock: {}", e))?;
+    let staged = path.with_extension("lock.tmp");
+    std::fs::write(&staged, json)
+        .and_then(|_| std::fs::rename(&staged, path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Whether the process now holding the lock's PID, started at
+/// `started_secs` (None when there is none), is the one that wrote it; a
+/// process started after the lock was written reuses the PID
+fn is_holder(lock: &InstanceLock, started_secs: Option<u64>) -> bool {
+    started_secs.is_some_and(|secs| secs.saturating_mul(1000) <= lock.started_at_ms + 1000)
+}
+
+/// The process that wrote `lock` is still running
+fn owner_alive(lock: &InstanceLock) -> bool {
+    let pid = Pid::from_u32(lock.pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    is_holder(lock, system.process(pid).map(|p| p.start_time()))
+}
+
+/// Services left behind at startup: `previous` is the lock file found (by
+/// `pid` itself after a restart, or by another instance), `older` the stale
+/// lock an earlier crash left. A previous holder that is gone and listed
+/// services is merged into the stale lock; the flag says it changed and
+/// must be saved.
+fn find_stale(
+    previous: Option<InstanceLock>,
+    older: Option<InstanceLock>,
+    pid: u32,
+    alive: impl Fn(&InstanceLock) -> bool,
+) -> (Option<InstanceLock>, bool) {
+    let older = older.filter(|lock| !lock.services.is_empty());
+    let Some(previous) = previous.filter(|lock| lock.pid != pid && !alive(lock)) else {
+        return (older, false);
+    };
+    if previous.services.is_empty() {
+        log::info!(
+            "Previous instance (pid {}) did not exit cleanly",
+            previous.pid
+        );
+        return (older, false);
+    }
+    log::warn!(
+        "Previous instance (pid {}) did not exit cleanly and left {:?} running",
+        previous.pid,
+        previous.services
+    );
+    let mut merged = previous;
+    for service in older.into_iter().flat_map(|older| older.services) {
+        if !merged.services.contains(&service) {
+            merged.services.push(service);
+        }
+    }
+    (Some(merged), true)
+}
+
+/// Take `service` off the stale lock; false when it isn't listed there.
+/// The lock goes once it lists nothing.
+fn take_over(stale: &mut Option<InstanceLock>, service: StartedService) -> bool {
+    let Some(lock) = stale
+        .as_mut()
+        .filter(|lock| lock.services.contains(&service))
+    else {
+        return false;
+    };
+    lock.services.retain(|s| *s != service);
+    if lock.services.is_empty() {
+        *stale = None;
+    }
+    true
+}
+
+/// Save the stale lock, or remove it once nothing is left to clean up
+fn write_stale(app: &AppHandle, stale: Option<&InstanceLock>) -> Result<(), String> {
+    let path = data_path(app, STALE_FILE)?;
+    match stale {
+        Some(lock) if !lock.services.is_empty() => write_to(&path, lock),
+        _ => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Pick up a stale lock, then take the lock for this instance
+pub fn init(app: &AppHandle) {
+    let paths = lock_path(app).and_then(|path| Ok((path, data_path(app, STALE_FILE)?)));
+    let (path, stale_path) = match paths {
+        Ok(paths) => paths,
+        Err(e) => {
+            log::warn!("{}", e);
+            return;
+        }
+    };
+
+    // Left by an instance killed before its services were cleaned up
+    let (stale, changed) = find_stale(
+        read(&path),
+        read(&stale_path),
+        std::process::id(),
+        owner_alive,
+    );
+    if changed {
+        // Keep it on disk until cleanup succeeds; the lock file is reused below
+        if let Err(e) = write_stale(app, stale.as_ref()) {
+            log::warn!("{}", e);
+        }
+    }
+    if let Some(stale) = stale {
+        crate::events::publish(app, "instance://stale", &stale);
+        *app.state::<InstanceLocks>()
+            .stale
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(stale);
+    }
+
+    let lock = InstanceLock {
+        pid: std::process::id(),
+        started_at_ms: crate::events::now_ms(),
+        project_id: crate::multi_user::project_id(),
+        services: Vec::new(),
+    };
+    if let Err(e) = write(app, &lock) {
+        log::warn!("{}", e);
+    }
+    *app.state::<InstanceLocks>()
+        .own
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(lock);
+}
+
+/// Update this instance's services in the lock file
+fn update(app: &AppHandle, change: impl FnOnce(&mut Vec<StartedService>)) {
+    let locks = app.state::<InstanceLocks>();
+    let mut own = locks.own.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(lock) = own.as_mut() else {
+        return;
+    };
+    let before = lock.services.clone();
+    change(&mut lock.services);
+    if lock.services != before {
+        if let Err(e) = write(app, lock) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// Record that this instance started `service`
+pub fn record_started(app: &AppHandle, service: StartedService) {
+    update(app, |services| {
+        if !services.contains(&service) {
+            services.push(service);
+        }
+    });
+}
+
+/// Record that this instance uses `service`, found already running; if a
+/// killed instance left it running, it is this instance's to stop now
+pub fn record_adopted(app: &AppHandle, service: StartedService) {
+    let locks = app.state::<InstanceLocks>();
+    let adopted = {
+        let mut stale = locks.stale.lock().unwrap_or_else(|e| e.into_inner());
+        let adopted = take_over(&mut stale, service);
+        if adopted {
+            if let Err(e) = write_stale(app, stale.as_ref()) {
+                log::warn!("{}", e);
+            }
+        }
+        adopted
+    };
+    if adopted {
+        log::info!(
+            "Took over {:?} left running by a previous instance",
+            service
+        );
+        record_started(app, service);
+    }
+    let mut in_use = locks.in_use.lock().unwrap_or_else(|e| e.into_inner());
+    if !in_use.contains(&service) {
+        in_use.push(service);
+    }
+}
+
+/// Record that `service` was stopped on purpose
+pub fn record_stopped(app: &AppHandle, service: StartedService) {
+    update(app, |services| services.retain(|s| *s != service));
+}
+
+/// Remove the lock on a clean exit
+pub fn release(app: &AppHandle) {
+    let own = app
+        .state::<InstanceLocks>()
+        .own
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if own.is_none() {
+        return;
+    }
+    if let Ok(path) = lock_path(app) {
+        if read(&path).is_some_and(|lock| lock.pid == std::process::id()) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// What a killed instance left running, if anything
+#[tauri::command]
+pub fn get_stale_instance(app: AppHandle) -> Option<InstanceLock> {
+    app.state::<InstanceLocks>()
+        .stale
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Stop the Supabase containers a killed instance left running
+#[tauri::command]
+pub async fn cleanup_stale_services(app: AppHandle) -> Result<StaleCleanup, FlowStateError> {
+    crate::trace::scope("cleanup_stale_services", async move {
+        crate::read_only::ensure_writable(&app, "cleanup_stale_services")?;
+        let Some(stale) = get_stale_instance(app.clone()) else {
+            return Ok(StaleCleanup::default());
+        };
+        let locks = app.state::<InstanceLocks>();
+        let mut ours = locks
+            .own
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|lock| lock.services.clone())
+            .unwrap_or_default();
+        ours.extend(
+            locks
+                .in_use
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter(),
+        );
+
+        let mut cleanup = StaleCleanup::default();
+        for service in stale.services {
+            match service {
+                // This instance started or is using the stack since; it's no longer orphaned
+                StartedService::Supabase if ours.contains(&service) => cleanup.kept.push(service),
+                StartedService::Supabase => {
+                    crate::supervisor::expect_stop(&app);
+                    let output = crate::trace::command(&app, "supabase")
+                        .args(["stop", "--project-id", stale.project_id.as_str()])
+                        .output()
+                        .await;
+                    match output {
+                        Ok(o) if o.status.success() => {
+                            crate::multi_user::release_stack();
+                            crate::events::publish(
+                                &app,
+                                "service://lifecycle",
+                                &serde_json::json!({ "service": "supabase", "state": "stopped" }),
+                            );
+                            cleanup.stopped.push(service);
+                        }
+                        Ok(o) => {
+                            cleanup.errors.push(format!(
+                                "Failed to stop Supabase: {}",
+                                String::from_utf8_lossy(&o.stderr).trim()
+                            ));
+                            cleanup.kept.push(service);
+                        }
+                        Err(e) => {
+                            cleanup
+                                .errors
+                                .push(format!("Failed to run supabase: {}", e));
+                            cleanup.kept.push(service);
+                        }
+                    }
+                }
+                StartedService::ContainerRuntime => cleanup.kept.push(service),
+            }
+        }
+
+        if cleanup.errors.is_empty() {
+            write_stale(&app, None)?;
+            *locks.stale.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            log::info!(
+                "Stopped {:?} left running by pid {}",
+                cleanup.stopped,
+                stale.pid
+            );
+        }
+        Ok(cleanup)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(pid: u32, services: &[StartedService]) -> InstanceLock {
+        InstanceLock {
+            pid,
+            started_at_ms: 1_700_000_000_000,
+            project_id: "flowstate".to_string(),
+            services: services.to_vec(),
+        }
+    }
+
+    #[test]
+    fn a_pid_reused_after_the_lock_is_not_the_holder() {
+        let held = lock(42, &[]);
+        // Started before the lock was written (start times are in seconds)
+        assert!(is_holder(&held, Some(1_699_999_000)));
+        assert!(is_holder(&held, Some(1_700_000_000)));
+        assert!(is_holder(&held, Some(1_700_000_001)));
+        // Started later: another process got the PID
+        assert!(!is_holder(&held, Some(1_700_000_002)));
+        assert!(!is_holder(&held, None));
+    }
+
+    #[test]
+    fn a_dead_holder_leaves_its_services_stale() {
+        use StartedService::*;
+        let dead = |_: &InstanceLock| false;
+        let alive = |_: &InstanceLock| true;
+
+        let (stale, changed) = find_stale(Some(lock(42, &[Supabase])), None, 7, dead);
+        assert!(changed);
+        assert_eq!(
+            stale.map(|lock| (lock.pid, lock.services)),
+            Some((42, vec![Supabase]))
+        );
+
+        // Merged with what an earlier crash left
+        let (stale, changed) = find_stale(
+            Some(lock(42, &[Supabase])),
+            Some(lock(41, &[ContainerRuntime, Supabase])),
+            7,
+            dead,
+        );
+        assert!(changed);
+        assert_eq!(stale.unwrap().services, [Supabase, ContainerRuntime]);
+
+        // Still running, or this very process after an in-place restart
+        let older = || Some(lock(41, &[ContainerRuntime]));
+        for (pid, holder_alive) in [(7, true), (42, false)] {
+            let holder = if holder_alive { alive } else { dead };
+            let (stale, changed) = find_stale(Some(lock(42, &[Supabase])), older(), pid, holder);
+            assert!(!changed);
+            assert_eq!(stale.unwrap().services, [ContainerRuntime]);
+        }
+
+        // A clean crash (nothing started) and an empty stale lock leave nothing
+        let (stale, changed) = find_stale(Some(lock(42, &[])), Some(lock(41, &[])), 7, dead);
+        assert!(!changed);
+        assert!(stale.is_none());
+    }
+
+    #[test]
+    fn services_of_a_dead_holder_can_be_taken_over() {
+        use StartedService::*;
+        let mut stale = Some(lock(42, &[Supabase, ContainerRuntime]));
+        assert!(take_over(&mut stale, Supabase));
+        assert_eq!(stale.as_ref().unwrap().services, [ContainerRuntime]);
+        // Not listed (any more): nothing to take over
+        assert!(!take_over(&mut stale, Supabase));
+        assert!(take_over(&mut stale, ContainerRuntime));
+        assert!(stale.is_none());
+        assert!(!take_over(&mut stale, ContainerRuntime));
+    }
+}
@@ -0,0 +1,202 @@
+//! Routing of launch arguments and `flowstate://` links.
+//!
+//! A second launch hands its argv/cwd to the running instance. Instead of just
+//! focusing the main window, the arguments are parsed into a `LaunchAction` and
+//! routed to the right window (quick capture, a specific task, or a file handed
+//! to the main window).
+//!
+//! The `flowstate` URL scheme is registered through the deep-link plugin. On
+//! Windows and Linux a link opened while the app runs arrives as an argument
+//! of a second launch; the link that started the app, and every link on macOS,
+//! come from the plugin. `flowstate://auth/callback?...` completes a pending
+//! SSO sign-in, or is handed to the main window to finish a Supabase OAuth
+//! flow. Actions for the main window are sent as `app://launch-action` with
+//! replay, so a window that is still loading picks them up.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+pub const QUICK_CAPTURE_LABEL: &str = "quick-capture";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum LaunchAction {
+    /// No actionable argument: just bring the main window forward
+    Focus,
+    QuickCapture,
+    OpenTask {
+        task_id: String,
+    },
+    OpenFile {
+        path: PathBuf,
+    },
+    /// OAuth redirect, with the full `flowstate://auth/callback?...` URL
+    AuthCallback {
+        url: String,
+    },
+}
+
+/// Parse the arguments of a second launch (argv[0] is the executable)
+pub fn parse_launch_args(args: &[String], cwd: &str) -> LaunchAction {
+    for arg in args.iter().skip(1) {
+        if arg.starts_with("flowstate://") {
+            return parse_url(arg);
+        }
+
+        if arg == "--quick-capture" {
+            return LaunchAction::QuickCapture;
+        }
+
+        if arg.starts_with('-') {
+            continue;
+        }
+
+        let path = Path::new(cwd).join(arg);
+        if path.is_file() {
+            return LaunchAction::OpenFile { path };
+        }
+    }
+
+    LaunchAction::Focus
+}
+
+/// Parse a `flowstate://` link
+pub fn parse_url(url: &str) -> LaunchAction {
+    let Some(rest) = url.strip_prefix("flowstate://") else {
+        return LaunchAction::Focus;
+    };
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let mut parts = path.trim_matches('/').split('/');
+
+    match (parts.next(), parts.next()) {
+        (Some("quick-capture"), _) => LaunchAction::QuickCapture,
+        (Some("auth"), Some("callback")) => LaunchAction::AuthCallback {
+            url: url.to_string(),
+        },
+        (Some("task"), Some(id)) if !id.is_empty() => LaunchAction::OpenTask {
+            task_id: id.to_string(),
+        },
+        _ => LaunchAction::Focus,
+    }
+}
+
+/// Carry out a launch action in the running instance
+pub fn route_launch_action(app: &AppHandle, action: LaunchAction) {
+    log::info!("Routing launch action: {:?}", action);
+
+    let result = match &action {
+        LaunchAction::Focus => focus_main_window(app),
+        LaunchAction::QuickCapture => open_quick_capture(app),
+        LaunchAction::OpenTask { task_id } => open_task_window(app, task_id),
+        LaunchAction::OpenFile { .. } => focus_main_window(app).map(|_| send_to_main(app, &action)),
+        LaunchAction::AuthCallback { url } => focus_main_window(app).map(|_| {
+            if !crate::sso::complete_redirect(app, url) {
+                send_to_main(app, &action);
+            }
+        }),
+    };
+
+    if let Err(e) = result {
+        log::error!("Failed to route launch action {:?}: {}", action, e);
+    }
+}
+
+fn send_to_main(app: &AppHandle, action: &LaunchAction) {
+    crate::events::publish_to(app, &["main".to_string()], "app://launch-action", action);
+}
+
+/// Register the URL scheme where that happens at runtime and route the link
+/// the app was started with
+pub fn init(app: &AppHandle) {
+    // Installers register it on Windows; AppImages and dev builds do it here
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    {
+        if let Err(e) = app.deep_link().register_all() {
+            log::warn!("Failed to register the flowstate:// scheme: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let handle = app.clone();
+        app.deep_link().on_open_url(move |event| {
+            for url in event.urls() {
+                route_launch_action(&handle, parse_url(url.as_str()));
+            }
+        });
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        match app.deep_link().get_current() {
+            Ok(Some(urls)) => {
+                for url in urls {
+                    route_launch_action(app, parse_url(url.as_str()));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to read the launch link: {}", e),
+        }
+    }
+}
+
+pub(crate) fn focus_main_window(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window("main") {
+        window.unminimize()?;
+        window.show()?;
+        window.set_focus()?;
+        crate::autostart::on_main_window_shown(app);
+    }
+    Ok(())
+}
+
+/// Show the small always-on-top quick capture window, creating it if needed
+pub fn open_quick_capture(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+        window.show()?;
+        return window.set_focus();
+    }
+
+    WebviewWindowBuilder::new(
+        app,
+        QUICK_CAPTURE_LABEL,
+        WebviewUrl::App("index.html#/quick-capture".into()),
+    )
+    .title("Quick Capture")
+    .inner_size(520.0, 180.0)
+    .resizable(false)
+    .always_on_top(true)
+    .center()
+    .build()?;
+
+    Ok(())
+}
+
+/// Open (or focus) a dedicated window for a single task
+fn open_task_window(app: &AppHandle, task_id: &str) -> tauri::Result<()> {
+    // Window labels only allow alphanumerics and -/:_
+    let safe_id: String = task_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let label = format!("task-{}", safe_id);
+
+    if let Some(window) = app.get_webview_window(&label) {
+        window.show()?;
+        return window.set_focus();
+    }
+
+    WebviewWindowBuilder::new(
+        app,
+        &label,
+        WebviewUrl::App(format!("index.html#/focus/{}", safe_id).into()),
+    )
+    .title("FlowState")
+    .inner_size(800.0, 600.0)
+    .build()?;
+
+    Ok(())
+}
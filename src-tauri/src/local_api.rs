@@ -0,0 +1,639 @@
+//! Optional local HTTP server for clients that can't use the IPC or the
+//! editor socket.
+//!
+//! Off by default. Once enabled it listens on `127.0.0.1:<port>` only and
+//! serves the CalDAV task collection under `/caldav/` (`caldav.rs`),
+//! Prometheus metrics at `/metrics` (`prometheus.rs`), GraphQL at
+//! `/graphql` (`graphql.rs`) and inbound webhooks under `/webhooks/`
+//! (`webhooks.rs`); the gRPC service (`grpc.rs`) runs next to it on its
+//! own port with the same token. Every request needs the server's token,
+//! either as `Authorization: Bearer <token>` or as the password of HTTP
+//! Basic auth (any user name), since any local process can reach a TCP
+//! port. Webhooks take their own tokens instead. The token is generated on
+//! first enable and kept in `local-api.json`; `reset_local_api_token`
+//! replaces it.
+//!
+//! The server speaks a small subset of HTTP/1.1: one request per
+//! connection, bodies with `Content-Length` only, up to `MAX_BODY_BYTES`.
+//! Event streams (GraphQL subscriptions) are sent until the connection
+//! closes, and end when the server is turned off.
+//! While the app is locked every request gets `423 Locked`.
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::error::FlowStateError;
+
+pub(crate) const LOCAL_API_STORE: &str = "local-api.json";
+const ENABLED_KEY: &str = "enabled";
+const PORT_KEY: &str = "port";
+const TOKEN_KEY: &str = "token";
+const GRPC_PORT_KEY: &str = "grpc_port";
+const DEFAULT_PORT: u16 = 47315;
+const DEFAULT_GRPC_PORT: u16 = 47316;
+/// How often the listener checks that it is still enabled on the same port
+pub(crate) const ACCEPT_POLL: Duration = Duration::from_secs(1);
+pub(crate) const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// A client that doesn't finish its request in time is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_HEADER_LINE: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalApiState {
+    pub enabled: bool,
+    pub port: u16,
+    /// Base URL while listening
+    pub url: Option<String>,
+    pub grpc_port: u16,
+    /// gRPC endpoint while listening
+    pub grpc_url: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Default)]
+pub struct LocalApi {
+    url: Mutex<Option<String>>,
+    grpc_url: Mutex<Option<String>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub method: String,
+    /// Percent-decoded path without the query
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Body sent piece by piece, without a length
+pub(crate) struct BodyStream(Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>);
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Sent after `body` until it ends
+    pub stream: Option<BodyStream>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            stream: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn body(self, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        let mut response = self.header("Content-Type", content_type);
+        response.body = body.into();
+        response
+    }
+
+    pub fn stream(
+        self,
+        content_type: &str,
+        body: impl Stream<Item = Vec<u8>> + Send + 'static,
+    ) -> Self {
+        let mut response = self.header("Content-Type", content_type);
+        response.stream = Some(BodyStream(Box::pin(body)));
+        response
+    }
+
+    pub fn text(status: u16, message: &str) -> Self {
+        Response::new(status).body("text/plain; charset=utf-8", format!("{}\n", message))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        207 => "Multi-Status",
+        301 => "Moved Permanently",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Content",
+        423 => "Locked",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+fn setting(app: &AppHandle, key: &str) -> Option<Value> {
+    app.store(LOCAL_API_STORE)
+        .ok()
+        .and_then(|store| store.get(key))
+}
+
+pub(crate) fn enabled(app: &AppHandle) -> bool {
+    setting(app, ENABLED_KEY)
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
+
+fn port_setting(app: &AppHandle, key: &str, default: u16) -> u16 {
+    setting(app, key)
+        .and_then(|value| value.as_u64())
+        .and_then(|port| u16::try_from(port).ok())
+        .filter(|port| *port != 0)
+        .unwrap_or(default)
+}
+
+fn port(app: &AppHandle) -> u16 {
+    port_setting(app, PORT_KEY, DEFAULT_PORT)
+}
+
+pub(crate) fn grpc_port(app: &AppHandle) -> u16 {
+    port_setting(app, GRPC_PORT_KEY, DEFAULT_GRPC_PORT)
+}
+
+pub(crate) fn token(app: &AppHandle) -> Option<String> {
+    setting(app, TOKEN_KEY).and_then(|value| value.as_str().map(str::to_string))
+}
+
+pub(crate) fn new_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn decode_percent(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Request line and headers, then the body; None when the client closed the
+/// connection without sending anything
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<Request>, Response> {
+    let bad = |message: &str| Response::text(400, message);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = (&mut *reader)
+            .take(MAX_HEADER_LINE)
+            .read_line(&mut line)
+            .await
+            .map_err(|_| bad("Unreadable request"))?;
+        if read == 0 {
+            return if lines.is_empty() {
+                Ok(None)
+            } else {
+                Err(bad("Request ended in the headers"))
+            };
+        }
+        if !line.ends_with('\n') {
+            return Err(bad("Header line too long"));
+        }
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            if lines.is_empty() {
+                // Stray newline before the request line
+                continue;
+            }
+            break;
+        }
+        if lines.len() > MAX_HEADERS {
+            return Err(bad("Too many headers"));
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines[0].split(' ');
+    let (Some(method), Some(target), Some(version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return Err(bad("Malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(Response::text(501, "Only HTTP/1.x is supported"));
+    }
+    let headers: Vec<(String, String)> = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut request = Request {
+        method: method.to_ascii_uppercase(),
+        path: decode_percent(target.split('?').next().unwrap_or(target)),
+        headers,
+        body: Vec::new(),
+    };
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(Response::text(501, "Send the body with a Content-Length"));
+    }
+    let length = match request.header("Content-Length") {
+        Some(length) => length
+            .parse::<usize>()
+            .map_err(|_| bad("Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(Response::text(413, "Request body too large"));
+    }
+    request.body = vec![0; length];
+    reader
+        .read_exact(&mut request.body)
+        .await
+        .map_err(|_| bad("Request ended in the body"))?;
+    Ok(Some(request))
+}
+
+fn digest(text: &str) -> Vec<u8> {
+    Sha256::digest(text.as_bytes()).to_vec()
+}
+
+/// Whether `given` is `token`; compared as digests so the time taken says
+/// nothing about the token
+pub(crate) fn token_matches(given: &str, token: &str) -> bool {
+    digest(given) == digest(token)
+}
+
+/// Whether the request carries `token`, as a bearer token or Basic password
+pub(crate) fn authorized(request: &Request, token: &str) -> bool {
+    use base64::Engine;
+
+    let Some(header) = request.header("Authorization") else {
+        return false;
+    };
+    let (scheme, credentials) = header.split_once(' ').unwrap_or((header, ""));
+    let given = if scheme.eq_ignore_ascii_case("bearer") {
+        Some(credentials.trim().to_string())
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        base64::engine::general_purpose::STANDARD
+            .decode(credentials.trim())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|pair| {
+                pair.split_once(':')
+                    .map(|(_, password)| password.to_string())
+            })
+    } else {
+        None
+    };
+    given.is_some_and(|given| token_matches(&given, token))
+}
+
+async fn route(app: &AppHandle, request: &Request) -> Response {
+    // Webhooks carry their own tokens
+    if let Some(id) = crate::webhooks::id(&request.path) {
+        return crate::webhooks::handle(app, id, request);
+    }
+    let Some(token) = token(app) else {
+        return Response::text(503, "The local API has no token yet");
+    };
+    if !authorized(request, &token) {
+        return Response::text(401, "Missing or wrong token").header(
+            "WWW-Authenticate",
+            "Basic realm=\"FlowState\", charset=\"UTF-8\"",
+        );
+    }
+    if app.state::<crate::app_lock::AppLock>().is_locked() {
+        return Response::text(423, "FlowState is locked");
+    }
+    if request.path == "/metrics" {
+        return crate::prometheus::handle(app, request);
+    }
+    if request.path == "/graphql" {
+        return crate::graphql::handle(app, request).await;
+    }
+    if crate::caldav::handles(&request.path) {
+        return crate::caldav::handle(app, request);
+    }
+    Response::text(404, "Not found")
+}
+
+fn encode(response: &Response) -> Vec<u8> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    // A stream ends when the connection closes
+    if response.stream.is_none() {
+        head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    let mut bytes = head.into_bytes();
+    bytes.extend_from_slice(&response.body);
+    bytes
+}
+
+async fn serve_client(app: AppHandle, stream: tokio::net::TcpStream) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = tokio::io::BufReader::new(read);
+    let mut response = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut reader)).await
+    {
+        Ok(Ok(Some(request))) => {
+            let response = route(&app, &request).await;
+            log::debug!(
+                "Local API {} {} -> {}",
+                request.method,
+                request.path,
+                response.status
+            );
+            response
+        }
+        Ok(Ok(None)) | Err(_) => return,
+        Ok(Err(response)) => response,
+    };
+    if let Err(e) = write.write_all(&encode(&response)).await {
+        log::debug!("Local API connection closed: {}", e);
+        return;
+    }
+    if let Some(BodyStream(mut stream)) = response.stream.take() {
+        while let Some(chunk) = stream.next().await {
+            if !enabled(&app) {
+                break;
+            }
+            if let Err(e) = write.write_all(&chunk).await {
+                log::debug!("Local API stream closed: {}", e);
+                return;
+            }
+        }
+    }
+    let _ = write.shutdown().await;
+}
+
+fn set_url(app: &AppHandle, url: Option<String>) {
+    *app.state::<LocalApi>()
+        .url
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = url;
+}
+
+pub(crate) fn set_grpc_url(app: &AppHandle, url: Option<String>) {
+    *app.state::<LocalApi>()
+        .grpc_url
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = url;
+}
+
+async fn listen(app: &AppHandle) -> Result<(), String> {
+    let port = port(app);
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let url = format!("http://127.0.0.1:{}", port);
+    set_url(app, Some(url.clone()));
+    log::info!("Local API listening on {}", url);
+
+    while enabled(app) && crate::local_api::port(app) == port {
+        match tokio::time::timeout(ACCEPT_POLL, listener.accept()).await {
+            Ok(Ok((stream, _))) => {
+                tauri::async_runtime::spawn(serve_client(app.clone(), stream));
+            }
+            Ok(Err(e)) => log::warn!("Local API connection failed: {}", e),
+            Err(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Serve the local API while enabled (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if enabled(&app) {
+                let result = listen(&app).await;
+                set_url(&app, None);
+                if let Err(e) = result {
+                    log::warn!("Local API unavailable: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+            tokio::time::sleep(ACCEPT_POLL).await;
+        }
+    })
+}
+
+fn state(app: &AppHandle) -> LocalApiState {
+    let api = app.state::<LocalApi>();
+    let state = LocalApiState {
+        enabled: enabled(app),
+        port: port(app),
+        url: api.url.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        grpc_port: grpc_port(app),
+        grpc_url: api
+            .grpc_url
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        token: token(app),
+    };
+    state
+}
+
+fn save(app: &AppHandle, values: &[(&str, Value)]) -> Result<(), String> {
+    let store = app
+        .store(LOCAL_API_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", LOCAL_API_STORE, e))?;
+    for (key, value) in values {
+        store.set(*key, value.clone());
+    }
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", LOCAL_API_STORE, e))
+}
+
+/// Settings, address and token of the local API
+#[tauri::command]
+pub fn get_local_api(app: AppHandle) -> Result<LocalApiState, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_local_api")?;
+    Ok(state(&app))
+}
+
+/// Turn the local API on or off, optionally on other ports
+#[tauri::command]
+pub fn set_local_api(
+    app: AppHandle,
+    enabled: bool,
+    port: Option<u16>,
+    grpc_port: Option<u16>,
+) -> Result<LocalApiState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_local_api")?;
+    crate::app_lock::ensure_unlocked(&app, "set_local_api")?;
+    if [port, grpc_port]
+        .into_iter()
+        .flatten()
+        .any(|port| port < 1024)
+    {
+        return Err("Pick a port from 1024 up".to_string().into());
+    }
+    if port.unwrap_or_else(|| self::port(&app))
+        == grpc_port.unwrap_or_else(|| self::grpc_port(&app))
+    {
+        return Err("The HTTP and gRPC ports must differ".to_string().into());
+    }
+    let mut values = vec![(ENABLED_KEY, Value::Bool(enabled))];
+    if let Some(port) = port {
+        values.push((PORT_KEY, Value::from(port)));
+    }
+    if let Some(port) = grpc_port {
+        values.push((GRPC_PORT_KEY, Value::from(port)));
+    }
+    if enabled && token(&app).is_none() {
+        values.push((TOKEN_KEY, Value::String(new_token())));
+    }
+    save(&app, &values)?;
+    Ok(state(&app))
+}
+
+/// Replace the local API token; clients need the new one
+#[tauri::command]
+pub fn reset_local_api_token(app: AppHandle) -> Result<LocalApiState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "reset_local_api_token")?;
+    crate::app_lock::ensure_unlocked(&app, "reset_local_api_token")?;
+    save(&app, &[(TOKEN_KEY, Value::String(new_token()))])?;
+    Ok(state(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(raw: &str) -> Result<Option<Request>, Response> {
+        let mut reader = tokio::io::BufReader::new(raw.as_bytes());
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(read_request(&mut reader))
+    }
+
+    fn request(authorization: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            headers: vec![("authorization".to_string(), authorization.to_string())],
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reads_a_request_with_its_body() {
+        let request = read(
+            "PUT /caldav/tasks/a%20b.ics?x=1 HTTP/1.1\r\nHost: 127.0.0.1\r\n\
+             Content-Length: 5\r\nIf-Match: \"abc\"\r\n\r\nhello and more",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/caldav/tasks/a b.ics");
+        assert_eq!(request.header("if-match"), Some("\"abc\""));
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn rejects_what_it_does_not_speak() {
+        assert!(read("").unwrap().is_none());
+        let status = |raw: &str| read(raw).err().map(|r| r.status);
+        assert_eq!(status("GET /\r\n\r\n"), Some(400));
+        assert_eq!(status("GET / HTTP/2\r\n\r\n"), Some(501));
+        assert_eq!(
+            status("PUT / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Some(501)
+        );
+        assert_eq!(
+            status("PUT / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n"),
+            Some(413)
+        );
+        assert_eq!(
+            status("PUT / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"),
+            Some(400)
+        );
+    }
+
+    #[test]
+    fn accepts_the_token_as_bearer_or_basic_password() {
+        assert!(authorized(&request("Bearer s3cret"), "s3cret"));
+        // "anyone:s3cret"
+        assert!(authorized(&request("Basic YW55b25lOnMzY3JldA=="), "s3cret"));
+        assert!(!authorized(&request("Bearer wrong"), "s3cret"));
+        // "anyone:wrong"
+        assert!(!authorized(&request("Basic YW55b25lOndyb25n"), "s3cret"));
+        assert!(!authorized(&request("Digest s3cret"), "s3cret"));
+    }
+
+    #[test]
+    fn streams_go_out_without_a_length() {
+        let full = String::from_utf8(encode(&Response::text(200, "ok"))).unwrap();
+        assert!(full.contains("Content-Length: 3\r\n"));
+
+        let events = Response::new(200).stream("text/event-stream", tokio_stream::empty());
+        let head = String::from_utf8(encode(&events)).unwrap();
+        assert!(!head.contains("Content-Length"));
+        assert!(head.ends_with("Connection: close\r\n\r\n"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Live Supabase container logs for the webview.
+//!
+//! `stream_supabase_logs` follows `docker logs` for one service of the local
+//! stack and publishes each line on `logs://supabase`, tagged with a stream id,
+//! so the UI can show why `supabase start` failed. A final event with
+//! `ended: true` marks the end of a stream; `stop_log_stream` ends it early.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+
+use crate::error::FlowStateError;
+
+/// Services the Supabase CLI runs as `supabase_<service>_<project_id>`
+const SERVICES: &[&str] = &[
+    "analytics",
+    "auth",
+    "db",
+    "edge_runtime",
+    "imgproxy",
+    "inbucket",
+    "kong",
+    "meta",
+    "pooler",
+    "realtime",
+    "rest",
+    "storage",
+    "studio",
+    "vector",
+];
+
+/// Lines of history sent before following
+const TAIL_LINES: &str = "200";
+
+#[derive(Default)]
+pub struct LogStreams {
+    next_id: AtomicU64,
+    children: Mutex<HashMap<u64, CommandChild>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    pub stream_id: u64,
+    pub service: String,
+    /// stdout | stderr
+    pub source: &'static str,
+    pub line: String,
+    pub ended: bool,
+}
+
+/// Follow the logs of one Supabase service; returns the stream id
+#[tauri::command]
+pub async fn stream_supabase_logs(app: AppHandle, service: String) -> Result<u64, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "stream_supabase_logs")?;
+    if !SERVICES.contains(&service.as_str()) {
+        return Err(format!(
+            "Unknown Supabase service '{}' (expected one of: {})",
+            service,
+            SERVICES.join(", ")
+        )
+        .into());
+    }
+
+    let container = format!("supabase_{}_{}", service, crate::multi_user::project_id());
+    let (mut rx, child) = crate::trace::command(&app, "docker")
+        .args(["logs", "--follow", "--tail", TAIL_LINES, container.as_str()])
+        .spawn()
+        .map_err(|e| format!("Failed to run docker logs: {}", e))?;
+
+    let streams = app.state::<LogStreams>();
+    let stream_id = streams.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+    streams
+        .children
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(stream_id, child);
+    log::info!("Streaming logs of {} (stream {})", container, stream_id);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let line = |source: &'static str, bytes: &[u8], ended: bool| LogLine {
+            stream_id,
+            service: service.clone(),
+            source,
+            line: String::from_utf8_lossy(bytes).trim_end().to_string(),
+            ended,
+        };
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(bytes) => {
+                    crate::events::publish(&app, "logs://supabase", &line("stdout", &bytes, false))
+                }
+                CommandEvent::Stderr(bytes) => {
+                    crate::events::publish(&app, "logs://supabase", &line("stderr", &bytes, false))
+                }
+                CommandEvent::Error(e) => crate::events::publish(
+                    &app,
+                    "logs://supabase",
+                    &line("stderr", e.as_bytes(), false),
+                ),
+                CommandEvent::Terminated(_) => break,
+                _ => {}
+            }
+        }
+
+        app.state::<LogStreams>()
+            .children
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&stream_id);
+        crate::events::publish(&app, "logs://supabase", &line("stdout", b"", true));
+    });
+
+    Ok(stream_id)
+}
+
+/// Stop a log stream started with `stream_supabase_logs`
+#[tauri::command]
+pub fn stop_log_stream(
+    streams: tauri::State<'_, LogStreams>,
+    stream_id: u64,
+) -> Result<(), FlowStateError> {
+    let child = streams
+        .children
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&stream_id)
+        .ok_or_else(|| format!("No active log stream {}", stream_id))?;
+
+    child
+        .kill()
+        .map_err(|e| format!("Failed to stop log stream {}: {}", stream_id, e).into())
+}
@@ -0,0 +1,66 @@
+//! Resource usage of the app process, for SIGTERM / OOM debugging (TASK-1060).
+//!
+//! Memory and CPU come from sysinfo, which covers Linux, macOS and Windows.
+//! CPU usage is measured between two refreshes, so the first sample reports 0
+//! and later ones the average since the previous call. Open descriptors are
+//! counted per platform: /proc/self/fd on Linux, /dev/fd on macOS and the
+//! process handle count on Windows.
+
+use std::sync::Mutex;
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::status::MemoryUsage;
+
+/// Kept between calls so CPU usage has a previous sample to compare against
+static SYSTEM: Mutex<Option<System>> = Mutex::new(None);
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<u64> {
+    Some(std::fs::read_dir("/proc/self/fd").ok()?.count() as u64)
+}
+
+#[cfg(target_os = "macos")]
+fn open_fds() -> Option<u64> {
+    Some(std::fs::read_dir("/dev/fd").ok()?.count() as u64)
+}
+
+#[cfg(target_os = "windows")]
+fn open_fds() -> Option<u64> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+    let mut count = 0u32;
+    // SAFETY: the pseudo handle of the current process is always valid and
+    // `count` outlives the call
+    let ok = unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) };
+    (ok != 0).then_some(count as u64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn open_fds() -> Option<u64> {
+    None
+}
+
+/// Current resource usage of this process
+pub fn sample() -> MemoryUsage {
+    let pid = std::process::id();
+    let mut system = SYSTEM.lock().unwrap_or_else(|e| e.into_inner());
+    let system = system.get_or_insert_with(System::new);
+
+    let sys_pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[sys_pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+    let process = system.process(sys_pid);
+
+    MemoryUsage {
+        pid,
+        rss_bytes: process.map(|p| p.memory()).unwrap_or(0),
+        virtual_bytes: process.map(|p| p.virtual_memory()).unwrap_or(0),
+        cpu_percent: process.map(|p| p.cpu_usage()).unwrap_or(0.0),
+        open_fds: open_fds(),
+        platform: std::env::consts::OS.to_string(),
+    }
+}
@@ -0,0 +1,200 @@
+//! Database migrations for the local stack, driven by the Supabase CLI from the
+//! configured project directory.
+//!
+//! `list_migrations` compares supabase/migrations with the database history,
+//! and `apply_migrations` runs `supabase migration up --local`, publishing a
+//! `migrations://progress` event as each migration starts and completes.
+//! A dry run returns the pending migrations without touching the database.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::CommandEvent;
+
+use crate::error::FlowStateError;
+use crate::parsers;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Migration {
+    pub version: String,
+    /// File name without the version prefix, when the file is present locally
+    pub name: Option<String>,
+    pub local: bool,
+    pub applied: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationProgress {
+    pub version: String,
+    /// applying | applied | failed
+    pub status: &'static str,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyReport {
+    pub dry_run: bool,
+    pub pending: Vec<Migration>,
+    pub applied: Vec<String>,
+}
+
+fn project_dir() -> Result<PathBuf, String> {
+    crate::project_dir::get().ok_or_else(|| {
+        "Set the Supabase project directory first (set_supabase_project_dir)".to_string()
+    })
+}
+
+/// Migration name from supabase/migrations/<version>_<name>.sql
+fn migration_name(dir: &std::path::Path, version: &str) -> Option<String> {
+    let prefix = format!("{}_", version);
+    std::fs::read_dir(dir.join("supabase").join("migrations"))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find_map(|file| {
+            file.strip_prefix(&prefix)?
+                .strip_suffix(".sql")
+                .map(String::from)
+        })
+}
+
+async fn list(app: &AppHandle) -> Result<Vec<Migration>, String> {
+    let dir = project_dir()?;
+    let output = crate::trace::command(app, "supabase")
+        .args(["migration", "list", "--local"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run supabase: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("Failed to list migrations: {}", stderr));
+    }
+
+    let rows = parsers::parse_migration_list(&output.stdout)
+        .map_err(|e| format!("Unexpected 'supabase migration list' output: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Migration {
+            name: migration_name(&dir, &row.version),
+            version: row.version,
+            local: row.local,
+            applied: row.applied,
+        })
+        .collect())
+}
+
+/// "Applying migration 20240101000000_tasks.sql..." -> "20240101000000"
+fn applying_version(line: &str) -> Option<String> {
+    let file = line.trim().strip_prefix("Applying migration ")?;
+    let version: String = file.chars().take_while(char::is_ascii_digit).collect();
+    (!version.is_empty()).then_some(version)
+}
+
+fn progress(app: &AppHandle, version: &str, status: &'static str, detail: Option<String>) {
+    crate::events::publish(
+        app,
+        "migrations://progress",
+        &MigrationProgress {
+            version: version.to_string(),
+            status,
+            detail,
+        },
+    );
+}
+
+/// Apply pending migrations (or only report them when `dry_run`)
+pub async fn apply(app: &AppHandle, dry_run: bool) -> Result<ApplyReport, FlowStateError> {
+    let pending: Vec<Migration> = list(app)
+        .await?
+        .into_iter()
+        .filter(|m| m.local && !m.applied)
+        .collect();
+
+    if dry_run || pending.is_empty() {
+        return Ok(ApplyReport {
+            dry_run,
+            pending,
+            applied: Vec::new(),
+        });
+    }
+
+    crate::read_only::ensure_writable(app, "apply_migrations")?;
+
+    let (mut rx, _child) = crate::trace::command(app, "supabase")
+        .args(["migration", "up", "--local"])
+        .spawn()
+        .map_err(|e| format!("Failed to run supabase: {}", e))?;
+
+    let mut applied = Vec::new();
+    let mut current: Option<String> = None;
+    let mut stderr = String::new();
+    let mut success = false;
+
+    while let Some(event) = rx.recv().await {
+        let bytes = match event {
+            CommandEvent::Stdout(bytes) | CommandEvent::Stderr(bytes) => bytes,
+            CommandEvent::Terminated(payload) => {
+                success = payload.code == Some(0);
+                break;
+            }
+            _ => continue,
+        };
+        let Ok(line) = parsers::parse_cli_line(&bytes) else {
+            continue;
+        };
+        log::info!("supabase migration up: {}", line);
+
+        if let Some(version) = applying_version(&line) {
+            if let Some(done) = current.replace(version.clone()) {
+                progress(app, &done, "applied", None);
+                applied.push(done);
+            }
+            progress(app, &version, "applying", None);
+        } else {
+            stderr.push_str(&line);
+            stderr.push('\n');
+        }
+    }
+
+    match (current, success) {
+        (Some(done), true) => {
+            progress(app, &done, "applied", None);
+            applied.push(done);
+        }
+        (Some(failed), false) => progress(app, &failed, "failed", Some(stderr.trim().to_string())),
+        (None, _) => {}
+    }
+
+    if !success {
+        return Err(format!("Failed to apply migrations: {}", stderr.trim()).into());
+    }
+
+    Ok(ApplyReport {
+        dry_run,
+        pending,
+        applied,
+    })
+}
+
+/// Local migrations and whether each one is applied to the local database
+#[tauri::command]
+pub async fn list_migrations(app: AppHandle) -> Result<Vec<Migration>, FlowStateError> {
+    crate::trace::scope("list_migrations", async move { Ok(list(&app).await?) }).await
+}
+
+/// Apply pending migrations with `migrations://progress` events; `dry_run` only lists them
+#[tauri::command]
+pub async fn apply_migrations(
+    app: AppHandle,
+    dry_run: Option<bool>,
+) -> Result<ApplyReport, FlowStateError> {
+    crate::trace::scope("apply_migrations", async move {
+        apply(&app, dry_run.unwrap_or(false)).await
+    })
+    .await
+}
@@ -0,0 +1,452 @@
+//! Awareness of other OS users running FlowState on the same machine.
+//!
+//! The Docker daemon and the Supabase ports (54321+) are shared machine-wide,
+//! so a second OS user starting the stack either collides with the first one's
+//! containers or fails on ports. The user that starts the stack records itself
+//! in a machine-wide claim file; the conflict check compares that claim and the
+//! port state with the current user and suggests a per-user namespace.
+//!
+//! When another user owns the stack at start (or this user turned it on), the
+//! namespace is applied: `supabase` runs from a generated workdir in the app
+//! data directory whose config.toml carries the per-user project id (which the
+//! CLI also uses for container and volume names) and ports shifted into the
+//! user's block. Everything else in the project's supabase/ directory is linked
+//! (copied on Windows). The choice persists in `multi-user.json`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+const CLAIM_FILE: &str = "flowstate-stack-owner.json";
+
+pub(crate) const NAMESPACE_STORE: &str = "multi-user.json";
+const NAMESPACE_KEY: &str = "namespaced";
+
+/// Per-user workdir, below the app data directory
+const WORKDIR: &str = "supabase-stack";
+
+/// Applied namespace: project id and the generated workdir
+struct Namespace {
+    project_id: String,
+    workdir: PathBuf,
+}
+
+static NAMESPACE: Mutex<Option<Namespace>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StackClaim {
+    user: String,
+    pid: u32,
+    project_id: String,
+    api_port: u16,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiUserReport {
+    pub current_user: String,
+    /// OS user recorded as owner of the running local stack, if any
+    pub stack_owner: Option<String>,
+    pub api_port_in_use: bool,
+    pub conflicts: Vec<String>,
+    /// Project id and API port this user could switch to to avoid collisions
+    pub suggested_project_id: String,
+    pub suggested_api_port: u16,
+    /// Whether the stack is started in the per-user namespace
+    pub namespace_active: bool,
+}
+
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Directory shared by all OS users of the machine
+fn shared_dir() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var("PROGRAMDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        PathBuf::from("/Users/Shared")
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        PathBuf::from("/tmp")
+    }
+}
+
+fn claim_path() -> PathBuf {
+    shared_dir().join(CLAIM_FILE)
+}
+
+fn read_claim() -> Option<StackClaim> {
+    let content = std::fs::read_to_string(claim_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Record the current user as owner of the local stack (after a successful start).
+/// The claim is about the shared default stack; a namespaced start leaves it alone.
+pub fn claim_stack() {
+    if namespace_active() {
+        return;
+    }
+    let claim = StackClaim {
+        user: current_user(),
+        pid: std::process::id(),
+        project_id: crate::SUPABASE_PROJECT_ID.to_string(),
+        api_port: crate::LOCAL_API_PORT,
+    };
+
+    match serde_json::to_string(&claim) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(claim_path(), json) {
+                log::warn!(
+                    "Failed to write stack claim {}: {}",
+                    claim_path().display(),
+                    e
+                );
+            }
+        }
+        Err(e) => log::warn!("Failed to serialize stack claim: {}", e),
+    }
+}
+
+/// Drop the claim if it belongs to the current user (after stopping the stack)
+pub fn release_stack() {
+    if namespace_active() {
+        return;
+    }
+    if read_claim().is_some_and(|c| c.user == current_user()) {
+        let _ = std::fs::remove_file(claim_path());
+    }
+}
+
+/// Owner of the stack when it is someone other than the current user
+pub fn foreign_owner() -> Option<String> {
+    read_claim()
+        .map(|c| c.user)
+        .filter(|user| *user != current_user())
+}
+
+fn port_in_use(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_err()
+}
+
+/// Deterministic per-user project id and API port block
+fn suggested_namespace(user: &str) -> (String, u16) {
+    let sanitized: String = user
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    let mut hasher = DefaultHasher::new();
+    user.hash(&mut hasher);
+    // Supabase uses ports base..base+9; keep blocks 100 apart above the default
+    let block = (hasher.finish() % 40) as u16 + 1;
+
+    (
+        format!("{}-{}", crate::SUPABASE_PROJECT_ID, sanitized),
+        crate::LOCAL_API_PORT + block * 100,
+    )
+}
+
+/// Report cross-user conflicts on the shared Docker daemon and ports
+#[tauri::command]
+pub fn check_multi_user_conflicts() -> MultiUserReport {
+    let user = current_user();
+    let stack_owner = read_claim().map(|c| c.user);
+    let api_port_in_use = port_in_use(crate::LOCAL_API_PORT);
+    let (suggested_project_id, suggested_api_port) = suggested_namespace(&user);
+    let namespace_active = namespace_active();
+
+    let mut conflicts = Vec::new();
+    if let Some(owner) = stack_owner.as_ref().filter(|o| **o != user) {
+        conflicts.push(format!(
+            "The local Supabase stack '{}' is owned by OS user '{}'",
+            crate::SUPABASE_PROJECT_ID,
+            owner
+        ));
+        if api_port_in_use && !namespace_active {
+            conflicts.push(format!(
+                "Port {} is held by {}'s stack; use project '{}' on port {} to run your own",
+                crate::LOCAL_API_PORT,
+                owner,
+                suggested_project_id,
+                suggested_api_port
+            ));
+        }
+    }
+
+    MultiUserReport {
+        current_user: user,
+        stack_owner,
+        api_port_in_use,
+        conflicts,
+        suggested_project_id,
+        suggested_api_port,
+        namespace_active,
+    }
+}
+
+fn namespace_active() -> bool {
+    NAMESPACE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_some()
+}
+
+/// Project id of the stack this user runs (container and volume names)
+pub fn project_id() -> String {
+    NAMESPACE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map_or_else(
+            || crate::SUPABASE_PROJECT_ID.to_string(),
+            |ns| ns.project_id.clone(),
+        )
+}
+
+/// Generated workdir `supabase` runs from while the namespace is applied
+pub fn workdir() -> Option<PathBuf> {
+    NAMESPACE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|ns| ns.workdir.clone())
+}
+
+fn workdir_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(WORKDIR))
+        .map_err(|e| format!("Failed to resolve the app data directory: {}", e))
+}
+
+/// config.toml with the per-user project id and every local `*port` key shifted
+/// by `offset` (`[auth.*]` ports point at external servers and stay)
+fn namespaced_config(content: &str, project_id: &str, offset: u16) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut section: Option<String> = None;
+    for line in content.lines() {
+        let code = line.split('#').next().unwrap_or_default().trim();
+        if let Some(name) = code.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim().to_string());
+        }
+        let rewritten = code.split_once('=').and_then(|(key, value)| {
+            let key = key.trim();
+            if key == "project_id" && section.is_none() {
+                return Some(format!("project_id = \"{}\"", project_id));
+            }
+            if section
+                .as_deref()
+                .is_some_and(|s| s == "auth" || s.starts_with("auth."))
+            {
+                return None;
+            }
+            let port = value.trim().parse::<u16>().ok()?;
+            (key == "port" || key.ends_with("_port"))
+                .then(|| port.checked_add(offset))
+                .flatten()
+                .map(|shifted| format!("{} = {}", key, shifted))
+        });
+        out.push_str(rewritten.as_deref().unwrap_or(line));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(unix)]
+fn link(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(not(unix))]
+fn link(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if src.is_dir() {
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            link(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(src, dst).map(|_| ())
+    }
+}
+
+/// (Re)generate the per-user workdir from the project's supabase/ directory
+fn write_workdir(workdir: &Path, project_id: &str, offset: u16) -> Result<(), String> {
+    let source = crate::project_dir::resolve("supabase");
+    let source = source
+        .canonicalize()
+        .map_err(|e| format!("Supabase project not found at {}: {}", source.display(), e))?;
+    let config = std::fs::read_to_string(source.join("config.toml")).map_err(|e| {
+        format!(
+            "Failed to read {}: {}",
+            source.join("config.toml").display(),
+            e
+        )
+    })?;
+
+    let target = workdir.join("supabase");
+    std::fs::create_dir_all(&target)
+        .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+
+    // Relink on every start so files added to the project show up
+    let entries = std::fs::read_dir(&target)
+        .map_err(|e| format!("Failed to read {}: {}", target.display(), e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        // The CLI keeps its own state in .temp / .branches; keep it per user
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let removed = match entry.file_type() {
+            Ok(t) if t.is_dir() => std::fs::remove_dir_all(&path),
+            _ => std::fs::remove_file(&path),
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+
+    let entries = std::fs::read_dir(&source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name == "config.toml" || name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        link(&entry.path(), &target.join(&name))
+            .map_err(|e| format!("Failed to link {}: {}", entry.path().display(), e))?;
+    }
+
+    std::fs::write(
+        target.join("config.toml"),
+        namespaced_config(&config, project_id, offset),
+    )
+    .map_err(|e| {
+        format!(
+            "Failed to write {}: {}",
+            target.join("config.toml").display(),
+            e
+        )
+    })
+}
+
+/// Generate the workdir and route `supabase` through it
+fn apply(app: &AppHandle) -> Result<(), String> {
+    let (project_id, api_port) = suggested_namespace(&current_user());
+    let workdir = workdir_path(app)?;
+    write_workdir(&workdir, &project_id, api_port - crate::LOCAL_API_PORT)?;
+    log::info!(
+        "Using per-user Supabase namespace '{}' (API port {}) from {}",
+        project_id,
+        api_port,
+        workdir.display()
+    );
+    *NAMESPACE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Namespace {
+        project_id,
+        workdir,
+    });
+    crate::endpoints::invalidate(app);
+    Ok(())
+}
+
+fn persist(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let store = app
+        .store(NAMESPACE_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", NAMESPACE_STORE, e))?;
+    store.set(NAMESPACE_KEY, enabled);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", NAMESPACE_STORE, e))
+}
+
+/// Before `supabase start`: apply the namespace when it is turned on or when
+/// another OS user owns the default stack (and remember that choice)
+pub fn prepare_start(app: &AppHandle) -> Result<(), String> {
+    if namespace_active() || persisted(app) {
+        // Regenerate so config.toml changes in the project are picked up
+        return apply(app);
+    }
+    if let Some(owner) = foreign_owner() {
+        log::info!(
+            "The default stack belongs to OS user '{}'; switching to a per-user namespace",
+            owner
+        );
+        apply(app)?;
+        persist(app, true)?;
+    }
+    Ok(())
+}
+
+fn persisted(app: &AppHandle) -> bool {
+    match app.store(NAMESPACE_STORE) {
+        Ok(store) => store
+            .get(NAMESPACE_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        Err(e) => {
+            log::warn!("Failed to open {}: {}", NAMESPACE_STORE, e);
+            false
+        }
+    }
+}
+
+/// Restore a persisted namespace whose workdir exists (otherwise the next start
+/// generates it; the project directory isn't restored yet at this point)
+pub fn init(app: &AppHandle) {
+    if !persisted(app) {
+        return;
+    }
+    match workdir_path(app) {
+        Ok(workdir) if workdir.join("supabase").join("config.toml").is_file() => {
+            let (project_id, _) = suggested_namespace(&current_user());
+            *NAMESPACE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Namespace {
+                project_id,
+                workdir,
+            });
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+}
+
+/// Turn the per-user namespace on or off. Takes effect for the stack started
+/// next; stop the current stack first.
+#[tauri::command]
+pub fn set_stack_namespace(
+    app: AppHandle,
+    enabled: bool,
+) -> Result<MultiUserReport, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_stack_namespace")?;
+    if enabled {
+        apply(&app)?;
+    } else {
+        *NAMESPACE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        crate::endpoints::invalidate(&app);
+    }
+    persist(&app, enabled)?;
+    Ok(check_multi_user_conflicts())
+}
@@ -0,0 +1,437 @@
+//! Notification decision engine.
+//!
+//! Delivery stays in the frontend (BUG-1289: the notification plugin panics on
+//! Linux), but whether a notification is shown is decided here: per-category
+//! toggles, quiet hours, a per-hour rate limit, and low-priority events folded
+//! into a periodic digest published as `notification://digest`. In privacy
+//! mode only generic text goes out. Preferences persist in `notifications.json`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+pub(crate) const PREFS_STORE: &str = "notifications.json";
+const PREFS_KEY: &str = "prefs";
+const HOUR_MS: u64 = 60 * 60 * 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    DueDates,
+    Breaks,
+    SyncErrors,
+    ServiceHealth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    Normal,
+    /// Ignores quiet hours (still counts against the rate limit)
+    High,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+    /// Minutes after local midnight; start > end wraps past midnight
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+impl QuietHours {
+    fn contains(&self, minute: u16) -> bool {
+        if self.start_minute <= self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            minute >= self.start_minute || minute < self.end_minute
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationPrefs {
+    pub due_dates: bool,
+    pub breaks: bool,
+    pub sync_errors: bool,
+    pub service_health: bool,
+    pub quiet_hours: Option<QuietHours>,
+    /// 0 disables the limit
+    pub max_per_hour: u32,
+    pub digest_interval_minutes: u32,
+}
+
+impl Default for NotificationPrefs {
+    fn default() -> Self {
+        NotificationPrefs {
+            due_dates: true,
+            breaks: true,
+            sync_errors: true,
+            service_health: true,
+            quiet_hours: None,
+            max_per_hour: 10,
+            digest_interval_minutes: 30,
+        }
+    }
+}
+
+impl Category {
+    /// Text shown instead of the real title in privacy mode
+    fn masked_text(&self) -> &'static str {
+        match self {
+            Category::DueDates => "1 reminder",
+            Category::Breaks => "Break reminder",
+            Category::SyncErrors => "Sync issue",
+            Category::ServiceHealth => "Service status changed",
+        }
+    }
+}
+
+impl NotificationPrefs {
+    fn enabled(&self, category: Category) -> bool {
+        match category {
+            Category::DueDates => self.due_dates,
+            Category::Breaks => self.breaks,
+            Category::SyncErrors => self.sync_errors,
+            Category::ServiceHealth => self.service_health,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestItem {
+    pub category: Category,
+    pub title: String,
+    pub timestamp_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Digest {
+    pub title: String,
+    pub body: String,
+    pub items: Vec<DigestItem>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", content = "reason", rename_all = "snake_case")]
+pub enum Decision {
+    Deliver,
+    /// Deliver with this generic text instead of the content (privacy mode)
+    DeliverMasked(String),
+    /// Queued for the next digest
+    Digest,
+    Suppress(String),
+}
+
+#[derive(Default)]
+struct EngineState {
+    prefs: NotificationPrefs,
+    /// Delivery timestamps within the last hour
+    delivered: VecDeque<u64>,
+    digest: Vec<DigestItem>,
+}
+
+#[derive(Default)]
+pub struct NotificationEngine {
+    state: Mutex<EngineState>,
+}
+
+fn local_minute() -> u16 {
+    local_minute_in(&chrono::Local)
+}
+
+/// Minutes after midnight on the clock of `tz`
+fn local_minute_in<Tz: chrono::TimeZone>(tz: &Tz) -> u16 {
+    let now = crate::clock::now_in(tz);
+    (now.hour() * 60 + now.minute()) as u16
+}
+
+impl NotificationEngine {
+    fn decide(&self, category: Category, priority: Priority, title: &str) -> Decision {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = crate::events::now_ms();
+
+        if !state.prefs.enabled(category) {
+            return Decision::Suppress("category disabled".to_string());
+        }
+
+        if priority == Priority::Low {
+            state.digest.push(DigestItem {
+                category,
+                title: title.to_string(),
+                timestamp_ms: now,
+            });
+            return Decision::Digest;
+        }
+
+        if priority != Priority::High
+            && state
+                .prefs
+                .quiet_hours
+                .as_ref()
+                .is_some_and(|q| q.contains(local_minute()))
+        {
+            return Decision::Suppress("quiet hours".to_string());
+        }
+
+        while state
+            .delivered
+            .front()
+            .is_some_and(|t| now.saturating_sub(*t) > HOUR_MS)
+        {
+            state.delivered.pop_front();
+        }
+        let limit = state.prefs.max_per_hour;
+        if limit > 0 && state.delivered.len() >= limit as usize {
+            return Decision::Suppress(format!("rate limit ({} per hour)", limit));
+        }
+
+        state.delivered.push_back(now);
+        Decision::Deliver
+    }
+
+    /// Drain queued low-priority items into one summary (kept queued during quiet hours)
+    fn take_digest(&self, masked: bool) -> Option<Digest> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.digest.is_empty()
+            || state
+                .prefs
+                .quiet_hours
+                .as_ref()
+                .is_some_and(|q| q.contains(local_minute()))
+        {
+            return None;
+        }
+
+        let mut items = std::mem::take(&mut state.digest);
+        state.delivered.push_back(crate::events::now_ms());
+        let title = format!("{} updates", items.len());
+
+        if masked {
+            items.clear();
+        }
+        let body = items
+            .iter()
+            .map(|i| format!("• {}", i.title))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(Digest { title, body, items })
+    }
+
+    fn digest_interval(&self) -> Duration {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        Duration::from_secs(u64::from(state.prefs.digest_interval_minutes.max(1)) * 60)
+    }
+}
+
+/// Load saved preferences and start the digest timer
+pub fn init(app: &AppHandle) {
+    match app.store(PREFS_STORE) {
+        Ok(store) => {
+            if let Some(value) = store.get(PREFS_KEY) {
+                match serde_json::from_value::<NotificationPrefs>(value) {
+                    Ok(prefs) => {
+                        let engine = app.state::<NotificationEngine>();
+                        engine.state.lock().unwrap_or_else(|e| e.into_inner()).prefs = prefs;
+                    }
+                    Err(e) => log::warn!("Ignoring invalid notification prefs: {}", e),
+                }
+            }
+        }
+        Err(e) => log::warn!("Failed to open {}: {}", PREFS_STORE, e),
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = app.state::<NotificationEngine>().digest_interval();
+            crate::clock::sleep(interval).await;
+            let masked = app.state::<crate::privacy::PrivacyMode>().is_enabled();
+            if let Some(digest) = app.state::<NotificationEngine>().take_digest(masked) {
+                crate::events::publish(&app, "notification://digest", &digest);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_notification_prefs(engine: tauri::State<'_, NotificationEngine>) -> NotificationPrefs {
+    engine
+        .state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .prefs
+        .clone()
+}
+
+#[tauri::command]
+pub fn set_notification_prefs(
+    app: AppHandle,
+    prefs: NotificationPrefs,
+) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_notification_prefs")?;
+    let store = app
+        .store(PREFS_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", PREFS_STORE, e))?;
+    store.set(
+        PREFS_KEY,
+        serde_json::to_value(&prefs).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", PREFS_STORE, e))?;
+
+    let engine = app.state::<NotificationEngine>();
+    engine.state.lock().unwrap_or_else(|e| e.into_inner()).prefs = prefs;
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NativeNotification {
+    pub category: Category,
+    pub title: String,
+    pub body: String,
+}
+
+/// Decide on and deliver a notification raised by the backend itself. Linux
+/// uses notify-send like the frontend; other platforms get
+/// `notification://show` and deliver it from the webview.
+pub fn notify(app: &AppHandle, category: Category, priority: Priority, title: &str, body: &str) {
+    let notification =
+        match evaluate_notification(app.clone(), category, priority, title.to_string()) {
+            Decision::Deliver => NativeNotification {
+                category,
+                title: title.to_string(),
+                body: body.to_string(),
+            },
+            Decision::DeliverMasked(text) => NativeNotification {
+                category,
+                title: text,
+                body: String::new(),
+            },
+            Decision::Digest | Decision::Suppress(_) => return,
+        };
+
+    if cfg!(target_os = "linux") {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = crate::trace::command(&app, "notify-send")
+                .args([
+                    "--app-name=FlowState",
+                    "--icon=dialog-information",
+                    // A title starting with '-' is still the title
+                    "--",
+                    notification.title.as_str(),
+                    notification.body.as_str(),
+                ])
+                .output()
+                .await;
+            match result {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => log::warn!(
+                    "notify-send failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => log::warn!("Failed to run notify-send: {}", e),
+            }
+        });
+    } else {
+        crate::events::publish(app, "notification://show", &notification);
+    }
+}
+
+/// Ask whether a notification should be shown now; the frontend delivers on
+/// `deliver` (or `deliver_masked`, with the given text only)
+#[tauri::command]
+pub fn evaluate_notification(
+    app: AppHandle,
+    category: Category,
+    priority: Priority,
+    title: String,
+) -> Decision {
+    let mut decision = app
+        .state::<NotificationEngine>()
+        .decide(category, priority, &title);
+    let masked = app.state::<crate::privacy::PrivacyMode>().is_enabled();
+    if decision == Decision::Deliver && masked {
+        decision = Decision::DeliverMasked(category.masked_text().to_string());
+    }
+    if decision != Decision::Deliver {
+        // Logs are shared in bug reports; keep titles out of them in privacy mode
+        let shown = if masked {
+            category.masked_text()
+        } else {
+            title.as_str()
+        };
+        log::info!("Notification '{}' ({:?}): {:?}", shown, category, decision);
+    }
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_clock(at: &str) {
+        crate::clock::set(
+            chrono::DateTime::parse_from_rfc3339(at)
+                .unwrap()
+                .timestamp_millis(),
+        );
+    }
+
+    #[test]
+    fn quiet_hours_follow_the_local_clock_across_dst() {
+        let tz = chrono_tz::America::New_York;
+        let quiet = QuietHours {
+            start_minute: 22 * 60,
+            end_minute: 7 * 60,
+        };
+        // 11:30 UTC is 06:30 EST on Saturday but 07:30 EDT on Sunday, after
+        // the clocks moved forward
+        set_clock("2026-03-07T11:30:00Z");
+        assert_eq!(local_minute_in(&tz), 6 * 60 + 30);
+        assert!(quiet.contains(local_minute_in(&tz)));
+        crate::clock::advance(24 * 60 * 60 * 1000);
+        assert_eq!(local_minute_in(&tz), 7 * 60 + 30);
+        assert!(!quiet.contains(local_minute_in(&tz)));
+        crate::clock::reset();
+    }
+
+    #[test]
+    fn digests_wait_their_interval_across_dst() {
+        let engine = NotificationEngine::default();
+        engine.state.lock().unwrap().prefs.digest_interval_minutes = 30;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            // 01:50 EST; ten minutes later the wall clock reads 03:00 EDT
+            set_clock("2026-03-08T01:50:00-05:00");
+            let timer = tokio::spawn(crate::clock::sleep(engine.digest_interval()));
+            tokio::task::yield_now().await;
+            crate::clock::advance(20 * 60 * 1000);
+            tokio::task::yield_now().await;
+            assert!(!timer.is_finished(), "fired on the wall clock");
+            crate::clock::advance(10 * 60 * 1000);
+            tokio::time::timeout(Duration::from_secs(5), timer)
+                .await
+                .expect("no digest after 30 minutes")
+                .unwrap();
+            crate::clock::reset();
+        });
+    }
+}
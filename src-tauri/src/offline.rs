@@ -0,0 +1,1183 @@
+//! Local-first task cache with background sync to Supabase.
+//!
+//! Tasks are mirrored into `task_cache.db` (SQLite, one JSON row per task as
+//! returned by `to_jsonb(tasks)`), so they can be read and edited while the
+//! stack is down or Docker isn't installed. Local edits go to the cache and an
+//! outbox; the sync engine pushes the outbox in order and then pulls rows
+//! changed since the last pull (plus a pass over ids to drop tasks deleted
+//! remotely). A push only writes the columns present in the edited row, so
+//! defaults still apply to new tasks. An entry that fails (including an
+//! upsert that writes nothing because the id belongs to another user's task)
+//! stays queued and holds back later edits of the same task; other tasks keep
+//! syncing. Runs
+//! are serialized, happen every `SYNC_INTERVAL` and after each local edit,
+//! and report `sync://progress`.
+//!
+//! Tasks sync with `public.tasks`, or with the active experiment branch's
+//! copy of it (`branches.rs`).
+//!
+//! Sync connects as the database superuser, so it is scoped to the signed-in
+//! user by hand: `set_sync_user` checks the frontend's access token with the
+//! auth service, every stored task is stamped with that user's id, and push
+//! and pull only touch rows owned by it. Tasks saved while signed out are
+//! adopted by the next user to sign in; switching users drops the cached rows
+//! but keeps the other user's queued edits for when they are back.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::conflicts::{LocalEdit, Outcome, RemoteRow};
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::sqlite::LocalDb;
+
+const DB_FILE: &str = "task_cache.db";
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// Longest a periodic sync waits for the user to pause
+const MAX_SYNC_DEFER: Duration = Duration::from_secs(5 * 60);
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    -- Server updated_at of the last pulled version (NULL for local-only tasks)
+    updated_at TEXT
+);
+CREATE TABLE IF NOT EXISTS outbox (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id TEXT NOT NULL,
+    op TEXT NOT NULL,
+    data TEXT,
+    -- Server updated_at the edit was based on
+    base_updated_at TEXT,
+    queued_at INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    -- Signed-in user when the edit was made (NULL: signed out)
+    user_id TEXT
+);
+-- Server version of a task when its first pending edit was made
+CREATE TABLE IF NOT EXISTS outbox_base (
+    task_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+-- Edits parked for manual resolution (conflicts.rs)
+CREATE TABLE IF NOT EXISTS conflicts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id TEXT NOT NULL,
+    local TEXT NOT NULL,
+    remote TEXT NOT NULL,
+    remote_updated_at TEXT,
+    base TEXT,
+    detected_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+-- Bulk edits (bulk.rs) with what they changed, for undo
+CREATE TABLE IF NOT EXISTS bulk_batches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    filter TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    applied_at INTEGER NOT NULL,
+    undone_at INTEGER
+);
+CREATE TABLE IF NOT EXISTS bulk_batch_tasks (
+    batch_id INTEGER NOT NULL,
+    task_id TEXT NOT NULL,
+    -- Previous values of the changed fields (the whole row if deleted)
+    before TEXT NOT NULL,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (batch_id, task_id)
+);
+-- Saved filters (smart_lists.rs)
+CREATE TABLE IF NOT EXISTS smart_lists (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+-- Custom field definitions (custom_fields.rs); user_id '' while signed out
+CREATE TABLE IF NOT EXISTS custom_fields (
+    user_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    -- JSON array of a select field's options
+    options TEXT,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+    -- Changed locally since the last push
+    dirty INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (user_id, key)
+);";
+
+#[derive(Default)]
+struct Health {
+    online: bool,
+    last_sync_at_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+pub struct TaskCache {
+    pub(crate) db: LocalDb,
+    health: Mutex<Health>,
+    /// Held for the duration of a sync run
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Default for TaskCache {
+    fn default() -> Self {
+        TaskCache {
+            db: LocalDb::new(DB_FILE, SCHEMA),
+            health: Mutex::new(Health::default()),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    /// Whether the last sync went through (pushed and pulled)
+    pub online: bool,
+    pub syncing: bool,
+    /// Local edits not yet pushed
+    pub pending: u64,
+    /// Pending edits whose last push failed
+    pub failed: u64,
+    /// Edits parked for `resolve_conflict`
+    pub conflicts: u64,
+    pub cached_tasks: u64,
+    pub last_sync_at_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPhase {
+    Pushing,
+    Pulling,
+    Done,
+    Offline,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub phase: SyncPhase,
+    pub done: u64,
+    pub total: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+struct OutboxEntry {
+    seq: i64,
+    task_id: String,
+    op: String,
+    data: Option<String>,
+    base_updated_at: Option<String>,
+    queued_at: i64,
+}
+
+/// Run `f` against the task cache
+pub(crate) fn with_db<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut rusqlite::Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    app.state::<TaskCache>().db.with(app, f)
+}
+
+fn meta_user(conn: &rusqlite::Connection) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT value FROM meta WHERE key = 'user_id'", [], |row| {
+        row.get(0)
+    })
+    .optional()
+}
+
+/// Id of the signed-in user (`set_sync_user`), which owns the cached tasks
+pub(crate) fn current_user(app: &AppHandle) -> Option<String> {
+    with_db(app, |conn| meta_user(conn)).ok().flatten()
+}
+
+fn progress(app: &AppHandle, phase: SyncPhase, done: u64, total: u64, error: Option<String>) {
+    crate::events::publish(
+        app,
+        "sync://progress",
+        &SyncProgress {
+            phase,
+            done,
+            total,
+            error,
+        },
+    );
+}
+
+/// Random v4 UUID for tasks created offline
+fn new_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The tasks table of `schema` (`public` or a branch), quoted for SQL
+pub(crate) fn tasks_table(schema: &str) -> String {
+    format!("{}.tasks", quote_ident(schema))
+}
+
+/// Columns of the tasks table of `schema`
+pub(crate) async fn task_columns(
+    client: &tokio_postgres::Client,
+    schema: &str,
+) -> Result<HashSet<String>, String> {
+    let rows = client
+        .query(
+            "SELECT column_name::text FROM information_schema.columns \
+             WHERE table_schema = $1 AND table_name = 'tasks'",
+            &[&schema],
+        )
+        .await
+        .map_err(|e| format!("Failed to read the tasks columns: {}", e))?;
+    Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+/// Insert or update a task from its JSON row, touching only the columns present
+async fn push_upsert(
+    client: &tokio_postgres::Client,
+    columns: &HashSet<String>,
+    schema: &str,
+    user: &str,
+    data: &str,
+) -> Result<(), String> {
+    let row: Map<String, Value> =
+        serde_json::from_str(data).map_err(|e| format!("Invalid queued task: {}", e))?;
+    if row.get("user_id").and_then(Value::as_str) != Some(user) {
+        return Err("Queued task is not owned by the signed-in user".to_string());
+    }
+    let names: Vec<String> = row
+        .keys()
+        .filter(|k| columns.contains(*k))
+        .map(|k| quote_ident(k))
+        .collect();
+    if !row.contains_key("id") {
+        return Err("Queued task has no id".to_string());
+    }
+
+    let updates: Vec<String> = names
+        .iter()
+        .filter(|n| n.as_str() != "\"id\"")
+        .map(|n| format!("{} = EXCLUDED.{}", n, n))
+        .collect();
+    // Never take over a row of another user that happens to share the id
+    let conflict = if updates.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!(
+            "DO UPDATE SET {} WHERE t.user_id = EXCLUDED.user_id",
+            updates.join(", ")
+        )
+    };
+    let list = names.join(", ");
+    let table = tasks_table(schema);
+    let sql = format!(
+        "INSERT INTO {} AS t ({}) \
+         SELECT {} FROM jsonb_populate_record(NULL::{}, $1::text::jsonb) \
+         ON CONFLICT (id) {}",
+        table, list, list, table, conflict
+    );
+    let written = client
+        .execute(&sql, &[&data])
+        .await
+        .map_err(|e| e.to_string())?;
+    // Nothing written: the id is taken by another user's task. The edit stays
+    // queued (and visible as failed) rather than being dropped.
+    if written == 0 {
+        return Err("The task id already belongs to another user".to_string());
+    }
+    Ok(())
+}
+
+/// Push one queued edit, resolving a conflict with a concurrent server change
+async fn push_edit(
+    app: &AppHandle,
+    client: &tokio_postgres::Client,
+    columns: &HashSet<String>,
+    schema: &str,
+    user: &str,
+    entry: &OutboxEntry,
+    data: &str,
+) -> Result<(), String> {
+    let remote = client
+        .query_opt(
+            &format!(
+                "SELECT to_jsonb(t)::text, updated_at::text FROM {} t \
+                 WHERE id::text = $1 AND user_id::text = $2",
+                tasks_table(schema)
+            ),
+            &[&entry.task_id, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to read task {}: {}", entry.task_id, e))?
+        .map(|row| RemoteRow {
+            data: row.get(0),
+            updated_at: row.get(1),
+        });
+
+    let edit = LocalEdit {
+        task_id: &entry.task_id,
+        data,
+        base_updated_at: entry.base_updated_at.as_deref(),
+        queued_at_ms: entry.queued_at,
+    };
+    match crate::conflicts::reconcile(app, &edit, remote.as_ref())? {
+        Outcome::Push(data) => push_upsert(client, columns, schema, user, &data).await,
+        Outcome::KeepRemote => {
+            if let Some(remote) = &remote {
+                with_db(app, |conn| {
+                    conn.execute(
+                        "UPDATE tasks SET data = ?2, updated_at = ?3 WHERE id = ?1",
+                        params![entry.task_id, remote.data, remote.updated_at],
+                    )
+                })?;
+                crate::search::reindex(app, &entry.task_id);
+            }
+            Ok(())
+        }
+        // The conflict holds the edit now
+        Outcome::Parked => Ok(()),
+    }
+}
+
+/// Queued edits of `user`, oldest first
+fn queued(conn: &rusqlite::Connection, user: &str) -> rusqlite::Result<Vec<OutboxEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT seq, task_id, op, data, base_updated_at, queued_at FROM outbox \
+         WHERE user_id = ?1 ORDER BY seq",
+    )?;
+    let rows = stmt.query_map(params![user], |row| {
+        Ok(OutboxEntry {
+            seq: row.get(0)?,
+            task_id: row.get(1)?,
+            op: row.get(2)?,
+            data: row.get(3)?,
+            base_updated_at: row.get(4)?,
+            queued_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Drop a pushed entry, or count the failed attempt and keep it queued
+fn settle(
+    conn: &rusqlite::Connection,
+    entry: &OutboxEntry,
+    result: &Result<(), String>,
+) -> rusqlite::Result<()> {
+    match result {
+        Ok(()) => {
+            conn.execute("DELETE FROM outbox WHERE seq = ?1", params![entry.seq])?;
+            conn.execute(
+                "DELETE FROM outbox_base WHERE task_id = ?1 \
+                 AND NOT EXISTS (SELECT 1 FROM outbox WHERE task_id = ?1) \
+                 AND NOT EXISTS (SELECT 1 FROM conflicts WHERE task_id = ?1)",
+                params![entry.task_id],
+            )?;
+        }
+        Err(e) => {
+            conn.execute(
+                "UPDATE outbox SET attempts = attempts + 1, last_error = ?1 WHERE seq = ?2",
+                params![e, entry.seq],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Push `entries` in queue order with `push_one` (given the entry's position)
+/// and hand each result to `record`. A failed entry holds back the later
+/// edits of its task, which would otherwise land out of order; other tasks
+/// keep syncing.
+async fn replay<Fut>(
+    entries: Vec<OutboxEntry>,
+    mut push_one: impl FnMut(usize, OutboxEntry) -> Fut,
+    mut record: impl FnMut(&OutboxEntry, &Result<(), String>) -> Result<(), String>,
+) -> Result<(), String>
+where
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    let mut blocked: HashSet<String> = HashSet::new();
+    for (done, entry) in entries.into_iter().enumerate() {
+        if blocked.contains(&entry.task_id) {
+            continue;
+        }
+        let result = push_one(done, entry.clone()).await;
+        if let Err(e) = &result {
+            log::warn!("Failed to push task {}: {}", entry.task_id, e);
+            blocked.insert(entry.task_id.clone());
+        }
+        record(&entry, &result)?;
+    }
+    Ok(())
+}
+
+/// Push one queued edit or delete
+async fn push_entry(
+    app: &AppHandle,
+    client: &tokio_postgres::Client,
+    columns: &HashSet<String>,
+    schema: &str,
+    user: &str,
+    entry: &OutboxEntry,
+) -> Result<(), String> {
+    match (entry.op.as_str(), entry.data.as_deref()) {
+        ("upsert", Some(data)) => push_edit(app, client, columns, schema, user, entry, data).await,
+        ("delete", _) => client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE id::text = $1 AND user_id::text = $2",
+                    tasks_table(schema)
+                ),
+                &[&entry.task_id, &user],
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        (op, _) => Err(format!("Unknown outbox operation '{}'", op)),
+    }
+}
+
+/// Push the signed-in user's queued edits to the tasks table of `schema`
+async fn push(
+    app: &AppHandle,
+    client: &tokio_postgres::Client,
+    schema: &str,
+    user: &str,
+) -> Result<(), String> {
+    let entries = with_db(app, |conn| queued(conn, user))?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let columns = task_columns(client, schema).await?;
+    let columns = &columns;
+    let total = entries.len() as u64;
+    replay(
+        entries,
+        |done, entry| async move {
+            progress(app, SyncPhase::Pushing, done as u64, total, None);
+            push_entry(app, client, columns, schema, user, &entry).await
+        },
+        |entry, result| with_db(app, |conn| settle(conn, entry, result)),
+    )
+    .await
+}
+
+/// The signed-in user's rows of `schema` changed since the last pull, and the
+/// ids that still exist remotely
+async fn pull(
+    app: &AppHandle,
+    client: &tokio_postgres::Client,
+    schema: &str,
+    user: &str,
+) -> Result<u64, String> {
+    let since = with_db(app, |conn| {
+        conn.query_row(
+            "SELECT value FROM meta WHERE key = 'last_pulled'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+    })?;
+
+    let table = tasks_table(schema);
+    let rows = client
+        .query(
+            &format!(
+                "SELECT id::text, to_jsonb(t)::text, updated_at::text FROM {} t \
+                 WHERE user_id::text = $2 \
+                 AND ($1::text IS NULL OR updated_at > $1::text::timestamptz) \
+                 ORDER BY updated_at",
+                table
+            ),
+            &[&since, &user],
+        )
+        .await
+        .map_err(|e| format!("Failed to pull tasks: {}", e))?;
+    let remote_ids: HashSet<String> = client
+        .query(
+            &format!("SELECT id::text FROM {} WHERE user_id::text = $1", table),
+            &[&user],
+        )
+        .await
+        .map_err(|e| format!("Failed to list tasks: {}", e))?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+
+    let total = rows.len() as u64;
+    progress(app, SyncPhase::Pulling, 0, total, None);
+    let changed: Vec<(String, String, Option<String>)> = rows
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect();
+    let last = changed.iter().filter_map(|(_, _, u)| u.clone()).next_back();
+
+    let applied = with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        let mut applied = 0;
+        let pending: HashSet<String> = {
+            let mut stmt =
+                tx.prepare("SELECT task_id FROM outbox UNION SELECT task_id FROM conflicts")?;
+            let ids = stmt.query_map([], |row| row.get(0))?;
+            ids.collect::<rusqlite::Result<_>>()?
+        };
+
+        // Local edits that haven't been pushed (or are parked) win until they are
+        for (id, data, updated_at) in &changed {
+            if pending.contains(id) {
+                continue;
+            }
+            applied += tx.execute(
+                "INSERT INTO tasks (id, data, updated_at) VALUES (?1, ?2, ?3) \
+                 ON CONFLICT (id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+                params![id, data, updated_at],
+            )?;
+        }
+
+        let cached: Vec<(String, Option<String>)> = {
+            let mut stmt = tx.prepare("SELECT id, updated_at FROM tasks")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for (id, updated_at) in cached {
+            // Pulled before but gone remotely: deleted elsewhere
+            if updated_at.is_some() && !remote_ids.contains(&id) && !pending.contains(&id) {
+                applied += tx.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+            }
+        }
+
+        if let Some(last) = &last {
+            tx.execute(
+                "INSERT INTO meta (key, value) VALUES ('last_pulled', ?1) \
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                params![last],
+            )?;
+        }
+        tx.commit()?;
+        Ok(applied)
+    })?;
+    if applied > 0 {
+        crate::search::rebuild(app)?;
+    }
+    Ok(total)
+}
+
+/// Push queued edits and pull remote changes; runs are serialized
+pub async fn sync(app: &AppHandle) -> Result<SyncStatus, String> {
+    let cache = app.state::<TaskCache>();
+    let _running = cache.running.lock().await;
+
+    let Some(user) = current_user(app) else {
+        let e = "Not signed in; local edits sync once a user signs in".to_string();
+        cache
+            .health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .last_error = Some(e.clone());
+        progress(app, SyncPhase::Offline, 0, 0, Some(e));
+        return status(app);
+    };
+
+    let client = match crate::db::connect(app).await {
+        Ok(client) => client,
+        Err(e) => {
+            {
+                let mut health = cache.health.lock().unwrap_or_else(|e| e.into_inner());
+                health.online = false;
+                health.last_error = Some(e.clone());
+            }
+            progress(app, SyncPhase::Offline, 0, 0, Some(e));
+            return status(app);
+        }
+    };
+
+    let started_ms = now_ms();
+    let result = async {
+        let schema = with_db(app, |conn| crate::branches::active_schema(conn))?;
+        push(app, &client, &schema, &user).await?;
+        crate::custom_fields::sync(app, &client, &user).await?;
+        pull(app, &client, &schema, &user).await
+    }
+    .await;
+    crate::otel::record_span(
+        "sync",
+        started_ms,
+        now_ms().saturating_sub(started_ms),
+        result.as_ref().err().cloned(),
+        vec![("pulled", result.as_ref().map_or(0, |p| *p).into())],
+    );
+    {
+        let mut health = cache.health.lock().unwrap_or_else(|e| e.into_inner());
+        // Online only once a whole round trip went through
+        health.online = result.is_ok();
+        match &result {
+            Ok(_) => {
+                health.last_sync_at_ms = Some(now_ms());
+                health.last_error = None;
+            }
+            Err(e) => health.last_error = Some(e.clone()),
+        }
+    }
+    match result {
+        Ok(pulled) => progress(app, SyncPhase::Done, pulled, pulled, None),
+        Err(e) => {
+            log::warn!("Task sync failed: {}", e);
+            progress(app, SyncPhase::Failed, 0, 0, Some(e));
+        }
+    }
+    status(app)
+}
+
+/// Wait for a running sync and keep others from starting until the guard
+/// is dropped
+pub(crate) async fn pause_sync(app: &AppHandle) -> tokio::sync::MutexGuard<'_, ()> {
+    app.state::<TaskCache>().inner().running.lock().await
+}
+
+/// Start a sync in the background unless one is already running
+pub(crate) fn sync_soon(app: &AppHandle) {
+    if app.state::<TaskCache>().running.try_lock().is_err() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = sync(&app).await;
+    });
+}
+
+pub(crate) fn status(app: &AppHandle) -> Result<SyncStatus, String> {
+    let cache = app.state::<TaskCache>();
+    let (pending, failed, conflicts, cached_tasks) = with_db(app, |conn| {
+        conn.query_row(
+            "SELECT (SELECT count(*) FROM outbox), \
+                    (SELECT count(*) FROM outbox WHERE attempts > 0), \
+                    (SELECT count(*) FROM conflicts), \
+                    (SELECT count(*) FROM tasks)",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            },
+        )
+    })?;
+    let syncing = cache.running.try_lock().is_err();
+    let health = cache.health.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(SyncStatus {
+        online: health.online,
+        syncing,
+        pending: pending as u64,
+        failed: failed as u64,
+        conflicts: conflicts as u64,
+        cached_tasks: cached_tasks as u64,
+        last_sync_at_ms: health.last_sync_at_ms,
+        last_error: health.last_error.clone(),
+    })
+}
+
+/// Keep the pulled version of a task before its first local edit, for merging
+fn remember_base(conn: &rusqlite::Connection, task_id: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR IGNORE INTO outbox_base (task_id, data) \
+         SELECT id, data FROM tasks WHERE id = ?1 AND updated_at IS NOT NULL",
+        params![task_id],
+    )
+}
+
+/// Queue an edit of `task_id` by `user`, keeping the server version it was
+/// based on (in a savepoint, so a caller's transaction can batch edits)
+fn enqueue(
+    conn: &mut rusqlite::Connection,
+    task_id: &str,
+    op: &str,
+    data: Option<&str>,
+    user: Option<&str>,
+) -> rusqlite::Result<()> {
+    let tx = conn.savepoint()?;
+    let base: Option<String> = match tx
+        .query_row(
+            "SELECT base_updated_at FROM outbox WHERE task_id = ?1 ORDER BY seq LIMIT 1",
+            params![task_id],
+            |row| row.get(0),
+        )
+        .optional()?
+    {
+        Some(base) => base,
+        None => tx
+            .query_row(
+                "SELECT updated_at FROM tasks WHERE id = ?1",
+                params![task_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten(),
+    };
+    // Only the latest state of a task needs pushing
+    tx.execute("DELETE FROM outbox WHERE task_id = ?1", params![task_id])?;
+    tx.execute(
+        "INSERT INTO outbox (task_id, op, data, base_updated_at, queued_at, user_id) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![task_id, op, data, base, now_ms() as i64, user],
+    )?;
+    tx.commit()
+}
+
+/// Start the periodic sync (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            crate::background::yield_to_user(&app, MAX_SYNC_DEFER).await;
+            let _ = sync(&app).await;
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    })
+}
+
+pub(crate) fn cached_tasks(app: &AppHandle) -> Result<Vec<Value>, String> {
+    let rows = with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT data FROM tasks ORDER BY id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(rows
+        .iter()
+        .filter_map(|data| serde_json::from_str(data).ok())
+        .collect())
+}
+
+/// All cached tasks (JSON rows as stored in Supabase, with local edits applied)
+#[tauri::command]
+pub fn get_cached_tasks(app: AppHandle) -> Result<Vec<Value>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_cached_tasks")?;
+    Ok(cached_tasks(&app)?)
+}
+
+/// Merge `task` into its cached row, owned by the signed-in user, and queue
+/// it; returns the stored row
+pub(crate) fn store_task(
+    conn: &mut rusqlite::Connection,
+    id: &str,
+    task: Map<String, Value>,
+) -> rusqlite::Result<String> {
+    let user = meta_user(conn)?;
+    let cached: Option<String> = conn
+        .query_row("SELECT data FROM tasks WHERE id = ?1", params![id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    let mut row: Map<String, Value> = cached
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    row.extend(task);
+    row.insert("id".to_string(), Value::String(id.to_string()));
+    match &user {
+        Some(user) => {
+            row.insert("user_id".to_string(), Value::String(user.clone()));
+        }
+        // Adopted by whoever signs in next
+        None => {
+            row.remove("user_id");
+        }
+    }
+    row.insert(
+        "updated_at".to_string(),
+        Value::String(chrono::Utc::now().to_rfc3339()),
+    );
+    let data = Value::Object(row).to_string();
+
+    remember_base(conn, id)?;
+    conn.execute(
+        "INSERT INTO tasks (id, data) VALUES (?1, ?2) \
+         ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+        params![id, data],
+    )?;
+    enqueue(conn, id, "upsert", Some(&data), user.as_deref())?;
+    Ok(data)
+}
+
+/// Save tasks into the cache (tasks without an id get a new one), queue them
+/// for sync and return the stored rows
+pub(crate) fn save_tasks(
+    app: &AppHandle,
+    tasks: Vec<Map<String, Value>>,
+) -> Result<Vec<Value>, String> {
+    let tasks = tasks
+        .into_iter()
+        .map(|task| match task.get("id") {
+            Some(Value::String(id)) if !id.is_empty() => Ok((id.clone(), task)),
+            Some(_) => Err("Task id must be a non-empty string".to_string()),
+            None => Ok((new_id(), task)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let tasks = crate::custom_fields::prepare(app, tasks)?;
+
+    let stored = with_db(app, |conn| {
+        tasks
+            .into_iter()
+            .map(|(id, task)| store_task(conn, &id, task))
+            .collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    crate::search::upsert(app, &stored);
+
+    sync_soon(app);
+    stored
+        .iter()
+        .map(|data| serde_json::from_str(data).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// An empty task row owned by the signed-in user (unowned while signed out,
+/// until the next user to sign in adopts it)
+pub(crate) fn owned_task(app: &AppHandle) -> Map<String, Value> {
+    let mut task = Map::new();
+    if let Some(user) = current_user(app) {
+        task.insert("user_id".to_string(), Value::String(user));
+    }
+    task
+}
+
+pub(crate) fn save_task(app: &AppHandle, task: Map<String, Value>) -> Result<Value, String> {
+    save_tasks(app, vec![task])?
+        .pop()
+        .ok_or_else(|| "Task was not saved".to_string())
+}
+
+/// Cached row of a task, if it is still cached
+pub(crate) fn cached_task(app: &AppHandle, id: &str) -> Result<Option<Map<String, Value>>, String> {
+    let data: Option<String> = with_db(app, |conn| {
+        conn.query_row("SELECT data FROM tasks WHERE id = ?1", params![id], |row| {
+            row.get(0)
+        })
+        .optional()
+    })?;
+    Ok(data.and_then(|data| serde_json::from_str(&data).ok()))
+}
+
+/// Save a task locally (merged into the cached row) and queue it for sync;
+/// tasks without an id get a new one. Returns the cached row.
+#[tauri::command]
+pub fn save_task_local(app: AppHandle, task: Map<String, Value>) -> Result<Value, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "save_task_local")?;
+    crate::read_only::ensure_writable(&app, "save_task_local")?;
+    Ok(save_task(&app, task)?)
+}
+
+/// Drop a task from the cache and queue the remote delete
+pub(crate) fn remove_task(conn: &mut rusqlite::Connection, id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+    let user = meta_user(conn)?;
+    enqueue(conn, id, "delete", None, user.as_deref())
+}
+
+pub(crate) fn delete_task(app: &AppHandle, id: &str) -> Result<(), String> {
+    with_db(app, |conn| remove_task(conn, id))?;
+    crate::search::remove(app, id);
+    sync_soon(app);
+    Ok(())
+}
+
+/// Remove a task locally and queue the remote delete
+#[tauri::command]
+pub fn delete_task_local(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "delete_task_local")?;
+    crate::read_only::ensure_writable(&app, "delete_task_local")?;
+    Ok(delete_task(&app, &id)?)
+}
+
+/// Id of the user an access token belongs to, as the local auth service sees it
+async fn token_user(app: &AppHandle, access_token: &str) -> Result<String, String> {
+    let endpoints = crate::endpoints::get(app).await;
+    let response = crate::health::client()?
+        .get(format!("{}/auth/v1/user", endpoints.api_base_url()))
+        .header("apikey", &endpoints.anon_key)
+        .bearer_auth(access_token)
+        .timeout(AUTH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the auth service: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Access token was rejected ({})", response.status()));
+    }
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the auth response: {}", e))?;
+    let user: Value =
+        serde_json::from_str(&body).map_err(|e| format!("Invalid auth response: {}", e))?;
+    user.get("id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Auth response has no user id".to_string())
+}
+
+/// Make `user` the owner of the cache; returns whether it changed
+fn switch_user(conn: &mut rusqlite::Connection, user: Option<&str>) -> rusqlite::Result<bool> {
+    let tx = conn.transaction()?;
+    let previous = meta_user(&tx)?;
+    if previous.as_deref() == user {
+        return Ok(false);
+    }
+    if previous.is_some() {
+        // The other user's queued edits stay in the outbox under their id
+        tx.execute_batch(
+            "DELETE FROM tasks; DELETE FROM outbox_base; \
+             DELETE FROM meta WHERE key IN ('last_pulled', 'branch');",
+        )?;
+    }
+    match user {
+        Some(user) => {
+            tx.execute(
+                "INSERT INTO meta (key, value) VALUES ('user_id', ?1) \
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                params![user],
+            )?;
+            // Saved while signed out
+            tx.execute(
+                "UPDATE outbox SET user_id = ?1, \
+                 data = CASE WHEN data IS NULL THEN NULL ELSE json_set(data, '$.user_id', ?1) END \
+                 WHERE user_id IS NULL",
+                params![user],
+            )?;
+            tx.execute(
+                "UPDATE tasks SET data = json_set(data, '$.user_id', ?1) \
+                 WHERE json_extract(data, '$.user_id') IS NULL",
+                params![user],
+            )?;
+            crate::custom_fields::adopt(&tx, user)?;
+        }
+        None => {
+            tx.execute("DELETE FROM meta WHERE key = 'user_id'", [])?;
+        }
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Set the user whose tasks are cached and synced, from the frontend's
+/// Supabase access token (None on sign-out). Returns the user id.
+#[tauri::command]
+pub async fn set_sync_user(
+    app: AppHandle,
+    access_token: Option<String>,
+) -> Result<Option<String>, FlowStateError> {
+    crate::trace::scope("set_sync_user", async move {
+        crate::read_only::ensure_writable(&app, "set_sync_user")?;
+        crate::app_lock::ensure_unlocked(&app, "set_sync_user")?;
+        let user = match access_token {
+            Some(token) => Some(token_user(&app, &token).await?),
+            None => None,
+        };
+        let changed = with_db(&app, |conn| switch_user(conn, user.as_deref()))?;
+        if changed {
+            log::info!(
+                "Task cache {}",
+                if user.is_some() {
+                    "switched to a new user"
+                } else {
+                    "signed out"
+                }
+            );
+            crate::search::rebuild(&app)?;
+            crate::heatmap::invalidate(&app);
+            sync_soon(&app);
+        }
+        Ok(user)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn get_sync_status(app: AppHandle) -> Result<SyncStatus, FlowStateError> {
+    Ok(status(&app)?)
+}
+
+/// Sync now and wait for the result
+#[tauri::command]
+pub async fn force_sync(app: AppHandle) -> Result<SyncStatus, FlowStateError> {
+    crate::trace::scope("force_sync", async move {
+        crate::app_lock::ensure_unlocked(&app, "force_sync")?;
+        crate::read_only::ensure_writable(&app, "force_sync")?;
+        Ok(sync(&app).await?)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn entry(seq: i64, task_id: &str) -> OutboxEntry {
+        OutboxEntry {
+            seq,
+            task_id: task_id.to_string(),
+            op: "upsert".to_string(),
+            data: Some(format!("{{\"id\":\"{}\"}}", task_id)),
+            base_updated_at: None,
+            queued_at: 0,
+        }
+    }
+
+    fn queued_ids(conn: &rusqlite::Connection, user: &str) -> Vec<String> {
+        queued(conn, user)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.task_id)
+            .collect()
+    }
+
+    #[test]
+    fn later_edits_replace_earlier_ones_and_keep_their_base() {
+        let mut conn = cache();
+        conn.execute(
+            "INSERT INTO tasks (id, data, updated_at) VALUES ('a', '{}', 'v1')",
+            [],
+        )
+        .unwrap();
+        enqueue(
+            &mut conn,
+            "a",
+            "upsert",
+            Some(r#"{"title":"1"}"#),
+            Some("u"),
+        )
+        .unwrap();
+        enqueue(
+            &mut conn,
+            "b",
+            "upsert",
+            Some(r#"{"title":"b"}"#),
+            Some("u"),
+        )
+        .unwrap();
+        // Pulled again in between; the edit is still based on v1
+        conn.execute("UPDATE tasks SET updated_at = 'v2' WHERE id = 'a'", [])
+            .unwrap();
+        enqueue(
+            &mut conn,
+            "a",
+            "upsert",
+            Some(r#"{"title":"2"}"#),
+            Some("u"),
+        )
+        .unwrap();
+        enqueue(&mut conn, "c", "delete", None, Some("other")).unwrap();
+
+        let entries = queued(&conn, "u").unwrap();
+        let order: Vec<&str> = entries.iter().map(|e| e.task_id.as_str()).collect();
+        assert_eq!(order, ["b", "a"]);
+        assert_eq!(entries[1].data.as_deref(), Some(r#"{"title":"2"}"#));
+        assert_eq!(entries[1].base_updated_at.as_deref(), Some("v1"));
+        assert_eq!(queued_ids(&conn, "other"), ["c"]);
+    }
+
+    #[test]
+    fn a_failed_push_stays_queued_and_holds_back_its_task() {
+        let mut conn = cache();
+        for task in ["a", "b"] {
+            enqueue(&mut conn, task, "upsert", Some("{}"), Some("u")).unwrap();
+        }
+        let mut entries = queued(&conn, "u").unwrap();
+        // A second entry of `a` after `b`, as if queued by another path
+        let mut later = entries[0].clone();
+        later.seq = 99;
+        entries.push(later);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut pushed = Vec::new();
+        runtime
+            .block_on(replay(
+                entries,
+                |done, entry| {
+                    pushed.push((done, entry.task_id.clone()));
+                    async move {
+                        if entry.task_id == "a" {
+                            Err("The task id already belongs to another user".to_string())
+                        } else {
+                            Ok(())
+                        }
+                    }
+                },
+                |entry, result| settle(&conn, entry, result).map_err(|e| e.to_string()),
+            ))
+            .unwrap();
+
+        // In queue order, and nothing more of `a` after it failed
+        assert_eq!(pushed, [(0, "a".to_string()), (1, "b".to_string())]);
+        assert_eq!(queued_ids(&conn, "u"), ["a"]);
+        let (attempts, error): (i64, Option<String>) = conn
+            .query_row(
+                "SELECT attempts, last_error FROM outbox WHERE task_id = 'a'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(attempts, 1);
+        assert_eq!(
+            error.as_deref(),
+            Some("The task id already belongs to another user")
+        );
+    }
+
+    #[test]
+    fn pushing_the_last_edit_clears_the_base_row() {
+        let conn = cache();
+        conn.execute_batch(
+            "INSERT INTO outbox_base (task_id, data) VALUES ('a', '{}');
+             INSERT INTO outbox (seq, task_id, op, data, queued_at, user_id)
+                 VALUES (1, 'a', 'upsert', '{}', 0, 'u');",
+        )
+        .unwrap();
+        settle(&conn, &entry(1, "a"), &Ok(())).unwrap();
+        let bases: i64 = conn
+            .query_row("SELECT count(*) FROM outbox_base", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(bases, 0);
+        assert!(queued_ids(&conn, "u").is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! Asking the operating system to confirm the user is present.
+//!
+//! Before plaintext secrets leave the backend, and to unlock the app without
+//! its PIN, `verify` shows the platform's own prompt and waits for it: Touch ID or the login password through
+//! LocalAuthentication on macOS, Windows Hello (face, fingerprint or PIN) on
+//! Windows, and the polkit authentication agent on Linux, for FlowState's own
+//! actions (`polkit/com.flowstate.app.reveal-secret.policy`, installed by the
+//! deb and rpm packages), which ask for the user's password every time. The check happens
+//! here rather than in the webview, so a compromised frontend can't skip it.
+//! Without a usable prompt (no Windows Hello set up, no polkit agent or
+//! action) the secret stays in the keychain.
+
+use tauri::AppHandle;
+
+/// What the user is asked to authenticate for
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Purpose {
+    RevealSecret,
+    Unlock,
+}
+
+impl Purpose {
+    /// polkit action checked on Linux: `auth_self` for the active session,
+    /// nothing retained between checks
+    #[cfg(target_os = "linux")]
+    fn polkit_action(self) -> &'static str {
+        match self {
+            Purpose::RevealSecret => "com.flowstate.app.reveal-secret",
+            Purpose::Unlock => "com.flowstate.app.unlock",
+        }
+    }
+}
+
+/// Show the OS authentication prompt with `reason` and wait for the user;
+/// Ok only when they authenticated
+pub(crate) async fn verify(app: &AppHandle, purpose: Purpose, reason: &str) -> Result<(), String> {
+    if prompt(app, purpose, reason).await? {
+        Ok(())
+    } else {
+        Err("Authentication was cancelled or failed".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+async fn prompt(_app: &AppHandle, _purpose: Purpose, reason: &str) -> Result<bool, String> {
+    let reason = reason.to_string();
+    tauri::async_runtime::spawn_blocking(move || local_authentication(&reason))
+        .await
+        .map_err(|e| format!("Authentication prompt failed: {}", e))?
+}
+
+#[cfg(target_os = "macos")]
+fn local_authentication(reason: &str) -> Result<bool, String> {
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_foundation::{NSError, NSString};
+    use objc2_local_authentication::{LAContext, LAPolicy};
+
+    // Biometrics, falling back to the login password
+    let policy = LAPolicy::DeviceOwnerAuthentication;
+    // SAFETY: a fresh context, used from this thread only
+    let context = unsafe { LAContext::new() };
+    unsafe { context.canEvaluatePolicy_error(policy) }.map_err(|e| {
+        format!(
+            "Device authentication unavailable: {}",
+            e.localizedDescription()
+        )
+    })?;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let reply = RcBlock::new(move |success: Bool, _error: *mut NSError| {
+        let _ = tx.send(success.as_bool());
+    });
+    // SAFETY: the reply block only sends on a channel, so it may run on any
+    // thread
+    unsafe {
+        context.evaluatePolicy_localizedReason_reply(policy, &NSString::from_str(reason), &reply)
+    };
+    rx.recv()
+        .map_err(|_| "Authentication prompt closed".to_string())
+}
+
+#[cfg(target_os = "windows")]
+async fn prompt(app: &AppHandle, _purpose: Purpose, reason: &str) -> Result<bool, String> {
+    use tauri::Manager;
+
+    let window = app
+        .get_webview_window("main")
+        .and_then(|w| w.hwnd().ok())
+        .map(|hwnd| hwnd.0 as isize);
+    let reason = reason.to_string();
+    tauri::async_runtime::spawn_blocking(move || windows_hello(&reason, window))
+        .await
+        .map_err(|e| format!("Authentication prompt failed: {}", e))?
+}
+
+#[cfg(target_os = "windows")]
+fn windows_hello(reason: &str, window: Option<isize>) -> Result<bool, String> {
+    use windows::core::{factory, HSTRING};
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+    use windows_future::IAsyncOperation;
+
+    let hello = |e: windows::core::Error| format!("Windows Hello failed: {}", e);
+    let availability = UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|op| op.get())
+        .map_err(hello)?;
+    if availability != UserConsentVerifierAvailability::Available {
+        return Err("Windows Hello is not set up on this device".to_string());
+    }
+    let message = HSTRING::from(reason);
+    let operation: IAsyncOperation<UserConsentVerificationResult> = match window {
+        // Owned by the app window so the prompt opens in front of it
+        Some(hwnd) => {
+            let interop =
+                factory::<UserConsentVerifier, IUserConsentVerifierInterop>().map_err(hello)?;
+            // SAFETY: hwnd is a live top-level window of this process
+            unsafe { interop.RequestVerificationForWindowAsync(HWND(hwnd as _), &message) }
+                .map_err(hello)?
+        }
+        None => UserConsentVerifier::RequestVerificationAsync(&message).map_err(hello)?,
+    };
+    Ok(operation.get().map_err(hello)? == UserConsentVerificationResult::Verified)
+}
+
+#[cfg(target_os = "linux")]
+async fn prompt(app: &AppHandle, purpose: Purpose, _reason: &str) -> Result<bool, String> {
+    // polkit agents show the action's own message, not ours. The start time
+    // pins the check to this process even if its pid is reused.
+    let stat = std::fs::read_to_string("/proc/self/stat")
+        .map_err(|e| format!("Failed to read the process start time: {}", e))?;
+    let start_time = start_time(&stat).ok_or("Unreadable /proc/self/stat")?;
+    let process = format!("{},{}", std::process::id(), start_time);
+    let output = crate::trace::command(app, "pkcheck")
+        .args([
+            "--action-id",
+            purpose.polkit_action(),
+            "--process",
+            process.as_str(),
+            "--allow-user-interaction",
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("pkcheck unavailable (is polkit installed?): {}", e))?;
+    // 0 authorized, 1 denied, 2 no agent to ask, 3 dismissed, 4 error
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) | Some(3) => Ok(false),
+        Some(2) => Err("No polkit authentication agent is running".to_string()),
+        _ => Err(format!(
+            "pkcheck failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Start time (field 22, in clock ticks since boot) from `/proc/<pid>/stat`;
+/// the command name before it may itself hold spaces and parentheses
+#[cfg(any(target_os = "linux", test))]
+fn start_time(stat: &str) -> Option<&str> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // Fields after the name start at 3 (state)
+    fields.split_whitespace().nth(22 - 3)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+async fn prompt(_app: &AppHandle, _purpose: Purpose, _reason: &str) -> Result<bool, String> {
+    Err("OS authentication is not supported on this platform".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/proc/<pid>/stat` of a process whose name holds a space and a `)`
+    const STAT: &str = "4242 (flow state)) S 1 4242 4242 0 -1 4194560 2811 0 0 0 \
+                        12 4 0 0 20 0 9 0 873312 1061142528 24390 18446744073709551615";
+
+    #[test]
+    fn start_time_is_field_22() {
+        assert_eq!(start_time(STAT), Some("873312"));
+        assert_eq!(start_time("4242 (flowstate) S 1"), None);
+        assert_eq!(start_time(""), None);
+    }
+}
@@ -0,0 +1,468 @@
+//! Optional OpenTelemetry export for self-hosters.
+//!
+//! Off by default. Users with an OTLP collector on their work machine turn it
+//! on in `otel.json` in the app config directory, a hand-edited file read
+//! again before every export, so changes apply without a restart:
+//!
+//! ```json
+//! { "enabled": true, "endpoint": "http://localhost:4318",
+//!   "headers": { "x-api-key": "..." }, "intervalSecs": 30 }
+//! ```
+//!
+//! Traced commands (`trace::scope`) and task sync runs become spans, and
+//! every interval the process memory, the stack supervisor phase, the sync
+//! queue and lag, and today's focus time and finished tasks are sent as
+//! gauges, both over OTLP/HTTP with JSON encoding. An
+//! organization policy with `disableTelemetry` turns the exporter off
+//! whatever the file says. Spans wait in a bounded buffer between exports;
+//! the oldest are dropped when the collector can't keep up.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::events::now_ms;
+
+const CONFIG_FILE: &str = "otel.json";
+const MAX_SPANS: usize = 2048;
+const MIN_INTERVAL_SECS: u64 = 5;
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a disabled exporter looks at the config file again
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Whether spans are collected; follows the config at each export
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static SPANS: Mutex<VecDeque<SpanRecord>> = Mutex::new(VecDeque::new());
+static LAST_EXPORT: Mutex<ExportResult> = Mutex::new(ExportResult {
+    at_ms: None,
+    error: None,
+});
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OtelConfig {
+    pub enabled: bool,
+    /// Collector base URL; `/v1/traces` and `/v1/metrics` are appended
+    pub endpoint: String,
+    pub headers: BTreeMap<String, String>,
+    pub service_name: String,
+    pub interval_secs: u64,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        OtelConfig {
+            enabled: false,
+            endpoint: "http://localhost:4318".to_string(),
+            headers: BTreeMap::new(),
+            service_name: "flowstate".to_string(),
+            interval_secs: 30,
+        }
+    }
+}
+
+struct SpanRecord {
+    name: String,
+    start_ms: u64,
+    duration_ms: u64,
+    error: Option<String>,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+pub(crate) struct Gauge {
+    pub name: &'static str,
+    /// UCUM unit, as OTLP expects
+    pub unit: &'static str,
+    pub value: f64,
+    pub attributes: Vec<(&'static str, Value)>,
+}
+
+impl Gauge {
+    fn new(name: &'static str, unit: &'static str, value: f64) -> Self {
+        Gauge {
+            name,
+            unit,
+            value,
+            attributes: Vec::new(),
+        }
+    }
+}
+
+struct ExportResult {
+    at_ms: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtelStatus {
+    /// Config file location, whether or not it exists
+    pub config_path: Option<PathBuf>,
+    pub enabled: bool,
+    pub disabled_by_policy: bool,
+    pub endpoint: String,
+    pub last_export_ms: Option<u64>,
+    pub last_error: Option<String>,
+    /// Spans dropped because the buffer was full
+    pub dropped_spans: u64,
+}
+
+fn config_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .ok()
+        .map(|dir| dir.join(CONFIG_FILE))
+}
+
+fn load_config(app: &AppHandle) -> OtelConfig {
+    let Some(content) = config_path(app).and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return OtelConfig::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid {}: {}", CONFIG_FILE, e);
+        OtelConfig::default()
+    })
+}
+
+fn disabled_by_policy() -> bool {
+    crate::policy::current().policy.disable_telemetry
+}
+
+/// Queue a finished operation as a span (a no-op while export is off)
+pub(crate) fn record_span(
+    name: &str,
+    start_ms: u64,
+    duration_ms: u64,
+    error: Option<String>,
+    attributes: Vec<(&'static str, Value)>,
+) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+    if spans.len() == MAX_SPANS {
+        spans.pop_front();
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    spans.push_back(SpanRecord {
+        name: name.to_string(),
+        start_ms,
+        duration_ms,
+        error,
+        attributes,
+    });
+}
+
+/// Random id of `bytes` bytes, hex encoded as OTLP/JSON expects
+fn hex_id(bytes: usize) -> String {
+    (0..bytes)
+        .map(|_| format!("{:02x}", rand::random::<u8>()))
+        .collect()
+}
+
+fn nanos(ms: u64) -> String {
+    (ms as u128 * 1_000_000).to_string()
+}
+
+/// OTLP JSON attribute value
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+fn attributes(pairs: &[(&'static str, Value)]) -> Value {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": any_value(value) }))
+        .collect()
+}
+
+fn resource(config: &OtelConfig) -> Value {
+    json!({
+        "attributes": attributes(&[
+            ("service.name", Value::from(config.service_name.as_str())),
+            ("service.version", Value::from(env!("CARGO_PKG_VERSION"))),
+            ("os.type", Value::from(std::env::consts::OS)),
+        ])
+    })
+}
+
+fn scope() -> Value {
+    json!({ "name": "flowstate", "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn traces_body(config: &OtelConfig, spans: &[SpanRecord]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let status = match &span.error {
+                // STATUS_CODE_ERROR
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 1 }),
+            };
+            json!({
+                "traceId": hex_id(16),
+                "spanId": hex_id(8),
+                "name": span.name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": nanos(span.start_ms),
+                "endTimeUnixNano": nanos(span.start_ms + span.duration_ms),
+                "attributes": attributes(&span.attributes),
+                "status": status,
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": resource(config),
+            "scopeSpans": [{ "scope": scope(), "spans": spans }],
+        }]
+    })
+}
+
+/// Local midnight of today on the app's clock, in ms since the epoch
+fn today_start_ms() -> i64 {
+    use chrono::TimeZone;
+
+    let midnight = crate::clock::now_local()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default();
+    chrono::Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or(0, |t| t.timestamp_millis())
+}
+
+/// Focus time and tasks done since local midnight, and the running focus
+/// phase and task timer
+fn activity_gauges(app: &AppHandle) -> Vec<Gauge> {
+    let focus = app.state::<crate::focus::FocusEngine>().snapshot();
+    let focusing = focus.status != crate::focus::FocusStatus::Idle
+        && focus.phase == crate::focus::FocusPhase::Focus;
+    let focus_elapsed_ms = if focusing {
+        focus.phase_duration_ms.saturating_sub(focus.remaining_ms)
+    } else {
+        0
+    };
+    let mut gauges = vec![Gauge::new(
+        "flowstate.focus.elapsed",
+        "s",
+        focus_elapsed_ms as f64 / 1000.0,
+    )];
+    match crate::time_tracking::active_timer(app) {
+        Ok(timer) => gauges.push(Gauge::new(
+            "flowstate.timer.elapsed",
+            "s",
+            timer.map_or(0, |entry| entry.duration_ms.max(0)) as f64 / 1000.0,
+        )),
+        Err(e) => log::debug!("No timer gauge: {}", e),
+    }
+
+    let today_ms = today_start_ms();
+    match crate::sessions::with_db(app, |conn| {
+        conn.query_row(
+            "SELECT coalesce(sum(duration_ms), 0) FROM focus_sessions WHERE started_at >= ?1",
+            [today_ms],
+            |row| row.get::<_, i64>(0),
+        )
+    }) {
+        Ok(ms) => gauges.push(Gauge::new(
+            "flowstate.focus.today",
+            "s",
+            (ms as u64 + focus_elapsed_ms) as f64 / 1000.0,
+        )),
+        Err(e) => log::debug!("No focus time gauge: {}", e),
+    }
+    match crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT json_extract(data, '$.completed_at') FROM tasks \
+             WHERE json_extract(data, '$.status') = 'done' \
+               AND coalesce(json_extract(data, '$.is_deleted'), 0) = 0",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, Option<String>>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    }) {
+        Ok(completed) => {
+            let today = completed
+                .iter()
+                .flatten()
+                .filter_map(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+                .filter(|at| at.timestamp_millis() >= today_ms)
+                .count();
+            gauges.push(Gauge::new(
+                "flowstate.tasks.completed_today",
+                "{task}",
+                today as f64,
+            ));
+        }
+        Err(e) => log::debug!("No task gauge: {}", e),
+    }
+    gauges
+}
+
+/// Health gauges: process resources, stack supervisor, sync queue and
+/// today's activity. Also served to Prometheus (`prometheus.rs`).
+pub(crate) fn gauges(app: &AppHandle) -> Vec<Gauge> {
+    let usage = crate::metrics::sample();
+    let supervisor = app.state::<crate::supervisor::Supervisor>().state();
+    let mut gauges = vec![
+        Gauge::new("process.memory.rss", "By", usage.rss_bytes as f64),
+        Gauge::new("process.cpu.utilization", "%", usage.cpu_percent as f64),
+        Gauge {
+            attributes: vec![(
+                "phase",
+                serde_json::to_value(supervisor.phase).unwrap_or(Value::Null),
+            )],
+            ..Gauge::new(
+                "flowstate.supabase.up",
+                "1",
+                (supervisor.phase == crate::supervisor::Phase::Running) as u8 as f64,
+            )
+        },
+    ];
+    match crate::offline::status(app) {
+        Ok(sync) => {
+            gauges.extend([
+                Gauge::new("flowstate.sync.online", "1", sync.online as u8 as f64),
+                Gauge::new("flowstate.sync.pending", "{edit}", sync.pending as f64),
+                Gauge::new("flowstate.sync.failed", "{edit}", sync.failed as f64),
+                Gauge::new("flowstate.sync.conflicts", "{edit}", sync.conflicts as f64),
+            ]);
+            if let Some(last) = sync.last_sync_at_ms {
+                gauges.push(Gauge::new(
+                    "flowstate.sync.lag",
+                    "s",
+                    now_ms().saturating_sub(last) as f64 / 1000.0,
+                ));
+            }
+        }
+        Err(e) => log::debug!("No sync gauges: {}", e),
+    }
+    gauges.extend(activity_gauges(app));
+    gauges
+}
+
+fn metrics_body(config: &OtelConfig, app: &AppHandle) -> Value {
+    let now = nanos(now_ms());
+    let metrics: Vec<Value> = gauges(app)
+        .into_iter()
+        .map(|gauge| {
+            json!({
+                "name": gauge.name,
+                "unit": gauge.unit,
+                "gauge": { "dataPoints": [{
+                    "timeUnixNano": now,
+                    "asDouble": gauge.value,
+                    "attributes": attributes(&gauge.attributes),
+                }] },
+            })
+        })
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(config),
+            "scopeMetrics": [{ "scope": scope(), "metrics": metrics }],
+        }]
+    })
+}
+
+async fn post(config: &OtelConfig, signal: &str, body: Value) -> Result<(), String> {
+    let url = format!("{}/v1/{}", config.endpoint.trim_end_matches('/'), signal);
+    let mut request = crate::health::client()?
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .timeout(EXPORT_TIMEOUT)
+        .body(body.to_string());
+    for (name, value) in &config.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "{} rejected the export: HTTP {}",
+            url,
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+async fn export(app: &AppHandle, config: &OtelConfig) -> Result<(), String> {
+    let spans: Vec<SpanRecord> = SPANS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+        .collect();
+    if !spans.is_empty() {
+        post(config, "traces", traces_body(config, &spans)).await?;
+    }
+    post(config, "metrics", metrics_body(config, app)).await
+}
+
+/// Export loop (a startup graph node); idles while the exporter is off
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = load_config(&app);
+            let enabled = config.enabled && !disabled_by_policy();
+            if ENABLED.swap(enabled, Ordering::Relaxed) != enabled {
+                log::info!(
+                    "OpenTelemetry export {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+            if !enabled {
+                SPANS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            tokio::time::sleep(Duration::from_secs(
+                config.interval_secs.max(MIN_INTERVAL_SECS),
+            ))
+            .await;
+            let result = export(&app, &config).await;
+            if let Err(e) = &result {
+                log::debug!("OpenTelemetry export failed: {}", e);
+            }
+            *LAST_EXPORT.lock().unwrap_or_else(|e| e.into_inner()) = ExportResult {
+                at_ms: Some(now_ms()),
+                error: result.err(),
+            };
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_otel_status(app: AppHandle) -> OtelStatus {
+    let config = load_config(&app);
+    let last = LAST_EXPORT.lock().unwrap_or_else(|e| e.into_inner());
+    OtelStatus {
+        config_path: config_path(&app),
+        enabled: config.enabled && !disabled_by_policy(),
+        disabled_by_policy: disabled_by_policy(),
+        endpoint: config.endpoint,
+        last_export_ms: last.at_ms,
+        last_error: last.error.clone(),
+        dropped_spans: DROPPED.load(Ordering::Relaxed),
+    }
+}
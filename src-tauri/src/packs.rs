@@ -0,0 +1,323 @@
+//! Theme packs: notification sounds, tray icon sets and overlay styles.
+//!
+//! A pack is a zip archive with a `pack.json` manifest at its root that lists
+//! every other file with its SHA-256. Installing checks the manifest, rejects
+//! files it doesn't list, unexpected file types and oversized archives, and
+//! verifies each hash while unpacking into a staging directory, which then
+//! replaces `packs/<id>/` in the app data directory in one rename. Listing
+//! re-hashes the files, so a pack changed on disk shows up as not intact.
+//! Changes publish `packs://changed`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+use crate::parsers::{self, PackManifest};
+
+const PACKS_DIR: &str = "packs";
+const MANIFEST: &str = "pack.json";
+const MAX_FILES: usize = 256;
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_PACK_SIZE: u64 = 64 * 1024 * 1024;
+const ALLOWED_EXTENSIONS: [&str; 8] = ["png", "ico", "wav", "ogg", "mp3", "css", "txt", "md"];
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledPack {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub author: Option<String>,
+    /// Event → absolute file path
+    pub sounds: BTreeMap<String, PathBuf>,
+    /// Tray state → absolute file path
+    pub tray_icons: BTreeMap<String, PathBuf>,
+    pub overlay_styles: Vec<PathBuf>,
+    pub size_bytes: u64,
+    /// Every file is present with the hash from the manifest
+    pub intact: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PacksChanged {
+    pub id: String,
+    pub installed: bool,
+}
+
+fn packs_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(PACKS_DIR))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Copy at most `limit` bytes of `reader` into `out`, returning the SHA-256
+/// and length, or an error if there was more
+fn copy_hashed(
+    reader: &mut impl Read,
+    out: &mut impl Write,
+    limit: u64,
+) -> Result<(String, u64), String> {
+    let mut hasher = Sha256::new();
+    let mut total = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut limited = reader.take(limit + 1);
+    loop {
+        let read = limited.read(&mut buf).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        if total > limit {
+            return Err(format!("larger than {} bytes", limit));
+        }
+        hasher.update(&buf[..read]);
+        out.write_all(&buf[..read]).map_err(|e| e.to_string())?;
+    }
+    Ok((hex(&hasher.finalize()), total))
+}
+
+fn resolve(dir: &Path, manifest: &PackManifest) -> InstalledPack {
+    let path = |file: &String| dir.join(file);
+    InstalledPack {
+        id: manifest.id.clone(),
+        name: manifest.name.clone(),
+        version: manifest.version.clone(),
+        author: manifest.author.clone(),
+        sounds: manifest
+            .sounds
+            .iter()
+            .map(|(k, v)| (k.clone(), path(v)))
+            .collect(),
+        tray_icons: manifest
+            .tray_icons
+            .iter()
+            .map(|(k, v)| (k.clone(), path(v)))
+            .collect(),
+        overlay_styles: manifest.overlay_styles.iter().map(path).collect(),
+        size_bytes: 0,
+        intact: false,
+    }
+}
+
+/// Validate the archive and unpack it into `staging`
+fn unpack(archive_path: &Path, staging: &Path) -> Result<PackManifest, String> {
+    let file = File::open(archive_path)
+        .map_err(|e| format!("Failed to open {}: {}", archive_path.display(), e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not a zip archive: {}", e))?;
+    if archive.len() > MAX_FILES {
+        return Err(format!("Pack has more than {} files", MAX_FILES));
+    }
+
+    let manifest = {
+        let mut entry = archive
+            .by_name(MANIFEST)
+            .map_err(|_| format!("Pack has no {}", MANIFEST))?;
+        let mut bytes = Vec::new();
+        entry
+            .by_ref()
+            .take(parsers::MAX_PACK_MANIFEST as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {}", MANIFEST, e))?;
+        parsers::parse_pack_manifest(&bytes).map_err(|e| format!("Invalid {}: {}", MANIFEST, e))?
+    };
+    for path in manifest.files.keys() {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        if !ALLOWED_EXTENSIONS.contains(&extension.as_str()) {
+            return Err(format!("{} is not an allowed file type", path));
+        }
+    }
+
+    let mut unpacked = 0u64;
+    let mut seen = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Corrupt archive: {}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        if name == MANIFEST {
+            continue;
+        }
+        let Some(expected) = manifest.files.get(&name) else {
+            return Err(format!("{} is not listed in {}", name, MANIFEST));
+        };
+        // Manifest paths are plain relative paths, but check what zip makes of it
+        if entry.enclosed_name().is_none() {
+            return Err(format!("Unsafe path in archive: {}", name));
+        }
+
+        let target = staging.join(&name);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut out = File::create(&target)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        let limit = MAX_FILE_SIZE.min(MAX_PACK_SIZE - unpacked);
+        let (hash, size) = copy_hashed(&mut entry, &mut out, limit)
+            .map_err(|e| format!("Failed to unpack {}: {}", name, e))?;
+        if !hash.eq_ignore_ascii_case(expected) {
+            return Err(format!("{} does not match its hash in {}", name, MANIFEST));
+        }
+        unpacked += size;
+        seen.push(name);
+    }
+
+    if let Some(missing) = manifest.files.keys().find(|path| !seen.contains(path)) {
+        return Err(format!("{} is listed in {} but missing", missing, MANIFEST));
+    }
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    std::fs::write(staging.join(MANIFEST), json)
+        .map_err(|e| format!("Failed to write {}: {}", MANIFEST, e))?;
+    Ok(manifest)
+}
+
+/// Read an installed pack and check its files against the manifest
+fn load(dir: &Path) -> Result<InstalledPack, String> {
+    let bytes = std::fs::read(dir.join(MANIFEST))
+        .map_err(|e| format!("Failed to read {}: {}", MANIFEST, e))?;
+    let manifest =
+        parsers::parse_pack_manifest(&bytes).map_err(|e| format!("Invalid {}: {}", MANIFEST, e))?;
+
+    let mut pack = resolve(dir, &manifest);
+    pack.intact = true;
+    for (path, expected) in &manifest.files {
+        let checked = File::open(dir.join(path))
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| copy_hashed(&mut file, &mut std::io::sink(), MAX_FILE_SIZE));
+        match checked {
+            Ok((hash, size)) => {
+                pack.size_bytes += size;
+                pack.intact &= hash.eq_ignore_ascii_case(expected);
+            }
+            Err(_) => pack.intact = false,
+        }
+    }
+    Ok(pack)
+}
+
+fn install(app: &AppHandle, archive: &Path) -> Result<InstalledPack, String> {
+    let root = packs_dir(app)?;
+    std::fs::create_dir_all(&root)
+        .map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+    let token: [u8; 8] = rand::random();
+    let staging = root.join(format!(".installing-{}", hex(&token)));
+
+    let manifest = match unpack(archive, &staging) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    // Swap in the new version; the old one is only deleted once it's out of
+    // the way
+    let target = root.join(&manifest.id);
+    let retired = root.join(format!(".removing-{}", hex(&token)));
+    if target.exists() {
+        std::fs::rename(&target, &retired)
+            .map_err(|e| format!("Failed to replace {}: {}", manifest.id, e))?;
+    }
+    if let Err(e) = std::fs::rename(&staging, &target) {
+        let _ = std::fs::rename(&retired, &target);
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(format!("Failed to install {}: {}", manifest.id, e));
+    }
+    let _ = std::fs::remove_dir_all(&retired);
+    load(&target)
+}
+
+/// Install (or upgrade) a theme pack from a zip archive
+#[tauri::command]
+pub async fn install_pack(app: AppHandle, path: String) -> Result<InstalledPack, FlowStateError> {
+    crate::trace::scope("install_pack", async move {
+        crate::read_only::ensure_writable(&app, "install_pack")?;
+        crate::app_lock::ensure_unlocked(&app, "install_pack")?;
+        let handle = app.clone();
+        let pack = tauri::async_runtime::spawn_blocking(move || install(&handle, Path::new(&path)))
+            .await
+            .map_err(|e| format!("Pack install failed: {}", e))??;
+
+        log::info!("Installed pack {} {}", pack.id, pack.version);
+        crate::events::publish(
+            &app,
+            "packs://changed",
+            &PacksChanged {
+                id: pack.id.clone(),
+                installed: true,
+            },
+        );
+        Ok(pack)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_installed_packs(app: AppHandle) -> Result<Vec<InstalledPack>, FlowStateError> {
+    let root = packs_dir(&app)?;
+    let packs = tauri::async_runtime::spawn_blocking(move || {
+        let Ok(entries) = std::fs::read_dir(&root) else {
+            return Vec::new();
+        };
+        let mut packs: Vec<InstalledPack> = entries
+            .flatten()
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| match load(&entry.path()) {
+                Ok(pack) => Some(pack),
+                Err(e) => {
+                    log::warn!("Skipping pack {}: {}", entry.path().display(), e);
+                    None
+                }
+            })
+            .collect();
+        packs.sort_by(|a, b| a.name.cmp(&b.name));
+        packs
+    })
+    .await
+    .map_err(|e| format!("Listing packs failed: {}", e))?;
+    Ok(packs)
+}
+
+#[tauri::command]
+pub fn remove_pack(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "remove_pack")?;
+    crate::app_lock::ensure_unlocked(&app, "remove_pack")?;
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(format!("Invalid pack id: {}", id).into());
+    }
+    let dir = packs_dir(&app)?.join(&id);
+    if !dir.is_dir() {
+        return Err(format!("Unknown pack: {}", id).into());
+    }
+    std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {}: {}", id, e))?;
+    crate::events::publish(
+        &app,
+        "packs://changed",
+        &PacksChanged {
+            id,
+            installed: false,
+        },
+    );
+    Ok(())
+}
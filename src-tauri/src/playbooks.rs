@@ -0,0 +1,376 @@
+//! Recovery playbooks for common local-stack failure states.
+//!
+//! A playbook is an ordered list of remediation and verification steps. The
+//! engine stops as soon as a verification step passes, pauses on steps marked
+//! as needing confirmation until the UI answers via `confirm_playbook_step`,
+//! and reports every transition on the `playbook://step` topic.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+
+use crate::error::FlowStateError;
+
+/// How long a confirmation gate waits for the user before aborting the run
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Copy)]
+enum StepAction {
+    VerifyDocker,
+    VerifyDatabase,
+    VerifySupabase,
+    StartDocker,
+    RestartDatabaseContainer,
+    RestartSupabase,
+    /// Nothing to execute; the description is shown to the user as advice
+    Suggest,
+}
+
+struct Step {
+    id: &'static str,
+    description: &'static str,
+    action: StepAction,
+    needs_confirmation: bool,
+}
+
+struct Playbook {
+    id: &'static str,
+    title: &'static str,
+    steps: &'static [Step],
+}
+
+const PLAYBOOKS: &[Playbook] = &[
+    Playbook {
+        id: "postgres-unhealthy",
+        title: "Database container unhealthy",
+        steps: &[
+            Step {
+                id: "check-db",
+                description: "Check the Postgres container health",
+                action: StepAction::VerifyDatabase,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "restart-db",
+                description: "Restart the Postgres container",
+                action: StepAction::RestartDatabaseContainer,
+                needs_confirmation: true,
+            },
+            Step {
+                id: "verify-db",
+                description: "Verify the Postgres container is healthy",
+                action: StepAction::VerifyDatabase,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "restart-stack",
+                description: "Restart the whole Supabase stack (supabase stop && supabase start)",
+                action: StepAction::RestartSupabase,
+                needs_confirmation: true,
+            },
+            Step {
+                id: "verify-stack",
+                description: "Verify Supabase is responding",
+                action: StepAction::VerifySupabase,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "suggest-prune",
+                description: "Still failing: free disk space with 'docker system prune' and try again",
+                action: StepAction::Suggest,
+                needs_confirmation: false,
+            },
+        ],
+    },
+    Playbook {
+        id: "supabase-unresponsive",
+        title: "Supabase not responding",
+        steps: &[
+            Step {
+                id: "check-stack",
+                description: "Check whether Supabase is responding",
+                action: StepAction::VerifySupabase,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "restart-stack",
+                description: "Restart the whole Supabase stack (supabase stop && supabase start)",
+                action: StepAction::RestartSupabase,
+                needs_confirmation: true,
+            },
+            Step {
+                id: "verify-stack",
+                description: "Verify Supabase is responding",
+                action: StepAction::VerifySupabase,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "suggest-logs",
+                description: "Still failing: check 'supabase status' and the container logs for port conflicts",
+                action: StepAction::Suggest,
+                needs_confirmation: false,
+            },
+        ],
+    },
+    Playbook {
+        id: "docker-down",
+        title: "Docker daemon not running",
+        steps: &[
+            Step {
+                id: "check-docker",
+                description: "Check whether the Docker daemon is running",
+                action: StepAction::VerifyDocker,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "start-docker",
+                description: "Start Docker Desktop",
+                action: StepAction::StartDocker,
+                needs_confirmation: true,
+            },
+            Step {
+                id: "verify-docker",
+                description: "Verify the Docker daemon is running",
+                action: StepAction::VerifyDocker,
+                needs_confirmation: false,
+            },
+            Step {
+                id: "suggest-manual",
+                description: "Still failing: start Docker manually from your applications menu",
+                action: StepAction::Suggest,
+                needs_confirmation: false,
+            },
+        ],
+    },
+];
+
+#[derive(Default)]
+pub struct PlaybookRunner {
+    next_run_id: AtomicU64,
+    pending: Mutex<HashMap<u64, oneshot::Sender<bool>>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybookInfo {
+    pub id: String,
+    pub title: String,
+    pub steps: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepEvent {
+    pub run_id: u64,
+    pub playbook_id: String,
+    pub step_id: String,
+    pub description: String,
+    /// running | awaiting_confirmation | succeeded | failed | declined | advice
+    pub status: String,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybookReport {
+    pub run_id: u64,
+    pub playbook_id: String,
+    /// resolved | unresolved | aborted
+    pub outcome: String,
+    pub steps: Vec<StepEvent>,
+}
+
+/// Run a step's action; Ok(true) means the action (or check) succeeded
+async fn execute(app: &AppHandle, action: StepAction) -> Result<bool, String> {
+    match action {
+        StepAction::VerifyDocker => Ok(crate::check_docker_status(app.clone()).await?.is_running()),
+        StepAction::VerifySupabase => Ok(crate::check_supabase_status(app.clone())
+            .await?
+            .is_running()),
+        StepAction::VerifyDatabase => {
+            let container = crate::db_container_name();
+            let output = crate::trace::command(app, "docker")
+                .args([
+                    "inspect",
+                    "--format",
+                    "{{.State.Health.Status}}",
+                    container.as_str(),
+                ])
+                .output()
+                .await
+                .map_err(|e| format!("Failed to run docker: {}", e))?;
+            let health = crate::parsers::parse_cli_line(&output.stdout).unwrap_or_default();
+            Ok(health == "healthy")
+        }
+        StepAction::StartDocker => {
+            crate::start_docker_desktop(app.clone(), None, None).await?;
+            Ok(true)
+        }
+        StepAction::RestartDatabaseContainer => {
+            let container = crate::db_container_name();
+            let output = crate::trace::command(app, "docker")
+                .args(["restart", container.as_str()])
+                .output()
+                .await
+                .map_err(|e| format!("Failed to run docker: {}", e))?;
+            Ok(output.status.success())
+        }
+        StepAction::RestartSupabase => {
+            // A failed stop is fine (stack may already be down), start must succeed
+            let _ = crate::stop_supabase(app.clone()).await;
+            crate::start_supabase(app.clone()).await?;
+            Ok(true)
+        }
+        StepAction::Suggest => Ok(true),
+    }
+}
+
+fn is_verification(action: StepAction) -> bool {
+    matches!(
+        action,
+        StepAction::VerifyDocker | StepAction::VerifyDatabase | StepAction::VerifySupabase
+    )
+}
+
+/// Pause until the UI approves or declines the step (or the gate times out)
+async fn wait_for_confirmation(app: &AppHandle, run_id: u64) -> bool {
+    let (tx, rx) = oneshot::channel();
+    {
+        let runner = app.state::<PlaybookRunner>();
+        let mut pending = runner.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.insert(run_id, tx);
+    }
+
+    let approved = matches!(
+        tokio::time::timeout(CONFIRMATION_TIMEOUT, rx).await,
+        Ok(Ok(true))
+    );
+
+    let runner = app.state::<PlaybookRunner>();
+    let mut pending = runner.pending.lock().unwrap_or_else(|e| e.into_inner());
+    pending.remove(&run_id);
+
+    approved
+}
+
+/// List the available recovery playbooks
+#[tauri::command]
+pub fn list_playbooks() -> Vec<PlaybookInfo> {
+    PLAYBOOKS
+        .iter()
+        .map(|p| PlaybookInfo {
+            id: p.id.to_string(),
+            title: p.title.to_string(),
+            steps: p.steps.iter().map(|s| s.description.to_string()).collect(),
+        })
+        .collect()
+}
+
+/// Run a recovery playbook, emitting `playbook://step` events as it goes
+#[tauri::command]
+pub async fn run_playbook(
+    app: AppHandle,
+    playbook_id: String,
+) -> Result<PlaybookReport, FlowStateError> {
+    crate::trace::scope("run_playbook", async move {
+        crate::read_only::ensure_writable(&app, "run_playbook")?;
+
+        let playbook = PLAYBOOKS
+            .iter()
+            .find(|p| p.id == playbook_id)
+            .ok_or_else(|| format!("Unknown playbook: {}", playbook_id))?;
+
+        let run_id = app
+            .state::<PlaybookRunner>()
+            .next_run_id
+            .fetch_add(1, Ordering::SeqCst)
+            + 1;
+        log::info!("Running playbook {} (run {})", playbook.id, run_id);
+
+        let mut steps = Vec::new();
+        let mut report = |app: &AppHandle, step: &Step, status: &str, detail: Option<String>| {
+            let event = StepEvent {
+                run_id,
+                playbook_id: playbook.id.to_string(),
+                step_id: step.id.to_string(),
+                description: step.description.to_string(),
+                status: status.to_string(),
+                detail,
+            };
+            crate::events::publish(app, "playbook://step", &event);
+            steps.push(event);
+        };
+
+        let mut outcome = "unresolved";
+
+        for step in playbook.steps {
+            if matches!(step.action, StepAction::Suggest) {
+                report(&app, step, "advice", None);
+                continue;
+            }
+
+            if step.needs_confirmation {
+                report(&app, step, "awaiting_confirmation", None);
+                if !wait_for_confirmation(&app, run_id).await {
+                    report(&app, step, "declined", None);
+                    outcome = "aborted";
+                    break;
+                }
+            }
+
+            report(&app, step, "running", None);
+            match execute(&app, step.action).await {
+                Ok(true) => {
+                    report(&app, step, "succeeded", None);
+                    // A passing check (including the initial diagnosis) means there
+                    // is nothing left to fix
+                    if is_verification(step.action) {
+                        outcome = "resolved";
+                        break;
+                    }
+                }
+                Ok(false) => report(&app, step, "failed", None),
+                Err(e) => report(&app, step, "failed", Some(e)),
+            }
+        }
+
+        log::info!(
+            "Playbook {} (run {}) finished: {}",
+            playbook.id,
+            run_id,
+            outcome
+        );
+
+        Ok(PlaybookReport {
+            run_id,
+            playbook_id: playbook.id.to_string(),
+            outcome: outcome.to_string(),
+            steps,
+        })
+    })
+    .await
+}
+
+/// Answer a pending confirmation gate of a running playbook
+#[tauri::command]
+pub fn confirm_playbook_step(
+    runner: tauri::State<'_, PlaybookRunner>,
+    run_id: u64,
+    approve: bool,
+) -> Result<(), FlowStateError> {
+    let sender = runner
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&run_id)
+        .ok_or_else(|| format!("Playbook run {} is not waiting for confirmation", run_id))?;
+
+    sender
+        .send(approve)
+        .map_err(|_| format!("Playbook run {} is no longer running", run_id).into())
+}
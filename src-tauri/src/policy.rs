@@ -0,0 +1,120 @@
+//! Admin-managed organization policy.
+//!
+//! IT departments can drop a `policy.json` into a machine-wide location that
+//! users can't write to. It is read once at startup and is read-only for the
+//! app: it can pin the update channel, turn off telemetry, AI features and
+//! cloud sync, preconfigure the remote backend and pin data retention rules
+//! (`retention.rs`). The frontend reads it with
+//! `get_org_policy` and must treat it as overriding user settings.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+const POLICY_FILE: &str = "policy.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RemoteBackend {
+    pub url: String,
+    pub anon_key: String,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OrgPolicy {
+    /// e.g. "stable"; users can't switch channels when set
+    pub update_channel: Option<String>,
+    #[serde(default)]
+    pub disable_telemetry: bool,
+    #[serde(default)]
+    pub disable_ai: bool,
+    #[serde(default)]
+    pub disable_cloud_sync: bool,
+    pub remote_backend: Option<RemoteBackend>,
+    /// Rules set here override the user's
+    #[serde(default)]
+    pub retention: crate::retention::RetentionRules,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyState {
+    /// File the policy was loaded from; None when unmanaged
+    pub source: Option<PathBuf>,
+    pub policy: OrgPolicy,
+    /// Set when a policy file exists but could not be applied
+    pub error: Option<String>,
+}
+
+static POLICY: OnceLock<PolicyState> = OnceLock::new();
+
+/// Machine-wide policy location (admin-writable only)
+fn policy_path() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var("PROGRAMDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(r"C:\ProgramData"))
+            .join("FlowState")
+            .join(POLICY_FILE)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        PathBuf::from("/Library/Application Support/FlowState").join(POLICY_FILE)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        PathBuf::from("/etc/flowstate").join(POLICY_FILE)
+    }
+}
+
+fn load() -> PolicyState {
+    let path = policy_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => {
+            return PolicyState {
+                source: None,
+                policy: OrgPolicy::default(),
+                error: None,
+            }
+        }
+    };
+
+    match serde_json::from_str::<OrgPolicy>(&content) {
+        Ok(policy) => {
+            log::info!("Organization policy loaded from {}", path.display());
+            PolicyState {
+                source: Some(path),
+                policy,
+                error: None,
+            }
+        }
+        Err(e) => {
+            // Fail closed on the switches an admin most likely meant to set
+            log::error!("Invalid organization policy {}: {}", path.display(), e);
+            PolicyState {
+                source: Some(path),
+                policy: OrgPolicy {
+                    disable_telemetry: true,
+                    disable_ai: true,
+                    disable_cloud_sync: true,
+                    ..OrgPolicy::default()
+                },
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Policy in effect for this run (loaded on first use)
+pub fn current() -> &'static PolicyState {
+    POLICY.get_or_init(load)
+}
+
+#[tauri::command]
+pub fn get_org_policy() -> PolicyState {
+    current().clone()
+}
@@ -0,0 +1,133 @@
+//! Preseeded configuration for silent enterprise installs.
+//!
+//! On first launch the backend looks for `flowstate-preseed.json` (path from
+//! `FLOWSTATE_PRESEED`, next to the executable, or in the machine-wide
+//! FlowState directory where an MSI custom action can copy it from beside the
+//! installer). The preseed is copied into the app's own store, so it survives
+//! the installer directory going away, and the frontend reads it with
+//! `get_preseed_config` to skip the interactive setup wizard.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+const PRESEED_FILE: &str = "flowstate-preseed.json";
+pub(crate) const PRESEED_STORE: &str = "preseed.json";
+const PRESEED_KEY: &str = "preseed";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preseed {
+    pub server_url: Option<String>,
+    pub anon_key: Option<String>,
+    pub workspace: Option<String>,
+    /// Identity provider / email domain to preselect on the login screen
+    pub sso_hint: Option<String>,
+    /// Skip the interactive setup wizard (defaults to true when a preseed exists)
+    #[serde(default = "default_skip_wizard")]
+    pub skip_wizard: bool,
+}
+
+fn default_skip_wizard() -> bool {
+    true
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedPreseed {
+    pub source: PathBuf,
+    pub applied_at_ms: u64,
+    pub config: Preseed,
+}
+
+fn candidates() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Ok(path) = std::env::var("FLOWSTATE_PRESEED") {
+        paths.push(PathBuf::from(path));
+    }
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+    {
+        paths.push(dir.join(PRESEED_FILE));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(data) = std::env::var("PROGRAMDATA") {
+            paths.push(PathBuf::from(data).join("FlowState").join(PRESEED_FILE));
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        paths.push(PathBuf::from("/Library/Application Support/FlowState").join(PRESEED_FILE));
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        paths.push(PathBuf::from("/etc/flowstate").join(PRESEED_FILE));
+    }
+
+    paths
+}
+
+/// Apply a preseed file on first launch (a no-op once one has been applied)
+pub fn init(app: &AppHandle) {
+    let store = match app.store(PRESEED_STORE) {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Failed to open {}: {}", PRESEED_STORE, e);
+            return;
+        }
+    };
+    if store.has(PRESEED_KEY) {
+        return;
+    }
+
+    for path in candidates() {
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        match serde_json::from_str::<Preseed>(&content) {
+            Ok(config) => {
+                let applied = AppliedPreseed {
+                    source: path.clone(),
+                    applied_at_ms: crate::events::now_ms(),
+                    config,
+                };
+                match serde_json::to_value(&applied) {
+                    Ok(value) => store.set(PRESEED_KEY, value),
+                    Err(e) => {
+                        log::error!("Failed to serialize preseed: {}", e);
+                        return;
+                    }
+                }
+                if let Err(e) = store.save() {
+                    log::error!("Failed to save {}: {}", PRESEED_STORE, e);
+                }
+                log::info!("Applied preseed configuration from {}", path.display());
+            }
+            Err(e) => log::error!("Invalid preseed file {}: {}", path.display(), e),
+        }
+        return;
+    }
+}
+
+/// Preseeded configuration applied on first launch, if any
+#[tauri::command]
+pub fn get_preseed_config(app: AppHandle) -> Result<Option<AppliedPreseed>, FlowStateError> {
+    let store = app
+        .store(PRESEED_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", PRESEED_STORE, e))?;
+
+    store
+        .get(PRESEED_KEY)
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| format!("Invalid stored preseed: {}", e).into())
+}
@@ -0,0 +1,55 @@
+//! Screen-share safe mode.
+//!
+//! One backend flag that every surface follows: the notification engine swaps
+//! titles for generic text ("1 reminder") and drops item text from digests,
+//! and the frontend blurs sensitive fields on `app://privacy-mode` (also part
+//! of the state snapshot, so reloaded windows come up masked).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+#[derive(Default)]
+pub struct PrivacyMode {
+    enabled: AtomicBool,
+}
+
+impl PrivacyMode {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivacyState {
+    pub enabled: bool,
+}
+
+#[tauri::command]
+pub fn get_privacy_mode(mode: tauri::State<'_, PrivacyMode>) -> PrivacyState {
+    PrivacyState {
+        enabled: mode.is_enabled(),
+    }
+}
+
+/// Turn screen-share safe mode on or off for all windows at once
+#[tauri::command]
+pub fn privacy_mode(app: AppHandle, enabled: bool) -> PrivacyState {
+    let previous = app
+        .state::<PrivacyMode>()
+        .enabled
+        .swap(enabled, Ordering::SeqCst);
+    let state = PrivacyState { enabled };
+
+    if previous != enabled {
+        log::info!(
+            "Privacy mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+        crate::events::publish(&app, "app://privacy-mode", &state);
+    }
+
+    state
+}
@@ -0,0 +1,184 @@
+//! Building external command invocations.
+//!
+//! Subprocesses get their program and arguments as separate values, never a
+//! command line for a shell to split: `trace::command` for tools on the PATH,
+//! `spawn_detached` for apps that outlive the call. Values that come from the
+//! user or from disk go through `operand` first, so they can't be read as a
+//! flag. Docker Desktop's install location is read from the registry on
+//! Windows instead of assuming `C:\Program Files`.
+
+use std::path::PathBuf;
+
+/// `value` as a positional argument or flag value: trimmed, and rejected if
+/// empty, if it would be read as a flag, or if it holds control characters
+pub fn operand<'a>(value: &'a str, what: &str) -> Result<&'a str, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("{} is empty", what));
+    }
+    if value.starts_with('-') || value.chars().any(char::is_control) {
+        return Err(format!("Invalid {}: {:?}", what, value));
+    }
+    Ok(value)
+}
+
+/// Read a string value under HKEY_LOCAL_MACHINE
+#[cfg(target_os = "windows")]
+fn registry_string(key: &str, value: &str) -> Option<std::ffi::OsString> {
+    use std::ffi::{OsStr, OsString};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+
+    let wide = |s: &str| -> Vec<u16> { OsStr::new(s).encode_wide().chain(Some(0)).collect() };
+    let (key, value) = (wide(key), wide(value));
+    let mut buffer = vec![0u16; 512];
+
+    loop {
+        let mut size = (buffer.len() * 2) as u32;
+        // SAFETY: key and value are NUL-terminated, and size is the byte
+        // length of buffer
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        match status {
+            ERROR_SUCCESS => {
+                let len = (size as usize / 2).min(buffer.len());
+                let end = buffer[..len].iter().position(|&c| c == 0).unwrap_or(len);
+                return Some(OsString::from_wide(&buffer[..end]));
+            }
+            ERROR_MORE_DATA if (size as usize / 2) > buffer.len() => {
+                buffer.resize(size as usize / 2, 0);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// `exe` in the first of `install_dirs` that has it, skipping the ones that
+/// weren't found
+#[cfg(any(target_os = "windows", test))]
+fn exe_in(
+    install_dirs: impl IntoIterator<Item = Option<std::ffi::OsString>>,
+    exe: &str,
+    exists: impl Fn(&std::path::Path) -> bool,
+) -> Option<PathBuf> {
+    install_dirs
+        .into_iter()
+        .flatten()
+        .map(|dir| PathBuf::from(dir).join(exe))
+        .find(|path| exists(path))
+}
+
+/// Docker Desktop's executable, if installed
+pub fn docker_desktop_exe() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        const EXE: &str = "Docker Desktop.exe";
+        let install_dirs = [
+            registry_string(r"SOFTWARE\Docker Inc.\Docker\1.0", "AppPath"),
+            registry_string(
+                r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Docker Desktop",
+                "InstallLocation",
+            ),
+            std::env::var_os("ProgramFiles").map(|dir| {
+                PathBuf::from(dir)
+                    .join("Docker")
+                    .join("Docker")
+                    .into_os_string()
+            }),
+        ];
+        exe_in(install_dirs, EXE, std::path::Path::is_file)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+/// Start `program` with its own process group and no console or standard
+/// streams, without waiting for it (the replacement for `cmd /c start`)
+#[cfg(target_os = "windows")]
+pub fn spawn_detached(program: &std::path::Path, args: &[&str]) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+    use windows_sys::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    if let Some(id) = crate::trace::current_request_id() {
+        log::info!("exec {}", program.display());
+        cmd.env("FLOWSTATE_REQUEST_ID", id.to_string());
+    }
+    cmd.spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run {}: {}", program.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn operands_are_trimmed() {
+        assert_eq!(operand("  abcd1234\t", "project ref"), Ok("abcd1234"));
+        assert_eq!(
+            operand("C:\\Program Files\\Docker", "path"),
+            Ok("C:\\Program Files\\Docker")
+        );
+        assert_eq!(operand("my-function", "name"), Ok("my-function"));
+    }
+
+    #[test]
+    fn operands_cannot_pass_as_flags() {
+        for value in ["-x", "--project-ref=other", " --debug", "--"] {
+            assert_eq!(
+                operand(value, "project ref"),
+                Err(format!("Invalid project ref: {:?}", value.trim())),
+            );
+        }
+    }
+
+    #[test]
+    fn empty_and_control_operands_are_rejected() {
+        assert_eq!(operand("", "name"), Err("name is empty".to_string()));
+        assert_eq!(operand(" \n ", "name"), Err("name is empty".to_string()));
+        for value in ["ab\ncd", "ab\0", "a\u{1b}[2J"] {
+            assert!(operand(value, "name").is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn finds_the_exe_in_the_first_install_dir_that_has_it() {
+        let dirs = || {
+            [
+                None,
+                Some("/registry".into()),
+                Some("/program-files".into()),
+            ]
+        };
+        let found = |path: &Path| path.starts_with("/program-files");
+        assert_eq!(
+            exe_in(dirs(), "Docker Desktop.exe", found),
+            Some(PathBuf::from("/program-files/Docker Desktop.exe"))
+        );
+        assert_eq!(
+            exe_in(dirs(), "Docker Desktop.exe", |_| true),
+            Some(PathBuf::from("/registry/Docker Desktop.exe"))
+        );
+        assert_eq!(exe_in(dirs(), "Docker Desktop.exe", |_| false), None);
+    }
+}
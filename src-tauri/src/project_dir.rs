@@ -0,0 +1,139 @@
+//! Configured Supabase project directory.
+//!
+//! The Supabase CLI resolves the project from its working directory, so running
+//! it from wherever the app was launched made `status`/`start`/`stop` fail
+//! unpredictably. Once set, every `supabase` subprocess runs in this directory.
+//! The setting persists in `supabase-project.json`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::Command;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+
+pub(crate) const PROJECT_STORE: &str = "supabase-project.json";
+const PROJECT_KEY: &str = "projectDir";
+
+static PROJECT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDirInfo {
+    pub path: Option<PathBuf>,
+    pub valid: bool,
+}
+
+/// Configured project directory, if any
+pub fn get() -> Option<PathBuf> {
+    PROJECT_DIR
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+fn set(dir: Option<PathBuf>) {
+    *PROJECT_DIR.lock().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+/// Resolve a path relative to the project directory (or the process cwd when unset)
+pub fn resolve(relative: &str) -> PathBuf {
+    match get() {
+        Some(dir) => dir.join(relative),
+        None => PathBuf::from(relative),
+    }
+}
+
+fn validate(dir: &Path) -> Result<(), String> {
+    if !dir.join("supabase").join("config.toml").is_file() {
+        return Err(format!(
+            "{} is not a Supabase project (supabase/config.toml not found)",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Run supabase subprocesses from the configured project directory (or the
+/// per-user namespace's workdir, see multi_user.rs)
+pub fn apply_cwd(program: &str, cmd: Command) -> Command {
+    match (program, crate::multi_user::workdir().or_else(get)) {
+        ("supabase", Some(dir)) => cmd.current_dir(dir),
+        _ => cmd,
+    }
+}
+
+/// Restore the persisted directory (ignored if it no longer holds a project)
+pub fn init(app: &AppHandle) {
+    let store = match app.store(PROJECT_STORE) {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Failed to open {}: {}", PROJECT_STORE, e);
+            return;
+        }
+    };
+
+    let Some(dir) = store
+        .get(PROJECT_KEY)
+        .and_then(|v| v.as_str().map(PathBuf::from))
+    else {
+        return;
+    };
+
+    match validate(&dir) {
+        Ok(()) => {
+            log::info!("Supabase project directory: {}", dir.display());
+            set(Some(dir));
+        }
+        Err(e) => log::warn!("Ignoring saved project directory: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_supabase_project_dir() -> ProjectDirInfo {
+    let path = get();
+    ProjectDirInfo {
+        valid: path.as_deref().is_some_and(|p| validate(p).is_ok()),
+        path,
+    }
+}
+
+/// Set (or clear with None) the directory all Supabase CLI commands run in
+#[tauri::command]
+pub fn set_supabase_project_dir(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<ProjectDirInfo, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_supabase_project_dir")?;
+    let store = app
+        .store(PROJECT_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", PROJECT_STORE, e))?;
+
+    let dir = match path {
+        Some(p) => {
+            let dir = PathBuf::from(p);
+            validate(&dir)?;
+            store.set(PROJECT_KEY, dir.to_string_lossy().to_string());
+            Some(dir)
+        }
+        None => {
+            store.delete(PROJECT_KEY);
+            None
+        }
+    };
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", PROJECT_STORE, e))?;
+
+    log::info!("Supabase project directory set to {:?}", dir);
+    set(dir.clone());
+    crate::endpoints::invalidate(&app);
+
+    Ok(ProjectDirInfo {
+        valid: dir.is_some(),
+        path: dir,
+    })
+}
@@ -0,0 +1,129 @@
+//! `/metrics` on the local API (`local_api.rs`) for Prometheus scrapers.
+//!
+//! The gauges are the ones the OpenTelemetry export sends (`otel::gauges`),
+//! in the Prometheus text format and named the Prometheus way:
+//! `flowstate.sync.lag` in seconds becomes `flowstate_sync_lag_seconds`.
+//! Scrapers authenticate like any other client, with the token as a bearer
+//! token (`authorization: { credentials: <token> }` in the scrape config).
+//! Everything is computed on each scrape; nothing is kept in between.
+
+use serde_json::Value;
+use tauri::AppHandle;
+
+use crate::local_api::{Request, Response};
+use crate::otel::Gauge;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Metric name for an OTLP gauge name and its UCUM unit
+fn metric_name(name: &str, unit: &str) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let suffix = match unit {
+        "By" => "_bytes",
+        "s" => "_seconds",
+        "%" => "_percent",
+        _ => "",
+    };
+    format!("{}{}", base, suffix)
+}
+
+fn label_value(value: &Value) -> String {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Text exposition of `gauges`; samples of one metric stay together under
+/// one TYPE line
+fn render(gauges: &[Gauge]) -> String {
+    let mut names: Vec<String> = Vec::new();
+    let mut samples: Vec<Vec<String>> = Vec::new();
+    for gauge in gauges {
+        let name = metric_name(gauge.name, gauge.unit);
+        let labels = if gauge.attributes.is_empty() {
+            String::new()
+        } else {
+            let pairs: Vec<String> = gauge
+                .attributes
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, label_value(value)))
+                .collect();
+            format!("{{{}}}", pairs.join(","))
+        };
+        let sample = format!("{}{} {}", name, labels, gauge.value);
+        match names.iter().position(|n| *n == name) {
+            Some(i) => samples[i].push(sample),
+            None => {
+                names.push(name);
+                samples.push(vec![sample]);
+            }
+        }
+    }
+    let mut out = String::new();
+    for (name, samples) in names.iter().zip(samples) {
+        out.push_str(&format!("# TYPE {} gauge\n", name));
+        for sample in samples {
+            out.push_str(&sample);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+pub(crate) fn handle(app: &AppHandle, request: &Request) -> Response {
+    if request.method != "GET" {
+        return Response::text(405, "Only GET").header("Allow", "GET");
+    }
+    Response::new(200).body(CONTENT_TYPE, render(&crate::otel::gauges(app)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn gauge(name: &'static str, unit: &'static str, value: f64) -> Gauge {
+        Gauge {
+            name,
+            unit,
+            value,
+            attributes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn renders_gauges_in_the_text_format() {
+        let gauges = [
+            gauge("process.memory.rss", "By", 1048576.0),
+            gauge("flowstate.sync.lag", "s", 12.5),
+            Gauge {
+                attributes: vec![("phase", json!("running"))],
+                ..gauge("flowstate.supabase.up", "1", 1.0)
+            },
+            Gauge {
+                attributes: vec![("phase", json!("say \"hi\""))],
+                ..gauge("flowstate.supabase.up", "1", 0.0)
+            },
+            gauge("flowstate.tasks.completed_today", "{task}", 3.0),
+        ];
+        assert_eq!(
+            render(&gauges),
+            "# TYPE process_memory_rss_bytes gauge\n\
+             process_memory_rss_bytes 1048576\n\
+             # TYPE flowstate_sync_lag_seconds gauge\n\
+             flowstate_sync_lag_seconds 12.5\n\
+             # TYPE flowstate_supabase_up gauge\n\
+             flowstate_supabase_up{phase=\"running\"} 1\n\
+             flowstate_supabase_up{phase=\"say \\\"hi\\\"\"} 0\n\
+             # TYPE flowstate_tasks_completed_today gauge\n\
+             flowstate_tasks_completed_today 3\n"
+        );
+    }
+}
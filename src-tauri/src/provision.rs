@@ -0,0 +1,447 @@
+//! Provisioning a hosted Supabase project for cloud sync.
+//!
+//! `provision_remote_project` replaces the manual dashboard steps with calls
+//! to the Supabase management API, authorized by the user's personal access
+//! token: it creates a project in the given organization and region, waits
+//! for it to come up, applies the migrations of the configured project
+//! directory (recording them the way `supabase migration` does), sets the
+//! edge function secrets and stores the project's keys and database password
+//! in the keychain (`secrets.rs`). Only the project ref, URL and region go
+//! into `remote-project.json`. Each step publishes `provision://progress`.
+//! The access token is used for this call only and is never stored.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tauri_plugin_http::reqwest;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+
+const MANAGEMENT_API: &str = "https://api.supabase.com/v1";
+pub(crate) const REMOTE_STORE: &str = "remote-project.json";
+const REMOTE_KEY: &str = "project";
+pub const REMOTE_ANON_KEY: &str = "remote.anon_key";
+pub const REMOTE_SERVICE_ROLE_KEY: &str = "remote.service_role_key";
+pub const REMOTE_DB_PASSWORD: &str = "remote.db_password";
+const DEFAULT_PROJECT_NAME: &str = "flowstate";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const READY_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Project statuses that won't turn healthy by waiting
+const FAILED_STATUSES: [&str; 4] = ["INIT_FAILED", "REMOVED", "PAUSED", "INACTIVE"];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteProject {
+    #[serde(rename = "ref")]
+    pub project_ref: String,
+    pub name: String,
+    pub organization: String,
+    pub region: String,
+    pub api_url: String,
+    /// Migration versions applied while provisioning
+    pub migrations: Vec<String>,
+    pub created_at_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionProgress {
+    /// creating | waiting | schema | secrets | keys | done
+    pub step: &'static str,
+    pub detail: Option<String>,
+}
+
+fn progress(app: &AppHandle, step: &'static str, detail: Option<String>) {
+    crate::events::publish(
+        app,
+        "provision://progress",
+        &ProvisionProgress { step, detail },
+    );
+}
+
+/// Management API client for one access token
+struct Management<'a> {
+    http: &'a reqwest::Client,
+    token: &'a str,
+}
+
+impl Management<'_> {
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let mut request = self
+            .http
+            .request(method, format!("{}{}", MANAGEMENT_API, path))
+            .bearer_auth(self.token)
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Supabase management API: {}", e))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read the management API response: {}", e))?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|v| v.get("message").and_then(Value::as_str).map(str::to_string))
+                .unwrap_or(body);
+            return Err(format!(
+                "{} failed (HTTP {}): {}",
+                path,
+                status.as_u16(),
+                message.trim()
+            ));
+        }
+        if body.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&body).map_err(|e| format!("Invalid response from {}: {}", path, e))
+    }
+
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        self.send(reqwest::Method::GET, path, None).await
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Value, String> {
+        self.send(reqwest::Method::POST, path, Some(body)).await
+    }
+
+    async fn query(&self, project_ref: &str, sql: &str) -> Result<Value, String> {
+        self.post(
+            &format!("/projects/{}/database/query", project_ref),
+            json!({ "query": sql }),
+        )
+        .await
+    }
+}
+
+fn validate(value: &str, what: &str) -> Result<(), String> {
+    let valid = !value.is_empty()
+        && value.len() <= 64
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid {}: {:?}", what, value))
+    }
+}
+
+/// Edge function secret names; `SUPABASE_` ones are set by Supabase itself
+fn validate_secret_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with("SUPABASE_")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid secret name: {:?}", name))
+    }
+}
+
+/// supabase/migrations/*.sql of the project directory in version order, as
+/// (version, name, path)
+fn migration_files() -> Result<Vec<(String, String, PathBuf)>, String> {
+    let dir = crate::project_dir::get()
+        .ok_or_else(|| {
+            "Set the Supabase project directory first (set_supabase_project_dir)".to_string()
+        })?
+        .join("supabase")
+        .join("migrations");
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut files: Vec<(String, String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file = entry.file_name().into_string().ok()?;
+            let stem = file.strip_suffix(".sql")?;
+            let (version, name) = stem.split_once('_').unwrap_or((stem, ""));
+            version
+                .chars()
+                .all(|c| c.is_ascii_digit())
+                .then(|| (version.to_string(), name.to_string(), entry.path()))
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("No migrations in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Wait until the project reports ACTIVE_HEALTHY
+async fn wait_until_ready(
+    app: &AppHandle,
+    api: &Management<'_>,
+    project_ref: &str,
+) -> Result<(), String> {
+    let started = Instant::now();
+    let mut last_status = String::new();
+    loop {
+        let project = api.get(&format!("/projects/{}", project_ref)).await?;
+        let status = project
+            .get("status")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if status == "ACTIVE_HEALTHY" {
+            return Ok(());
+        }
+        if FAILED_STATUSES.contains(&status.as_str()) {
+            return Err(format!("Project {} is {}", project_ref, status));
+        }
+        if status != last_status {
+            progress(app, "waiting", Some(status.clone()));
+            last_status = status;
+        }
+        if started.elapsed() > READY_TIMEOUT {
+            return Err(format!(
+                "Project {} was not ready after {} minutes",
+                project_ref,
+                READY_TIMEOUT.as_secs() / 60
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Apply the migrations and record them for the Supabase CLI; returns the
+/// applied versions
+async fn apply_schema(
+    app: &AppHandle,
+    api: &Management<'_>,
+    project_ref: &str,
+    files: &[(String, String, PathBuf)],
+) -> Result<Vec<String>, String> {
+    api.query(
+        project_ref,
+        "create schema if not exists supabase_migrations; \
+         create table if not exists supabase_migrations.schema_migrations \
+         (version text primary key, statements text[], name text);",
+    )
+    .await?;
+    let mut applied = Vec::with_capacity(files.len());
+    for (version, name, path) in files {
+        progress(app, "schema", Some(format!("{}_{}", version, name)));
+        let sql = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        api.query(project_ref, &sql)
+            .await
+            .map_err(|e| format!("Migration {} failed: {}", version, e))?;
+        api.query(
+            project_ref,
+            &format!(
+                "insert into supabase_migrations.schema_migrations (version, name) \
+                 values ({}, {}) on conflict (version) do nothing",
+                sql_string(version),
+                sql_string(name)
+            ),
+        )
+        .await?;
+        applied.push(version.clone());
+    }
+    Ok(applied)
+}
+
+/// Store the project's anon and service role keys in the keychain
+async fn store_keys(
+    app: &AppHandle,
+    api: &Management<'_>,
+    workspace: &str,
+    project_ref: &str,
+) -> Result<(), String> {
+    let keys = api
+        .get(&format!("/projects/{}/api-keys?reveal=true", project_ref))
+        .await?;
+    let key = |name: &str| {
+        keys.as_array()
+            .into_iter()
+            .flatten()
+            .find(|k| k.get("name").and_then(Value::as_str) == Some(name))
+            .and_then(|k| k.get("api_key").and_then(Value::as_str))
+            .map(str::to_string)
+            .ok_or_else(|| format!("Project {} has no {} key", project_ref, name))
+    };
+    crate::secrets::set(app, workspace, REMOTE_ANON_KEY, &key("anon")?)?;
+    crate::secrets::set(
+        app,
+        workspace,
+        REMOTE_SERVICE_ROLE_KEY,
+        &key("service_role")?,
+    )?;
+    Ok(())
+}
+
+fn save(app: &AppHandle, project: &RemoteProject) -> Result<(), String> {
+    let store = app
+        .store(REMOTE_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", REMOTE_STORE, e))?;
+    store.set(
+        REMOTE_KEY,
+        serde_json::to_value(project).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", REMOTE_STORE, e))
+}
+
+async fn provision(
+    app: &AppHandle,
+    token: &str,
+    organization: String,
+    region: String,
+    name: String,
+    secrets: BTreeMap<String, String>,
+) -> Result<RemoteProject, String> {
+    let files = migration_files()?;
+    let api = Management {
+        http: crate::health::client()?,
+        token,
+    };
+    let workspace = crate::conflicts::current_workspace();
+
+    progress(app, "creating", Some(name.clone()));
+    let password: String = rand::random::<[u8; 24]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let created = api
+        .post(
+            "/projects",
+            json!({
+                "name": name,
+                "organization_id": organization,
+                "region": region,
+                "db_pass": password,
+            }),
+        )
+        .await?;
+    let project_ref = created
+        .get("ref")
+        .or_else(|| created.get("id"))
+        .and_then(Value::as_str)
+        .ok_or("The management API returned no project ref")?
+        .to_string();
+    log::info!("Created Supabase project {} in {}", project_ref, region);
+    // Keep the password even if a later step fails, so the project stays usable
+    crate::secrets::set(app, &workspace, REMOTE_DB_PASSWORD, &password)?;
+
+    let mut project = RemoteProject {
+        api_url: format!("https://{}.supabase.co", project_ref),
+        project_ref,
+        name,
+        organization,
+        region,
+        migrations: Vec::new(),
+        created_at_ms: now_ms(),
+    };
+    save(app, &project)?;
+
+    let project_ref = project.project_ref.clone();
+    let steps = async {
+        wait_until_ready(app, &api, &project_ref).await?;
+        project.migrations = apply_schema(app, &api, &project_ref, &files).await?;
+        if !secrets.is_empty() {
+            progress(
+                app,
+                "secrets",
+                Some(secrets.keys().cloned().collect::<Vec<_>>().join(", ")),
+            );
+            let body: Vec<Value> = secrets
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect();
+            api.post(
+                &format!("/projects/{}/secrets", project_ref),
+                Value::from(body),
+            )
+            .await?;
+        }
+        progress(app, "keys", None);
+        store_keys(app, &api, &workspace, &project_ref).await
+    };
+    if let Err(e) = steps.await {
+        return Err(format!(
+            "Project {} was created but is not set up: {}",
+            project_ref, e
+        ));
+    }
+    save(app, &project)?;
+    progress(app, "done", Some(project.api_url.clone()));
+    Ok(project)
+}
+
+/// Create a hosted Supabase project, apply the schema, set edge function
+/// secrets and keep its keys in the keychain
+#[tauri::command]
+pub async fn provision_remote_project(
+    app: AppHandle,
+    access_token: String,
+    org: String,
+    region: String,
+    name: Option<String>,
+    secrets: Option<BTreeMap<String, String>>,
+) -> Result<RemoteProject, FlowStateError> {
+    crate::trace::scope("provision_remote_project", async move {
+        crate::read_only::ensure_writable(&app, "provision_remote_project")?;
+        crate::app_lock::ensure_unlocked(&app, "provision_remote_project")?;
+        let access_token = access_token.trim();
+        if access_token.is_empty() {
+            return Err("A Supabase access token is required".into());
+        }
+        let org = org.trim().to_string();
+        let region = region.trim().to_string();
+        let name = name
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| DEFAULT_PROJECT_NAME.to_string());
+        validate(&org, "organization")?;
+        validate(&region, "region")?;
+        validate(&name, "project name")?;
+        let secrets = secrets.unwrap_or_default();
+        for name in secrets.keys() {
+            validate_secret_name(name)?;
+        }
+
+        let result = provision(&app, access_token, org, region, name, secrets).await;
+        if let Err(e) = &result {
+            log::error!("Provisioning failed: {}", e);
+        }
+        Ok(result?)
+    })
+    .await
+}
+
+/// The project created by `provision_remote_project`, if any
+#[tauri::command]
+pub fn get_remote_project(app: AppHandle) -> Option<RemoteProject> {
+    app.store(REMOTE_STORE)
+        .ok()
+        .and_then(|store| store.get(REMOTE_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+}
@@ -0,0 +1,107 @@
+//! Read-only mode for demos, screenshots and shared-screen retrospectives.
+//!
+//! Enabled with `--read-only` on launch or toggled at runtime. While it is on,
+//! commands that change the environment (stopping the stack, deploying edge
+//! functions, remediation playbooks, login items, the docker context), stored
+//! data (tasks, time entries, secrets, encryption, packs, watched repos, feeds
+//! and calendars, the search index), settings (app lock, shortcuts,
+//! notifications, idle threshold) or the remote side (sync pushes, SSO
+//! sign-in and sign-out) are rejected with a `ReadOnlyError`.
+//! Bringing services up stays allowed so a demo machine can still start. The
+//! flag is published as `app://read-only` and included in the state snapshot.
+//! Timers keep running, in memory only, without being persisted
+//! (`time_tracking.rs`).
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+pub const READ_ONLY_FLAG: &str = "--read-only";
+
+#[derive(Default)]
+pub struct ReadOnlyMode {
+    enabled: AtomicBool,
+}
+
+impl ReadOnlyMode {
+    /// Initial state from the process arguments
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        ReadOnlyMode {
+            enabled: AtomicBool::new(args.any(|a| a == READ_ONLY_FLAG)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+}
+
+/// Error returned by mutating commands while read-only mode is on.
+/// Serialized as `read_only:<command> ...` so the frontend can match the prefix.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyError {
+    pub command: &'static str,
+}
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read_only:{} is disabled in read-only mode",
+            self.command
+        )
+    }
+}
+
+impl std::error::Error for ReadOnlyError {}
+
+impl From<ReadOnlyError> for String {
+    fn from(e: ReadOnlyError) -> String {
+        e.to_string()
+    }
+}
+
+/// Reject a mutating command when read-only mode is on
+pub fn ensure_writable(app: &AppHandle, command: &'static str) -> Result<(), ReadOnlyError> {
+    if app.state::<ReadOnlyMode>().is_enabled() {
+        log::info!("Rejected {} (read-only mode)", command);
+        return Err(ReadOnlyError { command });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyState {
+    pub enabled: bool,
+}
+
+#[tauri::command]
+pub fn get_read_only_mode(mode: tauri::State<'_, ReadOnlyMode>) -> ReadOnlyState {
+    ReadOnlyState {
+        enabled: mode.is_enabled(),
+    }
+}
+
+/// Toggle read-only mode at runtime
+#[tauri::command]
+pub fn set_read_only_mode(app: AppHandle, enabled: bool) -> ReadOnlyState {
+    let previous = app
+        .state::<ReadOnlyMode>()
+        .enabled
+        .swap(enabled, Ordering::SeqCst);
+    let state = ReadOnlyState { enabled };
+
+    if previous != enabled {
+        log::info!(
+            "Read-only mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+        crate::events::publish(&app, "app://read-only", &state);
+    }
+
+    state
+}
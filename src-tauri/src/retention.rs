@@ -0,0 +1,412 @@
+//! Data retention rules and the nightly purge that enforces them.
+//!
+//! Two rules: completed tasks are deleted a number of months after they were
+//! completed (`completed_at`, else their last update), and raw activity
+//! (focus sessions, idle periods, interruptions) is pruned after a number of
+//! days, leaving only its rollups (`rollups.rs`). Without a task rule tasks
+//! are kept; without an activity rule activity is kept for
+//! `rollups::DEFAULT_RETENTION_DAYS`. Users set the rules with
+//! `set_retention_rules`; the organization policy (`policy.rs`) can pin
+//! either one, and a pinned rule can't be changed here.
+//!
+//! The rules are enforced every night (a startup graph node) and with
+//! `run_retention`, whose dry run previews what would go without touching
+//! anything. Tasks are deleted through the offline cache like any local
+//! delete, so the deletion syncs to Supabase. Every real run, scheduled or
+//! not, first records a `retention.purge` entry in the audit log with what it
+//! is about to remove, and deletes nothing if that entry can't be written.
+
+use std::time::Duration;
+
+use chrono::{Local, Months, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::rollups::RollupRun;
+
+const RETENTION_STORE: &str = "retention.json";
+const RULES_KEY: &str = "rules";
+const RETENTION_ACTION: &str = "retention.purge";
+const MAX_TASK_MONTHS: u32 = 120;
+const MAX_ACTIVITY_DAYS: u32 = 3650;
+/// Local hour the nightly run starts at
+const NIGHTLY_HOUR: u32 = 3;
+/// Never run again right after a run
+const MIN_RUN_GAP: Duration = Duration::from_secs(60);
+/// Longest wait for the user to pause before a run
+const MAX_START_DEFER: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RetentionRules {
+    /// Delete completed tasks this many months after completion
+    pub completed_task_months: Option<u32>,
+    /// Keep raw activity this many days
+    pub activity_days: Option<u32>,
+}
+
+impl RetentionRules {
+    fn validate(&self) -> Result<(), String> {
+        if self
+            .completed_task_months
+            .is_some_and(|months| !(1..=MAX_TASK_MONTHS).contains(&months))
+        {
+            return Err(format!(
+                "Completed tasks are kept 1 to {} months",
+                MAX_TASK_MONTHS
+            ));
+        }
+        if self.activity_days.is_some_and(|days| {
+            !(crate::rollups::MIN_RETENTION_DAYS..=MAX_ACTIVITY_DAYS).contains(&days)
+        }) {
+            return Err(format!(
+                "Raw activity is kept {} to {} days",
+                crate::rollups::MIN_RETENTION_DAYS,
+                MAX_ACTIVITY_DAYS
+            ));
+        }
+        Ok(())
+    }
+
+    /// These rules with the ones `pinned` sets taking precedence
+    fn under(self, pinned: RetentionRules) -> RetentionRules {
+        RetentionRules {
+            completed_task_months: pinned.completed_task_months.or(self.completed_task_months),
+            activity_days: pinned.activity_days.or(self.activity_days),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionState {
+    /// Rules in effect
+    pub rules: RetentionRules,
+    /// Rules pinned by the organization policy
+    pub pinned: RetentionRules,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiredTask {
+    pub id: String,
+    pub title: String,
+    pub completed_at: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionRun {
+    pub dry_run: bool,
+    pub at_ms: u64,
+    pub rules: RetentionRules,
+    /// Completed tasks deleted (or that would be)
+    pub tasks: Vec<ExpiredTask>,
+    /// Raw activity pruned (or that would be)
+    pub activity: RollupRun,
+}
+
+fn pinned() -> RetentionRules {
+    crate::policy::current().policy.retention
+}
+
+fn user_rules(app: &AppHandle) -> RetentionRules {
+    app.store(RETENTION_STORE)
+        .ok()
+        .and_then(|store| store.get(RULES_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn state(app: &AppHandle) -> RetentionState {
+    RetentionState {
+        rules: user_rules(app).under(pinned()),
+        pinned: pinned(),
+    }
+}
+
+/// Completed tasks in the cache that finished before `cutoff` (RFC 3339)
+fn expired_tasks(conn: &Connection, cutoff: &str) -> rusqlite::Result<Vec<ExpiredTask>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, coalesce(json_extract(data, '$.title'), ''), \
+                coalesce(json_extract(data, '$.completed_at'), json_extract(data, '$.updated_at')) \
+         FROM tasks \
+         WHERE coalesce(json_extract(data, '$.is_deleted'), 0) = 0 \
+           AND json_extract(data, '$.status') = 'done' \
+           AND julianday(coalesce(json_extract(data, '$.completed_at'), \
+                                  json_extract(data, '$.updated_at'))) < julianday(?1) \
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![cutoff], |row| {
+        Ok(ExpiredTask {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            completed_at: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Completed tasks the month rule expires as of now
+fn tasks_to_purge(app: &AppHandle, months: u32) -> Result<Vec<ExpiredTask>, String> {
+    let cutoff = crate::clock::now_local()
+        .with_timezone(&Utc)
+        .checked_sub_months(Months::new(months))
+        .ok_or("Retention cutoff out of range")?
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+    crate::offline::with_db(app, |conn| expired_tasks(conn, &cutoff))
+}
+
+/// Delete `tasks` and prune the activity. The task deletes (and their outbox
+/// entries) sit in a savepoint that is only released once the activity prune
+/// committed, so a failure on either side leaves the tasks in place.
+fn purge(
+    app: &AppHandle,
+    tasks: &[ExpiredTask],
+    now_ms: u64,
+    activity_days: u32,
+) -> Result<RollupRun, String> {
+    let activity = crate::offline::with_db(app, |conn| {
+        conn.execute_batch("SAVEPOINT retention")?;
+        let pruned = tasks
+            .iter()
+            .try_for_each(|task| crate::offline::remove_task(conn, &task.id))
+            .map_err(|e| format!("Failed to delete expired tasks: {}", e))
+            .and_then(|()| crate::rollups::run(app, now_ms, activity_days, false));
+        match pruned {
+            Ok(_) => conn.execute_batch("RELEASE retention")?,
+            Err(_) => conn.execute_batch("ROLLBACK TO retention; RELEASE retention")?,
+        }
+        Ok(pruned)
+    })??;
+    if !tasks.is_empty() {
+        for task in tasks {
+            crate::search::remove(app, &task.id);
+        }
+        crate::offline::sync_soon(app);
+    }
+    Ok(activity)
+}
+
+/// Apply the rules in effect; a dry run only reports what would go.
+///
+/// A real run works out what it will delete first and records that in the
+/// audit log; if the entry can't be written nothing is deleted.
+pub(crate) fn enforce(app: &AppHandle, dry_run: bool) -> Result<RetentionRun, String> {
+    let rules = state(app).rules;
+    let activity_days = rules
+        .activity_days
+        .unwrap_or(crate::rollups::DEFAULT_RETENTION_DAYS);
+    let now = crate::events::now_ms();
+    let tasks = match rules.completed_task_months {
+        Some(months) => tasks_to_purge(app, months)?,
+        None => Vec::new(),
+    };
+    let activity = crate::rollups::run(app, now, activity_days, true)?;
+    let mut run = RetentionRun {
+        dry_run,
+        at_ms: now,
+        rules,
+        tasks,
+        activity,
+    };
+    if dry_run {
+        return Ok(run);
+    }
+
+    let detail = serde_json::json!({
+        "rules": run.rules,
+        "taskIds": run.tasks.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(),
+        "focusSessions": run.activity.pruned_sessions,
+        "idlePeriods": run.activity.pruned_idle_periods,
+        "interruptions": run.activity.pruned_interruptions,
+    });
+    crate::audit::record(
+        app,
+        RETENTION_ACTION,
+        "completed tasks and activity",
+        Some(&detail.to_string()),
+    )?;
+    run.activity = purge(app, &run.tasks, now, activity_days)?;
+    if !run.tasks.is_empty() || run.activity.pruned_sessions > 0 {
+        log::info!(
+            "Retention: deleted {} completed tasks and {} focus sessions",
+            run.tasks.len(),
+            run.activity.pruned_sessions
+        );
+    }
+    crate::events::publish(app, "retention://completed", &run);
+    Ok(run)
+}
+
+/// Time until the next nightly run on the clock of `tz`
+fn until_next_run_in<Tz: TimeZone>(tz: &Tz) -> Duration {
+    crate::clock::until_next(&crate::clock::now_in(tz), NIGHTLY_HOUR).max(MIN_RUN_GAP)
+}
+
+/// Enforce the rules at startup and every night (a startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            crate::background::yield_to_user(&app, MAX_START_DEFER).await;
+            if app.state::<crate::read_only::ReadOnlyMode>().is_enabled() {
+                log::info!("Read-only mode: retention run skipped");
+            } else if let Err(e) = enforce(&app, false) {
+                log::warn!("Retention run failed: {}", e);
+            }
+            crate::clock::sleep(until_next_run_in(&Local)).await;
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_retention_rules(app: AppHandle) -> RetentionState {
+    state(&app)
+}
+
+/// Save the user's rules; rules pinned by the organization can't change
+#[tauri::command]
+pub fn set_retention_rules(
+    app: AppHandle,
+    rules: RetentionRules,
+) -> Result<RetentionState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_retention_rules")?;
+    rules.validate()?;
+    let pinned = pinned();
+    let current = user_rules(&app).under(pinned);
+    if (pinned.completed_task_months.is_some()
+        && rules.completed_task_months != current.completed_task_months)
+        || (pinned.activity_days.is_some() && rules.activity_days != current.activity_days)
+    {
+        return Err("This retention rule is set by your organization".into());
+    }
+    let store = app
+        .store(RETENTION_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", RETENTION_STORE, e))?;
+    store.set(
+        RULES_KEY,
+        serde_json::to_value(rules).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", RETENTION_STORE, e))?;
+    Ok(state(&app))
+}
+
+/// Enforce the retention rules now, or with `dry_run` preview what would be
+/// deleted
+#[tauri::command]
+pub fn run_retention(app: AppHandle, dry_run: bool) -> Result<RetentionRun, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "run_retention")?;
+    if !dry_run {
+        crate::read_only::ensure_writable(&app, "run_retention")?;
+    }
+    Ok(enforce(&app, dry_run)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_rules_take_precedence() {
+        let user = RetentionRules {
+            completed_task_months: Some(6),
+            activity_days: Some(90),
+        };
+        let pinned = RetentionRules {
+            completed_task_months: None,
+            activity_days: Some(365),
+        };
+        assert_eq!(
+            user.under(pinned),
+            RetentionRules {
+                completed_task_months: Some(6),
+                activity_days: Some(365),
+            }
+        );
+        assert!(user.validate().is_ok());
+        assert!(RetentionRules {
+            activity_days: Some(7),
+            ..user
+        }
+        .validate()
+        .is_err());
+        assert!(RetentionRules {
+            completed_task_months: Some(0),
+            ..user
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn only_tasks_completed_before_the_cutoff_expire() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE tasks (id TEXT PRIMARY KEY, data TEXT NOT NULL)")
+            .unwrap();
+        for task in [
+            serde_json::json!({"id": "old", "title": "Old", "status": "done",
+                "completed_at": "2025-01-10T09:00:00.123456+00:00"}),
+            serde_json::json!({"id": "updated", "title": "Updated", "status": "done",
+                "updated_at": "2025-02-01T00:00:00Z"}),
+            serde_json::json!({"id": "recent", "title": "Recent", "status": "done",
+                "completed_at": "2026-01-10T09:00:00+00:00"}),
+            serde_json::json!({"id": "open", "title": "Open", "status": "planned",
+                "updated_at": "2024-01-01T00:00:00Z"}),
+            serde_json::json!({"id": "deleted", "title": "Deleted", "status": "done",
+                "is_deleted": true, "completed_at": "2024-01-01T00:00:00Z"}),
+            serde_json::json!({"id": "undated", "title": "Undated", "status": "done"}),
+        ] {
+            conn.execute(
+                "INSERT INTO tasks (id, data) VALUES (?1, ?2)",
+                params![task["id"].as_str(), task.to_string()],
+            )
+            .unwrap();
+        }
+        let expired = expired_tasks(&conn, "2025-06-01T00:00:00Z").unwrap();
+        let ids: Vec<&str> = expired.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["old", "updated"]);
+        assert_eq!(
+            expired[0].completed_at.as_deref(),
+            Some("2025-01-10T09:00:00.123456+00:00")
+        );
+    }
+
+    #[test]
+    fn runs_nightly_across_dst_changes() {
+        let tz = chrono_tz::Europe::Berlin;
+        // 2026-03-29 02:00 CET jumps to 03:00 CEST; 2026-10-25 03:00 CEST
+        // falls back to 02:00 CET
+        for (start, nights) in [
+            ("2026-03-27T12:00:00+01:00", 4),
+            ("2026-10-23T12:00:00+02:00", 4),
+        ] {
+            crate::clock::set(
+                chrono::DateTime::parse_from_rfc3339(start)
+                    .unwrap()
+                    .timestamp_millis(),
+            );
+            let mut last: Option<chrono::DateTime<chrono_tz::Tz>> = None;
+            for _ in 0..nights {
+                crate::clock::advance(until_next_run_in(&tz).as_millis() as u64);
+                let run = crate::clock::now_in(&tz);
+                assert_eq!(run.format("%H:%M").to_string(), "03:00", "ran at {}", run);
+                if let Some(last) = last {
+                    // One run per night, whatever the night's length
+                    assert_eq!(
+                        run.date_naive() - last.date_naive(),
+                        chrono::Duration::days(1)
+                    );
+                }
+                last = Some(run);
+            }
+        }
+        crate::clock::reset();
+    }
+}
@@ -0,0 +1,560 @@
+//! Hourly and daily rollups of the activity in `sessions.db`.
+//!
+//! As part of the nightly retention run (`retention.rs`), focus sessions,
+//! idle periods and interruptions of the completed local days are summed into
+//! `activity_hourly` (per local hour), `activity_daily` and `tag_daily`
+//! (focus time per session tag). A session counts toward the hour and day it
+//! started in, idle periods are capped like the anomaly baselines do, and
+//! untagged sessions are kept under the tag ''. Days are recomputed from the
+//! raw rows for `LATE_DAYS` after they end, so sessions tagged afterwards
+//! still land in the right totals.
+//!
+//! Raw rows older than the activity retention rule (180 days by default) are
+//! then deleted, whole local days at a time and only once they are rolled
+//! up; a dry run does the same in a transaction it rolls back. `pruned_before` marks where raw data ends: the
+//! anomaly baselines and the tag breakdown read the rollups before it, while
+//! session lists and the activity export only cover the raw window.
+
+use std::collections::BTreeMap;
+
+use chrono::{Local, NaiveDate, TimeZone, Timelike};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::error::FlowStateError;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+/// Days after their end that rollups are still recomputed from raw rows
+const LATE_DAYS: i64 = 7;
+pub const DEFAULT_RETENTION_DAYS: u32 = 180;
+/// Raw rows are kept at least this long
+pub const MIN_RETENTION_DAYS: u32 = LATE_DAYS as u32 + 23;
+
+const ROLLED_UP_TO: &str = "rolled_up_to";
+const PRUNED_BEFORE: &str = "pruned_before";
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityTotals {
+    pub focus_ms: i64,
+    pub focus_sessions: i64,
+    /// Focus phases that ran out rather than being stopped
+    pub completed: i64,
+    pub idle_ms: i64,
+    pub interruptions: i64,
+}
+
+impl ActivityTotals {
+    fn add(&mut self, other: &ActivityTotals) {
+        self.focus_ms += other.focus_ms;
+        self.focus_sessions += other.focus_sessions;
+        self.completed += other.completed;
+        self.idle_ms += other.idle_ms;
+        self.interruptions += other.interruptions;
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityBucket {
+    /// Start of the local hour or day in ms since the epoch
+    pub start_ms: i64,
+    /// Local day, "YYYY-MM-DD"
+    pub day: String,
+    #[serde(flatten)]
+    pub totals: ActivityTotals,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hour,
+    #[default]
+    Day,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollupState {
+    /// Raw rows start here (ms since the epoch); 0 when nothing was pruned
+    pub pruned_before_ms: i64,
+    /// Days before this are rolled up
+    pub rolled_up_to_ms: i64,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollupRun {
+    /// Local days recomputed
+    pub days: usize,
+    pub pruned_sessions: usize,
+    pub pruned_idle_periods: usize,
+    pub pruned_interruptions: usize,
+    pub state: Option<RollupState>,
+}
+
+/// Local midnight at the start of `day` in ms since the epoch
+pub(crate) fn day_start_ms(day: NaiveDate) -> i64 {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| midnight.and_utc().timestamp_millis())
+}
+
+fn local_day(ms: i64) -> NaiveDate {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| t.date_naive())
+        .unwrap_or_default()
+}
+
+/// Start of the local hour `ms` falls in
+fn hour_start_ms(ms: i64) -> i64 {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| {
+            let into_hour = i64::from(t.minute()) * 60_000
+                + i64::from(t.second()) * 1000
+                + i64::from(t.timestamp_subsec_millis());
+            ms - into_hour
+        })
+        .unwrap_or(ms - ms.rem_euclid(60 * 60 * 1000))
+}
+
+fn meta(conn: &Connection, key: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT value FROM rollup_meta WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn set_meta(conn: &Connection, key: &str, value: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO rollup_meta (key, value) VALUES (?1, ?2) \
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn state(conn: &Connection) -> rusqlite::Result<RollupState> {
+    Ok(RollupState {
+        pruned_before_ms: meta(conn, PRUNED_BEFORE)?.unwrap_or(0),
+        rolled_up_to_ms: meta(conn, ROLLED_UP_TO)?.unwrap_or(0),
+    })
+}
+
+/// Where raw rows end; totals before it come from the rollups
+pub(crate) fn pruned_before(conn: &Connection) -> rusqlite::Result<i64> {
+    Ok(meta(conn, PRUNED_BEFORE)?.unwrap_or(0))
+}
+
+type Buckets = (
+    BTreeMap<i64, ActivityTotals>,
+    BTreeMap<NaiveDate, ActivityTotals>,
+    BTreeMap<(NaiveDate, String), ActivityTotals>,
+);
+
+/// Sum the raw rows that started in [from_ms, to_ms)
+fn aggregate(tx: &Transaction, from_ms: i64, to_ms: i64) -> rusqlite::Result<Buckets> {
+    let (mut hours, mut days, mut tags) = Buckets::default();
+    let mut add = |at: i64, totals: ActivityTotals, session_tags: Option<&[String]>| {
+        hours.entry(hour_start_ms(at)).or_default().add(&totals);
+        let day = local_day(at);
+        days.entry(day).or_default().add(&totals);
+        if let Some(session_tags) = session_tags {
+            let untagged = [String::new()];
+            let session_tags = if session_tags.is_empty() {
+                &untagged[..]
+            } else {
+                session_tags
+            };
+            for tag in session_tags {
+                tags.entry((day, tag.clone())).or_default().add(&totals);
+            }
+        }
+    };
+
+    let mut stmt = tx.prepare(
+        "SELECT s.started_at, s.duration_ms, s.completed, \
+                (SELECT group_concat(tag, char(31)) FROM session_tags WHERE session_id = s.id) \
+         FROM focus_sessions s WHERE s.started_at >= ?1 AND s.started_at < ?2",
+    )?;
+    let mut rows = stmt.query(params![from_ms, to_ms])?;
+    while let Some(row) = rows.next()? {
+        let session_tags: Vec<String> = row
+            .get::<_, Option<String>>(3)?
+            .map(|t| t.split('\u{1f}').map(str::to_string).collect())
+            .unwrap_or_default();
+        let completed: bool = row.get(2)?;
+        add(
+            row.get(0)?,
+            ActivityTotals {
+                focus_ms: row.get(1)?,
+                focus_sessions: 1,
+                completed: i64::from(completed),
+                ..ActivityTotals::default()
+            },
+            Some(&session_tags),
+        );
+    }
+
+    let mut stmt = tx.prepare(
+        "SELECT started_at, min(ended_at - started_at, ?3) FROM idle_periods \
+         WHERE started_at >= ?1 AND started_at < ?2",
+    )?;
+    let mut rows = stmt.query(params![from_ms, to_ms, crate::anomalies::MAX_IDLE_MS])?;
+    while let Some(row) = rows.next()? {
+        add(
+            row.get(0)?,
+            ActivityTotals {
+                idle_ms: row.get(1)?,
+                ..ActivityTotals::default()
+            },
+            None,
+        );
+    }
+
+    let mut stmt = tx.prepare("SELECT at FROM interruptions WHERE at >= ?1 AND at < ?2")?;
+    let mut rows = stmt.query(params![from_ms, to_ms])?;
+    while let Some(row) = rows.next()? {
+        add(
+            row.get(0)?,
+            ActivityTotals {
+                interruptions: 1,
+                ..ActivityTotals::default()
+            },
+            None,
+        );
+    }
+    Ok((hours, days, tags))
+}
+
+fn write_totals(
+    tx: &Transaction,
+    table: &str,
+    key: &str,
+    start_ms: i64,
+    day: &NaiveDate,
+    totals: &ActivityTotals,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        &format!(
+            "INSERT INTO {} ({}, day, focus_ms, focus_sessions, completed, idle_ms, interruptions) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            table, key
+        ),
+        params![
+            start_ms,
+            day.to_string(),
+            totals.focus_ms,
+            totals.focus_sessions,
+            totals.completed,
+            totals.idle_ms,
+            totals.interruptions
+        ],
+    )?;
+    Ok(())
+}
+
+/// Recompute the rollups of the completed days and prune raw rows that are
+/// rolled up and older than `retention_days`; a dry run only counts
+pub(crate) fn roll_up(
+    conn: &mut Connection,
+    now_ms: i64,
+    retention_days: u32,
+    dry_run: bool,
+) -> rusqlite::Result<RollupRun> {
+    let tx = conn.transaction()?;
+    let before = state(&tx)?;
+    let to_ms = day_start_ms(local_day(now_ms));
+    let from_ms = if before.rolled_up_to_ms == 0 {
+        let oldest: Option<i64> = tx.query_row(
+            "SELECT min(at) FROM (SELECT min(started_at) AS at FROM focus_sessions \
+             UNION ALL SELECT min(started_at) FROM idle_periods \
+             UNION ALL SELECT min(at) FROM interruptions)",
+            [],
+            |row| row.get(0),
+        )?;
+        oldest.map_or(to_ms, |at| day_start_ms(local_day(at)))
+    } else {
+        let late = local_day(before.rolled_up_to_ms) - chrono::Duration::days(LATE_DAYS);
+        day_start_ms(late).max(before.pruned_before_ms)
+    };
+
+    let mut run = RollupRun::default();
+    if from_ms < to_ms {
+        let (hours, days, tags) = aggregate(&tx, from_ms, to_ms)?;
+        let (from_day, to_day) = (local_day(from_ms).to_string(), local_day(to_ms).to_string());
+        tx.execute(
+            "DELETE FROM activity_hourly WHERE hour_start >= ?1 AND hour_start < ?2",
+            params![from_ms, to_ms],
+        )?;
+        for table in ["activity_daily", "tag_daily"] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE day >= ?1 AND day < ?2", table),
+                params![from_day, to_day],
+            )?;
+        }
+        for (start_ms, totals) in &hours {
+            write_totals(
+                &tx,
+                "activity_hourly",
+                "hour_start",
+                *start_ms,
+                &local_day(*start_ms),
+                totals,
+            )?;
+        }
+        for (day, totals) in &days {
+            write_totals(
+                &tx,
+                "activity_daily",
+                "day_start",
+                day_start_ms(*day),
+                day,
+                totals,
+            )?;
+        }
+        for ((day, tag), totals) in &tags {
+            tx.execute(
+                "INSERT INTO tag_daily (day, tag, day_start, sessions, completed, focus_ms) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    day.to_string(),
+                    tag,
+                    day_start_ms(*day),
+                    totals.focus_sessions,
+                    totals.completed,
+                    totals.focus_ms
+                ],
+            )?;
+        }
+        run.days = days.len();
+        set_meta(&tx, ROLLED_UP_TO, to_ms)?;
+    }
+
+    let retention_ms = i64::from(retention_days.max(MIN_RETENTION_DAYS)) * DAY_MS;
+    let horizon = day_start_ms(local_day(now_ms - retention_ms)).min(to_ms);
+    if horizon > before.pruned_before_ms {
+        tx.execute(
+            "DELETE FROM session_tags WHERE session_id IN \
+             (SELECT id FROM focus_sessions WHERE started_at < ?1)",
+            params![horizon],
+        )?;
+        run.pruned_interruptions = tx.execute(
+            "DELETE FROM interruptions WHERE at < ?1 OR session_id IN \
+             (SELECT id FROM focus_sessions WHERE started_at < ?1)",
+            params![horizon],
+        )?;
+        run.pruned_sessions = tx.execute(
+            "DELETE FROM focus_sessions WHERE started_at < ?1",
+            params![horizon],
+        )?;
+        run.pruned_idle_periods = tx.execute(
+            "DELETE FROM idle_periods WHERE started_at < ?1",
+            params![horizon],
+        )?;
+        set_meta(&tx, PRUNED_BEFORE, horizon)?;
+    }
+    run.state = Some(state(&tx)?);
+    if !dry_run {
+        tx.commit()?;
+    }
+    Ok(run)
+}
+
+/// Roll up and prune the activity as of `now_ms`; see `roll_up`
+pub(crate) fn run(
+    app: &AppHandle,
+    now_ms: u64,
+    retention_days: u32,
+    dry_run: bool,
+) -> Result<RollupRun, String> {
+    let now = now_ms as i64;
+    let run = crate::sessions::with_db(app, |conn| roll_up(conn, now, retention_days, dry_run))?;
+    if !dry_run {
+        crate::events::publish(app, "rollups://completed", &run);
+    }
+    Ok(run)
+}
+
+/// Rolled-up activity per local hour or day for days in [from, to]
+/// ("YYYY-MM-DD"), oldest first
+#[tauri::command]
+pub fn get_activity_rollups(
+    app: AppHandle,
+    from: String,
+    to: String,
+    granularity: Option<Granularity>,
+) -> Result<Vec<ActivityBucket>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_activity_rollups")?;
+    let parse = |day: &str| {
+        NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("'{}' is not a date (YYYY-MM-DD)", day))
+    };
+    let (from, to) = (parse(&from)?, parse(&to)?);
+    let (table, key) = match granularity.unwrap_or_default() {
+        Granularity::Hour => ("activity_hourly", "hour_start"),
+        Granularity::Day => ("activity_daily", "day_start"),
+    };
+    Ok(crate::sessions::with_db(&app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, day, focus_ms, focus_sessions, completed, idle_ms, interruptions \
+             FROM {} WHERE day >= ?1 AND day <= ?2 ORDER BY {}",
+            key, table, key
+        ))?;
+        let rows = stmt.query_map(params![from.to_string(), to.to_string()], |row| {
+            Ok(ActivityBucket {
+                start_ms: row.get(0)?,
+                day: row.get(1)?,
+                totals: ActivityTotals {
+                    focus_ms: row.get(2)?,
+                    focus_sessions: row.get(3)?,
+                    completed: row.get(4)?,
+                    idle_ms: row.get(5)?,
+                    interruptions: row.get(6)?,
+                },
+            })
+        })?;
+        rows.collect()
+    })?)
+}
+
+#[tauri::command]
+pub fn get_rollup_state(app: AppHandle) -> Result<RollupState, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_rollup_state")?;
+    Ok(crate::sessions::with_db(&app, |conn| state(conn))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    fn db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(crate::sessions::SCHEMA).unwrap();
+        conn
+    }
+
+    fn session(conn: &Connection, started_at: i64, duration_ms: i64, tags: &[&str]) {
+        conn.execute(
+            "INSERT INTO focus_sessions (task_id, started_at, ended_at, duration_ms, completed) \
+             VALUES (NULL, ?1, ?2, ?3, 1)",
+            params![started_at, started_at + duration_ms, duration_ms],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        for tag in tags {
+            conn.execute(
+                "INSERT INTO session_tags (session_id, tag) VALUES (?1, ?2)",
+                params![id, tag],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO interruptions (session_id, phase_started_at, at, kind) \
+             VALUES (?1, ?2, ?2 + 1000, 'message')",
+            params![id, started_at],
+        )
+        .unwrap();
+    }
+
+    fn daily(conn: &Connection) -> Vec<(String, i64, i64, i64)> {
+        let mut stmt = conn
+            .prepare("SELECT day, focus_ms, focus_sessions, interruptions FROM activity_daily ORDER BY day")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn rolls_up_completed_days_and_keeps_totals_after_pruning() {
+        let mut conn = db();
+        let today = NaiveDate::from_ymd_opt(2026, 3, 20).unwrap();
+        let now = day_start_ms(today) + 12 * HOUR_MS;
+        let day = |back: i64| day_start_ms(today - chrono::Duration::days(back));
+        session(&conn, day(200) + 9 * HOUR_MS, 25 * 60_000, &["deep"]);
+        session(&conn, day(200) + 10 * HOUR_MS, 50 * 60_000, &[]);
+        session(&conn, day(3) + 9 * HOUR_MS, 25 * 60_000, &["deep", "admin"]);
+        // Today is not complete yet
+        session(&conn, day(0) + 9 * HOUR_MS, 25 * 60_000, &[]);
+        conn.execute(
+            "INSERT INTO idle_periods (started_at, ended_at) VALUES (?1, ?2)",
+            params![day(3) + 11 * HOUR_MS, day(3) + 20 * HOUR_MS],
+        )
+        .unwrap();
+
+        // A dry run changes nothing
+        let preview = roll_up(&mut conn, now, DEFAULT_RETENTION_DAYS, true).unwrap();
+        assert_eq!(preview.pruned_sessions, 2);
+        assert!(daily(&conn).is_empty());
+
+        let run = roll_up(&mut conn, now, DEFAULT_RETENTION_DAYS, false).unwrap();
+        assert_eq!(run.days, 2);
+        assert_eq!(run.pruned_sessions, 2);
+        assert_eq!(run.pruned_interruptions, 2);
+        let old = (today - chrono::Duration::days(200)).to_string();
+        let recent = (today - chrono::Duration::days(3)).to_string();
+        assert_eq!(
+            daily(&conn),
+            vec![
+                (old.clone(), 75 * 60_000, 2, 2),
+                (recent.clone(), 25 * 60_000, 1, 1)
+            ]
+        );
+        let idle: i64 = conn
+            .query_row(
+                "SELECT idle_ms FROM activity_daily WHERE day = ?1",
+                params![recent],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(idle, crate::anomalies::MAX_IDLE_MS);
+        let hours: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM activity_hourly WHERE day = ?1",
+                params![old],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hours, 2);
+        let tags: Vec<(String, i64)> = conn
+            .prepare("SELECT tag, focus_ms FROM tag_daily ORDER BY day, tag")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                (String::new(), 50 * 60_000),
+                ("deep".to_string(), 25 * 60_000),
+                ("admin".to_string(), 25 * 60_000),
+                ("deep".to_string(), 25 * 60_000)
+            ]
+        );
+        assert!(pruned_before(&conn).unwrap() > day(200));
+
+        // A rerun the next day recomputes recent days and leaves pruned ones alone
+        let run = roll_up(&mut conn, now + DAY_MS, DEFAULT_RETENTION_DAYS, false).unwrap();
+        assert_eq!(run.pruned_sessions, 0);
+        assert_eq!(daily(&conn).len(), 3);
+        assert_eq!(daily(&conn)[0], (old, 75 * 60_000, 2, 2));
+        assert_eq!(daily(&conn)[2].1, 25 * 60_000);
+    }
+}
@@ -0,0 +1,471 @@
+//! Full-text task search over the offline task cache.
+//!
+//! A tantivy index in memory is built from `task_cache.db` on startup and kept
+//! current as tasks are saved or deleted locally and after each sync that
+//! pulled changes, so searching never needs the stack. Every query word
+//! matches as a prefix ("meet" finds "meeting") in the title, description and
+//! tags, and in the text extracted from attachments (`attachment_index.rs`);
+//! exact words and title hits rank higher. Snippets come back as plain text
+//! with highlight ranges rather than HTML, so task text is never rendered as
+//! markup. Since every change to the cache passes through here, each is also
+//! published as `tasks://changed`.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tantivy::collector::TopDocs;
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value as _, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+
+const WRITER_MEMORY: usize = 15_000_000;
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 200;
+/// Characters of context around the first match in a snippet
+const SNIPPET_CONTEXT: usize = 60;
+/// Longest the startup build waits for the user to pause
+const MAX_BUILD_DEFER: Duration = Duration::from_secs(60);
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchFilters {
+    /// Any of these statuses
+    pub status: Option<Vec<String>>,
+    pub project_id: Option<String>,
+    pub include_deleted: bool,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    /// title | description | attachment
+    pub field: &'static str,
+    pub text: String,
+    /// Byte ranges of `text` to highlight
+    pub highlights: Vec<[usize; 2]>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub status: Option<String>,
+    pub project_id: Option<String>,
+    pub score: f32,
+    pub snippet: Option<Snippet>,
+}
+
+struct Fields {
+    id: Field,
+    title: Field,
+    description: Field,
+    tags: Field,
+    attachments: Field,
+    status: Field,
+    project_id: Field,
+    deleted: Field,
+}
+
+struct Inner {
+    fields: Fields,
+    writer: IndexWriter,
+    reader: IndexReader,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    inner: Mutex<Option<Inner>>,
+}
+
+fn create() -> tantivy::Result<Inner> {
+    let mut schema = Schema::builder();
+    let fields = Fields {
+        id: schema.add_text_field("id", STRING | STORED),
+        title: schema.add_text_field("title", TEXT | STORED),
+        description: schema.add_text_field("description", TEXT | STORED),
+        tags: schema.add_text_field("tags", TEXT),
+        attachments: schema.add_text_field("attachments", TEXT | STORED),
+        status: schema.add_text_field("status", STRING | STORED),
+        project_id: schema.add_text_field("project_id", STRING | STORED),
+        deleted: schema.add_text_field("deleted", STRING),
+    };
+    let index = Index::create_in_ram(schema.build());
+    let writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    Ok(Inner {
+        fields,
+        writer,
+        reader,
+    })
+}
+
+fn text<'a>(row: &'a Map<String, Value>, key: &str) -> &'a str {
+    row.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Replace the document of one cached task row
+fn add(inner: &mut Inner, row: &Map<String, Value>, attachments: &str) -> tantivy::Result<()> {
+    let id = text(row, "id");
+    if id.is_empty() {
+        return Ok(());
+    }
+    let f = &inner.fields;
+    let tags = row
+        .get("tags")
+        .and_then(Value::as_array)
+        .map(|tags| {
+            tags.iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+    let deleted = row
+        .get("is_deleted")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    inner.writer.delete_term(Term::from_field_text(f.id, id));
+    inner.writer.add_document(doc!(
+        f.id => id,
+        f.title => text(row, "title"),
+        f.description => text(row, "description"),
+        f.tags => tags,
+        f.attachments => attachments,
+        f.status => text(row, "status"),
+        f.project_id => text(row, "project_id"),
+        f.deleted => if deleted { "true" } else { "false" },
+    ))?;
+    Ok(())
+}
+
+fn commit(inner: &mut Inner) -> tantivy::Result<()> {
+    inner.writer.commit()?;
+    inner.reader.reload()
+}
+
+/// Run `f` on the index, creating it on first use
+fn with_index<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Inner) -> tantivy::Result<T>,
+) -> Result<T, String> {
+    let search = app.state::<SearchIndex>();
+    let mut slot = search.inner.lock().unwrap_or_else(|e| e.into_inner());
+    if slot.is_none() {
+        *slot = Some(create().map_err(|e| format!("Failed to create search index: {}", e))?);
+    }
+    match slot.as_mut() {
+        Some(inner) => f(inner).map_err(|e| format!("Search index error: {}", e)),
+        None => Err("Search index unavailable".to_string()),
+    }
+}
+
+/// Payload of `tasks://changed`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TasksChanged {
+    /// Tasks saved or pulled
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// The whole cache was re-read; anything may have changed
+    pub reset: bool,
+}
+
+fn changed(app: &AppHandle, change: TasksChanged) {
+    crate::events::publish(app, "tasks://changed", &change);
+    crate::smart_lists::tasks_changed(app);
+}
+
+/// Re-index everything in the task cache
+pub fn rebuild(app: &AppHandle) -> Result<usize, String> {
+    let rows = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT data FROM tasks")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    let attachments = crate::attachment_index::task_texts(app, None).unwrap_or_else(|e| {
+        log::warn!("Indexing tasks without attachment text: {}", e);
+        HashMap::new()
+    });
+    with_index(app, |inner| {
+        inner.writer.delete_all_documents()?;
+        for data in &rows {
+            if let Ok(row) = serde_json::from_str::<Map<String, Value>>(data) {
+                let text = attachments.get(text(&row, "id"));
+                add(inner, &row, text.map_or("", String::as_str))?;
+            }
+        }
+        commit(inner)?;
+        Ok(rows.len())
+    })
+    .inspect(|_| {
+        changed(
+            app,
+            TasksChanged {
+                reset: true,
+                ..TasksChanged::default()
+            },
+        )
+    })
+}
+
+/// Index (or re-index) cached task rows
+pub fn upsert(app: &AppHandle, rows: &[String]) {
+    if rows.is_empty() {
+        return;
+    }
+    // Attachment text is read before taking the index lock
+    let rows: Vec<(Map<String, Value>, String)> = rows
+        .iter()
+        .filter_map(|data| serde_json::from_str::<Map<String, Value>>(data).ok())
+        .map(|row| {
+            let attachments = crate::attachment_index::task_texts(app, Some(text(&row, "id")))
+                .map_err(|e| log::debug!("No attachment text: {}", e))
+                .ok()
+                .and_then(|mut texts| texts.remove(text(&row, "id")))
+                .unwrap_or_default();
+            (row, attachments)
+        })
+        .collect();
+    if let Err(e) = with_index(app, |inner| {
+        for (row, attachments) in &rows {
+            add(inner, row, attachments)?;
+        }
+        commit(inner)
+    }) {
+        log::warn!("{}", e);
+    }
+    changed(
+        app,
+        TasksChanged {
+            updated: rows
+                .iter()
+                .map(|(row, _)| text(row, "id").to_string())
+                .collect(),
+            ..TasksChanged::default()
+        },
+    );
+}
+
+/// Re-index one task from the cache, dropping it if no longer cached
+pub fn reindex(app: &AppHandle, id: &str) {
+    let data = crate::offline::with_db(app, |conn| {
+        conn.query_row("SELECT data FROM tasks WHERE id = ?1", [id], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+    });
+    match data {
+        Ok(Some(data)) => upsert(app, &[data]),
+        Ok(None) => remove(app, id),
+        Err(e) => log::warn!("{}", e),
+    }
+}
+
+pub fn remove(app: &AppHandle, id: &str) {
+    if let Err(e) = with_index(app, |inner| {
+        inner
+            .writer
+            .delete_term(Term::from_field_text(inner.fields.id, id));
+        commit(inner)
+    }) {
+        log::warn!("{}", e);
+    }
+    changed(
+        app,
+        TasksChanged {
+            removed: vec![id.to_string()],
+            ..TasksChanged::default()
+        },
+    );
+}
+
+/// Build the index in the background at startup, once the user pauses (a
+/// startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        crate::background::yield_to_user(&app, MAX_BUILD_DEFER).await;
+        let result = tauri::async_runtime::spawn_blocking(move || rebuild(&app)).await;
+        match result.map_err(|e| e.to_string()).and_then(|r| r) {
+            Ok(count) => log::info!("Indexed {} cached tasks for search", count),
+            Err(e) => log::warn!("Search index not built: {}", e),
+        }
+    })
+}
+
+/// Lowercased words of a query, split like tantivy's default tokenizer
+fn words(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn build_query(fields: &Fields, words: &[String], filters: &SearchFilters) -> BooleanQuery {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+    for word in words {
+        let mut alternatives: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for (field, boost) in [
+            (fields.title, 3.0),
+            (fields.description, 1.0),
+            (fields.tags, 2.0),
+            (fields.attachments, 0.5),
+        ] {
+            let term = Term::from_field_text(field, word);
+            let exact = TermQuery::new(term.clone(), IndexRecordOption::WithFreqs);
+            let prefix = FuzzyTermQuery::new_prefix(term, 0, true);
+            alternatives.push((
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(exact), boost * 2.0)),
+            ));
+            alternatives.push((
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(prefix), boost)),
+            ));
+        }
+        clauses.push((Occur::Must, Box::new(BooleanQuery::new(alternatives))));
+    }
+    if words.is_empty() {
+        clauses.push((Occur::Must, Box::new(AllQuery)));
+    }
+
+    let term = |field: Field, value: &str| -> Box<dyn Query> {
+        Box::new(TermQuery::new(
+            Term::from_field_text(field, value),
+            IndexRecordOption::Basic,
+        ))
+    };
+    if let Some(statuses) = filters.status.as_ref().filter(|s| !s.is_empty()) {
+        let any = statuses
+            .iter()
+            .map(|s| (Occur::Should, term(fields.status, s)))
+            .collect();
+        clauses.push((Occur::Must, Box::new(BooleanQuery::new(any))));
+    }
+    if let Some(project_id) = &filters.project_id {
+        clauses.push((Occur::Must, term(fields.project_id, project_id)));
+    }
+    if !filters.include_deleted {
+        clauses.push((Occur::MustNot, term(fields.deleted, "true")));
+    }
+    BooleanQuery::new(clauses)
+}
+
+/// Byte ranges of words in `text` that start with one of `words`
+fn highlights(text: &str, words: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                let word = text[s..i].to_lowercase();
+                if let Some(w) = words.iter().find(|w| word.starts_with(w.as_str())) {
+                    // Highlight the matched prefix only
+                    let len = text[s..i]
+                        .char_indices()
+                        .nth(w.chars().count())
+                        .map_or(i - s, |(n, _)| n);
+                    ranges.push(s..s + len);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Window of `text` around its first highlight
+fn snippet(field: &'static str, text: &str, words: &[String]) -> Option<Snippet> {
+    let ranges = highlights(text, words);
+    let first = ranges.first()?.start;
+
+    let floor = |mut i: usize| {
+        while !text.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let from = floor(first.saturating_sub(SNIPPET_CONTEXT));
+    let to = floor((first + SNIPPET_CONTEXT * 2).min(text.len()));
+    let prefix = if from > 0 { "…" } else { "" };
+    let suffix = if to < text.len() { "…" } else { "" };
+    let shift = prefix.len();
+
+    Some(Snippet {
+        field,
+        text: format!("{}{}{}", prefix, &text[from..to], suffix),
+        highlights: ranges
+            .into_iter()
+            .filter(|r| r.start >= from && r.end <= to)
+            .map(|r| [r.start - from + shift, r.end - from + shift])
+            .collect(),
+    })
+}
+
+/// Ranked task search with prefix matching and highlighted snippets
+#[tauri::command]
+pub fn search_tasks(
+    app: AppHandle,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> Result<Vec<SearchHit>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "search_tasks")?;
+    let filters = filters.unwrap_or_default();
+    let limit = filters.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let words = words(&query);
+
+    Ok(with_index(&app, |inner| {
+        let f = &inner.fields;
+        let searcher = inner.reader.searcher();
+        let top = searcher.search(
+            &build_query(f, &words, &filters),
+            &TopDocs::with_limit(limit),
+        )?;
+
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let get = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+            };
+            let title = get(f.title).unwrap_or_default();
+            let snippet = snippet("title", &title, &words)
+                .or_else(|| get(f.description).and_then(|d| snippet("description", &d, &words)))
+                .or_else(|| get(f.attachments).and_then(|a| snippet("attachment", &a, &words)));
+            hits.push(SearchHit {
+                id: get(f.id).unwrap_or_default(),
+                status: get(f.status),
+                project_id: get(f.project_id),
+                score,
+                snippet,
+                title,
+            });
+        }
+        Ok(hits)
+    })?)
+}
@@ -0,0 +1,355 @@
+//! Secrets in the OS keychain, namespaced per workspace.
+//!
+//! Keys and tokens never go into a store file: each secret is a keychain
+//! entry of the `flowstate-secrets` service with the account
+//! `<workspace>/<name>`, where the workspace defaults to the current one (see
+//! `conflicts::current_workspace`). The Supabase service role key, JWT secret
+//! and S3 secret key reported by `supabase status` are moved here as soon as
+//! they are read, and `SupabaseConfig` values leave the backend without them;
+//! read them with `get_secret("supabase.service_role_key")` and so on.
+//!
+//! Plaintext only leaves through `get_secret`, `export_secrets` and the
+//! clipboard (`clipboard::copy_secret`), and only
+//! after the OS authentication prompt (`os_auth.rs`) succeeds; each secret
+//! revealed gets an entry in the audit log (`audit.rs`), as do refused
+//! prompts. The keychain can't list entries, so the names stored per
+//! workspace are also kept in `secrets.json` for `list_secrets` and exports.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::os_auth::Purpose;
+use crate::status::SupabaseConfig;
+
+const KEYRING_SERVICE: &str = "flowstate-secrets";
+pub const SUPABASE_SERVICE_ROLE_KEY: &str = "supabase.service_role_key";
+pub const SUPABASE_JWT_SECRET: &str = "supabase.jwt_secret";
+pub const SUPABASE_S3_SECRET_KEY: &str = "supabase.s3_secret_key";
+/// Names of the secrets stored per workspace (never their values)
+pub(crate) const INDEX_STORE: &str = "secrets.json";
+/// Secrets the app itself writes, exported even if stored before the index
+const KNOWN_SECRETS: [&str; 6] = [
+    SUPABASE_SERVICE_ROLE_KEY,
+    SUPABASE_JWT_SECRET,
+    SUPABASE_S3_SECRET_KEY,
+    crate::provision::REMOTE_ANON_KEY,
+    crate::provision::REMOTE_SERVICE_ROLE_KEY,
+    crate::provision::REMOTE_DB_PASSWORD,
+];
+const REVEAL_ACTION: &str = "secret.reveal";
+const EXPORT_ACTION: &str = "secret.export";
+pub(crate) const COPY_ACTION: &str = "secret.copy";
+const DENIED_ACTION: &str = "secret.reveal_denied";
+
+/// Hashes of the values last written, so polling `supabase status` doesn't
+/// rewrite unchanged keychain entries
+#[derive(Default)]
+pub struct Secrets {
+    written: Mutex<HashMap<String, [u8; 32]>>,
+}
+
+fn validate(part: &str, what: &str) -> Result<(), String> {
+    let valid = !part.is_empty()
+        && part.len() <= 128
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid secret {}: {:?}", what, part))
+    }
+}
+
+fn account(workspace: &str, name: &str) -> String {
+    format!("{}/{}", workspace, name)
+}
+
+fn keyring_entry(workspace: &str, name: &str) -> Result<keyring::Entry, String> {
+    validate(workspace, "workspace")?;
+    validate(name, "name")?;
+    keyring::Entry::new(KEYRING_SERVICE, &account(workspace, name))
+        .map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// Names recorded for `workspace`
+fn indexed_names(app: &AppHandle, workspace: &str) -> Vec<String> {
+    app.store(INDEX_STORE)
+        .ok()
+        .and_then(|store| store.get(workspace))
+        .and_then(|names| serde_json::from_value(names).ok())
+        .unwrap_or_default()
+}
+
+/// Add `name` to or drop it from the index; the keychain stays the source of
+/// truth, so failures are only logged
+fn update_index(app: &AppHandle, workspace: &str, name: &str, stored: bool) {
+    let mut names: BTreeSet<String> = indexed_names(app, workspace).into_iter().collect();
+    let changed = if stored {
+        names.insert(name.to_string())
+    } else {
+        names.remove(name)
+    };
+    if !changed {
+        return;
+    }
+    let result = app
+        .store(INDEX_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", INDEX_STORE, e))
+        .and_then(|store| {
+            store.set(workspace, serde_json::json!(names));
+            store
+                .save()
+                .map_err(|e| format!("Failed to save {}: {}", INDEX_STORE, e))
+        });
+    if let Err(e) = result {
+        log::warn!("{}", e);
+    }
+}
+
+/// Names with a value in the keychain for `workspace`
+fn stored_names(app: &AppHandle, workspace: &str) -> Result<Vec<String>, String> {
+    let candidates: BTreeSet<String> = indexed_names(app, workspace)
+        .into_iter()
+        .chain(KNOWN_SECRETS.iter().map(|name| name.to_string()))
+        .collect();
+    let mut names = Vec::new();
+    for name in candidates {
+        if get(workspace, &name)?.is_some() {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+pub(crate) fn set(app: &AppHandle, workspace: &str, name: &str, value: &str) -> Result<(), String> {
+    keyring_entry(workspace, name)?
+        .set_password(value)
+        .map_err(|e| format!("Failed to store {} in keychain: {}", name, e))?;
+    update_index(app, workspace, name, true);
+    app.state::<Secrets>()
+        .written
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(account(workspace, name), Sha256::digest(value).into());
+    Ok(())
+}
+
+pub(crate) fn get(workspace: &str, name: &str) -> Result<Option<String>, String> {
+    match keyring_entry(workspace, name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read {} from keychain: {}", name, e)),
+    }
+}
+
+pub(crate) fn delete(app: &AppHandle, workspace: &str, name: &str) -> Result<(), String> {
+    match keyring_entry(workspace, name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(format!("Failed to delete {} from keychain: {}", name, e)),
+    }
+    update_index(app, workspace, name, false);
+    app.state::<Secrets>()
+        .written
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&account(workspace, name));
+    Ok(())
+}
+
+/// Workspaces with secrets in the index, and the current one
+fn workspaces(app: &AppHandle) -> BTreeSet<String> {
+    let mut workspaces: BTreeSet<String> = app
+        .store(INDEX_STORE)
+        .map(|store| store.keys())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|workspace| validate(workspace, "workspace").is_ok())
+        .collect();
+    workspaces.insert(crate::conflicts::current_workspace());
+    workspaces
+}
+
+/// Every stored secret by workspace and name, each audited as exported;
+/// callers show the OS authentication prompt first
+pub(crate) fn export_all(
+    app: &AppHandle,
+) -> Result<BTreeMap<String, BTreeMap<String, String>>, String> {
+    let mut secrets = BTreeMap::new();
+    for workspace in workspaces(app) {
+        let mut values = BTreeMap::new();
+        for name in stored_names(app, &workspace)? {
+            let Some(value) = get(&workspace, &name)? else {
+                continue;
+            };
+            crate::audit::record(app, EXPORT_ACTION, &account(&workspace, &name), None)?;
+            values.insert(name, value);
+        }
+        if !values.is_empty() {
+            secrets.insert(workspace, values);
+        }
+    }
+    Ok(secrets)
+}
+
+/// Delete every stored secret of every workspace; returns how many
+pub(crate) fn delete_all(app: &AppHandle) -> Result<usize, String> {
+    let mut deleted = 0;
+    for workspace in workspaces(app) {
+        for name in stored_names(app, &workspace)? {
+            delete(app, &workspace, &name)?;
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+/// Store `value` unless this process already wrote the same one
+fn set_if_changed(app: &AppHandle, workspace: &str, name: &str, value: &str) -> Result<(), String> {
+    let hash: [u8; 32] = Sha256::digest(value).into();
+    let unchanged = app
+        .state::<Secrets>()
+        .written
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&account(workspace, name))
+        == Some(&hash);
+    if unchanged {
+        return Ok(());
+    }
+    set(app, workspace, name, value)
+}
+
+/// Move the secret keys of `config` into the keychain of the current
+/// workspace, leaving only URLs and the (public) anon key
+pub(crate) fn stash_supabase_keys(app: &AppHandle, mut config: SupabaseConfig) -> SupabaseConfig {
+    let workspace = crate::conflicts::current_workspace();
+    let secrets = [
+        (SUPABASE_SERVICE_ROLE_KEY, config.service_role_key.take()),
+        (SUPABASE_JWT_SECRET, config.jwt_secret.take()),
+        (SUPABASE_S3_SECRET_KEY, config.s3_secret_key.take()),
+    ];
+    for (name, value) in secrets {
+        let Some(value) = value else {
+            continue;
+        };
+        if let Err(e) = set_if_changed(app, &workspace, name, &value) {
+            log::warn!("{}", e);
+        }
+    }
+    config
+}
+
+/// Store a secret for `workspace` (default: the current one)
+#[tauri::command]
+pub fn set_secret(
+    app: AppHandle,
+    workspace: Option<String>,
+    name: String,
+    value: String,
+) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_secret")?;
+    crate::app_lock::ensure_unlocked(&app, "set_secret")?;
+    let workspace = workspace.unwrap_or_else(crate::conflicts::current_workspace);
+    Ok(set(&app, &workspace, &name, &value)?)
+}
+
+/// Show the OS authentication prompt for revealing `what`; refusals are
+/// audited too
+async fn authorize(app: &AppHandle, subject: &str, what: &str) -> Result<(), String> {
+    let reason = format!("reveal {}", what);
+    if let Err(e) = crate::os_auth::verify(app, Purpose::RevealSecret, &reason).await {
+        crate::audit::record(app, DENIED_ACTION, subject, Some(&e))?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Plaintext of a secret once the user passes the OS authentication prompt,
+/// audited as `action`
+pub(crate) async fn reveal(
+    app: &AppHandle,
+    workspace: Option<String>,
+    name: &str,
+    action: &str,
+) -> Result<Option<String>, String> {
+    let workspace = workspace.unwrap_or_else(crate::conflicts::current_workspace);
+    validate(&workspace, "workspace")?;
+    validate(name, "name")?;
+    let subject = account(&workspace, name);
+    authorize(app, &subject, &format!("the secret {}", name)).await?;
+    let value = get(&workspace, name)?;
+    if value.is_some() {
+        crate::audit::record(app, action, &subject, None)?;
+    }
+    Ok(value)
+}
+
+/// Plaintext of a secret, once the user passes the OS authentication prompt
+#[tauri::command]
+pub async fn get_secret(
+    app: AppHandle,
+    workspace: Option<String>,
+    name: String,
+) -> Result<Option<String>, FlowStateError> {
+    crate::trace::scope("get_secret", async move {
+        crate::app_lock::ensure_unlocked(&app, "get_secret")?;
+        Ok(reveal(&app, workspace, &name, REVEAL_ACTION).await?)
+    })
+    .await
+}
+
+/// Names of the secrets stored for `workspace` (default: the current one)
+#[tauri::command]
+pub fn list_secrets(
+    app: AppHandle,
+    workspace: Option<String>,
+) -> Result<Vec<String>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "list_secrets")?;
+    let workspace = workspace.unwrap_or_else(crate::conflicts::current_workspace);
+    validate(&workspace, "workspace")?;
+    Ok(stored_names(&app, &workspace)?)
+}
+
+/// Every secret of `workspace` by name, after one OS authentication prompt;
+/// each is audited
+#[tauri::command]
+pub async fn export_secrets(
+    app: AppHandle,
+    workspace: Option<String>,
+) -> Result<BTreeMap<String, String>, FlowStateError> {
+    crate::trace::scope("export_secrets", async move {
+        crate::app_lock::ensure_unlocked(&app, "export_secrets")?;
+        let workspace = workspace.unwrap_or_else(crate::conflicts::current_workspace);
+        validate(&workspace, "workspace")?;
+        let subject = account(&workspace, "*");
+        authorize(&app, &subject, &format!("the secrets of {}", workspace)).await?;
+        let mut secrets = BTreeMap::new();
+        for name in stored_names(&app, &workspace)? {
+            let Some(value) = get(&workspace, &name)? else {
+                continue;
+            };
+            crate::audit::record(&app, EXPORT_ACTION, &account(&workspace, &name), None)?;
+            secrets.insert(name, value);
+        }
+        Ok(secrets)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn delete_secret(
+    app: AppHandle,
+    workspace: Option<String>,
+    name: String,
+) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "delete_secret")?;
+    crate::app_lock::ensure_unlocked(&app, "delete_secret")?;
+    let workspace = workspace.unwrap_or_else(crate::conflicts::current_workspace);
+    Ok(delete(&app, &workspace, &name)?)
+}
@@ -0,0 +1,271 @@
+//! Seed data for the local database.
+//!
+//! Seed files are `supabase/seed.sql` and any `supabase/seeds/*.sql` in the
+//! configured project directory. A file is split into statements and run in
+//! batches through `psql` inside the db container, each batch in its own
+//! transaction, so the result says which batch failed and why. Applying stops
+//! at the first failed batch since later statements usually depend on it.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::CommandEvent;
+
+use crate::error::FlowStateError;
+use crate::parsers;
+
+/// Statements per psql invocation
+const BATCH_SIZE: usize = 50;
+/// Characters of the first statement shown in a batch result
+const PREVIEW_LEN: usize = 80;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedFile {
+    /// Relative to the project directory, e.g. "supabase/seeds/tasks.sql"
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+    pub index: usize,
+    pub statements: usize,
+    /// Start of the batch's first statement
+    pub preview: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedReport {
+    pub file: String,
+    pub success: bool,
+    pub batches: Vec<BatchResult>,
+    /// Batches not run because an earlier one failed
+    pub skipped: usize,
+}
+
+fn project_dir() -> Result<PathBuf, String> {
+    crate::project_dir::get().ok_or_else(|| {
+        "Set the Supabase project directory first (set_supabase_project_dir)".to_string()
+    })
+}
+
+fn seed_file(dir: &Path, relative: String) -> Option<SeedFile> {
+    let metadata = std::fs::metadata(dir.join(&relative)).ok()?;
+    metadata.is_file().then_some(SeedFile {
+        path: relative,
+        bytes: metadata.len(),
+    })
+}
+
+/// seed.sql first, then supabase/seeds/*.sql in name order
+fn list(dir: &Path) -> Vec<SeedFile> {
+    let mut files: Vec<SeedFile> = seed_file(dir, "supabase/seed.sql".to_string())
+        .into_iter()
+        .collect();
+
+    let mut extra: Vec<String> = std::fs::read_dir(dir.join("supabase").join("seeds"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| name.ends_with(".sql"))
+                .collect()
+        })
+        .unwrap_or_default();
+    extra.sort();
+    files.extend(
+        extra
+            .into_iter()
+            .filter_map(|name| seed_file(dir, format!("supabase/seeds/{}", name))),
+    );
+
+    files
+}
+
+fn preview(statement: &str) -> String {
+    let line = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(PREVIEW_LEN) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line,
+    }
+}
+
+/// Run one batch of statements in a single transaction
+async fn run_batch(app: &AppHandle, statements: &[String]) -> Result<(), String> {
+    let container = crate::db_container_name();
+    let (mut rx, mut child) = crate::trace::command(app, "docker")
+        .args([
+            "exec",
+            "-i",
+            container.as_str(),
+            "psql",
+            "-U",
+            "postgres",
+            "-d",
+            "postgres",
+            "-q",
+            "-v",
+            "ON_ERROR_STOP=1",
+            "--single-transaction",
+        ])
+        .spawn()
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+
+    // Write from a blocking task while the output is drained here, so psql
+    // never stalls on a full pipe with input still to read
+    let input: String = statements.iter().map(|s| format!("{};\n", s)).collect();
+    let writer = tauri::async_runtime::spawn_blocking(move || {
+        let written = child
+            .write(input.as_bytes())
+            .map_err(|e| format!("psql stopped reading input: {}", e));
+        // Dropping the child closes stdin so psql sees EOF
+        drop(child);
+        written
+    });
+
+    let mut stderr = String::new();
+    let mut code = None;
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stderr(line) => stderr.push_str(&String::from_utf8_lossy(&line)),
+            CommandEvent::Terminated(payload) => {
+                code = Some(payload.code);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let written = writer
+        .await
+        .map_err(|e| format!("psql input task failed: {}", e))?;
+
+    match code {
+        Some(Some(0)) => written,
+        Some(_) => Err(stderr.trim().to_string()),
+        None => Err("psql exited without a status".to_string()),
+    }
+}
+
+/// Check that `file` is one of the project's seed files and split it into
+/// statements
+fn load(dir: &Path, file: &str) -> Result<Vec<String>, String> {
+    if !list(dir).iter().any(|f| f.path == file) {
+        return Err(format!("Not a seed file in this project: {}", file));
+    }
+
+    let path = dir.join(file);
+    let content =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parsers::split_sql_statements(&content).map_err(|e| format!("Failed to parse {}: {}", file, e))
+}
+
+async fn apply(app: &AppHandle, file: &str, statements: &[String]) -> SeedReport {
+    let chunks: Vec<&[String]> = statements.chunks(BATCH_SIZE).collect();
+    let mut batches = Vec::new();
+    let mut success = true;
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let result = run_batch(app, chunk).await;
+        let error = result.err();
+        success = error.is_none();
+        batches.push(BatchResult {
+            index,
+            statements: chunk.len(),
+            preview: preview(&chunk[0]),
+            success,
+            error,
+        });
+        if !success {
+            break;
+        }
+    }
+
+    // Earlier batches are committed even when a later one fails
+    crate::heatmap::invalidate(app);
+
+    let skipped = chunks.len() - batches.len();
+    if success {
+        log::info!("Applied seed {} ({} statements)", file, statements.len());
+    } else {
+        log::warn!(
+            "Seed {} failed at batch {} of {}",
+            file,
+            batches.len(),
+            chunks.len()
+        );
+    }
+
+    SeedReport {
+        file: file.to_string(),
+        success,
+        batches,
+        skipped,
+    }
+}
+
+/// Seed files in the configured project directory
+#[tauri::command]
+pub fn list_seed_files() -> Result<Vec<SeedFile>, FlowStateError> {
+    Ok(list(&project_dir()?))
+}
+
+/// Run one seed file (a path from `list_seed_files`) against the local database
+#[tauri::command]
+pub async fn apply_seed(app: AppHandle, file: String) -> Result<SeedReport, FlowStateError> {
+    crate::trace::scope("apply_seed", async move {
+        crate::app_lock::ensure_unlocked(&app, "apply_seed")?;
+        crate::read_only::ensure_writable(&app, "apply_seed")?;
+        let statements = load(&project_dir()?, &file)?;
+        Ok(apply(&app, &file, &statements).await)
+    })
+    .await
+}
+
+/// Reset the local database (re-running migrations) and apply the given seed
+/// files, or every seed file when none are given
+#[tauri::command]
+pub async fn reset_and_seed(
+    app: AppHandle,
+    files: Option<Vec<String>>,
+) -> Result<Vec<SeedReport>, FlowStateError> {
+    crate::trace::scope("reset_and_seed", async move {
+        crate::app_lock::ensure_unlocked(&app, "reset_and_seed")?;
+        crate::read_only::ensure_writable(&app, "reset_and_seed")?;
+        let dir = project_dir()?;
+        let files = files.unwrap_or_else(|| list(&dir).into_iter().map(|f| f.path).collect());
+        // Check every file before the reset wipes the database
+        let seeds = files
+            .into_iter()
+            .map(|file| load(&dir, &file).map(|statements| (file, statements)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output = crate::trace::command(&app, "supabase")
+            .args(["db", "reset", "--local", "--no-seed"])
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run supabase: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(format!("Failed to reset database: {}", stderr).into());
+        }
+        crate::heatmap::invalidate(&app);
+        log::info!("Reset local database");
+
+        let mut reports = Vec::new();
+        for (file, statements) in seeds {
+            let report = apply(&app, &file, &statements).await;
+            let failed = !report.success;
+            reports.push(report);
+            if failed {
+                break;
+            }
+        }
+        Ok(reports)
+    })
+    .await
+}
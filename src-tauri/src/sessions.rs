@@ -0,0 +1,533 @@
+//! Recorded focus sessions and their tags.
+//!
+//! Every focus phase that ends, whether it runs out or the session is
+//! stopped early, is written to `sessions.db` in the app data directory with
+//! the tags it carried (deep work, meetings, admin, ...). Tags are set when
+//! the session starts, changed while it runs (`focus.rs`) or replaced later
+//! with `tag_focus_session`, and live in their own table so the breakdown is
+//! a single GROUP BY. Changes are published as `sessions://changed` for the
+//! frontend to sync and to refresh the weekly report and tag budgets.
+//!
+//! Interruptions (a colleague, a message, a stray thought) are logged during
+//! a focus phase with `log_interruption` or the interruption shortcut
+//! (`shortcut.rs`), keyed by the phase's start, and linked to the session
+//! when it is recorded. Idle periods from the idle monitor (`idle.rs`) are
+//! kept here too, for the personal baselines in `anomalies.rs`, and so is
+//! the coding time editor plugins report (`ide.rs`). Past the
+//! retention window only the hourly and daily rollups of all this remain
+//! (`rollups.rs`).
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::focus::{FocusPhase, FocusStatus};
+use crate::sqlite::LocalDb;
+
+const DB_FILE: &str = "sessions.db";
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 40;
+const MAX_NOTE_LEN: usize = 500;
+/// Kind of interruptions logged through the shortcut
+pub const QUICK_INTERRUPTION_KIND: &str = "distraction";
+
+pub(crate) const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS focus_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id TEXT,
+    started_at INTEGER NOT NULL,
+    ended_at INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    completed INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_focus_sessions_started ON focus_sessions(started_at);
+CREATE TABLE IF NOT EXISTS session_tags (
+    session_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (session_id, tag)
+);
+CREATE INDEX IF NOT EXISTS idx_session_tags_tag ON session_tags(tag);
+CREATE TABLE IF NOT EXISTS interruptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER,
+    phase_started_at INTEGER NOT NULL,
+    at INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    note TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_interruptions_session ON interruptions(session_id);
+CREATE INDEX IF NOT EXISTS idx_interruptions_phase ON interruptions(phase_started_at);
+CREATE INDEX IF NOT EXISTS idx_interruptions_at ON interruptions(at);
+CREATE TABLE IF NOT EXISTS idle_periods (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL,
+    ended_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_idle_periods_started ON idle_periods(started_at);
+CREATE TABLE IF NOT EXISTS activity_hourly (
+    hour_start INTEGER PRIMARY KEY,
+    day TEXT NOT NULL,
+    focus_ms INTEGER NOT NULL,
+    focus_sessions INTEGER NOT NULL,
+    completed INTEGER NOT NULL,
+    idle_ms INTEGER NOT NULL,
+    interruptions INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_activity_hourly_day ON activity_hourly(day);
+CREATE TABLE IF NOT EXISTS activity_daily (
+    day TEXT PRIMARY KEY,
+    day_start INTEGER NOT NULL,
+    focus_ms INTEGER NOT NULL,
+    focus_sessions INTEGER NOT NULL,
+    completed INTEGER NOT NULL,
+    idle_ms INTEGER NOT NULL,
+    interruptions INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tag_daily (
+    day TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    day_start INTEGER NOT NULL,
+    sessions INTEGER NOT NULL,
+    completed INTEGER NOT NULL,
+    focus_ms INTEGER NOT NULL,
+    PRIMARY KEY (day, tag)
+);
+CREATE INDEX IF NOT EXISTS idx_tag_daily_start ON tag_daily(day_start);
+CREATE TABLE IF NOT EXISTS coding_spans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    workspace TEXT NOT NULL,
+    project_id TEXT,
+    language TEXT,
+    editor TEXT,
+    started_at INTEGER NOT NULL,
+    ended_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_coding_spans_started ON coding_spans(started_at);
+CREATE INDEX IF NOT EXISTS idx_coding_spans_workspace ON coding_spans(workspace, ended_at);
+CREATE TABLE IF NOT EXISTS rollup_meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);";
+
+const COLUMNS: &str = "s.id, s.task_id, s.started_at, s.ended_at, s.duration_ms, s.completed, \
+     (SELECT group_concat(tag, char(31)) FROM session_tags WHERE session_id = s.id), \
+     (SELECT count(*) FROM interruptions WHERE session_id = s.id)";
+
+pub struct Sessions {
+    pub(crate) db: LocalDb,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions {
+            db: LocalDb::new(DB_FILE, SCHEMA),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSessionRecord {
+    pub id: i64,
+    pub task_id: Option<String>,
+    pub started_at_ms: i64,
+    pub ended_at_ms: i64,
+    /// Time spent focusing (pauses excluded)
+    pub duration_ms: i64,
+    /// False when the session was stopped before the phase ran out
+    pub completed: bool,
+    pub tags: Vec<String>,
+    pub interruptions: i64,
+}
+
+impl FocusSessionRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let tags: Option<String> = row.get(6)?;
+        let mut tags: Vec<String> = tags
+            .map(|t| t.split('\u{1f}').map(str::to_string).collect())
+            .unwrap_or_default();
+        tags.sort();
+        Ok(FocusSessionRecord {
+            id: row.get(0)?,
+            task_id: row.get(1)?,
+            started_at_ms: row.get(2)?,
+            ended_at_ms: row.get(3)?,
+            duration_ms: row.get(4)?,
+            completed: row.get(5)?,
+            tags,
+            interruptions: row.get(7)?,
+        })
+    }
+}
+
+/// Sessions overlapping [from_ms, to_ms); open ends are unbounded
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionRange {
+    pub from_ms: Option<i64>,
+    pub to_ms: Option<i64>,
+    pub tag: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagTotal {
+    /// None for untagged sessions
+    pub tag: Option<String>,
+    pub sessions: i64,
+    pub completed: i64,
+    pub focus_ms: i64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interruption {
+    pub id: i64,
+    pub task_id: Option<String>,
+    pub at_ms: i64,
+    pub kind: String,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindCount {
+    pub kind: String,
+    pub count: i64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptionStats {
+    pub sessions: i64,
+    pub interruptions: i64,
+    /// Interruptions per recorded session (0 without sessions)
+    pub per_session: f64,
+    /// Most frequent first
+    pub by_kind: Vec<KindCount>,
+}
+
+pub(crate) fn with_db<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    app.state::<Sessions>().db.with(app, f)
+}
+
+/// Trimmed and lowercased tag or interruption kind; None if empty
+fn normalize_label(label: &str) -> Result<Option<String>, String> {
+    let label = label.trim().to_lowercase();
+    if label.chars().count() > MAX_TAG_LEN {
+        return Err(format!(
+            "'{}' is longer than {} characters",
+            label, MAX_TAG_LEN
+        ));
+    }
+    Ok(Some(label).filter(|l| !l.is_empty()))
+}
+
+/// Trim, lowercase and deduplicate tags
+pub(crate) fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let Some(tag) = normalize_label(&tag)? else {
+            continue;
+        };
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(format!("A session takes at most {} tags", MAX_TAGS));
+    }
+    normalized.sort();
+    Ok(normalized)
+}
+
+fn replace_tags(conn: &Connection, session_id: i64, tags: &[String]) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM session_tags WHERE session_id = ?1",
+        params![session_id],
+    )?;
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO session_tags (session_id, tag) VALUES (?1, ?2)",
+            params![session_id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// Record a finished focus phase; returns its id
+pub(crate) fn record(
+    app: &AppHandle,
+    task_id: Option<&str>,
+    started_at_ms: u64,
+    ended_at_ms: u64,
+    duration_ms: u64,
+    completed: bool,
+    tags: &[String],
+) -> Result<i64, String> {
+    let record = with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO focus_sessions (task_id, started_at, ended_at, duration_ms, completed) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                task_id,
+                started_at_ms as i64,
+                ended_at_ms as i64,
+                duration_ms as i64,
+                completed
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        replace_tags(&tx, id, tags)?;
+        let interruptions = tx.execute(
+            "UPDATE interruptions SET session_id = ?1 \
+             WHERE session_id IS NULL AND phase_started_at = ?2",
+            params![id, started_at_ms as i64],
+        )?;
+        tx.commit()?;
+        Ok(FocusSessionRecord {
+            id,
+            task_id: task_id.map(str::to_string),
+            started_at_ms: started_at_ms as i64,
+            ended_at_ms: ended_at_ms as i64,
+            duration_ms: duration_ms as i64,
+            completed,
+            tags: tags.to_vec(),
+            interruptions: interruptions as i64,
+        })
+    })?;
+    crate::events::publish(app, "sessions://changed", &record);
+    Ok(record.id)
+}
+
+/// Record a period without input, from the last input to the next one
+pub(crate) fn record_idle(
+    app: &AppHandle,
+    started_at_ms: i64,
+    ended_at_ms: i64,
+) -> Result<(), String> {
+    if ended_at_ms <= started_at_ms {
+        return Ok(());
+    }
+    with_db(app, |conn| {
+        conn.execute(
+            "INSERT INTO idle_periods (started_at, ended_at) VALUES (?1, ?2)",
+            params![started_at_ms, ended_at_ms],
+        )
+    })?;
+    Ok(())
+}
+
+/// Log an interruption against the current focus phase
+pub(crate) fn interrupt(
+    app: &AppHandle,
+    kind: &str,
+    note: Option<String>,
+) -> Result<Interruption, String> {
+    let kind = normalize_label(kind)?.ok_or("Interruption kind must not be empty")?;
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > MAX_NOTE_LEN)
+    {
+        return Err(format!("Note is longer than {} characters", MAX_NOTE_LEN));
+    }
+    let state = app.state::<crate::focus::FocusEngine>().snapshot();
+    let phase_started_at_ms = match (state.status, state.phase, state.phase_started_at_ms) {
+        (FocusStatus::Running | FocusStatus::Paused, FocusPhase::Focus, Some(started)) => started,
+        _ => return Err("No focus session running".to_string()),
+    };
+
+    let at_ms = now_ms() as i64;
+    let interruption = with_db(app, |conn| {
+        conn.execute(
+            "INSERT INTO interruptions (phase_started_at, at, kind, note) VALUES (?1, ?2, ?3, ?4)",
+            params![phase_started_at_ms as i64, at_ms, kind, note],
+        )?;
+        Ok(Interruption {
+            id: conn.last_insert_rowid(),
+            task_id: state.task_id.clone(),
+            at_ms,
+            kind,
+            note,
+        })
+    })?;
+    crate::events::publish(app, "sessions://interruption", &interruption);
+    Ok(interruption)
+}
+
+/// Log what broke the current focus phase (e.g. "message", "colleague")
+#[tauri::command]
+pub fn log_interruption(
+    app: AppHandle,
+    kind: String,
+    note: Option<String>,
+) -> Result<Interruption, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "log_interruption")?;
+    Ok(interrupt(&app, &kind, note)?)
+}
+
+/// Replace the tags of a recorded session
+#[tauri::command]
+pub fn tag_focus_session(
+    app: AppHandle,
+    session_id: i64,
+    tags: Vec<String>,
+) -> Result<FocusSessionRecord, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "tag_focus_session")?;
+    let tags = normalize_tags(tags)?;
+    let record = with_db(&app, |conn| {
+        let tx = conn.transaction()?;
+        let Some(record) = tx
+            .query_row(
+                &format!("SELECT {} FROM focus_sessions s WHERE s.id = ?1", COLUMNS),
+                params![session_id],
+                FocusSessionRecord::from_row,
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        replace_tags(&tx, session_id, &tags)?;
+        tx.commit()?;
+        Ok(Some(FocusSessionRecord { tags, ..record }))
+    })?
+    .ok_or_else(|| format!("No focus session {}", session_id))?;
+    crate::events::publish(&app, "sessions://changed", &record);
+    Ok(record)
+}
+
+/// Recorded sessions overlapping a range (optionally with one tag), oldest first
+#[tauri::command]
+pub fn list_focus_sessions(
+    app: AppHandle,
+    range: Option<SessionRange>,
+) -> Result<Vec<FocusSessionRecord>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "list_focus_sessions")?;
+    let range = range.unwrap_or_default();
+    Ok(with_db(&app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM focus_sessions s \
+             WHERE (?1 IS NULL OR s.ended_at > ?1) \
+               AND (?2 IS NULL OR s.started_at < ?2) \
+               AND (?3 IS NULL OR EXISTS \
+                    (SELECT 1 FROM session_tags WHERE session_id = s.id AND tag = ?3)) \
+             ORDER BY s.started_at",
+            COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![range.from_ms, range.to_ms, range.tag],
+            FocusSessionRecord::from_row,
+        )?;
+        rows.collect()
+    })?)
+}
+
+/// Focus time per tag in a range, most first. A session with several tags
+/// counts toward each of them, so the totals can add up to more than the
+/// time focused. Days past the retention window come from the daily
+/// rollups and count whole.
+#[tauri::command]
+pub fn get_tag_breakdown(
+    app: AppHandle,
+    range: Option<SessionRange>,
+) -> Result<Vec<TagTotal>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_tag_breakdown")?;
+    let range = range.unwrap_or_default();
+    Ok(with_db(&app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT t.tag, count(*), sum(s.completed), sum(s.duration_ms) \
+             FROM focus_sessions s LEFT JOIN session_tags t ON t.session_id = s.id \
+             WHERE (?1 IS NULL OR s.ended_at > ?1) AND (?2 IS NULL OR s.started_at < ?2) \
+             GROUP BY t.tag ORDER BY sum(s.duration_ms) DESC",
+        )?;
+        let mut totals: Vec<TagTotal> = stmt
+            .query_map(params![range.from_ms, range.to_ms], |row| {
+                Ok(TagTotal {
+                    tag: row.get(0)?,
+                    sessions: row.get(1)?,
+                    completed: row.get(2)?,
+                    focus_ms: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let pruned_before = crate::rollups::pruned_before(conn)?;
+        let mut stmt = conn.prepare(
+            "SELECT nullif(tag, ''), sum(sessions), sum(completed), sum(focus_ms) FROM tag_daily \
+             WHERE (?1 IS NULL OR day_start >= ?1) AND day_start < min(coalesce(?2, ?3), ?3) \
+             GROUP BY tag",
+        )?;
+        let rolled_up =
+            stmt.query_map(params![range.from_ms, range.to_ms, pruned_before], |row| {
+                Ok(TagTotal {
+                    tag: row.get(0)?,
+                    sessions: row.get(1)?,
+                    completed: row.get(2)?,
+                    focus_ms: row.get(3)?,
+                })
+            })?;
+        for old in rolled_up {
+            let old = old?;
+            match totals.iter_mut().find(|t| t.tag == old.tag) {
+                Some(total) => {
+                    total.sessions += old.sessions;
+                    total.completed += old.completed;
+                    total.focus_ms += old.focus_ms;
+                }
+                None => totals.push(old),
+            }
+        }
+        totals.sort_by_key(|t| std::cmp::Reverse(t.focus_ms));
+        Ok(totals)
+    })?)
+}
+
+/// Interruptions of the sessions recorded in a range
+#[tauri::command]
+pub fn get_interruption_stats(
+    app: AppHandle,
+    range: Option<SessionRange>,
+) -> Result<InterruptionStats, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_interruption_stats")?;
+    let range = range.unwrap_or_default();
+    Ok(with_db(&app, |conn| {
+        let (sessions, interruptions): (i64, i64) = conn.query_row(
+            "SELECT count(*), \
+                    coalesce(sum((SELECT count(*) FROM interruptions WHERE session_id = s.id)), 0) \
+             FROM focus_sessions s \
+             WHERE (?1 IS NULL OR s.ended_at > ?1) AND (?2 IS NULL OR s.started_at < ?2)",
+            params![range.from_ms, range.to_ms],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT i.kind, count(*) FROM interruptions i \
+             JOIN focus_sessions s ON s.id = i.session_id \
+             WHERE (?1 IS NULL OR s.ended_at > ?1) AND (?2 IS NULL OR s.started_at < ?2) \
+             GROUP BY i.kind ORDER BY count(*) DESC, i.kind",
+        )?;
+        let by_kind = stmt
+            .query_map(params![range.from_ms, range.to_ms], |row| {
+                Ok(KindCount {
+                    kind: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(InterruptionStats {
+            sessions,
+            interruptions,
+            per_session: if sessions > 0 {
+                interruptions as f64 / sessions as f64
+            } else {
+                0.0
+            },
+            by_kind,
+        })
+    })?)
+}
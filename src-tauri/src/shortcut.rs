@@ -0,0 +1,250 @@
+//! Global shortcuts for quick capture and interruptions.
+//!
+//! One system-wide accelerator (default `CommandOrControl+Shift+Space`) opens
+//! the always-on-top quick capture window from launch.rs, so a task can be
+//! added without switching to the app. A second one (default
+//! `CommandOrControl+Alt+I`) logs a distraction against the running focus
+//! session (`sessions.rs`) without opening anything. The accelerators persist
+//! in `shortcuts.json`; setting one to `None` turns it off.
+
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::notifications::{Category, Priority};
+
+pub(crate) const SHORTCUT_STORE: &str = "shortcuts.json";
+pub const DEFAULT_SHORTCUT: &str = "CommandOrControl+Shift+Space";
+pub const DEFAULT_INTERRUPTION_SHORTCUT: &str = "CommandOrControl+Alt+I";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    QuickCapture,
+    Interruption,
+}
+
+impl Action {
+    const ALL: [Action; 2] = [Action::QuickCapture, Action::Interruption];
+
+    fn store_key(self) -> &'static str {
+        match self {
+            Action::QuickCapture => "quickCapture",
+            Action::Interruption => "interruption",
+        }
+    }
+
+    fn default_accelerator(self) -> &'static str {
+        match self {
+            Action::QuickCapture => DEFAULT_SHORTCUT,
+            Action::Interruption => DEFAULT_INTERRUPTION_SHORTCUT,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::QuickCapture => "quick capture",
+            Action::Interruption => "interruption",
+        }
+    }
+}
+
+/// The accelerators currently registered, if any
+#[derive(Default)]
+pub struct GlobalShortcuts {
+    quick_capture: Mutex<Option<String>>,
+    interruption: Mutex<Option<String>>,
+}
+
+impl GlobalShortcuts {
+    fn slot(&self, action: Action) -> &Mutex<Option<String>> {
+        match action {
+            Action::QuickCapture => &self.quick_capture,
+            Action::Interruption => &self.interruption,
+        }
+    }
+
+    fn current(&self, action: Action) -> Option<String> {
+        self.slot(action)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalShortcutState {
+    /// None when the shortcut is turned off
+    pub shortcut: Option<String>,
+    /// False when another application already owns the accelerator
+    pub registered: bool,
+}
+
+/// Global shortcut plugin; dispatches on which accelerator was pressed
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+            if event.state() != ShortcutState::Pressed {
+                return;
+            }
+            let interruption = app
+                .state::<GlobalShortcuts>()
+                .current(Action::Interruption)
+                .and_then(|s| parse(&s).ok());
+            if interruption.is_some_and(|s| s.id() == shortcut.id()) {
+                log_interruption(app);
+            } else if let Err(e) = crate::launch::open_quick_capture(app) {
+                log::error!("Failed to open quick capture: {}", e);
+            }
+        })
+        .build()
+}
+
+fn log_interruption(app: &AppHandle) {
+    let result = crate::read_only::ensure_writable(app, "log_interruption")
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            crate::sessions::interrupt(app, crate::sessions::QUICK_INTERRUPTION_KIND, None)
+        });
+    let body = match result {
+        Ok(_) => "Interruption logged".to_string(),
+        Err(e) => {
+            log::info!("Interruption not logged: {}", e);
+            e
+        }
+    };
+    crate::notifications::notify(app, Category::Breaks, Priority::Low, "Focus session", &body);
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    Shortcut::from_str(accelerator)
+        .map_err(|e| format!("Invalid shortcut '{}': {}", accelerator, e))
+}
+
+fn state(app: &AppHandle, action: Action) -> GlobalShortcutState {
+    let shortcut = app.state::<GlobalShortcuts>().current(action);
+    let registered = shortcut
+        .as_deref()
+        .and_then(|s| parse(s).ok())
+        .is_some_and(|s| app.global_shortcut().is_registered(s));
+    GlobalShortcutState {
+        shortcut,
+        registered,
+    }
+}
+
+/// Swap the accelerator registered for `action` for `next`
+fn apply(app: &AppHandle, action: Action, next: Option<&str>) -> Result<(), String> {
+    let parsed = next.map(parse).transpose()?;
+    let shortcuts = app.state::<GlobalShortcuts>();
+    for other in Action::ALL.into_iter().filter(|a| *a != action) {
+        let taken = shortcuts
+            .current(other)
+            .and_then(|s| parse(&s).ok())
+            .zip(parsed)
+            .is_some_and(|(taken, next)| taken.id() == next.id());
+        if taken {
+            return Err(format!(
+                "'{}' is already the {} shortcut",
+                next.unwrap_or_default(),
+                other.label()
+            ));
+        }
+    }
+    let mut current = shortcuts
+        .slot(action)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some(previous) = current.as_deref().and_then(|s| parse(s).ok()) {
+        if let Err(e) = app.global_shortcut().unregister(previous) {
+            log::warn!("Failed to unregister {} shortcut: {}", action.label(), e);
+        }
+    }
+    *current = None;
+
+    if let (Some(accelerator), Some(shortcut)) = (next, parsed) {
+        app.global_shortcut()
+            .register(shortcut)
+            .map_err(|e| format!("Failed to register shortcut '{}': {}", accelerator, e))?;
+        *current = Some(accelerator.to_string());
+    }
+    Ok(())
+}
+
+/// Register the saved (or default) shortcuts at startup
+pub fn init(app: &AppHandle) {
+    let store = app
+        .store(SHORTCUT_STORE)
+        .map_err(|e| log::warn!("Failed to open {}: {}", SHORTCUT_STORE, e))
+        .ok();
+    for action in Action::ALL {
+        let saved = match store.as_ref().and_then(|s| s.get(action.store_key())) {
+            Some(serde_json::Value::String(s)) => Some(s),
+            Some(serde_json::Value::Null) => None,
+            _ => Some(action.default_accelerator().to_string()),
+        };
+        if let Err(e) = apply(app, action, saved.as_deref()) {
+            log::warn!("The {} shortcut is not available: {}", action.label(), e);
+        }
+    }
+}
+
+fn set(
+    app: &AppHandle,
+    action: Action,
+    shortcut: Option<String>,
+) -> Result<GlobalShortcutState, String> {
+    let shortcut = shortcut
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    apply(app, action, shortcut.as_deref())?;
+
+    let store = app
+        .store(SHORTCUT_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", SHORTCUT_STORE, e))?;
+    store.set(
+        action.store_key(),
+        shortcut.map_or(serde_json::Value::Null, serde_json::Value::String),
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", SHORTCUT_STORE, e))?;
+
+    Ok(state(app, action))
+}
+
+#[tauri::command]
+pub fn get_quick_capture_shortcut(app: AppHandle) -> GlobalShortcutState {
+    state(&app, Action::QuickCapture)
+}
+
+/// Change the quick capture accelerator (e.g. "Alt+Space"); `None` turns it off
+#[tauri::command]
+pub fn set_quick_capture_shortcut(
+    app: AppHandle,
+    shortcut: Option<String>,
+) -> Result<GlobalShortcutState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_quick_capture_shortcut")?;
+    Ok(set(&app, Action::QuickCapture, shortcut)?)
+}
+
+#[tauri::command]
+pub fn get_interruption_shortcut(app: AppHandle) -> GlobalShortcutState {
+    state(&app, Action::Interruption)
+}
+
+/// Change the accelerator that logs an interruption; `None` turns it off
+#[tauri::command]
+pub fn set_interruption_shortcut(
+    app: AppHandle,
+    shortcut: Option<String>,
+) -> Result<GlobalShortcutState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_interruption_shortcut")?;
+    Ok(set(&app, Action::Interruption, shortcut)?)
+}
@@ -0,0 +1,871 @@
+//! Saved filters ("smart lists") over the offline task cache.
+//!
+//! A smart list is a named filter: a small JSON tree of conditions (tag,
+//! status, priority, project, due date, title text, custom fields) joined
+//! with all/any/not, validated here and compiled to one parameterized SQL
+//! query over `task_cache.db`. `evaluate_smart_list` returns the matching tasks a page at
+//! a time (keyset on task id, so pages stay stable while tasks change), so the
+//! frontend never loads and re-filters every task. Whenever the cache changes
+//! (local edits, sync, conflict resolution), the lists are re-run and
+//! `smart_lists://changed` names the ones whose results changed, so open lists
+//! refresh only when they have to. Due-date conditions are relative to today
+//! and are evaluated at query time.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::bulk::{PRIORITIES, STATUSES};
+use crate::custom_fields::{CustomField, FieldKind};
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 200;
+/// Deepest nesting of all/any/not
+const MAX_DEPTH: usize = 8;
+/// Most conditions in one filter
+const MAX_CONDITIONS: usize = 64;
+const MAX_NAME: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum SmartFilter {
+    /// Every one of `filters`
+    All {
+        filters: Vec<SmartFilter>,
+    },
+    /// At least one of `filters`
+    Any {
+        filters: Vec<SmartFilter>,
+    },
+    Not {
+        filter: Box<SmartFilter>,
+    },
+    /// Tasks with this tag (case-insensitive)
+    Tag {
+        tag: String,
+    },
+    /// Any of these statuses
+    Status {
+        any: Vec<String>,
+    },
+    /// Any of these priorities
+    Priority {
+        any: Vec<String>,
+    },
+    /// Tasks in this project; None for tasks in no project
+    Project {
+        project_id: Option<String>,
+    },
+    /// Due before today plus `days` (negative: overdue by more than that)
+    DueBefore {
+        days: i64,
+    },
+    /// Due on or after today plus `days`
+    DueAfter {
+        days: i64,
+    },
+    NoDueDate,
+    /// Title contains this text (case-insensitive)
+    TitleContains {
+        text: String,
+    },
+    /// Custom field (`custom_fields.rs`) set to `value`
+    FieldEquals {
+        key: String,
+        value: Value,
+    },
+    /// Number or date custom field within `min..=max`
+    FieldBetween {
+        key: String,
+        min: Option<Value>,
+        max: Option<Value>,
+    },
+    /// Custom field not set
+    FieldEmpty {
+        key: String,
+    },
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartList {
+    pub id: i64,
+    pub name: String,
+    pub filter: SmartFilter,
+    pub created_at_ms: i64,
+    pub updated_at_ms: i64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartListPage {
+    pub tasks: Vec<Value>,
+    /// Pass back to get the next page; None on the last page
+    pub next_cursor: Option<String>,
+    /// Matching tasks across all pages
+    pub total: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartListsChanged {
+    pub ids: Vec<i64>,
+}
+
+/// Fingerprint of each list's results as last seen
+#[derive(Default)]
+pub struct SmartLists {
+    seen: Mutex<HashMap<i64, u64>>,
+}
+
+impl SmartFilter {
+    /// Check the tree against the user's custom fields, and normalize tags,
+    /// text and field values
+    fn validated(self, fields: &HashMap<String, CustomField>) -> Result<Self, String> {
+        let mut conditions = 0;
+        let filter = self.check(fields, 0, &mut conditions)?;
+        if conditions > MAX_CONDITIONS {
+            return Err(format!(
+                "A smart list can have at most {} conditions",
+                MAX_CONDITIONS
+            ));
+        }
+        Ok(filter)
+    }
+
+    fn check(
+        self,
+        fields: &HashMap<String, CustomField>,
+        depth: usize,
+        conditions: &mut usize,
+    ) -> Result<Self, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "Smart lists nest at most {} levels deep",
+                MAX_DEPTH
+            ));
+        }
+        *conditions += 1;
+        let children = |filters: Vec<SmartFilter>, conditions: &mut usize| {
+            if filters.is_empty() {
+                return Err("'all' and 'any' need at least one condition".to_string());
+            }
+            filters
+                .into_iter()
+                .map(|f| f.check(fields, depth + 1, conditions))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            SmartFilter::All { filters } => SmartFilter::All {
+                filters: children(filters, conditions)?,
+            },
+            SmartFilter::Any { filters } => SmartFilter::Any {
+                filters: children(filters, conditions)?,
+            },
+            SmartFilter::Not { filter } => SmartFilter::Not {
+                filter: Box::new(filter.check(fields, depth + 1, conditions)?),
+            },
+            SmartFilter::Tag { tag } => {
+                let tag = tag.trim().to_lowercase();
+                if tag.is_empty() {
+                    return Err("Tag must not be empty".to_string());
+                }
+                SmartFilter::Tag { tag }
+            }
+            SmartFilter::Status { any } => {
+                if let Some(status) = any.iter().find(|s| !STATUSES.contains(&s.as_str())) {
+                    return Err(format!("Unknown status '{}'", status));
+                }
+                SmartFilter::Status { any }
+            }
+            SmartFilter::Priority { any } => {
+                if let Some(priority) = any.iter().find(|p| !PRIORITIES.contains(&p.as_str())) {
+                    return Err(format!("Unknown priority '{}'", priority));
+                }
+                SmartFilter::Priority { any }
+            }
+            SmartFilter::Project { project_id } => SmartFilter::Project {
+                project_id: project_id.filter(|p| !p.is_empty()),
+            },
+            SmartFilter::TitleContains { text } => {
+                let text = text.trim().to_lowercase();
+                if text.is_empty() {
+                    return Err("Title text must not be empty".to_string());
+                }
+                SmartFilter::TitleContains { text }
+            }
+            SmartFilter::FieldEquals { key, value } => {
+                let value = field(fields, &key)?.normalize(&value)?;
+                if value.is_null() {
+                    return Err("Use 'field_empty' to match an unset field".to_string());
+                }
+                SmartFilter::FieldEquals { key, value }
+            }
+            SmartFilter::FieldBetween { key, min, max } => {
+                let field = field(fields, &key)?;
+                if !matches!(field.kind, FieldKind::Number | FieldKind::Date) {
+                    return Err(format!("'{}' is not a number or date field", field.name));
+                }
+                let bound = |value: Option<Value>| {
+                    value
+                        .map(|v| field.normalize(&v))
+                        .transpose()
+                        .map(|v| v.filter(|v| !v.is_null()))
+                };
+                let (min, max) = (bound(min)?, bound(max)?);
+                if min.is_none() && max.is_none() {
+                    return Err("'field_between' needs a min or a max".to_string());
+                }
+                SmartFilter::FieldBetween { key, min, max }
+            }
+            SmartFilter::FieldEmpty { key } => {
+                field(fields, &key)?;
+                SmartFilter::FieldEmpty { key }
+            }
+            filter @ (SmartFilter::DueBefore { .. }
+            | SmartFilter::DueAfter { .. }
+            | SmartFilter::NoDueDate) => filter,
+        })
+    }
+
+    /// SQL condition over `tasks.data`, pushing its parameters to `params`
+    fn to_sql(&self, params: &mut Vec<SqlValue>) -> String {
+        let mut param = |value: SqlValue| {
+            params.push(value);
+            format!("?{}", params.len())
+        };
+        let due = "julianday(date(json_extract(data, '$.due_date')))";
+        match self {
+            SmartFilter::All { filters } | SmartFilter::Any { filters } => {
+                let joiner = if matches!(self, SmartFilter::All { .. }) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                let parts: Vec<String> = filters.iter().map(|f| f.to_sql(params)).collect();
+                format!("({})", parts.join(joiner))
+            }
+            SmartFilter::Not { filter } => format!("NOT coalesce({}, 0)", filter.to_sql(params)),
+            SmartFilter::Tag { tag } => format!(
+                "EXISTS (SELECT 1 FROM json_each(data, '$.tags') WHERE lower(value) = {})",
+                param(SqlValue::Text(tag.clone()))
+            ),
+            SmartFilter::Status { any } => format!(
+                "json_extract(data, '$.status') IN (SELECT value FROM json_each({}))",
+                param(SqlValue::Text(Value::from(any.clone()).to_string()))
+            ),
+            SmartFilter::Priority { any } => format!(
+                "json_extract(data, '$.priority') IN (SELECT value FROM json_each({}))",
+                param(SqlValue::Text(Value::from(any.clone()).to_string()))
+            ),
+            SmartFilter::Project { project_id: None } => {
+                "json_extract(data, '$.project_id') IS NULL".to_string()
+            }
+            SmartFilter::Project {
+                project_id: Some(project_id),
+            } => format!(
+                "json_extract(data, '$.project_id') = {}",
+                param(SqlValue::Text(project_id.clone()))
+            ),
+            SmartFilter::DueBefore { days } => format!(
+                "{} < julianday(date('now')) + {}",
+                due,
+                param(SqlValue::Integer(*days))
+            ),
+            SmartFilter::DueAfter { days } => format!(
+                "{} >= julianday(date('now')) + {}",
+                due,
+                param(SqlValue::Integer(*days))
+            ),
+            SmartFilter::NoDueDate => "json_extract(data, '$.due_date') IS NULL".to_string(),
+            SmartFilter::TitleContains { text } => format!(
+                "instr(lower(coalesce(json_extract(data, '$.title'), '')), {}) > 0",
+                param(SqlValue::Text(text.clone()))
+            ),
+            // Keys are checked, and inlined so the field's index applies
+            SmartFilter::FieldEquals { key, value } => format!(
+                "{} = {}",
+                crate::custom_fields::value_sql(key),
+                param(sql_value(value))
+            ),
+            SmartFilter::FieldBetween { key, min, max } => {
+                let value = crate::custom_fields::value_sql(key);
+                let mut bounds = Vec::new();
+                if let Some(min) = min {
+                    bounds.push(format!("{} >= {}", value, param(sql_value(min))));
+                }
+                if let Some(max) = max {
+                    bounds.push(format!("{} <= {}", value, param(sql_value(max))));
+                }
+                format!("({})", bounds.join(" AND "))
+            }
+            SmartFilter::FieldEmpty { key } => {
+                format!("{} IS NULL", crate::custom_fields::value_sql(key))
+            }
+        }
+    }
+}
+
+fn field<'a>(
+    fields: &'a HashMap<String, CustomField>,
+    key: &str,
+) -> Result<&'a CustomField, String> {
+    fields
+        .get(key)
+        .ok_or_else(|| format!("Unknown custom field '{}'", key))
+}
+
+/// A normalized field value as an SQL parameter
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => SqlValue::Integer(n),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// The WHERE clause and parameters selecting a filter's live tasks
+fn compile(filter: &SmartFilter) -> (String, Vec<SqlValue>) {
+    let mut params = Vec::new();
+    let condition = filter.to_sql(&mut params);
+    // Bare, not coalesced, so custom field indexes apply; NULL doesn't match
+    (
+        format!(
+            "coalesce(json_extract(data, '$.is_deleted'), 0) = 0 AND {}",
+            condition
+        ),
+        params,
+    )
+}
+
+/// Task ids and rows
+type Rows = Vec<(String, String)>;
+
+/// A page of matching rows after `cursor`, the cursor of the next page and
+/// the total
+fn evaluate(
+    conn: &Connection,
+    filter: &SmartFilter,
+    cursor: Option<&str>,
+    limit: usize,
+) -> rusqlite::Result<(Rows, Option<String>, usize)> {
+    let (condition, mut params) = compile(filter);
+    let total: i64 = conn.query_row(
+        &format!("SELECT count(*) FROM tasks WHERE {}", condition),
+        params_from_iter(params.iter()),
+        |row| row.get(0),
+    )?;
+    params.push(SqlValue::Text(cursor.unwrap_or_default().to_string()));
+    let after = params.len();
+    params.push(SqlValue::Integer(limit as i64 + 1));
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, data FROM tasks WHERE {} AND id > ?{} ORDER BY id LIMIT ?{}",
+        condition,
+        after,
+        after + 1
+    ))?;
+    let rows = stmt.query_map(params_from_iter(params.iter()), |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    let mut rows = rows.collect::<rusqlite::Result<Rows>>()?;
+    let next_cursor = if rows.len() > limit {
+        rows.truncate(limit);
+        rows.last().map(|(id, _)| id.clone())
+    } else {
+        None
+    };
+    Ok((rows, next_cursor, total as usize))
+}
+
+/// Hash of everything a filter matches
+fn fingerprint(conn: &Connection, filter: &SmartFilter) -> rusqlite::Result<u64> {
+    let (condition, params) = compile(filter);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, data FROM tasks WHERE {} ORDER BY id",
+        condition
+    ))?;
+    let mut rows = stmt.query(params_from_iter(params.iter()))?;
+    let mut hasher = DefaultHasher::new();
+    while let Some(row) = rows.next()? {
+        row.get_ref(0)?
+            .as_str()
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        row.get_ref(1)?
+            .as_str()
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<(i64, String, String, i64, i64)> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn to_list(
+    (id, name, filter, created_at_ms, updated_at_ms): (i64, String, String, i64, i64),
+) -> Option<SmartList> {
+    Some(SmartList {
+        id,
+        name,
+        filter: serde_json::from_str(&filter).ok()?,
+        created_at_ms,
+        updated_at_ms,
+    })
+}
+
+fn lists(conn: &Connection) -> rusqlite::Result<Vec<SmartList>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, filter, created_at, updated_at FROM smart_lists ORDER BY name, id",
+    )?;
+    let rows = stmt.query_map([], from_row)?;
+    Ok(rows
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter_map(to_list)
+        .collect())
+}
+
+fn list(conn: &Connection, id: i64) -> rusqlite::Result<Option<SmartList>> {
+    Ok(conn
+        .query_row(
+            "SELECT id, name, filter, created_at, updated_at FROM smart_lists WHERE id = ?1",
+            params![id],
+            from_row,
+        )
+        .optional()?
+        .and_then(to_list))
+}
+
+fn valid_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Smart list name must not be empty".to_string());
+    }
+    if name.chars().count() > MAX_NAME {
+        return Err(format!(
+            "Smart list names are at most {} characters",
+            MAX_NAME
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Remember a list's current results, so only later changes are announced
+fn remember(app: &AppHandle, list: &SmartList) {
+    match crate::offline::with_db(app, |conn| fingerprint(conn, &list.filter)) {
+        Ok(print) => {
+            app.state::<SmartLists>()
+                .seen
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(list.id, print);
+        }
+        Err(e) => log::warn!("Failed to evaluate smart list {}: {}", list.id, e),
+    }
+}
+
+/// Re-run every list after the cache changed and announce those whose
+/// results differ on `smart_lists://changed`
+pub(crate) fn tasks_changed(app: &AppHandle) {
+    let prints = crate::offline::with_db(app, |conn| {
+        lists(conn)?
+            .into_iter()
+            .map(|list| Ok((list.id, fingerprint(conn, &list.filter)?)))
+            .collect::<rusqlite::Result<HashMap<_, _>>>()
+    });
+    let prints = match prints {
+        Ok(prints) => prints,
+        Err(e) => {
+            log::warn!("Failed to re-evaluate smart lists: {}", e);
+            return;
+        }
+    };
+    let state = app.state::<SmartLists>();
+    let mut changed: Vec<i64> = {
+        let mut seen = state.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = prints
+            .iter()
+            .filter(|(id, print)| seen.get(*id) != Some(*print))
+            .map(|(id, _)| *id)
+            .collect();
+        *seen = prints;
+        changed
+    };
+    if !changed.is_empty() {
+        changed.sort_unstable();
+        crate::events::publish(
+            app,
+            "smart_lists://changed",
+            &SmartListsChanged { ids: changed },
+        );
+    }
+}
+
+/// Save a new smart list
+#[tauri::command]
+pub fn create_smart_list(
+    app: AppHandle,
+    name: String,
+    filter: SmartFilter,
+) -> Result<SmartList, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "create_smart_list")?;
+    crate::read_only::ensure_writable(&app, "create_smart_list")?;
+    let name = valid_name(&name)?;
+    let fields = crate::offline::with_db(&app, |conn| crate::custom_fields::definitions(conn))?;
+    let filter = filter.validated(&fields)?;
+    let encoded = serde_json::to_string(&filter).map_err(|e| e.to_string())?;
+    let created = crate::offline::with_db(&app, |conn| {
+        let now = now_ms() as i64;
+        conn.execute(
+            "INSERT INTO smart_lists (name, filter, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![name, encoded, now],
+        )?;
+        list(conn, conn.last_insert_rowid())
+    })?
+    .ok_or_else(|| "Smart list was not saved".to_string())?;
+    remember(&app, &created);
+    Ok(created)
+}
+
+/// Rename a smart list or replace its filter
+#[tauri::command]
+pub fn update_smart_list(
+    app: AppHandle,
+    id: i64,
+    name: Option<String>,
+    filter: Option<SmartFilter>,
+) -> Result<SmartList, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "update_smart_list")?;
+    crate::read_only::ensure_writable(&app, "update_smart_list")?;
+    let name = name.as_deref().map(valid_name).transpose()?;
+    let fields = crate::offline::with_db(&app, |conn| crate::custom_fields::definitions(conn))?;
+    let filter = filter
+        .map(|f| f.validated(&fields))
+        .transpose()?
+        .map(|f| serde_json::to_string(&f))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let updated = crate::offline::with_db(&app, |conn| {
+        conn.execute(
+            "UPDATE smart_lists SET name = coalesce(?2, name), filter = coalesce(?3, filter), \
+             updated_at = ?4 WHERE id = ?1",
+            params![id, name, filter, now_ms() as i64],
+        )?;
+        list(conn, id)
+    })?
+    .ok_or_else(|| format!("Unknown smart list {}", id))?;
+    remember(&app, &updated);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn delete_smart_list(app: AppHandle, id: i64) -> Result<(), FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "delete_smart_list")?;
+    crate::read_only::ensure_writable(&app, "delete_smart_list")?;
+    let deleted = crate::offline::with_db(&app, |conn| {
+        conn.execute("DELETE FROM smart_lists WHERE id = ?1", params![id])
+    })?;
+    if deleted == 0 {
+        return Err(format!("Unknown smart list {}", id).into());
+    }
+    app.state::<SmartLists>()
+        .seen
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    Ok(())
+}
+
+/// Saved smart lists, by name
+#[tauri::command]
+pub fn get_smart_lists(app: AppHandle) -> Result<Vec<SmartList>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_smart_lists")?;
+    Ok(crate::offline::with_db(&app, |conn| lists(conn))?)
+}
+
+/// A page of the tasks a smart list matches, by id, after `cursor` (the
+/// previous page's `nextCursor`)
+#[tauri::command]
+pub fn evaluate_smart_list(
+    app: AppHandle,
+    id: i64,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<SmartListPage, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "evaluate_smart_list")?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (rows, next_cursor, total) = crate::offline::with_db(&app, |conn| {
+        let Some(list) = list(conn, id)? else {
+            return Ok(None);
+        };
+        evaluate(conn, &list.filter, cursor.as_deref(), limit).map(Some)
+    })?
+    .ok_or_else(|| format!("Unknown smart list {}", id))?;
+    Ok(SmartListPage {
+        tasks: rows
+            .iter()
+            .filter_map(|(_, data)| serde_json::from_str(data).ok())
+            .collect(),
+        next_cursor,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE tasks (id TEXT PRIMARY KEY, data TEXT NOT NULL)")
+            .unwrap();
+        let today = chrono::Utc::now().date_naive();
+        let day = |offset: i64| (today + chrono::Duration::days(offset)).to_string();
+        for task in [
+            serde_json::json!({"id": "a", "title": "Write report", "status": "planned",
+                "priority": "high", "tags": ["ClientX"], "due_date": day(-10),
+                "custom_fields": {"effort": 3, "stage": "won"}}),
+            serde_json::json!({"id": "b", "title": "Review report", "status": "done",
+                "priority": "low", "tags": ["clientx"], "due_date": day(-3), "project_id": "p1",
+                "custom_fields": {"effort": 8.5}}),
+            serde_json::json!({"id": "c", "title": "Plan sprint", "status": "in_progress",
+                "tags": [], "due_date": day(2), "project_id": "p1"}),
+            serde_json::json!({"id": "d", "title": "Old idea", "status": "backlog"}),
+            serde_json::json!({"id": "e", "title": "Gone report", "status": "planned",
+                "is_deleted": true}),
+        ] {
+            conn.execute(
+                "INSERT INTO tasks (id, data) VALUES (?1, ?2)",
+                params![task["id"].as_str().unwrap(), task.to_string()],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn fields() -> HashMap<String, CustomField> {
+        [
+            ("effort", FieldKind::Number, vec![]),
+            (
+                "stage",
+                FieldKind::Select,
+                vec!["lead".to_string(), "won".to_string()],
+            ),
+        ]
+        .into_iter()
+        .map(|(key, kind, options)| {
+            (
+                key.to_string(),
+                CustomField {
+                    key: key.to_string(),
+                    name: key.to_string(),
+                    kind,
+                    options,
+                },
+            )
+        })
+        .collect()
+    }
+
+    fn ids(conn: &Connection, filter: SmartFilter) -> Vec<String> {
+        let filter = filter.validated(&fields()).unwrap();
+        let (rows, next_cursor, total) = evaluate(conn, &filter, None, 100).unwrap();
+        assert_eq!(next_cursor, None);
+        assert_eq!(rows.len(), total);
+        rows.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn conditions_compile_to_sql() {
+        let conn = cache();
+        let tag = || SmartFilter::Tag {
+            tag: " CLIENTX ".to_string(),
+        };
+        assert_eq!(ids(&conn, tag()), ["a", "b"]);
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::All {
+                    filters: vec![
+                        tag(),
+                        SmartFilter::Not {
+                            filter: Box::new(SmartFilter::Status {
+                                any: vec!["done".to_string()]
+                            })
+                        },
+                        SmartFilter::DueBefore { days: -7 },
+                    ]
+                }
+            ),
+            ["a"]
+        );
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::Any {
+                    filters: vec![SmartFilter::NoDueDate, SmartFilter::DueAfter { days: 0 }]
+                }
+            ),
+            ["c", "d"]
+        );
+        assert_eq!(
+            ids(&conn, SmartFilter::Project { project_id: None }),
+            ["a", "d"]
+        );
+        // Tasks without a priority aren't excluded by NOT priority
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::Not {
+                    filter: Box::new(SmartFilter::Priority {
+                        any: vec!["high".to_string()]
+                    })
+                }
+            ),
+            ["b", "c", "d"]
+        );
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::TitleContains {
+                    text: "REPORT".to_string()
+                }
+            ),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn custom_fields_are_matched_by_value() {
+        let conn = cache();
+        let key = || "effort".to_string();
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::FieldBetween {
+                    key: key(),
+                    min: Some(Value::from(4)),
+                    max: None
+                }
+            ),
+            ["b"]
+        );
+        assert_eq!(
+            ids(
+                &conn,
+                SmartFilter::FieldEquals {
+                    key: "stage".to_string(),
+                    value: Value::from("won")
+                }
+            ),
+            ["a"]
+        );
+        assert_eq!(
+            ids(&conn, SmartFilter::FieldEmpty { key: key() }),
+            ["c", "d"]
+        );
+        for invalid in [
+            SmartFilter::FieldEquals {
+                key: "stage".to_string(),
+                value: Value::from("lost"),
+            },
+            SmartFilter::FieldBetween {
+                key: "stage".to_string(),
+                min: Some(Value::from("lead")),
+                max: None,
+            },
+            SmartFilter::FieldEmpty {
+                key: "owner".to_string(),
+            },
+        ] {
+            assert!(invalid.validated(&fields()).is_err());
+        }
+    }
+
+    #[test]
+    fn filters_are_validated() {
+        assert!(SmartFilter::Status {
+            any: vec!["later".to_string()]
+        }
+        .validated(&fields())
+        .is_err());
+        assert!(SmartFilter::Any { filters: vec![] }
+            .validated(&fields())
+            .is_err());
+        let mut deep = SmartFilter::NoDueDate;
+        for _ in 0..=MAX_DEPTH {
+            deep = SmartFilter::Not {
+                filter: Box::new(deep),
+            };
+        }
+        assert!(deep.validated(&fields()).is_err());
+        let parsed: SmartFilter = serde_json::from_str(
+            r#"{"op": "all", "filters": [{"op": "project", "projectId": "p1"}, {"op": "no_due_date"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed,
+            SmartFilter::All {
+                filters: vec![
+                    SmartFilter::Project {
+                        project_id: Some("p1".to_string())
+                    },
+                    SmartFilter::NoDueDate
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn pages_follow_the_cursor_and_fingerprints_track_changes() {
+        let conn = cache();
+        let filter = SmartFilter::Status {
+            any: STATUSES.iter().map(|s| s.to_string()).collect(),
+        };
+        let (first, cursor, total) = evaluate(&conn, &filter, None, 2).unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(first.len(), 2);
+        assert_eq!(cursor.as_deref(), Some("b"));
+        let (rest, cursor, _) = evaluate(&conn, &filter, cursor.as_deref(), 10).unwrap();
+        assert_eq!(cursor, None);
+        let rest: Vec<&str> = rest.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(rest, ["c", "d"]);
+
+        let tag = SmartFilter::Tag {
+            tag: "clientx".to_string(),
+        };
+        let before = fingerprint(&conn, &tag).unwrap();
+        conn.execute(
+            "UPDATE tasks SET data = json_set(data, '$.title', 'Renamed') WHERE id = 'c'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(fingerprint(&conn, &tag).unwrap(), before);
+        conn.execute(
+            "UPDATE tasks SET data = json_set(data, '$.title', 'Renamed') WHERE id = 'a'",
+            [],
+        )
+        .unwrap();
+        assert_ne!(fingerprint(&conn, &tag).unwrap(), before);
+    }
+}
@@ -0,0 +1,272 @@
+//! Webview reload detection and state resynchronization.
+//!
+//! The backend remembers the last known service statuses. Whenever a webview
+//! finishes loading, the reload is logged and a full snapshot is published to
+//! that webview as `app://state-snapshot` (through the event bus, so it has a
+//! `seq` and can be replayed), so a refreshed UI starts from real state
+//! instead of "unknown". While the app is locked the snapshot holds only the
+//! lock state.
+//!
+//! The focus session, the running task timer and the sync status are kept
+//! current from the event bus (`start`), so the page-load hook, which runs on
+//! the main thread, never reads the databases.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, Url, Webview};
+use tokio::sync::broadcast;
+
+use crate::events::BusEvent;
+use crate::focus::FocusSessionState;
+use crate::offline::SyncStatus;
+use crate::status::{MemoryUsage, ServiceStatus};
+use crate::time_tracking::TimeEntry;
+
+#[derive(Default)]
+struct SnapshotState {
+    docker_status: Option<ServiceStatus>,
+    supabase_status: Option<ServiceStatus>,
+    focus: Option<FocusSessionState>,
+    timer: Option<TimeEntry>,
+    sync_status: Option<SyncStatus>,
+    /// Finished page loads per webview label
+    loads: HashMap<String, u32>,
+}
+
+#[derive(Default)]
+pub struct StateSnapshot {
+    state: Mutex<SnapshotState>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotPayload {
+    pub cause: String,
+    pub load_count: u32,
+    pub locked: bool,
+    /// Left out while the app is locked
+    #[serde(flatten)]
+    pub state: Option<UnlockedSnapshot>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockedSnapshot {
+    pub docker_status: Option<ServiceStatus>,
+    pub supabase_status: Option<ServiceStatus>,
+    pub focus: Option<FocusSessionState>,
+    /// The running task timer
+    pub timer: Option<TimeEntry>,
+    pub sync_status: Option<SyncStatus>,
+    pub read_only: bool,
+    pub privacy_mode: bool,
+    pub memory: MemoryUsage,
+}
+
+impl SnapshotState {
+    /// Count a finished load of `label` and build what it gets; `unlocked`
+    /// gives the rest of the app state, None while the app is locked
+    fn on_load(
+        &mut self,
+        label: &str,
+        unlocked: Option<(bool, bool, MemoryUsage)>,
+    ) -> SnapshotPayload {
+        let count = self.loads.entry(label.to_string()).or_insert(0);
+        *count += 1;
+        let load_count = *count;
+
+        SnapshotPayload {
+            cause: if load_count == 1 { "initial" } else { "reload" }.to_string(),
+            load_count,
+            locked: unlocked.is_none(),
+            state: unlocked.map(|(read_only, privacy_mode, memory)| UnlockedSnapshot {
+                docker_status: self.docker_status.clone(),
+                supabase_status: self.supabase_status.clone(),
+                focus: self.focus.clone(),
+                timer: self.timer.clone(),
+                sync_status: self.sync_status.clone(),
+                read_only,
+                privacy_mode,
+                memory,
+            }),
+        }
+    }
+}
+
+impl StateSnapshot {
+    pub fn set_docker_status(&self, status: ServiceStatus) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.docker_status = Some(status);
+    }
+
+    pub fn set_supabase_status(&self, status: ServiceStatus) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.supabase_status = Some(status);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SnapshotState)) {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+fn refresh_sync_status(app: &AppHandle) {
+    match crate::offline::status(app) {
+        Ok(status) => app
+            .state::<StateSnapshot>()
+            .update(|state| state.sync_status = Some(status)),
+        Err(e) => log::debug!("Sync status unavailable for the snapshot: {}", e),
+    }
+}
+
+/// Read the focus session, timer and sync status afresh
+fn reload(app: &AppHandle) {
+    let focus = app.state::<crate::focus::FocusEngine>().snapshot();
+    let timer = crate::time_tracking::active_timer(app);
+    app.state::<StateSnapshot>().update(|state| {
+        state.focus = Some(focus);
+        match timer {
+            Ok(timer) => state.timer = timer,
+            Err(e) => log::debug!("Timer unavailable for the snapshot: {}", e),
+        }
+    });
+    refresh_sync_status(app);
+}
+
+/// Take in a bus event that changes the focus session, timer or sync status
+fn apply(app: &AppHandle, event: &BusEvent) {
+    let snapshot = app.state::<StateSnapshot>();
+    match event.topic.as_str() {
+        "focus://tick" | "focus://phase" => {
+            let state = match event.topic.as_str() {
+                "focus://phase" => event.payload.get("next").cloned(),
+                _ => Some(event.payload.clone()),
+            };
+            if let Some(focus) = state.and_then(|state| serde_json::from_value(state).ok()) {
+                snapshot.update(|state| state.focus = Some(focus));
+            }
+        }
+        "timer://started" => {
+            if let Ok(entry) = serde_json::from_value(event.payload.clone()) {
+                snapshot.update(|state| state.timer = Some(entry));
+            }
+        }
+        "timer://stopped" => snapshot.update(|state| state.timer = None),
+        topic if topic.starts_with("sync://") || topic == "tasks://changed" => {
+            refresh_sync_status(app)
+        }
+        _ => {}
+    }
+}
+
+/// Keep the snapshot's focus session, timer and sync status current (a
+/// startup graph node)
+pub fn start(app: &AppHandle) -> JoinHandle<()> {
+    let app = app.clone();
+    // Listening before reading, so no change falls in between
+    let mut events = app.state::<crate::events::EventBus>().listen();
+    tauri::async_runtime::spawn(async move {
+        reload(&app);
+        loop {
+            match events.recv().await {
+                Ok(event) => apply(&app, &event),
+                // Missed changes; read everything again
+                Err(broadcast::error::RecvError::Lagged(_)) => reload(&app),
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    })
+}
+
+/// Called from the builder's page-load hook once a page has finished loading
+pub fn on_page_loaded(webview: &Webview, url: &Url) {
+    let label = webview.label().to_string();
+    let app = webview.app_handle();
+    let unlocked = (!app.state::<crate::app_lock::AppLock>().is_locked()).then(|| {
+        (
+            app.state::<crate::read_only::ReadOnlyMode>().is_enabled(),
+            app.state::<crate::privacy::PrivacyMode>().is_enabled(),
+            // Sampling takes a moment; not while holding the lock
+            crate::get_memory_usage(),
+        )
+    });
+
+    let payload = app
+        .state::<StateSnapshot>()
+        .state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .on_load(&label, unlocked);
+
+    if payload.load_count > 1 {
+        log::warn!(
+            "Webview '{}' reloaded (load #{}) at {}",
+            label,
+            payload.load_count,
+            url.as_str()
+        );
+    } else {
+        log::info!("Webview '{}' loaded {}", label, url.as_str());
+    }
+
+    crate::events::publish_to(app, &[label], "app://state-snapshot", &payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> MemoryUsage {
+        MemoryUsage {
+            pid: 1,
+            rss_bytes: 0,
+            virtual_bytes: 0,
+            cpu_percent: 0.0,
+            open_fds: None,
+            platform: "linux".to_string(),
+        }
+    }
+
+    #[test]
+    fn counts_loads_per_webview() {
+        let mut state = SnapshotState::default();
+        let causes: Vec<_> = ["main", "main", "settings", "main"]
+            .into_iter()
+            .map(|label| {
+                let payload = state.on_load(label, Some((false, false, memory())));
+                (payload.cause, payload.load_count)
+            })
+            .collect();
+        assert_eq!(
+            causes,
+            [
+                ("initial".to_string(), 1),
+                ("reload".to_string(), 2),
+                ("initial".to_string(), 1),
+                ("reload".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_only_the_lock_state_while_locked() {
+        let mut state = SnapshotState {
+            docker_status: Some(ServiceStatus::running()),
+            ..Default::default()
+        };
+        let payload = serde_json::to_value(state.on_load("main", None)).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({ "cause": "initial", "loadCount": 1, "locked": true })
+        );
+
+        let payload =
+            serde_json::to_value(state.on_load("main", Some((true, false, memory())))).unwrap();
+        assert_eq!(payload["locked"], false);
+        assert_eq!(payload["readOnly"], true);
+        assert_eq!(payload["dockerStatus"]["state"], "running");
+        assert_eq!(payload["timer"], serde_json::Value::Null);
+    }
+}
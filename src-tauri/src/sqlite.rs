@@ -0,0 +1,138 @@
+//! Embedded SQLite databases in the app data directory.
+//!
+//! Used for data that has to work without the Supabase stack (time entries,
+//! the offline task cache). Each database is opened on first use, creating
+//! its schema, and serialized behind a mutex; closures run synchronously and
+//! must not be held across an await. With encryption at rest on
+//! (`encryption.rs`) databases are SQLCipher-keyed; a file still in the other
+//! form is converted when it is opened.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{Connection, ErrorCode};
+use tauri::{AppHandle, Manager};
+
+pub struct LocalDb {
+    file: &'static str,
+    /// Idempotent DDL run on open
+    schema: &'static str,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl LocalDb {
+    pub const fn new(file: &'static str, schema: &'static str) -> Self {
+        LocalDb {
+            file,
+            schema,
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn open(&self, app: &AppHandle) -> Result<Connection, String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("No app data directory: {}", e))?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(self.file);
+        let key = crate::encryption::sqlite_key();
+        let conn = match connect(&path, key.as_deref()) {
+            Ok(conn) => conn,
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => {
+                // Written before encryption was switched on or off
+                let previous = match key {
+                    Some(_) => None,
+                    None => crate::encryption::retired_sqlite_key(),
+                };
+                if key.is_none() && previous.is_none() {
+                    return Err(format!("Failed to open {}: {}", path.display(), e));
+                }
+                convert(&path, previous.as_deref(), key.as_deref())?;
+                connect(&path, key.as_deref())
+                    .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+            }
+            Err(e) => return Err(format!("Failed to open {}: {}", path.display(), e)),
+        };
+        conn.execute_batch(self.schema)
+            .map_err(|e| format!("Failed to create schema of {}: {}", self.file, e))?;
+        Ok(conn)
+    }
+
+    /// Close the connection and open the file again, converting it to the
+    /// current encryption state
+    pub fn reopen(&self, app: &AppHandle) -> Result<(), String> {
+        let mut slot = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        slot.take();
+        *slot = Some(self.open(app)?);
+        Ok(())
+    }
+
+    /// Run `f` against the database, opening it on first use
+    pub fn with<T>(
+        &self,
+        app: &AppHandle,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, String> {
+        let mut slot = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut conn = match slot.take() {
+            Some(conn) => conn,
+            None => self.open(app)?,
+        };
+        let result = f(&mut conn).map_err(|e| format!("{} error: {}", self.file, e));
+        *slot = Some(conn);
+        result
+    }
+}
+
+/// Open `path` with an optional SQLCipher key (hex) and check that it reads
+fn connect(path: &Path, key: Option<&str>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", format!("x'{}'", key))?;
+    }
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+    Ok(conn)
+}
+
+fn sql_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Rewrite the database at `path` from one key (None: plaintext) to another
+fn convert(path: &Path, from: Option<&str>, to: Option<&str>) -> Result<(), String> {
+    let failed = |e: rusqlite::Error| format!("Failed to convert {}: {}", path.display(), e);
+    let target = path.with_extension("db.converting");
+    let _ = std::fs::remove_file(&target);
+
+    let conn = connect(path, from).map_err(failed)?;
+    let key = to.map(|key| format!("x'{}'", key)).unwrap_or_default();
+    conn.execute(
+        &format!(
+            "ATTACH DATABASE {} AS converted KEY {}",
+            sql_string(&target.to_string_lossy()),
+            sql_string(&key)
+        ),
+        [],
+    )
+    .map_err(failed)?;
+    conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()))
+        .map_err(failed)?;
+    conn.execute("DETACH DATABASE converted", [])
+        .map_err(failed)?;
+    drop(conn);
+
+    std::fs::rename(&target, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    log::info!(
+        "{} {}",
+        if to.is_some() {
+            "Encrypted"
+        } else {
+            "Decrypted"
+        },
+        path.display()
+    );
+    Ok(())
+}
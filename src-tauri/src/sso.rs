@@ -0,0 +1,673 @@
+//! OIDC single sign-on for remote mode (Okta, Azure AD, ...).
+//!
+//! `sso_login` runs the authorization code flow with PKCE: the system browser
+//! opens the identity provider, a localhost listener from the oauth plugin
+//! (or, with `redirect: "deep_link"`, the `flowstate://auth/callback` link
+//! routed by `launch.rs`) captures the redirect, and the code is exchanged
+//! here. The refresh token
+//! lives in the OS keychain (access and ID tokens are short-lived and only kept
+//! in memory); a background task refreshes before expiry, also after a restart.
+//! Session changes are published as `auth://sso-session`, and the frontend
+//! hands the ID token to Supabase via `get_sso_tokens`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+use tauri_plugin_shell::ShellExt;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::parsers;
+
+pub(crate) const SSO_STORE: &str = "sso.json";
+const SSO_KEY: &str = "provider";
+const KEYRING_SERVICE: &str = "flowstate-sso";
+const REDIRECT_PORTS: [u16; 3] = [24895, 24896, 24897];
+const DEEP_LINK_REDIRECT: &str = "flowstate://auth/callback";
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+/// Refresh this long before the access token expires
+const REFRESH_MARGIN_MS: u64 = 60 * 1000;
+/// Used when the token response has no expires_in
+const DEFAULT_EXPIRY_MS: u64 = 60 * 60 * 1000;
+/// Wait before retrying a refresh that failed on a transport error
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+const CALLBACK_PAGE: &str =
+    "<html><body style=\"font-family:sans-serif;text-align:center;padding-top:20vh\">\
+    <h2>Signed in</h2><p>You can close this tab and return to FlowState.</p></body></html>";
+
+/// Identity provider the user signed in with (not secret, kept in sso.json)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Provider {
+    issuer: String,
+    client_id: String,
+    token_endpoint: String,
+}
+
+struct Session {
+    provider: Provider,
+    access_token: String,
+    id_token: Option<String>,
+    expires_at_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoSession {
+    pub signed_in: bool,
+    pub issuer: Option<String>,
+    pub expires_at_ms: Option<u64>,
+    /// Why the session ended (refresh rejected, ...)
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoTokens {
+    pub access_token: String,
+    pub id_token: Option<String>,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Default)]
+pub struct SsoState {
+    session: Mutex<Option<Session>>,
+    refresher: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// Sign-in waiting for a `flowstate://auth/callback` redirect, by its state
+    pending_redirect: Mutex<Option<(String, tokio::sync::oneshot::Sender<String>)>>,
+}
+
+/// Where the identity provider sends the browser back to
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsoRedirect {
+    /// A listener on 127.0.0.1
+    #[default]
+    Localhost,
+    /// The `flowstate://auth/callback` link
+    DeepLink,
+}
+
+/// Hand a `flowstate://auth/callback` URL to a waiting `sso_login`; false if
+/// none is waiting
+pub fn complete_redirect(app: &AppHandle, url: &str) -> bool {
+    let pending = app
+        .state::<SsoState>()
+        .pending_redirect
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    match pending {
+        Some((_, tx)) => tx.send(url.to_string()).is_ok(),
+        None => false,
+    }
+}
+
+/// Stop waiting for the redirect of the sign-in with state `csrf`
+fn stop_listening(app: &AppHandle, port: Option<u16>, csrf: &str) {
+    if let Some(port) = port {
+        let _ = tauri_plugin_oauth::cancel(port);
+    }
+    let state = app.state::<SsoState>();
+    let mut pending = state
+        .pending_redirect
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if pending.as_ref().is_some_and(|(state, _)| state == csrf) {
+        *pending = None;
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// The IdP's sign-in page for this attempt: `csrf` comes back as the
+/// redirect's state, and the challenge ties the code to `verifier`
+fn authorization_url(
+    endpoint: &str,
+    client_id: &str,
+    redirect_uri: &str,
+    scope: &str,
+    csrf: &str,
+    verifier: &str,
+) -> Result<reqwest::Url, String> {
+    reqwest::Url::parse_with_params(
+        endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("scope", scope),
+            ("state", csrf),
+            ("code_challenge", pkce_challenge(verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .map_err(|e| format!("Invalid authorization endpoint: {}", e))
+}
+
+/// The authorization code in the redirect the IdP sent back, if the redirect
+/// carries this sign-in's state
+fn authorization_code(callback: &str, csrf: &str) -> Result<String, String> {
+    let callback = reqwest::Url::parse(callback).map_err(|e| format!("Invalid redirect: {}", e))?;
+    let param = |name: &str| {
+        callback
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    if let Some(error) = param("error") {
+        return Err(format!(
+            "Sign-in failed: {}",
+            param("error_description").unwrap_or(error)
+        ));
+    }
+    if param("state").as_deref() != Some(csrf) {
+        return Err("Sign-in failed: state mismatch".to_string());
+    }
+    param("code").ok_or_else(|| "Sign-in failed: no authorization code".to_string())
+}
+
+/// When tokens issued at `now_ms` with the given `expires_in` (seconds) run out
+fn expires_at(now_ms: u64, expires_in: Option<u64>) -> u64 {
+    now_ms.saturating_add(
+        expires_in
+            .map(|s| s.saturating_mul(1000))
+            .unwrap_or(DEFAULT_EXPIRY_MS),
+    )
+}
+
+/// How long to wait before refreshing tokens that expire at `expires_at_ms`
+fn refresh_delay(expires_at_ms: u64, now_ms: u64) -> Duration {
+    Duration::from_millis(expires_at_ms.saturating_sub(now_ms.saturating_add(REFRESH_MARGIN_MS)))
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn keyring_entry(issuer: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, issuer).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+async fn discover(issuer: &str) -> Result<parsers::OidcDiscovery, String> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let response = client()?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "OIDC discovery failed: HTTP {}",
+            response.status().as_u16()
+        ));
+    }
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    parsers::parse_oidc_discovery(&body)
+        .map_err(|e| format!("Invalid OIDC discovery document: {}", e))
+}
+
+enum TokenError {
+    /// Network or keychain trouble; a refresh can be retried later
+    Transport(String),
+    /// The IdP rejected the grant; the session is over
+    Rejected(String),
+}
+
+impl From<TokenError> for String {
+    fn from(e: TokenError) -> String {
+        match e {
+            TokenError::Transport(e) | TokenError::Rejected(e) => e,
+        }
+    }
+}
+
+async fn token_request(
+    endpoint: &str,
+    form: &[(&str, &str)],
+) -> Result<parsers::OidcTokenResponse, TokenError> {
+    let response = client()
+        .map_err(TokenError::Transport)?
+        .post(endpoint)
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| TokenError::Transport(format!("Token request failed: {}", e)))?;
+    let status = response.status();
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| TokenError::Transport(e.to_string()))?;
+    if !status.is_success() {
+        let detail = String::from_utf8_lossy(&body[..body.len().min(512)]).to_string();
+        return Err(TokenError::Rejected(format!(
+            "Token endpoint returned HTTP {}: {}",
+            status.as_u16(),
+            detail
+        )));
+    }
+    parsers::parse_oidc_token_response(&body)
+        .map_err(|e| TokenError::Rejected(format!("Invalid token response: {}", e)))
+}
+
+fn session_info(state: &SsoState) -> SsoSession {
+    let session = state.session.lock().unwrap_or_else(|e| e.into_inner());
+    SsoSession {
+        signed_in: session.is_some(),
+        issuer: session.as_ref().map(|s| s.provider.issuer.clone()),
+        expires_at_ms: session.as_ref().map(|s| s.expires_at_ms),
+        error: None,
+    }
+}
+
+/// Keep the new tokens (rotating the stored refresh token if the IdP sent one)
+fn store_tokens(
+    app: &AppHandle,
+    provider: Provider,
+    tokens: parsers::OidcTokenResponse,
+) -> Result<(), String> {
+    if let Some(refresh) = &tokens.refresh_token {
+        keyring_entry(&provider.issuer)?
+            .set_password(refresh)
+            .map_err(|e| format!("Failed to store refresh token in keychain: {}", e))?;
+    }
+
+    let expires_at_ms = expires_at(crate::events::now_ms(), tokens.expires_in);
+    let state = app.state::<SsoState>();
+    *state.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(Session {
+        provider,
+        access_token: tokens.access_token,
+        id_token: tokens.id_token,
+        expires_at_ms,
+    });
+    crate::events::publish(app, "auth://sso-session", &session_info(&state));
+    Ok(())
+}
+
+fn end_session(app: &AppHandle, error: Option<String>) {
+    let state = app.state::<SsoState>();
+    let provider = state
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .map(|s| s.provider)
+        .or_else(|| load_provider(app));
+
+    if let Some(provider) = provider {
+        if let Ok(entry) = keyring_entry(&provider.issuer) {
+            let _ = entry.delete_credential();
+        }
+    }
+    if let Ok(store) = app.store(SSO_STORE) {
+        store.delete(SSO_KEY);
+        let _ = store.save();
+    }
+
+    crate::events::publish(
+        app,
+        "auth://sso-session",
+        &SsoSession {
+            signed_in: false,
+            issuer: None,
+            expires_at_ms: None,
+            error,
+        },
+    );
+}
+
+fn load_provider(app: &AppHandle) -> Option<Provider> {
+    let value = app.store(SSO_STORE).ok()?.get(SSO_KEY)?;
+    serde_json::from_value(value).ok()
+}
+
+async fn refresh(app: &AppHandle, provider: &Provider) -> Result<(), TokenError> {
+    let refresh_token = keyring_entry(&provider.issuer)
+        .map_err(TokenError::Transport)?
+        .get_password()
+        .map_err(|e| TokenError::Rejected(format!("No refresh token in keychain: {}", e)))?;
+
+    let tokens = token_request(
+        &provider.token_endpoint,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+            ("client_id", &provider.client_id),
+        ],
+    )
+    .await?;
+    store_tokens(app, provider.clone(), tokens).map_err(TokenError::Transport)
+}
+
+/// (Re)start the background task that refreshes tokens before they expire
+fn start_refresher(app: &AppHandle, provider: Provider) {
+    let state = app.state::<SsoState>();
+    let mut refresher = state.refresher.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(task) = refresher.take() {
+        task.abort();
+    }
+
+    let app = app.clone();
+    *refresher = Some(tauri::async_runtime::spawn(async move {
+        loop {
+            let expires_at_ms = app
+                .state::<SsoState>()
+                .session
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_ref()
+                .map(|s| s.expires_at_ms)
+                .unwrap_or(0);
+            tokio::time::sleep(refresh_delay(expires_at_ms, crate::events::now_ms())).await;
+
+            match refresh(&app, &provider).await {
+                Ok(()) => log::info!("Refreshed SSO tokens for {}", provider.issuer),
+                Err(TokenError::Transport(e)) => {
+                    log::warn!("SSO token refresh failed, retrying: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(TokenError::Rejected(e)) => {
+                    log::error!("SSO session ended: {}", e);
+                    end_session(&app, Some(e));
+                    return;
+                }
+            }
+        }
+    }));
+}
+
+/// Resume a previous SSO session (refreshing right away) if one was saved
+pub fn init(app: &AppHandle) {
+    if let Some(provider) = load_provider(app) {
+        log::info!("Resuming SSO session for {}", provider.issuer);
+        start_refresher(app, provider);
+    }
+}
+
+/// Sign in through the organization's OIDC provider in the system browser
+#[tauri::command]
+pub async fn sso_login(
+    app: AppHandle,
+    issuer: String,
+    client_id: String,
+    scopes: Option<Vec<String>>,
+    redirect: Option<SsoRedirect>,
+) -> Result<SsoSession, FlowStateError> {
+    crate::trace::scope("sso_login", async move {
+        crate::read_only::ensure_writable(&app, "sso_login")?;
+        let issuer = issuer.trim_end_matches('/').to_string();
+        let discovery = discover(&issuer).await?;
+
+        let verifier = random_token();
+        let csrf = random_token();
+        let (tx, callback_rx) = tokio::sync::oneshot::channel();
+        let (redirect_uri, port) = match redirect.unwrap_or_default() {
+            SsoRedirect::Localhost => {
+                // The listener calls back once per request; only the first redirect counts
+                let mut tx = Some(tx);
+                let port = tauri_plugin_oauth::start_with_config(
+                    tauri_plugin_oauth::OauthConfig {
+                        ports: Some(REDIRECT_PORTS.to_vec()),
+                        response: Some(CALLBACK_PAGE.into()),
+                    },
+                    move |url| {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(url);
+                        }
+                    },
+                )
+                .map_err(|e| format!("Failed to start redirect listener: {}", e))?;
+                (format!("http://127.0.0.1:{}", port), Some(port))
+            }
+            SsoRedirect::DeepLink => {
+                // Replaces (and so cancels) an earlier sign-in still waiting
+                *app.state::<SsoState>()
+                    .pending_redirect
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner()) = Some((csrf.clone(), tx));
+                (DEEP_LINK_REDIRECT.to_string(), None)
+            }
+        };
+
+        let scope = scopes
+            .map(|s| s.join(" "))
+            .unwrap_or_else(|| "openid profile email offline_access".to_string());
+        let auth_url = authorization_url(
+            &discovery.authorization_endpoint,
+            &client_id,
+            &redirect_uri,
+            &scope,
+            &csrf,
+            &verifier,
+        )?;
+
+        // The frontend opens URLs through the same (deprecated) shell API
+        #[allow(deprecated)]
+        let opened = app.shell().open(auth_url.as_str(), None);
+        if let Err(e) = opened {
+            stop_listening(&app, port, &csrf);
+            return Err(format!("Failed to open browser: {}", e).into());
+        }
+
+        let callback = tokio::time::timeout(LOGIN_TIMEOUT, callback_rx).await;
+        stop_listening(&app, port, &csrf);
+        let callback = callback
+            .map_err(|_| "Sign-in timed out".to_string())?
+            .map_err(|_| "Redirect listener closed".to_string())?;
+
+        let code = authorization_code(&callback, &csrf)?;
+
+        let tokens = token_request(
+            &discovery.token_endpoint,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", &redirect_uri),
+                ("client_id", &client_id),
+                ("code_verifier", &verifier),
+            ],
+        )
+        .await
+        .map_err(String::from)?;
+        if tokens.refresh_token.is_none() {
+            log::warn!(
+                "{} issued no refresh token; the session ends when the access token expires",
+                issuer
+            );
+        }
+
+        let provider = Provider {
+            issuer: issuer.clone(),
+            client_id,
+            token_endpoint: discovery.token_endpoint,
+        };
+        let store = app
+            .store(SSO_STORE)
+            .map_err(|e| format!("Failed to open {}: {}", SSO_STORE, e))?;
+        store.set(
+            SSO_KEY,
+            serde_json::to_value(&provider).map_err(|e| e.to_string())?,
+        );
+        store
+            .save()
+            .map_err(|e| format!("Failed to save {}: {}", SSO_STORE, e))?;
+
+        store_tokens(&app, provider.clone(), tokens)?;
+        start_refresher(&app, provider);
+        log::info!("Signed in via SSO ({})", issuer);
+
+        Ok(session_info(&app.state::<SsoState>()))
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn get_sso_session(state: tauri::State<'_, SsoState>) -> SsoSession {
+    session_info(&state)
+}
+
+/// Current access/ID tokens (for Supabase `signInWithIdToken`), None when signed out
+#[tauri::command]
+pub fn get_sso_tokens(app: AppHandle) -> Result<Option<SsoTokens>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_sso_tokens")?;
+    let state = app.state::<SsoState>();
+    let session = state.session.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(session.as_ref().map(|s| SsoTokens {
+        access_token: s.access_token.clone(),
+        id_token: s.id_token.clone(),
+        expires_at_ms: s.expires_at_ms,
+    }))
+}
+
+/// Sign out and forget the stored refresh token
+#[tauri::command]
+pub fn sso_logout(app: AppHandle) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "sso_logout")?;
+    if let Some(task) = app
+        .state::<SsoState>()
+        .refresher
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+    {
+        task.abort();
+    }
+    end_session(&app, None);
+    log::info!("Signed out of SSO");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn pkce_challenge_is_the_s256_of_the_verifier() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        let verifier = random_token();
+        // 32 random bytes, within the 43-128 characters PKCE allows
+        assert_eq!(verifier.len(), 43);
+        assert_ne!(verifier, random_token());
+    }
+
+    #[test]
+    fn authorization_url_carries_state_and_challenge() {
+        let url = authorization_url(
+            "https://login.example.com/oauth2/v1/authorize?prompt=login",
+            "flowstate",
+            "http://127.0.0.1:24895",
+            "openid email",
+            "csrf-123",
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+        )
+        .unwrap();
+        let params: BTreeMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["prompt"], "login");
+        assert_eq!(params["state"], "csrf-123");
+        assert_eq!(params["redirect_uri"], "http://127.0.0.1:24895");
+        assert_eq!(params["scope"], "openid email");
+        assert_eq!(
+            params["code_challenge"],
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(!url.as_str().contains("dBjftJeZ"), "verifier leaked");
+    }
+
+    #[test]
+    fn takes_the_code_from_a_redirect_with_our_state() {
+        assert_eq!(
+            authorization_code(
+                "http://127.0.0.1:24895/?code=abc%2F1&state=csrf-123",
+                "csrf-123"
+            ),
+            Ok("abc/1".to_string())
+        );
+        assert_eq!(
+            authorization_code(
+                "flowstate://auth/callback?state=csrf-123&code=abc",
+                "csrf-123"
+            ),
+            Ok("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_redirects_that_are_not_ours() {
+        let cases = [
+            (
+                "http://127.0.0.1:24895/?code=abc&state=other",
+                "Sign-in failed: state mismatch",
+            ),
+            (
+                "http://127.0.0.1:24895/?code=abc",
+                "Sign-in failed: state mismatch",
+            ),
+            (
+                "http://127.0.0.1:24895/?state=csrf-123",
+                "Sign-in failed: no authorization code",
+            ),
+            (
+                "http://127.0.0.1:24895/?error=access_denied&error_description=User+cancelled&state=csrf-123",
+                "Sign-in failed: User cancelled",
+            ),
+            (
+                "http://127.0.0.1:24895/?error=access_denied",
+                "Sign-in failed: access_denied",
+            ),
+        ];
+        for (callback, error) in cases {
+            assert_eq!(
+                authorization_code(callback, "csrf-123"),
+                Err(error.to_string()),
+                "{}",
+                callback
+            );
+        }
+        assert!(authorization_code("/?code=abc&state=csrf-123", "csrf-123")
+            .unwrap_err()
+            .starts_with("Invalid redirect"));
+    }
+
+    #[test]
+    fn refreshes_a_minute_before_expiry() {
+        let now = 1_000_000;
+        assert_eq!(expires_at(now, Some(3600)), now + 3_600_000);
+        assert_eq!(expires_at(now, None), now + DEFAULT_EXPIRY_MS);
+        assert_eq!(expires_at(now, Some(u64::MAX)), u64::MAX);
+
+        let expires = expires_at(now, Some(3600));
+        assert_eq!(refresh_delay(expires, now), Duration::from_secs(59 * 60));
+        // Within the margin, or already expired (e.g. after a restart): now
+        assert_eq!(refresh_delay(expires, expires - 30_000), Duration::ZERO);
+        assert_eq!(refresh_delay(expires, expires + 1), Duration::ZERO);
+        assert_eq!(refresh_delay(0, now), Duration::ZERO);
+    }
+}
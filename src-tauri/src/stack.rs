@@ -0,0 +1,355 @@
+//! One-shot startup of the local stack.
+//!
+//! `ensure_stack_ready` runs the sequence the startup screen used to chain
+//! itself (check Docker, start it, wait for the daemon, start Supabase, verify
+//! the schema) and publishes each step as `stack://step`. Steps that have
+//! nothing to do are reported as skipped; the run stops at the first failure,
+//! and the returned report names the step that failed. In read-only mode
+//! pending migrations are left alone: schema verification is skipped and
+//! the stack still counts as ready.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+use crate::status::ServiceStatus;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StackStep {
+    Detecting,
+    StartingDocker,
+    WaitingDocker,
+    StartingSupabase,
+    VerifyingSchema,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Running,
+    Succeeded,
+    Skipped,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackStepEvent {
+    pub step: StackStep,
+    pub status: StepStatus,
+    /// Command result ("already_running", "migrations_complete") or the error
+    pub detail: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackReport {
+    pub ready: bool,
+    pub failed_step: Option<StackStep>,
+    pub error: Option<FlowStateError>,
+    pub docker_version: Option<String>,
+    pub steps: Vec<StackStepEvent>,
+    pub duration_ms: u64,
+}
+
+struct Run<P> {
+    /// Where each step event goes (`stack://step`)
+    publish: P,
+    started: Instant,
+    steps: Vec<StackStepEvent>,
+}
+
+impl<P: Fn(&StackStepEvent)> Run<P> {
+    fn report(&mut self, step: StackStep, status: StepStatus, detail: Option<String>) {
+        let event = StackStepEvent {
+            step,
+            status,
+            detail,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+        };
+        (self.publish)(&event);
+        self.steps.push(event);
+    }
+
+    /// Run one step, reporting its start and outcome (`describe` fills the
+    /// detail); a step refused by read-only mode is reported as skipped
+    async fn step<T, F>(
+        &mut self,
+        step: StackStep,
+        fut: F,
+        describe: fn(&T) -> Option<String>,
+    ) -> Result<T, FlowStateError>
+    where
+        F: Future<Output = Result<T, FlowStateError>>,
+    {
+        self.report(step, StepStatus::Running, None);
+        let result = fut.await;
+        match &result {
+            Ok(value) => self.report(step, StepStatus::Succeeded, describe(value)),
+            Err(FlowStateError::ReadOnly(_)) => {
+                self.report(step, StepStatus::Skipped, Some("read_only".to_string()))
+            }
+            Err(e) => self.report(step, StepStatus::Failed, Some(e.to_string())),
+        }
+        result
+    }
+}
+
+/// Run the steps in order, given the work behind each. Futures do nothing
+/// until polled, so the ones for skipped steps, or steps after a failure,
+/// are dropped without running.
+async fn run_steps<P: Fn(&StackStepEvent)>(
+    run: &mut Run<P>,
+    detect: impl Future<Output = Result<ServiceStatus, FlowStateError>>,
+    start_docker: impl Future<Output = Result<String, FlowStateError>>,
+    wait_docker: impl Future<Output = Result<ServiceStatus, FlowStateError>>,
+    start_supabase: impl Future<Output = Result<String, FlowStateError>>,
+    verify_schema: impl Future<Output = Result<String, FlowStateError>>,
+) -> Result<Option<String>, (StackStep, FlowStateError)> {
+    let docker = run
+        .step(StackStep::Detecting, detect, |status| {
+            Some(
+                if status.is_running() {
+                    "docker_running"
+                } else {
+                    "docker_not_running"
+                }
+                .to_string(),
+            )
+        })
+        .await
+        .map_err(|e| (StackStep::Detecting, e))?;
+
+    let docker_version = if docker.is_running() {
+        run.report(
+            StackStep::StartingDocker,
+            StepStatus::Skipped,
+            Some("already_running".to_string()),
+        );
+        run.report(
+            StackStep::WaitingDocker,
+            StepStatus::Skipped,
+            Some("already_running".to_string()),
+        );
+        docker.version
+    } else {
+        run.step(StackStep::StartingDocker, start_docker, |result| {
+            Some(result.clone())
+        })
+        .await
+        .map_err(|e| (StackStep::StartingDocker, e))?;
+
+        run.step(StackStep::WaitingDocker, wait_docker, |_| None)
+            .await
+            .map_err(|e| (StackStep::WaitingDocker, e))?
+            .version
+    };
+
+    run.step(StackStep::StartingSupabase, start_supabase, |result| {
+        Some(result.clone())
+    })
+    .await
+    .map_err(|e| (StackStep::StartingSupabase, e))?;
+
+    match run
+        .step(StackStep::VerifyingSchema, verify_schema, |result| {
+            Some(result.clone())
+        })
+        .await
+    {
+        // Services are up; the migrations wait until read-only mode is off
+        Ok(_) | Err(FlowStateError::ReadOnly(_)) => {}
+        Err(e) => return Err((StackStep::VerifyingSchema, e)),
+    }
+
+    Ok(docker_version)
+}
+
+/// Bring up Docker and Supabase and verify the schema, publishing `stack://step`
+/// for each phase. Docker gets `docker_timeout_secs` (default 120) to answer.
+#[tauri::command]
+pub async fn ensure_stack_ready(
+    app: AppHandle,
+    runtime: Option<crate::container_runtime::ContainerRuntime>,
+    docker_timeout_secs: Option<u64>,
+) -> Result<StackReport, FlowStateError> {
+    crate::trace::scope("ensure_stack_ready", async move {
+        let timeout = docker_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(crate::DOCKER_START_TIMEOUT);
+        let mut run = Run {
+            publish: |event: &StackStepEvent| crate::events::publish(&app, "stack://step", event),
+            started: Instant::now(),
+            steps: Vec::new(),
+        };
+
+        let result = run_steps(
+            &mut run,
+            async {
+                crate::check_docker_installed(app.clone()).await?;
+                Ok(crate::probe_docker_status(&app).await?)
+            },
+            async {
+                crate::launch_container_runtime(&app, runtime)
+                    .await
+                    .map_err(FlowStateError::DockerStartFailed)
+            },
+            async {
+                let status = crate::wait_for_docker(&app, timeout).await?;
+                app.state::<crate::snapshot::StateSnapshot>()
+                    .set_docker_status(status.clone());
+                Ok(status)
+            },
+            crate::start_supabase(app.clone()),
+            crate::run_supabase_migrations(app.clone()),
+        )
+        .await;
+        let duration_ms = run.started.elapsed().as_millis() as u64;
+
+        Ok(match result {
+            Ok(docker_version) => {
+                log::info!("Local stack ready after {} ms", duration_ms);
+                StackReport {
+                    ready: true,
+                    failed_step: None,
+                    error: None,
+                    docker_version,
+                    steps: run.steps,
+                    duration_ms,
+                }
+            }
+            Err((step, error)) => {
+                log::warn!("Local stack not ready ({:?} failed): {}", step, error);
+                StackReport {
+                    ready: false,
+                    failed_step: Some(step),
+                    error: Some(error),
+                    docker_version: None,
+                    steps: run.steps,
+                    duration_ms,
+                }
+            }
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::ready;
+
+    fn run() -> Run<impl Fn(&StackStepEvent)> {
+        Run {
+            publish: |_: &StackStepEvent| {},
+            started: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    fn block_on<T>(fut: impl Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    /// Step and status of each event, without the running ones
+    fn outcomes<P>(run: &Run<P>) -> Vec<(StackStep, StepStatus)> {
+        run.steps
+            .iter()
+            .filter(|e| e.status != StepStatus::Running)
+            .map(|e| (e.step, e.status))
+            .collect()
+    }
+
+    async fn unreachable<T>() -> Result<T, FlowStateError> {
+        panic!("step should not run")
+    }
+
+    #[test]
+    fn skips_starting_docker_when_it_is_already_running() {
+        let mut run = run();
+        let docker = ServiceStatus {
+            version: Some("27.3.1".to_string()),
+            ..ServiceStatus::running()
+        };
+        let result = block_on(run_steps(
+            &mut run,
+            ready(Ok(docker)),
+            unreachable(),
+            unreachable(),
+            ready(Ok("already_running".to_string())),
+            ready(Ok("migrations_complete".to_string())),
+        ));
+        assert_eq!(result.unwrap(), Some("27.3.1".to_string()));
+        assert_eq!(
+            outcomes(&run),
+            [
+                (StackStep::Detecting, StepStatus::Succeeded),
+                (StackStep::StartingDocker, StepStatus::Skipped),
+                (StackStep::WaitingDocker, StepStatus::Skipped),
+                (StackStep::StartingSupabase, StepStatus::Succeeded),
+                (StackStep::VerifyingSchema, StepStatus::Succeeded),
+            ]
+        );
+        assert_eq!(run.steps[2].detail.as_deref(), Some("already_running"));
+    }
+
+    #[test]
+    fn stops_at_the_first_failure() {
+        let mut run = run();
+        let result = block_on(run_steps(
+            &mut run,
+            ready(Ok(ServiceStatus::not_running())),
+            ready(Ok("started".to_string())),
+            ready(Err(FlowStateError::DockerDaemonDown(
+                "Docker did not answer within 120s".to_string(),
+            ))),
+            unreachable(),
+            unreachable(),
+        ));
+        let (step, error) = result.unwrap_err();
+        assert_eq!(step, StackStep::WaitingDocker);
+        assert_eq!(error.code(), "docker_daemon_down");
+        assert_eq!(
+            outcomes(&run),
+            [
+                (StackStep::Detecting, StepStatus::Succeeded),
+                (StackStep::StartingDocker, StepStatus::Succeeded),
+                (StackStep::WaitingDocker, StepStatus::Failed),
+            ]
+        );
+        let last = run.steps.last().unwrap();
+        assert_eq!(
+            last.detail.as_deref(),
+            Some("Docker did not answer within 120s")
+        );
+    }
+
+    #[test]
+    fn read_only_schema_check_still_counts_as_ready() {
+        let mut run = run();
+        let result = block_on(run_steps(
+            &mut run,
+            ready(Ok(ServiceStatus::running())),
+            unreachable(),
+            unreachable(),
+            ready(Ok("already_running".to_string())),
+            ready(Err(crate::read_only::ReadOnlyError {
+                command: "run_supabase_migrations",
+            }
+            .into())),
+        ));
+        assert!(result.is_ok());
+        assert_eq!(
+            run.steps.last().map(|e| (e.step, e.status)),
+            Some((StackStep::VerifyingSchema, StepStatus::Skipped))
+        );
+    }
+}
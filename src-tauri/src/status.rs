@@ -0,0 +1,138 @@
+//! Typed payloads returned by the service commands.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::container_runtime::ContainerRuntime;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Running,
+    NotRunning,
+}
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceState::Running => write!(f, "running"),
+            ServiceState::NotRunning => write!(f, "not_running"),
+        }
+    }
+}
+
+/// Status of Docker or the local Supabase stack
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    /// Docker server version, when running
+    pub version: Option<String>,
+    /// Container runtime serving the Docker API, when known
+    pub runtime: Option<ContainerRuntime>,
+    /// Connection details, when Supabase is running and the CLI could report them
+    pub config: Option<SupabaseConfig>,
+}
+
+impl ServiceStatus {
+    pub fn running() -> Self {
+        ServiceStatus {
+            state: ServiceState::Running,
+            version: None,
+            runtime: None,
+            config: None,
+        }
+    }
+
+    pub fn not_running() -> Self {
+        ServiceStatus {
+            state: ServiceState::NotRunning,
+            version: None,
+            runtime: None,
+            config: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == ServiceState::Running
+    }
+}
+
+/// Connection details from `supabase status -o json` (keys as printed by the CLI).
+/// The secret keys are moved to the keychain before this leaves the backend
+/// (see `secrets::stash_supabase_keys`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct SupabaseConfig {
+    pub api_url: Option<String>,
+    pub graphql_url: Option<String>,
+    pub s3_storage_url: Option<String>,
+    pub db_url: Option<String>,
+    pub studio_url: Option<String>,
+    pub inbucket_url: Option<String>,
+    pub jwt_secret: Option<String>,
+    pub anon_key: Option<String>,
+    pub service_role_key: Option<String>,
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+    pub s3_region: Option<String>,
+}
+
+impl SupabaseConfig {
+    /// Pick the known fields out of parsed `supabase status` output
+    pub fn from_fields(fields: &BTreeMap<String, String>) -> Self {
+        let get = |key: &str| fields.get(key).cloned();
+        SupabaseConfig {
+            api_url: get("API_URL"),
+            graphql_url: get("GRAPHQL_URL"),
+            s3_storage_url: get("S3_STORAGE_URL"),
+            db_url: get("DB_URL"),
+            studio_url: get("STUDIO_URL"),
+            inbucket_url: get("INBUCKET_URL"),
+            jwt_secret: get("JWT_SECRET"),
+            anon_key: get("ANON_KEY"),
+            service_role_key: get("SERVICE_ROLE_KEY"),
+            s3_access_key: get("S3_ACCESS_KEY"),
+            s3_secret_key: get("S3_SECRET_KEY"),
+            s3_region: get("S3_REGION"),
+        }
+    }
+}
+
+/// Process resource usage (for SIGTERM debugging - TASK-1060)
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    pub pid: u32,
+    pub rss_bytes: u64,
+    pub virtual_bytes: u64,
+    /// Since the previous sample; 100 = one core fully busy
+    pub cpu_percent: f32,
+    /// Open file descriptors (handles on Windows), None if unavailable
+    pub open_fds: Option<u64>,
+    /// "linux", "macos" or "windows"
+    pub platform: String,
+}
+
+/// API v1 shape of `MemoryUsage`, with sizes printed like /proc ("123456 kB")
+#[derive(Clone, Debug, Serialize)]
+pub struct LegacyMemoryUsage {
+    pub pid: u32,
+    pub rss: String,
+    #[serde(rename = "virtual")]
+    pub virtual_size: String,
+    pub platform: String,
+}
+
+impl From<MemoryUsage> for LegacyMemoryUsage {
+    fn from(usage: MemoryUsage) -> Self {
+        LegacyMemoryUsage {
+            pid: usage.pid,
+            rss: format!("{} kB", usage.rss_bytes / 1024),
+            virtual_size: format!("{} kB", usage.virtual_bytes / 1024),
+            platform: usage.platform,
+        }
+    }
+}
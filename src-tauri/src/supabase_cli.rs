@@ -0,0 +1,271 @@
+//! Managed Supabase CLI.
+//!
+//! "supabase: command not found" is the most common setup failure, so the app
+//! can install a pinned CLI release into its data directory. The archive for
+//! this platform is downloaded from the CLI's GitHub releases, checked against
+//! the SHA-256 pinned in this file (not a checksums file fetched from the same
+//! place as the archive), and unpacked to
+//! `<app data>/bin`. Once present, `trace::command` runs every `supabase`
+//! invocation with the managed binary instead of whatever is on PATH.
+//! Download progress is published as `supabase-cli://install`.
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+
+use crate::error::FlowStateError;
+use crate::parsers;
+
+/// CLI release installed by `install_supabase_cli`
+pub const PINNED_VERSION: &str = "2.20.5";
+/// SHA-256 of each `PINNED_VERSION` archive. Bump both together: run
+/// `scripts/pin-supabase-cli.sh <version>`, compare its output with the
+/// checksums file on the release page, and paste it here.
+const PINNED_SHA256: &[(&str, &str)] = &[];
+const RELEASES_URL: &str = "https://github.com/supabase/cli/releases/download";
+/// Upper bound for the downloaded archive
+const MAX_ARCHIVE_SIZE: u64 = 256 * 1024 * 1024;
+/// Bytes between progress events
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+
+#[cfg(target_os = "windows")]
+const BINARY_NAME: &str = "supabase.exe";
+#[cfg(not(target_os = "windows"))]
+const BINARY_NAME: &str = "supabase";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallProgress {
+    pub version: &'static str,
+    pub bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// downloading | verifying | installed
+    pub stage: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInfo {
+    /// Output of `supabase --version`, None when no CLI runs
+    pub version: Option<String>,
+    /// Whether the managed binary is the one in use
+    pub managed: bool,
+    pub path: Option<PathBuf>,
+    pub pinned_version: &'static str,
+}
+
+fn managed_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("bin").join(BINARY_NAME))
+}
+
+/// Program to run for `program`: the managed binary for "supabase" once installed
+/// (as a path, so a non-UTF-8 home directory survives)
+pub fn resolve(app: &AppHandle, program: &str) -> PathBuf {
+    if program == "supabase" {
+        if let Some(path) = managed_path(app).filter(|p| p.is_file()) {
+            return path;
+        }
+    }
+    PathBuf::from(program)
+}
+
+/// "supabase_linux_arm64.tar.gz"
+fn asset_name() -> Result<String, String> {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        "linux" => "linux",
+        "windows" => "windows",
+        other => return Err(format!("No Supabase CLI build for {}", other)),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => return Err(format!("No Supabase CLI build for {}", other)),
+    };
+    Ok(format!("supabase_{}_{}.tar.gz", os, arch))
+}
+
+fn publish(app: &AppHandle, stage: &'static str, bytes: u64, total_bytes: Option<u64>) {
+    crate::events::publish(
+        app,
+        "supabase-cli://install",
+        &InstallProgress {
+            version: PINNED_VERSION,
+            bytes,
+            total_bytes,
+            stage,
+        },
+    );
+}
+
+async fn download(app: &AppHandle, url: &str, limit: u64) -> Result<Vec<u8>, String> {
+    let mut response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download {}: HTTP {}",
+            url,
+            response.status().as_u16()
+        ));
+    }
+
+    let total = response.content_length();
+    if total.is_some_and(|t| t > limit) {
+        return Err(format!("{} is larger than expected", url));
+    }
+
+    let mut body = Vec::new();
+    let mut next_report = PROGRESS_STEP;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Download of {} interrupted: {}", url, e))?
+    {
+        body.extend_from_slice(&chunk);
+        let bytes = body.len() as u64;
+        if bytes > limit {
+            return Err(format!("{} is larger than expected", url));
+        }
+        if bytes >= next_report {
+            publish(app, "downloading", bytes, total);
+            next_report += PROGRESS_STEP;
+        }
+    }
+    Ok(body)
+}
+
+/// Extract the CLI binary from the release archive
+fn unpack(archive: &[u8]) -> Result<Vec<u8>, String> {
+    let mut entries = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in entries
+        .entries()
+        .map_err(|e| format!("Invalid archive: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Invalid archive: {}", e))?;
+        let is_binary = entry
+            .path()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n == BINARY_NAME))
+            .unwrap_or(false);
+        if is_binary {
+            let mut binary = Vec::new();
+            entry
+                .read_to_end(&mut binary)
+                .map_err(|e| format!("Invalid archive: {}", e))?;
+            return Ok(binary);
+        }
+    }
+    Err(format!("{} not found in archive", BINARY_NAME))
+}
+
+/// Download, verify and install the pinned CLI release
+async fn install(app: &AppHandle) -> Result<PathBuf, String> {
+    let asset = asset_name()?;
+    let base = format!("{}/v{}", RELEASES_URL, PINNED_VERSION);
+
+    let expected = PINNED_SHA256
+        .iter()
+        .find(|(file, _)| *file == asset)
+        .map(|(_, sha256)| *sha256)
+        .ok_or_else(|| format!("No pinned checksum for {} {}", asset, PINNED_VERSION))?;
+
+    let archive = download(app, &format!("{}/{}", base, asset), MAX_ARCHIVE_SIZE).await?;
+    publish(
+        app,
+        "verifying",
+        archive.len() as u64,
+        Some(archive.len() as u64),
+    );
+
+    let actual: String = Sha256::digest(&archive)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {} (expected {}, got {})",
+            asset, expected, actual
+        ));
+    }
+
+    let binary = unpack(&archive)?;
+    let path = managed_path(app).ok_or_else(|| "App data directory unavailable".to_string())?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| "Invalid install path".to_string())?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    // Write next to the target and rename, so a running CLI is never half-written
+    let staged = path.with_extension("download");
+    std::fs::write(&staged, &binary)
+        .map_err(|e| format!("Failed to write {}: {}", staged.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {} executable: {}", staged.display(), e))?;
+    }
+    std::fs::rename(&staged, &path)
+        .map_err(|e| format!("Failed to install {}: {}", path.display(), e))?;
+
+    publish(
+        app,
+        "installed",
+        archive.len() as u64,
+        Some(archive.len() as u64),
+    );
+    log::info!(
+        "Installed Supabase CLI {} to {}",
+        PINNED_VERSION,
+        path.display()
+    );
+    Ok(path)
+}
+
+async fn info(app: &AppHandle) -> CliInfo {
+    let version = crate::trace::command(app, "supabase")
+        .args(["--version"])
+        .output()
+        .await
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| parsers::parse_cli_line(&o.stdout).ok());
+    let path = managed_path(app).filter(|p| p.is_file());
+
+    CliInfo {
+        version,
+        managed: path.is_some(),
+        path,
+        pinned_version: PINNED_VERSION,
+    }
+}
+
+/// Install the pinned Supabase CLI into the app data directory
+#[tauri::command]
+pub async fn install_supabase_cli(app: AppHandle) -> Result<CliInfo, FlowStateError> {
+    crate::trace::scope("install_supabase_cli", async move {
+        crate::read_only::ensure_writable(&app, "install_supabase_cli")?;
+        install(&app).await?;
+        Ok(info(&app).await)
+    })
+    .await
+}
+
+/// Version of the Supabase CLI in use and whether it is the managed one
+#[tauri::command]
+pub async fn get_supabase_cli_version(app: AppHandle) -> Result<CliInfo, FlowStateError> {
+    crate::trace::scope(
+        "get_supabase_cli_version",
+        async move { Ok(info(&app).await) },
+    )
+    .await
+}
@@ -0,0 +1,273 @@
+//! Supervisor for the local Supabase stack.
+//!
+//! The service watcher reports every Supabase status it sees; when the stack
+//! goes from running to not running without `stop_supabase`/`cleanup_services`
+//! asking for it, the restart policy decides what happens: `off` (the
+//! default) only reports it, `on_failure` restarts Docker (if needed) and the stack with exponential
+//! backoff, up to `max_attempts`. Each transition is published as
+//! `supervisor://state`. The policy persists in `supervisor.json`.
+//! Detection depends on the watcher running (see watcher.rs).
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::status::ServiceStatus;
+
+pub(crate) const POLICY_STORE: &str = "supervisor.json";
+const POLICY_KEY: &str = "policy";
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartMode {
+    Off,
+    OnFailure,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    pub max_attempts: u32,
+    /// Delay before the first attempt; doubles with each further attempt
+    pub backoff_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            mode: RestartMode::Off,
+            max_attempts: 3,
+            backoff_secs: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Stack not seen running yet, or stopped on request
+    Idle,
+    Running,
+    /// Died unexpectedly (and the policy is off, or a restart is pending)
+    Down,
+    Restarting,
+    /// Restart attempts exhausted
+    GaveUp,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupervisorState {
+    pub phase: Phase,
+    pub policy: RestartPolicy,
+    pub attempt: u32,
+    pub last_error: Option<FlowStateError>,
+}
+
+struct Inner {
+    policy: RestartPolicy,
+    phase: Phase,
+    attempt: u32,
+    last_error: Option<FlowStateError>,
+    /// Set by an explicit stop so the next "not running" isn't a failure
+    expected_stop: bool,
+}
+
+pub struct Supervisor {
+    inner: Mutex<Inner>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor {
+            inner: Mutex::new(Inner {
+                policy: RestartPolicy::default(),
+                phase: Phase::Idle,
+                attempt: 0,
+                last_error: None,
+                expected_stop: false,
+            }),
+        }
+    }
+}
+
+impl Supervisor {
+    pub(crate) fn state(&self) -> SupervisorState {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        SupervisorState {
+            phase: inner.phase,
+            policy: inner.policy.clone(),
+            attempt: inner.attempt,
+            last_error: inner.last_error.clone(),
+        }
+    }
+}
+
+/// Update the phase and publish the transition
+fn transition(app: &AppHandle, phase: Phase, attempt: u32, error: Option<FlowStateError>) {
+    let supervisor = app.state::<Supervisor>();
+    {
+        let mut inner = supervisor.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.phase = phase;
+        inner.attempt = attempt;
+        inner.last_error = error;
+    }
+    crate::events::publish(app, "supervisor://state", &supervisor.state());
+}
+
+/// Called before an intentional stop of the stack (also cancels pending restarts)
+pub fn expect_stop(app: &AppHandle) {
+    let supervisor = app.state::<Supervisor>();
+    let phase = {
+        let mut inner = supervisor.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.expected_stop = true;
+        inner.phase
+    };
+    if matches!(phase, Phase::Down | Phase::Restarting | Phase::GaveUp) {
+        transition(app, Phase::Idle, 0, None);
+    }
+}
+
+/// Feed a Supabase status seen by the watcher
+pub fn observe_supabase(app: &AppHandle, status: &ServiceStatus) {
+    let (phase, expected_stop, mode) = {
+        let supervisor = app.state::<Supervisor>();
+        let inner = supervisor.inner.lock().unwrap_or_else(|e| e.into_inner());
+        (inner.phase, inner.expected_stop, inner.policy.mode)
+    };
+
+    if status.is_running() {
+        if phase != Phase::Running {
+            if matches!(phase, Phase::Down | Phase::Restarting | Phase::GaveUp) {
+                log::info!("Supabase stack recovered");
+            }
+            app.state::<Supervisor>()
+                .inner
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .expected_stop = false;
+            transition(app, Phase::Running, 0, None);
+        }
+        return;
+    }
+
+    if phase != Phase::Running {
+        return;
+    }
+    if expected_stop {
+        transition(app, Phase::Idle, 0, None);
+        return;
+    }
+
+    log::warn!("Supabase stack stopped unexpectedly");
+    transition(app, Phase::Down, 0, None);
+    if mode == RestartMode::OnFailure {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { restart(app).await });
+    }
+}
+
+async fn restart_once(app: &AppHandle) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(app, "supervisor")?;
+    if !crate::probe_docker_status(app).await?.is_running() {
+        crate::start_docker_desktop(app.clone(), None, None).await?;
+    }
+    crate::start_supabase(app.clone()).await?;
+    Ok(())
+}
+
+/// Restart the stack per the policy until it comes back or attempts run out
+async fn restart(app: AppHandle) {
+    let policy = app.state::<Supervisor>().state().policy;
+    let mut last_error = None;
+
+    for attempt in 1..=policy.max_attempts {
+        let doublings = (attempt - 1).min(16);
+        let delay = Duration::from_secs(policy.backoff_secs.saturating_mul(1 << doublings))
+            .min(MAX_BACKOFF);
+        tokio::time::sleep(delay).await;
+
+        // Stopped on purpose, recovered by itself, or policy switched off meanwhile
+        let state = app.state::<Supervisor>().state();
+        if !matches!(state.phase, Phase::Down | Phase::Restarting)
+            || state.policy.mode == RestartMode::Off
+        {
+            return;
+        }
+
+        transition(&app, Phase::Restarting, attempt, last_error.clone());
+        match restart_once(&app).await {
+            Ok(()) => {
+                log::info!("Restarted Supabase stack (attempt {})", attempt);
+                return;
+            }
+            Err(e) => {
+                log::warn!("Supabase restart attempt {} failed: {}", attempt, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    log::error!(
+        "Giving up restarting the Supabase stack after {} attempts",
+        policy.max_attempts
+    );
+    transition(&app, Phase::GaveUp, policy.max_attempts, last_error);
+}
+
+/// Load the saved restart policy
+pub fn init(app: &AppHandle) {
+    let Ok(store) = app.store(POLICY_STORE) else {
+        return;
+    };
+    if let Some(value) = store.get(POLICY_KEY) {
+        match serde_json::from_value::<RestartPolicy>(value) {
+            Ok(policy) => {
+                let supervisor = app.state::<Supervisor>();
+                supervisor
+                    .inner
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .policy = policy;
+            }
+            Err(e) => log::warn!("Ignoring invalid restart policy: {}", e),
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_supervisor_state(supervisor: tauri::State<'_, Supervisor>) -> SupervisorState {
+    supervisor.state()
+}
+
+#[tauri::command]
+pub fn set_restart_policy(
+    app: AppHandle,
+    policy: RestartPolicy,
+) -> Result<SupervisorState, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_restart_policy")?;
+    let store = app
+        .store(POLICY_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", POLICY_STORE, e))?;
+    store.set(
+        POLICY_KEY,
+        serde_json::to_value(&policy).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", POLICY_STORE, e))?;
+
+    let supervisor = app.state::<Supervisor>();
+    supervisor
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .policy = policy;
+    Ok(supervisor.state())
+}
@@ -0,0 +1,674 @@
+//! Taking out and deleting everything the app stores about the user.
+//!
+//! `export_all_my_data` writes a folder holding the stores, every local
+//! database (decrypted when encryption at rest is on), the keychain secrets
+//! of every workspace, the attached files and the signed-in user's rows of
+//! the Supabase tables, with a README describing the layout and a manifest
+//! with row counts. Secrets only leave after the OS authentication prompt
+//! (`os_auth.rs`), and each is audited like `export_secrets`.
+//!
+//! Tables are streamed to their files row by row rather than built up in
+//! memory. The takeout is an export job like the Parquet export
+//! (`export.rs`): it reports `export://progress`, stops on `cancel_export`,
+//! and removes the whole folder when it doesn't finish, secrets included.
+//!
+//! `delete_all_my_data` takes a typed confirmation and the same prompt,
+//! deletes the user's remote rows in one transaction first (and refuses
+//! while signed in but offline, so nothing local is lost without the remote
+//! rows going too), then the keychain secrets, the local databases, the
+//! attached files, the search index and the stores. The audit log stays, with
+//! an entry for the deletion, and so does the app lock's PIN (`app_lock.rs`).
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::export::{Counted, ExportJob, BATCH_ROWS};
+use crate::os_auth::Purpose;
+use crate::sqlite::LocalDb;
+
+/// Typed by the user to confirm `delete_all_my_data`
+pub const DELETE_CONFIRMATION: &str = "delete all my data";
+/// Version of the archive layout described in `README`
+const TAKEOUT_VERSION: u32 = 1;
+const EXPORT_ACTION: &str = "data.export";
+pub(crate) const DELETE_ACTION: &str = "data.delete";
+const DENIED_ACTION: &str = "data.denied";
+
+/// Supabase tables with a `user_id`, rows referring to others first
+const REMOTE_TABLES: [&str; 24] = [
+    "pomodoro_history",
+    "timer_sessions",
+    "notifications",
+    "quick_sort_sessions",
+    "pinned_tasks",
+    "tasks",
+    "groups",
+    "projects",
+    "custom_fields",
+    "user_settings",
+    // Written by the delete trigger on tasks
+    "tombstones",
+    "task_dedup_audit",
+    "xp_logs",
+    "user_achievements",
+    "user_purchases",
+    "user_stats",
+    "user_gamification",
+    "challenge_history",
+    "user_challenges",
+    "arena_runs",
+    "ai_work_profiles",
+    "ai_conversations",
+    "ai_usage_log",
+    "push_subscriptions",
+];
+
+const README: &str = "# FlowState data export
+
+Everything FlowState stored about you when this export was made.
+
+- `manifest.json`: export version, time, app version, signed-in user and
+  the number of entries in each part below.
+- `stores/<name>.json`: app settings and state, as key/value objects.
+- `databases/<name>.json`: the local SQLite databases, one array of rows
+  per table. Timestamps are milliseconds since 1970 (UTC); binary values
+  are hex strings.
+  - `task_cache`: tasks (as in Supabase) and edits waiting to sync
+  - `time_tracking`: time entries, billing rates, invoices and locks
+  - `sessions`: focus sessions, their tags, interruptions, idle periods and
+    editor coding spans, and hourly and daily rollups
+  - `attachments`: attached files and their extracted text
+  - `audit`: secrets revealed or exported, and data exports and deletions
+- `secrets.json`: secrets from the OS keychain by workspace and name.
+  Keep this file safe.
+- `attachments/<id>-<file name>`: the attached files (`id` as in
+  `databases/attachments.json`).
+- `remote/<table>.json`: your rows of each Supabase table, when signed in.
+";
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TakeoutStats {
+    pub stores: usize,
+    /// Rows per database
+    pub databases: BTreeMap<String, usize>,
+    pub secrets: usize,
+    pub attachments: usize,
+    /// Rows per Supabase table; empty when signed out
+    pub remote: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Takeout {
+    pub path: PathBuf,
+    pub stats: TakeoutStats,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionStats {
+    pub remote_rows: u64,
+    pub secrets: usize,
+    pub local_rows: usize,
+    pub attachment_files: usize,
+    pub stores: usize,
+}
+
+/// The local databases by export name (the audit log last, so it holds the
+/// export's own entries)
+fn databases(app: &AppHandle) -> [(&'static str, &LocalDb); 5] {
+    [
+        (
+            "task_cache",
+            &app.state::<crate::offline::TaskCache>().inner().db,
+        ),
+        (
+            "time_tracking",
+            &app.state::<crate::time_tracking::TimeTracker>().inner().db,
+        ),
+        (
+            "sessions",
+            &app.state::<crate::sessions::Sessions>().inner().db,
+        ),
+        (
+            "attachments",
+            &app.state::<crate::attachments::Attachments>().inner().db,
+        ),
+        ("audit", &app.state::<crate::audit::AuditLog>().inner().db),
+    ]
+}
+
+fn tables(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master \
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text)),
+        ValueRef::Blob(bytes) => Value::from(
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+        ),
+    }
+}
+
+/// Write every table to `out` as a JSON object of row arrays, one row per
+/// line; `progress` gets the rows and bytes written since its last call
+/// every `BATCH_ROWS` rows and after each table, and stops the dump by
+/// failing. Returns the row count.
+fn dump<W: Write>(
+    conn: &Connection,
+    out: &mut Counted<W>,
+    progress: &mut dyn FnMut(u64, u64) -> Result<(), String>,
+) -> Result<usize, String> {
+    let mut count = 0;
+    let mut reported = (0, out.bytes);
+    let mut report = |out: &Counted<W>, count: usize| {
+        let delta = ((count - reported.0) as u64, out.bytes - reported.1);
+        reported = (count, out.bytes);
+        progress(delta.0, delta.1)
+    };
+    let io = |e: std::io::Error| e.to_string();
+
+    out.write_all(b"{").map_err(io)?;
+    for (i, table) in tables(conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .enumerate()
+    {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM \"{}\"", table.replace('"', "\"\"")))
+            .map_err(|e| e.to_string())?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+
+        out.write_all(if i == 0 { b"\n" } else { b",\n" })
+            .map_err(io)?;
+        serde_json::to_writer(&mut *out, &table).map_err(|e| e.to_string())?;
+        out.write_all(b": [").map_err(io)?;
+        let mut first = true;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let mut object = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                object.insert(
+                    column.clone(),
+                    json_value(row.get_ref(i).map_err(|e| e.to_string())?),
+                );
+            }
+            out.write_all(if first { b"\n" } else { b",\n" })
+                .map_err(io)?;
+            serde_json::to_writer(&mut *out, &object).map_err(|e| e.to_string())?;
+            first = false;
+            count += 1;
+            if count % BATCH_ROWS == 0 {
+                report(out, count)?;
+            }
+        }
+        out.write_all(if first { b"]" } else { b"\n]" })
+            .map_err(io)?;
+        report(out, count)?;
+    }
+    out.write_all(b"\n}\n").map_err(io)?;
+    Ok(count)
+}
+
+/// Delete every row of every table, keeping the schema; returns how many
+fn wipe(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let mut deleted = 0;
+    for table in tables(&tx)? {
+        deleted += tx.execute(
+            &format!("DELETE FROM \"{}\"", table.replace('"', "\"\"")),
+            [],
+        )?;
+    }
+    // Ids start over
+    let autoincrement: bool = tx.query_row(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE name = 'sqlite_sequence'",
+        [],
+        |row| row.get(0),
+    )?;
+    if autoincrement {
+        tx.execute("DELETE FROM sqlite_sequence", [])?;
+    }
+    tx.commit()?;
+    Ok(deleted)
+}
+
+/// A file name that stays inside the attachments folder
+fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+/// Write `value` to `path`; returns the bytes written
+fn write_json(path: &Path, value: &impl Serialize) -> Result<u64, String> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(path, &text)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(text.len() as u64)
+}
+
+fn create_file(path: &Path) -> Result<Counted<BufWriter<File>>, String> {
+    File::create(path)
+        .map(|file| Counted::new(BufWriter::new(file)))
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+}
+
+fn create_dir(path: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+}
+
+/// Ask for the OS prompt, auditing a refusal
+async fn authorize(app: &AppHandle, what: &str) -> Result<(), String> {
+    if let Err(e) = crate::os_auth::verify(app, Purpose::RevealSecret, what).await {
+        crate::audit::record(app, DENIED_ACTION, what, Some(&e))?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Stores that exist on disk
+fn existing_stores(app: &AppHandle) -> Result<Vec<&'static str>, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?;
+    Ok(crate::encryption::STORES
+        .iter()
+        .copied()
+        .filter(|name| dir.join(name).is_file())
+        .collect())
+}
+
+/// Whether `public.<table>` exists (not every migration may be applied)
+async fn table_exists(client: &tokio_postgres::Client, table: &str) -> Result<bool, String> {
+    let row = client
+        .query_one(
+            "SELECT to_regclass($1) IS NOT NULL",
+            &[&format!("public.{}", table)],
+        )
+        .await
+        .map_err(|e| format!("Failed to check {}: {}", table, e))?;
+    Ok(row.get(0))
+}
+
+async fn export_remote(
+    app: &AppHandle,
+    job: &ExportJob,
+    user: &str,
+    dir: &Path,
+    stats: &mut TakeoutStats,
+) -> Result<(), String> {
+    let mut client = crate::db::connect(app).await?;
+    create_dir(dir)?;
+    for table in REMOTE_TABLES {
+        if !table_exists(&client, table).await? {
+            continue;
+        }
+        let path = dir.join(format!("{}.json", table));
+        let failed = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+        let mut out = create_file(&path)?;
+        out.write_all(b"[").map_err(failed)?;
+        let mut count = 0;
+        crate::export::stream_rows(
+            &mut client,
+            job,
+            &format!(
+                "SELECT to_jsonb(t)::text FROM public.{} t WHERE user_id::text = $1",
+                table
+            ),
+            &[&user],
+            |rows| {
+                let before = out.bytes;
+                for row in rows {
+                    out.write_all(if count == 0 { b"\n" } else { b",\n" })
+                        .and_then(|_| out.write_all(row.get::<_, &str>(0).as_bytes()))
+                        .map_err(failed)?;
+                    count += 1;
+                }
+                job.advance(table, rows.len() as u64, out.bytes - before);
+                Ok(())
+            },
+        )
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+        out.write_all(if count == 0 { b"]\n" } else { b"\n]\n" })
+            .and_then(|_| out.flush())
+            .map_err(failed)?;
+        stats.remote.insert(table.to_string(), count);
+    }
+    Ok(())
+}
+
+/// Everything but the README and manifest, into `dir`
+async fn write_takeout(
+    app: &AppHandle,
+    job: &ExportJob,
+    dir: &Path,
+    user: Option<&str>,
+) -> Result<TakeoutStats, String> {
+    let mut stats = TakeoutStats::default();
+
+    let stores_dir = dir.join("stores");
+    create_dir(&stores_dir)?;
+    for name in existing_stores(app)? {
+        job.check()?;
+        let store = app
+            .store(name)
+            .map_err(|e| format!("Failed to open {}: {}", name, e))?;
+        let entries: Map<String, Value> = store.entries().into_iter().collect();
+        let file = if name.ends_with(".json") {
+            name.to_string()
+        } else {
+            format!("{}.json", name)
+        };
+        job.advance("stores", 0, write_json(&stores_dir.join(file), &entries)?);
+        stats.stores += 1;
+    }
+
+    // Before the databases, so the audit log has the secrets' entries
+    job.check()?;
+    let secrets = crate::secrets::export_all(app)?;
+    stats.secrets = secrets.values().map(BTreeMap::len).sum();
+    job.advance(
+        "secrets",
+        0,
+        write_json(&dir.join("secrets.json"), &secrets)?,
+    );
+
+    let databases_dir = dir.join("databases");
+    create_dir(&databases_dir)?;
+    for (name, db) in databases(app) {
+        let path = databases_dir.join(format!("{}.json", name));
+        let mut out = create_file(&path)?;
+        let rows = db.with(app, |conn| {
+            Ok(dump(conn, &mut out, &mut |rows, bytes| {
+                job.check()?;
+                job.advance(name, rows, bytes);
+                Ok(())
+            }))
+        })??;
+        out.flush()
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        stats.databases.insert(name.to_string(), rows);
+    }
+
+    let attachments: Vec<(String, String, String)> = crate::attachments::with_db(app, |conn| {
+        let mut stmt = conn.prepare("SELECT id, file_name, hash FROM attachments")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    })?;
+    let attachments_dir = dir.join("attachments");
+    create_dir(&attachments_dir)?;
+    for (id, file_name, hash) in attachments {
+        job.check()?;
+        let path = attachments_dir.join(format!("{}-{}", id, safe_file_name(&file_name)));
+        let mut file = create_file(&path)?;
+        crate::attachments::read_blob(app, &hash, &mut file)?;
+        file.flush()
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        job.advance("attachments", 0, file.bytes);
+        stats.attachments += 1;
+    }
+
+    if let Some(user) = user {
+        export_remote(app, job, user, &dir.join("remote"), &mut stats).await?;
+    }
+    Ok(stats)
+}
+
+/// Export everything stored about the user into a new folder inside `path`
+/// (picked in a dialog when not given)
+#[tauri::command]
+pub async fn export_all_my_data(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<Takeout, FlowStateError> {
+    crate::trace::scope("export_all_my_data", async move {
+        crate::app_lock::ensure_unlocked(&app, "export_all_my_data")?;
+        let parent = crate::export::choose_dir(&app, path).await?;
+        let job = ExportJob::start(&app, "takeout")?;
+        authorize(&app, "export all your data").await?;
+        let dir = parent.join(format!(
+            "flowstate-takeout-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        crate::audit::record(&app, EXPORT_ACTION, &dir.display().to_string(), None)?;
+        create_dir(&dir)?;
+        let user = crate::offline::current_user(&app);
+
+        let stats = match write_takeout(&app, &job, &dir, user.as_deref()).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                // A partial takeout still holds secrets; don't leave it behind
+                if let Err(removed) = std::fs::remove_dir_all(&dir) {
+                    log::warn!("Failed to remove {}: {}", dir.display(), removed);
+                }
+                return Err(e.into());
+            }
+        };
+
+        std::fs::write(dir.join("README.md"), README)
+            .map_err(|e| format!("Failed to write README.md: {}", e))?;
+        write_json(
+            &dir.join("manifest.json"),
+            &serde_json::json!({
+                "version": TAKEOUT_VERSION,
+                "exportedAt": chrono::Utc::now().to_rfc3339(),
+                "appVersion": app.package_info().version.to_string(),
+                "userId": user,
+                "stats": stats,
+            }),
+        )?;
+        log::info!("Exported all data to {}", dir.display());
+        Ok(Takeout { path: dir, stats })
+    })
+    .await
+}
+
+async fn delete_remote(app: &AppHandle, user: &str) -> Result<u64, String> {
+    let mut client = crate::db::connect(app)
+        .await
+        .map_err(|e| format!("Remote data can't be deleted while offline: {}", e))?;
+    let mut existing = Vec::new();
+    for table in REMOTE_TABLES {
+        if table_exists(&client, table).await? {
+            existing.push(table);
+        }
+    }
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| format!("Failed to start the deletion: {}", e))?;
+    let mut deleted = 0;
+    for table in existing {
+        deleted += tx
+            .execute(
+                &format!("DELETE FROM public.{} WHERE user_id::text = $1", table),
+                &[&user],
+            )
+            .await
+            .map_err(|e| format!("Failed to delete from {}: {}", table, e))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to delete remote data: {}", e))?;
+    Ok(deleted)
+}
+
+/// Delete everything stored about the user, here and in Supabase;
+/// `confirmation` must be `DELETE_CONFIRMATION`
+#[tauri::command]
+pub async fn delete_all_my_data(
+    app: AppHandle,
+    confirmation: String,
+) -> Result<DeletionStats, FlowStateError> {
+    crate::trace::scope("delete_all_my_data", async move {
+        crate::read_only::ensure_writable(&app, "delete_all_my_data")?;
+        crate::app_lock::ensure_unlocked(&app, "delete_all_my_data")?;
+        if confirmation.trim() != DELETE_CONFIRMATION {
+            return Err(format!("Type \"{}\" to confirm", DELETE_CONFIRMATION).into());
+        }
+        authorize(&app, "delete all your data").await?;
+        let user = crate::offline::current_user(&app);
+        crate::audit::record(
+            &app,
+            DELETE_ACTION,
+            user.as_deref().unwrap_or("local"),
+            None,
+        )?;
+        let mut stats = DeletionStats::default();
+
+        if let Some(user) = &user {
+            stats.remote_rows = delete_remote(&app, user).await?;
+        }
+        crate::time_tracking::stop_at(
+            &app,
+            crate::events::now_ms() as i64,
+            crate::time_tracking::StopReason::Manual,
+        )?;
+        stats.secrets = crate::secrets::delete_all(&app)?;
+        for (name, db) in databases(&app) {
+            if name != "audit" {
+                stats.local_rows += db.with(&app, wipe)?;
+            }
+        }
+        crate::attachments::clean_opened(&app);
+        stats.attachment_files = crate::attachments::collect_garbage(&app)?;
+        if let Err(e) = crate::search::rebuild(&app) {
+            log::warn!("Failed to empty the search index: {}", e);
+        }
+        for name in existing_stores(&app)? {
+            if name == crate::app_lock::LOCK_STORE {
+                continue;
+            }
+            let store = app
+                .store(name)
+                .map_err(|e| format!("Failed to open {}: {}", name, e))?;
+            store.clear();
+            store
+                .save()
+                .map_err(|e| format!("Failed to save {}: {}", name, e))?;
+            stats.stores += 1;
+        }
+        log::info!(
+            "Deleted all data: {} remote rows, {} local rows, {} secrets",
+            stats.remote_rows,
+            stats.local_rows,
+            stats.secrets
+        );
+        Ok(stats)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dump` of `conn` parsed back, and its row count
+    fn dumped(conn: &Connection) -> (Value, usize) {
+        let mut written = Vec::new();
+        let rows = dump(conn, &mut Counted::new(&mut written), &mut |_, _| Ok(())).unwrap();
+        (serde_json::from_slice(&written).unwrap(), rows)
+    }
+
+    fn fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY AUTOINCREMENT, body TEXT, score REAL, raw BLOB);
+             CREATE TABLE \"odd \"\"name\" (x INTEGER);
+             INSERT INTO notes (body, score, raw) VALUES ('first', 1.5, x'00ff'), (NULL, NULL, NULL);
+             INSERT INTO \"odd \"\"name\" VALUES (7);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn dumps_every_table_as_rows() {
+        let (tables, rows) = dumped(&fixture());
+        assert_eq!(rows, 3);
+        assert_eq!(
+            tables["notes"],
+            serde_json::json!([
+                { "id": 1, "body": "first", "score": 1.5, "raw": "00ff" },
+                { "id": 2, "body": null, "score": null, "raw": null }
+            ])
+        );
+        assert_eq!(tables["odd \"name"], serde_json::json!([{ "x": 7 }]));
+        assert!(tables.get("sqlite_sequence").is_none());
+    }
+
+    #[test]
+    fn reports_progress_and_stops_when_it_fails() {
+        let mut out = Counted::new(Vec::new());
+        let mut reports = Vec::new();
+        let rows = dump(&fixture(), &mut out, &mut |rows, bytes| {
+            reports.push((rows, bytes));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(rows, 3);
+        // One report per table, adding up to everything written
+        assert_eq!(reports.iter().map(|r| r.0).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(reports.iter().map(|r| r.1).sum::<u64>() + 3, out.bytes);
+
+        let mut out = Counted::new(Vec::new());
+        let stopped = dump(&fixture(), &mut out, &mut |_, _| {
+            Err("Export cancelled".to_string())
+        });
+        assert_eq!(stopped.unwrap_err(), "Export cancelled");
+    }
+
+    #[test]
+    fn wipes_rows_but_keeps_tables() {
+        let mut conn = fixture();
+        assert_eq!(wipe(&mut conn).unwrap(), 3);
+        let (tables, rows) = dumped(&conn);
+        assert_eq!(rows, 0);
+        assert_eq!(tables, serde_json::json!({ "notes": [], "odd \"name": [] }));
+        conn.execute("INSERT INTO notes (body) VALUES ('again')", [])
+            .unwrap();
+        let id: i64 = conn
+            .query_row("SELECT id FROM notes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(id, 1);
+    }
+
+    #[test]
+    fn attachment_names_stay_in_the_folder() {
+        assert_eq!(safe_file_name("report.pdf"), "report.pdf");
+        assert_eq!(safe_file_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(safe_file_name("a\\b:c"), "a_b_c");
+        assert_eq!(safe_file_name(".hidden"), "hidden");
+    }
+}
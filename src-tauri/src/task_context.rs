@@ -0,0 +1,348 @@
+//! Suggesting the task a focus session is about from the working context.
+//!
+//! The context is the active repository branch (`git.rs`) and, when the
+//! caller knows it, the focused window's title (the desktop offers no
+//! portable way to read it here). Open cached tasks are scored on three
+//! signals: their share of the time tracked on that branch over the last 90
+//! days, a task id or the title's words in the branch name, and the title's
+//! words in the window title. The signals combine as independent evidence
+//! into a 0..1 confidence. Nothing is linked automatically: with suggestions
+//! on, a focus session started without a task publishes
+//! `focus://task_suggestion`, and the user confirms with
+//! `link_focus_session_task`.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::params;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+
+const SUGGEST_KEY: &str = "suggest_tasks";
+/// Tracked time considered for the branch history
+const HISTORY_MS: i64 = 90 * 24 * 60 * 60 * 1000;
+const MIN_CONFIDENCE: f64 = 0.25;
+const MAX_SUGGESTIONS: usize = 5;
+/// Weight of each signal at full strength
+const HISTORY_WEIGHT: f64 = 0.9;
+const TASK_ID_WEIGHT: f64 = 0.95;
+const BRANCH_WEIGHT: f64 = 0.7;
+const WINDOW_WEIGHT: f64 = 0.6;
+/// Leading characters of a task id that mark it in a branch name
+const TASK_ID_PREFIX: usize = 8;
+/// Branch prefixes that say nothing about the task
+const BRANCH_PREFIXES: [&str; 6] = ["feature", "feat", "fix", "bugfix", "hotfix", "chore"];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskSuggestion {
+    pub task_id: String,
+    pub title: String,
+    /// 0..1
+    pub confidence: f64,
+    /// Why, in words, strongest first
+    pub reasons: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskContext {
+    pub repo: Option<String>,
+    pub branch: Option<String>,
+    pub window_title: Option<String>,
+    pub suggestions: Vec<TaskSuggestion>,
+}
+
+/// What the suggestions are scored against
+#[derive(Default)]
+struct Context {
+    branch: Option<String>,
+    /// Milliseconds tracked per task on the branch
+    history: HashMap<String, i64>,
+    window_title: Option<String>,
+}
+
+/// Lowercase words of at least three letters or digits
+fn words(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Share of the title's words that appear in `context`
+fn coverage(title: &str, context: &HashSet<String>) -> f64 {
+    let title = words(title);
+    if title.is_empty() {
+        return 0.0;
+    }
+    title.intersection(context).count() as f64 / title.len() as f64
+}
+
+fn branch_words(branch: &str) -> HashSet<String> {
+    let name = branch
+        .split_once('/')
+        .filter(|(prefix, _)| BRANCH_PREFIXES.contains(&prefix.to_lowercase().as_str()))
+        .map_or(branch, |(_, rest)| rest);
+    words(name)
+}
+
+fn score(id: &str, title: &str, context: &Context) -> Option<TaskSuggestion> {
+    let mut signals: Vec<(f64, String)> = Vec::new();
+
+    let total: i64 = context.history.values().sum();
+    if let (Some(tracked), Some(branch)) = (context.history.get(id), &context.branch) {
+        if total > 0 && *tracked > 0 {
+            let share = *tracked as f64 / total as f64;
+            signals.push((
+                HISTORY_WEIGHT * share,
+                format!(
+                    "{}% of the time tracked on {}",
+                    (share * 100.0).round(),
+                    branch
+                ),
+            ));
+        }
+    }
+    if let Some(branch) = &context.branch {
+        let prefix: String = id.chars().take(TASK_ID_PREFIX).collect();
+        if prefix.chars().count() == TASK_ID_PREFIX
+            && branch.to_lowercase().contains(&prefix.to_lowercase())
+        {
+            signals.push((TASK_ID_WEIGHT, format!("Branch {} names the task", branch)));
+        }
+        let matched = coverage(title, &branch_words(branch));
+        if matched > 0.0 {
+            signals.push((
+                BRANCH_WEIGHT * matched,
+                format!("Branch {} matches the title", branch),
+            ));
+        }
+    }
+    if let Some(window_title) = &context.window_title {
+        let matched = coverage(title, &words(window_title));
+        if matched > 0.0 {
+            signals.push((
+                WINDOW_WEIGHT * matched,
+                "Window title matches the title".into(),
+            ));
+        }
+    }
+
+    let confidence = 1.0 - signals.iter().map(|(s, _)| 1.0 - s).product::<f64>();
+    if confidence < MIN_CONFIDENCE {
+        return None;
+    }
+    signals.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Some(TaskSuggestion {
+        task_id: id.to_string(),
+        title: title.to_string(),
+        confidence: (confidence * 100.0).round() / 100.0,
+        reasons: signals.into_iter().map(|(_, reason)| reason).collect(),
+    })
+}
+
+/// Best matches among the open tasks, most confident first
+fn rank(tasks: &[Map<String, Value>], context: &Context) -> Vec<TaskSuggestion> {
+    let mut suggestions: Vec<TaskSuggestion> = tasks
+        .iter()
+        .filter_map(|task| {
+            let id = task.get("id")?.as_str()?;
+            let title = task
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            score(id, title, context)
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// Cached tasks that are neither done nor deleted
+fn open_tasks(app: &AppHandle) -> Result<Vec<Map<String, Value>>, String> {
+    let rows = crate::offline::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT data FROM tasks \
+             WHERE coalesce(json_extract(data, '$.is_deleted'), 0) = 0 \
+               AND coalesce(json_extract(data, '$.status'), '') <> 'done'",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(rows
+        .iter()
+        .filter_map(|data| serde_json::from_str(data).ok())
+        .collect())
+}
+
+/// Time tracked per task on a branch recently
+fn branch_history(
+    app: &AppHandle,
+    repo: &str,
+    branch: &str,
+) -> Result<HashMap<String, i64>, String> {
+    let since = now_ms() as i64 - HISTORY_MS;
+    crate::time_tracking::with_db(app, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT e.task_id, sum(coalesce(e.ended_at, e.last_seen_at) - e.started_at) \
+             FROM time_entries e JOIN time_entry_branches b ON b.entry_id = e.id \
+             WHERE b.repo = ?1 AND b.branch = ?2 AND e.started_at >= ?3 \
+             GROUP BY e.task_id",
+        )?;
+        let rows = stmt.query_map(params![repo, branch, since], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        rows.collect()
+    })
+}
+
+fn context(app: &AppHandle, window_title: Option<String>) -> Result<TaskContext, String> {
+    let active = crate::git::active_branch(app);
+    let history = match &active {
+        Some(active) => branch_history(app, &active.repo, &active.branch)?,
+        None => HashMap::new(),
+    };
+    let window_title = window_title
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+    let scored = Context {
+        branch: active.as_ref().map(|a| a.branch.clone()),
+        history,
+        window_title: window_title.clone(),
+    };
+    Ok(TaskContext {
+        repo: active.as_ref().map(|a| a.repo.clone()),
+        branch: scored.branch.clone(),
+        window_title,
+        suggestions: rank(&open_tasks(app)?, &scored),
+    })
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.store(crate::focus::SESSION_STORE)
+        .ok()
+        .and_then(|store| store.get(SUGGEST_KEY))
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
+
+/// With suggestions on, publish the likely tasks of a session started
+/// without one
+pub(crate) fn suggest_for_session(app: &AppHandle) {
+    if !enabled(app) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || match context(&app, None) {
+        Ok(context) if !context.suggestions.is_empty() => {
+            crate::events::publish(&app, "focus://task_suggestion", &context)
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("No task suggestion: {}", e),
+    });
+}
+
+/// Tasks that likely match the current context, with a confidence each;
+/// `window_title` is the focused window's title when the caller has it
+#[tauri::command]
+pub fn suggest_task_for_context(
+    app: AppHandle,
+    window_title: Option<String>,
+) -> Result<TaskContext, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "suggest_task_for_context")?;
+    Ok(context(&app, window_title)?)
+}
+
+#[tauri::command]
+pub fn get_task_suggestions(app: AppHandle) -> bool {
+    enabled(&app)
+}
+
+/// Turn suggestions for sessions started without a task on or off
+#[tauri::command]
+pub fn set_task_suggestions(app: AppHandle, enabled: bool) -> Result<bool, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_task_suggestions")?;
+    let store = app
+        .store(crate::focus::SESSION_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", crate::focus::SESSION_STORE, e))?;
+    store.set(SUGGEST_KEY, enabled);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", crate::focus::SESSION_STORE, e))?;
+    Ok(enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, title: &str) -> Map<String, Value> {
+        Map::from_iter([
+            ("id".to_string(), Value::from(id)),
+            ("title".to_string(), Value::from(title)),
+        ])
+    }
+
+    fn tasks() -> Vec<Map<String, Value>> {
+        vec![
+            task("0d1f9a2b-0000-4000-8000-000000000001", "Fix login redirect"),
+            task(
+                "7c3e5b11-0000-4000-8000-000000000002",
+                "Export invoices to PDF",
+            ),
+            task("a9b8c7d6-0000-4000-8000-000000000003", "Weekly review"),
+        ]
+    }
+
+    #[test]
+    fn branch_words_and_history_point_at_a_task() {
+        let context = Context {
+            branch: Some("feature/invoice-pdf-export".to_string()),
+            history: HashMap::from([
+                ("7c3e5b11-0000-4000-8000-000000000002".to_string(), 3_000),
+                ("a9b8c7d6-0000-4000-8000-000000000003".to_string(), 1_000),
+            ]),
+            window_title: None,
+        };
+        let ranked = rank(&tasks(), &context);
+        assert_eq!(ranked[0].title, "Export invoices to PDF");
+        // 1 - (1 - 0.9 * 0.75) * (1 - 0.7 * 2/3)
+        assert_eq!(ranked[0].confidence, 0.83);
+        assert_eq!(ranked[0].reasons.len(), 2);
+        assert_eq!(ranked.len(), 1, "a quarter of the time alone is too weak");
+    }
+
+    #[test]
+    fn task_ids_and_window_titles_count() {
+        let context = Context {
+            branch: Some("0D1F9A2B-oauth".to_string()),
+            history: HashMap::new(),
+            window_title: Some("Weekly review — Notes".to_string()),
+        };
+        let ranked = rank(&tasks(), &context);
+        let titles: Vec<&str> = ranked.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Fix login redirect", "Weekly review"]);
+        assert_eq!(ranked[0].confidence, 0.95);
+        assert_eq!(ranked[1].confidence, 0.6);
+    }
+
+    #[test]
+    fn no_context_suggests_nothing() {
+        assert!(rank(&tasks(), &Context::default()).is_empty());
+        assert_eq!(
+            branch_words("fix/login"),
+            HashSet::from(["login".to_string()])
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Per-task time tracking in a local SQLite database.
+//!
+//! Entries live in `time_tracking.db` in the app data directory, independent
+//! of the Supabase stack, so tracking works while services are down. Only one
+//! timer runs at a time: starting another stops the current one first. The
+//! running entry is stamped every minute, so after a crash it is closed at
+//! the last stamp on the next start instead of running on forever. Timers
+//! also stop on `cleanup_services` (app exit) and when the user goes idle
+//! (closed at the last input). Changes are published as `timer://started`
+//! and `timer://stopped` so every window shows the same timer. New entries
+//! are tagged with the active git branch (`git.rs`) and stamped with their
+//! task's project for billing (`billing.rs`). In read-only mode a started
+//! timer runs in memory only (id 0) and is gone once stopped.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::FlowStateError;
+use crate::events::now_ms;
+use crate::sqlite::LocalDb;
+
+const DB_FILE: &str = "time_tracking.db";
+const HEARTBEAT: Duration = Duration::from_secs(60);
+
+pub(crate) const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS time_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    ended_at INTEGER,
+    last_seen_at INTEGER NOT NULL,
+    stop_reason TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_time_entries_started ON time_entries(started_at);
+CREATE INDEX IF NOT EXISTS idx_time_entries_task ON time_entries(task_id);
+CREATE TABLE IF NOT EXISTS time_entry_branches (
+    entry_id INTEGER PRIMARY KEY,
+    repo TEXT NOT NULL,
+    branch TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS time_entry_billing (
+    entry_id INTEGER PRIMARY KEY,
+    project_id TEXT,
+    billable INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS project_rates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id TEXT NOT NULL,
+    rate_cents INTEGER NOT NULL,
+    currency TEXT NOT NULL,
+    effective_from INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_project_rates_project ON project_rates(project_id, effective_from);
+CREATE TABLE IF NOT EXISTS invoices (
+    id TEXT PRIMARY KEY,
+    client_id TEXT NOT NULL,
+    from_ms INTEGER,
+    to_ms INTEGER,
+    created_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS invoice_entries (
+    invoice_id TEXT NOT NULL,
+    entry_id INTEGER NOT NULL,
+    PRIMARY KEY (invoice_id, entry_id)
+);
+CREATE TABLE IF NOT EXISTS time_entry_locks (
+    entry_id INTEGER PRIMARY KEY,
+    invoice_id TEXT,
+    locked_at INTEGER NOT NULL
+);";
+
+pub(crate) const COLUMNS: &str = "id, task_id, started_at, ended_at, stop_reason";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Manual,
+    /// Another task's timer was started
+    Switched,
+    Idle,
+    Exit,
+    /// Left running by a crash; closed at the last heartbeat
+    Recovered,
+}
+
+impl StopReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            StopReason::Manual => "manual",
+            StopReason::Switched => "switched",
+            StopReason::Idle => "idle",
+            StopReason::Exit => "exit",
+            StopReason::Recovered => "recovered",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string())).ok()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeEntry {
+    /// 0 for a timer started in read-only mode, which isn't saved
+    pub id: i64,
+    pub task_id: String,
+    pub started_at_ms: i64,
+    /// None while the timer runs
+    pub ended_at_ms: Option<i64>,
+    pub duration_ms: i64,
+    pub stop_reason: Option<StopReason>,
+}
+
+impl TimeEntry {
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let started_at_ms: i64 = row.get(2)?;
+        let ended_at_ms: Option<i64> = row.get(3)?;
+        let reason: Option<String> = row.get(4)?;
+        Ok(TimeEntry {
+            id: row.get(0)?,
+            task_id: row.get(1)?,
+            started_at_ms,
+            ended_at_ms,
+            duration_ms: ended_at_ms.unwrap_or(now_ms() as i64) - started_at_ms,
+            stop_reason: reason.as_deref().and_then(StopReason::parse),
+        })
+    }
+}
+
+/// Entries overlapping [from_ms, to_ms); open ends are unbounded
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeRange {
+    pub from_ms: Option<i64>,
+    pub to_ms: Option<i64>,
+    pub task_id: Option<String>,
+}
+
+pub struct TimeTracker {
+    pub(crate) db: LocalDb,
+    /// Timer started in read-only mode
+    unsaved: Mutex<Option<TimeEntry>>,
+}
+
+impl Default for TimeTracker {
+    fn default() -> Self {
+        TimeTracker {
+            db: LocalDb::new(DB_FILE, SCHEMA),
+            unsaved: Mutex::new(None),
+        }
+    }
+}
+
+fn unsaved(app: &AppHandle) -> std::sync::MutexGuard<'_, Option<TimeEntry>> {
+    app.state::<TimeTracker>()
+        .inner()
+        .unsaved
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run `f` against the time tracking database
+pub(crate) fn with_db<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    app.state::<TimeTracker>().db.with(app, f)
+}
+
+fn active(conn: &Connection) -> rusqlite::Result<Option<TimeEntry>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM time_entries WHERE ended_at IS NULL ORDER BY started_at DESC LIMIT 1",
+            COLUMNS
+        ),
+        [],
+        TimeEntry::from_row,
+    )
+    .optional()
+}
+
+/// Close the running entry at `at_ms` (not before its start)
+fn close(conn: &Connection, at_ms: i64, reason: StopReason) -> rusqlite::Result<Option<TimeEntry>> {
+    let Some(entry) = active(conn)? else {
+        return Ok(None);
+    };
+    let ended = at_ms.max(entry.started_at_ms);
+    conn.execute(
+        "UPDATE time_entries SET ended_at = ?1, last_seen_at = ?1, stop_reason = ?2 WHERE id = ?3",
+        params![ended, reason.as_str(), entry.id],
+    )?;
+    Ok(Some(TimeEntry {
+        ended_at_ms: Some(ended),
+        duration_ms: ended - entry.started_at_ms,
+        stop_reason: Some(reason),
+        ..entry
+    }))
+}
+
+fn stopped(app: &AppHandle, entry: &TimeEntry) {
+    log::info!(
+        "Stopped timer for task {} ({}, {}s)",
+        entry.task_id,
+        entry
+            .stop_reason
+            .map(StopReason::as_str)
+            .unwrap_or_default(),
+        entry.duration_ms / 1000
+    );
+    if entry.id != 0 {
+        crate::audit::record_time_entry(app, entry);
+    }
+    crate::events::publish(app, "timer://stopped", entry);
+}
+
+/// Stop the unsaved timer, if any, at `at_ms`
+fn stop_unsaved(app: &AppHandle, at_ms: i64, reason: StopReason) -> Option<TimeEntry> {
+    let entry = unsaved(app).take()?;
+    let ended = at_ms.max(entry.started_at_ms);
+    let entry = TimeEntry {
+        ended_at_ms: Some(ended),
+        duration_ms: ended - entry.started_at_ms,
+        stop_reason: Some(reason),
+        ..entry
+    };
+    stopped(app, &entry);
+    Some(entry)
+}
+
+/// Stop the running timer, if any, at `at_ms`
+pub fn stop_at(
+    app: &AppHandle,
+    at_ms: i64,
+    reason: StopReason,
+) -> Result<Option<TimeEntry>, String> {
+    if let Some(entry) = stop_unsaved(app, at_ms, reason) {
+        return Ok(Some(entry));
+    }
+    let entry = with_db(app, |conn| close(conn, at_ms, reason))?;
+    if let Some(entry) = &entry {
+        stopped(app, entry);
+    }
+    Ok(entry)
+}
+
+/// Close entries a crash left running and start the heartbeat
+pub fn init(app: &AppHandle) {
+    let recovered = with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        let running: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id FROM time_entries WHERE ended_at IS NULL")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        tx.execute(
+            "UPDATE time_entries SET ended_at = last_seen_at, stop_reason = ?1 WHERE ended_at IS NULL",
+            params![StopReason::Recovered.as_str()],
+        )?;
+        let entries = running
+            .iter()
+            .map(|id| {
+                tx.query_row(
+                    &format!("SELECT {} FROM time_entries WHERE id = ?1", COLUMNS),
+                    params![id],
+                    TimeEntry::from_row,
+                )
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(entries)
+    });
+    match recovered {
+        Ok(entries) if entries.is_empty() => {}
+        Ok(entries) => {
+            log::warn!(
+                "Closed {} timer(s) left running by the previous session",
+                entries.len()
+            );
+            for entry in &entries {
+                crate::audit::record_time_entry(app, entry);
+            }
+        }
+        Err(e) => log::warn!("Time tracking unavailable: {}", e),
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            crate::clock::sleep(HEARTBEAT).await;
+            let _ = with_db(&app, |conn| {
+                conn.execute(
+                    "UPDATE time_entries SET last_seen_at = ?1 WHERE ended_at IS NULL",
+                    params![now_ms() as i64],
+                )
+            });
+        }
+    });
+}
+
+/// Start a timer that isn't saved, for read-only mode
+fn start_unsaved(app: &AppHandle, task_id: String, now: i64) -> TimeEntry {
+    let mut slot = unsaved(app);
+    if let Some(current) = slot.as_ref().filter(|e| e.task_id == task_id) {
+        return current.clone();
+    }
+    let entry = TimeEntry {
+        id: 0,
+        task_id,
+        started_at_ms: now,
+        ended_at_ms: None,
+        duration_ms: 0,
+        stop_reason: None,
+    };
+    let previous = slot.replace(entry.clone());
+    drop(slot);
+    if let Some(previous) = previous {
+        stopped(
+            app,
+            &TimeEntry {
+                ended_at_ms: Some(now),
+                duration_ms: now - previous.started_at_ms,
+                stop_reason: Some(StopReason::Switched),
+                ..previous
+            },
+        );
+    }
+    log::info!(
+        "Started unsaved timer for task {} (read-only mode)",
+        entry.task_id
+    );
+    crate::events::publish(app, "timer://started", &entry);
+    entry
+}
+
+/// Start timing a task; a timer already running for another task is stopped.
+/// In read-only mode the timer isn't saved.
+#[tauri::command]
+pub fn start_task_timer(app: AppHandle, task_id: String) -> Result<TimeEntry, FlowStateError> {
+    if task_id.trim().is_empty() {
+        return Err("task_id must not be empty".into());
+    }
+    let now = now_ms() as i64;
+    if app.state::<crate::read_only::ReadOnlyMode>().is_enabled() {
+        return Ok(start_unsaved(&app, task_id, now));
+    }
+    stop_unsaved(&app, now, StopReason::Switched);
+
+    let branch = crate::git::active_branch(&app);
+    let project_id = crate::offline::cached_task(&app, &task_id)
+        .unwrap_or_else(|e| {
+            log::warn!("No project for the time entry: {}", e);
+            None
+        })
+        .and_then(|task| task.get("project_id")?.as_str().map(str::to_string));
+    // Checked, switched and inserted in one transaction, so two starts can't
+    // leave two timers running
+    let (entry, switched) = with_db(&app, |conn| {
+        let tx = conn.transaction()?;
+        if let Some(current) = active(&tx)?.filter(|e| e.task_id == task_id) {
+            return Ok((current, None));
+        }
+        let switched = close(&tx, now, StopReason::Switched)?;
+        tx.execute(
+            "INSERT INTO time_entries (task_id, started_at, last_seen_at) VALUES (?1, ?2, ?2)",
+            params![task_id, now],
+        )?;
+        let id = tx.last_insert_rowid();
+        if let Some(branch) = &branch {
+            crate::git::tag_entry(&tx, id, branch)?;
+        }
+        crate::billing::stamp_entry(&tx, id, project_id.as_deref())?;
+        tx.commit()?;
+        let entry = TimeEntry {
+            id,
+            task_id: task_id.clone(),
+            started_at_ms: now,
+            ended_at_ms: None,
+            duration_ms: 0,
+            stop_reason: None,
+        };
+        Ok((entry, Some(switched)))
+    })?;
+    // None when the task's timer was already running
+    let Some(switched) = switched else {
+        return Ok(entry);
+    };
+    if let Some(previous) = &switched {
+        stopped(&app, previous);
+    }
+    log::info!("Started timer for task {}", entry.task_id);
+    crate::events::publish(&app, "timer://started", &entry);
+    Ok(entry)
+}
+
+/// Stop the running timer; returns the finished entry (None if none was running)
+#[tauri::command]
+pub fn stop_task_timer(app: AppHandle) -> Result<Option<TimeEntry>, FlowStateError> {
+    Ok(stop_at(&app, now_ms() as i64, StopReason::Manual)?)
+}
+
+/// The running timer, if any
+pub(crate) fn active_timer(app: &AppHandle) -> Result<Option<TimeEntry>, String> {
+    if let Some(entry) = unsaved(app).as_ref() {
+        return Ok(Some(TimeEntry {
+            duration_ms: now_ms() as i64 - entry.started_at_ms,
+            ..entry.clone()
+        }));
+    }
+    with_db(app, |conn| active(conn))
+}
+
+#[tauri::command]
+pub fn get_active_task_timer(app: AppHandle) -> Result<Option<TimeEntry>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_active_task_timer")?;
+    Ok(active_timer(&app)?)
+}
+
+/// Time entries overlapping a range (optionally for one task), oldest first
+#[tauri::command]
+pub fn get_time_entries(
+    app: AppHandle,
+    range: Option<TimeRange>,
+) -> Result<Vec<TimeEntry>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_time_entries")?;
+    let range = range.unwrap_or_default();
+    Ok(with_db(&app, |conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM time_entries \
+             WHERE (?1 IS NULL OR coalesce(ended_at, ?4) > ?1) \
+               AND (?2 IS NULL OR started_at < ?2) \
+               AND (?3 IS NULL OR task_id = ?3) \
+             ORDER BY started_at",
+            COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![range.from_ms, range.to_ms, range.task_id, now_ms() as i64],
+            TimeEntry::from_row,
+        )?;
+        rows.collect()
+    })?)
+}
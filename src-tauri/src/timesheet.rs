@@ -0,0 +1,773 @@
+//! Timesheets for billing clients from tracked time.
+//!
+//! Clients, the issuer, currency, tax and rounding live in `timesheet.json`
+//! (`set_timesheet_settings`). A client owns a list of project ids; the
+//! finished billable time entries of their tasks that started in the range
+//! go on the sheet, grouped by task, each entry rounded by the rounding rule
+//! before it is priced at its project's rate (`billing.rs`) or the rate
+//! given for the whole sheet. `generate_timesheet` writes the same sheet as CSV (one row per
+//! entry) and as an A4 PDF into the chosen folder. The PDF uses the builtin
+//! Helvetica font, which only covers Latin-1 text. Each sheet is recorded
+//! under the next invoice number, which its files and the PDF carry and whose
+//! entries `lock_entries` can freeze; regenerating a sheet gets a new number
+//! rather than replacing an earlier invoice.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, TimeZone};
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::billing::PricedEntry;
+use crate::error::FlowStateError;
+use crate::time_tracking::TimeRange;
+
+pub(crate) const TIMESHEET_STORE: &str = "timesheet.json";
+const SETTINGS_KEY: &str = "settings";
+const PAGE_WIDTH: Mm = Mm(210.0);
+const PAGE_HEIGHT: Mm = Mm(297.0);
+const MARGIN_MM: f32 = 20.0;
+const LINE_MM: f32 = 6.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetClient {
+    pub id: String,
+    pub name: String,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+    /// Projects whose tasks are billed to this client
+    pub project_ids: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    #[default]
+    Up,
+    Nearest,
+    Down,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Rounding {
+    /// 0 rounds to the nearest minute
+    pub increment_minutes: u32,
+    pub mode: RoundingMode,
+}
+
+impl Rounding {
+    /// Billed minutes for a duration
+    fn minutes(&self, duration_ms: i64) -> i64 {
+        let duration_ms = duration_ms.max(0) as f64;
+        if self.increment_minutes == 0 {
+            return (duration_ms / 60_000.0).round() as i64;
+        }
+        let increment = f64::from(self.increment_minutes);
+        let units = duration_ms / (increment * 60_000.0);
+        let units = match self.mode {
+            RoundingMode::Up => units.ceil(),
+            RoundingMode::Nearest => units.round(),
+            RoundingMode::Down => units.floor(),
+        };
+        (units * increment) as i64
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimesheetSettings {
+    pub issuer_name: Option<String>,
+    pub issuer_address: Option<String>,
+    pub issuer_tax_id: Option<String>,
+    /// ISO 4217 code printed next to amounts
+    pub currency: String,
+    /// e.g. "VAT"
+    pub tax_label: String,
+    pub tax_percent: f64,
+    pub rounding: Rounding,
+    pub clients: Vec<TimesheetClient>,
+}
+
+impl Default for TimesheetSettings {
+    fn default() -> Self {
+        TimesheetSettings {
+            issuer_name: None,
+            issuer_address: None,
+            issuer_tax_id: None,
+            currency: "USD".to_string(),
+            tax_label: "Tax".to_string(),
+            tax_percent: 0.0,
+            rounding: Rounding::default(),
+            clients: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetLine {
+    pub entry_id: i64,
+    /// Local date the entry started (YYYY-MM-DD)
+    pub date: String,
+    pub started_at_ms: i64,
+    pub duration_ms: i64,
+    /// After rounding
+    pub minutes: i64,
+    /// Per hour
+    pub rate_cents: i64,
+    pub amount_cents: i64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetGroup {
+    pub task_id: String,
+    pub title: String,
+    pub minutes: i64,
+    pub amount_cents: i64,
+    pub lines: Vec<TimesheetLine>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timesheet {
+    pub client_id: String,
+    pub client_name: String,
+    pub from_ms: Option<i64>,
+    pub to_ms: Option<i64>,
+    pub currency: String,
+    /// Rate given for the whole sheet; None when priced at project rates
+    pub hourly_rate: Option<f64>,
+    pub groups: Vec<TimesheetGroup>,
+    pub minutes: i64,
+    pub subtotal_cents: i64,
+    pub tax_label: String,
+    pub tax_percent: f64,
+    pub tax_cents: i64,
+    pub total_cents: i64,
+    /// Invoice number ("INV-00042"); `lock_entries` takes it
+    pub invoice_id: String,
+    pub csv_path: PathBuf,
+    pub pdf_path: PathBuf,
+}
+
+fn settings(app: &AppHandle) -> TimesheetSettings {
+    app.store(TIMESHEET_STORE)
+        .ok()
+        .and_then(|store| store.get(SETTINGS_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn local_date(ms: i64) -> String {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn cents(minutes: i64, rate_cents: i64) -> i64 {
+    (minutes as f64 * rate_cents as f64 / 60.0).round() as i64
+}
+
+/// Total minutes, subtotal and tax of the groups
+fn totals(groups: &[TimesheetGroup], tax_percent: f64) -> (i64, i64, i64) {
+    let minutes = groups.iter().map(|g| g.minutes).sum();
+    let subtotal_cents: i64 = groups.iter().map(|g| g.amount_cents).sum();
+    let tax_cents = (subtotal_cents as f64 * tax_percent / 100.0).round() as i64;
+    (minutes, subtotal_cents, tax_cents)
+}
+
+fn money(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!(
+        "{}{}.{:02} {}",
+        sign,
+        cents.abs() / 100,
+        cents.abs() % 100,
+        currency
+    )
+}
+
+fn hours(minutes: i64) -> String {
+    format!("{:.2}", minutes as f64 / 60.0)
+}
+
+/// Lowercase ASCII alphanumerics and dashes, for file names
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "client".to_string()
+    } else {
+        slug
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Title and project of a cached task
+fn task_info(app: &AppHandle, task_id: &str) -> Result<(String, Option<String>), String> {
+    let task = crate::offline::cached_task(app, task_id)?.unwrap_or_default();
+    let title = task
+        .get("title")
+        .and_then(Value::as_str)
+        .unwrap_or(task_id)
+        .to_string();
+    let project_id = task
+        .get("project_id")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    Ok((title, project_id))
+}
+
+/// Group the billable entries of `projects` by task, in order of the first
+/// entry of each task; `rate_cents` overrides the project rates
+fn group(
+    app: &AppHandle,
+    entries: Vec<PricedEntry>,
+    projects: &[String],
+    rounding: &Rounding,
+    rate_cents: Option<i64>,
+    currency: &str,
+) -> Result<Vec<TimesheetGroup>, String> {
+    let mut tasks: HashMap<String, (String, Option<String>)> = HashMap::new();
+    let mut groups: Vec<TimesheetGroup> = Vec::new();
+    for priced in entries {
+        if !priced.billable {
+            continue;
+        }
+        let entry = &priced.entry;
+        let (title, task_project) = match tasks.get(&entry.task_id) {
+            Some(info) => info.clone(),
+            None => {
+                let info = task_info(app, &entry.task_id)?;
+                tasks.insert(entry.task_id.clone(), info.clone());
+                info
+            }
+        };
+        // Entries from before billing weren't stamped with a project
+        let project = priced.project_id.clone().or(task_project);
+        if !project.as_ref().is_some_and(|p| projects.contains(p)) {
+            continue;
+        }
+        let rate_cents = match (rate_cents, &priced.rate) {
+            (Some(rate_cents), _) => rate_cents,
+            (None, Some(rate)) if rate.currency == currency => rate.rate_cents,
+            (None, Some(rate)) => {
+                return Err(format!(
+                    "Project {} is billed in {}, the timesheet in {}",
+                    rate.project_id, rate.currency, currency
+                ))
+            }
+            (None, None) if priced.project_id.is_none() => {
+                return Err(format!(
+                    "'{}' on {} predates project rates; give a rate for the timesheet",
+                    title,
+                    local_date(entry.started_at_ms)
+                ))
+            }
+            (None, None) => {
+                return Err(format!(
+                    "No rate for '{}' on {}; set a project rate or give one for the timesheet",
+                    title,
+                    local_date(entry.started_at_ms)
+                ))
+            }
+        };
+
+        let minutes = rounding.minutes(entry.duration_ms);
+        let line = TimesheetLine {
+            entry_id: entry.id,
+            date: local_date(entry.started_at_ms),
+            started_at_ms: entry.started_at_ms,
+            duration_ms: entry.duration_ms,
+            minutes,
+            rate_cents,
+            amount_cents: cents(minutes, rate_cents),
+        };
+        let index = match groups.iter().position(|g| g.task_id == entry.task_id) {
+            Some(index) => index,
+            None => {
+                groups.push(TimesheetGroup {
+                    task_id: entry.task_id.clone(),
+                    title,
+                    minutes: 0,
+                    amount_cents: 0,
+                    lines: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[index];
+        group.minutes += line.minutes;
+        group.amount_cents += line.amount_cents;
+        group.lines.push(line);
+    }
+    Ok(groups)
+}
+
+fn write_csv(path: &Path, sheet: &Timesheet) -> Result<(), String> {
+    let mut csv = String::from("date,task,task_id,entry_id,hours,rate,amount,currency\n");
+    for group in &sheet.groups {
+        for line in &group.lines {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{:.2},{:.2},{}",
+                line.date,
+                csv_field(&group.title),
+                csv_field(&group.task_id),
+                line.entry_id,
+                hours(line.minutes),
+                line.rate_cents as f64 / 100.0,
+                line.amount_cents as f64 / 100.0,
+                sheet.currency
+            );
+        }
+    }
+    std::fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Writes lines top to bottom, starting a new page when one is full
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, PAGE_WIDTH, PAGE_HEIGHT, "Layer 1");
+        let font = |font| {
+            doc.add_builtin_font(font)
+                .map_err(|e| format!("Failed to load PDF font: {}", e))
+        };
+        let regular = font(BuiltinFont::Helvetica)?;
+        let bold = font(BuiltinFont::HelveticaBold)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(PdfWriter {
+            doc,
+            layer,
+            regular,
+            bold,
+            y: PAGE_HEIGHT.0 - MARGIN_MM,
+        })
+    }
+
+    fn ensure_room(&mut self) {
+        if self.y < MARGIN_MM {
+            let (page, layer) = self.doc.add_page(PAGE_WIDTH, PAGE_HEIGHT, "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT.0 - MARGIN_MM;
+        }
+    }
+
+    /// One line of (x in mm, text) cells
+    fn row(&mut self, cells: &[(f32, &str)], bold: bool, size: f32) {
+        self.ensure_room();
+        let font = if bold { &self.bold } else { &self.regular };
+        for (x, text) in cells {
+            self.layer
+                .use_text(*text, size, Mm(MARGIN_MM + x), Mm(self.y), font);
+        }
+        self.y -= LINE_MM * size / 10.0;
+    }
+
+    fn gap(&mut self) {
+        self.y -= LINE_MM / 2.0;
+    }
+}
+
+fn write_pdf(path: &Path, sheet: &Timesheet, settings: &TimesheetSettings) -> Result<(), String> {
+    let mut pdf = PdfWriter::new(&format!("Timesheet {}", sheet.client_name))?;
+    pdf.row(&[(0.0, "Timesheet")], true, 16.0);
+    let period = format!(
+        "Period: {} to {}",
+        sheet
+            .from_ms
+            .map(local_date)
+            .unwrap_or_else(|| "start".to_string()),
+        sheet
+            .to_ms
+            .map(local_date)
+            .unwrap_or_else(|| "today".to_string())
+    );
+    pdf.row(&[(0.0, &period)], false, 10.0);
+    pdf.row(
+        &[(0.0, &format!("Invoice: {}", sheet.invoice_id))],
+        false,
+        10.0,
+    );
+    pdf.gap();
+
+    let client = settings.clients.iter().find(|c| c.id == sheet.client_id);
+    let issuer = [
+        settings.issuer_name.as_deref(),
+        settings.issuer_address.as_deref(),
+        settings.issuer_tax_id.as_deref(),
+    ];
+    let billed_to = [
+        Some(sheet.client_name.as_str()),
+        client.and_then(|c| c.address.as_deref()),
+        client.and_then(|c| c.tax_id.as_deref()),
+    ];
+    pdf.row(&[(0.0, "From"), (90.0, "Bill to")], true, 10.0);
+    let from_lines: Vec<&str> = issuer.iter().flatten().flat_map(|s| s.lines()).collect();
+    let to_lines: Vec<&str> = billed_to.iter().flatten().flat_map(|s| s.lines()).collect();
+    for i in 0..from_lines.len().max(to_lines.len()) {
+        let cells: Vec<(f32, &str)> = [(0.0, from_lines.get(i)), (90.0, to_lines.get(i))]
+            .into_iter()
+            .filter_map(|(x, text)| text.map(|t| (x, *t)))
+            .collect();
+        pdf.row(&cells, false, 10.0);
+    }
+    pdf.gap();
+
+    pdf.row(
+        &[
+            (0.0, "Date"),
+            (25.0, "Task"),
+            (100.0, "Rate"),
+            (125.0, "Hours"),
+            (145.0, "Amount"),
+        ],
+        true,
+        10.0,
+    );
+    for group in &sheet.groups {
+        for line in &group.lines {
+            let title: String = group.title.chars().take(45).collect();
+            pdf.row(
+                &[
+                    (0.0, &line.date),
+                    (25.0, &title),
+                    (100.0, &money(line.rate_cents, &sheet.currency)),
+                    (125.0, &hours(line.minutes)),
+                    (145.0, &money(line.amount_cents, &sheet.currency)),
+                ],
+                false,
+                10.0,
+            );
+        }
+        pdf.row(
+            &[
+                (25.0, "Task total"),
+                (125.0, &hours(group.minutes)),
+                (145.0, &money(group.amount_cents, &sheet.currency)),
+            ],
+            true,
+            10.0,
+        );
+        pdf.gap();
+    }
+
+    let tax = format!("{} ({}%)", sheet.tax_label, sheet.tax_percent);
+    let subtotal = money(sheet.subtotal_cents, &sheet.currency);
+    pdf.row(
+        &[
+            (90.0, "Subtotal"),
+            (125.0, &hours(sheet.minutes)),
+            (145.0, &subtotal),
+        ],
+        false,
+        10.0,
+    );
+    pdf.row(
+        &[
+            (90.0, &tax),
+            (145.0, &money(sheet.tax_cents, &sheet.currency)),
+        ],
+        false,
+        10.0,
+    );
+    pdf.row(
+        &[
+            (90.0, "Total"),
+            (145.0, &money(sheet.total_cents, &sheet.currency)),
+        ],
+        true,
+        10.0,
+    );
+
+    let bytes = pdf
+        .doc
+        .save_to_bytes()
+        .map_err(|e| format!("Failed to render PDF: {}", e))?;
+    std::fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[tauri::command]
+pub fn get_timesheet_settings(app: AppHandle) -> Result<TimesheetSettings, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "get_timesheet_settings")?;
+    Ok(settings(&app))
+}
+
+#[tauri::command]
+pub fn set_timesheet_settings(
+    app: AppHandle,
+    settings: TimesheetSettings,
+) -> Result<TimesheetSettings, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "set_timesheet_settings")?;
+    crate::app_lock::ensure_unlocked(&app, "set_timesheet_settings")?;
+    if !settings.tax_percent.is_finite() || !(0.0..=100.0).contains(&settings.tax_percent) {
+        return Err("Tax must be between 0 and 100 percent".into());
+    }
+    if settings.currency.trim().is_empty() {
+        return Err("Currency must not be empty".into());
+    }
+    let mut ids: Vec<&str> = settings.clients.iter().map(|c| c.id.as_str()).collect();
+    ids.sort_unstable();
+    if ids.iter().any(|id| id.trim().is_empty()) || ids.windows(2).any(|w| w[0] == w[1]) {
+        return Err("Client ids must be unique and not empty".into());
+    }
+
+    let store = app
+        .store(TIMESHEET_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", TIMESHEET_STORE, e))?;
+    store.set(
+        SETTINGS_KEY,
+        serde_json::to_value(&settings).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", TIMESHEET_STORE, e))?;
+    Ok(settings)
+}
+
+/// Write a client's timesheet for a range as CSV and PDF into `dir` (asks
+/// for a folder when not given). `rate` (per hour, in the settings'
+/// currency) prices every entry; without it each entry uses its project's
+/// rate.
+#[tauri::command]
+pub async fn generate_timesheet(
+    app: AppHandle,
+    client: String,
+    range: Option<TimeRange>,
+    rate: Option<f64>,
+    dir: Option<String>,
+) -> Result<Timesheet, FlowStateError> {
+    crate::trace::scope("generate_timesheet", async move {
+        crate::read_only::ensure_writable(&app, "generate_timesheet")?;
+        crate::app_lock::ensure_unlocked(&app, "generate_timesheet")?;
+        if rate.is_some_and(|rate| !rate.is_finite() || rate < 0.0) {
+            return Err("Rate must be a positive number".into());
+        }
+        let settings = settings(&app);
+        let billed = settings
+            .clients
+            .iter()
+            .find(|c| c.id == client)
+            .cloned()
+            .ok_or_else(|| format!("Unknown client '{}'", client))?;
+        if billed.project_ids.is_empty() {
+            return Err(format!("Client '{}' has no projects", billed.name).into());
+        }
+
+        let range = range.unwrap_or_default();
+        let entries = crate::billing::priced_entries(&app, &range)?;
+        let groups = group(
+            &app,
+            entries,
+            &billed.project_ids,
+            &settings.rounding,
+            rate.map(|rate| (rate * 100.0).round() as i64),
+            &settings.currency,
+        )?;
+        let (minutes, subtotal_cents, tax_cents) = totals(&groups, settings.tax_percent);
+        let entry_ids: Vec<i64> = groups
+            .iter()
+            .flat_map(|g| g.lines.iter().map(|line| line.entry_id))
+            .collect();
+
+        let dir = crate::export::choose_dir(&app, dir).await?;
+        let invoice_id = crate::entry_locks::record_invoice(&app, &billed.id, &range, &entry_ids)?;
+        let name = format!(
+            "timesheet-{}-{}-{}-{}",
+            slug(&billed.name),
+            range
+                .from_ms
+                .map(local_date)
+                .unwrap_or_else(|| "start".to_string()),
+            range
+                .to_ms
+                .map(local_date)
+                .unwrap_or_else(|| local_date(crate::events::now_ms() as i64)),
+            invoice_id
+        );
+        let sheet = Timesheet {
+            client_id: billed.id.clone(),
+            client_name: billed.name.clone(),
+            from_ms: range.from_ms,
+            to_ms: range.to_ms,
+            currency: settings.currency.clone(),
+            hourly_rate: rate,
+            groups,
+            minutes,
+            subtotal_cents,
+            tax_label: settings.tax_label.clone(),
+            tax_percent: settings.tax_percent,
+            tax_cents,
+            total_cents: subtotal_cents + tax_cents,
+            invoice_id,
+            csv_path: dir.join(format!("{}.csv", name)),
+            pdf_path: dir.join(format!("{}.pdf", name)),
+        };
+        let written = {
+            let sheet = sheet.clone();
+            tauri::async_runtime::spawn_blocking(move || -> Result<(), String> {
+                std::fs::create_dir_all(&dir)
+                    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+                write_csv(&sheet.csv_path, &sheet)?;
+                write_pdf(&sheet.pdf_path, &sheet, &settings)
+            })
+            .await
+            .map_err(|e| format!("Timesheet writer failed: {}", e))
+            .and_then(|written| written)
+        };
+        if let Err(e) = written {
+            // Nothing was billed under the number, so it is free again
+            if let Err(discard) = crate::entry_locks::discard_invoice(&app, &sheet.invoice_id) {
+                log::warn!(
+                    "Failed to discard invoice {}: {}",
+                    sheet.invoice_id,
+                    discard
+                );
+            }
+            return Err(e.into());
+        }
+        log::info!(
+            "Wrote timesheet {} for {} ({} entries)",
+            sheet.invoice_id,
+            sheet.client_name,
+            entry_ids.len()
+        );
+        Ok(sheet)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounding(increment_minutes: u32, mode: RoundingMode) -> Rounding {
+        Rounding {
+            increment_minutes,
+            mode,
+        }
+    }
+
+    fn group(lines: &[(i64, i64)]) -> TimesheetGroup {
+        let lines: Vec<TimesheetLine> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, &(minutes, rate_cents))| TimesheetLine {
+                entry_id: i as i64,
+                date: "2026-09-01".to_string(),
+                started_at_ms: 0,
+                duration_ms: minutes * 60_000,
+                minutes,
+                rate_cents,
+                amount_cents: cents(minutes, rate_cents),
+            })
+            .collect();
+        TimesheetGroup {
+            task_id: "task".to_string(),
+            title: "Task".to_string(),
+            minutes: lines.iter().map(|l| l.minutes).sum(),
+            amount_cents: lines.iter().map(|l| l.amount_cents).sum(),
+            lines,
+        }
+    }
+
+    #[test]
+    fn rounding_modes() {
+        // 22 minutes 30 seconds
+        let duration_ms = 22 * 60_000 + 30_000;
+        assert_eq!(rounding(15, RoundingMode::Up).minutes(duration_ms), 30);
+        assert_eq!(rounding(15, RoundingMode::Nearest).minutes(duration_ms), 30);
+        assert_eq!(rounding(15, RoundingMode::Nearest).minutes(20 * 60_000), 15);
+        assert_eq!(rounding(15, RoundingMode::Down).minutes(duration_ms), 15);
+        assert_eq!(rounding(6, RoundingMode::Up).minutes(duration_ms), 24);
+        assert_eq!(rounding(6, RoundingMode::Nearest).minutes(duration_ms), 24);
+        assert_eq!(rounding(6, RoundingMode::Down).minutes(duration_ms), 18);
+        // No increment: the nearest minute, whatever the mode
+        assert_eq!(rounding(0, RoundingMode::Up).minutes(duration_ms), 23);
+        assert_eq!(
+            rounding(0, RoundingMode::Down).minutes(22 * 60_000 + 29_999),
+            22
+        );
+    }
+
+    #[test]
+    fn rounding_edges() {
+        // Exact multiples stay put
+        assert_eq!(rounding(15, RoundingMode::Up).minutes(30 * 60_000), 30);
+        // Half an increment rounds up
+        assert_eq!(rounding(10, RoundingMode::Nearest).minutes(5 * 60_000), 10);
+        // A clock that went backwards bills nothing
+        assert_eq!(rounding(15, RoundingMode::Up).minutes(-60_000), 0);
+        assert_eq!(rounding(15, RoundingMode::Up).minutes(0), 0);
+    }
+
+    #[test]
+    fn line_amounts_round_to_cents() {
+        // 10 minutes at 100.00/h
+        assert_eq!(cents(10, 10_000), 1667);
+        // 7 minutes at 95.50/h is 11.141...
+        assert_eq!(cents(7, 9550), 1114);
+        assert_eq!(cents(0, 9550), 0);
+    }
+
+    #[test]
+    fn subtotal_is_the_sum_of_rounded_lines() {
+        // Three 10 minute lines at 100.00/h: 16.67 each, not 50.00 for 30
+        // minutes
+        let groups = [group(&[(10, 10_000), (10, 10_000)]), group(&[(10, 10_000)])];
+        let (minutes, subtotal_cents, tax_cents) = totals(&groups, 0.0);
+        assert_eq!(minutes, 30);
+        assert_eq!(subtotal_cents, 5001);
+        assert_eq!(tax_cents, 0);
+    }
+
+    #[test]
+    fn tax_rounds_to_cents() {
+        let groups = [group(&[(60, 12_345)])];
+        // 19% of 123.45 is 23.4555
+        assert_eq!(totals(&groups, 19.0), (60, 12_345, 2346));
+        // 7.5% of 123.45 is 9.25875
+        assert_eq!(totals(&groups, 7.5).2, 926);
+        assert_eq!(totals(&[], 19.0), (0, 0, 0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Per-request tracing across IPC, subprocesses and events.
+//!
+//! Commands run inside `scope`, which instruments them with a `request` span
+//! carrying a request id (and nested spans keep it for whatever they call). The
+//! log formatter tags every line logged inside that span with `[req:N]` and
+//! keeps it in an in-memory buffer, subprocesses spawned through `command` get
+//! the id as `FLOWSTATE_REQUEST_ID`, and bus events carry it as `requestId`, so
+//! a multi-step failure can be pulled back together with `get_trace`. `init`
+//! installs the span registry; the rest of the backend keeps logging through
+//! `log`.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_shell::process::Command;
+use tauri_plugin_shell::ShellExt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Instrument, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::error::FlowStateError;
+
+/// Log lines and requests kept for `get_trace`
+const MAX_LINES: usize = 2000;
+const MAX_REQUESTS: usize = 200;
+
+/// Name of the span `scope` opens for a command
+const REQUEST_SPAN: &str = "request";
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Request id stored in the extensions of a `request` span
+struct RequestId(u64);
+
+struct RequestIdVisitor(Option<u64>);
+
+impl Visit for RequestIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "id" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Keeps the id of `request` spans where `current_request_id` can find it
+struct RequestLayer;
+
+impl<S> Layer<S> for RequestLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != REQUEST_SPAN {
+            return;
+        }
+        let mut visitor = RequestIdVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(request_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(RequestId(request_id));
+        }
+    }
+}
+
+/// Install the span registry (once, before any command runs)
+pub fn init() {
+    let subscriber = Registry::default().with(RequestLayer);
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        log::warn!("Request tracing unavailable: {}", e);
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceLine {
+    pub request_id: u64,
+    pub timestamp_ms: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestSummary {
+    pub request_id: u64,
+    pub command: String,
+    pub started_ms: u64,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+struct TraceBuffer {
+    lines: VecDeque<TraceLine>,
+    requests: VecDeque<RequestSummary>,
+}
+
+static BUFFER: Mutex<TraceBuffer> = Mutex::new(TraceBuffer {
+    lines: VecDeque::new(),
+    requests: VecDeque::new(),
+});
+
+/// Request id of the command whose span is entered on this thread, if any
+pub fn current_request_id() -> Option<u64> {
+    tracing::dispatcher::get_default(|dispatch| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let current = registry.current_span();
+        let span = registry.span(current.id()?)?;
+        span.scope()
+            .find_map(|s| s.extensions().get::<RequestId>().map(|r| r.0))
+    })
+}
+
+/// Run a command body under a fresh request id (nested scopes reuse the outer id)
+pub async fn scope<T, F>(command: &'static str, fut: F) -> Result<T, FlowStateError>
+where
+    F: Future<Output = Result<T, FlowStateError>>,
+{
+    if current_request_id().is_some() {
+        return fut.await;
+    }
+
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+    let started_ms = crate::events::now_ms();
+    {
+        let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+        if buffer.requests.len() == MAX_REQUESTS {
+            buffer.requests.pop_front();
+        }
+        buffer.requests.push_back(RequestSummary {
+            request_id: id,
+            command: command.to_string(),
+            started_ms,
+            duration_ms: None,
+            error: None,
+        });
+    }
+
+    let span = tracing::info_span!(REQUEST_SPAN, id, command);
+    let result = async move {
+        log::info!("{} started", command);
+        let result = fut.await;
+        if let Err(e) = &result {
+            log::warn!("{} failed: {}", command, e);
+        }
+        result
+    }
+    .instrument(span)
+    .await;
+
+    let duration_ms = crate::events::now_ms().saturating_sub(started_ms);
+    let error = result.as_ref().err().map(|e| e.to_string());
+    crate::otel::record_span(
+        command,
+        started_ms,
+        duration_ms,
+        error.clone(),
+        vec![("flowstate.request_id", id.into())],
+    );
+    let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(summary) = buffer.requests.iter_mut().find(|r| r.request_id == id) {
+        summary.duration_ms = Some(duration_ms);
+        summary.error = error;
+    }
+
+    result
+}
+
+/// Shell command that carries the current request id into the subprocess
+/// (`supabase` runs the managed CLI when one is installed)
+pub fn command(app: &AppHandle, program: &str) -> Command {
+    let resolved = crate::supabase_cli::resolve(app, program);
+    let cmd = crate::docker_context::apply_env(program, app.shell().command(resolved));
+    let cmd = crate::project_dir::apply_cwd(program, cmd);
+    match current_request_id() {
+        Some(id) => {
+            log::info!("exec {}", program);
+            cmd.env("FLOWSTATE_REQUEST_ID", id.to_string())
+        }
+        None => cmd,
+    }
+}
+
+/// Log formatter: prefixes the request id and records the line for `get_trace`
+pub fn format_line(message: &std::fmt::Arguments, record: &log::Record) -> String {
+    let message = message.to_string();
+    let timestamp = chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]");
+
+    match current_request_id() {
+        Some(id) => {
+            let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+            if buffer.lines.len() == MAX_LINES {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back(TraceLine {
+                request_id: id,
+                timestamp_ms: crate::events::now_ms(),
+                level: record.level().to_string(),
+                target: record.target().to_string(),
+                message: message.clone(),
+            });
+
+            format!(
+                "{}[{}][{}][req:{}] {}",
+                timestamp,
+                record.target(),
+                record.level(),
+                id,
+                message
+            )
+        }
+        None => format!(
+            "{}[{}][{}] {}",
+            timestamp,
+            record.target(),
+            record.level(),
+            message
+        ),
+    }
+}
+
+/// Log lines correlated with a request id
+#[tauri::command]
+pub fn get_trace(request_id: u64) -> Vec<TraceLine> {
+    let buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    buffer
+        .lines
+        .iter()
+        .filter(|l| l.request_id == request_id)
+        .cloned()
+        .collect()
+}
+
+/// Most recent traced requests, newest first
+#[tauri::command]
+pub fn get_recent_requests() -> Vec<RequestSummary> {
+    let buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+    buffer.requests.iter().rev().cloned().collect()
+}
@@ -0,0 +1,384 @@
+//! Tray / menu bar icon and the focus pill countdown, drawn at runtime.
+//!
+//! Both images are rendered with tiny-skia from the focus session state: a
+//! ring showing the phase progress with a badge of the remaining minutes for
+//! the tray, and a rounded pill with the `mm:ss` countdown for the overlay.
+//! They are drawn at the pixel size of the monitor they are shown on (the
+//! tray icon's monitor, the overlay window's scale factor), so no pre-baked
+//! PNGs per DPI are shipped. The focus engine calls `refresh` on every tick;
+//! the icon is only replaced when what it shows (or its pixel size) changes.
+//!
+//! The icon's menu is the way back to the main window where clicks on the
+//! icon aren't reported (Linux), e.g. after launching at login to the tray.
+
+use std::sync::Mutex;
+
+use base64::Engine;
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuEvent, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, WebviewWindow, Wry};
+use tiny_skia::{FillRule, LineCap, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use crate::error::FlowStateError;
+use crate::focus::{FocusEngine, FocusPhase, FocusSessionState, FocusStatus};
+
+pub(crate) const TRAY_ID: &str = "main";
+const MENU_SHOW: &str = "show";
+const MENU_QUIT: &str = "quit";
+/// Logical size of the tray icon; Windows draws 16px icons, menu bars 22pt
+const TRAY_SIZE: f64 = if cfg!(windows) { 16.0 } else { 22.0 };
+/// Logical height of the focus pill
+const PILL_HEIGHT: f64 = 24.0;
+/// Steps of the progress ring, so it isn't redrawn every second
+const PROGRESS_STEPS: u64 = 48;
+
+/// 3×5 digit glyphs, one row per entry, most significant bit on the left
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// What the tray icon currently shows
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct TrayFace {
+    status: FocusStatus,
+    phase: FocusPhase,
+    minutes: u64,
+    progress: u64,
+    size: u32,
+}
+
+#[derive(Default)]
+pub struct TrayIcons {
+    shown: Mutex<Option<TrayFace>>,
+}
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+/// Accent colour of the current phase
+fn accent(status: FocusStatus, phase: FocusPhase) -> Paint<'static> {
+    match (status, phase) {
+        (FocusStatus::Idle, _) => rgba(0x8b, 0x8d, 0x98, 0xff),
+        (FocusStatus::Paused, _) => rgba(0xf5, 0xa5, 0x24, 0xff),
+        (FocusStatus::Running, FocusPhase::Focus) => rgba(0xe5, 0x48, 0x4d, 0xff),
+        (FocusStatus::Running, _) => rgba(0x30, 0xa4, 0x6c, 0xff),
+    }
+}
+
+fn minutes_left(state: &FocusSessionState) -> u64 {
+    state.remaining_ms.div_ceil(60_000)
+}
+
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Option<tiny_skia::Path> {
+    let r = r.min(w / 2.0).min(h / 2.0);
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + r, y);
+    pb.line_to(x + w - r, y);
+    pb.quad_to(x + w, y, x + w, y + r);
+    pb.line_to(x + w, y + h - r);
+    pb.quad_to(x + w, y + h, x + w - r, y + h);
+    pb.line_to(x + r, y + h);
+    pb.quad_to(x, y + h, x, y + h - r);
+    pb.line_to(x, y + r);
+    pb.quad_to(x, y, x + r, y);
+    pb.close();
+    pb.finish()
+}
+
+/// Width in cells of `text` drawn by `draw_text`
+fn text_cells(text: &str) -> u32 {
+    let cells: u32 = text.chars().map(|c| if c == ':' { 2 } else { 4 }).sum();
+    cells.saturating_sub(1)
+}
+
+/// Draw digits and colons with the 3×5 glyphs, `cell` pixels per glyph pixel
+fn draw_text(pixmap: &mut Pixmap, text: &str, x: f32, y: f32, cell: f32, paint: &Paint) {
+    let mut left = x;
+    for c in text.chars() {
+        if c == ':' {
+            for row in [1.0, 3.0] {
+                if let Some(dot) = Rect::from_xywh(left, y + row * cell, cell, cell) {
+                    pixmap.fill_rect(dot, paint, Transform::identity(), None);
+                }
+            }
+            left += 2.0 * cell;
+            continue;
+        }
+        let Some(glyph) = c.to_digit(10).map(|d| DIGITS[d as usize]) else {
+            continue;
+        };
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                let rect = Rect::from_xywh(
+                    left + column as f32 * cell,
+                    y + row as f32 * cell,
+                    cell,
+                    cell,
+                );
+                if let Some(rect) = rect {
+                    pixmap.fill_rect(rect, paint, Transform::identity(), None);
+                }
+            }
+        }
+        left += 4.0 * cell;
+    }
+}
+
+/// The tray icon: progress ring plus a badge with the minutes left
+fn render_tray(face: &TrayFace) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(face.size, face.size)?;
+    let size = face.size as f32;
+    let width = (size * 0.14).max(1.5);
+    let center = size / 2.0;
+    let radius = center - width / 2.0 - 0.5;
+    let stroke = Stroke {
+        width,
+        line_cap: LineCap::Round,
+        ..Default::default()
+    };
+    let paint = accent(face.status, face.phase);
+
+    let track = PathBuilder::from_circle(center, center, radius)?;
+    let track_paint = if face.status == FocusStatus::Idle {
+        paint.clone()
+    } else {
+        rgba(0x8b, 0x8d, 0x98, 0x80)
+    };
+    pixmap.stroke_path(&track, &track_paint, &stroke, Transform::identity(), None);
+
+    if face.status != FocusStatus::Idle && face.progress > 0 {
+        // Remaining time, clockwise from 12 o'clock
+        let sweep = std::f32::consts::TAU * face.progress as f32 / PROGRESS_STEPS as f32;
+        let segments = (face.progress * 2).max(2);
+        let mut pb = PathBuilder::new();
+        for i in 0..=segments {
+            let angle = -std::f32::consts::FRAC_PI_2 + sweep * i as f32 / segments as f32;
+            let (x, y) = (center + radius * angle.cos(), center + radius * angle.sin());
+            if i == 0 {
+                pb.move_to(x, y);
+            } else {
+                pb.line_to(x, y);
+            }
+        }
+        if let Some(arc) = pb.finish() {
+            pixmap.stroke_path(&arc, &paint, &stroke, Transform::identity(), None);
+        }
+    }
+
+    if face.status != FocusStatus::Idle {
+        let text = face.minutes.min(99).to_string();
+        let cell = (size / 16.0).floor().max(1.0);
+        let badge_w = (text_cells(&text) + 2) as f32 * cell;
+        let badge_h = 7.0 * cell;
+        let (x, y) = (size - badge_w, size - badge_h);
+        let badge = rounded_rect(x, y, badge_w, badge_h, cell * 1.5)?;
+        pixmap.fill_path(
+            &badge,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+        let white = rgba(0xff, 0xff, 0xff, 0xff);
+        draw_text(&mut pixmap, &text, x + cell, y + cell, cell, &white);
+    }
+    Some(pixmap)
+}
+
+/// The overlay pill: `mm:ss` on the phase colour, `scale` pixels per point
+fn render_pill(state: &FocusSessionState, scale: f64) -> Option<Pixmap> {
+    let seconds = state.remaining_ms.div_ceil(1000);
+    let text = format!("{:02}:{:02}", (seconds / 60).min(99), seconds % 60);
+    let height = (PILL_HEIGHT * scale).round().max(8.0) as u32;
+    let cell = (height / 8).max(1);
+    let width = text_cells(&text) * cell + height;
+
+    let mut pixmap = Pixmap::new(width, height)?;
+    let (w, h, cell) = (width as f32, height as f32, cell as f32);
+    let pill = rounded_rect(0.0, 0.0, w, h, h / 2.0)?;
+    let paint = accent(state.status, state.phase);
+    pixmap.fill_path(
+        &pill,
+        &paint,
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+    let white = rgba(0xff, 0xff, 0xff, 0xff);
+    draw_text(
+        &mut pixmap,
+        &text,
+        h / 2.0,
+        (h - 5.0 * cell) / 2.0,
+        cell,
+        &white,
+    );
+    Some(pixmap)
+}
+
+/// Scale factor of the monitor the tray icon is on
+fn tray_scale(app: &AppHandle, tray: &tauri::tray::TrayIcon) -> f64 {
+    let on_tray = tray.rect().ok().flatten().and_then(|rect| {
+        let position = rect.position.to_physical::<f64>(1.0);
+        app.monitor_from_point(position.x, position.y)
+            .ok()
+            .flatten()
+    });
+    on_tray
+        .or_else(|| app.primary_monitor().ok().flatten())
+        .map(|monitor| monitor.scale_factor())
+        .unwrap_or(1.0)
+}
+
+fn face(state: &FocusSessionState, size: u32) -> TrayFace {
+    let progress = if state.phase_duration_ms == 0 {
+        0
+    } else {
+        (state.remaining_ms * PROGRESS_STEPS).div_ceil(state.phase_duration_ms)
+    };
+    TrayFace {
+        status: state.status,
+        phase: state.phase,
+        minutes: minutes_left(state),
+        progress: progress.min(PROGRESS_STEPS),
+        size,
+    }
+}
+
+fn tooltip(state: &FocusSessionState) -> String {
+    let phase = match state.phase {
+        FocusPhase::Focus => "Focus",
+        FocusPhase::ShortBreak => "Short break",
+        FocusPhase::LongBreak => "Long break",
+    };
+    match state.status {
+        FocusStatus::Idle => "FlowState".to_string(),
+        FocusStatus::Running => format!("FlowState · {}: {} min left", phase, minutes_left(state)),
+        FocusStatus::Paused => format!("FlowState · {} paused", phase),
+    }
+}
+
+/// Redraw the tray icon for `state` if it shows something else or the
+/// monitor's pixel density changed
+pub fn refresh(app: &AppHandle, state: &FocusSessionState) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let size = (TRAY_SIZE * tray_scale(app, &tray)).round() as u32;
+    let face = face(state, size);
+    {
+        let icons = app.state::<TrayIcons>();
+        let mut shown = icons.shown.lock().unwrap_or_else(|e| e.into_inner());
+        if *shown == Some(face) {
+            return;
+        }
+        *shown = Some(face);
+    }
+
+    let Some(pixmap) = render_tray(&face) else {
+        log::warn!("Failed to render {}px tray icon", size);
+        return;
+    };
+    // Tauri images are straight RGBA; tiny-skia's pixels are premultiplied
+    let rgba: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let image = Image::new_owned(rgba, face.size, face.size);
+    if let Err(e) = tray.set_icon(Some(image)) {
+        log::warn!("Failed to update tray icon: {}", e);
+    }
+    let _ = tray.set_tooltip(Some(tooltip(state)));
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Err(e) = crate::launch::focus_main_window(app) {
+        log::warn!("Failed to show main window: {}", e);
+    }
+}
+
+fn menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let show = MenuItem::with_id(app, MENU_SHOW, "Show FlowState", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, MENU_QUIT, "Quit", true, None::<&str>)?;
+    Menu::with_items(app, &[&show, &quit])
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        MENU_SHOW => show_main_window(app),
+        MENU_QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Create the tray icon; clicking it (or "Show FlowState" in its menu)
+/// brings the main window forward
+pub fn init(app: &AppHandle) {
+    let menu = match menu(app) {
+        Ok(menu) => menu,
+        Err(e) => {
+            log::error!("Failed to create tray menu: {}", e);
+            return;
+        }
+    };
+    let built = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("FlowState")
+        .menu(&menu)
+        // Left click shows the window where it is reported; the menu is on right click
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        })
+        .build(app);
+    if let Err(e) = built {
+        log::error!("Failed to create tray icon: {}", e);
+        return;
+    }
+    refresh(app, &app.state::<FocusEngine>().snapshot());
+}
+
+/// The focus pill countdown as a PNG data URL, at `window`'s pixel density
+#[tauri::command]
+pub fn render_focus_pill(window: WebviewWindow) -> Result<String, FlowStateError> {
+    let scale = window
+        .scale_factor()
+        .map_err(|e| format!("No scale factor: {}", e))?;
+    let state = window.app_handle().state::<FocusEngine>().snapshot();
+    let png = render_pill(&state, scale)
+        .ok_or("Failed to render focus pill")?
+        .encode_png()
+        .map_err(|e| format!("Failed to encode focus pill: {}", e))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
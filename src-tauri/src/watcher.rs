@@ -0,0 +1,141 @@
+//! Background watcher for Docker and Supabase status.
+//!
+//! Instead of the UI polling `check_docker_status`/`check_supabase_status`, one
+//! task started in `setup()` probes both on an interval and publishes
+//! `service://docker-status` / `service://supabase-status` only when a status
+//! changes. `subscribe_service_status` stops, restarts or re-times it.
+//!
+//! Polls only use the Engine API socket and the REST gateway; the `docker`
+//! and `supabase` CLIs are left to the on-demand checks, except for one
+//! `supabase status` when the stack comes up, to read its connection details.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::status::ServiceStatus;
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct ServiceWatcher {
+    task: Mutex<Option<(JoinHandle<()>, Duration)>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherState {
+    pub running: bool,
+    pub interval_ms: Option<u64>,
+}
+
+/// Start (or restart) the watcher with the given interval
+pub fn start(app: &AppHandle, interval: Duration) {
+    let interval = interval.max(MIN_INTERVAL);
+    let handle = tauri::async_runtime::spawn(watch(app.clone(), interval));
+
+    let watcher = app.state::<ServiceWatcher>();
+    let mut task = watcher.task.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((previous, _)) = task.replace((handle, interval)) {
+        previous.abort();
+    }
+    log::info!("Service watcher polling every {:?}", interval);
+}
+
+fn stop(app: &AppHandle) {
+    let watcher = app.state::<ServiceWatcher>();
+    let mut task = watcher.task.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((handle, _)) = task.take() {
+        handle.abort();
+        log::info!("Service watcher stopped");
+    }
+}
+
+async fn watch(app: AppHandle, interval: Duration) {
+    let mut docker: Option<ServiceStatus> = None;
+    let mut supabase: Option<ServiceStatus> = None;
+
+    loop {
+        let status = crate::probe_docker_socket()
+            .await
+            .unwrap_or_else(ServiceStatus::not_running);
+        publish_if_changed(&app, "service://docker-status", &mut docker, status.clone());
+        app.state::<crate::snapshot::StateSnapshot>()
+            .set_docker_status(status);
+
+        match supabase_status(&app, supabase.as_ref()).await {
+            Ok(status) => {
+                publish_if_changed(
+                    &app,
+                    "service://supabase-status",
+                    &mut supabase,
+                    status.clone(),
+                );
+                crate::supervisor::observe_supabase(&app, &status);
+                app.state::<crate::snapshot::StateSnapshot>()
+                    .set_supabase_status(status);
+            }
+            Err(e) => log::warn!("Service watcher: {}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Supabase status from the REST gateway; the last status is kept while the
+/// gateway's answer agrees with it
+async fn supabase_status(
+    app: &AppHandle,
+    last: Option<&ServiceStatus>,
+) -> Result<ServiceStatus, String> {
+    let endpoints = crate::endpoints::get(app).await;
+    let up = crate::health::check_rest_api(&endpoints).await.status() == Some(200);
+    match last {
+        Some(last) if last.is_running() == up => Ok(last.clone()),
+        // Came up: read the connection details once
+        _ if up => crate::probe_supabase_status(app).await,
+        _ => Ok(ServiceStatus::not_running()),
+    }
+}
+
+fn publish_if_changed(
+    app: &AppHandle,
+    topic: &str,
+    last: &mut Option<ServiceStatus>,
+    status: ServiceStatus,
+) {
+    if last.as_ref() != Some(&status) {
+        crate::events::publish(app, topic, &status);
+        *last = Some(status);
+    }
+}
+
+/// Start or stop the background status watcher (interval defaults to 10 s)
+#[tauri::command]
+pub fn subscribe_service_status(
+    app: AppHandle,
+    enabled: bool,
+    interval_ms: Option<u64>,
+) -> WatcherState {
+    if enabled {
+        let interval = interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INTERVAL);
+        start(&app, interval);
+    } else {
+        stop(&app);
+    }
+
+    let watcher = app.state::<ServiceWatcher>();
+    let task = watcher.task.lock().unwrap_or_else(|e| e.into_inner());
+    WatcherState {
+        running: task.is_some(),
+        interval_ms: task
+            .as_ref()
+            .map(|(_, interval)| interval.as_millis() as u64),
+    }
+}
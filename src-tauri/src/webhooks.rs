@@ -0,0 +1,499 @@
+//! Inbound webhooks on the local API (`POST /webhooks/<id>`, `local_api.rs`).
+//!
+//! Each webhook stands for one source (a form service, a CI job, an n8n or
+//! Zapier flow) and has a token of its own, so a source can be cut off
+//! without touching the others or the local API token. Deliveries send the
+//! token as `Authorization: Bearer <token>` or as the Basic password, with a
+//! JSON or form-encoded body. The webhook's template maps the payload onto
+//! a new inbox task: every template field is text in which `{{path}}` is
+//! replaced by the payload value at that dotted path (`{{repo.name}}`,
+//! `{{commits.0.id}}`). Each webhook takes at most `rate_per_minute`
+//! deliveries in any minute; more get `429` with a `Retry-After`. Webhooks
+//! live in `webhooks.json`, and each task made publishes `webhook://task`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::error::FlowStateError;
+use crate::local_api::{Request, Response};
+
+pub(crate) const WEBHOOK_STORE: &str = "webhooks.json";
+const WEBHOOK_KEY: &str = "webhooks";
+const PATH_PREFIX: &str = "/webhooks/";
+const RATE_WINDOW_MS: u64 = 60 * 1000;
+const DEFAULT_RATE_PER_MINUTE: u32 = 30;
+const MAX_RATE_PER_MINUTE: u32 = 600;
+/// Characters of the rendered title kept
+const TITLE_CHARS: usize = 200;
+const TASK_TAG: &str = "webhook";
+
+/// How a payload becomes a task; each field is a `{{path}}` template
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TaskTemplate {
+    pub title: String,
+    pub description: Option<String>,
+    /// Must render to high, medium or low
+    pub priority: Option<String>,
+    /// Must render to an RFC 3339 time or a YYYY-MM-DD date
+    pub due_date: Option<String>,
+    /// Each renders to a comma-separated list
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub name: String,
+    pub token: String,
+    pub template: TaskTemplate,
+    pub rate_per_minute: u32,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookTaskCreated {
+    pub webhook_id: String,
+    pub task_id: String,
+}
+
+/// Recent delivery times per webhook, oldest first
+#[derive(Default)]
+pub struct Webhooks {
+    deliveries: Mutex<HashMap<String, VecDeque<u64>>>,
+}
+
+fn load(app: &AppHandle) -> Vec<Webhook> {
+    app.store(WEBHOOK_STORE)
+        .ok()
+        .and_then(|store| store.get(WEBHOOK_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn save(app: &AppHandle, webhooks: &[Webhook]) -> Result<(), String> {
+    let store = app
+        .store(WEBHOOK_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", WEBHOOK_STORE, e))?;
+    store.set(
+        WEBHOOK_KEY,
+        serde_json::to_value(webhooks).map_err(|e| e.to_string())?,
+    );
+    store
+        .save()
+        .map_err(|e| format!("Failed to save {}: {}", WEBHOOK_STORE, e))
+}
+
+/// Webhook id of a delivery path
+pub(crate) fn id(path: &str) -> Option<&str> {
+    path.strip_prefix(PATH_PREFIX)
+        .map(|id| id.trim_end_matches('/'))
+}
+
+/// Count a delivery at `now_ms` if fewer than `limit` came in the last
+/// minute; otherwise the milliseconds until one is allowed
+fn admit(deliveries: &mut VecDeque<u64>, now_ms: u64, limit: u32) -> Result<(), u64> {
+    while deliveries
+        .front()
+        .is_some_and(|at| now_ms.saturating_sub(*at) >= RATE_WINDOW_MS)
+    {
+        deliveries.pop_front();
+    }
+    if deliveries.len() >= limit as usize {
+        let oldest = deliveries.front().copied().unwrap_or(now_ms);
+        return Err(oldest + RATE_WINDOW_MS - now_ms);
+    }
+    deliveries.push_back(now_ms);
+    Ok(())
+}
+
+/// Form fields as a flat object of strings
+fn form_fields(body: &[u8]) -> Value {
+    let decode = |text: &str| crate::local_api::decode_percent(&text.replace('+', " "));
+    let fields: Map<String, Value> = String::from_utf8_lossy(body)
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), Value::String(decode(value)))
+        })
+        .collect();
+    Value::Object(fields)
+}
+
+fn payload(request: &Request) -> Result<Value, Response> {
+    let content_type = request
+        .header("Content-Type")
+        .unwrap_or("application/json")
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match content_type.as_str() {
+        "application/x-www-form-urlencoded" => Ok(form_fields(&request.body)),
+        "application/json" => serde_json::from_slice(&request.body)
+            .map_err(|e| Response::text(400, &format!("Invalid JSON: {}", e))),
+        _ => Err(Response::text(415, "Send JSON or a form")),
+    }
+}
+
+fn lookup<'a>(payload: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(payload, |value, key| match value {
+        Value::Object(fields) => fields.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// `template` with each `{{path}}` replaced by the payload value there;
+/// missing values render empty, objects and arrays as JSON
+fn render(template: &str, payload: &Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match lookup(payload, rest[start + 2..start + end].trim()) {
+            Some(Value::String(text)) => out.push_str(text),
+            Some(Value::Null) | None => {}
+            Some(value) => out.push_str(&value.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Rendered optional field; None when it renders empty
+fn render_optional(template: Option<&String>, payload: &Value) -> Option<String> {
+    template
+        .map(|template| render(template, payload).trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Task fields for a delivery, checked
+fn task_fields(template: &TaskTemplate, payload: &Value) -> Result<Map<String, Value>, String> {
+    let rendered = render(&template.title, payload);
+    let title: String = rendered
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .ok_or_else(|| "The title template rendered empty".to_string())?
+        .chars()
+        .take(TITLE_CHARS)
+        .collect();
+
+    let mut task = Map::new();
+    task.insert("title".to_string(), Value::from(title));
+    task.insert("status".to_string(), Value::from("planned"));
+    task.insert("is_in_inbox".to_string(), Value::from(true));
+    if let Some(description) = render_optional(template.description.as_ref(), payload) {
+        task.insert("description".to_string(), Value::from(description));
+    }
+    if let Some(priority) = render_optional(template.priority.as_ref(), payload) {
+        let priority = priority.to_lowercase();
+        if !crate::graphql::PRIORITIES.contains(&priority.as_str()) {
+            return Err(format!(
+                "Priority must be one of {}, not {}",
+                crate::graphql::PRIORITIES.join(", "),
+                priority
+            ));
+        }
+        task.insert("priority".to_string(), Value::from(priority));
+    }
+    if let Some(due) = render_optional(template.due_date.as_ref(), payload) {
+        crate::graphql::check_due_date(&due)?;
+        task.insert("due_date".to_string(), Value::from(due));
+    }
+    let mut tags = vec![TASK_TAG.to_string()];
+    for tag in template
+        .tags
+        .iter()
+        .flat_map(|template| {
+            render(template, payload)
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .filter(|tag| !tag.is_empty())
+    {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    task.insert("tags".to_string(), Value::from(tags));
+    Ok(task)
+}
+
+/// Answer a delivery to webhook `id`
+pub(crate) fn handle(app: &AppHandle, id: &str, request: &Request) -> Response {
+    // Unknown webhooks look like wrong tokens
+    let Some(webhook) = load(app)
+        .into_iter()
+        .find(|webhook| webhook.id == id)
+        .filter(|webhook| crate::local_api::authorized(request, &webhook.token))
+    else {
+        return Response::text(401, "Missing or wrong webhook token");
+    };
+    if request.method != "POST" {
+        return Response::text(405, "Deliver webhooks with POST").header("Allow", "POST");
+    }
+    if app.state::<crate::app_lock::AppLock>().is_locked() {
+        return Response::text(423, "FlowState is locked");
+    }
+    let admitted = {
+        let state = app.state::<Webhooks>();
+        let mut deliveries = state.deliveries.lock().unwrap_or_else(|e| e.into_inner());
+        admit(
+            deliveries.entry(webhook.id.clone()).or_default(),
+            crate::clock::now_ms(),
+            webhook.rate_per_minute,
+        )
+    };
+    if let Err(wait_ms) = admitted {
+        return Response::text(429, "Too many deliveries; try again later")
+            .header("Retry-After", wait_ms.div_ceil(1000).to_string());
+    }
+    if crate::read_only::ensure_writable(app, "webhook").is_err() {
+        return Response::text(403, "FlowState is in read-only mode");
+    }
+
+    let payload = match payload(request) {
+        Ok(payload) => payload,
+        Err(response) => return response,
+    };
+    let fields = match task_fields(&webhook.template, &payload) {
+        Ok(fields) => fields,
+        Err(e) => return Response::text(422, &e),
+    };
+    let mut task = crate::offline::owned_task(app);
+    task.extend(fields);
+    let saved = match crate::offline::save_task(app, task) {
+        Ok(saved) => saved,
+        Err(e) => {
+            log::warn!("Webhook {}: task not saved: {}", webhook.name, e);
+            return Response::text(500, "The task could not be saved");
+        }
+    };
+    let task_id = saved
+        .get("id")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    log::info!("Webhook {} created task {}", webhook.name, task_id);
+    crate::events::publish(
+        app,
+        "webhook://task",
+        &WebhookTaskCreated {
+            webhook_id: webhook.id,
+            task_id: task_id.clone(),
+        },
+    );
+    Response::new(201).body(
+        "application/json",
+        serde_json::json!({ "id": task_id }).to_string(),
+    )
+}
+
+fn check_rate(rate_per_minute: u32) -> Result<u32, String> {
+    if !(1..=MAX_RATE_PER_MINUTE).contains(&rate_per_minute) {
+        return Err(format!(
+            "Rate must be 1 to {} deliveries a minute",
+            MAX_RATE_PER_MINUTE
+        ));
+    }
+    Ok(rate_per_minute)
+}
+
+/// Webhooks with their tokens
+#[tauri::command]
+pub fn list_webhooks(app: AppHandle) -> Result<Vec<Webhook>, FlowStateError> {
+    crate::app_lock::ensure_unlocked(&app, "list_webhooks")?;
+    Ok(load(&app))
+}
+
+/// Add a webhook (without `id`) or change one; new webhooks get a token
+#[tauri::command]
+pub fn save_webhook(
+    app: AppHandle,
+    id: Option<String>,
+    name: String,
+    template: TaskTemplate,
+    rate_per_minute: Option<u32>,
+) -> Result<Webhook, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "save_webhook")?;
+    crate::app_lock::ensure_unlocked(&app, "save_webhook")?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Name the webhook".to_string().into());
+    }
+    if template.title.trim().is_empty() {
+        return Err("The task title template can't be empty".to_string().into());
+    }
+    let rate = rate_per_minute.map(check_rate).transpose()?;
+
+    let mut webhooks = load(&app);
+    let webhook = match id {
+        Some(id) => {
+            let Some(webhook) = webhooks.iter_mut().find(|webhook| webhook.id == id) else {
+                return Err(format!("Unknown webhook: {}", id).into());
+            };
+            webhook.name = name;
+            webhook.template = template;
+            if let Some(rate) = rate {
+                webhook.rate_per_minute = rate;
+            }
+            webhook.clone()
+        }
+        None => {
+            let bytes: [u8; 8] = rand::random();
+            let webhook = Webhook {
+                id: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+                name,
+                token: crate::local_api::new_token(),
+                template,
+                rate_per_minute: rate.unwrap_or(DEFAULT_RATE_PER_MINUTE),
+            };
+            webhooks.push(webhook.clone());
+            webhook
+        }
+    };
+    save(&app, &webhooks)?;
+    Ok(webhook)
+}
+
+/// Replace a webhook's token; its source needs the new one
+#[tauri::command]
+pub fn reset_webhook_token(app: AppHandle, id: String) -> Result<Webhook, FlowStateError> {
+    crate::read_only::ensure_writable(&app, "reset_webhook_token")?;
+    crate::app_lock::ensure_unlocked(&app, "reset_webhook_token")?;
+    let mut webhooks = load(&app);
+    let Some(webhook) = webhooks.iter_mut().find(|webhook| webhook.id == id) else {
+        return Err(format!("Unknown webhook: {}", id).into());
+    };
+    webhook.token = crate::local_api::new_token();
+    let webhook = webhook.clone();
+    save(&app, &webhooks)?;
+    Ok(webhook)
+}
+
+#[tauri::command]
+pub fn remove_webhook(app: AppHandle, id: String) -> Result<(), FlowStateError> {
+    crate::read_only::ensure_writable(&app, "remove_webhook")?;
+    crate::app_lock::ensure_unlocked(&app, "remove_webhook")?;
+    let mut webhooks = load(&app);
+    let before = webhooks.len();
+    webhooks.retain(|webhook| webhook.id != id);
+    if webhooks.len() == before {
+        return Err(format!("Unknown webhook: {}", id).into());
+    }
+    save(&app, &webhooks)?;
+    app.state::<Webhooks>()
+        .deliveries
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn template(title: &str) -> TaskTemplate {
+        TaskTemplate {
+            title: title.to_string(),
+            ..TaskTemplate::default()
+        }
+    }
+
+    #[test]
+    fn renders_payload_paths() {
+        let payload = json!({
+            "repo": { "name": "flow-state" },
+            "run": { "number": 42, "ok": false, "url": null },
+            "commits": [{ "id": "abc" }],
+        });
+        assert_eq!(
+            render("{{repo.name}} #{{ run.number }} ({{run.ok}})", &payload),
+            "flow-state #42 (false)"
+        );
+        assert_eq!(render("{{commits.0.id}}", &payload), "abc");
+        assert_eq!(render("[{{run.url}}{{missing.path}}]", &payload), "[]");
+        assert_eq!(render("{{repo}}", &payload), r#"{"name":"flow-state"}"#);
+        assert_eq!(render("open {{repo.name", &payload), "open {{repo.name");
+    }
+
+    #[test]
+    fn maps_a_payload_onto_a_task() {
+        let template = TaskTemplate {
+            title: "CI failed: {{repo}}\n{{log}}".to_string(),
+            description: Some("{{url}}".to_string()),
+            priority: Some("{{level}}".to_string()),
+            due_date: Some("{{due}}".to_string()),
+            tags: vec!["ci, {{repo}}".to_string(), "webhook".to_string()],
+        };
+        let payload = json!({ "repo": "app", "log": "error", "level": "HIGH", "due": "" });
+        let task = task_fields(&template, &payload).unwrap();
+        assert_eq!(task["title"], "CI failed: app");
+        assert_eq!(task["priority"], "high");
+        assert_eq!(task["is_in_inbox"], true);
+        assert_eq!(task["tags"], json!(["webhook", "ci", "app"]));
+        // Empty renders are left out
+        assert!(!task.contains_key("description"));
+        assert!(!task.contains_key("due_date"));
+    }
+
+    #[test]
+    fn refuses_payloads_that_map_badly() {
+        assert!(task_fields(&template("{{missing}}"), &json!({})).is_err());
+        let priority = TaskTemplate {
+            priority: Some("{{p}}".to_string()),
+            ..template("Task")
+        };
+        assert!(task_fields(&priority, &json!({ "p": "urgent" })).is_err());
+        let due = TaskTemplate {
+            due_date: Some("{{d}}".to_string()),
+            ..template("Task")
+        };
+        assert!(task_fields(&due, &json!({ "d": "tomorrow" })).is_err());
+        assert!(task_fields(&due, &json!({ "d": "2026-11-01" })).is_ok());
+    }
+
+    #[test]
+    fn reads_form_submissions() {
+        assert_eq!(
+            form_fields(b"name=Ada+Lovelace&msg=hi%21&empty="),
+            json!({ "name": "Ada Lovelace", "msg": "hi!", "empty": "" })
+        );
+    }
+
+    #[test]
+    fn limits_deliveries_per_minute() {
+        let mut deliveries = VecDeque::new();
+        assert_eq!(admit(&mut deliveries, 1_000, 2), Ok(()));
+        assert_eq!(admit(&mut deliveries, 20_000, 2), Ok(()));
+        assert_eq!(admit(&mut deliveries, 30_000, 2), Err(31_000));
+        // The first delivery has left the window
+        assert_eq!(admit(&mut deliveries, 61_000, 2), Ok(()));
+        assert_eq!(admit(&mut deliveries, 61_500, 2), Err(18_500));
+        assert_eq!(deliveries.len(), 2);
+    }
+
+    #[test]
+    fn finds_the_webhook_in_the_path() {
+        assert_eq!(id("/webhooks/ab12"), Some("ab12"));
+        assert_eq!(id("/webhooks/ab12/"), Some("ab12"));
+        assert_eq!(id("/caldav/"), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! Main window size, position and maximized state across launches.
+//!
+//! Move and resize events record the window's bounds (in physical pixels)
+//! and the monitor it is on, saved to `window-state.json` once the window has
+//! been still for a moment. At startup `restore` applies them while the
+//! window is still hidden, so it never flashes at the default size: if the
+//! saved monitor is gone or the title bar would end up off screen, the size
+//! is kept (shrunk to fit) and the window is centered on the primary monitor.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, Window, WindowEvent};
+use tauri_plugin_store::StoreExt;
+
+pub(crate) const WINDOW_STORE: &str = "window-state.json";
+const MAIN_WINDOW: &str = "main";
+/// Quiet time after the last move or resize before saving
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+/// Height of the strip at the top of the window that must stay on screen
+const TITLE_BAR: i32 = 32;
+/// Width of that strip that must stay on screen
+const MIN_VISIBLE_WIDTH: i32 = 120;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedWindow {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+    monitor: Option<String>,
+}
+
+/// A monitor's name and bounds, in physical pixels
+#[derive(Clone, Debug, PartialEq)]
+struct Screen {
+    name: Option<String>,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl From<&Monitor> for Screen {
+    fn from(monitor: &Monitor) -> Self {
+        Screen {
+            name: monitor.name().cloned(),
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WindowState {
+    /// Bounds last seen while not maximized or minimized
+    current: Mutex<Option<SavedWindow>>,
+    /// Events before `restore` are the default placement, not the user's
+    restored: AtomicBool,
+    generation: AtomicU64,
+}
+
+fn load(app: &AppHandle) -> Option<SavedWindow> {
+    let store = app.store(WINDOW_STORE).ok()?;
+    serde_json::from_value(store.get(MAIN_WINDOW)?).ok()
+}
+
+fn save(app: &AppHandle) {
+    let Some(saved) = app
+        .state::<WindowState>()
+        .current
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+    else {
+        return;
+    };
+    let result = app
+        .store(WINDOW_STORE)
+        .map_err(|e| format!("Failed to open {}: {}", WINDOW_STORE, e))
+        .and_then(|store| {
+            store.set(MAIN_WINDOW, serde_json::json!(saved));
+            store
+                .save()
+                .map_err(|e| format!("Failed to save {}: {}", WINDOW_STORE, e))
+        });
+    if let Err(e) = result {
+        log::warn!("{}", e);
+    }
+}
+
+/// Length of the overlap of [a, a + a_len) and [b, b + b_len)
+fn overlap(a: i32, a_len: i32, b: i32, b_len: i32) -> i32 {
+    ((a + a_len).min(b + b_len) - a.max(b)).max(0)
+}
+
+/// Enough of the window's title bar is on `screen` to drag it
+fn title_bar_visible(saved: &SavedWindow, screen: &Screen) -> bool {
+    let width = saved.width.min(i32::MAX as u32) as i32;
+    overlap(saved.x, width, screen.x, screen.width as i32) >= MIN_VISIBLE_WIDTH.min(width)
+        && overlap(saved.y, TITLE_BAR, screen.y, screen.height as i32) >= TITLE_BAR
+}
+
+/// Where to put the window: the saved bounds when they still fit the saved
+/// monitor, else the saved size centered on the primary monitor
+fn placement(saved: &SavedWindow, monitors: &[Screen], primary: Option<&Screen>) -> SavedWindow {
+    let on_saved_monitor = monitors.iter().find(|m| {
+        saved.monitor.is_some() && m.name == saved.monitor && title_bar_visible(saved, m)
+    });
+    if on_saved_monitor.is_some() {
+        return saved.clone();
+    }
+
+    let Some(target) = primary.or(monitors.first()) else {
+        return saved.clone();
+    };
+    let width = saved.width.min(target.width);
+    let height = saved.height.min(target.height);
+    SavedWindow {
+        x: target.x + ((target.width - width) / 2) as i32,
+        y: target.y + ((target.height - height) / 2) as i32,
+        width,
+        height,
+        maximized: saved.maximized,
+        monitor: target.name.clone(),
+    }
+}
+
+/// Apply the saved state to the (still hidden) main window
+pub fn restore(app: &AppHandle) {
+    let state = app.state::<WindowState>();
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+
+    if let Some(saved) = load(app) {
+        let monitors = window.available_monitors().unwrap_or_default();
+        let monitors: Vec<Screen> = monitors.iter().map(Screen::from).collect();
+        let primary = window.primary_monitor().ok().flatten();
+        let primary = primary.as_ref().map(Screen::from);
+        let target = placement(&saved, &monitors, primary.as_ref());
+        if let Err(e) = window
+            .set_size(PhysicalSize::new(target.width, target.height))
+            .and_then(|_| window.set_position(PhysicalPosition::new(target.x, target.y)))
+        {
+            log::warn!("Failed to restore window bounds: {}", e);
+        }
+        if target.maximized {
+            if let Err(e) = window.maximize() {
+                log::warn!("Failed to maximize window: {}", e);
+            }
+        }
+        *state.current.lock().unwrap_or_else(|e| e.into_inner()) = Some(target);
+    }
+
+    state.restored.store(true, Ordering::SeqCst);
+}
+
+/// The main window's current state, or None while minimized (or hidden,
+/// when position and size aren't reliable)
+fn snapshot(window: &Window, previous: Option<&SavedWindow>) -> Option<SavedWindow> {
+    if window.is_minimized().unwrap_or(false) || !window.is_visible().unwrap_or(true) {
+        return None;
+    }
+    let maximized = window.is_maximized().unwrap_or(false);
+    if let (true, Some(previous)) = (maximized, previous) {
+        // Keep the bounds to go back to when unmaximized
+        return Some(SavedWindow {
+            maximized: true,
+            ..previous.clone()
+        });
+    }
+    let position = window.outer_position().ok()?;
+    let size = window.inner_size().ok()?;
+    Some(SavedWindow {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized,
+        monitor: window
+            .current_monitor()
+            .ok()
+            .flatten()
+            .and_then(|m| m.name().cloned()),
+    })
+}
+
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if window.label() != MAIN_WINDOW {
+        return;
+    }
+    let app = window.app_handle();
+    let state = window.state::<WindowState>();
+    if !state.restored.load(Ordering::SeqCst) {
+        return;
+    }
+
+    match event {
+        WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+            {
+                let mut current = state.current.lock().unwrap_or_else(|e| e.into_inner());
+                match snapshot(window, current.as_ref()) {
+                    Some(saved) => *current = Some(saved),
+                    None => return,
+                }
+            }
+            let generation = state.generation.fetch_add(1, Ordering::SeqCst) + 1;
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(SAVE_DELAY).await;
+                if app.state::<WindowState>().generation.load(Ordering::SeqCst) == generation {
+                    save(&app);
+                }
+            });
+        }
+        WindowEvent::CloseRequested { .. } => {
+            state.generation.fetch_add(1, Ordering::SeqCst);
+            save(app);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(name: &str, x: i32, y: i32, width: u32, height: u32) -> Screen {
+        Screen {
+            name: Some(name.to_string()),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn window(monitor: &str, x: i32, y: i32, width: u32, height: u32) -> SavedWindow {
+        SavedWindow {
+            x,
+            y,
+            width,
+            height,
+            maximized: false,
+            monitor: Some(monitor.to_string()),
+        }
+    }
+
+    #[test]
+    fn keeps_bounds_that_still_fit_the_saved_monitor() {
+        let screens = [
+            screen("left", 0, 0, 1920, 1080),
+            screen("right", 1920, 0, 2560, 1440),
+        ];
+        let saved = window("right", 2200, 100, 1200, 800);
+        let target = placement(&saved, &screens, Some(&screens[0]));
+        assert_eq!(
+            (target.x, target.y, target.monitor),
+            (2200, 100, saved.monitor)
+        );
+    }
+
+    #[test]
+    fn centers_on_the_primary_monitor_when_the_saved_one_is_gone() {
+        let screens = [screen("laptop", 0, 0, 1920, 1080)];
+        let saved = SavedWindow {
+            maximized: true,
+            ..window("external", 2200, 100, 1200, 800)
+        };
+        let target = placement(&saved, &screens, Some(&screens[0]));
+        assert_eq!(
+            (target.x, target.y, target.width, target.height),
+            (360, 140, 1200, 800)
+        );
+        assert_eq!(target.monitor.as_deref(), Some("laptop"));
+        assert!(target.maximized);
+    }
+
+    #[test]
+    fn moves_a_window_whose_title_bar_is_off_screen() {
+        let screens = [screen("main", 0, 0, 1920, 1080)];
+        // Only 50px of the title bar is left on screen
+        let saved = window("main", 1870, 100, 800, 600);
+        let target = placement(&saved, &screens, None);
+        assert_eq!((target.x, target.y), (560, 240));
+        // Above the top edge
+        let saved = window("main", 100, -20, 800, 600);
+        assert_eq!(placement(&saved, &screens, None).y, 240);
+    }
+
+    #[test]
+    fn shrinks_a_window_larger_than_the_monitor() {
+        let screens = [screen("small", -1280, 0, 1280, 720)];
+        let saved = window("gone", 0, 0, 2560, 1440);
+        let target = placement(&saved, &screens, None);
+        assert_eq!(
+            (target.x, target.y, target.width, target.height),
+            (-1280, 0, 1280, 720)
+        );
+    }
+
+    #[test]
+    fn leaves_the_bounds_alone_without_monitors() {
+        let saved = window("gone", -5000, -5000, 800, 600);
+        let target = placement(&saved, &[], None);
+        assert_eq!((target.x, target.y), (-5000, -5000));
+    }
+}
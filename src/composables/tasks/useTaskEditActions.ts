@@ -3,6 +3,7 @@ import { useTaskStore, type Task, type Subtask, type TaskInstance, type TaskRecu
 import { useCanvasStore } from '@/stores/canvas'
 import { useCanvasUiStore } from '@/stores/canvas/canvasUi'
 import { generateRecurringInstances } from '@/utils/recurrenceUtils'
+import { getRecurrenceSkips } from '@/utils/daysOff'
 import { getUndoSystem } from '@/composables/undoSingleton'
 import { useToast } from '@/composables/useToast'
 
@@ -219,11 +220,16 @@ export function useTaskEditActions(
             // Generate recurring instances if enabled
             if (editedTask.value.recurrence?.isEnabled && editedTask.value.recurrence.rule) {
                 const startDate = editedTask.value.scheduledDate || editedTask.value.dueDate || new Date().toISOString().split('T')[0]
+                const exceptions = editedTask.value.recurrence.exceptions || []
+                // Holidays and vacation days, when recurring tasks should skip them
+                const daysOff = (await getRecurrenceSkips(new Date(startDate)))
+                    .filter(date => !exceptions.some(ex => ex.date === date))
+                    .map(date => ({ id: `day-off-${date}`, date, action: 'skip' as const }))
                 const instances = generateRecurringInstances(
                     editedTask.value.id,
                     editedTask.value.recurrence.rule,
                     editedTask.value.recurrence.endCondition,
-                    editedTask.value.recurrence.exceptions || [],
+                    [...exceptions, ...daysOff],
                     new Date(startDate),
                     editedTask.value.scheduledTime,
                     editedTask.value.estimatedDuration
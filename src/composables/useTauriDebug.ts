@@ -10,13 +10,18 @@
 import { ref, onMounted, onUnmounted } from 'vue'
 import { isTauri } from './useTauriStartup'
 
-export interface MemorySnapshot {
-  timestamp: number
+/** Returned by get_memory_usage */
+export interface MemoryUsage {
   pid: number
-  rss: string // Resident Set Size (actual RAM used)
-  virtual: string // Virtual memory
+  rssBytes: number // Resident Set Size (actual RAM used)
+  virtualBytes: number // Virtual memory
+  cpuPercent: number // Since the previous sample; 100 = one full core
+  openFds: number | null // File descriptors (handles on Windows)
   platform: string
-  rssBytes?: number // Parsed RSS in bytes
+}
+
+export interface MemorySnapshot extends MemoryUsage {
+  timestamp: number
 }
 
 export interface MemoryTrend {
@@ -27,15 +32,6 @@ export interface MemoryTrend {
   currentRss: number
 }
 
-// Parse memory string like "123456 kB" to bytes
-function parseMemoryKb(memStr: string): number {
-  const match = memStr.match(/(\d+)\s*kB/i)
-  if (match) {
-    return parseInt(match[1], 10) * 1024
-  }
-  return 0
-}
-
 export function useTauriDebug() {
   const isEnabled = ref(false)
   const memoryHistory = ref<MemorySnapshot[]>([])
@@ -55,19 +51,9 @@ export function useTauriDebug() {
 
     try {
       const { invoke } = await import('@tauri-apps/api/core')
-      const result = await invoke<string>('get_memory_usage')
-      const data = JSON.parse(result)
-
-      const snapshot: MemorySnapshot = {
-        timestamp: Date.now(),
-        pid: data.pid,
-        rss: data.rss,
-        virtual: data.virtual,
-        platform: data.platform,
-        rssBytes: parseMemoryKb(data.rss)
-      }
+      const data = await invoke<MemoryUsage>('get_memory_usage')
 
-      return snapshot
+      return { ...data, timestamp: Date.now() }
     } catch (error) {
       console.error('[TauriDebug] Failed to get memory usage:', error)
       lastError.value = String(error)
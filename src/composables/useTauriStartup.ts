@@ -1,16 +1,19 @@
 /**
  * Tauri Startup Composable
  *
- * Manages the startup flow for the Tauri desktop app:
- * 1. Check if Docker is running
- * 2. Start Docker if needed
- * 3. Check if Supabase is running
- * 4. Start Supabase if needed
- * 5. Get Supabase connection config
+ * Manages the startup flow for the Tauri desktop app. The backend runs the
+ * sequence in one call (`ensure_stack_ready`, src-tauri/src/stack.rs):
+ * 1. Check if Docker is installed and running
+ * 2. Start Docker if needed and wait for the daemon
+ * 3. Start Supabase if it isn't running
+ * 4. Verify the database schema
+ * This composable follows its `stack://step` events for progress, then
+ * fetches the Supabase connection config.
  */
 
 import { ref, computed, onUnmounted } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { getCurrentWindow } from '@tauri-apps/api/window'
 
 export type StartupStep =
@@ -44,19 +47,81 @@ export interface StartupState {
   progress: number // 0-100
 }
 
+/** Returned by check_docker_status / check_supabase_status */
+export interface ServiceStatus {
+  state: 'running' | 'not_running'
+  version: string | null
+  config: SupabaseConfig | null
+}
+
 export interface SupabaseConfig {
-  API_URL: string
-  GRAPHQL_URL: string
-  S3_STORAGE_URL: string
-  DB_URL: string
-  STUDIO_URL: string
-  INBUCKET_URL: string
-  JWT_SECRET: string
-  ANON_KEY: string
-  SERVICE_ROLE_KEY: string
-  S3_ACCESS_KEY: string
-  S3_SECRET_KEY: string
-  S3_REGION: string
+  API_URL: string | null
+  GRAPHQL_URL: string | null
+  S3_STORAGE_URL: string | null
+  DB_URL: string | null
+  STUDIO_URL: string | null
+  INBUCKET_URL: string | null
+  /** Always null; read `supabase.jwt_secret` with the `get_secret` command */
+  JWT_SECRET: string | null
+  ANON_KEY: string | null
+  /** Always null; read `supabase.service_role_key` with the `get_secret` command */
+  SERVICE_ROLE_KEY: string | null
+  S3_ACCESS_KEY: string | null
+  /** Always null; read `supabase.s3_secret_key` with the `get_secret` command */
+  S3_SECRET_KEY: string | null
+  S3_REGION: string | null
+}
+
+/** Rejection value of every Tauri command; branch on `code`, show `message` */
+export interface CommandError {
+  code: string
+  message: string
+}
+
+export type StackStep =
+  | 'detecting'
+  | 'starting_docker'
+  | 'waiting_docker'
+  | 'starting_supabase'
+  | 'verifying_schema'
+
+/** Payload of `stack://step` */
+export interface StackStepEvent {
+  step: StackStep
+  status: 'running' | 'succeeded' | 'skipped' | 'failed'
+  detail: string | null
+  elapsedMs: number
+}
+
+/** Returned by ensure_stack_ready */
+export interface StackReport {
+  ready: boolean
+  failedStep: StackStep | null
+  error: CommandError | null
+  dockerVersion: string | null
+  steps: StackStepEvent[]
+  durationMs: number
+}
+
+/** Event bus envelope around every backend event */
+interface BusEvent<T> {
+  seq: number
+  topic: string
+  timestampMs: number
+  payload: T
+}
+
+/** Startup screen step and progress while a backend step runs */
+const STEP_PROGRESS: Record<StackStep, [StartupStep, number]> = {
+  detecting: ['checking_docker', 10],
+  starting_docker: ['starting_docker', 20],
+  waiting_docker: ['waiting_docker', 30],
+  starting_supabase: ['starting_supabase', 70],
+  verifying_schema: ['running_migrations', 92]
+}
+
+function isCommandError(error: unknown): error is CommandError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
 }
 
 /**
@@ -123,110 +188,12 @@ export function useTauriStartup() {
     }
   })
 
-  /**
-   * Check Docker status via Tauri command
-   */
-  async function checkDocker(): Promise<boolean> {
-    try {
-      const result = await invoke<string>('check_docker_status')
-
-      if (result.startsWith('running:')) {
-        state.value.dockerStatus = 'running'
-        state.value.dockerVersion = result.replace('running:', '')
-        return true
-      } else {
-        state.value.dockerStatus = 'not_running'
-        return false
-      }
-    } catch (error) {
-      console.error('Failed to check Docker:', error)
-      state.value.dockerStatus = 'not_installed'
-      return false
-    }
-  }
-
-  /**
-   * Start Docker Desktop via Tauri command
-   */
-  async function startDocker(): Promise<boolean> {
-    try {
-      const result = await invoke<string>('start_docker_desktop')
-      return result === 'started'
-    } catch (error) {
-      console.error('Failed to start Docker:', error)
-      return false
-    }
-  }
-
-  /**
-   * Wait for Docker to be ready with polling
-   */
-  async function waitForDocker(timeoutMs: number = 60000): Promise<boolean> {
-    const startTime = Date.now()
-    const pollInterval = 2000
-
-    while (Date.now() - startTime < timeoutMs) {
-      const isRunning = await checkDocker()
-      if (isRunning) return true
-
-      // Update progress based on elapsed time
-      const elapsed = Date.now() - startTime
-      state.value.progress = Math.min(30 + (elapsed / timeoutMs) * 20, 50)
-
-      await new Promise(resolve => setTimeout(resolve, pollInterval))
-    }
-
-    return false
-  }
-
-  /**
-   * Check Supabase status via Tauri command
-   */
-  async function checkSupabase(): Promise<boolean> {
-    try {
-      const result = await invoke<string>('check_supabase_status')
-
-      if (result.startsWith('running:')) {
-        state.value.supabaseStatus = 'running'
-        const configJson = result.replace('running:', '')
-        try {
-          state.value.supabaseConfig = JSON.parse(configJson)
-        } catch {
-          // Config parsing failed but Supabase is running
-        }
-        return true
-      } else {
-        state.value.supabaseStatus = 'not_running'
-        return false
-      }
-    } catch (error) {
-      console.error('Failed to check Supabase:', error)
-      state.value.supabaseStatus = 'not_running'
-      return false
-    }
-  }
-
-  /**
-   * Start Supabase via Tauri command
-   */
-  async function startSupabase(): Promise<boolean> {
-    try {
-      const result = await invoke<string>('start_supabase')
-      return result === 'started' || result === 'already_running'
-    } catch (error) {
-      console.error('Failed to start Supabase:', error)
-      state.value.error = String(error)
-      return false
-    }
-  }
-
   /**
    * Get Supabase connection config
    */
   async function getSupabaseConfig(): Promise<SupabaseConfig | null> {
     try {
-      const result = await invoke<string>('get_supabase_config')
-      const config = JSON.parse(result) as SupabaseConfig
+      const config = await invoke<SupabaseConfig>('get_supabase_config')
       state.value.supabaseConfig = config
       return config
     } catch (error) {
@@ -235,20 +202,6 @@ export function useTauriStartup() {
     }
   }
 
-  /**
-   * Run database migrations via Tauri command
-   */
-  async function runMigrations(): Promise<boolean> {
-    try {
-      const result = await invoke<string>('run_supabase_migrations')
-      return result === 'migrations_complete' || result === 'no_migrations_needed'
-    } catch (error) {
-      console.error('Failed to run migrations:', error)
-      state.value.error = String(error)
-      return false
-    }
-  }
-
   /**
    * Cleanup services on app exit
    * @param stopSupabase - Whether to stop Supabase containers (default: false to keep running for quick restart)
@@ -311,113 +264,90 @@ export function useTauriStartup() {
     unregisterCloseHandler()
   })
 
+  /**
+   * Record why the stack didn't come up
+   */
+  function fail(report: StackReport): void {
+    const code = report.error?.code
+    const message = report.error?.message || null
+    state.value.step = 'error'
+
+    if (code === 'docker_not_installed') {
+      state.value.dockerStatus = 'not_installed'
+      state.value.errorType = 'docker_not_installed'
+      state.value.error = 'Docker is not installed. Please install Docker Desktop to use this app.'
+    } else if (report.failedStep === 'starting_docker') {
+      state.value.dockerStatus = 'not_running'
+      state.value.errorType = 'docker_start_failed'
+      state.value.error = 'Failed to start Docker Desktop. Please start it manually from your applications menu.'
+    } else if (report.failedStep === 'detecting' || report.failedStep === 'waiting_docker') {
+      state.value.dockerStatus = 'not_running'
+      state.value.errorType = 'docker_not_running'
+      state.value.error = 'Docker is taking too long to start. Please ensure Docker Desktop is running and try again.'
+    } else if (code === 'supabase_cli_missing') {
+      state.value.supabaseStatus = 'not_installed'
+      state.value.errorType = 'supabase_not_installed'
+      state.value.error = message
+    } else if (code === 'port_conflict') {
+      state.value.supabaseStatus = 'not_running'
+      state.value.errorType = 'supabase_port_conflict'
+      state.value.error = 'Port conflict detected. Another service may be using the required ports (54321-54329). Please stop conflicting services and try again.'
+    } else if (report.failedStep === 'starting_supabase') {
+      state.value.supabaseStatus = 'not_running'
+      state.value.errorType = 'supabase_start_failed'
+      state.value.error = message || 'Failed to start database services. Please ensure Supabase CLI is installed.'
+    } else if (report.failedStep === 'verifying_schema') {
+      state.value.errorType = 'migration_failed'
+      state.value.error = message || 'Failed to set up database schema. Please check Supabase logs.'
+    } else {
+      state.value.errorType = 'unknown'
+      state.value.error = message
+    }
+  }
+
   /**
    * Run the full startup sequence
    */
   async function runStartupSequence(): Promise<boolean> {
     // Reset state
+    state.value.step = 'checking_docker'
     state.value.error = null
     state.value.errorType = null
     state.value.progress = 0
 
-    // Step 1: Check Docker
-    state.value.step = 'checking_docker'
-    state.value.progress = 10
-
-    let dockerRunning = await checkDocker()
-
-    // Step 2: Start Docker if needed
-    if (!dockerRunning) {
-      if (state.value.dockerStatus === 'not_installed') {
-        state.value.step = 'error'
-        state.value.errorType = 'docker_not_installed'
-        state.value.error = 'Docker is not installed. Please install Docker Desktop to use this app.'
-        return false
-      }
-
-      state.value.step = 'starting_docker'
-      state.value.progress = 20
-
-      const started = await startDocker()
-      if (!started) {
-        state.value.step = 'error'
-        state.value.errorType = 'docker_start_failed'
-        state.value.error = 'Failed to start Docker Desktop. Please start it manually from your applications menu.'
-        return false
-      }
-
-      // Step 3: Wait for Docker
-      state.value.step = 'waiting_docker'
-      state.value.progress = 30
-
-      dockerRunning = await waitForDocker(60000)
-      if (!dockerRunning) {
-        state.value.step = 'error'
-        state.value.errorType = 'docker_not_running'
-        state.value.error = 'Docker is taking too long to start. Please ensure Docker Desktop is running and try again.'
-        return false
-      }
-    }
-
-    state.value.progress = 50
-
-    // Step 4: Check Supabase
-    state.value.step = 'checking_supabase'
-    state.value.progress = 60
-
-    let supabaseRunning = await checkSupabase()
-
-    // Step 5: Start Supabase if needed
-    if (!supabaseRunning) {
-      state.value.step = 'starting_supabase'
-      state.value.progress = 70
-
-      const started = await startSupabase()
-      if (!started) {
-        state.value.step = 'error'
-        // Check if it's a port conflict based on error message
-        const errorMsg = state.value.error || ''
-        if (errorMsg.includes('port') || errorMsg.includes('already in use') || errorMsg.includes('address already')) {
-          state.value.errorType = 'supabase_port_conflict'
-          state.value.error = 'Port conflict detected. Another service may be using the required ports (54321-54329). Please stop conflicting services and try again.'
-        } else {
-          state.value.errorType = 'supabase_start_failed'
-          state.value.error = errorMsg || 'Failed to start database services. Please ensure Supabase CLI is installed.'
-        }
-        return false
-      }
-
-      // Wait a bit for Supabase to fully start
-      state.value.progress = 85
-      await new Promise(resolve => setTimeout(resolve, 5000))
-
-      // Verify it's running and get config
-      supabaseRunning = await checkSupabase()
-      if (!supabaseRunning) {
-        state.value.step = 'error'
-        state.value.errorType = 'supabase_start_failed'
-        state.value.error = 'Database services started but are not responding. Please check Docker logs or try running "supabase start" manually.'
-        return false
-      }
-    }
+    const unlisten = await listen<BusEvent<StackStepEvent>>('stack://step', (event) => {
+      const { step, status } = event.payload.payload
+      if (status !== 'running') return
+      const [startupStep, progress] = STEP_PROGRESS[step]
+      state.value.step = startupStep
+      state.value.progress = progress
+    })
 
-    // Step 6: Get config if we don't have it yet
-    if (!state.value.supabaseConfig) {
-      await getSupabaseConfig()
+    let report: StackReport
+    try {
+      report = await invoke<StackReport>('ensure_stack_ready')
+    } catch (error) {
+      // Only the app lock rejects the call itself
+      console.error('Failed to start the local stack:', error)
+      state.value.step = 'error'
+      state.value.errorType = 'unknown'
+      state.value.error = isCommandError(error) ? error.message : String(error)
+      return false
+    } finally {
+      unlisten()
     }
 
-    // Step 7: Run database migrations
-    state.value.step = 'running_migrations'
-    state.value.progress = 92
-
-    const migrationsOk = await runMigrations()
-    if (!migrationsOk) {
-      state.value.step = 'error'
-      state.value.errorType = 'migration_failed'
-      state.value.error = state.value.error || 'Failed to set up database schema. Please check Supabase logs.'
+    if (!report.ready) {
+      fail(report)
       return false
     }
 
+    state.value.dockerStatus = 'running'
+    state.value.dockerVersion = report.dockerVersion
+    state.value.supabaseStatus = 'running'
+    state.value.progress = 96
+    await getSupabaseConfig()
+
     state.value.progress = 100
     state.value.step = 'ready'
     return true
@@ -454,14 +384,7 @@ export function useTauriStartup() {
     skipStartup,
     retry,
 
-    // Individual checks (for manual control)
-    checkDocker,
-    startDocker,
-    waitForDocker,
-    checkSupabase,
-    startSupabase,
     getSupabaseConfig,
-    runMigrations,
     cleanup,
     registerCloseHandler,
     unregisterCloseHandler
@@ -41,6 +41,7 @@ import {
   XP_MULTIPLIERS,
   STREAK_CONFIG,
 } from '@/types/gamification'
+import { countMissedDays } from '@/utils/daysOff'
 
 // =============================================================================
 // Type Mappers (Database snake_case <-> App camelCase)
@@ -630,7 +631,9 @@ export const useGamificationStore = defineStore('gamification', () => {
     const yesterday = new Date(today)
     yesterday.setDate(yesterday.getDate() - 1)
     const yesterdayStr = getLocalDateString(yesterday)
-    const wasActiveYesterday = lastActiveStr === yesterdayStr
+    // Holidays and vacation days in between don't break the streak
+    const missedDays = lastActiveStr ? await countMissedDays(lastActiveStr) : null
+    const wasActiveYesterday = lastActiveStr === yesterdayStr || missedDays === 0
 
     let newStreak = profile.value.currentStreak
     let streakBroken = false
@@ -642,9 +645,11 @@ export const useGamificationStore = defineStore('gamification', () => {
       newStreak = wasActiveYesterday ? newStreak + 1 : 1
     } else {
       // Streak broken - check for freeze
-      const daysMissed = lastActive
-        ? Math.floor((today.getTime() - new Date(lastActive).getTime()) / (1000 * 60 * 60 * 24))
-        : 999
+      const daysMissed = missedDays !== null
+        ? missedDays + 1
+        : lastActive
+          ? Math.floor((today.getTime() - new Date(lastActive).getTime()) / (1000 * 60 * 60 * 24))
+          : 999
 
       if (daysMissed <= 2 && profile.value.streakFreezes > 0) {
         // Use freeze
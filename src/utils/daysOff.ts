@@ -0,0 +1,44 @@
+/**
+ * Holidays and vacation days from the desktop backend (src-tauri/src/holidays.rs).
+ * Outside the desktop app there are no days off and these return nothing.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { isTauri } from '@/utils/platform'
+import { formatDateKey } from '@/utils/dateUtils'
+
+/** The backend answers for at most about three years at a time */
+const MAX_RANGE_DAYS = 3 * 365
+
+/**
+ * Days missed since `lastActive` (YYYY-MM-DD), not counting today, holidays
+ * or vacation days; null when unknown
+ */
+export async function countMissedDays(lastActive: string): Promise<number | null> {
+  if (!isTauri()) return null
+  try {
+    return await invoke<number>('count_missed_days', { lastActive })
+  } catch (error) {
+    console.warn('[DaysOff] Could not count missed days:', error)
+    return null
+  }
+}
+
+/**
+ * Dates (YYYY-MM-DD) recurring tasks starting at `start` skip, when the user
+ * turned that on
+ */
+export async function getRecurrenceSkips(start: Date): Promise<string[]> {
+  if (!isTauri()) return []
+  const end = new Date(start)
+  end.setDate(end.getDate() + MAX_RANGE_DAYS)
+  try {
+    return await invoke<string[]>('get_recurrence_skips', {
+      from: formatDateKey(start),
+      to: formatDateKey(end),
+    })
+  } catch (error) {
+    console.warn('[DaysOff] Could not load days off:', error)
+    return []
+  }
+}
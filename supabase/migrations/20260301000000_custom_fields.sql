@@ -0,0 +1,41 @@
+-- Custom fields on tasks (text, number, select, date)
+-- Definitions are per user; values live in tasks.custom_fields keyed by field key.
+-- The desktop app validates values against the definitions before saving.
+
+ALTER TABLE public.tasks ADD COLUMN IF NOT EXISTS custom_fields JSONB DEFAULT '{}'::jsonb;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_custom_fields ON public.tasks USING GIN (custom_fields jsonb_path_ops);
+
+CREATE TABLE IF NOT EXISTS public.custom_fields (
+  user_id UUID NOT NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+  key TEXT NOT NULL CHECK (key ~ '^[a-z][a-z0-9_]{0,39}$'),
+  name TEXT NOT NULL,
+  kind TEXT NOT NULL CHECK (kind IN ('text', 'number', 'select', 'date')),
+  -- Allowed values of a select field
+  options JSONB,
+  -- Deleted fields stay as tombstones so the deletion syncs
+  deleted BOOLEAN NOT NULL DEFAULT false,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (user_id, key)
+);
+
+-- RLS policies
+ALTER TABLE public.custom_fields ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Users can read own custom fields"
+  ON public.custom_fields FOR SELECT
+  USING (auth.uid() = user_id);
+
+CREATE POLICY "Users can insert own custom fields"
+  ON public.custom_fields FOR INSERT
+  WITH CHECK (auth.uid() = user_id);
+
+CREATE POLICY "Users can update own custom fields"
+  ON public.custom_fields FOR UPDATE
+  USING (auth.uid() = user_id);
+
+CREATE POLICY "Users can delete own custom fields"
+  ON public.custom_fields FOR DELETE
+  USING (auth.uid() = user_id);
+
+COMMENT ON COLUMN public.tasks.custom_fields IS 'Custom field values {key: value}; definitions in public.custom_fields';